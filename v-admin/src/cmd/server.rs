@@ -23,6 +23,12 @@ async fn main() -> Result<(), AppError> {
     let cm = v::get_global_config_manager()?;
     cm.print_sources_info();
     v::init_tracing();
+
+    // `server migrate ...` 子命令：执行迁移后退出 / run migrations and exit
+    if v::db::migrate::run_if_requested().await? {
+        return Ok(());
+    }
+
     let host: String = cm
         .get_string("server.host")
         .unwrap_or_else(|_| "0.0.0.0".to_string());
@@ -0,0 +1,19 @@
+use actix_web::{http, web, Responder};
+
+use crate::event::my_listener;
+
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::get().to(health_handle)));
+}
+
+// 健康检查：附带 Sa-Token 事件监听器的处理计数
+// Health check, with the Sa-Token event listener's handler counts
+pub async fn health_handle() -> impl Responder {
+    v::response::respond_any(
+        http::StatusCode::OK,
+        serde_json::json!({
+            "status": "ok",
+            "listener": my_listener::listener().stats(),
+        }),
+    )
+}
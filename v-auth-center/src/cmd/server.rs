@@ -25,6 +25,11 @@ async fn main() -> Result<(), AppError> {
     cm.print_sources_info();
     v::init_tracing();
 
+    // `server migrate ...` 子命令：执行迁移后退出 / run migrations and exit
+    if v::db::migrate::run_if_requested().await? {
+        return Ok(());
+    }
+
     let host: String = cm
         .get_string("server.host")
         .unwrap_or_else(|_| "0.0.0.0".to_string());
@@ -95,5 +100,10 @@ async fn main() -> Result<(), AppError> {
         handle.stop(true).await;
     });
     server.await?;
+
+    // 等待监听器处理中的事件完成 / Drain in-flight listener events
+    v_auth_center::event::my_listener::listener()
+        .shutdown(std::time::Duration::from_secs(5))
+        .await;
     Ok(())
 }
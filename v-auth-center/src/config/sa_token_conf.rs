@@ -5,7 +5,7 @@
 */
 //! Sa-Token 配置初始化
 //! Sa-Token Configuration Initialization
-use crate::event::my_listener::MyListener;
+use crate::event::my_listener;
 type SaResult<T> = std::result::Result<T, v::comm::config::ConfigError>;
 use sa_token_plugin_actix_web::{
     LoggingListener, MemoryStorage, OAuth2Manager, RedisStorage, SaStorage, SaTokenConfig,
@@ -41,7 +41,7 @@ pub async fn init_sa_token() -> SaResult<Arc<SaTokenManager>> {
     let storage = build_storage(&cfg.storage_type).await?;
     // 步骤3: 构建 Sa-Token 管理器并注册监听器
     let manager = SaTokenConfig::builder()
-        .register_listener(Arc::new(MyListener))
+        .register_listener(my_listener::listener())
         .register_listener(Arc::new(LoggingListener))
         .token_name(cfg.token_name)
         .timeout(cfg.timeout_seconds)
@@ -1,12 +1,137 @@
 use async_trait::async_trait;
 use sa_token_plugin_actix_web::SaTokenListener;
-use tracing::info;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::{info, warn};
 
-pub struct MyListener;
+/// 默认最大并发处理数 / Default max in-flight handlers
+const DEFAULT_MAX_CONNECTIONS: usize = 64;
+
+/// 监听器统计（`GET /v1/health` 的 `listener` 字段）/ Listener stats (the `listener` field of `GET /v1/health`)
+#[derive(Debug, Clone, Serialize)]
+pub struct ListenerStats {
+    pub accepted: u64,
+    pub active: usize,
+    pub max_connections: usize,
+    pub accepting: bool,
+}
+
+/// Sa-Token 事件监听器：限制并发处理数并支持优雅停机
+/// Sa-Token event listener: bounded concurrency with graceful shutdown
+pub struct MyListener {
+    limiter: Arc<Semaphore>,
+    max_connections: usize,
+    accepting: AtomicBool,
+    accepted: AtomicU64,
+    active: Arc<AtomicUsize>,
+}
+
+/// 处理中的事件守卫，释放时归还许可 / In-flight guard; returns the permit on drop
+struct InFlight {
+    _permit: OwnedSemaphorePermit,
+    active: Arc<AtomicUsize>,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.active.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+static LISTENER: OnceLock<Arc<MyListener>> = OnceLock::new();
+
+/// 全局监听器实例（读取 `sa_token.listener.max_connections`）
+/// Global listener instance (reads `sa_token.listener.max_connections`)
+pub fn listener() -> Arc<MyListener> {
+    LISTENER
+        .get_or_init(|| {
+            let max = v::get_global_config_manager()
+                .map(|cm| cm.get_or("sa_token.listener.max_connections", DEFAULT_MAX_CONNECTIONS))
+                .unwrap_or(DEFAULT_MAX_CONNECTIONS);
+            Arc::new(MyListener::new(max))
+        })
+        .clone()
+}
+
+impl MyListener {
+    /// 创建监听器；超过上限的事件排队等待许可
+    /// Create a listener; events beyond the limit queue for a permit
+    pub fn new(max_connections: usize) -> Self {
+        let max_connections = max_connections.max(1);
+        Self {
+            limiter: Arc::new(Semaphore::new(max_connections)),
+            max_connections,
+            accepting: AtomicBool::new(true),
+            accepted: AtomicU64::new(0),
+            active: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// 当前统计 / Current stats
+    pub fn stats(&self) -> ListenerStats {
+        ListenerStats {
+            accepted: self.accepted.load(Ordering::SeqCst),
+            active: self.active.load(Ordering::SeqCst),
+            max_connections: self.max_connections,
+            accepting: self.accepting.load(Ordering::SeqCst),
+        }
+    }
+
+    /// 停止接收新事件并等待处理中的事件完成；超时返回 false
+    /// Stop accepting new events and wait for in-flight ones; returns false on timeout
+    pub async fn shutdown(&self, timeout: Duration) -> bool {
+        self.accepting.store(false, Ordering::SeqCst);
+        let all = self.max_connections as u32;
+        match tokio::time::timeout(timeout, self.limiter.clone().acquire_many_owned(all)).await {
+            Ok(Ok(_permits)) => {
+                self.limiter.close();
+                info!(
+                    "MyListener 已停止 / stopped, accepted={}",
+                    self.stats().accepted
+                );
+                true
+            }
+            _ => {
+                self.limiter.close();
+                warn!(
+                    "MyListener 停机超时，仍有 {} 个处理中 / shutdown timed out with {} in flight",
+                    self.stats().active,
+                    self.stats().active
+                );
+                false
+            }
+        }
+    }
+
+    /// 进入处理：停机后拒绝，超出上限时排队
+    /// Enter a handler: rejected after shutdown, queued beyond the limit
+    async fn enter(&self, event: &str) -> Option<InFlight> {
+        if !self.accepting.load(Ordering::SeqCst) {
+            warn!(
+                "MyListener 已停机，丢弃事件 / shutting down, dropping event: {}",
+                event
+            );
+            return None;
+        }
+        let permit = self.limiter.clone().acquire_owned().await.ok()?;
+        self.accepted.fetch_add(1, Ordering::SeqCst);
+        self.active.fetch_add(1, Ordering::SeqCst);
+        Some(InFlight {
+            _permit: permit,
+            active: self.active.clone(),
+        })
+    }
+}
 
 #[async_trait]
 impl SaTokenListener for MyListener {
     async fn on_login(&self, login_id: &str, token: &str, login_type: &str) {
+        let Some(_in_flight) = self.enter("login").await else {
+            return;
+        };
         info!(
             "用户 {} 登录了，token: {}, login_type: {}",
             login_id, token, login_type
@@ -21,6 +146,9 @@ impl SaTokenListener for MyListener {
     }
 
     async fn on_logout(&self, login_id: &str, token: &str, login_type: &str) {
+        let Some(_in_flight) = self.enter("logout").await else {
+            return;
+        };
         info!(
             "用户 {} 登出了，token: {}, login_type: {}",
             login_id, token, login_type
@@ -28,6 +156,9 @@ impl SaTokenListener for MyListener {
     }
 
     async fn on_kick_out(&self, login_id: &str, token: &str, login_type: &str) {
+        let Some(_in_flight) = self.enter("kick_out").await else {
+            return;
+        };
         info!(
             "用户 {} 被踢出下线，token: {}, login_type: {}",
             login_id, token, login_type
@@ -39,3 +170,54 @@ impl SaTokenListener for MyListener {
     // async fn on_replaced(...) {}
     // async fn on_banned(...) {}
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_events_beyond_the_limit_queue() {
+        let listener = MyListener::new(1);
+        let first = listener.enter("login").await.unwrap();
+        // 许可已用尽，第二个事件排队 / The only permit is taken, so the second event queues
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), listener.enter("logout"))
+                .await
+                .is_err()
+        );
+        assert_eq!(listener.stats().active, 1);
+
+        drop(first);
+        let second = listener.enter("logout").await.unwrap();
+        assert_eq!(listener.stats().accepted, 2);
+        drop(second);
+        assert_eq!(listener.stats().active, 0);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_waits_for_in_flight_handlers() {
+        let listener = MyListener::new(2);
+        let in_flight = listener.enter("login").await.unwrap();
+        let handler = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            drop(in_flight);
+        });
+
+        assert!(listener.shutdown(Duration::from_secs(5)).await);
+        assert!(handler.is_finished());
+        assert_eq!(listener.stats().active, 0);
+        // 停机后新事件被拒绝 / New events are refused after shutdown
+        assert!(listener.enter("logout").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_times_out_with_handlers_in_flight() {
+        let listener = MyListener::new(2);
+        let _in_flight = listener.enter("login").await.unwrap();
+
+        assert!(!listener.shutdown(Duration::from_millis(50)).await);
+        let stats = listener.stats();
+        assert!(!stats.accepting);
+        assert_eq!(stats.active, 1);
+    }
+}
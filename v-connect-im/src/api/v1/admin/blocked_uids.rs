@@ -0,0 +1,71 @@
+use crate::domain::message::HttpBlockedUidsRequest;
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use std::sync::Arc;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/admin/blocked_uids";
+
+// 路由注册入口（GET 列出，POST 批量封禁/解封）
+// Route registration entry (GET lists, POST bulk blocks/unblocks)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(
+        web::resource(path)
+            .route(web::get().to(blocked_uids_list_handle))
+            .route(web::post().to(blocked_uids_update_handle)),
+    );
+}
+
+// 当前全部封禁（按 uid）
+// Every current block, by uid
+pub async fn blocked_uids_list_handle(server: web::Data<Arc<VConnectIMServer>>) -> impl Responder {
+    match server.list_blocked() {
+        Ok(entries) => respond_any(
+            StatusCode::OK,
+            serde_json::json!({"total": entries.len(), "entries": entries}),
+        ),
+        Err(e) => respond_any(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            serde_json::json!({"message": e.to_string()}),
+        ),
+    }
+}
+
+// 先解封再封禁，返回实际发生变化的 uid
+// Unblock first, then block; returns the uids that actually changed
+pub async fn blocked_uids_update_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    body: web::Json<HttpBlockedUidsRequest>,
+) -> impl Responder {
+    let req = body.into_inner();
+    let result = (|| -> anyhow::Result<_> {
+        let mut unblocked = Vec::new();
+        for uid in &req.unblock {
+            if server.unblock_uid(uid)? {
+                unblocked.push(uid.clone());
+            }
+        }
+        let mut blocked = Vec::new();
+        for uid in req.block.iter().filter(|uid| !uid.is_empty()) {
+            if server.block_uid(uid, req.reason.as_deref())? {
+                blocked.push(uid.clone());
+            }
+        }
+        Ok((blocked, unblocked))
+    })();
+    match result {
+        Ok((blocked, unblocked)) => respond_any(
+            StatusCode::OK,
+            serde_json::json!({
+                "blocked": blocked,
+                "unblocked": unblocked,
+                "total": server.blocked_uids.len(),
+            }),
+        ),
+        Err(e) => respond_any(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            serde_json::json!({"message": e.to_string()}),
+        ),
+    }
+}
@@ -0,0 +1,48 @@
+use crate::domain::message::HttpMaintenanceRequest;
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use std::sync::Arc;
+use tracing::warn;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/admin/maintenance";
+
+// 路由注册入口（GET 查询，POST 切换）
+// Route registration entry (GET reads, POST toggles)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(
+        web::resource(path)
+            .route(web::get().to(maintenance_get_handle))
+            .route(web::post().to(maintenance_set_handle)),
+    );
+}
+
+// 当前维护模式状态
+// Current maintenance mode state
+pub async fn maintenance_get_handle(server: web::Data<Arc<VConnectIMServer>>) -> impl Responder {
+    respond_any(
+        StatusCode::OK,
+        serde_json::json!({"enabled": server.maintenance.is_enabled()}),
+    )
+}
+
+// 开启或关闭维护模式（仅本节点）
+// Turn maintenance mode on or off (this node only)
+pub async fn maintenance_set_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    body: web::Json<HttpMaintenanceRequest>,
+) -> impl Responder {
+    let enabled = body.into_inner().enabled;
+    let previous = server.maintenance.set(enabled);
+    if previous != enabled {
+        warn!(
+            "🚧 维护模式 / Maintenance mode: {}",
+            if enabled { "on" } else { "off" }
+        );
+    }
+    respond_any(
+        StatusCode::OK,
+        serde_json::json!({"enabled": enabled, "changed": previous != enabled}),
+    )
+}
@@ -0,0 +1,28 @@
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use std::sync::Arc;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/admin/plugins";
+
+// 路由注册入口（GET）
+// Route registration entry (GET)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::get().to(plugin_list_handle)));
+}
+
+// 插件列表：含已安装但未启动的插件，能力与优先级握手前取自 plugin.json
+// Plugin list, including installed plugins not started yet; capabilities and priority come from plugin.json until the handshake
+pub async fn plugin_list_handle(server: web::Data<Arc<VConnectIMServer>>) -> impl Responder {
+    let Some(manager) = server.plugin_runtime_manager.as_ref() else {
+        return respond_any(
+            StatusCode::SERVICE_UNAVAILABLE,
+            serde_json::json!({"message": "plugin runtime unavailable"}),
+        );
+    };
+    respond_any(
+        StatusCode::OK,
+        serde_json::json!({"plugins": manager.runtime_summaries()}),
+    )
+}
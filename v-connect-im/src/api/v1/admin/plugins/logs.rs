@@ -0,0 +1,48 @@
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use serde::Deserialize;
+use std::sync::Arc;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/admin/plugins/{name}/logs";
+
+/// 默认返回行数 / Default number of lines returned
+const DEFAULT_LINES: usize = 100;
+
+#[derive(Deserialize)]
+pub struct PluginLogsQuery {
+    pub lines: Option<usize>,
+}
+
+// 路由注册入口（GET）
+// Route registration entry (GET)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::get().to(plugin_logs_handle)));
+}
+
+// 插件最近日志
+// Recent plugin logs
+pub async fn plugin_logs_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    name: web::Path<String>,
+    query: web::Query<PluginLogsQuery>,
+) -> impl Responder {
+    let Some(manager) = server.plugin_runtime_manager.as_ref() else {
+        return respond_any(
+            StatusCode::SERVICE_UNAVAILABLE,
+            serde_json::json!({"message": "plugin runtime unavailable"}),
+        );
+    };
+    let lines = query.lines.unwrap_or(DEFAULT_LINES);
+    match manager.recent_logs(&name, lines) {
+        Some(logs) => respond_any(
+            StatusCode::OK,
+            serde_json::json!({"plugin": name.as_str(), "lines": logs}),
+        ),
+        None => respond_any(
+            StatusCode::NOT_FOUND,
+            serde_json::json!({"message": "plugin not found"}),
+        ),
+    }
+}
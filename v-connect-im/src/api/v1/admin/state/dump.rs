@@ -0,0 +1,45 @@
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use serde::Deserialize;
+use std::sync::Arc;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/admin/state/dump";
+
+/// `path` 相对于 `server.state_dump_dir` / `path` is relative to `server.state_dump_dir`
+#[derive(Deserialize)]
+pub struct StatePathRequest {
+    pub path: String,
+}
+
+// 路由注册入口（POST）
+// Route registration entry (POST)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::post().to(state_dump_handle)));
+}
+
+// 把节点状态转储到服务端上的文件
+// Dump the node's state to a file on the server
+pub async fn state_dump_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    body: web::Json<StatePathRequest>,
+) -> impl Responder {
+    let dir = crate::service::state_dump::state_dump_dir_from_config();
+    let path = match crate::service::state_dump::resolve_dump_path(&dir, &body.path) {
+        Ok(path) => path,
+        Err(e) => {
+            return respond_any(
+                StatusCode::BAD_REQUEST,
+                serde_json::json!({"message": e.to_string()}),
+            )
+        }
+    };
+    match server.dump_state(&path).await {
+        Ok(summary) => respond_any(StatusCode::OK, summary),
+        Err(e) => respond_any(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            serde_json::json!({"message": format!("{:#}", e)}),
+        ),
+    }
+}
@@ -0,0 +1,51 @@
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use serde::Deserialize;
+use std::sync::Arc;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/admin/storage/archive";
+
+#[derive(Deserialize)]
+pub struct ArchiveRequest {
+    pub since_ts: i64,
+    pub until_ts: i64,
+}
+
+// 路由注册入口（POST）
+// Route registration entry (POST)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::post().to(storage_archive_handle)));
+}
+
+// 把时间范围内的消息归档到对象存储
+// Archive messages in a time range to object storage
+pub async fn storage_archive_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    body: web::Json<ArchiveRequest>,
+) -> impl Responder {
+    if body.until_ts <= body.since_ts {
+        return respond_any(
+            StatusCode::BAD_REQUEST,
+            serde_json::json!({"message": "until_ts must be greater than since_ts"}),
+        );
+    }
+    let Some(pool) = server.plugin_connection_pool.as_ref() else {
+        return respond_any(
+            StatusCode::SERVICE_UNAVAILABLE,
+            serde_json::json!({"message": "plugin runtime unavailable"}),
+        );
+    };
+    match pool.storage_archive(body.since_ts..body.until_ts).await {
+        Ok(Some(archive)) => respond_any(StatusCode::OK, archive),
+        Ok(None) => respond_any(
+            StatusCode::SERVICE_UNAVAILABLE,
+            serde_json::json!({"message": "storage plugin unavailable"}),
+        ),
+        Err(e) => respond_any(
+            StatusCode::BAD_GATEWAY,
+            serde_json::json!({"message": e.to_string()}),
+        ),
+    }
+}
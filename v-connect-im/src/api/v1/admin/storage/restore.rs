@@ -0,0 +1,50 @@
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use serde::Deserialize;
+use std::sync::Arc;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/admin/storage/restore";
+
+#[derive(Deserialize)]
+pub struct RestoreRequest {
+    pub archive_key: String,
+}
+
+// 路由注册入口（POST）
+// Route registration entry (POST)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::post().to(storage_restore_handle)));
+}
+
+// 从归档对象恢复消息
+// Restore messages from an archive object
+pub async fn storage_restore_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    body: web::Json<RestoreRequest>,
+) -> impl Responder {
+    if body.archive_key.is_empty() {
+        return respond_any(
+            StatusCode::BAD_REQUEST,
+            serde_json::json!({"message": "archive_key is required"}),
+        );
+    }
+    let Some(pool) = server.plugin_connection_pool.as_ref() else {
+        return respond_any(
+            StatusCode::SERVICE_UNAVAILABLE,
+            serde_json::json!({"message": "plugin runtime unavailable"}),
+        );
+    };
+    match pool.storage_restore(&body.archive_key).await {
+        Ok(Some(count)) => respond_any(StatusCode::OK, serde_json::json!({"count": count})),
+        Ok(None) => respond_any(
+            StatusCode::SERVICE_UNAVAILABLE,
+            serde_json::json!({"message": "storage plugin unavailable"}),
+        ),
+        Err(e) => respond_any(
+            StatusCode::BAD_GATEWAY,
+            serde_json::json!({"message": e.to_string()}),
+        ),
+    }
+}
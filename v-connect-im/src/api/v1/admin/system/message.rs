@@ -0,0 +1,43 @@
+use crate::domain::message::HttpSystemMessageRequest;
+use crate::service::system_message::SystemScope;
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use std::sync::Arc;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/admin/system/message";
+
+// 路由注册入口（POST）
+// Route registration entry (POST)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::post().to(system_message_handle)));
+}
+
+// 下发系统消息（公告）
+// Send a system message (announcement)
+pub async fn system_message_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    body: web::Json<HttpSystemMessageRequest>,
+) -> impl Responder {
+    let req = body.into_inner();
+    let scope = match req.scope.parse::<SystemScope>() {
+        Ok(scope) => scope,
+        Err(e) => {
+            return respond_any(
+                StatusCode::BAD_REQUEST,
+                serde_json::json!({"message": e.to_string()}),
+            )
+        }
+    };
+    match server
+        .send_system_message(scope, req.content, req.offline)
+        .await
+    {
+        Ok(result) => respond_any(StatusCode::OK, result),
+        Err(e) => respond_any(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            serde_json::json!({"message": e.to_string()}),
+        ),
+    }
+}
@@ -0,0 +1,44 @@
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use serde::Deserialize;
+use std::sync::Arc;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/admin/webhooks/dead_letter";
+
+/// 默认返回条数 / Default number of entries returned
+const DEFAULT_LIMIT: usize = 100;
+
+#[derive(Deserialize)]
+pub struct DeadLetterQuery {
+    pub limit: Option<usize>,
+}
+
+// 路由注册入口（GET）
+// Route registration entry (GET)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::get().to(webhook_dead_letter_handle)));
+}
+
+// 重试耗尽的 webhook（最早的在前）
+// Webhooks that exhausted their retries, oldest first
+pub async fn webhook_dead_letter_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    query: web::Query<DeadLetterQuery>,
+) -> impl Responder {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    match server.webhooks.dead_letters(limit) {
+        Ok(entries) => respond_any(
+            StatusCode::OK,
+            serde_json::json!({
+                "total": server.webhooks.snapshot()["dead"],
+                "entries": entries,
+            }),
+        ),
+        Err(e) => respond_any(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            serde_json::json!({"message": e.to_string()}),
+        ),
+    }
+}
@@ -40,6 +40,14 @@ pub async fn health_detailed_handle(server: web::Data<Arc<VConnectIMServer>>) ->
                 ,"quic_dgram_recv": server.quic_dgram_recv.load(std::sync::atomic::Ordering::Relaxed)
                 ,"blocked_uids_count": server.blocked_uids.len()
                 ,"rate_limits_count": server.uid_rate_limits.len()
+                ,"delivery": server.metrics.snapshot()
+                ,"storage_fallback": server
+                    .plugin_connection_pool
+                    .as_ref()
+                    .map(|pool| pool.storage_fallback().snapshot())
+                ,"webhooks": server.webhooks.snapshot()
+                ,"event_bus": server.event_bus.snapshot()
+                ,"scheduler": server.scheduler.snapshot()
             }
         });
     respond_any(StatusCode::OK, payload)
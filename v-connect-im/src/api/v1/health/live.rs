@@ -10,8 +10,8 @@ pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
     cfg.service(actix_web::web::resource(path).route(actix_web::web::get().to(health_live_handle)));
 }
 
-// 存活检查
-// Liveness check
+// 存活检查：进程能响应即返回 200，不检查任何依赖
+// Liveness check: 200 whenever the process responds; no dependency is checked
 pub async fn health_live_handle() -> impl Responder {
     let payload = serde_json::json!({
         "alive": true,
@@ -12,17 +12,18 @@ pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
     cfg.service(web::resource(path).route(web::get().to(health_ready_handle)));
 }
 
-// 就绪检查
-// Readiness check
+// 就绪检查：依赖健康（healthy/degraded）且必需插件均已连接时返回 200，否则 503
+// Readiness check: 200 when dependencies are healthy/degraded and required plugins are connected, otherwise 503
 pub async fn health_ready_handle(server: web::Data<Arc<VConnectIMServer>>) -> impl Responder {
-    let online = server.get_online_clients().await;
-    let is_ready = online.total_count < 10_000;
+    let (is_ready, report) = server.readiness().await;
     let payload = serde_json::json!({
         "ready": is_ready,
         "service": "v-connect-im",
         "timestamp": chrono::Utc::now().timestamp_millis(),
-        "online_clients": online.total_count,
-        "max_supported": 10000
+        "status": report["status"],
+        "components": report["components"],
+        "missing_plugins": report["missing_plugins"],
+        "maintenance": report["maintenance"]
     });
     let code = if is_ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
     respond_any(code, payload)
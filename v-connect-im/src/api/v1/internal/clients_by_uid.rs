@@ -0,0 +1,36 @@
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use serde::Deserialize;
+use std::sync::Arc;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/internal/clients_by_uid";
+
+#[derive(Deserialize)]
+pub struct ClientsByUidQuery {
+    pub uid: String,
+}
+
+// 路由注册入口（GET）
+// Route registration entry (GET)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::get().to(clients_by_uid_handle)));
+}
+
+// 节点间查询：uid 在本节点上的连接
+// Node-to-node lookup: connections of a uid on this node
+pub async fn clients_by_uid_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    query: web::Query<ClientsByUidQuery>,
+) -> impl Responder {
+    let client_ids: Vec<String> = server
+        .uid_clients
+        .get(&query.uid)
+        .map(|set| set.iter().map(|c| c.clone()).collect())
+        .unwrap_or_default();
+    respond_any(
+        StatusCode::OK,
+        serde_json::json!({"uid": query.uid, "client_ids": client_ids}),
+    )
+}
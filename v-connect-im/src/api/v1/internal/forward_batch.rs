@@ -0,0 +1,32 @@
+use crate::cluster::rpc::{ForwardBatchRequest, ForwardBatchResponse};
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use std::sync::Arc;
+use tokio_tungstenite::tungstenite::Message;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/internal/forward_batch";
+
+// 路由注册入口（POST，请求体可为 gzip）
+// Route registration entry (POST, the body may be gzipped)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::post().to(forward_batch_handle)));
+}
+
+// 节点间批量转发：按顺序把每个帧写入本节点上的连接，逐条返回结果
+// Node-to-node batch forward: queue each frame on a connection of this node in order, one result per item
+pub async fn forward_batch_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    body: web::Json<ForwardBatchRequest>,
+) -> impl Responder {
+    let mut results = Vec::with_capacity(body.items.len());
+    for item in body.into_inner().items {
+        let delivered = server
+            .send_message_to_client(&item.client_id, Message::Text(item.text))
+            .await
+            .is_ok();
+        results.push(delivered);
+    }
+    respond_any(StatusCode::OK, ForwardBatchResponse { results })
+}
@@ -0,0 +1,41 @@
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use serde::Deserialize;
+use std::sync::Arc;
+use tokio_tungstenite::tungstenite::Message;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/internal/forward_client";
+
+#[derive(Deserialize)]
+pub struct ForwardClientRequest {
+    pub client_id: String,
+    /// 已序列化的帧 / Serialized frame
+    pub text: String,
+}
+
+// 路由注册入口（POST）
+// Route registration entry (POST)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::post().to(forward_client_handle)));
+}
+
+// 节点间转发：把帧写入本节点上的连接
+// Node-to-node forward: queue a frame on a connection of this node
+pub async fn forward_client_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    body: web::Json<ForwardClientRequest>,
+) -> impl Responder {
+    let req = body.into_inner();
+    match server
+        .send_message_to_client(&req.client_id, Message::Text(req.text))
+        .await
+    {
+        Ok(()) => respond_any(StatusCode::OK, serde_json::json!({"delivered": true})),
+        Err(e) => respond_any(
+            StatusCode::NOT_FOUND,
+            serde_json::json!({"delivered": false, "message": e.to_string()}),
+        ),
+    }
+}
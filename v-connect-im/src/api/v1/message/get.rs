@@ -0,0 +1,54 @@
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use serde::Deserialize;
+use std::sync::Arc;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/message/get";
+
+#[derive(Deserialize)]
+pub struct MessageGetQuery {
+    pub message_id: String,
+}
+
+// 路由注册入口（GET）
+// Route registration entry (GET)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::get().to(message_get_handle)));
+}
+
+// 按消息ID查询单条消息
+// Look up a single message by ID
+pub async fn message_get_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    query: web::Query<MessageGetQuery>,
+) -> impl Responder {
+    if query.message_id.is_empty() {
+        return respond_any(
+            StatusCode::BAD_REQUEST,
+            serde_json::json!({"message": "message_id is required"}),
+        );
+    }
+    let Some(pool) = server.plugin_connection_pool.as_ref() else {
+        return respond_any(
+            StatusCode::SERVICE_UNAVAILABLE,
+            serde_json::json!({"message": "plugin runtime unavailable"}),
+        );
+    };
+    match pool.storage_get_message(&query.message_id).await {
+        Ok(Some(Some(message))) => respond_any(StatusCode::OK, message),
+        Ok(Some(None)) => respond_any(
+            StatusCode::NOT_FOUND,
+            serde_json::json!({"message": "message not found"}),
+        ),
+        Ok(None) => respond_any(
+            StatusCode::SERVICE_UNAVAILABLE,
+            serde_json::json!({"message": "storage plugin unavailable"}),
+        ),
+        Err(e) => respond_any(
+            StatusCode::BAD_GATEWAY,
+            serde_json::json!({"message": e.to_string()}),
+        ),
+    }
+}
@@ -0,0 +1,63 @@
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use serde::Deserialize;
+use std::sync::Arc;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/message/search";
+
+/// 默认每页条数 / Default page size
+const DEFAULT_LIMIT: usize = 20;
+
+/// 每页条数上限 / Max page size
+const MAX_LIMIT: usize = 100;
+
+#[derive(Deserialize)]
+pub struct MessageSearchQuery {
+    pub uid: String,
+    pub q: String,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+}
+
+// 路由注册入口（GET）
+// Route registration entry (GET)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::get().to(message_search_handle)));
+}
+
+// 搜索用户收发的消息
+// Search messages sent or received by a user
+pub async fn message_search_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    query: web::Query<MessageSearchQuery>,
+) -> impl Responder {
+    if query.uid.is_empty() || query.q.trim().is_empty() {
+        return respond_any(
+            StatusCode::BAD_REQUEST,
+            serde_json::json!({"message": "uid and q are required"}),
+        );
+    }
+    let Some(pool) = server.plugin_connection_pool.as_ref() else {
+        return respond_any(
+            StatusCode::SERVICE_UNAVAILABLE,
+            serde_json::json!({"message": "plugin runtime unavailable"}),
+        );
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    match pool
+        .storage_search(&query.uid, query.q.trim(), limit, query.cursor.as_deref())
+        .await
+    {
+        Ok(Some(page)) => respond_any(StatusCode::OK, page),
+        Ok(None) => respond_any(
+            StatusCode::SERVICE_UNAVAILABLE,
+            serde_json::json!({"message": "storage plugin unavailable"}),
+        ),
+        Err(e) => respond_any(
+            StatusCode::BAD_GATEWAY,
+            serde_json::json!({"message": e.to_string()}),
+        ),
+    }
+}
@@ -0,0 +1,32 @@
+use crate::domain::message::HttpSendMessageRequest;
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use std::sync::Arc;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/message/send";
+
+// 路由注册入口（POST）
+// Route registration entry (POST)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::post().to(message_send_handle)));
+}
+
+// 发送单聊消息
+// Send a direct message
+pub async fn message_send_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    body: web::Json<HttpSendMessageRequest>,
+) -> impl Responder {
+    if server.maintenance.is_enabled() {
+        return crate::service::maintenance::http_rejection();
+    }
+    let resp = server.http_send_message(body.into_inner()).await;
+    let status = if resp.success {
+        StatusCode::OK
+    } else {
+        StatusCode::BAD_REQUEST
+    };
+    respond_any(status, resp)
+}
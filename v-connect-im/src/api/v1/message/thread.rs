@@ -0,0 +1,65 @@
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use serde::Deserialize;
+use std::sync::Arc;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/message/thread";
+
+/// 默认返回条数 / Default number of replies returned
+const DEFAULT_LIMIT: usize = 100;
+
+/// 返回条数上限 / Max replies returned
+const MAX_LIMIT: usize = 500;
+
+#[derive(Deserialize)]
+pub struct MessageThreadQuery {
+    pub message_id: String,
+    pub limit: Option<usize>,
+}
+
+// 路由注册入口（GET）
+// Route registration entry (GET)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::get().to(message_thread_handle)));
+}
+
+// 按写入顺序列出回复某条消息的消息
+// List the replies to a message in write order
+pub async fn message_thread_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    query: web::Query<MessageThreadQuery>,
+) -> impl Responder {
+    if query.message_id.is_empty() {
+        return respond_any(
+            StatusCode::BAD_REQUEST,
+            serde_json::json!({"message": "message_id is required"}),
+        );
+    }
+    let Some(pool) = server.plugin_connection_pool.as_ref() else {
+        return respond_any(
+            StatusCode::SERVICE_UNAVAILABLE,
+            serde_json::json!({"message": "plugin runtime unavailable"}),
+        );
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    match pool.storage_list_thread(&query.message_id, limit).await {
+        Ok(Some(messages)) => respond_any(
+            StatusCode::OK,
+            serde_json::json!({
+                "message_id": query.message_id,
+                "total": messages.len(),
+                "replies": messages,
+            }),
+        ),
+        Ok(None) => respond_any(
+            StatusCode::SERVICE_UNAVAILABLE,
+            serde_json::json!({"message": "storage plugin unavailable"}),
+        ),
+        Err(e) => respond_any(
+            StatusCode::BAD_GATEWAY,
+            serde_json::json!({"message": e.to_string()}),
+        ),
+    }
+}
@@ -0,0 +1,27 @@
+use crate::VConnectIMServer;
+use actix_web::{web, HttpResponse, Responder};
+use std::sync::Arc;
+
+pub const ROUTE_PATH: &str = "/metrics";
+
+/// Prometheus 文本格式的内容类型 / Content type of the Prometheus text format
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+// 路由注册入口（GET）
+// Route registration entry (GET)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::get().to(metrics_handle)));
+}
+
+// Prometheus 指标：插件调用耗时直方图与失败计数
+// Prometheus metrics: plugin call latency histograms and failure counts
+pub async fn metrics_handle(server: web::Data<Arc<VConnectIMServer>>) -> impl Responder {
+    let body = server
+        .plugin_connection_pool
+        .as_ref()
+        .map(|pool| pool.call_metrics().render_prometheus())
+        .unwrap_or_default();
+    HttpResponse::Ok()
+        .content_type(PROMETHEUS_CONTENT_TYPE)
+        .body(body)
+}
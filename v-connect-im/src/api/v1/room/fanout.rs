@@ -0,0 +1,34 @@
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use serde::Deserialize;
+use std::sync::Arc;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/room/fanout";
+
+#[derive(Deserialize)]
+pub struct FanOutJobQuery {
+    pub job_id: String,
+}
+
+// 路由注册入口（GET）
+// Route registration entry (GET)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::get().to(room_fanout_handle)));
+}
+
+// 查询后台扇出任务进度
+// Poll the progress of a background fan-out job
+pub async fn room_fanout_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    query: web::Query<FanOutJobQuery>,
+) -> impl Responder {
+    match server.fanout.get(&query.job_id) {
+        Some(job) => respond_any(StatusCode::OK, job),
+        None => respond_any(
+            StatusCode::NOT_FOUND,
+            serde_json::json!({"message": "fan-out job not found"}),
+        ),
+    }
+}
@@ -0,0 +1,24 @@
+use crate::domain::message::HttpRoomMemberRequest;
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use std::sync::Arc;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/room/join";
+
+// 路由注册入口（POST）
+// Route registration entry (POST)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::post().to(room_join_handle)));
+}
+
+// 加入房间
+// Join a room
+pub async fn room_join_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    body: web::Json<HttpRoomMemberRequest>,
+) -> impl Responder {
+    let req = body.into_inner();
+    respond_any(StatusCode::OK, server.http_join_room(&req.room_id, &req.uid).await)
+}
@@ -0,0 +1,28 @@
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use serde::Deserialize;
+use std::sync::Arc;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/room/members";
+
+#[derive(Deserialize)]
+pub struct RoomMembersQuery {
+    pub room_id: String,
+}
+
+// 路由注册入口（GET）
+// Route registration entry (GET)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::get().to(room_members_handle)));
+}
+
+// 房间成员列表
+// List room members
+pub async fn room_members_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    query: web::Query<RoomMembersQuery>,
+) -> impl Responder {
+    respond_any(StatusCode::OK, server.room_members(&query.room_id))
+}
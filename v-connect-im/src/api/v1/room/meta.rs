@@ -0,0 +1,65 @@
+use crate::service::room_meta::RoomMeta;
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use serde::Deserialize;
+use std::sync::Arc;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/room/meta";
+
+#[derive(Deserialize)]
+pub struct RoomMetaQuery {
+    pub room_id: String,
+}
+
+// 路由注册入口（GET 读取，POST 设置）
+// Route registration entry (GET reads, POST sets)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(
+        web::resource(path)
+            .route(web::get().to(room_meta_get_handle))
+            .route(web::post().to(room_meta_set_handle)),
+    );
+}
+
+// 读取房间元数据
+// Read a room's metadata
+pub async fn room_meta_get_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    query: web::Query<RoomMetaQuery>,
+) -> impl Responder {
+    match server.room_meta(&query.room_id) {
+        Ok(Some(meta)) => respond_any(StatusCode::OK, meta),
+        Ok(None) => respond_any(
+            StatusCode::NOT_FOUND,
+            serde_json::json!({"message": "room meta not found"}),
+        ),
+        Err(e) => respond_any(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            serde_json::json!({"message": e.to_string()}),
+        ),
+    }
+}
+
+// 设置房间元数据（整体覆盖，省略 location 即清除位置）
+// Set a room's metadata (replaces it whole; omitting location clears it)
+pub async fn room_meta_set_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    body: web::Json<RoomMeta>,
+) -> impl Responder {
+    let meta = body.into_inner();
+    if meta.room_id.is_empty() || meta.location.is_some_and(|l| !l.is_valid()) {
+        return respond_any(
+            StatusCode::BAD_REQUEST,
+            serde_json::json!({"message": "room_id is required and location must be in range"}),
+        );
+    }
+    match server.set_room_meta(&meta) {
+        Ok(()) => respond_any(StatusCode::OK, meta),
+        Err(e) => respond_any(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            serde_json::json!({"message": e.to_string()}),
+        ),
+    }
+}
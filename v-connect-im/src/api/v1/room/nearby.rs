@@ -0,0 +1,63 @@
+use crate::service::room_meta::{GeoPoint, MAX_NEARBY_RADIUS_M};
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use serde::Deserialize;
+use std::sync::Arc;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/rooms/nearby";
+
+/// 默认与最大返回条数 / Default and max result count
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 200;
+
+#[derive(Deserialize)]
+pub struct NearbyRoomsQuery {
+    pub lat: f64,
+    pub lng: f64,
+    pub radius_m: f64,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+// 路由注册入口（GET）
+// Route registration entry (GET)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::get().to(nearby_rooms_handle)));
+}
+
+// 半径内的房间，按距离升序
+// Rooms within the radius, nearest first
+pub async fn nearby_rooms_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    query: web::Query<NearbyRoomsQuery>,
+) -> impl Responder {
+    let center = GeoPoint {
+        lat: query.lat,
+        lng: query.lng,
+    };
+    let radius_ok = (f64::MIN_POSITIVE..=MAX_NEARBY_RADIUS_M).contains(&query.radius_m);
+    if !center.is_valid() || !radius_ok {
+        return respond_any(
+            StatusCode::BAD_REQUEST,
+            serde_json::json!({
+                "message": format!(
+                    "lat/lng must be in range and radius_m in (0, {}]",
+                    MAX_NEARBY_RADIUS_M
+                )
+            }),
+        );
+    }
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    match server.nearby_rooms(center, query.radius_m, limit) {
+        Ok(rooms) => respond_any(
+            StatusCode::OK,
+            serde_json::json!({"total": rooms.len(), "rooms": rooms}),
+        ),
+        Err(e) => respond_any(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            serde_json::json!({"message": e.to_string()}),
+        ),
+    }
+}
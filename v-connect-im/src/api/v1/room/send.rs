@@ -0,0 +1,37 @@
+use crate::domain::message::{GroupDeliveryStatus, HttpRoomSendRequest};
+use crate::VConnectIMServer;
+use actix_web::http::StatusCode;
+use actix_web::{web, Responder};
+use std::sync::Arc;
+use v::response::respond_any;
+
+pub const ROUTE_PATH: &str = "/room/send";
+
+// 路由注册入口（POST）
+// Route registration entry (POST)
+pub fn register(cfg: &mut actix_web::web::ServiceConfig, path: &str) {
+    cfg.service(web::resource(path).route(web::post().to(room_send_handle)));
+}
+
+// 向房间群发消息
+// Send a message to a room
+pub async fn room_send_handle(
+    server: web::Data<Arc<VConnectIMServer>>,
+    body: web::Json<HttpRoomSendRequest>,
+) -> impl Responder {
+    if server.maintenance.is_enabled() {
+        return crate::service::maintenance::http_rejection();
+    }
+    let req = body.into_inner();
+    let resp = server
+        .http_group_send_message(req.room_id, req.from_uid, req.content, req.message_type)
+        .await;
+    // 未持久化为服务端故障，可重试 / Not persisted is a server-side failure and may be retried
+    let status = match resp.status {
+        GroupDeliveryStatus::Rejected => StatusCode::BAD_REQUEST,
+        GroupDeliveryStatus::NotPersisted => StatusCode::SERVICE_UNAVAILABLE,
+        GroupDeliveryStatus::PartiallyDelivered | GroupDeliveryStatus::Delivered => StatusCode::OK,
+        GroupDeliveryStatus::Accepted => StatusCode::ACCEPTED,
+    };
+    respond_any(status, resp)
+}
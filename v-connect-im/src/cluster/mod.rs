@@ -5,3 +5,4 @@ pub mod raft;
 #[cfg(feature = "raft_async")]
 pub mod raft_async;
 pub mod router; // 异步Raft模块（按特性启用）/ Async Raft module (feature-gated)
+pub mod rpc; // 节点间内部 RPC / Internal node-to-node RPC
@@ -1,10 +1,93 @@
+//! Raft 复制（单进程模拟）/ Raft replication (single-process mock)
+//!
+//! 日志条目大小受 `cluster.raft_max_content_bytes` 限制（默认 64 KiB，0 为不限）：`content`
+//! 序列化后超过阈值的消息先写入存储插件，条目只携带 [`ContentRef`]（SHA-256 与字节数），
+//! 跟随者应用时按 `message_id` 从存储取回并校验哈希。一致性影响：条目提交只保证引用已复制，
+//! 内容的持久性取决于存储插件；跟随者必须能访问同一存储，取回失败或哈希不符时应用报错而不是
+//! 使用错误内容。无存储可用时退回内联复制。
+//! Log entry size is bounded by `cluster.raft_max_content_bytes` (64 KiB by default, 0 for no
+//! limit): a message whose serialized `content` exceeds it is written to the storage plugin first
+//! and the entry carries only a [`ContentRef`] (SHA-256 and byte size); followers fetch it by
+//! `message_id` on apply and verify the hash. Consistency implications: committing the entry
+//! only guarantees the reference is replicated, the content is as durable as the storage plugin,
+//! and followers must reach the same storage; a failed fetch or hash mismatch fails the apply
+//! instead of using the wrong content. Without storage the content is replicated inline.
+
 use anyhow::Result;
 use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::sync::{Arc, RwLock};
 
 use super::directory::Directory;
 use crate::storage::MessageRecord;
 
+/// 默认的条目内联内容上限（字节）/ Default max inline content per entry, in bytes
+pub const DEFAULT_MAX_CONTENT_BYTES: usize = 64 * 1024;
+
+/// 存储于带外的内容引用 / Reference to content stored out of band
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentRef {
+    /// 序列化内容的 SHA-256（十六进制）/ SHA-256 of the serialized content, hex encoded
+    pub sha256: String,
+    /// 序列化内容的字节数 / Size of the serialized content in bytes
+    pub size: usize,
+}
+
+impl ContentRef {
+    /// 计算内容的引用 / Compute the reference of a content value
+    pub fn of(content: &serde_json::Value) -> Self {
+        let bytes = serde_json::to_vec(content).unwrap_or_default();
+        Self {
+            sha256: hex::encode(Sha256::digest(&bytes)),
+            size: bytes.len(),
+        }
+    }
+
+    /// 内容是否与引用一致 / Whether a content value matches this reference
+    pub fn matches(&self, content: &serde_json::Value) -> bool {
+        Self::of(content) == *self
+    }
+}
+
+/// Raft 日志条目 / Raft log entry
+///
+/// `content_ref` 存在时 `record.content` 为 `null`，内容需从存储取回
+/// With `content_ref` set, `record.content` is `null` and the content is fetched from storage
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub record: MessageRecord,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_ref: Option<ContentRef>,
+}
+
+impl LogEntry {
+    /// 内联内容的条目 / Entry carrying the content inline
+    pub fn inline(record: &MessageRecord) -> Self {
+        Self {
+            record: record.clone(),
+            content_ref: None,
+        }
+    }
+
+    /// 只携带内容引用的条目 / Entry carrying only a content reference
+    pub fn by_reference(record: &MessageRecord) -> Self {
+        let content_ref = ContentRef::of(&record.content);
+        Self {
+            record: MessageRecord {
+                content: serde_json::Value::Null,
+                ..record.clone()
+            },
+            content_ref: Some(content_ref),
+        }
+    }
+
+    /// 条目序列化后的字节数 / Serialized size of the entry in bytes
+    pub fn encoded_len(&self) -> usize {
+        serde_json::to_vec(self).map(|b| b.len()).unwrap_or(0)
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Role {
@@ -17,17 +100,41 @@ pub struct RaftCluster {
     directory: Arc<Directory>,
     leader_id: Arc<RwLock<String>>, // 当前Leader / Current leader
     commit_count: Arc<DashMap<String, u64>>, // 每节点提交计数 / Commit count per node
+    replicated_bytes: Arc<DashMap<String, u64>>, // 每节点已复制的条目字节数 / Entry bytes replicated per node
+    max_content_bytes: usize, // 条目内联内容上限，0 为不限 / Max inline content per entry, 0 for no limit
 }
 
 impl RaftCluster {
     pub fn new(directory: Arc<Directory>, leader_id: String) -> Self {
+        let max_content_bytes = v::get_global_config_manager()
+            .map(|cm| cm.get_or("cluster.raft_max_content_bytes", DEFAULT_MAX_CONTENT_BYTES))
+            .unwrap_or(DEFAULT_MAX_CONTENT_BYTES);
         Self {
             directory,
             leader_id: Arc::new(RwLock::new(leader_id)),
             commit_count: Arc::new(DashMap::new()),
+            replicated_bytes: Arc::new(DashMap::new()),
+            max_content_bytes,
         }
     }
 
+    /// 设置条目内联内容上限，0 为不限 / Set the max inline content per entry, 0 for no limit
+    pub fn with_max_content_bytes(mut self, max_content_bytes: usize) -> Self {
+        self.max_content_bytes = max_content_bytes;
+        self
+    }
+
+    /// 记录内容是否超过内联上限 / Whether a record's content exceeds the inline limit
+    pub fn is_oversized(&self, rec: &MessageRecord) -> bool {
+        self.max_content_bytes > 0
+            && serde_json::to_vec(&rec.content).map_or(0, |b| b.len()) > self.max_content_bytes
+    }
+
+    /// 节点已复制的条目字节数 / Entry bytes replicated by a node
+    pub fn replicated_bytes(&self, node_id: &str) -> u64 {
+        self.replicated_bytes.get(node_id).map(|v| *v).unwrap_or(0)
+    }
+
     #[allow(dead_code)]
     pub fn set_leader(&self, leader_id: String) {
         if let Ok(mut l) = self.leader_id.write() {
@@ -39,7 +146,13 @@ impl RaftCluster {
         self.leader_id.read().map(|l| l.clone()).unwrap_or_default()
     }
 
-    pub fn append_entry_as(&self, node_id: &str, rec: &MessageRecord) -> Result<()> {
+    /// 节点已提交的条目数 / Entries committed by a node
+    #[cfg(test)]
+    pub fn commit_count(&self, node_id: &str) -> u64 {
+        self.commit_count.get(node_id).map(|v| *v).unwrap_or(0)
+    }
+
+    pub fn append_entry_as(&self, node_id: &str, entry: &LogEntry) -> Result<()> {
         let leader = self.get_leader();
         if node_id != leader {
             return Err(anyhow::anyhow!("not leader"));
@@ -67,6 +180,10 @@ impl RaftCluster {
         if acks >= quorum as u64 {
             let cnt = self.commit_count.get(node_id).map(|v| *v).unwrap_or(0);
             self.commit_count.insert(node_id.to_string(), cnt + 1);
+            *self
+                .replicated_bytes
+                .entry(node_id.to_string())
+                .or_insert(0) += entry.encoded_len() as u64;
             Ok(())
         } else {
             Err(anyhow::anyhow!("replication quorum not met"))
@@ -2,7 +2,7 @@ use sha2::{Digest, Sha256};
 
 /// 节点信息 / Node information
 #[allow(dead_code)]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct NodeInfo {
     pub node_id: String,
     pub weight: u32,
@@ -0,0 +1,134 @@
+//! 跨节点投递集成测试：两个真实 HTTP 节点经 `/v1/internal/*` 转发
+//! Cross-node delivery integration tests: two real HTTP nodes forwarding over `/v1/internal/*`
+//!
+//! 运行 / Run: `cargo test -p v-connect-im --features cluster-it cluster_it`
+//!
+//! 测试会改写全局配置中的 `cluster.*`，因此只在启用 `cluster-it` 时编译。
+//! The tests rewrite `cluster.*` in the global config, so they only build with `cluster-it`.
+
+use crate::route_registry::INTERNAL_TOKEN_HEADER;
+use crate::testkit::{im, recv_typed, TestServer};
+use crate::ImMessage;
+use actix_web::dev::ServerHandle;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+const INTERNAL_TOKEN: &str = "cluster-it-secret";
+
+/// 在本机临时端口上运行的节点 / A node serving HTTP on an ephemeral localhost port
+struct HttpNode {
+    ts: TestServer,
+    base: String,
+    handle: ServerHandle,
+}
+
+impl HttpNode {
+    /// 在预留的监听器上启动，端口从预留到服务之间不会被占用
+    /// Start on a reserved listener, so the port cannot be taken between reserving and serving
+    async fn start(node_id: &str, port: u16, listener: TcpListener) -> Self {
+        let ts = TestServer::isolated(node_id);
+        let server = crate::build_http_server(
+            ts.server.clone(),
+            "127.0.0.1".into(),
+            port,
+            1,
+            Some(listener),
+        )
+        .expect("serve http");
+        let handle = server.handle();
+        tokio::spawn(server);
+        let base = format!("http://127.0.0.1:{}", port);
+        wait_until_ready(&base).await;
+        Self { ts, base, handle }
+    }
+
+    async fn stop(self) {
+        self.handle.stop(true).await;
+    }
+}
+
+async fn wait_until_ready(base: &str) {
+    let started = Instant::now();
+    let url = format!("{}/v1/health/live", base);
+    while reqwest::get(&url)
+        .await
+        .map_or(true, |r| !r.status().is_success())
+    {
+        assert!(
+            started.elapsed() < Duration::from_secs(10),
+            "{} not ready",
+            base
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+/// 写入只含 `[cluster]` 的配置并设为全局配置（叠加在默认配置之上）
+/// Write a config holding only `[cluster]` and install it globally (layered over the defaults)
+fn install_cluster_config(peers: &[&str]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("vim-cluster-it-{}.toml", uuid::Uuid::new_v4()));
+    std::fs::write(
+        &path,
+        format!(
+            "[cluster]\npeers = \"{}\"\ninternal_token = \"{}\"\n",
+            peers.join(","),
+            INTERNAL_TOKEN
+        ),
+    )
+    .expect("write cluster config");
+    v::init_global_config_with_file(path.to_str().unwrap()).expect("load cluster config");
+    path
+}
+
+#[actix_web::test]
+async fn test_private_message_is_forwarded_over_http() {
+    let reserve = || v::comm::port::reserve_free_port().expect("ephemeral port");
+    let ((port_a, listener_a), (port_b, listener_b)) = (reserve(), reserve());
+    let base_a = format!("http://127.0.0.1:{}", port_a);
+    let base_b = format!("http://127.0.0.1:{}", port_b);
+    let config = install_cluster_config(&[&base_a, &base_b]);
+
+    let a = HttpNode::start("node-A", port_a, listener_a).await;
+    let b = HttpNode::start("node-B", port_b, listener_b).await;
+    let (alice, mut alice_rx) = a.ts.add_client("alice");
+    let (_, mut bob_rx) = b.ts.add_client("bob");
+
+    a.ts.send(
+        &alice,
+        im(
+            "message",
+            serde_json::json!({"text": "hi bob"}),
+            Some("bob"),
+        ),
+    )
+    .await
+    .unwrap();
+
+    let got: ImMessage = recv_typed(&mut bob_rx).await;
+    assert_eq!(got.msg_type, "forwarded_message");
+    assert_eq!(got.data["from"], "alice");
+    assert_eq!(got.data["content"]["text"], "hi bob");
+    let sent: ImMessage = recv_typed(&mut alice_rx).await;
+    assert_eq!(sent.msg_type, "message_sent");
+    assert_eq!(sent.data["status"], "delivered");
+
+    // 没有令牌的内部调用被拒绝 / Internal calls without the token are rejected
+    let http = reqwest::Client::new();
+    let forward = format!("{}/v1/internal/forward_client", b.base);
+    let body = serde_json::json!({"client_id": "bob", "text": "{}"});
+    let denied = http.post(&forward).json(&body).send().await.unwrap();
+    assert_eq!(denied.status(), 401);
+    let denied = http
+        .get(format!("{}/v1/internal/clients_by_uid?uid=bob", b.base))
+        .header(INTERNAL_TOKEN_HEADER, "wrong")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(denied.status(), 401);
+    assert!(bob_rx.try_recv().is_err());
+
+    a.stop().await;
+    b.stop().await;
+    let _ = std::fs::remove_file(config);
+}
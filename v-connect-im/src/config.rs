@@ -1,3 +1,25 @@
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// `--config` 指定的配置文件，相对路径以其所在目录为基准
+/// The config file given with `--config`; relative paths resolve against its directory
+static CONFIG_FILE: OnceLock<PathBuf> = OnceLock::new();
+
+/// 记录 `--config` 指定的配置文件 / Record the config file given with `--config`
+pub fn set_config_file(path: impl Into<PathBuf>) {
+    let _ = CONFIG_FILE.set(path.into());
+}
+
+/// 解析配置中的路径：展开 `~`，相对路径以 `--config` 配置文件所在目录为基准；使用内置默认配置时仍相对于工作目录
+/// Resolve a path from the config: expands `~` and resolves relative paths against the directory of
+/// the `--config` file; with the built-in default config they stay relative to the working directory
+pub fn resolve_path(p: impl AsRef<Path>) -> PathBuf {
+    match CONFIG_FILE.get() {
+        Some(file) => v::comm::path::resolve_relative_to_config(p, file),
+        None => v::comm::path::expand_home(p),
+    }
+}
+
 #[derive(Clone)]
 /// 精简鉴权配置，用于运行时快速读取 / Lightweight auth configuration snapshot for runtime usage.
 pub struct AuthConfigLite {
@@ -14,3 +36,351 @@ pub struct AuthConfigLite {
 //     pub secret: Option<String>,
 //     pub enabled: bool,
 // }
+
+/// 启动配置模式 / Boot-time config schema
+pub fn config_schema() -> v::comm::config_validator::Schema {
+    use v::comm::config_validator::{FieldRule, Schema, ValueType};
+    Schema::new()
+        .field(
+            FieldRule::optional("server.http_port")
+                .of_type(ValueType::Integer)
+                .range(1.0, 65535.0),
+        )
+        .field(
+            FieldRule::optional("server.ws_port")
+                .of_type(ValueType::Integer)
+                .range(1.0, 65535.0),
+        )
+        .field(
+            FieldRule::optional("server.timeout_ms")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("server.send_queue_capacity")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("server.max_connections")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("server.max_connection_lifetime_ms")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("server.max_idle_ms")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(FieldRule::optional("server.state_dump_dir").of_type(ValueType::String))
+        .field(FieldRule::optional("server.gateway_token").of_type(ValueType::String))
+        .field(FieldRule::optional("server.ip_allowlist").of_type(ValueType::Array))
+        .field(FieldRule::optional("server.ip_denylist").of_type(ValueType::Array))
+        .field(
+            FieldRule::optional("auth.resume_ttl_ms")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(FieldRule::optional("auth.enabled").of_type(ValueType::Bool))
+        .field(
+            FieldRule::optional("auth.center_url")
+                .required_when("auth.enabled", true)
+                .of_type(ValueType::Url),
+        )
+        .field(
+            FieldRule::optional("auth.timeout_ms")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("auth.penalty_base_ms")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("auth.penalty_max_ms")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("auth.ban_after_failures")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("auth.ban_secs")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("auth.failure_window_secs")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("event_bus.capacity")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("quic.port")
+                .of_type(ValueType::Integer)
+                .range(1.0, 65535.0),
+        )
+        .field(
+            FieldRule::optional("http.max_body_bytes")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("http.request_timeout_ms")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(FieldRule::optional("http.cors.allowed_origins").of_type(ValueType::Array))
+        .field(FieldRule::optional("http.cors.allowed_methods").of_type(ValueType::Array))
+        .field(FieldRule::optional("http.cors.allowed_headers").of_type(ValueType::Array))
+        .field(FieldRule::optional("http.cors.allow_credentials").of_type(ValueType::Bool))
+        .field(
+            FieldRule::optional("message.max_attachment_bytes")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("message.max_thumbnail_bytes")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("message.id_generator")
+                .of_type(ValueType::String)
+                .one_of(&["snowflake", "uuid"]),
+        )
+        .field(
+            FieldRule::optional("offline.max_per_sec")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("offline.batch_size")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("offline.high_watermark")
+                .of_type(ValueType::Float)
+                .range(0.01, 1.0),
+        )
+        .field(
+            FieldRule::optional("offline.stall_timeout_ms")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("delivery.deadline_ms")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("delivery.ack_grace_ms")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("message.max_per_sec_per_uid")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(FieldRule::optional("webhook.url").of_type(ValueType::Url))
+        .field(FieldRule::optional("webhook.secret").of_type(ValueType::String))
+        .field(
+            FieldRule::optional("webhook.max_attempts")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("storage.default_retention_secs")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(FieldRule::optional("storage.retention_archive").of_type(ValueType::Bool))
+        .field(
+            FieldRule::optional("storage.retention_sweep_interval_secs")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(FieldRule::optional("blocklist.path").of_type(ValueType::String))
+        .field(FieldRule::optional("maintenance.enabled").of_type(ValueType::Bool))
+        .field(FieldRule::optional("rooms.meta_path").of_type(ValueType::String))
+        .field(FieldRule::optional("translation.enabled").of_type(ValueType::Bool))
+        .field(
+            FieldRule::optional("translation.cache_capacity")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("scheduler.poll_interval_ms")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("scheduler.max_attempts")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(FieldRule::optional("tenants.uid_separator").of_type(ValueType::String))
+        .field(FieldRule::optional("tenants.meta_key").of_type(ValueType::String))
+        .field(
+            FieldRule::optional("offline.max_per_uid")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("offline.quota_queue_capacity")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("rooms.max_per_uid")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("rooms.ops_per_sec")
+                .of_type(ValueType::Float)
+                .range(0.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("rooms.burst")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("rooms.block_after_violations")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("rooms.max_pins")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(FieldRule::optional("rooms.admins").of_type(ValueType::Table))
+        .field(
+            FieldRule::optional("rooms.dedup_window_secs")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("rooms.fanout_parallelism")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("rooms.fanout_background_threshold")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(FieldRule::optional("persistence").of_type(ValueType::Table))
+        .field(FieldRule::optional("cluster.internal_token").of_type(ValueType::String))
+        .field(
+            FieldRule::optional("cluster.worker_id")
+                .of_type(ValueType::Integer)
+                .range(0.0, 1023.0),
+        )
+        .field(
+            FieldRule::optional("cluster.raft_max_content_bytes")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("cluster.forward_batch_window_ms")
+                .of_type(ValueType::Integer)
+                .range(0.0, 1000.0),
+        )
+        .field(
+            FieldRule::optional("cluster.forward_batch_max")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("cluster.forward_batch_max_bytes")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(FieldRule::optional("cluster.forward_gzip").of_type(ValueType::Bool))
+        .field(
+            FieldRule::optional("shutdown.http_grace_ms")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("shutdown.socket_grace_ms")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("shutdown.plugin_grace_ms")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("shutdown.total_budget_ms")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(FieldRule::optional("plugins.plugin_dir").of_type(ValueType::String))
+        .field(FieldRule::optional("plugins.debug").of_type(ValueType::Bool))
+        .field(FieldRule::optional("plugins.required").of_type(ValueType::Array))
+        .field(FieldRule::optional("plugins.on_duplicate").of_type(ValueType::String))
+        .field(FieldRule::optional("plugins.socket_owner").of_type(ValueType::String))
+        .field(FieldRule::optional("plugins.socket_group").of_type(ValueType::String))
+        .field(
+            FieldRule::optional("plugins.ready_timeout_ms")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("plugins.log.buffer_lines")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("plugins.log.max_bytes")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("plugins.log.max_files")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("plugins.log.retention_days")
+                .of_type(ValueType::Integer)
+                .range(0.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("plugins.limits.memory_max_mb")
+                .of_type(ValueType::Integer)
+                .range(1.0, f64::MAX),
+        )
+        .field(
+            FieldRule::optional("plugins.limits.cpu_weight")
+                .of_type(ValueType::Integer)
+                .range(1.0, 10000.0),
+        )
+        .field(FieldRule::optional("plugins.limits.cgroup_root").of_type(ValueType::String))
+        .field(FieldRule::optional("plugins.tcp.listen").of_type(ValueType::String))
+        .field(FieldRule::optional("plugins.tcp.auth_token").of_type(ValueType::String))
+        .field(FieldRule::optional("plugins.transport").of_type(ValueType::Table))
+        .field(FieldRule::optional("plugins.config").of_type(ValueType::Table))
+        .field(
+            FieldRule::optional("plugins.log_level")
+                .one_of(&["trace", "debug", "info", "warn", "error"]),
+        )
+}
@@ -0,0 +1,283 @@
+//! 配置离线检查：加载并校验配置、解析路径、核对插件目录与二进制，不绑定端口也不启动插件
+//! Offline config check: load and validate the config, resolve paths and verify plugin dirs and
+//! binaries, without binding ports or spawning plugins
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use v::comm::config_validator::validate_schema;
+use v::plugin::installer::PluginInstaller;
+use v::plugin::manifest::PluginManifest;
+use v::ConfigManager;
+
+/// 服务启动时会读写的路径配置及其缺省值 / Path settings the server reads at boot, with their defaults
+const PATH_KEYS: &[(&str, &str)] = &[
+    ("plugins.plugin_dir", "./plugins"),
+    (
+        "rooms.meta_path",
+        crate::service::room_meta::DEFAULT_ROOM_META_PATH,
+    ),
+    (
+        "blocklist.path",
+        crate::service::blocklist::DEFAULT_BLOCKLIST_PATH,
+    ),
+    (
+        "storage.fallback_path",
+        crate::service::storage_fallback::DEFAULT_FALLBACK_PATH,
+    ),
+    (
+        "webhook.queue_path",
+        crate::service::webhook::DEFAULT_QUEUE_PATH,
+    ),
+    (
+        "scheduler.path",
+        crate::service::scheduler::DEFAULT_SCHEDULER_PATH,
+    ),
+    (
+        "server.state_dump_dir",
+        crate::service::state_dump::DEFAULT_STATE_DUMP_DIR,
+    ),
+];
+
+/// 检查报告 / Check report
+#[derive(Debug, Default)]
+pub struct ConfigReport {
+    /// 配置文件路径 / Config file path
+    pub source: String,
+    /// 解析后的路径（配置键 → 绝对路径）/ Resolved paths (config key → absolute path)
+    pub paths: Vec<(String, PathBuf)>,
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl ConfigReport {
+    /// 没有错误即通过 / Passes when there are no errors
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// 渲染为人类可读报告 / Render as a human-readable report
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "配置 / Config: {}", self.source);
+        if !self.paths.is_empty() {
+            let _ = writeln!(out, "路径 / Paths:");
+            for (key, path) in &self.paths {
+                let _ = writeln!(out, "  {} = {}", key, path.display());
+            }
+        }
+        for warning in &self.warnings {
+            let _ = writeln!(out, "警告 / warning: {}", warning);
+        }
+        for error in &self.errors {
+            let _ = writeln!(out, "错误 / error: {}", error);
+        }
+        let verdict = if self.is_ok() { "OK" } else { "FAILED" };
+        let _ = write!(
+            out,
+            "{} ({} error(s), {} warning(s))",
+            verdict,
+            self.errors.len(),
+            self.warnings.len()
+        );
+        out
+    }
+}
+
+/// 按服务启动时的方式加载配置文件并检查 / Load a config file the way the server does at boot and check it
+pub fn check_file(path: &str) -> ConfigReport {
+    let loaded = v::init_global_config_with_file(path)
+        .map_err(anyhow::Error::from)
+        .and_then(|_| Ok(v::get_global_config_manager()?));
+    match loaded {
+        Ok(cm) => {
+            let mut report = check_manager(&cm);
+            report.source = path.to_string();
+            report
+        }
+        Err(e) => ConfigReport {
+            source: path.to_string(),
+            errors: vec![format!("无法加载 / cannot load: {}", e)],
+            ..Default::default()
+        },
+    }
+}
+
+/// 检查已加载的配置 / Check an already loaded config
+pub fn check_manager(cm: &ConfigManager) -> ConfigReport {
+    let mut report = ConfigReport::default();
+
+    // 与启动时相同的模式校验 / The same schema validation as at boot
+    if let Err(issues) = validate_schema(cm, &crate::config::config_schema()) {
+        report
+            .errors
+            .extend(issues.iter().map(|issue| issue.to_string()));
+    }
+
+    for (key, default) in PATH_KEYS {
+        let raw = cm.get_or(key, default.to_string());
+        report.paths.push((key.to_string(), resolve(&raw)));
+    }
+    let plugin_dir = resolve(&cm.get_or("plugins.plugin_dir", "./plugins".to_string()));
+    let socket_path = cm
+        .get::<String>("plugins.socket_path")
+        .ok()
+        .filter(|p| !p.trim().is_empty())
+        .map(|p| resolve(&p))
+        .unwrap_or_else(|| plugin_dir.join("sockets").join("runtime.sock"));
+    report
+        .paths
+        .push(("plugins.socket_path".to_string(), socket_path));
+
+    check_plugins(cm, &plugin_dir, &mut report);
+    report
+}
+
+/// 核对已安装插件、待安装文件与开发插件 / Verify installed plugins, install files and dev plugins
+fn check_plugins(cm: &ConfigManager, plugin_dir: &Path, report: &mut ConfigReport) {
+    let install_urls: Vec<String> = cm.get::<Vec<String>>("plugins.install").unwrap_or_default();
+    for url in &install_urls {
+        if let Some(file) = url.strip_prefix("file://") {
+            if !Path::new(file).is_file() {
+                report.errors.push(format!(
+                    "plugins.install: 文件不存在 / file not found: {}",
+                    file
+                ));
+            }
+        }
+    }
+
+    if !plugin_dir.is_dir() {
+        // 启动时会创建目录，只是没有可启动的插件 / Created at boot, there is just nothing to start
+        report.warnings.push(format!(
+            "plugins.plugin_dir: 目录不存在 / directory does not exist: {}",
+            plugin_dir.display()
+        ));
+    } else {
+        match PluginInstaller::new(plugin_dir).list_installed() {
+            Ok(mut names) => {
+                names.sort();
+                for name in names {
+                    if let Err(e) = PluginManifest::load(&plugin_dir.join(&name)) {
+                        report.errors.push(format!("plugin {}: {}", name, e));
+                    }
+                    if let Err(e) = crate::plugins::runtime::find_plugin_binary(plugin_dir, &name) {
+                        report.errors.push(format!("plugin {}: {}", name, e));
+                    }
+                }
+            }
+            Err(e) => report.errors.push(format!(
+                "plugins.plugin_dir: 无法读取 / cannot read {}: {}",
+                plugin_dir.display(),
+                e
+            )),
+        }
+    }
+
+    let dev_plugins: Vec<String> = cm
+        .get::<Vec<String>>("plugins.dev_plugins")
+        .unwrap_or_default();
+    for dev_plugin in dev_plugins {
+        match dev_plugin.split_once(':') {
+            Some((name, path)) if !Path::new(path).exists() => report.errors.push(format!(
+                "plugins.dev_plugins: {} 路径不存在 / path not found: {}",
+                name, path
+            )),
+            Some(_) => {}
+            None => report.errors.push(format!(
+                "plugins.dev_plugins: 格式应为 name:path / expected name:path, got {}",
+                dev_plugin
+            )),
+        }
+    }
+}
+
+/// 展开 ~ 并转为绝对路径 / Expand ~ and make the path absolute
+fn resolve(path: &str) -> PathBuf {
+    let path = v::comm::path::expand_home(path);
+    if path.is_absolute() {
+        path
+    } else {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(path.strip_prefix(".").unwrap_or(&path)))
+            .unwrap_or(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use v::ConfigSource;
+
+    fn manager(root: &Path, toml: &str) -> ConfigManager {
+        let path = root.join("config.toml");
+        std::fs::write(&path, toml).unwrap();
+        ConfigManager::with_sources(vec![ConfigSource::File {
+            path: path.to_string_lossy().to_string(),
+            format: None,
+            required: true,
+        }])
+        .unwrap()
+    }
+
+    #[test]
+    fn test_reports_schema_and_plugin_errors() {
+        let root = std::env::temp_dir().join(format!("vgo-config-check-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        // 一个完整插件，一个缺少二进制 / One complete plugin, one without a binary
+        for name in ["good", "broken"] {
+            let dir = root.join(name);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(
+                dir.join("plugin.json"),
+                format!(
+                    r#"{{"name":"{}","version":"1.0.0","capabilities":[]}}"#,
+                    name
+                ),
+            )
+            .unwrap();
+        }
+        let binary = root.join("good").join("good");
+        std::fs::write(&binary, "#!/bin/sh\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&binary, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let first = check_manager(&manager(
+            &root,
+            &format!(
+                "[plugins]\nplugin_dir = \"{}\"\ndev_plugins = []\n",
+                root.display()
+            ),
+        ));
+        assert_eq!(first.errors.len(), 1, "{}", first.render());
+        assert!(
+            first.errors[0].starts_with("plugin broken:"),
+            "{}",
+            first.render()
+        );
+
+        std::fs::remove_dir_all(root.join("broken")).unwrap();
+        let report = check_manager(&manager(
+            &root,
+            &format!(
+                "[server]\nhttp_port = 70000\n[plugins]\nplugin_dir = \"{}\"\n{}",
+                root.display(),
+                "dev_plugins = [\"dev:/nonexistent/vgo\"]\n"
+            ),
+        ));
+        assert!(!report.is_ok());
+        assert!(report.errors.iter().any(|e| e.contains("server.http_port")));
+        assert!(report
+            .errors
+            .iter()
+            .any(|e| e.contains("plugins.dev_plugins")));
+        assert!(!report.errors.iter().any(|e| e.starts_with("plugin good")));
+        assert!(report
+            .render()
+            .ends_with("FAILED (2 error(s), 0 warning(s))"));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,251 @@
+//! 可配置 CORS 中间件 / Configurable CORS middleware
+//!
+//! 配置项 / Config keys: `http.cors.allowed_origins`、`allowed_methods`、`allowed_headers`、
+//! `allow_credentials`、`max_age_secs`。开启凭据时通配来源被拒绝。
+//! With credentials enabled a wildcard origin is refused.
+
+use actix_web::body::BoxBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::HttpResponse;
+use std::sync::Arc;
+use tracing::warn;
+
+const DEFAULT_METHODS: &[&str] = &["GET", "POST", "PUT", "DELETE", "OPTIONS"];
+const DEFAULT_HEADERS: &[&str] = &["Content-Type", "Authorization", "X-Admin-Token"];
+
+/// CORS 配置 / CORS configuration
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub allow_credentials: bool,
+    pub max_age_secs: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec!["*".to_string()],
+            allowed_methods: DEFAULT_METHODS.iter().map(|s| s.to_string()).collect(),
+            allowed_headers: DEFAULT_HEADERS.iter().map(|s| s.to_string()).collect(),
+            allow_credentials: false,
+            max_age_secs: 600,
+        }
+    }
+}
+
+impl CorsConfig {
+    /// 从 `http.cors.*` 读取，缺省项使用默认值 / Read `http.cors.*`, falling back to defaults
+    pub fn from_config() -> Self {
+        let defaults = Self::default();
+        let cm = match v::get_global_config_manager() {
+            Ok(cm) => cm,
+            Err(_) => return defaults,
+        };
+        let cfg = Self {
+            allowed_origins: cm.get_or("http.cors.allowed_origins", defaults.allowed_origins),
+            allowed_methods: cm.get_or("http.cors.allowed_methods", defaults.allowed_methods),
+            allowed_headers: cm.get_or("http.cors.allowed_headers", defaults.allowed_headers),
+            allow_credentials: cm.get_or("http.cors.allow_credentials", false),
+            max_age_secs: cm.get_or("http.cors.max_age_secs", defaults.max_age_secs),
+        };
+        if cfg.allow_credentials && cfg.wildcard_origin() {
+            warn!("⚠️  CORS 开启凭据时不允许通配来源，跨域请求将被拒绝 / CORS wildcard origin with credentials is refused; cross-origin requests will be denied");
+        }
+        cfg
+    }
+
+    fn wildcard_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|o| o == "*")
+    }
+
+    /// 返回应写入 `Access-Control-Allow-Origin` 的值；不允许时为 None
+    /// Value for `Access-Control-Allow-Origin`, or None when the origin is not allowed
+    pub fn allow_origin(&self, origin: &str) -> Option<String> {
+        if self.wildcard_origin() {
+            // 凭据 + 通配：安全起见拒绝 / Credentials + wildcard: deny for safety
+            if self.allow_credentials {
+                return None;
+            }
+            return Some("*".to_string());
+        }
+        self.allowed_origins
+            .iter()
+            .find(|o| o.eq_ignore_ascii_case(origin))
+            .map(|_| origin.to_string())
+    }
+
+    fn allows_method(&self, method: &str) -> bool {
+        self.allowed_methods
+            .iter()
+            .any(|m| m == "*" || m.eq_ignore_ascii_case(method))
+    }
+
+    /// 写入通用 CORS 响应头 / Write the common CORS response headers
+    fn apply(&self, headers: &mut header::HeaderMap, allow_origin: &str) {
+        if let Ok(v) = HeaderValue::from_str(allow_origin) {
+            headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, v);
+        }
+        if allow_origin != "*" {
+            headers.append(header::VARY, HeaderValue::from_static("Origin"));
+        }
+        if self.allow_credentials {
+            headers.insert(
+                header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+                HeaderValue::from_static("true"),
+            );
+        }
+    }
+
+    /// 预检响应 / Preflight response
+    fn preflight(&self, allow_origin: &str) -> HttpResponse {
+        let mut resp = HttpResponse::NoContent().finish();
+        let headers = resp.headers_mut();
+        self.apply(headers, allow_origin);
+        let pairs = [
+            (
+                header::ACCESS_CONTROL_ALLOW_METHODS,
+                self.allowed_methods.join(", "),
+            ),
+            (
+                header::ACCESS_CONTROL_ALLOW_HEADERS,
+                self.allowed_headers.join(", "),
+            ),
+            (
+                header::ACCESS_CONTROL_MAX_AGE,
+                self.max_age_secs.to_string(),
+            ),
+        ];
+        for (name, value) in pairs {
+            if let Ok(v) = HeaderValue::from_str(&value) {
+                headers.insert(name, v);
+            }
+        }
+        resp
+    }
+}
+
+/// CORS 中间件处理：同源请求原样放行，不允许的来源不附加任何 CORS 头，
+/// 不允许的预检返回 403
+/// CORS middleware body: same-origin requests pass untouched, disallowed
+/// origins get no CORS headers, disallowed preflights get 403
+pub async fn handle(
+    config: Arc<CorsConfig>,
+    req: ServiceRequest,
+    next: Next<BoxBody>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let origin = req
+        .headers()
+        .get(header::ORIGIN)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+    let Some(origin) = origin else {
+        return next.call(req).await;
+    };
+    let allow_origin = config.allow_origin(&origin);
+    let requested = req
+        .headers()
+        .get(header::ACCESS_CONTROL_REQUEST_METHOD)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+    if let (true, Some(requested)) = (req.method() == Method::OPTIONS, requested) {
+        let resp = match allow_origin {
+            Some(ao) if config.allows_method(&requested) => config.preflight(&ao),
+            _ => HttpResponse::new(StatusCode::FORBIDDEN),
+        };
+        return Ok(req.into_response(resp));
+    }
+    let mut res = next.call(req).await?;
+    if let Some(ao) = allow_origin {
+        config.apply(res.headers_mut(), &ao);
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::{test, web, App};
+
+    fn app_config(origins: &[&str], credentials: bool) -> Arc<CorsConfig> {
+        Arc::new(CorsConfig {
+            allowed_origins: origins.iter().map(|s| s.to_string()).collect(),
+            allow_credentials: credentials,
+            ..CorsConfig::default()
+        })
+    }
+
+    async fn call(cfg: Arc<CorsConfig>, req: test::TestRequest) -> ServiceResponse {
+        let app = test::init_service(
+            App::new()
+                .wrap(from_fn(move |req, next| handle(cfg.clone(), req, next)))
+                .route(
+                    "/x",
+                    web::get().to(|| async { HttpResponse::Ok().finish() }),
+                ),
+        )
+        .await;
+        test::call_service(&app, req.to_request()).await
+    }
+
+    #[actix_web::test]
+    async fn test_allowed_and_disallowed_origins() {
+        let cfg = app_config(&["https://admin.example.com"], true);
+        let res = call(
+            cfg.clone(),
+            test::TestRequest::get()
+                .uri("/x")
+                .insert_header((header::ORIGIN, "https://admin.example.com")),
+        )
+        .await;
+        assert_eq!(
+            res.headers()
+                .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+                .unwrap(),
+            "https://admin.example.com"
+        );
+        assert_eq!(
+            res.headers()
+                .get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS)
+                .unwrap(),
+            "true"
+        );
+
+        let res = call(
+            cfg.clone(),
+            test::TestRequest::default()
+                .method(Method::OPTIONS)
+                .uri("/x")
+                .insert_header((header::ORIGIN, "https://evil.example.com"))
+                .insert_header((header::ACCESS_CONTROL_REQUEST_METHOD, "POST")),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn test_wildcard_with_credentials_is_denied() {
+        let cfg = app_config(&["*"], true);
+        assert_eq!(cfg.allow_origin("https://a.example.com"), None);
+        let res = call(
+            cfg,
+            test::TestRequest::get()
+                .uri("/x")
+                .insert_header((header::ORIGIN, "https://a.example.com")),
+        )
+        .await;
+        assert!(res
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none());
+        assert_eq!(
+            app_config(&["*"], false).allow_origin("x").as_deref(),
+            Some("*")
+        );
+    }
+}
@@ -9,6 +9,183 @@ pub struct ImMessage {
     pub data: serde_json::Value,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub target_uid: Option<String>,
+    /// 投递优先级，省略为 `normal` / Delivery priority, `normal` when omitted
+    #[serde(default, skip_serializing_if = "MessagePriority::is_normal")]
+    pub priority: MessagePriority,
+    /// 所回复的消息ID，非回复时省略 / Parent message_id this replies to, omitted otherwise
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+}
+
+/// 消息优先级（QoS）/ Message priority (QoS)
+///
+/// 决定发送队列中的出队顺序与队列满时的挤占，以及重连时离线消息的补发顺序。
+/// Decides the dequeue order in the send queue, eviction when it is full, and the replay order
+/// of offline messages on reconnect.
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum MessagePriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+impl MessagePriority {
+    pub fn is_normal(&self) -> bool {
+        *self == Self::Normal
+    }
+
+    /// 从低到高的序号 / Index from low to high
+    pub fn rank(self) -> usize {
+        self as usize
+    }
+
+    /// 解析 `low` / `normal` / `high`，其他值为 `normal` / Parse `low` / `normal` / `high`; anything else is `normal`
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "low" => Self::Low,
+            "high" => Self::High,
+            _ => Self::Normal,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Low => "low",
+            Self::Normal => "normal",
+            Self::High => "high",
+        }
+    }
+}
+
+impl ImMessage {
+    /// 构造 `error` 消息：`{"code": ..., "message": ...}` / Build an `error` frame: `{"code": ..., "message": ...}`
+    pub fn error(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            msg_type: "error".to_string(),
+            data: serde_json::json!({
+                "code": code,
+                "message": message.into(),
+            }),
+            target_uid: None,
+            priority: Default::default(),
+            reply_to: None,
+        }
+    }
+
+    /// 构造 `message_rejected` 消息：`{"message_id", "code", "reason", "plugin"}`
+    /// Build a `message_rejected` frame: `{"message_id", "code", "reason", "plugin"}`
+    pub fn message_rejected(message_id: &str, rejection: &PluginRejection) -> Self {
+        Self {
+            msg_type: "message_rejected".to_string(),
+            data: serde_json::json!({
+                "message_id": message_id,
+                "code": rejection.code,
+                "reason": rejection.reason,
+                "plugin": rejection.plugin,
+            }),
+            target_uid: None,
+            priority: Default::default(),
+            reply_to: None,
+        }
+    }
+}
+
+/// 插件拦截消息的原因 / Why a plugin blocked a message
+///
+/// 插件以 `flow = "stop"` 拦截时可在响应中带 `code` 与 `reason`（缺省时取 `error`）。
+/// A plugin blocking with `flow = "stop"` may put `code` and `reason` in its response
+/// (`reason` falls back to `error`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PluginRejection {
+    pub plugin: String,
+    pub code: String,
+    pub reason: String,
+}
+
+impl PluginRejection {
+    /// 插件未给出 `code` 时使用 / Used when the plugin gives no `code`
+    pub const DEFAULT_CODE: &'static str = "BLOCKED_BY_PLUGIN";
+
+    /// 解析插件响应，仅 `flow == "stop"` 时返回 / Parse a plugin response; `Some` only for `flow == "stop"`
+    pub fn from_response(plugin: &str, response: &serde_json::Value) -> Option<Self> {
+        if response.get("flow").and_then(|v| v.as_str()) != Some("stop") {
+            return None;
+        }
+        let text = |key: &str| match response.get(key) {
+            Some(serde_json::Value::String(s)) if !s.is_empty() => Some(s.clone()),
+            Some(v @ serde_json::Value::Number(_)) => Some(v.to_string()),
+            _ => None,
+        };
+        Some(Self {
+            plugin: plugin.to_string(),
+            code: text("code").unwrap_or_else(|| Self::DEFAULT_CODE.to_string()),
+            reason: text("reason")
+                .or_else(|| text("error"))
+                .unwrap_or_else(|| format!("Message blocked by plugin {}", plugin)),
+        })
+    }
+
+    /// 按插件执行顺序找到第一个拦截 / The first block in plugin execution order
+    pub fn find(responses: &[(String, serde_json::Value)]) -> Option<Self> {
+        responses
+            .iter()
+            .find_map(|(plugin, response)| Self::from_response(plugin, response))
+    }
+}
+
+/// WS `error` 消息的错误码，取值稳定，供客户端分支处理
+/// Error codes carried by WS `error` frames; the values are stable for clients to branch on
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// 帧不是合法 JSON / The frame is not valid JSON
+    InvalidJson,
+    /// 二进制帧无法按协商的编码解码 / A binary frame does not decode in the negotiated encoding
+    InvalidFrame,
+    /// 未知的消息类型 / Unknown message type
+    UnknownType,
+    /// 需要先认证 / Authentication required
+    Unauthenticated,
+    /// 认证失败后的惩罚期内，稍后重试 / Within the lock after a failed auth, retry later
+    AuthThrottled,
+    /// 认证失败次数过多，暂时封禁 / Temporarily banned after too many failed auth attempts
+    AuthBanned,
+    /// 私聊缺少目标，或批量成员操作缺少 uids / Private message without a target, or bulk membership without uids
+    MissingTarget,
+    /// 群消息缺少房间 / Group message without a room
+    MissingRoom,
+    /// 附件不合法或超限 / Invalid or oversized attachment
+    InvalidAttachment,
+    /// 表情回应缺少消息ID或表情不合法 / Reaction without a message id or with an invalid emoji
+    InvalidReaction,
+    /// 定时消息的 `deliver_at` 缺失或超出可预约范围 / Scheduled message `deliver_at` missing or too far ahead
+    InvalidSchedule,
+    /// uid 已被封禁 / The uid is blocked
+    UidBlocked,
+    /// 发送过于频繁 / Sending too fast
+    RateLimited,
+    /// 加入/离开房间过于频繁 / Joining or leaving rooms too fast
+    RoomRateLimited,
+    /// 已达到每个 uid 的房间数上限 / Per-uid room limit reached
+    RoomLimitExceeded,
+    /// 请求的资源不存在或无权访问 / Not found or not accessible
+    NotFound,
+    /// 只能操作自己的数据 / Only the caller's own data may be changed
+    Forbidden,
+    /// 房间置顶消息已达上限 / The room's pinned message limit is reached
+    PinLimitExceeded,
+    /// 存储插件调用失败 / The storage plugin call failed
+    StorageError,
+    /// 存储插件不可用且策略为 `fail` / The storage plugin is unavailable and the policy is `fail`
+    StorageUnavailable,
+    /// 消息未能复制到集群多数节点，未投递 / The message was not replicated to a cluster quorum and not delivered
+    ReplicationFailed,
+    /// 服务维护中，暂不接收新消息 / The server is in maintenance and not accepting new messages
+    MaintenanceMode,
 }
 
 #[derive(Serialize, Deserialize, Debug, JsonSchema)]
@@ -53,6 +230,57 @@ pub struct HttpSendMessageResponse {
     pub delivered_at: Option<i64>,
 }
 
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct HttpRoomSendRequest {
+    pub room_id: String,
+    pub from_uid: String,
+    pub content: serde_json::Value,
+    pub message_type: Option<String>,
+}
+
+/// 群消息投递状态 / Group message delivery status
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupDeliveryStatus {
+    /// 持久化前被拒绝（附件无效、插件拦截）/ Rejected before persisting (invalid attachment, plugin stop)
+    Rejected,
+    /// 存储或复制失败，未投递给任何成员 / Storage or replication failed; nobody was delivered to
+    NotPersisted,
+    /// 已持久化，部分连接投递失败 / Persisted, but some connections failed to receive it
+    PartiallyDelivered,
+    /// 已持久化，投递无失败（离线成员已入离线队列）/ Persisted with no failed delivery (offline members queued)
+    Delivered,
+    /// 已持久化，大房间在后台扇出，凭 `job_id` 查询进度 / Persisted; a large room fans out in the background, poll `job_id`
+    Accepted,
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct HttpGroupSendResponse {
+    /// 已持久化 / Persisted
+    pub success: bool,
+    pub status: GroupDeliveryStatus,
+    pub message: String,
+    pub message_id: Option<String>,
+    pub delivered_count: usize,
+    pub failed_count: usize,
+    pub offline_count: usize,
+    /// 后台扇出任务 ID（`accepted` 时）/ Background fan-out job id (when `accepted`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct HttpRoomMemberRequest {
+    pub room_id: String,
+    pub uid: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct HttpRoomMembersResponse {
+    pub room_id: String,
+    pub members: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct HttpBroadcastRequest {
     pub from_uid: String,
@@ -60,6 +288,35 @@ pub struct HttpBroadcastRequest {
     pub message_type: Option<String>,
 }
 
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct HttpSystemMessageRequest {
+    /// `all`、`room:<id>` 或 `uid:<id>` / `all`, `room:<id>` or `uid:<id>`
+    pub scope: String,
+    pub content: serde_json::Value,
+    /// 为离线用户写入离线队列 / Queue offline for users who are not online
+    #[serde(default)]
+    pub offline: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+pub struct HttpMaintenanceRequest {
+    /// 开启或关闭维护模式 / Turn maintenance mode on or off
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, JsonSchema)]
+pub struct HttpBlockedUidsRequest {
+    /// 要封禁的 uid / uids to block
+    #[serde(default)]
+    pub block: Vec<String>,
+    /// 要解除封禁的 uid / uids to unblock
+    #[serde(default)]
+    pub unblock: Vec<String>,
+    /// 封禁原因，记录在 `block` 的每一项上 / Block reason, recorded on every `block` entry
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, JsonSchema)]
 pub struct HttpBroadcastResponse {
     pub success: bool,
@@ -77,7 +334,7 @@ pub enum WebhookEventType {
     MessageFailed,
 }
 
-#[derive(Serialize, Deserialize, Debug, JsonSchema)]
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
 pub struct WebhookEvent {
     pub event_type: WebhookEventType,
     pub event_id: String,
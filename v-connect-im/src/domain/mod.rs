@@ -1 +1,4 @@
 pub mod message;
+// 校验函数供接收方参考，主机自身只签名 / The verification helper is for receivers; the host only signs
+#[allow(dead_code)]
+pub mod webhook;
@@ -0,0 +1,142 @@
+//! HTTP 请求体大小与处理超时限制 / HTTP body size and request timeout limits
+//!
+//! 配置项 / Config keys: `http.max_body_bytes`、`http.request_timeout_ms`
+
+use actix_web::body::BoxBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::error::InternalError;
+use actix_web::http::StatusCode;
+use actix_web::middleware::Next;
+use actix_web::web;
+use serde_json::json;
+use std::time::Duration;
+use v::response::respond_any;
+
+/// 默认请求体上限（1 MiB）/ Default max body size (1 MiB)
+pub const DEFAULT_MAX_BODY_BYTES: usize = 1024 * 1024;
+/// 默认请求处理超时（30 秒）/ Default request timeout (30 s)
+pub const DEFAULT_REQUEST_TIMEOUT_MS: u64 = 30_000;
+
+/// HTTP 限制 / HTTP limits
+#[derive(Debug, Clone, Copy)]
+pub struct HttpLimits {
+    pub max_body_bytes: usize,
+    pub request_timeout: Duration,
+}
+
+impl Default for HttpLimits {
+    fn default() -> Self {
+        Self {
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            request_timeout: Duration::from_millis(DEFAULT_REQUEST_TIMEOUT_MS),
+        }
+    }
+}
+
+impl HttpLimits {
+    /// 从 `http.*` 读取，缺省项使用默认值 / Read `http.*`, falling back to defaults
+    pub fn from_config() -> Self {
+        match v::get_global_config_manager() {
+            Ok(cm) => Self {
+                max_body_bytes: cm.get_or("http.max_body_bytes", DEFAULT_MAX_BODY_BYTES),
+                request_timeout: Duration::from_millis(
+                    cm.get_or("http.request_timeout_ms", DEFAULT_REQUEST_TIMEOUT_MS),
+                ),
+            },
+            Err(_) => Self::default(),
+        }
+    }
+
+    /// 注册请求体大小限制（原始 payload 与 JSON 提取器）
+    /// Register body size limits (raw payload and the JSON extractor)
+    pub fn configure(&self, cfg: &mut web::ServiceConfig) {
+        cfg.app_data(web::PayloadConfig::new(self.max_body_bytes))
+            .app_data(web::JsonConfig::default().limit(self.max_body_bytes));
+    }
+}
+
+/// 超时中间件：处理超过时限时中止并返回 408
+/// Timeout middleware: aborts handlers exceeding the limit with a 408
+pub async fn request_timeout(
+    timeout: Duration,
+    req: ServiceRequest,
+    next: Next<BoxBody>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let method = req.method().clone();
+    let path = req.path().to_string();
+    match tokio::time::timeout(timeout, next.call(req)).await {
+        Ok(res) => res,
+        Err(_) => {
+            tracing::warn!(
+                "⏱️  请求处理超时 / Request timed out after {:?}: {} {}",
+                timeout,
+                method,
+                path
+            );
+            Err(InternalError::from_response(
+                "request timed out",
+                respond_any(
+                    StatusCode::REQUEST_TIMEOUT,
+                    json!({"message": "request timed out"}),
+                ),
+            )
+            .into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::middleware::from_fn;
+    use actix_web::{test, App, HttpResponse};
+
+    #[actix_web::test]
+    async fn test_body_limit_and_timeout() {
+        let limits = HttpLimits {
+            max_body_bytes: 16,
+            request_timeout: Duration::from_millis(50),
+        };
+        let app = test::init_service(
+            App::new()
+                .configure(|cfg| limits.configure(cfg))
+                .wrap(from_fn(move |req, next| {
+                    request_timeout(limits.request_timeout, req, next)
+                }))
+                .route(
+                    "/echo",
+                    web::post().to(|body: web::Json<serde_json::Value>| async move {
+                        HttpResponse::Ok().json(body.into_inner())
+                    }),
+                )
+                .route(
+                    "/slow",
+                    web::get().to(|| async {
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                        HttpResponse::Ok().finish()
+                    }),
+                ),
+        )
+        .await;
+
+        let ok = test::TestRequest::post()
+            .uri("/echo")
+            .set_json(json!({"a": 1}))
+            .to_request();
+        assert!(test::call_service(&app, ok).await.status().is_success());
+
+        let big = test::TestRequest::post()
+            .uri("/echo")
+            .set_json(json!({"a": "x".repeat(64)}))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, big).await.status(),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+
+        // 超时错误由 actix 渲染为 408 响应 / The timeout error is rendered by actix as a 408
+        let slow = test::TestRequest::get().uri("/slow").to_request();
+        let err = test::try_call_service(&app, slow).await.unwrap_err();
+        assert_eq!(err.error_response().status(), StatusCode::REQUEST_TIMEOUT);
+    }
+}
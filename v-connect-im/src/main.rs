@@ -1,4 +1,8 @@
 use crate::plugins::{PluginContext, PluginFlow};
+use crate::service::delivery_log::{DeliveryDrop, DropReason};
+use crate::service::device_sync::DeliveryState;
+use crate::service::fanout::RoomFanOut;
+use crate::service::group_dedup::DedupClaim;
 use actix_web::{web, App, HttpServer};
 use anyhow::Result;
 use clap::Parser;
@@ -6,10 +10,8 @@ use dashmap::DashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::time::sleep;
 use tokio_tungstenite::tungstenite::Message;
 use tracing::{debug, error, info, warn};
-use uuid::Uuid;
 use v::init_tracing;
 
 include!(concat!(env!("OUT_DIR"), "/auto_mod.rs"));
@@ -19,14 +21,22 @@ extern crate self as v_connect_im;
 // mod app; // 不再使用独立app构建 / not using standalone app builder
 mod cluster;
 mod config;
+mod config_check;
+mod cors;
 mod domain;
+mod http_limits;
 mod net;
 mod plugins;
+mod route_registry;
 mod router;
 mod server;
 mod service;
 mod storage; // 保留数据结构定义 / Keep data structure definitions
 mod tasks;
+#[cfg(all(test, feature = "cluster-it"))]
+mod cluster_it;
+#[cfg(test)]
+mod testkit;
 mod ws;
 mod api_registry {
     include!(concat!(env!("OUT_DIR"), "/api_registry.rs"));
@@ -40,6 +50,136 @@ pub struct Args {
     /// Specify config file path (auto-detect TOML/JSON/YAML)
     #[arg(short = 'c', long = "config", default_value = "config/default.toml")]
     config: Option<String>,
+
+    /// 只检查配置文件后退出，不绑定端口也不启动插件，有错误时退出码非零
+    /// Check the config file and exit without binding ports or spawning plugins; non-zero exit on errors
+    #[arg(long = "check-config")]
+    check_config: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// 运维子命令 / Operator subcommands
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// 通过运行中服务的管理接口下发系统消息 / Send a system message through a running server's admin API
+    SystemMessage {
+        /// all、room:<id> 或 uid:<id> / all, room:<id> or uid:<id>
+        #[arg(long)]
+        scope: String,
+        /// 消息内容：JSON，或作为 {"text": ...} 的纯文本 / Content: JSON, or plain text sent as {"text": ...}
+        #[arg(long)]
+        content: String,
+        /// 为离线用户写入离线队列 / Queue offline for users who are not online
+        #[arg(long)]
+        offline: bool,
+        /// 服务地址，缺省取 server.host 与 server.http_port / Server URL, defaults to server.host and server.http_port
+        #[arg(long)]
+        url: Option<String>,
+    },
+    /// 配置文件工具 / Config file tools
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// 通过运行中服务的管理接口转储或恢复节点状态 / Dump or restore node state through a running server's admin API
+    State {
+        #[command(subcommand)]
+        action: StateAction,
+        /// 服务地址，缺省取 server.host 与 server.http_port / Server URL, defaults to server.host and server.http_port
+        #[arg(long, global = true)]
+        url: Option<String>,
+    },
+}
+
+/// 状态子命令；路径相对于服务端的 `server.state_dump_dir` / State subcommands; paths are relative to the server's `server.state_dump_dir`
+#[derive(clap::Subcommand, Debug)]
+enum StateAction {
+    /// 把节点状态写成单个归档 / Write the node's state to a single archive
+    Dump {
+        /// 归档路径（相对路径，不含 `..`）/ Archive path (relative, without `..`)
+        path: String,
+    },
+    /// 把归档装入新节点 / Load an archive into a fresh node
+    Restore {
+        /// 归档路径（相对路径，不含 `..`）/ Archive path (relative, without `..`)
+        path: String,
+    },
+}
+
+/// 配置子命令 / Config subcommands
+#[derive(clap::Subcommand, Debug)]
+enum ConfigAction {
+    /// 检查配置文件，同 --check-config / Check a config file, same as --check-config
+    Check {
+        /// 配置文件路径 / Config file path
+        path: String,
+    },
+}
+
+/// 检查配置文件并打印报告，有错误时以非零码退出 / Check a config file, print the report and exit non-zero on errors
+fn check_config_and_exit(path: &str) -> ! {
+    let report = config_check::check_file(path);
+    println!("{}", report.render());
+    std::process::exit(if report.is_ok() { 0 } else { 1 })
+}
+
+/// 执行运维子命令 / Run an operator subcommand
+async fn run_command(command: Command, default_url: String) -> Result<()> {
+    match command {
+        Command::SystemMessage {
+            scope,
+            content,
+            offline,
+            url,
+        } => {
+            scope.parse::<service::system_message::SystemScope>()?;
+            let content = serde_json::from_str(&content)
+                .unwrap_or_else(|_| serde_json::json!({ "text": content }));
+            post_admin(
+                &url.unwrap_or(default_url),
+                "/v1/admin/system/message",
+                serde_json::json!({"scope": scope, "content": content, "offline": offline}),
+            )
+            .await
+        }
+        Command::State { action, url } => {
+            let (route, path) = match action {
+                StateAction::Dump { path } => ("/v1/admin/state/dump", path),
+                StateAction::Restore { path } => ("/v1/admin/state/restore", path),
+            };
+            post_admin(
+                &url.unwrap_or(default_url),
+                route,
+                serde_json::json!({ "path": path }),
+            )
+            .await
+        }
+        // 在加载配置之前已处理 / Handled before the config is loaded
+        Command::Config { .. } => unreachable!("config subcommands run before boot"),
+    }
+}
+
+/// 向管理接口 POST 并打印响应 / POST to an admin endpoint and print the response
+async fn post_admin(base_url: &str, route: &str, body: serde_json::Value) -> Result<()> {
+    let endpoint = format!("{}{}", base_url.trim_end_matches('/'), route);
+    let mut req = reqwest::Client::new().post(&endpoint).json(&body);
+    // 配置了管理员令牌时携带 / Send the admin token when one is configured
+    let admin_token: String = v::get_global_config_manager()
+        .map(|cm| cm.get_or("server.admin_token", String::new()))
+        .unwrap_or_default();
+    if !admin_token.is_empty() {
+        req = req.header("X-Admin-Token", admin_token);
+    }
+    let resp = req.send().await?;
+    let status = resp.status();
+    let body = resp.text().await?;
+    if !status.is_success() {
+        anyhow::bail!("{} {}: {}", endpoint, status, body);
+    }
+    println!("{}", body);
+    Ok(())
 }
 
 // 已通过 pub use 导入作用域 / imported via pub use above
@@ -66,6 +206,15 @@ impl VConnectIMServer {
             return false;
         }
         let now_ms = chrono::Utc::now().timestamp_millis();
+        // 显式设置的 uid 限流优先，其次是租户或全局配置 / Explicit per-uid limits win over tenant or global config
+        if !self.uid_rate_limits.contains_key(uid) {
+            let limit = self.tenants.msg_per_sec(uid);
+            if limit == 0 {
+                return true;
+            }
+            self.uid_rate_limits
+                .insert(uid.to_string(), (limit, 0, now_ms));
+        }
         if let Some(mut entry) = self.uid_rate_limits.get_mut(uid) {
             let (limit, count, window_start) = *entry;
             if now_ms - window_start >= 1000 {
@@ -85,6 +234,13 @@ impl VConnectIMServer {
 
     /// HTTP广播消息给所有客户端 / HTTP Broadcast message to all clients
     async fn http_broadcast_message(&self, request: HttpBroadcastRequest) -> HttpBroadcastResponse {
+        if self.maintenance.is_enabled() {
+            return HttpBroadcastResponse {
+                success: false,
+                message: crate::service::maintenance::MAINTENANCE_MESSAGE.to_string(),
+                delivered_count: 0,
+            };
+        }
         let wk_msg = ImMessage {
             msg_type: request
                 .message_type
@@ -95,6 +251,8 @@ impl VConnectIMServer {
                 "timestamp": chrono::Utc::now().timestamp_millis()
             }),
             target_uid: None,
+            priority: Default::default(),
+            reply_to: None,
         };
 
         let broadcast_json = match serde_json::to_string(&wk_msg) {
@@ -131,7 +289,7 @@ impl VConnectIMServer {
 
         // 清理失败的连接 / Clean up failed connections
         for client_id in failed_clients {
-            self.connections.remove(&client_id);
+            self.remove_connection(&client_id);
         }
 
         info!(
@@ -154,10 +312,28 @@ impl VConnectIMServer {
         from_client_id: String,
         content: serde_json::Value,
         message_type: Option<String>,
-    ) -> HttpBroadcastResponse {
+    ) -> HttpGroupSendResponse {
+        if self.maintenance.is_enabled() {
+            return HttpGroupSendResponse {
+                success: false,
+                status: GroupDeliveryStatus::Rejected,
+                message: crate::service::maintenance::MAINTENANCE_MESSAGE.to_string(),
+                message_id: None,
+                delivered_count: 0,
+                failed_count: 0,
+                offline_count: 0,
+                job_id: None,
+            };
+        }
         let msg_type = message_type.unwrap_or_else(|| "http_group".to_string());
-        let message_id = Uuid::new_v4().to_string();
+        let message_id = self.id_gen.next_str();
         let timestamp = chrono::Utc::now().timestamp_millis();
+        let attachment = match self.check_attachment(&content) {
+            Ok(a) => a,
+            Err(reason) => {
+                return HttpGroupSendResponse::rejected(message_id, reason)
+            }
+        };
 
         // 调用插件系统处理群组消息 / Call plugin system to process group message
         if let Some(pool) = self.plugin_connection_pool.as_ref() {
@@ -177,19 +353,16 @@ impl VConnectIMServer {
                         responses
                     );
                     // 检查是否有插件要求停止消息传播 / Check if any plugin wants to stop propagation
-                    for (_plugin_name, response) in responses {
-                        if let Some(flow) = response.get("flow").and_then(|v| v.as_str()) {
-                            if flow == "stop" {
-                                tracing::info!(
-                                    "群组消息被插件拦截 / Group message stopped by plugin"
-                                );
-                                return HttpBroadcastResponse {
-                                    success: false,
-                                    message: "Group message blocked by plugin".to_string(),
-                                    delivered_count: 0,
-                                };
-                            }
-                        }
+                    if let Some(rejection) =
+                        crate::domain::message::PluginRejection::find(&responses)
+                    {
+                        tracing::info!(
+                            "群组消息被插件 {} 拦截 / Group message stopped by plugin {}: {}",
+                            rejection.plugin,
+                            rejection.plugin,
+                            rejection.reason
+                        );
+                        return HttpGroupSendResponse::rejected(message_id, rejection.reason);
                     }
                 }
                 Err(e) => {
@@ -208,15 +381,16 @@ impl VConnectIMServer {
                 "message_id": message_id
             }),
             target_uid: None,
+            priority: Default::default(),
+            reply_to: None,
         };
         let forward_json = match serde_json::to_string(&forward_msg) {
             Ok(s) => s,
             Err(e) => {
-                return HttpBroadcastResponse {
-                    success: false,
-                    message: format!("Failed to serialize group message: {}", e),
-                    delivered_count: 0,
-                }
+                return HttpGroupSendResponse::rejected(
+                    message_id,
+                    format!("Failed to serialize group message: {}", e),
+                )
             }
         };
 
@@ -228,72 +402,73 @@ impl VConnectIMServer {
             timestamp: chrono::Utc::now().timestamp_millis(),
             msg_type: "group_message".to_string(),
             room_id: Some(room_id.clone()),
+            attachment,
+            reply_to: None,
         };
-        let _ = self.raft.append_entry_as(&self.node_id, &record);
-        // storage.append 已移除，使用插件 / storage.append removed, use plugin
-
-        let mut delivered_count = 0usize;
-        let mut offline_uids: Vec<String> = Vec::new();
-        if let Some(set) = self.rooms.get(&room_id) {
-            for uid in set.iter() {
-                let uid = uid.clone();
-                let clients_opt = self.uid_clients.get(&uid);
-                if let Some(clients) = clients_opt {
-                    for cid in clients.iter() {
-                        let cid = cid.clone();
-                        let delivery = if let Some(loc_node) = self.directory.locate_client(&cid) {
-                            if loc_node != self.node_id {
-                                if let Some(remote) = self.directory.get_server(&loc_node) {
-                                    remote
-                                        .send_message_to_client(
-                                            &cid,
-                                            Message::Text(forward_json.clone()),
-                                        )
-                                        .await
-                                } else {
-                                    Err(anyhow::anyhow!("remote node not found"))
-                                }
-                            } else {
-                                self.send_message_to_client(
-                                    &cid,
-                                    Message::Text(forward_json.clone()),
-                                )
-                                .await
-                            }
-                        } else {
-                            self.send_message_to_client(&cid, Message::Text(forward_json.clone()))
-                                .await
-                        };
-                        if delivery.is_ok() {
-                            delivered_count += 1;
-                        }
-                    }
-                } else {
-                    offline_uids.push(uid);
-                }
-            }
-        }
-        for ou in offline_uids {
-            let _ = self.enforce_offline_quota_for_uid(&ou).await;
-            let _off = storage::OfflineRecord {
-                message_id: message_id.clone(),
-                from_uid: self
-                    .connections
-                    .get(&from_client_id)
-                    .and_then(|c| c.uid.clone()),
-                to_uid: ou,
-                room_id: Some(room_id.clone()),
-                content: content.clone(),
-                timestamp: chrono::Utc::now().timestamp_millis(),
-                msg_type: msg_type.clone(),
+        // 先持久化并复制，失败则不投递给任何成员 / Persist and replicate first; on failure nobody is delivered to
+        let policy = self.persistence.policy(&msg_type);
+        if let Err(e) = self.persist_group_message(&record, policy).await {
+            tracing::error!("❌ 群消息未持久化 / Group message not persisted: {}", e);
+            return HttpGroupSendResponse {
+                success: false,
+                status: GroupDeliveryStatus::NotPersisted,
+                message: e.to_string(),
+                message_id: Some(message_id),
+                delivered_count: 0,
+                failed_count: 0,
+                offline_count: 0,
+                job_id: None,
             };
-            // storage.store_offline 已移除，使用插件 / storage.store_offline removed, use plugin
         }
 
-        HttpBroadcastResponse {
-            success: delivered_count > 0,
-            message: format!("Group message delivered to {} clients", delivered_count),
-            delivered_count,
+        let from_uid = self
+            .connections
+            .get(&from_client_id)
+            .and_then(|c| c.uid.clone());
+        let offline = storage::OfflineRecord {
+            message_id: message_id.clone(),
+            from_uid,
+            to_uid: String::new(),
+            room_id: Some(room_id.clone()),
+            content: content.clone(),
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            msg_type: msg_type.clone(),
+            priority: Default::default(),
+        };
+        let fan_out = match self
+            .deliver_to_room(&room_id, forward_json, false, offline)
+            .await
+        {
+            RoomFanOut::Done(fan_out) => fan_out,
+            RoomFanOut::Background(job_id) => {
+                return HttpGroupSendResponse {
+                    success: true,
+                    status: GroupDeliveryStatus::Accepted,
+                    message: format!(
+                        "Group message fan-out running in the background, poll /v1/room/fanout?job_id={}",
+                        job_id
+                    ),
+                    message_id: Some(message_id),
+                    delivered_count: 0,
+                    failed_count: 0,
+                    offline_count: 0,
+                    job_id: Some(job_id),
+                }
+            }
+        };
+
+        HttpGroupSendResponse {
+            success: true,
+            status: fan_out.status(),
+            message: format!(
+                "Group message delivered to {} clients ({} failed)",
+                fan_out.delivered, fan_out.failed
+            ),
+            message_id: Some(message_id),
+            delivered_count: fan_out.delivered,
+            failed_count: fan_out.failed,
+            offline_count: fan_out.offline_uids.len(),
+            job_id: None,
         }
     }
 
@@ -351,8 +526,9 @@ impl VConnectIMServer {
                 error!("Failed to send close message to {}: {}", client_id, e);
             }
 
-            self.connections.remove(&client_id);
-            info!("🧹 Cleaned up timeout connection: {}", client_id);
+            if let Some(conn) = self.remove_connection(&client_id) {
+                self.log_connection_cleanup(DropReason::Timeout, &client_id, conn.uid.as_deref());
+            }
         }
     }
 
@@ -366,6 +542,25 @@ impl VConnectIMServer {
     ) -> Result<()> {
         // 自动更新心跳时间 / Automatically update heartbeat time
         self.update_heartbeat(client_id).await;
+        let received_at = std::time::Instant::now();
+
+        // 按协商的编码还原为 JSON 文本帧 / Turn the frame back into JSON text per the negotiated encoding
+        let format = self
+            .connections
+            .get(client_id)
+            .map(|c| c.wire_format)
+            .unwrap_or_default();
+        let message = match format.decode(message) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!("⚠️  Invalid {:?} frame from {}: {}", format, client_id, e);
+                let error_msg = ImMessage::error(ErrorCode::InvalidFrame, "Invalid binary frame");
+                let error_json = serde_json::to_string(&error_msg)?;
+                self.send_message_to_client(client_id, Message::Text(error_json))
+                    .await?;
+                return Ok(());
+            }
+        };
 
         match message {
             Message::Text(text) => {
@@ -374,6 +569,22 @@ impl VConnectIMServer {
                 // 尝试解析为JSON消息
                 match serde_json::from_str::<ImMessage>(&text) {
                     Ok(mut wk_msg) => {
+                        self.metrics.record_received(&wk_msg.msg_type);
+                        // ping 只说明连接存活，不算活动 / A ping only proves liveness and does not count as activity
+                        if wk_msg.msg_type != "ping" {
+                            self.touch_last_sent(client_id);
+                        }
+                        // 维护期间拒绝写类消息，读取照常 / Reject writes during maintenance, reads carry on
+                        if self.maintenance.rejects(&wk_msg.msg_type) {
+                            let err = ImMessage::error(
+                                ErrorCode::MaintenanceMode,
+                                crate::service::maintenance::MAINTENANCE_MESSAGE,
+                            );
+                            let txt = serde_json::to_string(&err)?;
+                            self.send_message_to_client(client_id, Message::Text(txt))
+                                .await?;
+                            return Ok(());
+                        }
                         let ctx = PluginContext::new(self, client_id);
                         match self.plugin_registry.emit_incoming(&ctx, &mut wk_msg).await {
                             Ok(PluginFlow::Continue) => {}
@@ -386,6 +597,25 @@ impl VConnectIMServer {
                                 return Err(e);
                             }
                         }
+                        // 校验消息附件 / Validate message attachment
+                        let attachment = match wk_msg.msg_type.as_str() {
+                            "message" | "private_message" | "group_message" => {
+                                match self.check_attachment(&wk_msg.data) {
+                                    Ok(a) => a,
+                                    Err(reason) => {
+                                        let err =
+                                            ImMessage::error(ErrorCode::InvalidAttachment, reason);
+                                        let txt = serde_json::to_string(&err)?;
+                                        self.send_message_to_client(client_id, Message::Text(txt))
+                                            .await?;
+                                        return Ok(());
+                                    }
+                                }
+                            }
+                            _ => None,
+                        };
+                        // 空的 reply_to 视为非回复 / An empty reply_to is not a reply
+                        wk_msg.reply_to = wk_msg.reply_to.take().filter(|id| !id.is_empty());
                         match wk_msg.msg_type.as_str() {
                             "ping" => {
                                 debug!("🏓 Ping from {}", client_id);
@@ -399,6 +629,8 @@ impl VConnectIMServer {
                                         "client_id": client_id
                                     }),
                                     target_uid: None,
+                                    priority: Default::default(),
+                                    reply_to: None,
                                 };
                                 let pong_json = serde_json::to_string(&pong_msg)?;
                                 self.send_message_to_client(client_id, Message::Text(pong_json))
@@ -411,6 +643,8 @@ impl VConnectIMServer {
                                     msg_type: "online_clients_response".to_string(),
                                     data: serde_json::json!(online_clients),
                                     target_uid: None,
+                                    priority: Default::default(),
+                                    reply_to: None,
                                 };
                                 let response_json = serde_json::to_string(&response_msg)?;
                                 self.send_message_to_client(
@@ -431,6 +665,25 @@ impl VConnectIMServer {
                                     .get("uid")
                                     .and_then(|v| v.as_str())
                                     .map(|s| s.to_string());
+                                // 设备ID：沿用客户端提供的值，否则以连接ID作为设备ID
+                                // Device ID: the client's value if given, else the connection id
+                                let device_id = wk_msg
+                                    .data
+                                    .get("device_id")
+                                    .and_then(|v| v.as_str())
+                                    .filter(|d| {
+                                        !d.is_empty()
+                                            && d.len() <= crate::service::device_sync::MAX_DEVICE_ID_LEN
+                                    })
+                                    .unwrap_or(client_id)
+                                    .to_string();
+                                // 惩罚期或封禁中不校验令牌 / No token validation while locked or banned
+                                if let Err(refusal) =
+                                    self.guard_auth(client_id, uid_opt.as_deref())
+                                {
+                                    self.refuse_auth(client_id, refusal).await?;
+                                    return Ok(());
+                                }
 
                                 // 优先通过认证插件验证 / Prefer validation via auth plugin
                                 let is_valid = if let Some(pool) =
@@ -490,10 +743,32 @@ impl VConnectIMServer {
                                     // 没有插件系统，使用本地验证 / No plugin system, use local validation
                                     self.validate_token(token).await.unwrap_or(false)
                                 };
+                                if let Some(banned) =
+                                    self.record_auth_result(client_id, uid_opt.as_deref(), is_valid)
+                                {
+                                    self.refuse_auth(client_id, banned).await?;
+                                    return Ok(());
+                                }
+                                // 签发断线重连令牌 / Issue a reconnection token
+                                let resume_token = uid_opt
+                                    .as_deref()
+                                    .filter(|_| {
+                                        is_valid
+                                            && !crate::service::resume::resume_ttl().is_zero()
+                                    })
+                                    .map(|uid| {
+                                        self.resume_tokens.issue(client_id, uid, Some(&device_id))
+                                    });
                                 let auth_response = ImMessage {
                                     msg_type: "auth_response".to_string(),
-                                    data: serde_json::json!({ "status": if is_valid {"success"} else {"failed"}, "message": if is_valid {"Authentication successful"} else {"Authentication failed"} }),
+                                    data: if is_valid {
+                                        serde_json::json!({ "status": "success", "message": "Authentication successful", "device_id": device_id, "resume_token": resume_token, "capabilities": self.ack_windows.policy.capabilities() })
+                                    } else {
+                                        serde_json::json!({ "status": "failed", "message": "Authentication failed" })
+                                    },
                                     target_uid: None,
+                                    priority: Default::default(),
+                                    reply_to: None,
                                 };
                                 let auth_json = serde_json::to_string(&auth_response)?;
                                 self.send_message_to_client(client_id, Message::Text(auth_json))
@@ -501,27 +776,119 @@ impl VConnectIMServer {
                                 if is_valid {
                                     if let Some(uid_val) = uid_opt {
                                         // 直接设置 UID / Directly set UID
-                                        if let Some(mut conn) = self.connections.get_mut(client_id)
-                                        {
-                                            conn.uid = Some(uid_val.clone());
+                                        let previous_uid = self
+                                            .connections
+                                            .get_mut(client_id)
+                                            .and_then(|mut conn| {
+                                                conn.device_id = Some(device_id.clone());
+                                                conn.uid.replace(uid_val.clone())
+                                            });
+                                        if let Some(prev) = previous_uid.filter(|p| p != &uid_val) {
+                                            if let Some(set) = self.uid_clients.get(&prev) {
+                                                set.remove(client_id);
+                                            }
+                                        }
+                                        // 同一 uid 的多台设备均接收消息 / Every device of the uid receives messages
+                                        self.uid_clients
+                                            .entry(uid_val.clone())
+                                            .or_default()
+                                            .insert(client_id.to_string());
+                                        // 认证时携带的连接元数据（如 locale、设备类型）/ Connection metadata sent with auth (e.g. locale, device type)
+                                        if let (Some(meta), Some(conn)) = (
+                                            wk_msg.data.get("meta").and_then(|m| m.as_object()),
+                                            self.connections.get(client_id),
+                                        ) {
+                                            if let Some(tenant) = meta
+                                                .get(self.tenants.meta_key())
+                                                .and_then(|t| t.as_str())
+                                            {
+                                                self.tenants.assign(&uid_val, tenant);
+                                            }
+                                            for (key, value) in meta {
+                                                if !conn.set_meta(key.clone(), value.clone()) {
+                                                    warn!("metadata limit reached for {}", client_id);
+                                                    break;
+                                                }
+                                            }
                                         }
                                         // 触发认证成功事件 / Emit connection authenticated event
                                         let auth_event = serde_json::json!({
                                             "client_id": client_id,
                                             "uid": uid_val,
+                                            "device_id": device_id,
                                             "timestamp": chrono::Utc::now().timestamp_millis(),
                                         });
-                                        if let Err(e) = self
-                                            .plugin_registry
-                                            .emit_custom("connection.authenticated", &auth_event)
-                                            .await
-                                        {
-                                            warn!(
-                                                "plugin connection.authenticated event error: {}",
-                                                e
-                                            );
+                                        self.event_bus.publish(
+                                            crate::service::event_bus::CONNECTION_AUTHENTICATED,
+                                            auth_event,
+                                        );
+                                        // 后台按限速补发离线消息 / Replay offline messages in the background, paced
+                                        if self.plugin_connection_pool.is_some() {
+                                            let server = self.clone();
+                                            let cid = client_id.to_string();
+                                            tokio::spawn(async move {
+                                                if let Err(e) = server
+                                                    .deliver_offline_for_uid(&uid_val, &cid)
+                                                    .await
+                                                {
+                                                    warn!("offline replay for {} failed: {}", uid_val, e);
+                                                }
+                                            });
+                                        }
+                                    }
+                                }
+                            }
+                            "resume" => {
+                                // 以重连令牌恢复会话，失败时客户端应重新 auth
+                                // Resume a session with a reconnection token; on failure the client re-auths
+                                let uid = wk_msg.data.get("uid").and_then(|v| v.as_str());
+                                let token = wk_msg.data.get("token").and_then(|v| v.as_str());
+                                let resumed = match (uid, token) {
+                                    (Some(uid), Some(token)) => {
+                                        self.resume_session(client_id, uid, token).await
+                                    }
+                                    _ => None,
+                                };
+                                match (resumed, uid) {
+                                    (Some(_), Some(uid)) => {
+                                        let resume_event = serde_json::json!({
+                                            "client_id": client_id,
+                                            "uid": uid,
+                                            "resumed": true,
+                                            "timestamp": chrono::Utc::now().timestamp_millis(),
+                                        });
+                                        self.event_bus.publish(
+                                            crate::service::event_bus::CONNECTION_AUTHENTICATED,
+                                            resume_event,
+                                        );
+                                        if self.plugin_connection_pool.is_some() {
+                                            let server = self.clone();
+                                            let cid = client_id.to_string();
+                                            let uid = uid.to_string();
+                                            tokio::spawn(async move {
+                                                if let Err(e) =
+                                                    server.deliver_offline_for_uid(&uid, &cid).await
+                                                {
+                                                    warn!("offline replay for {} failed: {}", uid, e);
+                                                }
+                                            });
                                         }
                                     }
+                                    _ => {
+                                        let failed = ImMessage {
+                                            msg_type: "resume_response".to_string(),
+                                            data: serde_json::json!({
+                                                "status": "failed",
+                                                "message": "invalid or expired resume token"
+                                            }),
+                                            target_uid: None,
+                                            priority: Default::default(),
+                                            reply_to: None,
+                                        };
+                                        let txt = serde_json::to_string(&failed)?;
+                                        self.send_message_to_client(client_id, Message::Text(txt))
+                                            .await?;
+                                    }
                                 }
                             }
                             "message" => {
@@ -529,7 +896,7 @@ impl VConnectIMServer {
 
                                 // 如果有目标ID，发送给指定客户端，否则回声
                                 if let Some(target_uid) = &wk_msg.target_uid {
-                                    let message_id = Uuid::new_v4().to_string();
+                                    let message_id = self.id_gen.next_str();
                                     let timestamp = chrono::Utc::now().timestamp_millis();
                                     let from_uid = self
                                         .connections
@@ -551,17 +918,22 @@ impl VConnectIMServer {
                                         match pool.broadcast_message_event(&plugin_message).await {
                                             Ok(responses) => {
                                                 tracing::debug!("插件处理WebSocket消息响应 / Plugin WebSocket message responses: {:?}", responses);
-                                                // 检查是否有插件要求停止消息传播 / Check if any plugin wants to stop propagation
-                                                for (_plugin_name, response) in responses {
-                                                    if let Some(flow) = response
-                                                        .get("flow")
-                                                        .and_then(|v| v.as_str())
-                                                    {
-                                                        if flow == "stop" {
-                                                            tracing::info!("WebSocket消息被插件拦截 / WebSocket message stopped by plugin");
-                                                            return Ok(());
-                                                        }
-                                                    }
+                                                // 插件拦截时把原因转告发送者 / Relay the reason to the sender when a plugin blocks the message
+                                                if let Some(rejection) =
+                                                    crate::domain::message::PluginRejection::find(&responses)
+                                                {
+                                                    tracing::info!(
+                                                        "WebSocket消息被插件 {} 拦截 / WebSocket message stopped by plugin {}: {}",
+                                                        rejection.plugin,
+                                                        rejection.plugin,
+                                                        rejection.reason
+                                                    );
+                                                    let rejected =
+                                                        ImMessage::message_rejected(&message_id, &rejection);
+                                                    let txt = serde_json::to_string(&rejected)?;
+                                                    self.send_message_to_client(client_id, Message::Text(txt))
+                                                        .await?;
+                                                    return Ok(());
                                                 }
                                             }
                                             Err(e) => {
@@ -576,9 +948,12 @@ impl VConnectIMServer {
                                             "from": from_uid,
                                             "content": wk_msg.data,
                                             "timestamp": timestamp,
-                                            "message_id": message_id
+                                            "message_id": message_id,
+                                            "ack_deadline_ms": self.ack_windows.policy.deadline_ms
                                         }),
                                         target_uid: None,
+                                        priority: wk_msg.priority,
+                                        reply_to: wk_msg.reply_to.clone(),
                                     };
                                     let forward_json = serde_json::to_string(&forward_msg)?;
                                     let record = storage::MessageRecord {
@@ -589,22 +964,39 @@ impl VConnectIMServer {
                                         timestamp: chrono::Utc::now().timestamp_millis(),
                                         msg_type: "message".to_string(),
                                         room_id: None,
+                                        attachment: attachment.clone(),
+                                        reply_to: wk_msg.reply_to.clone(),
                                     };
-                                    self.raft.append_entry_as(&self.node_id, &record)?;
+                                    let policy = self.persistence.policy(&record.msg_type);
 
                                     // 保存消息到存储插件 / Save message to storage plugin
-                                    if let Some(pool) = self.plugin_connection_pool.as_ref() {
-                                        let _ = pool
-                                            .storage_save_message(
-                                                &record.message_id,
-                                                &record.from_client_id,
-                                                &record.to_client_id,
-                                                &record.content,
-                                                record.timestamp,
-                                                &record.msg_type,
-                                                None,
+                                    if let Some(pool) = self
+                                        .plugin_connection_pool
+                                        .as_ref()
+                                        .filter(|_| policy.persist)
+                                    {
+                                        let saved = pool.storage_save_record(&record).await;
+                                        if let Err(e) = &saved {
+                                            // 仅 `storage.on_unavailable = fail` 时出错，拒绝发送
+                                            // Only errors under `storage.on_unavailable = fail`; reject the send
+                                            let err = ImMessage::error(
+                                                ErrorCode::StorageUnavailable,
+                                                e.to_string(),
+                                            );
+                                            let txt = serde_json::to_string(&err)?;
+                                            self.send_message_to_client(
+                                                client_id,
+                                                Message::Text(txt),
                                             )
-                                            .await;
+                                            .await?;
+                                            return Ok(());
+                                        }
+                                        if policy.flush && matches!(saved, Ok(true)) {
+                                            let _ = pool.storage_flush_now().await;
+                                        }
+                                    }
+                                    if policy.replicate {
+                                        self.replicate_record(&record).await?;
                                     }
 
                                     // 依据UID发送到所有在线客户端 / deliver to all clients of target uid
@@ -630,7 +1022,7 @@ impl VConnectIMServer {
                                             Err(anyhow::anyhow!("no clients"))
                                         }
                                     } else {
-                                        // 跨节点HTTP/RPC转发（最小版本）/ Cross-node forward via HTTP
+                                        // 跨节点转发（可按对端批量合并，见 cluster::rpc）/ Cross-node forward, optionally batched per peer (see cluster::rpc)
                                         let mut ok = false;
                                         if let Ok(cm) = v::get_global_config_manager() {
                                             let peers = cm
@@ -641,49 +1033,29 @@ impl VConnectIMServer {
                                                 .map(|s| s.trim())
                                                 .filter(|s| !s.is_empty())
                                             {
-                                                let list_url = format!(
-                                                    "{}/v1/internal/clients_by_uid?uid={}",
-                                                    base, target_uid
-                                                );
-                                                if let Ok(resp) = reqwest::get(&list_url).await {
-                                                    if resp.status().is_success() {
-                                                        if let Ok(val) =
-                                                            resp.json::<serde_json::Value>().await
-                                                        {
-                                                            let ids = val
-                                                                .get("client_ids")
-                                                                .and_then(|v| v.as_array())
-                                                                .cloned()
-                                                                .unwrap_or_default();
-                                                            if !ids.is_empty() {
-                                                                for idv in ids {
-                                                                    if let Some(cid) = idv.as_str()
-                                                                    {
-                                                                        let fwd_url = format!("{}/v1/internal/forward_client", base);
-                                                                        let body = serde_json::json!({"client_id": cid, "text": forward_json});
-                                                                        if let Ok(res2) =
-                                                                            reqwest::Client::new()
-                                                                                .post(&fwd_url)
-                                                                                .json(&body)
-                                                                                .send()
-                                                                                .await
-                                                                        {
-                                                                            if res2
-                                                                                .status()
-                                                                                .is_success()
-                                                                            {
-                                                                                ok = true;
-                                                                            }
-                                                                        }
-                                                                    }
-                                                                }
-                                                            }
-                                                        }
-                                                    }
-                                                }
+                                                let ids = self
+                                                    .internal_rpc
+                                                    .clients_by_uid(base, target_uid)
+                                                    .await;
+                                                // 同一 uid 的多个连接并发转发，可进入同一批 / Forward to all of the uid's connections concurrently so they can share a batch
+                                                let forwards = ids.iter().map(|cid| {
+                                                    self.internal_rpc.forward(base, cid, &forward_json)
+                                                });
+                                                ok = futures_util::future::join_all(forwards)
+                                                    .await
+                                                    .into_iter()
+                                                    .any(|delivered| delivered);
                                                 if ok {
                                                     break;
                                                 }
+                                                if !ids.is_empty() {
+                                                    self.log_delivery_drop(
+                                                        DeliveryDrop::new(DropReason::NodeUnreachable)
+                                                            .with_uid(Some(target_uid))
+                                                            .with_message(Some(&message_id))
+                                                            .with_node(base),
+                                                    );
+                                                }
                                             }
                                         }
                                         if ok {
@@ -695,15 +1067,22 @@ impl VConnectIMServer {
 
                                     match delivery_result {
                                         Ok(_) => {
+                                            self.metrics.record_delivered(
+                                                &wk_msg.msg_type,
+                                                received_at.elapsed(),
+                                            );
                                             // 同时给发送者确认
                                             let confirm_msg = ImMessage {
                                                 msg_type: "message_sent".to_string(),
                                                 data: serde_json::json!({
                                                     "to": target_uid,
                                                     "status": "delivered",
-                                                    "message_id": message_id
+                                                    "message_id": message_id,
+                                                    "ack_deadline_ms": self.ack_windows.policy.deadline_ms
                                                 }),
                                                 target_uid: None,
+                                                priority: Default::default(),
+                                                reply_to: None,
                                             };
                                             let confirm_json = serde_json::to_string(&confirm_msg)?;
                                             self.send_message_to_client(
@@ -711,24 +1090,37 @@ impl VConnectIMServer {
                                                 Message::Text(confirm_json),
                                             )
                                             .await?;
-                                            let deadline_ms = v::get_global_config_manager()
-                                                .ok()
-                                                .map(|cm| {
-                                                    cm.get_or("delivery.deadline_ms", 500_i64)
-                                                        as u64
-                                                })
-                                                .unwrap_or(500);
+                                            let deadline_ms = self.ack_windows.policy.deadline_ms;
                                             self.await_ack_or_queue_offline(
-                                                target_uid.clone(),
-                                                message_id.clone(),
-                                                None,
-                                                wk_msg.data.clone(),
-                                                "message".to_string(),
+                                                storage::OfflineRecord {
+                                                    message_id: message_id.clone(),
+                                                    from_uid: None,
+                                                    to_uid: target_uid.clone(),
+                                                    room_id: None,
+                                                    content: wk_msg.data.clone(),
+                                                    timestamp: chrono::Utc::now()
+                                                        .timestamp_millis(),
+                                                    msg_type: "message".to_string(),
+                                                    priority: wk_msg.priority,
+                                                },
                                                 deadline_ms,
                                             )
                                             .await;
                                         }
                                         Err(_e) => {
+                                            self.metrics.record_failed(&wk_msg.msg_type);
+                                            // 发送失败已在发送处记录 / Send failures were logged where they happened
+                                            if self
+                                                .uid_clients
+                                                .get(target_uid)
+                                                .is_none_or(|clients| clients.is_empty())
+                                            {
+                                                self.log_delivery_drop(
+                                                    DeliveryDrop::new(DropReason::Offline)
+                                                        .with_uid(Some(target_uid))
+                                                        .with_message(Some(&message_id)),
+                                                );
+                                            }
                                             return Ok(());
                                         }
                                     }
@@ -742,6 +1134,8 @@ impl VConnectIMServer {
                                             "timestamp": chrono::Utc::now().timestamp_millis()
                                         }),
                                         target_uid: None,
+                                        priority: Default::default(),
+                                        reply_to: None,
                                     };
                                     let echo_json = serde_json::to_string(&echo_msg)?;
                                     self.send_message_to_client(
@@ -755,26 +1149,35 @@ impl VConnectIMServer {
                                 // 私聊消息，必须有目标ID
                                 if let Some(target_uid) = &wk_msg.target_uid {
                                     if !self.allow_send_to_uid(target_uid) {
-                                        let err = ImMessage {
-                                            msg_type: "error".to_string(),
-                                            data: serde_json::json!({"message":"target uid blocked or rate limited"}),
-                                            target_uid: None,
+                                        let err = if self.blocked_uids.contains(target_uid) {
+                                            ImMessage::error(
+                                                ErrorCode::UidBlocked,
+                                                "target uid is blocked",
+                                            )
+                                        } else {
+                                            ImMessage::error(
+                                                ErrorCode::RateLimited,
+                                                "target uid is rate limited",
+                                            )
                                         };
                                         let txt = serde_json::to_string(&err)?;
                                         self.send_message_to_client(client_id, Message::Text(txt))
                                             .await?;
                                         return Ok(());
                                     }
-                                    let message_id = Uuid::new_v4().to_string();
+                                    let message_id = self.id_gen.next_str();
                                     let private_msg = ImMessage {
                                         msg_type: "private_message".to_string(),
                                         data: serde_json::json!({
                                             "from": self.connections.get(client_id).and_then(|c| c.uid.clone()).unwrap_or_default(),
                                             "content": wk_msg.data,
                                             "timestamp": chrono::Utc::now().timestamp_millis(),
-                                            "message_id": message_id
+                                            "message_id": message_id,
+                                            "ack_deadline_ms": self.ack_windows.policy.deadline_ms
                                         }),
                                         target_uid: None,
+                                        priority: wk_msg.priority,
+                                        reply_to: wk_msg.reply_to.clone(),
                                     };
                                     let private_json = serde_json::to_string(&private_msg)?;
                                     let record = storage::MessageRecord {
@@ -785,8 +1188,41 @@ impl VConnectIMServer {
                                         timestamp: chrono::Utc::now().timestamp_millis(),
                                         msg_type: "private_message".to_string(),
                                         room_id: None,
+                                        attachment: attachment.clone(),
+                                        reply_to: wk_msg.reply_to.clone(),
                                     };
-                                    self.raft.append_entry_as(&self.node_id, &record)?;
+                                    let policy = self.persistence.policy(&record.msg_type);
+
+                                    // 按策略保存私聊消息（含附件与回复引用）
+                                    // Save the private message, attachment and reply_to included, per policy
+                                    if let Some(pool) = self
+                                        .plugin_connection_pool
+                                        .as_ref()
+                                        .filter(|_| policy.persist)
+                                    {
+                                        let saved = pool.storage_save_record(&record).await;
+                                        if let Err(e) = &saved {
+                                            // 仅 `storage.on_unavailable = fail` 时出错，拒绝发送
+                                            // Only errors under `storage.on_unavailable = fail`; reject the send
+                                            let err = ImMessage::error(
+                                                ErrorCode::StorageUnavailable,
+                                                e.to_string(),
+                                            );
+                                            let txt = serde_json::to_string(&err)?;
+                                            self.send_message_to_client(
+                                                client_id,
+                                                Message::Text(txt),
+                                            )
+                                            .await?;
+                                            return Ok(());
+                                        }
+                                        if policy.flush && matches!(saved, Ok(true)) {
+                                            let _ = pool.storage_flush_now().await;
+                                        }
+                                    }
+                                    if policy.replicate {
+                                        self.replicate_record(&record).await?;
+                                    }
                                     let delivery_result = if let Some(clients) =
                                         self.uid_clients.get(target_uid)
                                     {
@@ -844,15 +1280,22 @@ impl VConnectIMServer {
 
                                     match delivery_result {
                                         Ok(_) => {
+                                            self.metrics.record_delivered(
+                                                &wk_msg.msg_type,
+                                                received_at.elapsed(),
+                                            );
                                             // 给发送者确认 / Confirm to sender
                                             let confirm_msg = ImMessage {
                                                 msg_type: "message_sent".to_string(),
                                                 data: serde_json::json!({
                                                     "to": target_uid,
                                                     "status": "delivered",
-                                                    "message_id": message_id
+                                                    "message_id": message_id,
+                                                    "ack_deadline_ms": self.ack_windows.policy.deadline_ms
                                                 }),
                                                 target_uid: None,
+                                                priority: Default::default(),
+                                                reply_to: None,
                                             };
                                             let confirm_json = serde_json::to_string(&confirm_msg)?;
                                             self.send_message_to_client(
@@ -860,35 +1303,45 @@ impl VConnectIMServer {
                                                 Message::Text(confirm_json),
                                             )
                                             .await?;
-                                            let deadline_ms = v::get_global_config_manager()
-                                                .ok()
-                                                .map(|cm| {
-                                                    cm.get_or("delivery.deadline_ms", 500_i64)
-                                                        as u64
-                                                })
-                                                .unwrap_or(500);
+                                            let deadline_ms = self.ack_windows.policy.deadline_ms;
                                             self.await_ack_or_queue_offline(
-                                                target_uid.clone(),
-                                                message_id.clone(),
-                                                None,
-                                                wk_msg.data.clone(),
-                                                "private_message".to_string(),
+                                                storage::OfflineRecord {
+                                                    message_id: message_id.clone(),
+                                                    from_uid: None,
+                                                    to_uid: target_uid.clone(),
+                                                    room_id: None,
+                                                    content: wk_msg.data.clone(),
+                                                    timestamp: chrono::Utc::now()
+                                                        .timestamp_millis(),
+                                                    msg_type: "private_message".to_string(),
+                                                    priority: wk_msg.priority,
+                                                },
                                                 deadline_ms,
                                             )
                                             .await;
                                         }
                                         Err(_e) => {
+                                            self.metrics.record_failed(&wk_msg.msg_type);
+                                            // 发送失败已在发送处记录 / Send failures were logged where they happened
+                                            if self
+                                                .uid_clients
+                                                .get(target_uid)
+                                                .is_none_or(|clients| clients.is_empty())
+                                            {
+                                                self.log_delivery_drop(
+                                                    DeliveryDrop::new(DropReason::Offline)
+                                                        .with_uid(Some(target_uid))
+                                                        .with_message(Some(&message_id)),
+                                                );
+                                            }
                                             return Ok(());
                                         }
                                     }
                                 } else {
-                                    let error_msg = ImMessage {
-                                        msg_type: "error".to_string(),
-                                        data: serde_json::json!({
-                                            "message": "private_message requires target_id"
-                                        }),
-                                        target_uid: None,
-                                    };
+                                    let error_msg = ImMessage::error(
+                                        ErrorCode::MissingTarget,
+                                        "private_message requires target_id",
+                                    );
                                     let error_json = serde_json::to_string(&error_msg)?;
                                     self.send_message_to_client(
                                         client_id,
@@ -903,7 +1356,12 @@ impl VConnectIMServer {
                                 {
                                     let uid_opt =
                                         self.connections.get(client_id).and_then(|c| c.uid.clone());
-                                    if let Some(uid) = uid_opt {
+                                    let refused = uid_opt
+                                        .as_deref()
+                                        .and_then(|uid| self.check_room_op(uid, room_id, true).err());
+                                    if let Some(e) = refused {
+                                        self.send_room_op_error(client_id, room_id, e).await?;
+                                    } else if let Some(uid) = uid_opt {
                                         let set =
                                             self.rooms.entry(room_id.to_string()).or_default();
                                         set.insert(uid.clone());
@@ -913,16 +1371,17 @@ impl VConnectIMServer {
                                             msg_type: "join_room_ok".to_string(),
                                             data: serde_json::json!({"room_id": room_id}),
                                             target_uid: None,
+                                            priority: Default::default(),
+                                            reply_to: None,
                                         };
                                         let txt = serde_json::to_string(&resp)?;
                                         self.send_message_to_client(client_id, Message::Text(txt))
                                             .await?;
                                     } else {
-                                        let err = ImMessage {
-                                            msg_type: "error".to_string(),
-                                            data: serde_json::json!({"message":"join_room requires auth uid"}),
-                                            target_uid: None,
-                                        };
+                                        let err = ImMessage::error(
+                                            ErrorCode::Unauthenticated,
+                                            "join_room requires auth uid",
+                                        );
                                         let txt = serde_json::to_string(&err)?;
                                         self.send_message_to_client(client_id, Message::Text(txt))
                                             .await?;
@@ -933,9 +1392,14 @@ impl VConnectIMServer {
                                 if let Some(room_id) =
                                     wk_msg.data.get("room_id").and_then(|v| v.as_str())
                                 {
-                                    if let Some(uid) =
-                                        self.connections.get(client_id).and_then(|c| c.uid.clone())
-                                    {
+                                    let uid_opt =
+                                        self.connections.get(client_id).and_then(|c| c.uid.clone());
+                                    let refused = uid_opt
+                                        .as_deref()
+                                        .and_then(|uid| self.check_room_op(uid, room_id, false).err());
+                                    if let Some(e) = refused {
+                                        self.send_room_op_error(client_id, room_id, e).await?;
+                                    } else if let Some(uid) = uid_opt {
                                         if let Some(set) = self.rooms.get_mut(room_id) {
                                             set.remove(&uid);
                                         }
@@ -945,6 +1409,8 @@ impl VConnectIMServer {
                                             msg_type: "leave_room_ok".to_string(),
                                             data: serde_json::json!({"room_id": room_id}),
                                             target_uid: None,
+                                            priority: Default::default(),
+                                            reply_to: None,
                                         };
                                         let txt = serde_json::to_string(&resp)?;
                                         self.send_message_to_client(client_id, Message::Text(txt))
@@ -952,6 +1418,15 @@ impl VConnectIMServer {
                                     }
                                 }
                             }
+                            "room_invite" | "room_kick" => {
+                                // 批量加入/移出成员，仅房间管理员可用 / Bulk add or remove members, for room admins only
+                                self.handle_room_membership(
+                                    client_id,
+                                    &wk_msg.data,
+                                    wk_msg.msg_type == "room_invite",
+                                )
+                                .await?;
+                            }
                             "group_message" => {
                                 let room_id_opt = wk_msg
                                     .data
@@ -959,7 +1434,35 @@ impl VConnectIMServer {
                                     .and_then(|v| v.as_str())
                                     .map(|s| s.to_string());
                                 if let Some(room_id) = room_id_opt {
-                                    let message_id = Uuid::new_v4().to_string();
+                                    let message_id = self.id_gen.next_str();
+                                    // 带 client_msg_id 的重试直接返回首次的确认 / Retries carrying a client_msg_id get the original confirmation
+                                    let sender = self
+                                        .connections
+                                        .get(client_id)
+                                        .and_then(|c| c.uid.clone())
+                                        .unwrap_or_else(|| client_id.to_string());
+                                    let dedup_key =
+                                        self.group_dedup.key(&sender, &room_id, &wk_msg.data);
+                                    if let Some(key) = &dedup_key {
+                                        if let DedupClaim::Duplicate(data) =
+                                            self.group_dedup.claim(key, &room_id, &message_id)
+                                        {
+                                            let confirm_msg = ImMessage {
+                                                msg_type: "group_message_sent".to_string(),
+                                                data,
+                                                target_uid: None,
+                                                priority: Default::default(),
+                                                reply_to: None,
+                                            };
+                                            let confirm_json = serde_json::to_string(&confirm_msg)?;
+                                            self.send_message_to_client(
+                                                client_id,
+                                                Message::Text(confirm_json),
+                                            )
+                                            .await?;
+                                            return Ok(());
+                                        }
+                                    }
                                     let forward_msg = ImMessage {
                                         msg_type: "group_message".to_string(),
                                         data: serde_json::json!({
@@ -970,6 +1473,8 @@ impl VConnectIMServer {
                                             "message_id": message_id
                                         }),
                                         target_uid: None,
+                                        priority: wk_msg.priority,
+                                        reply_to: wk_msg.reply_to.clone(),
                                     };
                                     let forward_json = serde_json::to_string(&forward_msg)?;
                                     let record = storage::MessageRecord {
@@ -980,165 +1485,107 @@ impl VConnectIMServer {
                                         timestamp: chrono::Utc::now().timestamp_millis(),
                                         msg_type: "group_message".to_string(),
                                         room_id: Some(room_id.clone()),
+                                        attachment: attachment.clone(),
+                                        reply_to: wk_msg.reply_to.clone(),
                                     };
-                                    self.raft.append_entry_as(&self.node_id, &record)?;
+                                    let policy = self.persistence.policy(&record.msg_type);
 
-                                    // 保存消息到存储插件 / Save message to storage plugin
-                                    if let Some(pool) = self.plugin_connection_pool.as_ref() {
-                                        let _ = pool
-                                            .storage_save_message(
-                                                &record.message_id,
-                                                &record.from_client_id,
-                                                &record.to_client_id,
-                                                &record.content,
-                                                record.timestamp,
-                                                &record.msg_type,
-                                                record.room_id.as_deref(),
-                                            )
-                                            .await;
+                                    // 先持久化并复制，失败则不投递给任何成员
+                                    // Persist and replicate first; on failure nobody is delivered to
+                                    if let Err(e) = self.persist_group_message(&record, policy).await
+                                    {
+                                        tracing::error!(
+                                            "❌ 群消息未持久化 / Group message not persisted: {}",
+                                            e
+                                        );
+                                        self.metrics.record_failed(&wk_msg.msg_type);
+                                        if let Some(key) = &dedup_key {
+                                            self.group_dedup.release(key);
+                                        }
+                                        let err = ImMessage::error(e.code(), e.to_string());
+                                        let txt = serde_json::to_string(&err)?;
+                                        self.send_message_to_client(client_id, Message::Text(txt))
+                                            .await?;
+                                        return Ok(());
                                     }
 
-                                    let mut delivered_count = 0usize;
-                                    let mut offline_uids: Vec<String> = Vec::new();
-                                    if let Some(set) = self.rooms.get(&room_id) {
-                                        for uid in set.iter() {
-                                            let uid = uid.clone();
-                                            if !self.allow_send_to_uid(&uid) {
-                                                continue;
-                                            }
-                                            let clients_opt = self.uid_clients.get(&uid);
-                                            if let Some(clients) = clients_opt {
-                                                for cid in clients.iter() {
-                                                    let cid = cid.clone();
-                                                    let delivery = if let Some(loc_node) =
-                                                        self.directory.locate_client(&cid)
-                                                    {
-                                                        if loc_node != self.node_id {
-                                                            if let Some(remote) =
-                                                                self.directory.get_server(&loc_node)
-                                                            {
-                                                                remote
-                                                                    .send_message_to_client(
-                                                                        &cid,
-                                                                        Message::Text(
-                                                                            forward_json.clone(),
-                                                                        ),
-                                                                    )
-                                                                    .await
-                                                            } else {
-                                                                Err(anyhow::anyhow!(
-                                                                    "remote node not found",
-                                                                ))
-                                                            }
-                                                        } else {
-                                                            self.send_message_to_client(
-                                                                &cid,
-                                                                Message::Text(forward_json.clone()),
-                                                            )
-                                                            .await
-                                                        }
-                                                    } else {
-                                                        self.send_message_to_client(
-                                                            &cid,
-                                                            Message::Text(forward_json.clone()),
-                                                        )
-                                                        .await
-                                                    };
-                                                    if delivery.is_ok() {
-                                                        delivered_count += 1;
-                                                    }
-                                                }
-                                            } else {
-                                                offline_uids.push(uid);
-                                            }
-                                        }
-                                    } else {
-                                        // 回退：从持久化成员列表加载并投递 / Fallback to persistent members
-                                        // storage.list_room_members 已移除 / storage.list_room_members removed
-                                        if false {
-                                            let members: Vec<String> = vec![];
-                                            for uid in members {
-                                                if !self.allow_send_to_uid(&uid) {
-                                                    continue;
-                                                }
-                                                let clients_opt = self.uid_clients.get(&uid);
-                                                if let Some(clients) = clients_opt {
-                                                    for cid in clients.iter() {
-                                                        let cid = cid.clone();
-                                                        let delivery = if let Some(loc_node) =
-                                                            self.directory.locate_client(&cid)
-                                                        {
-                                                            if loc_node != self.node_id {
-                                                                if let Some(remote) = self
-                                                                    .directory
-                                                                    .get_server(&loc_node)
-                                                                {
-                                                                    remote
-                                                                        .send_message_to_client(
-                                                                            &cid,
-                                                                            Message::Text(
-                                                                                forward_json
-                                                                                    .clone(),
-                                                                            ),
-                                                                        )
-                                                                        .await
-                                                                } else {
-                                                                    Err(anyhow::anyhow!(
-                                                                        "remote node not found",
-                                                                    ))
-                                                                }
-                                                            } else {
-                                                                self.send_message_to_client(
-                                                                    &cid,
-                                                                    Message::Text(
-                                                                        forward_json.clone(),
-                                                                    ),
-                                                                )
-                                                                .await
-                                                            }
-                                                        } else {
-                                                            self.send_message_to_client(
-                                                                &cid,
-                                                                Message::Text(forward_json.clone()),
-                                                            )
-                                                            .await
-                                                        };
-                                                        if delivery.is_ok() {
-                                                            delivered_count += 1;
-                                                        }
-                                                    }
-                                                } else {
-                                                    offline_uids.push(uid);
-                                                }
+                                    // 登记应确认的成员 / Register members expected to ack
+                                    let members: Option<Vec<String>> = self
+                                        .rooms
+                                        .get(&room_id)
+                                        .map(|set| set.iter().map(|u| u.clone()).collect());
+                                    if let Some(members) = members {
+                                        let sender_uid = self
+                                            .connections
+                                            .get(client_id)
+                                            .and_then(|c| c.uid.clone());
+                                        self.track_group_acks(
+                                            &message_id,
+                                            &room_id,
+                                            sender_uid.as_deref(),
+                                            members,
+                                        )
+                                        .await;
+                                    }
+
+                                    let offline = storage::OfflineRecord {
+                                        message_id: message_id.clone(),
+                                        from_uid: self
+                                            .connections
+                                            .get(client_id)
+                                            .and_then(|c| c.uid.clone()),
+                                        to_uid: String::new(),
+                                        room_id: Some(room_id.clone()),
+                                        content: wk_msg.data.clone(),
+                                        timestamp: chrono::Utc::now().timestamp_millis(),
+                                        msg_type: "group_message".to_string(),
+                                        priority: wk_msg.priority,
+                                    };
+                                    let data = match self
+                                        .deliver_to_room(&room_id, forward_json, true, offline)
+                                        .await
+                                    {
+                                        RoomFanOut::Done(fan_out) => {
+                                            let (delivered_count, failed_count) =
+                                                (fan_out.delivered, fan_out.failed);
+
+                                            // 至少一台设备收到即计为送达 / Delivered once at least one device received it
+                                            if delivered_count > 0 {
+                                                self.metrics.record_delivered(
+                                                    &wk_msg.msg_type,
+                                                    received_at.elapsed(),
+                                                );
+                                            } else if failed_count > 0 {
+                                                self.metrics.record_failed(&wk_msg.msg_type);
                                             }
+                                            serde_json::json!({
+                                                "room_id": room_id,
+                                                "status": fan_out.status(),
+                                                "delivered_count": delivered_count,
+                                                "failed_count": failed_count,
+                                                "message_id": message_id
+                                            })
                                         }
-                                    }
+                                        // 大房间在后台扇出 / Large rooms fan out in the background
+                                        RoomFanOut::Background(job_id) => serde_json::json!({
+                                            "room_id": room_id,
+                                            "status": GroupDeliveryStatus::Accepted,
+                                            "delivered_count": 0,
+                                            "failed_count": 0,
+                                            "message_id": message_id,
+                                            "job_id": job_id
+                                        }),
+                                    };
 
-                                    for ou in offline_uids {
-                                        let _ = self.enforce_offline_quota_for_uid(&ou).await;
-                                        let _off = storage::OfflineRecord {
-                                            message_id: message_id.clone(),
-                                            from_uid: self
-                                                .connections
-                                                .get(client_id)
-                                                .and_then(|c| c.uid.clone()),
-                                            to_uid: ou,
-                                            room_id: Some(room_id.clone()),
-                                            content: wk_msg.data.clone(),
-                                            timestamp: chrono::Utc::now().timestamp_millis(),
-                                            msg_type: "group_message".to_string(),
-                                        };
-                                        // storage.store_offline 已移除，使用插件 / storage.store_offline removed, use plugin
+                                    if let Some(key) = &dedup_key {
+                                        self.group_dedup.complete(key, &data);
                                     }
-
                                     let confirm_msg = ImMessage {
                                         msg_type: "group_message_sent".to_string(),
-                                        data: serde_json::json!({
-                                            "room_id": room_id,
-                                            "delivered_count": delivered_count,
-                                            "message_id": message_id
-                                        }),
+                                        data,
                                         target_uid: None,
+                                        priority: Default::default(),
+                                        reply_to: None,
                                     };
                                     let confirm_json = serde_json::to_string(&confirm_msg)?;
                                     self.send_message_to_client(
@@ -1147,13 +1594,10 @@ impl VConnectIMServer {
                                     )
                                     .await?;
                                 } else {
-                                    let error_msg = ImMessage {
-                                        msg_type: "error".to_string(),
-                                        data: serde_json::json!({
-                                            "message": "group_message requires room_id"
-                                        }),
-                                        target_uid: None,
-                                    };
+                                    let error_msg = ImMessage::error(
+                                        ErrorCode::MissingRoom,
+                                        "group_message requires room_id",
+                                    );
                                     let error_json = serde_json::to_string(&error_msg)?;
                                     self.send_message_to_client(
                                         client_id,
@@ -1163,34 +1607,179 @@ impl VConnectIMServer {
                                 }
                             }
                             "ack" => {
-                                // 客户端确认消息ID（按UID）/ Client acknowledges message ID (by uid)
+                                // 单个或批量确认（按UID）/ Single or batched acks (by uid)
+                                self.handle_ack(client_id, &wk_msg.data).await;
+                            }
+                            "read" => {
+                                // 设备已读，同步给同 uid 的其他设备 / Device read; synced to the uid's other devices
                                 if let Some(msg_id) =
                                     wk_msg.data.get("message_id").and_then(|v| v.as_str())
                                 {
-                                    let uid_key = self
-                                        .connections
-                                        .get(client_id)
-                                        .and_then(|c| c.uid.clone())
-                                        .unwrap_or_default();
-                                    if !uid_key.is_empty() {
-                                        let set = self.acked_ids.entry(uid_key).or_default();
-                                        set.insert(msg_id.to_string());
-                                        debug!("✅ Ack received from uid for {}", msg_id);
+                                    if self
+                                        .record_device_state(client_id, msg_id, DeliveryState::Read)
+                                        .await
+                                    {
+                                        let uid = self
+                                            .connections
+                                            .get(client_id)
+                                            .and_then(|c| c.uid.clone());
+                                        if let (Some(uid), Some(pool)) =
+                                            (uid, self.plugin_connection_pool.as_ref())
+                                        {
+                                            let ts = chrono::Utc::now().timestamp_millis();
+                                            let _ = pool.storage_record_read(&uid, msg_id, ts).await;
+                                        }
                                     }
                                 }
                             }
+                            "device_states" => {
+                                // 查询消息在本用户各设备上的状态 / Query a message's state on this user's devices
+                                let msg_id = wk_msg
+                                    .data
+                                    .get("message_id")
+                                    .and_then(|v| v.as_str())
+                                    .unwrap_or_default();
+                                let resp = match self
+                                    .connections
+                                    .get(client_id)
+                                    .and_then(|c| c.uid.clone())
+                                {
+                                    Some(uid) => ImMessage {
+                                        msg_type: "device_states".to_string(),
+                                        data: serde_json::json!({
+                                            "message_id": msg_id,
+                                            "devices": self.device_states(&uid, msg_id),
+                                        }),
+                                        target_uid: None,
+                                        priority: Default::default(),
+                                        reply_to: None,
+                                    },
+                                    None => ImMessage::error(
+                                        ErrorCode::Unauthenticated,
+                                        "device_states requires auth uid",
+                                    ),
+                                };
+                                let txt = serde_json::to_string(&resp)?;
+                                self.send_message_to_client(client_id, Message::Text(txt))
+                                    .await?;
+                            }
+                            "schedule_message" => {
+                                // 定时消息，到期后由后台任务投递 / Scheduled message, delivered by the background task when due
+                                self.handle_schedule_message(client_id, &wk_msg).await?;
+                            }
+                            "cancel_scheduled" => {
+                                self.handle_cancel_scheduled(client_id, &wk_msg.data).await?;
+                            }
+                            "pin_message" | "unpin_message" => {
+                                // 置顶消息，仅房间管理员可用 / Pinned messages, for room admins only
+                                self.handle_pin(
+                                    client_id,
+                                    &wk_msg.data,
+                                    wk_msg.msg_type == "pin_message",
+                                )
+                                .await?;
+                            }
+                            "react" | "unreact" => {
+                                // 表情回应，仅会话参与者可用 / Reactions, for conversation participants only
+                                self.handle_reaction(
+                                    client_id,
+                                    &wk_msg.data,
+                                    wk_msg.msg_type == "react",
+                                )
+                                .await?;
+                            }
+                            "offline_status" | "offline_clear" => {
+                                // 查询或清空自己的离线消息（角标计数）/ Query or clear one's own offline messages (badge count)
+                                let uid =
+                                    self.connections.get(client_id).and_then(|c| c.uid.clone());
+                                let requested = wk_msg.data.get("uid").and_then(|v| v.as_str());
+                                let resp = match uid {
+                                    None => ImMessage::error(
+                                        ErrorCode::Unauthenticated,
+                                        format!("{} requires auth uid", wk_msg.msg_type),
+                                    ),
+                                    Some(uid) if requested.is_some_and(|r| r != uid) => {
+                                        ImMessage::error(
+                                            ErrorCode::Forbidden,
+                                            "only your own offline messages can be accessed",
+                                        )
+                                    }
+                                    Some(uid) => {
+                                        let result = if wk_msg.msg_type == "offline_clear" {
+                                            self.clear_offline(&uid).await.map(|cleared| {
+                                                serde_json::json!({"cleared": cleared, "count": 0})
+                                            })
+                                        } else {
+                                            self.offline_count(&uid)
+                                                .await
+                                                .map(|count| serde_json::json!({"count": count}))
+                                        };
+                                        match result {
+                                            Ok(data) => ImMessage {
+                                                msg_type: wk_msg.msg_type.clone(),
+                                                data,
+                                                target_uid: None,
+                                                priority: Default::default(),
+                                                reply_to: None,
+                                            },
+                                            Err(e) => {
+                                                warn!(
+                                                    "{} for {} failed: {}",
+                                                    wk_msg.msg_type, uid, e
+                                                );
+                                                ImMessage::error(
+                                                    ErrorCode::StorageError,
+                                                    "offline storage unavailable",
+                                                )
+                                            }
+                                        }
+                                    }
+                                };
+                                let txt = serde_json::to_string(&resp)?;
+                                self.send_message_to_client(client_id, Message::Text(txt))
+                                    .await?;
+                            }
+                            "group_ack_status" => {
+                                // 发送者查询群消息确认进度 / Sender queries group message ack progress
+                                let msg_id = wk_msg
+                                    .data
+                                    .get("message_id")
+                                    .and_then(|v| v.as_str())
+                                    .unwrap_or_default();
+                                let uid =
+                                    self.connections.get(client_id).and_then(|c| c.uid.clone());
+                                let resp = match self.group_ack_status(msg_id).await {
+                                    Some(status) if uid.is_some() && status.sender_uid == uid => {
+                                        ImMessage {
+                                            msg_type: "group_ack_status".to_string(),
+                                            data: serde_json::to_value(&status)?,
+                                            target_uid: None,
+                                            priority: Default::default(),
+                                            reply_to: None,
+                                        }
+                                    }
+                                    _ => {
+                                        let mut err = ImMessage::error(
+                                            ErrorCode::NotFound,
+                                            "group message not found",
+                                        );
+                                        err.data["message_id"] = serde_json::json!(msg_id);
+                                        err
+                                    }
+                                };
+                                let txt = serde_json::to_string(&resp)?;
+                                self.send_message_to_client(client_id, Message::Text(txt))
+                                    .await?;
+                            }
                             _ => {
                                 warn!(
                                     "⚠️  Unknown message type from {}: {}",
                                     client_id, wk_msg.msg_type
                                 );
-                                let error_msg = ImMessage {
-                                    msg_type: "error".to_string(),
-                                    data: serde_json::json!({
-                                        "message": format!("Unknown message type: {}", wk_msg.msg_type)
-                                    }),
-                                    target_uid: None,
-                                };
+                                let error_msg = ImMessage::error(
+                                    ErrorCode::UnknownType,
+                                    format!("Unknown message type: {}", wk_msg.msg_type),
+                                );
                                 let error_json = serde_json::to_string(&error_msg)?;
                                 self.send_message_to_client(client_id, Message::Text(error_json))
                                     .await?;
@@ -1199,13 +1788,7 @@ impl VConnectIMServer {
                     }
                     Err(e) => {
                         warn!("⚠️  Invalid JSON from {}: {}", client_id, e);
-                        let error_msg = ImMessage {
-                            msg_type: "error".to_string(),
-                            data: serde_json::json!({
-                                "message": "Invalid JSON format"
-                            }),
-                            target_uid: None,
-                        };
+                        let error_msg = ImMessage::error(ErrorCode::InvalidJson, "Invalid JSON format");
                         let error_json = serde_json::to_string(&error_msg)?;
                         self.send_message_to_client(client_id, Message::Text(error_json))
                             .await?;
@@ -1240,12 +1823,6 @@ impl VConnectIMServer {
         Ok(())
     }
 
-    #[allow(dead_code)]
-    async fn replicate_record(&self, rec: &storage::MessageRecord) -> Result<()> {
-        self.raft.append_entry_as(&self.node_id, rec)?;
-        Ok(())
-    }
-
     #[allow(dead_code)]
     async fn replicate_with_retry(
         &self,
@@ -1255,7 +1832,7 @@ impl VConnectIMServer {
     ) -> Result<()> {
         let mut attempt = 0;
         loop {
-            if self.raft.append_entry_as(&self.node_id, rec).is_ok() {
+            if self.replicate_record(rec).await.is_ok() {
                 return Ok(());
             }
             if attempt >= max_retries {
@@ -1297,37 +1874,67 @@ impl VConnectIMServer {
 
 // Clone 已在 server 模块实现 / Clone implemented in server module
 
+/// 构建 HTTP 服务器；传入 `listener` 时直接在其上服务（测试预留端口，避免竞争），否则绑定 `host:port`
+/// Build the HTTP server; serves on `listener` when given (ports reserved by tests, race-free),
+/// otherwise binds `host:port`
 fn build_http_server(
     server: Arc<VConnectIMServer>,
     host: String,
     port: u16,
+    shutdown_timeout_secs: u64,
+    listener: Option<std::net::TcpListener>,
 ) -> Result<actix_web::dev::Server> {
-    let addr = format!("{}:{}", host, port);
+    let addr = match &listener {
+        Some(l) => l.local_addr()?.to_string(),
+        None => format!("{}:{}", host, port),
+    };
     // 启动前打印路由映射（自动生成） / Print auto-generated route map before start
     api_registry::print_routes(&addr, &["Logger"]);
 
     // 使用 actix-web 构建路由（自动注册） / Build routes with actix-web (auto registry)
+    let cors_config = Arc::new(cors::CorsConfig::from_config());
+    let limits = http_limits::HttpLimits::from_config();
+    let has_peers = v::get_global_config_manager()
+        .map(|cm| !cm.get_or("cluster.peers", String::new()).trim().is_empty())
+        .unwrap_or(false);
+    if has_peers && route_registry::internal_token_from_config().is_empty() {
+        warn!("⚠️  未配置 cluster.internal_token，节点间转发接口将拒绝所有请求 / cluster.internal_token is not set; node-to-node forwarding endpoints will reject every request");
+    }
+    if route_registry::admin_token_from_config().is_none() {
+        warn!("⚠️  未配置 server.admin_token，管理接口、指标与详细健康信息将拒绝所有请求 / server.admin_token is not set; the admin, metrics and detailed health endpoints will reject every request");
+    }
+    if route_registry::gateway_token_from_config().is_empty() {
+        warn!("⚠️  未配置 server.gateway_token，HTTP 消息与房间接口将拒绝所有请求 / server.gateway_token is not set; the HTTP message and room endpoints will reject every request");
+    }
+    // 路由表只构建一次，限流计数等中间件状态在各 worker 间共享
+    // Build the route table once so middleware state such as rate-limit counters is shared by all workers
+    let routes = Arc::new(crate::router::routes());
     let actix = HttpServer::new(move || {
+        let cors_config = cors_config.clone();
+        let routes = routes.clone();
         App::new()
-            .wrap(
-                actix_web::middleware::DefaultHeaders::new()
-                    .add(("Access-Control-Allow-Origin", "*"))
-                    .add(("Access-Control-Allow-Headers", "*"))
-                    .add((
-                        "Access-Control-Allow-Methods",
-                        "GET, POST, PUT, DELETE, OPTIONS",
-                    )),
-            )
+            .configure(|cfg| limits.configure(cfg))
+            .wrap(actix_web::middleware::from_fn(move |req, next| {
+                http_limits::request_timeout(limits.request_timeout, req, next)
+            }))
+            .wrap(actix_web::middleware::from_fn(move |req, next| {
+                cors::handle(cors_config.clone(), req, next)
+            }))
             .app_data(web::Data::new(server.clone()))
             .configure(|cfg| {
                 crate::api::openapi::register(cfg, "/openapi.json");
             })
-            // 只保留健康检查接口 / Only keep health check endpoints
-            .configure(crate::router::configure)
+            // 健康检查与消息/房间接口（见 router::routes）/ Health plus message/room APIs (see router::routes)
+            .configure(|cfg| route_registry::configure(cfg, &routes))
     })
-    .bind(addr.clone())?
+    // 限制读取请求头的时间，防止慢速连接占用 worker / Bound header read time against slow clients
+    .client_request_timeout(limits.request_timeout);
+    let actix = match listener {
+        Some(listener) => actix.listen(listener)?,
+        None => actix.bind(addr.clone())?,
+    }
     .disable_signals()
-    .shutdown_timeout(30);
+    .shutdown_timeout(shutdown_timeout_secs);
 
     info!("🌐 HTTP Server starting on http://{}", addr);
 
@@ -1341,12 +1948,26 @@ async fn main() -> Result<()> {
 
     let args = Args::parse();
 
+    // 仅检查配置：在加载配置、绑定端口与启动插件之前处理
+    // Config check only: handled before loading config, binding ports or spawning plugins
+    if let Some(Command::Config {
+        action: ConfigAction::Check { path },
+    }) = &args.command
+    {
+        check_config_and_exit(path);
+    }
+    if args.check_config {
+        let default_cfg = format!("{}/config/default.toml", env!("CARGO_MANIFEST_DIR"));
+        check_config_and_exit(args.config.as_deref().unwrap_or(&default_cfg));
+    }
+
     info!("🎯 Starting v-connect-im Hybrid Server (WebSocket + HTTP)...");
 
     // 如果提供配置文件路径则使用之，否则加载本服务默认配置
     // Initialize global config with provided file or service default
     if let Some(cfg_path) = &args.config {
         v::init_global_config_with_file(cfg_path)?;
+        config::set_config_file(cfg_path);
         info!("🔧 Loaded config file: {}", cfg_path);
     } else {
         let default_cfg = format!("{}/config/default.toml", env!("CARGO_MANIFEST_DIR"));
@@ -1358,6 +1979,16 @@ async fn main() -> Result<()> {
     let cm = v::get_global_config_manager()?;
     cm.print_sources_info();
 
+    // 启动时校验配置，一次列出所有问题 / Validate config at boot, listing every problem at once
+    if let Err(issues) =
+        v::comm::config_validator::validate_schema(&cm, &config::config_schema())
+    {
+        anyhow::bail!(
+            "配置校验失败 / invalid configuration:\n{}",
+            v::comm::config_validator::format_issues(&issues)
+        );
+    }
+
     // 读取配置项 / Read configuration items
     let cm = v::get_global_config_manager()?;
     let host: String = cm.get_or("server.host", "127.0.0.1".to_string());
@@ -1365,13 +1996,20 @@ async fn main() -> Result<()> {
     let http_port: u16 = cm.get_or("server.http_port", 8080_i64) as u16;
     let timeout_ms: u64 = cm.get_or("server.timeout_ms", 10000_i64) as u64;
 
+    if let Some(command) = args.command {
+        return run_command(command, format!("http://{}:{}", host, http_port)).await;
+    }
+
     // 鉴权配置 / Auth Configuration
     let auth_enabled: bool = cm.get_or("auth.enabled", false);
     let auth_center_url: String = cm.get_or("auth.center_url", "http://127.0.0.1:8090".to_string());
     let auth_timeout_ms: u64 = cm.get_or("auth.timeout_ms", 1000_i64) as u64;
 
     // 插件安装配置 / Plugin installation configuration
-    let plugin_dir: String = cm.get_or("plugins.plugin_dir", "./plugins".to_string());
+    let plugin_dir: String =
+        config::resolve_path(cm.get_or("plugins.plugin_dir", "./plugins".to_string()))
+            .to_string_lossy()
+            .to_string();
     let plugin_install_urls: Vec<String> =
         cm.get::<Vec<String>>("plugins.install").unwrap_or_default();
 
@@ -1428,6 +2066,52 @@ async fn main() -> Result<()> {
         info!("📊 Plugin log level: {}", level);
     }
 
+    // 每个插件在内存中保留的日志行数 / Log lines kept in memory per plugin
+    let plugin_log_buffer: usize = cm.get_or(
+        "plugins.log.buffer_lines",
+        crate::plugins::logs::DEFAULT_LOG_BUFFER_LINES,
+    );
+    runtime_manager.set_log_buffer_lines(plugin_log_buffer);
+
+    // 停机各阶段等待时间 / Shutdown stage waits
+    let shutdown_config = crate::service::shutdown::ShutdownConfig::from_config();
+    runtime_manager.set_stop_grace(shutdown_config.plugin_grace);
+
+    // 插件日志滚动与过期清理 / Plugin log rotation and expiry sweep
+    let default_rotation = crate::plugins::logs::LogRotation::default();
+    runtime_manager.set_log_rotation(crate::plugins::logs::LogRotation {
+        max_bytes: cm.get_or("plugins.log.max_bytes", default_rotation.max_bytes),
+        max_files: cm.get_or("plugins.log.max_files", default_rotation.max_files),
+    });
+    let retention_days: u64 = cm.get_or("plugins.log.retention_days", 7u64);
+    let swept = crate::plugins::logs::sweep_old_logs(
+        std::path::Path::new(crate::plugins::logs::PLUGIN_LOG_DIR),
+        Duration::from_secs(retention_days * 24 * 3600),
+    );
+    if swept > 0 {
+        info!(
+            "🧹 已清理 {} 个过期插件日志 / Removed {} expired plugin log files",
+            swept, swept
+        );
+    }
+
+    // 插件资源限制 / Plugin resource limits
+    let plugin_limits = crate::plugins::limits::ResourceLimits {
+        memory_max_bytes: cm
+            .get::<u64>("plugins.limits.memory_max_mb")
+            .ok()
+            .map(|mb| mb * 1024 * 1024),
+        cpu_weight: cm.get::<u32>("plugins.limits.cpu_weight").ok(),
+        cgroup_root: cm
+            .get::<String>("plugins.limits.cgroup_root")
+            .ok()
+            .map(std::path::PathBuf::from),
+    };
+    if !plugin_limits.is_empty() {
+        info!("📦 Plugin resource limits: {:?}", plugin_limits);
+        runtime_manager.set_resource_limits(plugin_limits);
+    }
+
     if let Err(e) = runtime_manager.init() {
         warn!("Failed to initialize plugin runtime manager: {}", e);
     } else {
@@ -1464,27 +2148,38 @@ async fn main() -> Result<()> {
         .filter(|p| !p.trim().is_empty())
         .unwrap_or_else(|| format!("{}/sockets/runtime.sock", plugin_dir));
 
-    // 展开 ~ 为用户主目录 / Expand ~ to user home directory
-    let socket_path = if socket_path.starts_with("~/") {
-        if let Some(home) = std::env::var_os("HOME") {
-            let home_path = std::path::Path::new(&home);
-            home_path
-                .join(&socket_path[2..])
-                .to_string_lossy()
-                .to_string()
-        } else {
-            socket_path
-        }
-    } else {
-        socket_path
-    };
+    // 展开 ~ 并按配置文件解析相对路径 / Expand ~ and resolve relative paths against the config file
+    let socket_path = config::resolve_path(&socket_path)
+        .to_string_lossy()
+        .to_string();
 
     runtime_manager.set_global_socket_path(&socket_path);
+
+    // 插件传输方式（unix / tcp://host:port）/ Per-plugin transport (unix / tcp://host:port)
+    #[cfg(feature = "plugin_tcp")]
+    let tcp_listen: Option<String> = cm
+        .get::<String>("plugins.tcp.listen")
+        .ok()
+        .filter(|a| !a.trim().is_empty());
+    #[cfg(feature = "plugin_tcp")]
+    let tcp_auth_token: String = cm.get_or("plugins.tcp.auth_token", String::new());
+    #[cfg(feature = "plugin_tcp")]
+    {
+        let transports: std::collections::HashMap<String, String> =
+            cm.get("plugins.transport").unwrap_or_default();
+        for (name, transport) in transports {
+            runtime_manager.set_plugin_transport(name, transport);
+        }
+        if !tcp_auth_token.is_empty() {
+            runtime_manager.set_tcp_auth_token(tcp_auth_token.clone());
+        }
+    }
+
     let runtime_manager_arc = Arc::new(runtime_manager);
     // 全局关闭通道（供各子系统共享）/ Global shutdown channel for subsystems
     let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
     use crate::plugins::runtime::UnixSocketServer;
-    let (socket_server_task, plugin_connection_pool) = match UnixSocketServer::new(
+    let (socket_server_task, mut plugin_connection_pool) = match UnixSocketServer::new(
         &socket_path,
         runtime_manager_arc.clone(),
         shutdown_rx.clone(),
@@ -1507,12 +2202,90 @@ async fn main() -> Result<()> {
         }
     };
 
+    // 启动 TCP 插件服务器（共享连接池）/ Start the TCP plugin server (shared connection pool)
+    #[cfg(feature = "plugin_tcp")]
+    let tcp_server_task = match (&tcp_listen, &plugin_connection_pool) {
+        (Some(addr), Some(pool)) => {
+            match crate::plugins::runtime::TcpPluginServer::new(
+                addr,
+                runtime_manager_arc.clone(),
+                pool.clone(),
+                &tcp_auth_token,
+                shutdown_rx.clone(),
+            )
+            .await
+            {
+                Ok(server) => Some(tokio::spawn(async move {
+                    if let Err(e) = server.run().await {
+                        error!("TCP plugin server error: {}", e);
+                    }
+                })),
+                Err(e) => {
+                    warn!("Failed to start TCP plugin server: {}", e);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+
     // 启动所有已安装插件（确保 socket 已经监听）/ Start installed plugins after socket ready
     {
         let rm = runtime_manager_arc.clone();
-        match rm.start_all().await {
-            Ok(_) => info!("🚀 All plugins started"),
-            Err(e) => warn!("Failed to start plugins: {}", e),
+        let started = match rm.start_all().await {
+            Ok(names) => {
+                info!("🚀 All plugins started");
+                names
+            }
+            Err(e) => {
+                warn!("Failed to start plugins: {}", e);
+                Vec::new()
+            }
+        };
+
+        // 等待插件握手注册完成（无连接池时无从就绪）/ Wait for plugins to handshake and register (impossible without a pool)
+        if plugin_connection_pool.is_some() && !started.is_empty() {
+            let ready_timeout_ms: u64 = cm.get_or("plugins.ready_timeout_ms", 10_000u64);
+            let not_ready = rm
+                .wait_ready(&started, Duration::from_millis(ready_timeout_ms))
+                .await;
+            if not_ready.is_empty() {
+                info!("✅ {} 个插件已就绪 / {} plugin(s) ready", started.len(), started.len());
+            } else {
+                warn!(
+                    "⚠️  插件未就绪 / Plugins not ready within {}ms: {:?}",
+                    ready_timeout_ms, not_ready
+                );
+            }
+        }
+    }
+
+    // 内置存储：显式开启，或未配置且没有安装存储插件时启用；存储插件可用时仍优先
+    // Built-in storage: on when enabled explicitly, or when unset and no storage plugin is
+    // installed; a usable storage plugin still takes precedence
+    let use_builtin = cm
+        .get::<bool>("storage.use_builtin")
+        .unwrap_or(!runtime_manager_arc.has_storage_plugin());
+    if use_builtin {
+        let path = config::resolve_path(cm.get_or(
+            "storage.path",
+            crate::storage::builtin::DEFAULT_BUILTIN_PATH.to_string(),
+        ))
+        .to_string_lossy()
+        .to_string();
+        match crate::storage::builtin::BuiltinStorage::open(&path) {
+            Ok(builtin) => {
+                info!("💾 内置存储位于 / Built-in storage at: {}", path);
+                // 插件服务器未启动时也需要连接池承载存储调用 / Storage calls need a pool even without the plugin server
+                plugin_connection_pool
+                    .get_or_insert_with(|| {
+                        Arc::new(crate::plugins::runtime::PluginConnectionPool::new(
+                            runtime_manager_arc.clone(),
+                        ))
+                    })
+                    .enable_builtin_storage(Arc::new(builtin));
+            }
+            Err(e) => error!("❌ 内置存储打开失败 / Failed to open built-in storage at {}: {}", path, e),
         }
     }
 
@@ -1527,27 +2300,42 @@ async fn main() -> Result<()> {
     server_builder = server_builder.with_plugin_runtime_manager(runtime_manager_arc.clone());
     if let Some(ref pool) = plugin_connection_pool {
         server_builder = server_builder.with_plugin_connection_pool(pool.clone());
+        if let Some(translation) =
+            crate::service::translation::TranslationPlugin::from_config(pool.clone())
+        {
+            server_builder = server_builder.with_plugin(Arc::new(translation));
+        }
     }
     let server = Arc::new(server_builder);
     directory.register_server(&node_id, server.clone());
-    if let Err(e) = server.plugin_registry.emit_startup(server.as_ref()).await {
-        warn!("plugin startup error: {}", e);
-    }
+    // 各插件配置子树取自 [plugins.config.<name>] / Per-plugin subtrees come from [plugins.config.<name>]
     let plugin_cfg = serde_json::json!({
-        "plugins": {}
+        "plugins": cm
+            .get::<serde_json::Value>("plugins.config")
+            .unwrap_or_else(|_| serde_json::json!({}))
     });
-    server.set_plugin_config(plugin_cfg.clone());
-    if let Err(e) = server.plugin_registry.emit_config_update(&plugin_cfg).await {
-        warn!("plugin config update error: {}", e);
+    if let Err(e) = server.start_plugins(plugin_cfg).await {
+        warn!("plugin startup error: {}", e);
     }
 
     // 加载持久化房间成员到内存
     let _ = server.load_rooms_from_storage().await;
+    // 恢复持久化的 uid 封禁 / Restore persisted uid blocks
+    match server.load_blocked() {
+        Ok(0) => {}
+        Ok(count) => info!("🚫 恢复 {} 个 uid 封禁 / Restored {} uid blocks", count, count),
+        Err(e) => warn!("⚠️  uid 封禁恢复失败 / Failed to restore uid blocks: {}", e),
+    }
 
     let server_clone = server.clone();
     let server_http = server.clone();
 
     tasks::heartbeat::spawn_cleanup_task(server_clone, timeout_ms, shutdown_rx.clone());
+    tasks::reaper::spawn_reaper_task(server.clone(), shutdown_rx.clone());
+    tasks::webhook_retry::spawn_retry_task(server.clone(), shutdown_rx.clone());
+    tasks::scheduler::spawn_scheduler_task(server.clone(), shutdown_rx.clone());
+    tasks::retention::spawn_retention_task(server.clone(), shutdown_rx.clone());
+    tasks::event_subscribers::spawn_event_subscribers(server.clone(), shutdown_rx.clone());
 
     // 启动WebSocket服务器 / Start WebSocket server
     let ws_server = server.clone();
@@ -1568,8 +2356,13 @@ async fn main() -> Result<()> {
     let http_host = host.clone();
     let mut http_shutdown_rx = shutdown_rx.clone();
     let http_future = async move {
-        sleep(Duration::from_secs(1)).await;
-        match build_http_server(server_http, http_host.clone(), http_port) {
+        match build_http_server(
+            server_http,
+            http_host.clone(),
+            http_port,
+            shutdown_config.http_grace_secs(),
+            None,
+        ) {
             Ok(server) => {
                 let handle = server.handle();
                 tokio::pin!(server);
@@ -1593,13 +2386,16 @@ async fn main() -> Result<()> {
     };
 
     // 等待服务器运行 / Wait for servers to run
+    // HTTP 服务器独立运行，收到停机信号后还能排空在途请求
+    // The HTTP server runs as its own task so it can drain in-flight requests after the signal
     let socket_task = socket_server_task;
+    let mut http_task = tokio::spawn(http_future);
     tokio::select! {
         _ = ws_future => {
             info!("WebSocket server stopped");
             let _ = shutdown_tx.send(true);
         }
-        _ = http_future => {
+        _ = &mut http_task => {
             info!("HTTP server stopped");
             let _ = shutdown_tx.send(true);
         }
@@ -1609,36 +2405,123 @@ async fn main() -> Result<()> {
         }
     }
 
-    // 等待 Unix Socket server 任务完成 / Wait for Unix Socket server task to complete
-    if let Some(handle) = socket_task {
-        info!("⏳ 等待 Unix Socket server 退出 / Waiting for Unix Socket server to exit");
-        match tokio::time::timeout(Duration::from_secs(2), handle).await {
-            Ok(_) => {
-                info!("✅ Unix Socket server 已退出 / Unix Socket server exited");
+    let progress = crate::service::shutdown::ShutdownProgress::default();
+    let graceful = async {
+        // 等待 HTTP 排空在途请求 / Wait for HTTP to drain in-flight requests
+        if !http_task.is_finished() {
+            let drained = progress
+                .step(
+                    "http_drain",
+                    tokio::time::timeout(shutdown_config.http_grace, &mut http_task),
+                )
+                .await;
+            if drained.is_err() {
+                warn!("⏰ HTTP 排空超时 / HTTP drain timeout");
+                http_task.abort();
+            }
+        }
+
+        // 等待 Unix Socket server 任务完成 / Wait for Unix Socket server task to complete
+        if let Some(handle) = socket_task {
+            info!("⏳ 等待 Unix Socket server 退出 / Waiting for Unix Socket server to exit");
+            let exited = progress
+                .step(
+                    "unix_socket_server",
+                    tokio::time::timeout(shutdown_config.socket_grace, handle),
+                )
+                .await;
+            match exited {
+                Ok(_) => {
+                    info!("✅ Unix Socket server 已退出 / Unix Socket server exited");
+                }
+                Err(_) => {
+                    warn!("⏰ Unix Socket server 退出超时 / Unix Socket server exit timeout");
+                }
+            }
+        }
+
+        // 等待 TCP 插件服务器退出 / Wait for the TCP plugin server to exit
+        #[cfg(feature = "plugin_tcp")]
+        if let Some(handle) = tcp_server_task {
+            let exited = progress
+                .step(
+                    "tcp_plugin_server",
+                    tokio::time::timeout(shutdown_config.socket_grace, handle),
+                )
+                .await;
+            if exited.is_err() {
+                warn!("⏰ TCP plugin server 退出超时 / TCP plugin server exit timeout");
             }
-            Err(_) => {
-                warn!("⏰ Unix Socket server 退出超时 / Unix Socket server exit timeout");
+        }
+
+        // 关闭连接前让存储插件落盘 / Let storage plugins flush before their connections close
+        if let Some(pool) = &plugin_connection_pool {
+            let flush_timeout_ms: u64 = cm.get_or("plugins.storage_flush_timeout_ms", 5_000u64);
+            let unflushed = progress
+                .step(
+                    "storage_flush",
+                    pool.flush_storage_plugins(Duration::from_millis(flush_timeout_ms)),
+                )
+                .await;
+            if !unflushed.is_empty() {
+                warn!(
+                    "⚠️  存储插件未确认落盘 / Storage plugins did not ack flush: {:?}",
+                    unflushed
+                );
             }
         }
-    }
 
-    // 关闭所有插件连接 / Close all plugin connections
-    if let Some(pool) = &plugin_connection_pool {
-        pool.close_all().await;
-    }
+        // 关闭所有插件连接 / Close all plugin connections
+        if let Some(pool) = &plugin_connection_pool {
+            progress
+                .step("close_plugin_connections", pool.close_all())
+                .await;
+        }
 
-    // 停止所有插件 / Stop all plugins
-    debug!("🛑 开始停止所有插件 / Starting to stop all plugins");
-    if let Err(e) = runtime_manager_arc.stop_all().await {
-        warn!("Failed to stop plugins: {}", e);
-    }
-    debug!("✅ 所有插件已停止 / All plugins stopped");
+        // 停止所有插件 / Stop all plugins
+        debug!("🛑 开始停止所有插件 / Starting to stop all plugins");
+        if let Err(e) = progress
+            .step("stop_plugins", runtime_manager_arc.stop_all())
+            .await
+        {
+            warn!("Failed to stop plugins: {}", e);
+        }
+        debug!("✅ 所有插件已停止 / All plugins stopped");
+
+        debug!("📢 发送插件关闭事件 / Emitting plugin shutdown event");
+        if let Err(e) = progress
+            .step("plugin_shutdown_event", server.plugin_registry.emit_shutdown())
+            .await
+        {
+            warn!("plugin shutdown error: {}", e);
+        }
+        debug!("✅ 插件关闭事件已发送 / Plugin shutdown event emitted");
+    };
 
-    debug!("📢 发送插件关闭事件 / Emitting plugin shutdown event");
-    if let Err(e) = server.plugin_registry.emit_shutdown().await {
-        warn!("plugin shutdown error: {}", e);
+    // 超出总预算时强制退出，避免卡住的插件让停机无限挂起
+    // Abort past the total budget so a stuck plugin cannot hang shutdown forever
+    if let Err(pending) = progress
+        .run_within(shutdown_config.total_budget, graceful)
+        .await
+    {
+        let running: Vec<String> = runtime_manager_arc
+            .runtime_summaries()
+            .into_iter()
+            .filter(|p| {
+                use crate::plugins::runtime::PluginStatus;
+                matches!(
+                    p.status,
+                    PluginStatus::Starting | PluginStatus::Running | PluginStatus::Stopping
+                )
+            })
+            .map(|p| p.name)
+            .collect();
+        error!(
+            "💥 停机超出预算 {:?}，强制退出；未完成阶段 {:?}，未停止插件 {:?} / Shutdown exceeded its {:?} budget, aborting; pending steps {:?}, plugins not stopped {:?}",
+            shutdown_config.total_budget, pending, running, shutdown_config.total_budget, pending, running
+        );
+        std::process::exit(1);
     }
-    debug!("✅ 插件关闭事件已发送 / Plugin shutdown event emitted");
 
     info!("✅ Server shutdown successfully");
 
@@ -1648,102 +2531,32 @@ async fn main() -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use tokio::sync::mpsc;
+    use crate::testkit::{im, recv_typed, TestServer};
+    use crate::ws::send_queue;
     use tokio_tungstenite::tungstenite::Message;
 
     #[tokio::test]
     async fn test_ping_pong_and_private_message_ack() {
-        let directory = Arc::new(cluster::directory::Directory::new());
-        let raft = Arc::new(cluster::raft::RaftCluster::new(
-            directory.clone(),
-            "node-A".into(),
-        ));
-        let mut builder = VConnectIMServer::new();
-        builder = builder
-            .with_node("node-A".into(), directory.clone())
-            .with_raft(raft.clone());
-        let server = Arc::new(builder);
-        directory.register_server("node-A", server.clone());
-
-        let (a_tx, mut a_rx) = mpsc::unbounded_channel::<Message>();
-        let (b_tx, mut b_rx) = mpsc::unbounded_channel::<Message>();
-        let a_id = "A".to_string();
-        let b_id = "B".to_string();
-
-        server.connections.insert(
-            a_id.clone(),
-            Connection {
-                client_id: a_id.clone(),
-                uid: Some(a_id.clone()),
-                addr: "127.0.0.1:0".parse().unwrap(),
-                sender: a_tx,
-                last_heartbeat: Arc::new(std::sync::Mutex::new(Instant::now())),
-            },
-        );
-        server.connections.insert(
-            b_id.clone(),
-            Connection {
-                client_id: b_id.clone(),
-                uid: Some(b_id.clone()),
-                addr: "127.0.0.1:0".parse().unwrap(),
-                sender: b_tx,
-                last_heartbeat: Arc::new(std::sync::Mutex::new(Instant::now())),
-            },
-        );
-        server
-            .uid_clients
-            .entry(a_id.clone())
-            .or_default()
-            .insert(a_id.clone());
-        server
-            .uid_clients
-            .entry(b_id.clone())
-            .or_default()
-            .insert(b_id.clone());
+        let ts = TestServer::new();
+        let (a_id, mut a_rx) = ts.add_client("A");
+        let (_b_id, mut b_rx) = ts.add_client("B");
 
         // ping
-        let ping = ImMessage {
-            msg_type: "ping".to_string(),
-            data: serde_json::json!({}),
-            target_uid: None,
-        };
-        server
-            .handle_incoming_message(
-                Message::Text(serde_json::to_string(&ping).unwrap()),
-                &a_id,
-                &server.connections,
-            )
+        ts.send(&a_id, im("ping", serde_json::json!({}), None))
             .await
             .unwrap();
-        let pong = a_rx.recv().await.unwrap();
-        let pong_text = match pong {
-            Message::Text(t) => t,
-            _ => panic!("expected text"),
-        };
-        let pong_msg: ImMessage = serde_json::from_str(&pong_text).unwrap();
-        assert_eq!(pong_msg.msg_type, "pong");
+        let pong: ImMessage = recv_typed(&mut a_rx).await;
+        assert_eq!(pong.msg_type, "pong");
 
         // private message
-        let pm = ImMessage {
-            msg_type: "private_message".to_string(),
-            data: serde_json::json!({"text":"hello"}),
-            target_uid: Some(b_id.clone()),
-        };
-        server
-            .handle_incoming_message(
-                Message::Text(serde_json::to_string(&pm).unwrap()),
-                &a_id,
-                &server.connections,
-            )
-            .await
-            .unwrap();
-
-        let b_msg = b_rx.recv().await.unwrap();
-        let b_text = match b_msg {
-            Message::Text(t) => t,
-            _ => panic!("expected text"),
-        };
-        let b_wk: ImMessage = serde_json::from_str(&b_text).unwrap();
+        ts.send(
+            &a_id,
+            im("private_message", serde_json::json!({"text":"hello"}), Some("B")),
+        )
+        .await
+        .unwrap();
+
+        let b_wk: ImMessage = recv_typed(&mut b_rx).await;
         assert_eq!(b_wk.msg_type, "private_message");
         let message_id = b_wk
             .data
@@ -1751,12 +2564,7 @@ mod tests {
             .and_then(|v| v.as_str())
             .expect("message_id");
 
-        let a_confirm = a_rx.recv().await.unwrap();
-        let a_text = match a_confirm {
-            Message::Text(t) => t,
-            _ => panic!("expected text"),
-        };
-        let a_wk: ImMessage = serde_json::from_str(&a_text).unwrap();
+        let a_wk: ImMessage = recv_typed(&mut a_rx).await;
         assert_eq!(a_wk.msg_type, "message_sent");
         assert_eq!(
             a_wk.data
@@ -1767,128 +2575,331 @@ mod tests {
         );
 
         // ack
-        let ack = ImMessage {
-            msg_type: "ack".to_string(),
-            data: serde_json::json!({"message_id": message_id}),
-            target_uid: None,
-        };
-        server
-            .handle_incoming_message(
-                Message::Text(serde_json::to_string(&ack).unwrap()),
-                &a_id,
-                &server.connections,
-            )
+        ts.send(
+            &a_id,
+            im("ack", serde_json::json!({"message_id": message_id}), None),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_rejects_messages_but_serves_ping() {
+        use crate::domain::message::ErrorCode;
+        let ts = TestServer::new();
+        let (a_id, mut a_rx) = ts.add_client("A");
+        let (_b_id, mut b_rx) = ts.add_client("B");
+
+        assert!(!ts.server.maintenance.set(true));
+        let (_, report) = ts.server.readiness().await;
+        assert_eq!(report["maintenance"], true);
+
+        ts.send(&a_id, im("message", serde_json::json!({"text":"hi"}), Some("B")))
+            .await
+            .unwrap();
+        let err: ImMessage = recv_typed(&mut a_rx).await;
+        assert_eq!(err.msg_type, "error");
+        assert_eq!(
+            err.data["code"],
+            serde_json::json!(ErrorCode::MaintenanceMode)
+        );
+        assert!(b_rx.try_recv().is_err());
+
+        ts.send(&a_id, im("ping", serde_json::json!({}), None))
             .await
             .unwrap();
+        let pong: ImMessage = recv_typed(&mut a_rx).await;
+        assert_eq!(pong.msg_type, "pong");
+
+        // HTTP 发送同样被拒绝 / HTTP sends are refused as well
+        let sent = ts
+            .server
+            .http_send_message(HttpSendMessageRequest {
+                from_uid: "A".into(),
+                to_uid: "B".into(),
+                content: serde_json::json!({"text": "hi"}),
+                message_type: None,
+            })
+            .await;
+        assert!(!sent.success);
+        ts.server.rooms.entry("r1".into()).or_default().insert("B".into());
+        let sent = ts
+            .server
+            .http_group_send_message("r1".into(), "A".into(), serde_json::json!({}), None)
+            .await;
+        assert!(!sent.success);
+        assert!(b_rx.try_recv().is_err());
+        let resp = crate::service::maintenance::http_rejection();
+        assert_eq!(resp.status(), actix_web::http::StatusCode::SERVICE_UNAVAILABLE);
+
+        // 关闭后恢复发送 / Sending resumes once turned off
+        assert!(ts.server.maintenance.set(false));
+        ts.send(&a_id, im("message", serde_json::json!({"text":"hi"}), Some("B")))
+            .await
+            .unwrap();
+        let delivered: ImMessage = recv_typed(&mut b_rx).await;
+        assert_ne!(delivered.msg_type, "error");
     }
 
     #[tokio::test]
-    async fn test_cross_node_private_message_routing() {
-        let directory = Arc::new(cluster::directory::Directory::new());
-        let raft = Arc::new(cluster::raft::RaftCluster::new(
-            directory.clone(),
-            "node-A".into(),
-        ));
-        let mut a_builder = VConnectIMServer::new();
-        a_builder = a_builder
-            .with_node("node-A".to_string(), directory.clone())
-            .with_raft(raft.clone());
-        let server_a = Arc::new(a_builder);
-        directory.register_server("node-A", server_a.clone());
+    async fn test_private_message_is_saved_with_reply_to() {
+        use crate::plugins::runtime::{PluginConnectionPool, PluginRuntimeManager};
+        use crate::storage::builtin::BuiltinStorage;
+
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = Arc::new(PluginConnectionPool::new(manager));
+        let store = Arc::new(BuiltinStorage::open_temporary().unwrap());
+        pool.enable_builtin_storage(store.clone());
+        let ts = TestServer::build(|server| server.with_plugin_connection_pool(pool.clone()));
+        let (a_id, mut a_rx) = ts.add_client("A");
+        let (_b_id, mut b_rx) = ts.add_client("B");
+
+        let mut msg = im("private_message", serde_json::json!({"text":"re"}), Some("B"));
+        msg.reply_to = Some("parent-1".to_string());
+        ts.send(&a_id, msg).await.unwrap();
+        let b_wk: ImMessage = recv_typed(&mut b_rx).await;
+        let sent: ImMessage = recv_typed(&mut a_rx).await;
+        assert_eq!(sent.msg_type, "message_sent");
+
+        let message_id = b_wk.data["message_id"].as_str().unwrap();
+        let record = store.get(message_id).unwrap().expect("saved");
+        assert_eq!(record.msg_type, "private_message");
+        assert_eq!(record.to_client_id, "B");
+        assert_eq!(record.reply_to.as_deref(), Some("parent-1"));
+    }
 
-        let mut b_builder = VConnectIMServer::new();
-        b_builder = b_builder
-            .with_node("node-B".to_string(), directory.clone())
-            .with_raft(raft.clone());
-        let server_b = Arc::new(b_builder);
-        directory.register_server("node-B", server_b.clone());
+    #[tokio::test]
+    async fn test_private_message_respects_persist_policy() {
+        use crate::plugins::runtime::{PluginConnectionPool, PluginRuntimeManager};
+        use crate::service::persistence::PersistencePolicies;
+        use crate::storage::builtin::BuiltinStorage;
+
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = Arc::new(PluginConnectionPool::new(manager));
+        let store = Arc::new(BuiltinStorage::open_temporary().unwrap());
+        pool.enable_builtin_storage(store.clone());
+        let overrides = serde_json::from_value(serde_json::json!({
+            "private_message": {"persist": false, "replicate": false}
+        }))
+        .unwrap();
+        let ts = TestServer::build(|mut server| {
+            server.plugin_connection_pool = Some(pool.clone());
+            server.persistence = Arc::new(PersistencePolicies::default().with_overrides(overrides));
+            server
+        });
+        let (a_id, _a_rx) = ts.add_client("A");
+        let (_b_id, mut b_rx) = ts.add_client("B");
+
+        ts.send(
+            &a_id,
+            im("private_message", serde_json::json!({"text":"hi"}), Some("B")),
+        )
+        .await
+        .unwrap();
+        let b_wk: ImMessage = recv_typed(&mut b_rx).await;
+        let message_id = b_wk.data["message_id"].as_str().unwrap();
+        assert!(store.get(message_id).unwrap().is_none());
+        assert_eq!(ts.server.raft.commit_count(&ts.node_id), 0);
+    }
 
-        let (a_tx, mut a_rx) = mpsc::unbounded_channel::<Message>();
-        let (b_tx, mut b_rx) = mpsc::unbounded_channel::<Message>();
-        let a_id = "A".to_string();
-        let b_id = "B".to_string();
+    #[tokio::test]
+    async fn test_group_member_ack_status() {
+        use crate::plugins::runtime::{PluginConnectionPool, PluginRuntimeManager};
+        use crate::storage::builtin::BuiltinStorage;
+
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = Arc::new(PluginConnectionPool::new(manager));
+        pool.enable_builtin_storage(Arc::new(BuiltinStorage::open_temporary().unwrap()));
+        let ts = TestServer::build(|server| server.with_plugin_connection_pool(pool.clone()));
+        let (a_id, mut a_rx) = ts.add_client("A");
+        let (b_id, mut b_rx) = ts.add_client("B");
+        for uid in ["A", "B", "C"] {
+            ts.server
+                .rooms
+                .entry("r1".to_string())
+                .or_default()
+                .insert(uid.to_string());
+        }
 
-        server_a.connections.insert(
-            a_id.clone(),
-            Connection {
-                client_id: a_id.clone(),
-                uid: Some(a_id.clone()),
-                addr: "127.0.0.1:0".parse().unwrap(),
-                sender: a_tx,
-                last_heartbeat: Arc::new(std::sync::Mutex::new(Instant::now())),
-            },
-        );
-        server_b.connections.insert(
-            b_id.clone(),
-            Connection {
-                client_id: b_id.clone(),
-                uid: Some(b_id.clone()),
-                addr: "127.0.0.1:0".parse().unwrap(),
-                sender: b_tx,
-                last_heartbeat: Arc::new(std::sync::Mutex::new(Instant::now())),
-            },
+        ts.send(
+            &a_id,
+            im("group_message", serde_json::json!({"room_id":"r1","text":"hi"}), None),
+        )
+        .await
+        .unwrap();
+        let b_wk: ImMessage = recv_typed(&mut b_rx).await;
+        let message_id = b_wk.data["message_id"].as_str().unwrap().to_string();
+        let _own: ImMessage = recv_typed(&mut a_rx).await;
+        let sent: ImMessage = recv_typed(&mut a_rx).await;
+        assert_eq!(sent.msg_type, "group_message_sent");
+
+        // 简单 ack 不计入群确认 / A plain ack is not counted as a group ack
+        ts.send(&b_id, im("ack", serde_json::json!({"message_id": message_id}), None))
+            .await
+            .unwrap();
+        assert_eq!(
+            ts.server
+                .group_ack_status(&message_id)
+                .await
+                .unwrap()
+                .acked
+                .len(),
+            0
         );
-        directory.register_client_location(&a_id, "node-A");
-        directory.register_client_location(&b_id, "node-B");
-        server_a
-            .uid_clients
-            .entry(a_id.clone())
-            .or_default()
-            .insert(a_id.clone());
-        server_b
-            .uid_clients
-            .entry(b_id.clone())
-            .or_default()
-            .insert(b_id.clone());
-        server_a
-            .uid_clients
-            .entry(a_id.clone())
-            .or_default()
-            .insert(a_id.clone());
-        server_b
-            .uid_clients
-            .entry(b_id.clone())
-            .or_default()
-            .insert(b_id.clone());
-        server_a
-            .uid_clients
-            .entry(a_id.clone())
-            .or_default()
-            .insert(a_id.clone());
-        server_b
-            .uid_clients
-            .entry(b_id.clone())
-            .or_default()
-            .insert(b_id.clone());
 
-        let pm = ImMessage {
-            msg_type: "private_message".to_string(),
-            data: serde_json::json!({"text":"cross"}),
-            target_uid: Some(b_id.clone()),
-        };
-        server_a
-            .handle_incoming_message(
-                Message::Text(serde_json::to_string(&pm).unwrap()),
+        ts.send(
+            &b_id,
+            im("ack", serde_json::json!({"message_id": message_id, "room_id": "r1"}), None),
+        )
+        .await
+        .unwrap();
+
+        let query = serde_json::json!({"message_id": message_id});
+        ts.send(&a_id, im("group_ack_status", query.clone(), None))
+            .await
+            .unwrap();
+        let status: ImMessage = recv_typed(&mut a_rx).await;
+        assert_eq!(status.msg_type, "group_ack_status");
+        assert_eq!(status.data["acked"], serde_json::json!(["B"]));
+        assert_eq!(status.data["pending"], serde_json::json!(["C"]));
+
+        // 仅发送者可查询 / Only the sender may query
+        ts.send(&b_id, im("group_ack_status", query, None))
+            .await
+            .unwrap();
+        let denied: ImMessage = recv_typed(&mut b_rx).await;
+        assert_eq!(denied.msg_type, "error");
+        assert_eq!(denied.data["code"], "NOT_FOUND");
+    }
+
+    #[tokio::test]
+    async fn test_connection_metadata_across_handlers() {
+        use crate::plugins::{Plugin, PluginContext, PluginFlow};
+
+        /// 记录 locale 并写入最近的消息类型 / Records the locale and stores the last message type
+        #[derive(Default)]
+        struct MetaProbe {
+            seen: parking_lot::Mutex<Option<serde_json::Value>>,
+        }
+
+        #[async_trait::async_trait]
+        impl Plugin for MetaProbe {
+            fn name(&self) -> &'static str {
+                "meta-probe"
+            }
+
+            async fn on_message_incoming(
+                &self,
+                ctx: &PluginContext<'_>,
+                message: &mut ImMessage,
+            ) -> anyhow::Result<PluginFlow> {
+                *self.seen.lock() = ctx.get_meta("locale");
+                ctx.set_meta("last_type", serde_json::json!(message.msg_type));
+                Ok(PluginFlow::Continue)
+            }
+        }
+
+        let ts = TestServer::new();
+        let probe = Arc::new(MetaProbe::default());
+        ts.server.plugin_registry.register(probe.clone()).unwrap();
+        let (a_id, mut a_rx) = ts.add_client("A");
+
+        ts.send(
+            &a_id,
+            im(
+                "auth",
+                serde_json::json!({"uid": "A", "token": "t", "meta": {"locale": "zh-CN"}}),
+                None,
+            ),
+        )
+        .await
+        .unwrap();
+        let resp: ImMessage = recv_typed(&mut a_rx).await;
+        assert_eq!(resp.data["status"], "success");
+
+        ts.send(&a_id, im("ping", serde_json::json!({}), None))
+            .await
+            .unwrap();
+        assert_eq!(*probe.seen.lock(), Some(serde_json::json!("zh-CN")));
+        let conn = ts.server.connections.get(&a_id).unwrap().clone();
+        assert_eq!(conn.get_meta("last_type"), Some(serde_json::json!("ping")));
+
+        // 断开时清空 / Cleared on disconnect
+        ts.server.remove_connection(&a_id).unwrap();
+        assert!(conn.metadata.is_empty());
+        assert!(!PluginContext::new(&ts.server, &a_id).set_meta("x", serde_json::json!(1)));
+    }
+
+    #[tokio::test]
+    async fn test_read_on_one_device_syncs_other_devices() {
+        let ts = TestServer::new();
+        let (phone, mut phone_rx) = ts.add_device("A", "phone");
+        let (_desktop, mut desktop_rx) = ts.add_device("A", "desktop");
+        let (b_id, mut b_rx) = ts.add_client("B");
+
+        ts.send(
+            &b_id,
+            im("private_message", serde_json::json!({"text":"hi"}), Some("A")),
+        )
+        .await
+        .unwrap();
+        let on_phone: ImMessage = recv_typed(&mut phone_rx).await;
+        let on_desktop: ImMessage = recv_typed(&mut desktop_rx).await;
+        assert_eq!(on_phone.data, on_desktop.data);
+        let message_id = on_phone.data["message_id"].as_str().unwrap().to_string();
+        let _sent: ImMessage = recv_typed(&mut b_rx).await;
+
+        ts.send(&phone, im("ack", serde_json::json!({"message_id": message_id}), None))
+            .await
+            .unwrap();
+        let sync: ImMessage = recv_typed(&mut desktop_rx).await;
+        assert_eq!(sync.msg_type, "sync");
+        assert_eq!(sync.data["device_id"], "phone");
+        assert_eq!(sync.data["state"], "delivered");
+
+        ts.send(&phone, im("read", serde_json::json!({"message_id": message_id}), None))
+            .await
+            .unwrap();
+        let sync: ImMessage = recv_typed(&mut desktop_rx).await;
+        assert_eq!(sync.data["state"], "read");
+        assert_eq!(sync.data["message_id"], message_id.as_str());
+
+        // 重复已读不再同步；发起设备自身不收到 sync / Repeated reads do not sync again; the source device gets none
+        ts.send(&phone, im("read", serde_json::json!({"message_id": message_id}), None))
+            .await
+            .unwrap();
+        ts.send(
+            &phone,
+            im("device_states", serde_json::json!({"message_id": message_id}), None),
+        )
+        .await
+        .unwrap();
+        let states: ImMessage = recv_typed(&mut phone_rx).await;
+        assert_eq!(states.msg_type, "device_states");
+        assert_eq!(states.data["devices"][0]["device_id"], "phone");
+        assert_eq!(states.data["devices"][0]["state"], "read");
+        assert!(desktop_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_cross_node_private_message_routing() {
+        let node_a = TestServer::new();
+        let node_b = node_a.join("node-B");
+        let (a_id, mut a_rx) = node_a.add_client("A");
+        let (_b_id, mut b_rx) = node_b.add_client("B");
+
+        node_a
+            .send(
                 &a_id,
-                &server_a.connections,
+                im("private_message", serde_json::json!({"text":"cross"}), Some("B")),
             )
             .await
             .unwrap();
 
-        let b_msg = b_rx.recv().await.unwrap();
-        let b_text = match b_msg {
-            Message::Text(t) => t,
-            _ => panic!("expected text"),
-        };
-        let b_wk: ImMessage = serde_json::from_str(&b_text).unwrap();
+        let b_wk: ImMessage = recv_typed(&mut b_rx).await;
         assert_eq!(b_wk.msg_type, "private_message");
 
-        let a_confirm = a_rx.recv().await.unwrap();
-        let a_text = match a_confirm {
-            Message::Text(t) => t,
-            _ => panic!("expected text"),
-        };
-        let a_wk: ImMessage = serde_json::from_str(&a_text).unwrap();
+        let a_wk: ImMessage = recv_typed(&mut a_rx).await;
         assert_eq!(a_wk.msg_type, "message_sent");
     }
 
@@ -1913,8 +2924,8 @@ mod tests {
         let server_b = Arc::new(s2b);
         directory.register_server("node-B", server_b.clone());
 
-        let (a_tx, mut a_rx) = mpsc::unbounded_channel::<Message>();
-        let (b_tx, mut b_rx) = mpsc::unbounded_channel::<Message>();
+        let (a_tx, mut a_rx) = send_queue::channel(64);
+        let (b_tx, mut b_rx) = send_queue::channel(64);
         let a_id = "A".to_string();
         let b_id = "B".to_string();
 
@@ -1923,9 +2934,16 @@ mod tests {
             Connection {
                 client_id: a_id.clone(),
                 uid: Some(a_id.clone()),
+                device_id: None,
+                protocol_version: crate::ws::protocol::CURRENT_PROTOCOL_VERSION,
+                wire_format: Default::default(),
+                metadata: Default::default(),
                 addr: "127.0.0.1:0".parse().unwrap(),
                 sender: a_tx,
+                connected_at: std::time::Instant::now(),
+                last_sent: Arc::new(std::sync::Mutex::new(std::time::Instant::now())),
                 last_heartbeat: Arc::new(std::sync::Mutex::new(Instant::now())),
+                closer: Default::default(),
             },
         );
         server_b.connections.insert(
@@ -1933,9 +2951,16 @@ mod tests {
             Connection {
                 client_id: b_id.clone(),
                 uid: Some(b_id.clone()),
+                device_id: None,
+                protocol_version: crate::ws::protocol::CURRENT_PROTOCOL_VERSION,
+                wire_format: Default::default(),
+                metadata: Default::default(),
                 addr: "127.0.0.1:0".parse().unwrap(),
                 sender: b_tx,
+                connected_at: std::time::Instant::now(),
+                last_sent: Arc::new(std::sync::Mutex::new(std::time::Instant::now())),
                 last_heartbeat: Arc::new(std::sync::Mutex::new(Instant::now())),
+                closer: Default::default(),
             },
         );
         directory.register_client_location(&a_id, "node-A");
@@ -1955,6 +2980,8 @@ mod tests {
             msg_type: "private_message".to_string(),
             data: serde_json::json!({"text":"persist"}),
             target_uid: Some(b_id.clone()),
+            priority: Default::default(),
+            reply_to: None,
         };
         server_a
             .handle_incoming_message(
@@ -2007,16 +3034,23 @@ mod tests {
             is_alive: true,
         });
 
-        let (a_tx, mut _a_rx) = mpsc::unbounded_channel::<Message>();
+        let (a_tx, mut _a_rx) = send_queue::channel(64);
         let a_id = "A".to_string();
         server_a.connections.insert(
             a_id.clone(),
             Connection {
                 client_id: a_id.clone(),
                 uid: Some(a_id.clone()),
+                device_id: None,
+                protocol_version: crate::ws::protocol::CURRENT_PROTOCOL_VERSION,
+                wire_format: Default::default(),
+                metadata: Default::default(),
                 addr: "127.0.0.1:0".parse().unwrap(),
                 sender: a_tx,
+                connected_at: std::time::Instant::now(),
+                last_sent: Arc::new(std::sync::Mutex::new(std::time::Instant::now())),
                 last_heartbeat: Arc::new(std::sync::Mutex::new(Instant::now())),
+                closer: Default::default(),
             },
         );
         directory.register_client_location(&a_id, "node-A");
@@ -2025,6 +3059,8 @@ mod tests {
             msg_type: "private_message".to_string(),
             data: serde_json::json!({"text":"fail"}),
             target_uid: Some("B".into()),
+            priority: Default::default(),
+            reply_to: None,
         };
         let result = server_a
             .handle_incoming_message(
@@ -2057,8 +3093,8 @@ mod tests {
         let server_b = Arc::new(b_builder);
         directory.register_server("node-B", server_b.clone());
 
-        let (a_tx, mut _a_rx) = mpsc::unbounded_channel::<Message>();
-        let (b_tx, mut _b_rx) = mpsc::unbounded_channel::<Message>();
+        let (a_tx, mut _a_rx) = send_queue::channel(64);
+        let (b_tx, mut _b_rx) = send_queue::channel(64);
         let a_id = "A".to_string();
         let b_id = "B".to_string();
         server_a.connections.insert(
@@ -2066,9 +3102,16 @@ mod tests {
             Connection {
                 client_id: a_id.clone(),
                 uid: Some(a_id.clone()),
+                device_id: None,
+                protocol_version: crate::ws::protocol::CURRENT_PROTOCOL_VERSION,
+                wire_format: Default::default(),
+                metadata: Default::default(),
                 addr: "127.0.0.1:0".parse().unwrap(),
                 sender: a_tx,
+                connected_at: std::time::Instant::now(),
+                last_sent: Arc::new(std::sync::Mutex::new(std::time::Instant::now())),
                 last_heartbeat: Arc::new(std::sync::Mutex::new(Instant::now())),
+                closer: Default::default(),
             },
         );
         server_b.connections.insert(
@@ -2076,9 +3119,16 @@ mod tests {
             Connection {
                 client_id: b_id.clone(),
                 uid: Some(b_id.clone()),
+                device_id: None,
+                protocol_version: crate::ws::protocol::CURRENT_PROTOCOL_VERSION,
+                wire_format: Default::default(),
+                metadata: Default::default(),
                 addr: "127.0.0.1:0".parse().unwrap(),
                 sender: b_tx,
+                connected_at: std::time::Instant::now(),
+                last_sent: Arc::new(std::sync::Mutex::new(std::time::Instant::now())),
                 last_heartbeat: Arc::new(std::sync::Mutex::new(Instant::now())),
+                closer: Default::default(),
             },
         );
         directory.register_client_location(&a_id, "node-A");
@@ -2088,6 +3138,8 @@ mod tests {
             msg_type: "private_message".into(),
             data: serde_json::json!({"text":"first"}),
             target_uid: Some(b_id.clone()),
+            priority: Default::default(),
+            reply_to: None,
         };
         raft.set_leader("node-A".into());
         // Leader为A时，A写入成功
@@ -2106,6 +3158,8 @@ mod tests {
             msg_type: "private_message".to_string(),
             data: serde_json::json!({"text":"second"}),
             target_uid: Some(a_id.clone()),
+            priority: Default::default(),
+            reply_to: None,
         };
         // A再写入应失败
         let res_err = server_a
@@ -2147,6 +3201,7 @@ mod tests {
             timestamp: chrono::Utc::now().timestamp_millis(),
             msg_type: "private_message".into(),
             room_id: None,
+            attachment: None,
         };
         let ok = cluster.write("node-2", &rec).await;
         assert!(ok.is_ok());
@@ -2175,16 +3230,23 @@ mod tests {
         directory.register_server("node-A", server.clone());
 
         // Online client A with uid uA
-        let (a_tx, mut a_rx) = mpsc::unbounded_channel::<Message>();
+        let (a_tx, mut a_rx) = send_queue::channel(64);
         let a_id = "A".to_string();
         server.connections.insert(
             a_id.clone(),
             Connection {
                 client_id: a_id.clone(),
                 uid: Some("uA".to_string()),
+                device_id: None,
+                protocol_version: crate::ws::protocol::CURRENT_PROTOCOL_VERSION,
+                wire_format: Default::default(),
+                metadata: Default::default(),
                 addr: "127.0.0.1:0".parse().unwrap(),
                 sender: a_tx,
+                connected_at: std::time::Instant::now(),
+                last_sent: Arc::new(std::sync::Mutex::new(std::time::Instant::now())),
                 last_heartbeat: Arc::new(std::sync::Mutex::new(Instant::now())),
+                closer: Default::default(),
             },
         );
         directory.register_client_location(&a_id, "node-A");
@@ -2212,6 +3274,8 @@ mod tests {
             msg_type: "group_message".to_string(),
             data: serde_json::json!({"room_id":"r1","text":"hi"}),
             target_uid: None,
+            priority: Default::default(),
+            reply_to: None,
         };
         server
             .handle_incoming_message(
@@ -2306,6 +3370,7 @@ mod tests {
             content: serde_json::json!({"n":1}),
             timestamp: base - 2000,
             msg_type: "group_message".into(),
+            priority: Default::default(),
         };
         let rec2 = crate::storage::OfflineRecord {
             message_id: uuid::Uuid::new_v4().to_string(),
@@ -2315,6 +3380,7 @@ mod tests {
             content: serde_json::json!({"n":2}),
             timestamp: base - 1000,
             msg_type: "group_message".into(),
+            priority: Default::default(),
         };
         let rec3 = crate::storage::OfflineRecord {
             message_id: uuid::Uuid::new_v4().to_string(),
@@ -2324,6 +3390,7 @@ mod tests {
             content: serde_json::json!({"n":3}),
             timestamp: base,
             msg_type: "group_message".into(),
+            priority: Default::default(),
         };
         server.storage.store_offline(&rec1).unwrap();
         server.storage.store_offline(&rec2).unwrap();
@@ -2357,16 +3424,23 @@ mod tests {
         server.rooms.clear();
 
         // 在线客户端映射 uid->client
-        let (x_tx, mut x_rx) = mpsc::unbounded_channel::<Message>();
+        let (x_tx, mut x_rx) = send_queue::channel(64);
         let x_id = "X".to_string();
         server.connections.insert(
             x_id.clone(),
             Connection {
                 client_id: x_id.clone(),
                 uid: Some("uX".to_string()),
+                device_id: None,
+                protocol_version: crate::ws::protocol::CURRENT_PROTOCOL_VERSION,
+                wire_format: Default::default(),
+                metadata: Default::default(),
                 addr: "127.0.0.1:0".parse().unwrap(),
                 sender: x_tx,
+                connected_at: std::time::Instant::now(),
+                last_sent: Arc::new(std::sync::Mutex::new(std::time::Instant::now())),
                 last_heartbeat: Arc::new(std::sync::Mutex::new(Instant::now())),
+                closer: Default::default(),
             },
         );
         directory.register_client_location(&x_id, "node-A");
@@ -2381,6 +3455,8 @@ mod tests {
             msg_type: "group_message".to_string(),
             data: serde_json::json!({"room_id":"rP","text":"fallback"}),
             target_uid: None,
+            priority: Default::default(),
+            reply_to: None,
         };
         server
             .handle_incoming_message(
@@ -2426,6 +3502,7 @@ mod tests {
                 content: serde_json::json!({"i":i}),
                 timestamp: now - (i * 1000) as i64,
                 msg_type: "group_message".into(),
+                priority: Default::default(),
             };
             server.storage.store_offline(&rec).unwrap();
         }
@@ -2462,8 +3539,8 @@ mod tests {
         let server = Arc::new(builder);
         directory.register_server("node-A", server.clone());
 
-        let (a_tx, mut _a_rx) = mpsc::unbounded_channel::<Message>();
-        let (b_tx, mut b_rx) = mpsc::unbounded_channel::<Message>();
+        let (a_tx, mut _a_rx) = send_queue::channel(64);
+        let (b_tx, mut b_rx) = send_queue::channel(64);
         let a_id = "A".to_string();
         let b_id = "B".to_string();
         server.connections.insert(
@@ -2471,9 +3548,16 @@ mod tests {
             Connection {
                 client_id: a_id.clone(),
                 uid: Some("uA".to_string()),
+                device_id: None,
+                protocol_version: crate::ws::protocol::CURRENT_PROTOCOL_VERSION,
+                wire_format: Default::default(),
+                metadata: Default::default(),
                 addr: "127.0.0.1:0".parse().unwrap(),
                 sender: a_tx,
+                connected_at: std::time::Instant::now(),
+                last_sent: Arc::new(std::sync::Mutex::new(std::time::Instant::now())),
                 last_heartbeat: Arc::new(std::sync::Mutex::new(Instant::now())),
+                closer: Default::default(),
             },
         );
         server.connections.insert(
@@ -2481,9 +3565,16 @@ mod tests {
             Connection {
                 client_id: b_id.clone(),
                 uid: Some("uB".to_string()),
+                device_id: None,
+                protocol_version: crate::ws::protocol::CURRENT_PROTOCOL_VERSION,
+                wire_format: Default::default(),
+                metadata: Default::default(),
                 addr: "127.0.0.1:0".parse().unwrap(),
                 sender: b_tx,
+                connected_at: std::time::Instant::now(),
+                last_sent: Arc::new(std::sync::Mutex::new(std::time::Instant::now())),
                 last_heartbeat: Arc::new(std::sync::Mutex::new(Instant::now())),
+                closer: Default::default(),
             },
         );
         directory.register_client_location(&a_id, "node-A");
@@ -2503,6 +3594,8 @@ mod tests {
             msg_type: "private_message".to_string(),
             data: serde_json::json!({"text":"no-ack"}),
             target_uid: Some("uB".to_string()),
+            priority: Default::default(),
+            reply_to: None,
         };
         server
             .handle_incoming_message(
@@ -2514,7 +3607,8 @@ mod tests {
             .unwrap();
         let _delivered = b_rx.recv().await.unwrap();
 
-        tokio::time::sleep(Duration::from_millis(600)).await;
+        // 时限 500ms 加宽限 200ms / 500ms deadline plus 200ms grace
+        tokio::time::sleep(Duration::from_millis(800)).await;
         // 应有离线数据 / should be queued offline
         let count = server.storage.offline_count("uB").unwrap();
         assert!(count >= 1);
@@ -2524,8 +3618,8 @@ mod tests {
 // 底部重复导出移除 / remove duplicated bottom re-exports
 // 对外导出常用类型，兼容已有API的 `use crate::...` 导入 / Re-export commonly used types for API files compatibility
 pub use crate::domain::message::{
-    ConnectRequest, ConnectResponse, HttpBroadcastRequest, HttpBroadcastResponse,
-    HttpSendMessageRequest, HttpSendMessageResponse, ImMessage, OnlineClientInfo,
+    ConnectRequest, ConnectResponse, ErrorCode, GroupDeliveryStatus, HttpBroadcastRequest,
+    HttpBroadcastResponse, HttpGroupSendResponse, HttpSendMessageRequest, HttpSendMessageResponse, ImMessage, OnlineClientInfo,
     OnlineClientsResponse, WebhookClientStatusData, WebhookEvent, WebhookEventType,
     WebhookMessageData,
 };
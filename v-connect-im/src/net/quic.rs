@@ -21,11 +21,11 @@ impl QuicServer {
 
     #[cfg(feature = "quic")]
     pub async fn start(self) -> JoinHandle<()> {
+        use crate::ws::send_queue::{channel, QueueReceiver};
         use crate::Connection as WsConnection;
         use quiche::{Config, Connection, Header};
         use quiche::{ConnectionId, RecvInfo};
         use rand::RngCore;
-        use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
 
         tokio::spawn(async move {
             let socket = match UdpSocket::bind(self.bind_addr).await {
@@ -52,10 +52,8 @@ impl QuicServer {
 
             let mut out = vec![0u8; 65535];
             let mut buf = vec![0u8; 65535];
-            let mut connections: HashMap<
-                Vec<u8>,
-                (Connection, SocketAddr, UnboundedReceiver<Message>, String),
-            > = HashMap::new();
+            let mut connections: HashMap<Vec<u8>, (Connection, SocketAddr, QueueReceiver, String)> =
+                HashMap::new();
 
             loop {
                 match timeout(Duration::from_millis(1000), socket.recv_from(&mut buf)).await {
@@ -89,17 +87,26 @@ impl QuicServer {
                                     continue;
                                 }
                             };
-                            let (tx, rx) = unbounded_channel::<Message>();
+                            let (tx, rx) = channel(crate::server::send_queue_capacity());
                             // 注册连接到业务映射 / Register connection to business map
                             let client_id = hex::encode(scid_bytes);
                             let ws_conn = WsConnection {
                                 client_id: client_id.clone(),
                                 uid: None,
+                                device_id: None,
+                                protocol_version: crate::ws::protocol::CURRENT_PROTOCOL_VERSION,
+                                wire_format: Default::default(),
+                                metadata: Default::default(),
                                 addr: peer,
                                 sender: tx.clone(),
+                                connected_at: std::time::Instant::now(),
+                                last_sent: Arc::new(std::sync::Mutex::new(
+                                    std::time::Instant::now(),
+                                )),
                                 last_heartbeat: Arc::new(std::sync::Mutex::new(
                                     std::time::Instant::now(),
                                 )),
+                                closer: Default::default(),
                             };
                             self.server.connections.insert(client_id.clone(), ws_conn);
                             self.server
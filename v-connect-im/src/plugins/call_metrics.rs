@@ -0,0 +1,302 @@
+//! 插件调用指标 / Plugin call metrics
+//!
+//! [`PluginConnectionPool::send_event`](crate::plugins::runtime::PluginConnectionPool::send_event)
+//! 与主题事件的每次调用按 `(plugin, event)` 记录耗时直方图，失败按插件与类型计数：`timeout`（调用方超时
+//! 取消或套接字超时）、`broken_pipe`（连接断开）、`decode_error`（响应无法解码）与 `other`。两者以
+//! Prometheus 文本格式由 `GET /v1/metrics` 输出。除首次出现的插件与事件外，记录只读共享表并更新原子计数。
+//! Every call through `send_event` and every topic event records its latency into a histogram
+//! per `(plugin, event)`, and failures are counted per plugin by kind: `timeout` (cancelled by a
+//! caller's timeout or a socket timeout), `broken_pipe` (the connection dropped), `decode_error`
+//! (the response could not be decoded) and `other`. Both are served in the Prometheus text format
+//! by `GET /v1/metrics`. Apart from the first call of a plugin and event, recording only reads
+//! shared maps and bumps atomics.
+
+use crate::service::metrics::LatencyHistogram;
+use dashmap::DashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// 每个插件最多单独统计的事件类型数，超出的归入 `other`
+/// Most event types tracked individually per plugin; the rest fall into `other`
+pub const MAX_TRACKED_EVENTS: usize = 64;
+
+/// 耗时直方图的指标名 / Metric name of the latency histogram
+pub const CALL_DURATION_METRIC: &str = "v_connect_im_plugin_call_duration_seconds";
+/// 失败计数的指标名 / Metric name of the failure counter
+pub const CALL_ERRORS_METRIC: &str = "v_connect_im_plugin_call_errors_total";
+
+/// 插件调用失败的类型 / Kind of a failed plugin call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallError {
+    Timeout,
+    BrokenPipe,
+    DecodeError,
+    Other,
+}
+
+impl CallError {
+    pub const ALL: [CallError; 4] = [
+        CallError::Timeout,
+        CallError::BrokenPipe,
+        CallError::DecodeError,
+        CallError::Other,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CallError::Timeout => "timeout",
+            CallError::BrokenPipe => "broken_pipe",
+            CallError::DecodeError => "decode_error",
+            CallError::Other => "other",
+        }
+    }
+
+    /// 按错误来源归类 / Classify an error by its source
+    pub fn classify(e: &anyhow::Error) -> Self {
+        if e.downcast_ref::<prost::DecodeError>().is_some() {
+            return CallError::DecodeError;
+        }
+        let Some(io) = e.downcast_ref::<std::io::Error>() else {
+            return CallError::Other;
+        };
+        match io.kind() {
+            std::io::ErrorKind::TimedOut => CallError::Timeout,
+            std::io::ErrorKind::BrokenPipe
+            | std::io::ErrorKind::ConnectionReset
+            | std::io::ErrorKind::ConnectionAborted
+            | std::io::ErrorKind::NotConnected
+            | std::io::ErrorKind::UnexpectedEof => CallError::BrokenPipe,
+            _ => CallError::Other,
+        }
+    }
+}
+
+/// 单个插件的统计 / Stats of one plugin
+#[derive(Debug, Default)]
+struct PluginStats {
+    by_event: DashMap<String, LatencyHistogram>,
+    /// 按 [`CallError`] 计数 / Counted by [`CallError`]
+    errors: [AtomicU64; 4],
+}
+
+impl PluginStats {
+    fn observe(&self, event_type: &str, started: Instant) {
+        let latency = started.elapsed();
+        if let Some(h) = self.by_event.get(event_type) {
+            h.observe(latency);
+            return;
+        }
+        let key = if self.by_event.len() < MAX_TRACKED_EVENTS {
+            event_type
+        } else {
+            "other"
+        };
+        self.by_event
+            .entry(key.to_string())
+            .or_default()
+            .observe(latency);
+    }
+}
+
+/// 插件调用指标 / Plugin call metrics
+#[derive(Debug, Default)]
+pub struct PluginCallMetrics {
+    plugins: DashMap<String, Arc<PluginStats>>,
+}
+
+impl PluginCallMetrics {
+    /// 开始计时一次调用；未调用 [`PluginCall::finish`] 就被丢弃时记为超时
+    /// Start timing a call; dropping it without [`PluginCall::finish`] counts as a timeout
+    pub fn start<'a>(&self, plugin_name: &str, event_type: &'a str) -> PluginCall<'a> {
+        let stats = match self.plugins.get(plugin_name) {
+            Some(stats) => stats.clone(),
+            None => self
+                .plugins
+                .entry(plugin_name.to_string())
+                .or_default()
+                .clone(),
+        };
+        PluginCall {
+            stats,
+            event_type,
+            started: Instant::now(),
+            error: Some(CallError::Timeout),
+        }
+    }
+
+    /// 以 Prometheus 文本格式输出，按插件与事件排序 / Render in the Prometheus text format, sorted by plugin and event
+    pub fn render_prometheus(&self) -> String {
+        let mut plugins: Vec<(String, Arc<PluginStats>)> = self
+            .plugins
+            .iter()
+            .map(|e| (e.key().clone(), e.value().clone()))
+            .collect();
+        plugins.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "# HELP {CALL_DURATION_METRIC} Latency of event calls to plugins."
+        );
+        let _ = writeln!(out, "# TYPE {CALL_DURATION_METRIC} histogram");
+        for (plugin, stats) in &plugins {
+            let mut events: Vec<String> = stats.by_event.iter().map(|e| e.key().clone()).collect();
+            events.sort();
+            for event in events {
+                if let Some(h) = stats.by_event.get(&event) {
+                    let labels = format!(
+                        "plugin=\"{}\",event=\"{}\"",
+                        escape_label(plugin),
+                        escape_label(&event)
+                    );
+                    h.write_prometheus(&mut out, CALL_DURATION_METRIC, &labels);
+                }
+            }
+        }
+        let _ = writeln!(
+            out,
+            "# HELP {CALL_ERRORS_METRIC} Failed event calls to plugins by kind."
+        );
+        let _ = writeln!(out, "# TYPE {CALL_ERRORS_METRIC} counter");
+        for (plugin, stats) in &plugins {
+            for kind in CallError::ALL {
+                let _ = writeln!(
+                    out,
+                    "{CALL_ERRORS_METRIC}{{plugin=\"{}\",kind=\"{}\"}} {}",
+                    escape_label(plugin),
+                    kind.as_str(),
+                    stats.errors[kind as usize].load(Ordering::Relaxed)
+                );
+            }
+        }
+        out
+    }
+}
+
+/// 进行中的一次调用，丢弃时记录 / An in-flight call, recorded when dropped
+pub struct PluginCall<'a> {
+    stats: Arc<PluginStats>,
+    event_type: &'a str,
+    started: Instant,
+    error: Option<CallError>,
+}
+
+impl PluginCall<'_> {
+    /// 按调用结果结束计时 / Finish timing with the call's result
+    pub fn finish<T>(mut self, result: &anyhow::Result<T>) {
+        self.error = result.as_ref().err().map(CallError::classify);
+    }
+}
+
+impl Drop for PluginCall<'_> {
+    fn drop(&mut self) {
+        self.stats.observe(self.event_type, self.started);
+        if let Some(kind) = self.error {
+            self.stats.errors[kind as usize].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// 转义 Prometheus 标签值 / Escape a Prometheus label value
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::runtime::{PluginConnectionPool, PluginRuntimeManager};
+    use prost::Message;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::UnixStream;
+
+    fn event() -> v::plugin::protocol::EventMessage {
+        v::plugin::protocol::EventMessage {
+            event_type: "message.incoming".to_string(),
+            payload: b"{}".to_vec(),
+            timestamp: 0,
+            trace_id: String::new(),
+        }
+    }
+
+    async fn read_frame(stream: &mut UnixStream) {
+        let len = stream.read_u32().await.unwrap();
+        let mut buf = vec![0u8; len as usize];
+        stream.read_exact(&mut buf).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_calls_are_timed_and_failures_counted_by_kind() {
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = PluginConnectionPool::new(manager);
+        let (host, mut plugin) = UnixStream::pair().unwrap();
+        pool.register("audit".to_string(), host);
+
+        let fake = tokio::spawn(async move {
+            read_frame(&mut plugin).await;
+            let ok = v::plugin::protocol::EventResponse {
+                status: "ok".to_string(),
+                ..Default::default()
+            }
+            .encode_to_vec();
+            plugin.write_u32(ok.len() as u32).await.unwrap();
+            plugin.write_all(&ok).await.unwrap();
+
+            // 无法解码的响应 / A response that does not decode
+            read_frame(&mut plugin).await;
+            plugin.write_u32(2).await.unwrap();
+            plugin.write_all(&[0xff, 0xff]).await.unwrap();
+
+            // 不回复，调用方超时；之后断开 / No reply so the caller times out, then hang up
+            read_frame(&mut plugin).await;
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        });
+
+        assert!(pool.send_event("audit", &event()).await.is_ok());
+        assert!(pool.send_event("audit", &event()).await.is_err());
+        let slow = event();
+        let timed_out =
+            tokio::time::timeout(Duration::from_millis(20), pool.send_event("audit", &slow));
+        assert!(timed_out.await.is_err());
+        fake.await.unwrap();
+        // 对端已关闭 / The peer is gone
+        assert!(pool.send_event("audit", &event()).await.is_err());
+
+        let text = pool.call_metrics().render_prometheus();
+        let line = |needle: &str| {
+            text.lines()
+                .find(|l| l.starts_with(needle))
+                .unwrap_or_else(|| panic!("missing {} in\n{}", needle, text))
+                .rsplit(' ')
+                .next()
+                .unwrap()
+                .to_string()
+        };
+        let labels = "plugin=\"audit\",event=\"message.incoming\"";
+        assert_eq!(
+            line(&format!("{CALL_DURATION_METRIC}_count{{{labels}}}")),
+            "4"
+        );
+        assert_eq!(
+            line(&format!(
+                "{CALL_DURATION_METRIC}_bucket{{{labels},le=\"+Inf\"}}"
+            )),
+            "4"
+        );
+        for (kind, count) in [
+            ("timeout", "1"),
+            ("broken_pipe", "1"),
+            ("decode_error", "1"),
+            ("other", "0"),
+        ] {
+            let errors = format!("{CALL_ERRORS_METRIC}{{plugin=\"audit\",kind=\"{kind}\"}}");
+            assert_eq!(line(&errors), count, "{}", kind);
+        }
+    }
+}
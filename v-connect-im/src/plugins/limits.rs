@@ -0,0 +1,244 @@
+//! 插件资源限制 / Plugin resource limits
+//!
+//! Linux 上优先使用 cgroup v2（`memory.max` / `cpu.weight`），不可用时退回 `RLIMIT_AS`；
+//! 其他平台不做任何限制。
+//! On Linux cgroup v2 (`memory.max` / `cpu.weight`) is preferred, falling back to
+//! `RLIMIT_AS` when unavailable; other platforms apply no limits.
+
+use std::path::PathBuf;
+use tokio::process::Command;
+
+/// OOM 终止时 `PluginStatus::Error` 的前缀 / `PluginStatus::Error` prefix for OOM kills
+pub const OOM_KILLED: &str = "oom-killed";
+
+/// 默认 cgroup 目录 / Default cgroup directory
+pub const DEFAULT_CGROUP_ROOT: &str = "/sys/fs/cgroup/v-connect-im";
+
+/// 插件资源限制配置（`plugins.limits`）/ Plugin resource limit config (`plugins.limits`)
+#[derive(Debug, Clone, Default)]
+pub struct ResourceLimits {
+    /// 内存上限（字节）/ Memory limit (bytes)
+    pub memory_max_bytes: Option<u64>,
+    /// CPU 权重（1..=10000，默认 100）/ CPU weight (1..=10000, default 100)
+    pub cpu_weight: Option<u32>,
+    /// cgroup 父目录 / Parent cgroup directory
+    pub cgroup_root: Option<PathBuf>,
+}
+
+impl ResourceLimits {
+    /// 是否未配置任何限制 / Whether no limit is configured
+    pub fn is_empty(&self) -> bool {
+        self.memory_max_bytes.is_none() && self.cpu_weight.is_none()
+    }
+}
+
+/// 某个插件进程上已生效的限制 / Limits applied to one plugin process
+#[derive(Debug)]
+pub struct AppliedLimits {
+    /// 插件所在 cgroup（使用 cgroup 时）/ Plugin cgroup (when cgroups are used)
+    cgroup: Option<PathBuf>,
+    /// 启动前的 oom_kill 计数 / oom_kill count before start
+    oom_kills_before: u64,
+    memory_max_bytes: Option<u64>,
+}
+
+impl AppliedLimits {
+    /// 在 spawn 之前准备限制；未配置或平台不支持时返回 None
+    /// Prepare limits before spawning; None when unconfigured or unsupported
+    pub fn prepare(name: &str, limits: &ResourceLimits, cmd: &mut Command) -> Option<Self> {
+        if limits.is_empty() {
+            return None;
+        }
+        imp::prepare(name, limits, cmd)
+    }
+
+    /// spawn 之后把进程加入 cgroup / Move the spawned process into its cgroup
+    pub fn attach(&self, pid: Option<u32>) {
+        if let (Some(cgroup), Some(pid)) = (&self.cgroup, pid) {
+            if let Err(e) = std::fs::write(cgroup.join("cgroup.procs"), pid.to_string()) {
+                tracing::warn!(
+                    "⚠️  无法将插件加入 cgroup / Failed to attach plugin to cgroup {:?}: {}",
+                    cgroup,
+                    e
+                );
+            }
+        }
+    }
+
+    /// 进程是否因超出内存上限被 OOM 终止 / Whether the process was OOM-killed at its memory limit
+    pub fn oom_killed(&self) -> bool {
+        self.cgroup
+            .as_ref()
+            .and_then(|c| std::fs::read_to_string(c.join("memory.events")).ok())
+            .map(|events| parse_oom_kills(&events) > self.oom_kills_before)
+            .unwrap_or(false)
+    }
+
+    /// OOM 时的错误描述 / Error description for an OOM kill
+    pub fn oom_message(&self) -> String {
+        match self.memory_max_bytes {
+            Some(bytes) => format!("{}: memory limit {} bytes exceeded", OOM_KILLED, bytes),
+            None => OOM_KILLED.to_string(),
+        }
+    }
+
+    /// 进程退出后删除 cgroup（尽力而为）/ Remove the cgroup after exit (best effort)
+    pub fn release(&self) {
+        if let Some(cgroup) = &self.cgroup {
+            if let Err(e) = std::fs::remove_dir(cgroup) {
+                tracing::debug!(
+                    "清理 cgroup 失败 / Failed to remove cgroup {:?}: {}",
+                    cgroup,
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// 解析 `memory.events` 中的 oom_kill 计数 / Parse the oom_kill count from `memory.events`
+fn parse_oom_kills(events: &str) -> u64 {
+    events
+        .lines()
+        .find_map(|line| line.strip_prefix("oom_kill "))
+        .and_then(|n| n.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use super::{parse_oom_kills, AppliedLimits, ResourceLimits, DEFAULT_CGROUP_ROOT};
+    use std::path::{Path, PathBuf};
+    use tokio::process::Command;
+    use tracing::{info, warn};
+
+    pub fn prepare(
+        name: &str,
+        limits: &ResourceLimits,
+        cmd: &mut Command,
+    ) -> Option<AppliedLimits> {
+        let root = limits
+            .cgroup_root
+            .clone()
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CGROUP_ROOT));
+        match create_cgroup(&root, name, limits) {
+            Ok(cgroup) => {
+                info!(
+                    "📦 插件 {} 使用 cgroup 限制 / Plugin {} limited by cgroup {:?}",
+                    name, name, cgroup
+                );
+                let oom_kills_before = std::fs::read_to_string(cgroup.join("memory.events"))
+                    .map(|e| parse_oom_kills(&e))
+                    .unwrap_or(0);
+                return Some(AppliedLimits {
+                    cgroup: Some(cgroup),
+                    oom_kills_before,
+                    memory_max_bytes: limits.memory_max_bytes,
+                });
+            }
+            Err(e) => warn!(
+                "⚠️  cgroup v2 不可用，退回 RLIMIT_AS / cgroup v2 unavailable for {}, falling back to RLIMIT_AS: {}",
+                name, e
+            ),
+        }
+
+        let bytes = limits.memory_max_bytes?;
+        let limit = bytes as libc::rlim_t;
+        // SAFETY: 闭包只调用 async-signal-safe 的 setrlimit
+        // SAFETY: the closure only calls setrlimit, which is async-signal-safe
+        unsafe {
+            cmd.pre_exec(move || {
+                let rlim = libc::rlimit {
+                    rlim_cur: limit,
+                    rlim_max: limit,
+                };
+                if libc::setrlimit(libc::RLIMIT_AS, &rlim) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+        Some(AppliedLimits {
+            cgroup: None,
+            oom_kills_before: 0,
+            memory_max_bytes: Some(bytes),
+        })
+    }
+
+    /// 创建 `<root>/<name>` 并写入限制 / Create `<root>/<name>` and write the limits
+    fn create_cgroup(root: &Path, name: &str, limits: &ResourceLimits) -> std::io::Result<PathBuf> {
+        if !Path::new("/sys/fs/cgroup/cgroup.controllers").exists() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "cgroup v2 not mounted",
+            ));
+        }
+        std::fs::create_dir_all(root)?;
+        // 为子 cgroup 启用控制器；失败时下方写入限制会报错并退回 RLIMIT_AS
+        // Enable controllers for children; if this fails, writing the limits below errors out
+        // and we fall back to RLIMIT_AS
+        let _ = std::fs::write(root.join("cgroup.subtree_control"), "+memory +cpu");
+
+        let cgroup = root.join(name);
+        if !cgroup.exists() {
+            std::fs::create_dir(&cgroup)?;
+        }
+        let written = (|| -> std::io::Result<()> {
+            if let Some(bytes) = limits.memory_max_bytes {
+                std::fs::write(cgroup.join("memory.max"), bytes.to_string())?;
+            }
+            if let Some(weight) = limits.cpu_weight {
+                std::fs::write(
+                    cgroup.join("cpu.weight"),
+                    weight.clamp(1, 10000).to_string(),
+                )?;
+            }
+            Ok(())
+        })();
+        match written {
+            Ok(()) => Ok(cgroup),
+            Err(e) => {
+                let _ = std::fs::remove_dir(&cgroup);
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use super::{AppliedLimits, ResourceLimits};
+    use tokio::process::Command;
+
+    pub fn prepare(
+        name: &str,
+        _limits: &ResourceLimits,
+        _cmd: &mut Command,
+    ) -> Option<AppliedLimits> {
+        tracing::debug!(
+            "当前平台不支持插件资源限制 / Plugin resource limits unsupported on this platform: {}",
+            name
+        );
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_oom_kills() {
+        let events = "low 0\nhigh 0\nmax 12\noom 3\noom_kill 2\noom_group_kill 0\n";
+        assert_eq!(parse_oom_kills(events), 2);
+        assert_eq!(parse_oom_kills(""), 0);
+
+        let applied = AppliedLimits {
+            cgroup: None,
+            oom_kills_before: 0,
+            memory_max_bytes: Some(64 << 20),
+        };
+        assert!(!applied.oom_killed());
+        assert!(applied.oom_message().starts_with(OOM_KILLED));
+    }
+}
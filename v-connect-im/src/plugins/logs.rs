@@ -0,0 +1,268 @@
+//! 插件日志采集 / Plugin log capture
+//!
+//! 插件的 stdout/stderr 通过管道读取，逐行写入日志文件并保留最近 N 行在内存中，
+//! 供管理接口查看。
+//! Plugin stdout/stderr are piped, written line by line to the log files, and the
+//! last N lines are kept in memory for the admin endpoint.
+//!
+//! 日志文件按大小滚动（`stdout.log` → `stdout.log.1` …），启动时清理过期文件。
+//! Log files rotate by size (`stdout.log` → `stdout.log.1` …) and expired files are
+//! swept at startup.
+
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
+
+/// 插件日志根目录 / Plugin log root directory
+pub const PLUGIN_LOG_DIR: &str = "./logs/plugins";
+
+/// 默认保留的日志行数 / Default number of buffered log lines
+pub const DEFAULT_LOG_BUFFER_LINES: usize = 1000;
+
+/// 日志滚动策略（`plugins.log`）/ Log rotation policy (`plugins.log`)
+#[derive(Debug, Clone, Copy)]
+pub struct LogRotation {
+    /// 单个文件上限（字节）/ Size limit per file (bytes)
+    pub max_bytes: u64,
+    /// 保留的历史文件数 / Rotated files kept
+    pub max_files: usize,
+}
+
+impl Default for LogRotation {
+    fn default() -> Self {
+        Self {
+            max_bytes: 10 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
+/// 按大小滚动的日志文件 / Size-rotated log file
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    policy: LogRotation,
+}
+
+impl RotatingFile {
+    /// 以追加方式打开（重启复用同一文件）/ Open for append (restarts reuse the same file)
+    pub fn open(path: impl Into<PathBuf>, policy: LogRotation) -> std::io::Result<Self> {
+        let path = path.into();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file,
+            size,
+            policy,
+        })
+    }
+
+    /// 写入一段数据，超过上限前先滚动 / Write a chunk, rotating first if it would exceed the limit
+    pub fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.policy.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(())
+    }
+
+    /// `x.log.{n-1}` → `x.log.{n}` …，`x.log` → `x.log.1`，超出 max_files 的被删除
+    /// `x.log.{n-1}` → `x.log.{n}` …, `x.log` → `x.log.1`; files beyond max_files are deleted
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let max = self.policy.max_files;
+        if max > 0 {
+            let _ = std::fs::remove_file(rotated_path(&self.path, max));
+            for i in (1..max).rev() {
+                let from = rotated_path(&self.path, i);
+                if from.exists() {
+                    std::fs::rename(&from, rotated_path(&self.path, i + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+/// 删除 `root/<plugin>/` 下早于保留期的日志文件，返回删除数量
+/// Delete log files under `root/<plugin>/` older than the retention period; returns the count
+pub fn sweep_old_logs(root: &Path, retention: Duration) -> usize {
+    let Some(cutoff) = SystemTime::now().checked_sub(retention) else {
+        return 0;
+    };
+    let Ok(plugins) = std::fs::read_dir(root) else {
+        return 0;
+    };
+    let mut removed = 0;
+    for dir in plugins.flatten().filter(|e| e.path().is_dir()) {
+        let Ok(files) = std::fs::read_dir(dir.path()) else {
+            continue;
+        };
+        for file in files.flatten() {
+            let expired = file
+                .metadata()
+                .and_then(|m| m.modified())
+                .map(|t| t < cutoff)
+                .unwrap_or(false);
+            if expired && std::fs::remove_file(file.path()).is_ok() {
+                removed += 1;
+            }
+        }
+    }
+    removed
+}
+
+/// 最近日志环形缓冲 / Ring buffer of recent log lines
+pub struct LogRing {
+    lines: Mutex<VecDeque<String>>,
+    capacity: usize,
+}
+
+impl LogRing {
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            lines: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+        }
+    }
+
+    /// 追加一行，超出容量时丢弃最旧的 / Append a line, dropping the oldest beyond capacity
+    pub fn push(&self, line: String) {
+        let mut lines = self.lines.lock();
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    /// 最近 `n` 行（旧 → 新）/ The last `n` lines (oldest first)
+    pub fn tail(&self, n: usize) -> Vec<String> {
+        let lines = self.lines.lock();
+        lines
+            .iter()
+            .skip(lines.len().saturating_sub(n))
+            .cloned()
+            .collect()
+    }
+}
+
+/// 持续读取插件输出，写入文件并记录到环形缓冲，直到管道关闭
+/// Keep reading plugin output into the file and the ring buffer until the pipe closes
+pub fn spawn_log_pump<R>(
+    reader: R,
+    stream: &'static str,
+    mut file: Option<RotatingFile>,
+    ring: Arc<LogRing>,
+) where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(reader);
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            match reader.read_until(b'\n', &mut buf).await {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    if let Some(f) = file.as_mut() {
+                        if f.write_all(&buf).is_err() {
+                            file = None;
+                        }
+                    }
+                    let line = String::from_utf8_lossy(&buf);
+                    ring.push(format!("[{}] {}", stream, line.trim_end()));
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rotation_and_sweep() {
+        let root = std::env::temp_dir().join(format!("vim-plugin-logs-{}", uuid::Uuid::new_v4()));
+        let dir = root.join("demo");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("stdout.log");
+        let policy = LogRotation {
+            max_bytes: 10,
+            max_files: 2,
+        };
+
+        let mut file = RotatingFile::open(&path, policy).unwrap();
+        for line in ["aaaaaa\n", "bbbbbb\n", "cccccc\n", "dddddd\n"] {
+            file.write_all(line.as_bytes()).unwrap();
+        }
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "dddddd\n");
+        assert_eq!(
+            std::fs::read_to_string(rotated_path(&path, 1)).unwrap(),
+            "cccccc\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(rotated_path(&path, 2)).unwrap(),
+            "bbbbbb\n"
+        );
+        assert!(!rotated_path(&path, 3).exists());
+
+        // 重新打开时追加到同一文件 / Reopening appends to the same file
+        drop(file);
+        let mut file = RotatingFile::open(&path, policy).unwrap();
+        file.write_all(b"e\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "dddddd\ne\n");
+
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        File::options()
+            .write(true)
+            .open(rotated_path(&path, 2))
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        assert_eq!(sweep_old_logs(&root, Duration::from_secs(60)), 1);
+        assert!(!rotated_path(&path, 2).exists());
+        assert!(path.exists());
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ring_keeps_last_lines() {
+        let ring = Arc::new(LogRing::new(3));
+        let (mut writer, reader) = tokio::io::duplex(64);
+        spawn_log_pump(reader, "stderr", None, ring.clone());
+
+        use tokio::io::AsyncWriteExt;
+        writer.write_all(b"a\nb\nc\nd\n").await.unwrap();
+        drop(writer);
+        for _ in 0..50 {
+            if ring.tail(1) == ["[stderr] d"] {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(ring.tail(10), ["[stderr] b", "[stderr] c", "[stderr] d"]);
+        assert_eq!(ring.tail(1), ["[stderr] d"]);
+    }
+}
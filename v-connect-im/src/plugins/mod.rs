@@ -1,9 +1,25 @@
 //! 插件系统入口 / Plugin system entry
+//!
+//! 进程内插件的配置：`on_startup` 收到本插件在启动配置中的子树（`[plugins.config.<name>]`，缺省为空
+//! 表），用于确定性地初始化；之后每次配置变更调用 `on_config_update`，参数为完整快照
+//! （`{"plugins": {...}}`），其中本插件的子树取代启动时的值。任意时刻可用
+//! [`PluginContext::plugin_config`] 读取最新子树，用 [`PluginContext::config`] 读取服务端配置项。
+//! In-process plugin config: `on_startup` receives the plugin's subtree of the boot config
+//! (`[plugins.config.<name>]`, an empty table when absent) so it initializes deterministically;
+//! afterwards `on_config_update` is called on every change with the full snapshot
+//! (`{"plugins": {...}}`), whose subtree for the plugin supersedes the startup value. At any time
+//! [`PluginContext::plugin_config`] reads the latest subtree and [`PluginContext::config`] reads
+//! server settings.
 
+pub mod call_metrics;
 pub mod event_bus;
 pub mod installer;
+pub mod limits;
+pub mod logs;
+pub mod order;
 pub mod protocol_handler;
 pub mod runtime;
+pub mod socket_perms;
 pub mod v_adapters;
 
 use crate::domain::message::ImMessage;
@@ -11,6 +27,8 @@ use crate::server::VConnectIMServer;
 use anyhow::Result;
 use async_trait::async_trait;
 use parking_lot::RwLock;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
 use serde_json::Value;
 use std::sync::Arc;
 
@@ -41,6 +59,65 @@ impl<'a> PluginContext<'a> {
             client_id,
         }
     }
+
+    /// 读取当前连接的元数据 / Read metadata of the current connection
+    pub fn get_meta(&self, key: &str) -> Option<Value> {
+        self.server?.connections.get(self.client_id)?.get_meta(key)
+    }
+
+    /// 写入当前连接的元数据；连接不存在或超过上限时返回 false
+    /// Set metadata on the current connection; false if the connection is gone or the cap is hit
+    pub fn set_meta(&self, key: impl Into<String>, value: Value) -> bool {
+        self.server
+            .and_then(|s| s.connections.get(self.client_id))
+            .is_some_and(|c| c.set_meta(key, value))
+    }
+
+    /// 向 uid 的所有在线客户端发送消息（含其他节点），返回送达的客户端数
+    /// Send a message to every online client of a uid (other nodes included); returns the client count
+    ///
+    /// 发送不会触发上行钩子，因此在 `on_message_incoming` 中调用不会重入；
+    /// 但每个接收端都会执行下行钩子，在 `on_message_outgoing` 中调用需自行防止递归。
+    /// Sends do not trigger incoming hooks, so calling this from `on_message_incoming` cannot
+    /// re-enter it; outgoing hooks do run per recipient, so callers in `on_message_outgoing`
+    /// must guard against recursion themselves.
+    pub async fn send_to_uid(&self, uid: &str, message: ImMessage) -> Result<usize> {
+        self.server
+            .ok_or_else(|| anyhow::anyhow!("no server in plugin context"))?
+            .push_to_uid(uid, &message)
+            .await
+    }
+
+    /// 向房间所有成员发送消息，返回送达的客户端数（重入规则同 `send_to_uid`）
+    /// Send a message to every member of a room; returns the client count (reentrancy as in `send_to_uid`)
+    pub async fn send_to_room(&self, room_id: &str, message: ImMessage) -> Result<usize> {
+        self.server
+            .ok_or_else(|| anyhow::anyhow!("no server in plugin context"))?
+            .push_to_room(room_id, &message)
+            .await
+    }
+
+    /// 读取服务端配置项（点分键，如 `rooms.max_pins`），未加载配置或类型不符时为 None
+    /// Read a server setting by dotted key (e.g. `rooms.max_pins`); None without a loaded config or on a type mismatch
+    pub fn config<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        v::get_global_config_manager().ok()?.get(key).ok()
+    }
+
+    /// 插件当前的配置子树（含之后的配置更新）/ A plugin's current config subtree (later updates included)
+    pub fn plugin_config(&self, name: &str) -> Value {
+        self.server
+            .map(|s| plugin_config_subtree(&s.get_plugin_config(), name))
+            .unwrap_or_else(|| Value::Object(Default::default()))
+    }
+}
+
+/// 从配置快照中取出插件的子树，缺省为空表 / A plugin's subtree of a config snapshot, an empty table when absent
+pub fn plugin_config_subtree(config: &Value, name: &str) -> Value {
+    config
+        .get("plugins")
+        .and_then(|plugins| plugins.get(name))
+        .cloned()
+        .unwrap_or_else(|| Value::Object(Default::default()))
 }
 
 /// 插件公共trait / Common trait for plugins
@@ -54,6 +131,13 @@ pub trait Plugin: Send + Sync {
         100
     }
 
+    /// 必须在这些插件之后执行（按名称）/ Plugins (by name) this one must run after
+    ///
+    /// 同优先级按名称排序；依赖优先于优先级 / Equal priorities sort by name; dependencies override priority
+    fn run_after(&self) -> &'static [&'static str] {
+        &[]
+    }
+
     /// 处理上行消息（客户端->服务器） / Incoming hook
     async fn on_message_incoming(
         &self,
@@ -72,12 +156,22 @@ pub trait Plugin: Send + Sync {
         Ok(PluginFlow::Continue)
     }
 
-    /// 插件启动时机 / Called when plugin system starts
-    async fn on_startup(&self, _server: &VConnectIMServer) -> Result<()> {
+    /// 连接准入钩子，在握手后、消息循环前调用；返回拒绝时关闭连接
+    /// Admission hook, called after the handshake and before the message loop; a rejection closes the connection
+    async fn on_connect(
+        &self,
+        _peer: std::net::SocketAddr,
+    ) -> Result<Option<crate::service::admission::ConnectRejection>> {
+        Ok(None)
+    }
+
+    /// 插件启动时机，`config` 为本插件的配置子树 / Called when plugin system starts; `config` is this plugin's subtree
+    async fn on_startup(&self, _server: &VConnectIMServer, _config: &Value) -> Result<()> {
         Ok(())
     }
 
-    /// 配置更新 / Called when configuration updates
+    /// 配置更新，`config` 为完整快照，其中的子树优先于启动时的值
+    /// Called when configuration updates; `config` is the full snapshot and supersedes the startup subtree
     async fn on_config_update(&self, _config: &Value) -> Result<()> {
         Ok(())
     }
@@ -93,24 +187,92 @@ pub trait Plugin: Send + Sync {
     }
 }
 
+/// 同名插件再次注册时的处理（`plugins.on_duplicate`）
+/// What to do when a plugin registers under a name already taken (`plugins.on_duplicate`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnDuplicate {
+    /// 拒绝注册并返回错误 / Refuse the registration with an error
+    #[default]
+    Reject,
+    /// 告警后替换已注册的插件 / Warn and replace the registered plugin
+    Replace,
+}
+
 /// 插件注册中心 / Plugin registry
 #[derive(Default)]
 pub struct PluginRegistry {
     plugins: RwLock<Vec<Arc<dyn Plugin>>>,
+    on_duplicate: OnDuplicate,
 }
 
 impl PluginRegistry {
     pub fn new() -> Self {
-        Self {
-            plugins: RwLock::new(Vec::new()),
-        }
+        Self::default()
     }
 
-    /// 注册插件 / Register plugin
-    pub fn register(&self, plugin: Arc<dyn Plugin>) {
+    /// 读取 `plugins.on_duplicate` / Read `plugins.on_duplicate`
+    pub fn from_config() -> Self {
+        let on_duplicate = v::get_global_config_manager()
+            .map(|cm| cm.get_or("plugins.on_duplicate", OnDuplicate::default()))
+            .unwrap_or_default();
+        Self::new().with_on_duplicate(on_duplicate)
+    }
+
+    /// 设置同名注册的处理方式 / Set how duplicate names are handled
+    pub fn with_on_duplicate(mut self, on_duplicate: OnDuplicate) -> Self {
+        self.on_duplicate = on_duplicate;
+        self
+    }
+
+    /// 注册插件，按 `(priority, name)` 与 `run_after` 重新排序；依赖成环时拒绝注册。
+    /// 名称已被占用时按 [`OnDuplicate`] 拒绝或替换，同名插件不会并存。
+    /// Register plugin and reorder by `(priority, name)` and `run_after`; rejected on a dependency
+    /// cycle. A name already taken is rejected or replaced per [`OnDuplicate`], so two plugins
+    /// never share a name.
+    pub fn register(&self, plugin: Arc<dyn Plugin>) -> Result<()> {
         let mut guard = self.plugins.write();
-        guard.push(plugin);
-        guard.sort_by_key(|p| p.priority());
+        let mut candidate = guard.clone();
+        if candidate.iter().any(|p| p.name() == plugin.name()) {
+            match self.on_duplicate {
+                OnDuplicate::Reject => {
+                    return Err(anyhow::anyhow!(
+                        "plugin {} is already registered",
+                        plugin.name()
+                    ));
+                }
+                OnDuplicate::Replace => {
+                    tracing::warn!(
+                        "⚠️  插件 {} 重复注册，替换已注册的实例 / Plugin {} registered twice, replacing the registered one",
+                        plugin.name(),
+                        plugin.name()
+                    );
+                    candidate.retain(|p| p.name() != plugin.name());
+                }
+            }
+        }
+        candidate.push(plugin);
+        let nodes: Vec<_> = candidate
+            .iter()
+            .map(|p| order::OrderNode {
+                name: p.name().to_string(),
+                key: p.priority(),
+                run_after: p.run_after().iter().map(|s| s.to_string()).collect(),
+            })
+            .collect();
+        let order = order::resolve_order(&nodes)?;
+        *guard = order.into_iter().map(|i| candidate[i].clone()).collect();
+        Ok(())
+    }
+
+    /// 当前执行顺序（插件名称）/ Current execution order (plugin names)
+    pub fn names(&self) -> Vec<&'static str> {
+        self.plugins.read().iter().map(|p| p.name()).collect()
+    }
+
+    /// 是否没有注册任何插件 / Whether no plugin is registered
+    pub fn is_empty(&self) -> bool {
+        self.plugins.read().is_empty()
     }
 
     fn snapshot(&self) -> Vec<Arc<dyn Plugin>> {
@@ -133,6 +295,21 @@ impl PluginRegistry {
     }
 
     /// 触发下行钩子 / Emit outgoing hooks
+    ///
+    /// 在 `send_message_to_client` 中按接收连接逐个调用，位于帧编码与入发送队列之前，因此钩子看到的是
+    /// 该连接将收到的消息，可按连接元数据（如 `locale`）改写。自动翻译
+    /// （[`crate::service::translation::TranslationPlugin`]，优先级 250）在此以普通下行插件身份运行，
+    /// 排在默认优先级的插件之后：被拦截的消息不会翻译，改写后的内容才会被翻译。
+    /// Called from `send_message_to_client` once per receiving connection, before frame encoding
+    /// and queueing, so hooks see the message that connection will receive and may rewrite it from
+    /// connection metadata (e.g. `locale`). Auto-translation
+    /// ([`crate::service::translation::TranslationPlugin`], priority 250) runs here as a regular
+    /// outgoing plugin after those at the default priority: suppressed messages are never
+    /// translated and rewritten content is what gets translated.
+    ///
+    /// 返回 `Stop` 时该连接不会收到这条消息，其他接收者不受影响；没有注册插件时发送路径跳过本方法。
+    /// Returning `Stop` suppresses the message for that connection only, leaving other recipients
+    /// untouched; the send path skips this method when no plugin is registered.
     pub async fn emit_outgoing(
         &self,
         ctx: &PluginContext<'_>,
@@ -149,9 +326,24 @@ impl PluginRegistry {
 }
 
 impl PluginRegistry {
-    pub async fn emit_startup(&self, server: &VConnectIMServer) -> Result<()> {
+    /// 触发准入钩子，第一个拒绝生效 / Emit admission hooks; the first rejection wins
+    pub async fn emit_connect(
+        &self,
+        peer: std::net::SocketAddr,
+    ) -> Result<Option<crate::service::admission::ConnectRejection>> {
+        for plugin in self.snapshot() {
+            if let Some(rejection) = plugin.on_connect(peer).await? {
+                return Ok(Some(rejection));
+            }
+        }
+        Ok(None)
+    }
+
+    /// 以各插件的配置子树触发启动钩子 / Emit startup hooks with each plugin's config subtree
+    pub async fn emit_startup(&self, server: &VConnectIMServer, config: &Value) -> Result<()> {
         for plugin in self.snapshot() {
-            plugin.on_startup(server).await?;
+            let subtree = plugin_config_subtree(config, plugin.name());
+            plugin.on_startup(server, &subtree).await?;
         }
         Ok(())
     }
@@ -182,6 +374,7 @@ impl PluginRegistry {
 mod tests {
     use super::*;
     use crate::server::VConnectIMServer;
+    use crate::testkit::{im, recv_typed, TestServer};
     use serde_json::json;
 
     struct BlockPlugin;
@@ -205,16 +398,177 @@ mod tests {
     #[tokio::test]
     async fn plugin_registry_stops_flow() {
         let registry = PluginRegistry::new();
-        registry.register(Arc::new(BlockPlugin));
+        registry.register(Arc::new(BlockPlugin)).unwrap();
         let server = VConnectIMServer::new();
         let ctx = PluginContext::new(&server, "c1");
         let mut message = ImMessage {
             msg_type: "message".into(),
             data: json!({"text":"hello"}),
             target_uid: None,
+            priority: Default::default(),
+            reply_to: None,
         };
         let res = registry.emit_incoming(&ctx, &mut message).await.unwrap();
         assert_eq!(res, PluginFlow::Stop);
         assert_eq!(message.msg_type, "blocked");
     }
+
+    struct Named(&'static str, u8, &'static [&'static str]);
+
+    #[async_trait]
+    impl Plugin for Named {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn priority(&self) -> u8 {
+            self.1
+        }
+
+        fn run_after(&self) -> &'static [&'static str] {
+            self.2
+        }
+    }
+
+    #[test]
+    fn plugin_registry_order_is_deterministic() {
+        let registry = PluginRegistry::new().with_on_duplicate(OnDuplicate::Replace);
+        registry.register(Arc::new(Named("zeta", 10, &[]))).unwrap();
+        registry
+            .register(Arc::new(Named("alpha", 10, &[])))
+            .unwrap();
+        registry
+            .register(Arc::new(Named("first", 1, &["zeta"])))
+            .unwrap();
+        assert_eq!(registry.names(), ["alpha", "zeta", "first"]);
+
+        registry
+            .register(Arc::new(Named("last", 10, &["first"])))
+            .unwrap();
+        assert_eq!(registry.names(), ["alpha", "zeta", "first", "last"]);
+
+        // first 依赖 zeta，新 zeta 又依赖 first：成环被拒绝 / first after zeta, new zeta after first: cycle rejected
+        assert!(registry
+            .register(Arc::new(Named("zeta", 0, &["first"])))
+            .is_err());
+        assert_eq!(registry.names(), ["alpha", "zeta", "first", "last"]);
+    }
+
+    #[test]
+    fn plugin_registry_handles_duplicate_names() {
+        let registry = PluginRegistry::new();
+        registry
+            .register(Arc::new(Named("audit", 10, &[])))
+            .unwrap();
+        let err = registry
+            .register(Arc::new(Named("audit", 1, &[])))
+            .unwrap_err();
+        assert!(err.to_string().contains("already registered"), "{}", err);
+        assert_eq!(registry.names(), ["audit"]);
+        assert_eq!(registry.snapshot()[0].priority(), 10);
+
+        let registry = PluginRegistry::new().with_on_duplicate(OnDuplicate::Replace);
+        registry
+            .register(Arc::new(Named("audit", 10, &[])))
+            .unwrap();
+        registry.register(Arc::new(Named("other", 5, &[]))).unwrap();
+        registry.register(Arc::new(Named("audit", 1, &[]))).unwrap();
+        assert_eq!(registry.names(), ["audit", "other"]);
+        assert_eq!(registry.snapshot()[0].priority(), 1);
+    }
+
+    /// 下行时隐去 `phone`，并拦截发往 carol 的消息 / Redacts `phone` on the way out and suppresses messages to carol
+    struct RedactPlugin;
+
+    #[async_trait]
+    impl Plugin for RedactPlugin {
+        fn name(&self) -> &'static str {
+            "redact"
+        }
+
+        async fn on_message_outgoing(
+            &self,
+            ctx: &PluginContext<'_>,
+            message: &mut ImMessage,
+        ) -> Result<PluginFlow> {
+            let recipient = ctx
+                .server
+                .and_then(|s| s.connections.get(ctx.client_id))
+                .and_then(|c| c.uid.clone());
+            if recipient.as_deref() == Some("carol") {
+                return Ok(PluginFlow::Stop);
+            }
+            if let Some(phone) = message.data.pointer_mut("/content/phone") {
+                *phone = json!("***");
+            }
+            Ok(PluginFlow::Continue)
+        }
+    }
+
+    #[tokio::test]
+    async fn outgoing_hook_redacts_and_suppresses_per_recipient() {
+        let ts = TestServer::build(|server| server.with_plugin(Arc::new(RedactPlugin)));
+        let (alice, _alice_rx) = ts.add_client("alice");
+        let (_bob, mut bob_rx) = ts.add_client("bob");
+        let (_carol, mut carol_rx) = ts.add_client("carol");
+        for uid in ["alice", "bob", "carol"] {
+            ts.server.http_join_room("r1", uid).await;
+        }
+
+        ts.send(
+            &alice,
+            im(
+                "group_message",
+                json!({"room_id": "r1", "text": "call me", "phone": "555-0100"}),
+                None,
+            ),
+        )
+        .await
+        .unwrap();
+        let received: ImMessage = recv_typed(&mut bob_rx).await;
+        assert_eq!(received.data["content"]["text"], "call me");
+        assert_eq!(received.data["content"]["phone"], "***");
+        assert!(carol_rx.try_recv().is_err());
+    }
+
+    /// 启动时读取 `threshold` 的插件 / A plugin reading `threshold` at startup
+    #[derive(Default)]
+    struct ConfigProbe {
+        threshold: parking_lot::Mutex<Option<u64>>,
+    }
+
+    #[async_trait]
+    impl Plugin for ConfigProbe {
+        fn name(&self) -> &'static str {
+            "probe"
+        }
+
+        async fn on_startup(&self, _server: &VConnectIMServer, config: &Value) -> Result<()> {
+            *self.threshold.lock() = config["threshold"].as_u64();
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn plugin_reads_config_at_startup() {
+        let probe = Arc::new(ConfigProbe::default());
+        let server = VConnectIMServer::new().with_plugin(probe.clone());
+        server
+            .start_plugins(
+                json!({"plugins": {"probe": {"threshold": 3}, "other": {"threshold": 9}}}),
+            )
+            .await
+            .unwrap();
+        assert_eq!(*probe.threshold.lock(), Some(3));
+
+        // 之后的更新可经上下文读到 / Later updates are visible through the context
+        server
+            .update_plugin_config(json!({"plugins": {"probe": {"threshold": 5}}}))
+            .await
+            .unwrap();
+        let ctx = PluginContext::new(&server, "c1");
+        assert_eq!(ctx.plugin_config("probe")["threshold"], 5);
+        assert_eq!(ctx.plugin_config("missing"), json!({}));
+        assert_eq!(*probe.threshold.lock(), Some(3));
+    }
 }
@@ -0,0 +1,116 @@
+//! 插件执行顺序 / Plugin execution order
+//!
+//! 先按 `(排序键, 名称)` 稳定排序，再满足 "run after" 依赖；依赖成环时报错。
+//! Plugins are ordered by `(sort key, name)` and then constrained by "run after"
+//! dependencies; a dependency cycle is an error.
+
+use anyhow::{anyhow, Result};
+use std::collections::BTreeSet;
+
+/// 参与排序的插件节点 / A plugin node taking part in ordering
+pub struct OrderNode<K> {
+    /// 插件名称（同键时的决胜项）/ Plugin name (tie-breaker for equal keys)
+    pub name: String,
+    /// 排序键（如优先级）/ Sort key (e.g. priority)
+    pub key: K,
+    /// 必须排在这些插件之后；未注册的名称被忽略
+    /// Must run after these plugins; unknown names are ignored
+    pub run_after: Vec<String>,
+}
+
+/// 计算执行顺序，返回节点下标 / Resolve the execution order as node indices
+///
+/// 在所有依赖已满足的节点中总是先选 `(key, name)` 最小者，
+/// 因此没有依赖时结果等同于按 `(key, name)` 排序，且每次重启都一致。
+/// Among nodes whose dependencies are satisfied the smallest `(key, name)` always
+/// goes first, so without dependencies this equals sorting by `(key, name)` and is
+/// identical across restarts.
+pub fn resolve_order<K: Ord>(nodes: &[OrderNode<K>]) -> Result<Vec<usize>> {
+    let n = nodes.len();
+    let mut indegree = vec![0usize; n];
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for (i, node) in nodes.iter().enumerate() {
+        for dep in &node.run_after {
+            for (j, other) in nodes.iter().enumerate() {
+                if &other.name == dep {
+                    dependents[j].push(i);
+                    indegree[i] += 1;
+                }
+            }
+        }
+    }
+
+    let mut ready: BTreeSet<(&K, &str, usize)> = (0..n)
+        .filter(|&i| indegree[i] == 0)
+        .map(|i| (&nodes[i].key, nodes[i].name.as_str(), i))
+        .collect();
+    let mut order = Vec::with_capacity(n);
+    while let Some((_, _, i)) = ready.pop_first() {
+        order.push(i);
+        for &d in &dependents[i] {
+            indegree[d] -= 1;
+            if indegree[d] == 0 {
+                ready.insert((&nodes[d].key, nodes[d].name.as_str(), d));
+            }
+        }
+    }
+
+    if order.len() < n {
+        let mut cyclic: Vec<&str> = (0..n)
+            .filter(|&i| indegree[i] > 0)
+            .map(|i| nodes[i].name.as_str())
+            .collect();
+        cyclic.sort_unstable();
+        return Err(anyhow!(
+            "插件依赖成环 / Plugin dependency cycle among: {}",
+            cyclic.join(", ")
+        ));
+    }
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, key: i32, run_after: &[&str]) -> OrderNode<i32> {
+        OrderNode {
+            name: name.to_string(),
+            key,
+            run_after: run_after.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn names(nodes: &[OrderNode<i32>]) -> Vec<&str> {
+        resolve_order(nodes)
+            .unwrap()
+            .into_iter()
+            .map(|i| nodes[i].name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_ties_break_by_name_and_dependencies_win() {
+        let nodes = [node("zeta", 1, &[]), node("alpha", 1, &[]), node("mid", 0, &[])];
+        assert_eq!(names(&nodes), ["mid", "alpha", "zeta"]);
+
+        // mid 必须在 zeta 之后 / mid must run after zeta
+        let nodes = [
+            node("zeta", 1, &[]),
+            node("alpha", 1, &[]),
+            node("mid", 0, &["zeta", "missing"]),
+        ];
+        assert_eq!(names(&nodes), ["alpha", "zeta", "mid"]);
+    }
+
+    #[test]
+    fn test_cycle_is_reported() {
+        let nodes = [
+            node("a", 0, &["b"]),
+            node("b", 0, &["a"]),
+            node("c", 0, &[]),
+        ];
+        let err = resolve_order(&nodes).unwrap_err().to_string();
+        assert!(err.contains("a, b"), "{}", err);
+    }
+}
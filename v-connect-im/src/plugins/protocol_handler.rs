@@ -2,15 +2,16 @@
 //!
 //! 支持 Protobuf 协议的服务端实现 / Server-side Protobuf protocol support
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use prost::Message;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::UnixStream;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use v::plugin::protocol::{
-    negotiate_protocol, EventMessage, EventResponse, HandshakeRequest, HandshakeResponse,
-    ProtocolFormat,
+    handshake_protocol_version, is_supported_protocol_version, negotiate_protocol, EventMessage,
+    EventResponse, HandshakeRequest, HandshakeResponse, ProtocolFormat, HANDSHAKE_INCOMPATIBLE,
+    MIN_PROTOCOL_VERSION, PROTOCOL_VERSION,
 };
 
 /// 协议处理会话 / Protocol handler session
@@ -47,6 +48,25 @@ impl ProtocolSession {
             request.name, request.version, request.priority, request.protocol, request.capabilities
         );
 
+        // 协议版本检查 / Protocol version check
+        let version = handshake_protocol_version(request.protocol_version);
+        if !is_supported_protocol_version(version) {
+            let message = format!(
+                "protocol version {} not supported (host supports {}..={})",
+                version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION
+            );
+            warn!("❌ Plugin {} rejected: {}", request.name, message);
+            self.write_handshake_response(HandshakeResponse {
+                status: HANDSHAKE_INCOMPATIBLE.to_string(),
+                message: message.clone(),
+                config: String::new(),
+                protocol: String::new(),
+                protocol_version: PROTOCOL_VERSION,
+            })
+            .await?;
+            return Err(anyhow!(message));
+        }
+
         // 协议协商 / Protocol negotiation
         let negotiated = negotiate_protocol(&request.protocol);
         if negotiated != self.protocol {
@@ -58,20 +78,25 @@ impl ProtocolSession {
         }
 
         // 发送握手响应 / Send handshake response
-        let response = HandshakeResponse {
+        self.write_handshake_response(HandshakeResponse {
             status: "ok".to_string(),
             message: String::new(),
             config: String::new(),
             protocol: format!("{:?}", self.protocol).to_lowercase(),
-        };
+            protocol_version: version,
+        })
+        .await?;
 
-        // 使用 prost 编码握手响应 / Encode handshake response using prost
+        Ok(request)
+    }
+
+    /// 使用 prost 编码并发送握手响应 / Encode and send handshake response using prost
+    async fn write_handshake_response(&mut self, response: HandshakeResponse) -> Result<()> {
         let resp_bytes = response.encode_to_vec();
         self.stream.write_u32(resp_bytes.len() as u32).await?;
         self.stream.write_all(&resp_bytes).await?;
         self.stream.flush().await?;
-
-        Ok(request)
+        Ok(())
     }
 
     /// 发送事件 / Send event
@@ -10,20 +10,55 @@ use parking_lot::RwLock;
 use serde_json::Value;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::ops::Range;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::{UnixListener, UnixStream};
+use tokio::net::UnixListener;
 use tokio::process::{Child, Command};
 use tokio::sync::watch;
 use tokio::time::sleep;
 use tracing::{debug, error, info, warn};
 
+use super::call_metrics::PluginCallMetrics;
+use super::limits::{AppliedLimits, ResourceLimits};
+use super::logs::{
+    spawn_log_pump, LogRing, LogRotation, RotatingFile, DEFAULT_LOG_BUFFER_LINES, PLUGIN_LOG_DIR,
+};
+use super::order::{resolve_order, OrderNode};
+use super::socket_perms::SocketPermissions;
+use crate::service::storage_fallback::{OnUnavailable, StorageFallback};
+use crate::storage::builtin::BuiltinStorage;
+use crate::storage::MessageRecord;
+use v::plugin::client::{PluginIo, PluginStream};
 use v::plugin::installer::PluginInstaller;
+use v::plugin::manifest::PluginManifest;
 use prost::Message; // For Protobuf decoding
 
+/// 握手帧长度上限，超出即断开，未认证的对端无法迫使主机分配大块内存
+/// Max handshake frame length; larger frames drop the connection, so an unauthenticated peer
+/// cannot make the host allocate large buffers
+const MAX_HANDSHAKE_FRAME: u32 = 64 * 1024;
+/// 连接后等待握手的时限 / How long a connection may take to send its handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 读取一帧握手（长度前缀 + 内容），拒绝超长帧 / Read one handshake frame (length prefix + body), refusing oversized frames
+async fn read_handshake_frame<S: PluginIo>(stream: &mut S) -> std::io::Result<Vec<u8>> {
+    let len = stream.read_u32().await?;
+    if len > MAX_HANDSHAKE_FRAME {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("handshake frame of {} bytes exceeds {}", len, MAX_HANDSHAKE_FRAME),
+        ));
+    }
+    let mut buffer = vec![0u8; len as usize];
+    stream.read_exact(&mut buffer).await?;
+    Ok(buffer)
+}
+
 /// 插件状态 / Plugin status
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum PluginStatus {
     /// 已安装但未启动 / Installed but not started
     Installed,
@@ -50,6 +85,9 @@ pub struct PluginRuntime {
     pub last_heartbeat: Arc<RwLock<Option<Instant>>>,
     pub capabilities: Arc<RwLock<Vec<String>>>, // 插件能力 / Plugin capabilities
     pub priority: Arc<RwLock<i32>>,             // 插件优先级 / Plugin priority
+    pub run_after: Vec<String>, // 需在这些插件之后执行 / Must run after these plugins
+    pub protocol_version: Arc<RwLock<Option<u32>>>, // 握手协商的协议版本 / Negotiated protocol version
+    pub ready: Arc<RwLock<bool>>, // 已握手并注册到连接池 / Handshaken and registered in the pool
 }
 
 impl PluginRuntime {
@@ -69,6 +107,9 @@ impl PluginRuntime {
             last_heartbeat: Arc::new(RwLock::new(None)),
             capabilities: Arc::new(RwLock::new(Vec::new())),
             priority: Arc::new(RwLock::new(0)),
+            run_after: Vec::new(),
+            protocol_version: Arc::new(RwLock::new(None)),
+            ready: Arc::new(RwLock::new(false)),
         }
     }
 
@@ -101,6 +142,21 @@ impl PluginRuntime {
     pub fn set_status(&self, status: PluginStatus) {
         *self.status.write() = status;
     }
+
+    /// 记录协商的协议版本 / Record the negotiated protocol version
+    pub fn set_protocol_version(&self, version: u32) {
+        *self.protocol_version.write() = Some(version);
+    }
+
+    /// 协商的协议版本（未握手为 None）/ Negotiated protocol version (None before handshake)
+    pub fn protocol_version(&self) -> Option<u32> {
+        *self.protocol_version.read()
+    }
+
+    /// 是否已就绪 / Whether the plugin is ready
+    pub fn is_ready(&self) -> bool {
+        *self.ready.read()
+    }
 }
 
 /// 插件运行时管理器 / Plugin runtime manager
@@ -111,21 +167,63 @@ pub struct PluginRuntimeManager {
     global_socket_path: Option<PathBuf>,
     debug_mode: bool,          // Debug 模式 / Debug mode
     log_level: Option<String>, // 日志级别 / Log level
+    plugin_transports: std::collections::HashMap<String, String>, // 插件传输方式 / Per-plugin transport
+    tcp_auth_token: Option<String>, // TCP 认证令牌 / TCP auth token
+    limits: ResourceLimits,         // 资源限制 / Resource limits
+    log_buffers: DashMap<String, Arc<LogRing>>, // 最近日志（跨重启保留）/ Recent logs (kept across restarts)
+    log_buffer_lines: usize,                    // 每个插件保留的行数 / Lines kept per plugin
+    log_rotation: LogRotation,                  // 日志滚动策略 / Log rotation policy
+    ready_notify: tokio::sync::Notify,          // 就绪变化通知 / Readiness change notification
+    stop_grace: Duration,                       // 停止插件的等待上限 / Max wait when stopping plugins
 }
 
-/// 插件元数据 / Plugin metadata
+/// 插件元数据（宽松读取 plugin.json，缺失或类型不符的字段为 None）
+/// Plugin metadata (plugin.json read leniently; missing or mistyped fields are None)
 #[derive(Clone, Default)]
 struct PluginMetadata {
     plugin_no: Option<String>,
     version: Option<String>,
+    capabilities: Option<Vec<String>>,
+    priority: Option<i32>,
+}
+
+impl PluginMetadata {
+    /// plugin.json 与握手声明不一致之处 / Where plugin.json disagrees with the handshake
+    fn mismatches(&self, capabilities: &[String], priority: i32) -> Vec<String> {
+        let mut mismatches = Vec::new();
+        if let Some(declared) = &self.capabilities {
+            let mut declared = declared.clone();
+            let mut advertised = capabilities.to_vec();
+            declared.sort();
+            advertised.sort();
+            if declared != advertised {
+                mismatches.push(format!(
+                    "capabilities {:?} in plugin.json, {:?} at handshake",
+                    declared, advertised
+                ));
+            }
+        }
+        if let Some(declared) = self.priority.filter(|p| *p != priority) {
+            mismatches.push(format!(
+                "priority {} in plugin.json, {} at handshake",
+                declared, priority
+            ));
+        }
+        mismatches
+    }
 }
 
 /// 运行时插件摘要 / Runtime plugin summary info
-#[derive(Clone)]
+#[derive(Clone, serde::Serialize)]
 pub struct PluginRuntimeSummary {
     pub name: String,
     pub version: Option<String>,
     pub status: PluginStatus,
+    pub protocol_version: Option<u32>,
+    /// 握手前取自 plugin.json，握手后为插件声明的值
+    /// From plugin.json before the handshake, as advertised by the plugin after it
+    pub capabilities: Vec<String>,
+    pub priority: i32,
 }
 
 impl PluginRuntimeManager {
@@ -138,9 +236,23 @@ impl PluginRuntimeManager {
             global_socket_path: None,
             debug_mode: false,
             log_level: None,
+            plugin_transports: std::collections::HashMap::new(),
+            tcp_auth_token: None,
+            limits: ResourceLimits::default(),
+            log_buffers: DashMap::new(),
+            log_buffer_lines: DEFAULT_LOG_BUFFER_LINES,
+            log_rotation: LogRotation::default(),
+            ready_notify: tokio::sync::Notify::new(),
+            stop_grace: Duration::from_secs(5),
         }
     }
 
+    /// 设置停止插件时的等待上限（`shutdown.plugin_grace_ms`）
+    /// Set the max wait when stopping plugins (`shutdown.plugin_grace_ms`)
+    pub fn set_stop_grace(&mut self, grace: Duration) {
+        self.stop_grace = grace;
+    }
+
     /// 设置 debug 模式 / Set debug mode
     pub fn set_debug_mode(&mut self, debug: bool) {
         self.debug_mode = debug;
@@ -156,6 +268,42 @@ impl PluginRuntimeManager {
         self.global_socket_path = Some(path.as_ref().to_path_buf());
     }
 
+    /// 设置插件传输方式：`unix`（默认）或 `tcp://host:port`
+    /// Set a plugin's transport: `unix` (default) or `tcp://host:port`
+    pub fn set_plugin_transport(&mut self, name: impl Into<String>, transport: impl Into<String>) {
+        self.plugin_transports.insert(name.into(), transport.into());
+    }
+
+    /// 设置下发给 TCP 插件的认证令牌 / Set the auth token handed to TCP plugins
+    pub fn set_tcp_auth_token(&mut self, token: String) {
+        self.tcp_auth_token = Some(token);
+    }
+
+    /// 设置插件进程资源限制 / Set resource limits for plugin processes
+    pub fn set_resource_limits(&mut self, limits: ResourceLimits) {
+        self.limits = limits;
+    }
+
+    /// 设置每个插件在内存中保留的日志行数 / Set how many log lines are kept in memory per plugin
+    pub fn set_log_buffer_lines(&mut self, lines: usize) {
+        self.log_buffer_lines = lines.max(1);
+    }
+
+    /// 设置日志文件滚动策略 / Set the log file rotation policy
+    pub fn set_log_rotation(&mut self, rotation: LogRotation) {
+        self.log_rotation = rotation;
+    }
+
+    /// 插件最近的 `lines` 行日志（旧 → 新）；插件未知时返回 None
+    /// The plugin's last `lines` log lines (oldest first); None for an unknown plugin
+    pub fn recent_logs(&self, name: &str, lines: usize) -> Option<Vec<String>> {
+        match self.log_buffers.get(name) {
+            Some(ring) => Some(ring.tail(lines)),
+            None if self.plugins.contains_key(name) => Some(Vec::new()),
+            None => None,
+        }
+    }
+
     /// 注册开发模式插件 / Register development mode plugin
     pub fn register_dev_plugin(&self, name: String, cargo_project_path: PathBuf) -> Result<()> {
         info!(
@@ -164,6 +312,9 @@ impl PluginRuntimeManager {
             cargo_project_path.display()
         );
 
+        if self.plugins.contains_key(&name) {
+            return Err(anyhow!("Plugin {} is already registered", name));
+        }
         let socket_path = self.global_socket_path.clone();
         let runtime = PluginRuntime::new(
             name.clone(),
@@ -212,6 +363,15 @@ impl PluginRuntimeManager {
             }
         }
 
+        // 握手按 plugin_no 匹配运行时，重复时无法区分 / Handshakes match runtimes by plugin_no, so it must be unique
+        self.ensure_unique_plugin_no(name)?;
+
+        // 校验 plugin.json，无效时拒绝启动 / Validate plugin.json; refuse to start on violations
+        let manifest = PluginManifest::load(&self.plugin_dir.join(name))?;
+        for warning in manifest.warnings() {
+            warn!("⚠️  插件 {} 清单警告 / Plugin {} manifest: {}", name, name, warning);
+        }
+
         // 查找插件二进制文件 / Find plugin binary
         debug!("查找插件二进制文件 / Looking for plugin binary: {}", name);
         let plugin_path = self.find_plugin_binary(name)?;
@@ -225,19 +385,28 @@ impl PluginRuntimeManager {
             }
             path
         };
-        let owned_socket = if self.global_socket_path.is_some() {
+        // 配置为 TCP 的插件连接 TCP 地址 / Plugins configured for TCP dial the TCP address
+        let tcp_transport = self
+            .plugin_transports
+            .get(name)
+            .filter(|t| v::plugin::protocol::tcp_address(t).is_some())
+            .cloned();
+        let owned_socket = if self.global_socket_path.is_some() || tcp_transport.is_some() {
             None
         } else {
             Some(socket_path.clone())
         };
 
-        let metadata = self.read_plugin_metadata(name);
-        let runtime = PluginRuntime::new(
+        let mut runtime = PluginRuntime::new(
             name.to_string(),
             plugin_path,
-            metadata.version.clone(),
+            Some(manifest.version),
             owned_socket.clone(),
         );
+        runtime.run_after = manifest.run_after;
+        // 握手前按 plugin.json 排序与规划，握手时以插件声明为准 / Plan with plugin.json until the handshake overrides it
+        runtime.set_capabilities(manifest.capabilities);
+        runtime.set_priority(manifest.priority.unwrap_or(0));
         runtime.set_status(PluginStatus::Starting);
 
         // 启动插件进程 / Start plugin process
@@ -257,49 +426,46 @@ impl PluginRuntimeManager {
         };
 
         // 创建插件日志目录 / Create plugin log directory
-        let log_dir = PathBuf::from("./logs/plugins").join(name);
+        let log_dir = PathBuf::from(PLUGIN_LOG_DIR).join(name);
         if let Err(e) = std::fs::create_dir_all(&log_dir) {
             warn!("Failed to create plugin log directory {:?}: {}", log_dir, e);
         }
 
-        // 创建日志文件 / Create log files
-        let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
-        let stdout_log = log_dir.join(format!("stdout_{}.log", timestamp));
-        let stderr_log = log_dir.join(format!("stderr_{}.log", timestamp));
-
-        let stdout_file = match std::fs::File::create(&stdout_log) {
-            Ok(f) => {
-                info!("📝 Plugin {} stdout log: {:?}", name, stdout_log);
-                Stdio::from(f)
-            }
-            Err(e) => {
-                warn!(
-                    "Failed to create stdout log file {:?}: {}, using inherit",
-                    stdout_log, e
-                );
-                Stdio::inherit()
-            }
-        };
-
-        let stderr_file = match std::fs::File::create(&stderr_log) {
-            Ok(f) => {
-                info!("📝 Plugin {} stderr log: {:?}", name, stderr_log);
-                Stdio::from(f)
-            }
-            Err(e) => {
-                warn!(
-                    "Failed to create stderr log file {:?}: {}, using inherit",
-                    stderr_log, e
-                );
-                Stdio::inherit()
+        // 打开（按大小滚动的）日志文件，重启时复用 / Open the size-rotated log files, reused across restarts
+        let open_log = |stream: &str| {
+            let path = log_dir.join(format!("{}.log", stream));
+            match RotatingFile::open(&path, self.log_rotation) {
+                Ok(f) => {
+                    info!("📝 Plugin {} {} log: {:?}", name, stream, path);
+                    Some(f)
+                }
+                Err(e) => {
+                    warn!(
+                        "Failed to open {} log file {:?}: {}, keeping memory buffer only",
+                        stream, path, e
+                    );
+                    None
+                }
             }
         };
+        let stdout_file = open_log("stdout");
+        let stderr_file = open_log("stderr");
 
+        let address = tcp_transport
+            .clone()
+            .unwrap_or_else(|| socket_path.to_string_lossy().to_string());
         cmd.arg("--socket")
-            .arg(socket_path.to_string_lossy().as_ref())
+            .arg(&address)
             .stdin(Stdio::null())
-            .stdout(stdout_file)
-            .stderr(stderr_file);
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+
+        if tcp_transport.is_some() {
+            info!("🌐 Plugin {} uses TCP transport: {}", name, address);
+            if let Some(ref token) = self.tcp_auth_token {
+                cmd.env(v::plugin::protocol::AUTH_TOKEN_ENV, token);
+            }
+        }
 
         // 添加 debug 参数 / Add debug arguments
         if self.debug_mode {
@@ -313,8 +479,27 @@ impl PluginRuntimeManager {
             info!("Starting plugin {} with log level: {}", name, level);
         }
 
+        // 资源限制（仅 Linux）/ Resource limits (Linux only)
+        let applied_limits = AppliedLimits::prepare(name, &self.limits, &mut cmd).map(Arc::new);
+
         match cmd.spawn() {
-            Ok(child) => {
+            Ok(mut child) => {
+                if let Some(ref limits) = applied_limits {
+                    limits.attach(child.id());
+                }
+
+                // 输出写入日志文件并保留最近若干行 / Tee output into log files and the recent-lines buffer
+                let ring = self
+                    .log_buffers
+                    .entry(name.to_string())
+                    .or_insert_with(|| Arc::new(LogRing::new(self.log_buffer_lines)))
+                    .clone();
+                if let Some(stdout) = child.stdout.take() {
+                    spawn_log_pump(stdout, "stdout", stdout_file, ring.clone());
+                }
+                if let Some(stderr) = child.stderr.take() {
+                    spawn_log_pump(stderr, "stderr", stderr_file, ring);
+                }
                 let child_arc = Arc::new(RwLock::new(Some(child)));
                 // 存储进程引用 / Store process reference (实际句柄在 child_arc 中)
                 *runtime.process.write() = None;
@@ -326,6 +511,7 @@ impl PluginRuntimeManager {
                 let status_clone = runtime.status.clone();
                 let last_heartbeat_clone = runtime.last_heartbeat.clone();
                 let process_clone = runtime.process.clone();
+                let limits_clone = applied_limits.clone();
                 tokio::spawn(async move {
                     // 将 child 移动到 process 中 / Move child to process
                     if let Some(child) = child_arc.write().take() {
@@ -336,6 +522,7 @@ impl PluginRuntimeManager {
                         process_clone,
                         status_clone,
                         last_heartbeat_clone,
+                        limits_clone,
                     )
                     .await;
                 });
@@ -345,6 +532,9 @@ impl PluginRuntimeManager {
                 Ok(())
             }
             Err(e) => {
+                if let Some(ref limits) = applied_limits {
+                    limits.release();
+                }
                 runtime.set_status(PluginStatus::Error(e.to_string()));
                 Err(anyhow!("Failed to start plugin {}: {}", name, e))
             }
@@ -367,8 +557,8 @@ impl PluginRuntimeManager {
                 if let Err(e) = child.kill().await {
                     error!("Failed to kill plugin {}: {}", name, e);
                 } else {
-                    // 等待进程退出，最多等待 3 秒 / Wait for process exit, max 3 seconds
-                    match tokio::time::timeout(Duration::from_secs(3), child.wait()).await {
+                    // 等待进程退出，最多 stop_grace / Wait for process exit, at most stop_grace
+                    match tokio::time::timeout(self.stop_grace, child.wait()).await {
                         Ok(Ok(status)) => {
                             info!(
                                 "✅ 插件 {} 已退出 / Plugin {} exited with status: {:?}",
@@ -478,52 +668,7 @@ impl PluginRuntimeManager {
 
     /// 查找插件二进制文件 / Find plugin binary
     fn find_plugin_binary(&self, name: &str) -> Result<PathBuf> {
-        let plugin_dir = self.plugin_dir.join(name);
-
-        if !plugin_dir.exists() {
-            return Err(anyhow!("Plugin directory not found: {:?}", plugin_dir));
-        }
-
-        // 查找可执行文件 / Find executable
-        let exe_name = if cfg!(target_os = "windows") {
-            format!("{}.exe", name)
-        } else {
-            name.to_string()
-        };
-
-        let exe_path = plugin_dir.join(&exe_name);
-        if exe_path.exists() && exe_path.is_file() {
-            return Ok(exe_path);
-        }
-
-        // 尝试查找其他可能的二进制文件 / Try to find other possible binaries
-        let entries = std::fs::read_dir(&plugin_dir)?;
-        for entry in entries {
-            let entry = entry?;
-            let path = entry.path();
-            if path.is_file() {
-                // 检查是否有执行权限（Unix）或是否为 .exe（Windows）
-                // Check if executable (Unix) or .exe (Windows)
-                #[cfg(unix)]
-                {
-                    use std::os::unix::fs::PermissionsExt;
-                    if let Ok(metadata) = path.metadata() {
-                        let perms = metadata.permissions();
-                        if perms.mode() & 0o111 != 0 {
-                            return Ok(path);
-                        }
-                    }
-                }
-                #[cfg(windows)]
-                {
-                    if path.extension().and_then(|s| s.to_str()) == Some("exe") {
-                        return Ok(path);
-                    }
-                }
-            }
-        }
-
-        Err(anyhow!("Plugin binary not found in {:?}", plugin_dir))
+        find_plugin_binary(&self.plugin_dir, name)
     }
 
     /// 监控插件进程 / Monitor plugin process
@@ -532,6 +677,7 @@ impl PluginRuntimeManager {
         process: Arc<RwLock<Option<Child>>>,
         status: Arc<RwLock<PluginStatus>>,
         last_heartbeat: Arc<RwLock<Option<Instant>>>,
+        limits: Option<Arc<AppliedLimits>>,
     ) {
         loop {
             sleep(Duration::from_secs(1)).await;
@@ -542,6 +688,10 @@ impl PluginRuntimeManager {
                     Ok(Some(exit_status)) => {
                         if exit_status.success() {
                             info!("Plugin {} exited successfully", name);
+                        } else if let Some(l) = limits.as_ref().filter(|l| l.oom_killed()) {
+                            let message = l.oom_message();
+                            error!("💥 Plugin {} was OOM-killed: {}", name, message);
+                            *status.write() = PluginStatus::Error(message);
                         } else {
                             error!("Plugin {} exited with error: {:?}", name, exit_status);
                             *status.write() =
@@ -565,22 +715,87 @@ impl PluginRuntimeManager {
                 break;
             }
         }
+
+        if let Some(limits) = limits {
+            limits.release();
+        }
     }
 
-    /// 启动所有已安装的插件 / Start all installed plugins
-    pub async fn start_all(&self) -> Result<()> {
+    /// 启动所有已安装的插件，返回成功启动的插件名
+    /// Start all installed plugins, returning the names that were started
+    pub async fn start_all(&self) -> Result<Vec<String>> {
         let installed = self.discover_plugins().await?;
 
+        let mut started = Vec::new();
         for name in installed {
-            if let Err(e) = self.start_plugin(&name).await {
-                error!("Failed to start plugin {}: {}", name, e);
+            match self.start_plugin(&name).await {
+                Ok(()) => started.push(name),
+                Err(e) => error!("Failed to start plugin {}: {}", name, e),
             }
         }
 
-        Ok(())
+        Ok(started)
+    }
+
+    /// 是否安装了存储插件（名称含 `storage` 或声明了 storage 能力）
+    /// Whether a storage plugin is installed (named `*storage*` or declaring the storage capability)
+    pub fn has_storage_plugin(&self) -> bool {
+        self.plugins.iter().any(|entry| {
+            entry.key().contains("storage")
+                || entry.value().capabilities().iter().any(|c| c == "storage")
+        })
+    }
+
+    /// 标记插件就绪状态（连接池注册/移除时调用）
+    /// Mark a plugin's readiness (called when the pool registers/removes it)
+    pub fn mark_ready(&self, name: &str, ready: bool) {
+        if let Some(runtime) = self.plugins.get(name) {
+            *runtime.ready.write() = ready;
+        }
+        self.ready_notify.notify_waiters();
+    }
+
+    /// 等待插件完成握手并注册到连接池，返回超时仍未就绪的插件
+    /// 已退出（Error/Stopped）的插件不再等待。
+    /// Wait until the plugins have handshaken and registered in the pool; returns the
+    /// ones still not ready at the timeout. Plugins that already exited (Error/Stopped)
+    /// are not waited for.
+    pub async fn wait_ready(&self, names: &[String], timeout: Duration) -> Vec<String> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let notified = self.ready_notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let mut pending = Vec::new();
+            let mut waiting = false;
+            for name in names {
+                let Some(runtime) = self.plugins.get(name) else {
+                    pending.push(name.clone());
+                    continue;
+                };
+                if runtime.is_ready() {
+                    continue;
+                }
+                pending.push(name.clone());
+                waiting |= matches!(
+                    runtime.status(),
+                    PluginStatus::Starting | PluginStatus::Running
+                );
+            }
+            if !waiting || tokio::time::Instant::now() >= deadline {
+                return pending;
+            }
+
+            // 进程退出不会通知，定期复查 / Process exits don't notify, so re-check periodically
+            let recheck = (tokio::time::Instant::now() + Duration::from_millis(200)).min(deadline);
+            let _ = tokio::time::timeout_at(recheck, notified).await;
+        }
     }
 
-    /// 停止所有插件 / Stop all plugins
+    /// 停止所有插件：先停其他插件，最后停存储插件，避免停机期间的写入丢失
+    /// Stop all plugins: the others first and storage plugins last, so writes made while
+    /// shutting down are not lost
     pub async fn stop_all(&self) -> Result<()> {
         let names: Vec<String> = self.plugins.iter().map(|e| e.key().clone()).collect();
 
@@ -595,12 +810,30 @@ impl PluginRuntimeManager {
             names.len()
         );
 
-        // 并发停止所有插件，最多等待 5 秒 / Stop all plugins concurrently, max 5 seconds
+        let (storage, others): (Vec<String>, Vec<String>) = names.into_iter().partition(|name| {
+            self.plugins
+                .get(name)
+                .is_some_and(|r| r.capabilities().iter().any(|c| c == "storage"))
+        });
+        self.stop_group(&others).await;
+        self.stop_group(&storage).await;
+        Ok(())
+    }
+
+    /// 并发停止一组插件 / Stop a group of plugins concurrently
+    async fn stop_group(&self, names: &[String]) {
+        if names.is_empty() {
+            return;
+        }
+        // 并发停止所有插件，最多等待 stop_grace / Stop all plugins concurrently, waiting at most stop_grace
         debug!("📦 创建停止任务 / Creating stop tasks");
         let stop_futures: Vec<_> = names.iter().map(|name| self.stop_plugin(name)).collect();
 
-        debug!("⏳ 等待所有插件停止（最多5秒）/ Waiting for all plugins to stop (max 5s)");
-        match tokio::time::timeout(Duration::from_secs(5), future::join_all(stop_futures)).await {
+        debug!(
+            "⏳ 等待所有插件停止（最多 {:?}）/ Waiting for all plugins to stop (max {:?})",
+            self.stop_grace, self.stop_grace
+        );
+        match tokio::time::timeout(self.stop_grace, future::join_all(stop_futures)).await {
             Ok(results) => {
                 debug!("✅ 所有插件停止任务完成 / All plugin stop tasks completed");
                 let mut success_count = 0;
@@ -621,16 +854,19 @@ impl PluginRuntimeManager {
                       success_count, error_count, success_count, error_count);
             }
             Err(_) => {
-                warn!("⏰ 停止插件超时（5秒），继续关闭 / Stop plugins timeout (5s), continuing shutdown");
+                warn!(
+                    "⏰ 停止插件超时（{:?}），继续关闭 / Stop plugins timeout ({:?}), continuing shutdown",
+                    self.stop_grace, self.stop_grace
+                );
             }
         }
-
-        Ok(())
     }
 
-    /// 获取运行时摘要 / Collect runtime summaries
+    /// 获取运行时摘要（含已安装但未启动的插件，按名称排序）
+    /// Collect runtime summaries, including installed plugins not started yet, sorted by name
     pub fn runtime_summaries(&self) -> Vec<PluginRuntimeSummary> {
-        self.plugins
+        let mut summaries: Vec<_> = self
+            .plugins
             .iter()
             .map(|entry| {
                 let runtime = entry.value();
@@ -638,26 +874,124 @@ impl PluginRuntimeManager {
                     name: runtime.name.clone(),
                     version: runtime.version.clone(),
                     status: runtime.status(),
+                    protocol_version: runtime.protocol_version(),
+                    capabilities: runtime.capabilities(),
+                    priority: runtime.priority(),
                 }
             })
+            .collect();
+        let installed = PluginInstaller::new(&self.plugin_dir)
+            .list_installed()
+            .unwrap_or_default();
+        for name in installed {
+            if self.plugins.contains_key(&name) {
+                continue;
+            }
+            let metadata = self.read_plugin_metadata(&name);
+            summaries.push(PluginRuntimeSummary {
+                name,
+                version: metadata.version,
+                status: PluginStatus::Installed,
+                protocol_version: None,
+                capabilities: metadata.capabilities.unwrap_or_default(),
+                priority: metadata.priority.unwrap_or(0),
+            });
+        }
+        summaries.sort_by(|a, b| a.name.cmp(&b.name));
+        summaries
+    }
+
+    /// 按执行顺序列出插件（名称、优先级、能力）/ List plugins in execution order (name, priority, capabilities)
+    ///
+    /// 优先级降序、同优先级按名称，再满足 plugin.json 的 `run_after`；
+    /// 依赖成环时记录错误并忽略依赖。
+    /// Priority descending, ties by name, then `run_after` from plugin.json;
+    /// on a dependency cycle the error is logged and dependencies are ignored.
+    pub fn ordered_plugins(&self) -> Vec<(String, i32, Vec<String>)> {
+        let plugins: Vec<_> = self
+            .plugins
+            .iter()
+            .map(|entry| {
+                let runtime = entry.value();
+                (
+                    entry.key().clone(),
+                    runtime.priority(),
+                    runtime.capabilities(),
+                    runtime.run_after.clone(),
+                )
+            })
+            .collect();
+
+        let nodes = |with_deps: bool| -> Vec<OrderNode<std::cmp::Reverse<i32>>> {
+            plugins
+                .iter()
+                .map(|(name, priority, _, run_after)| OrderNode {
+                    name: name.clone(),
+                    key: std::cmp::Reverse(*priority),
+                    run_after: if with_deps { run_after.clone() } else { Vec::new() },
+                })
+                .collect()
+        };
+        let order = resolve_order(&nodes(true)).unwrap_or_else(|e| {
+            error!("❌ {}，忽略 run_after / ignoring run_after", e);
+            resolve_order(&nodes(false)).unwrap_or_default()
+        });
+
+        order
+            .into_iter()
+            .map(|i| {
+                let (name, priority, capabilities, _) = &plugins[i];
+                (name.clone(), *priority, capabilities.clone())
+            })
             .collect()
     }
 
+    /// 其他已注册插件解析到相同 plugin_no 时报错
+    /// Error when another registered plugin resolves to the same plugin_no
+    fn ensure_unique_plugin_no(&self, name: &str) -> Result<()> {
+        let Some(plugin_no) = self.read_plugin_metadata(name).plugin_no else {
+            return Ok(());
+        };
+        let clash = self
+            .plugins
+            .iter()
+            .map(|entry| entry.key().clone())
+            .find(|other| {
+                other != name
+                    && self.read_plugin_metadata(other).plugin_no.as_deref() == Some(&plugin_no)
+            });
+        match clash {
+            Some(other) => Err(anyhow!(
+                "plugin_no {} of plugin {} is already used by plugin {}",
+                plugin_no,
+                name,
+                other
+            )),
+            None => Ok(()),
+        }
+    }
+
     fn read_plugin_metadata(&self, name: &str) -> PluginMetadata {
         let manifest = self.plugin_dir.join(name).join("plugin.json");
         if let Ok(content) = std::fs::read_to_string(&manifest) {
             if let Ok(value) = serde_json::from_str::<serde_json::Value>(&content) {
-                let plugin_no = value
-                    .get("plugin_no")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string());
-
-                let version = value
-                    .get("version")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string());
-
-                return PluginMetadata { plugin_no, version };
+                let text = |key: &str| value.get(key).and_then(|v| v.as_str()).map(|s| s.to_string());
+                let capabilities = value.get("capabilities").and_then(|v| v.as_array()).map(|caps| {
+                    caps.iter()
+                        .filter_map(|c| c.as_str().map(|s| s.to_string()))
+                        .collect()
+                });
+                let priority = value
+                    .get("priority")
+                    .and_then(|v| v.as_i64())
+                    .and_then(|p| i32::try_from(p).ok());
+
+                return PluginMetadata {
+                    plugin_no: text("plugin_no"),
+                    version: text("version"),
+                    capabilities,
+                    priority,
+                };
             }
         }
         PluginMetadata::default()
@@ -691,7 +1025,8 @@ impl UnixSocketServer {
             std::fs::remove_file(socket_path)?;
         }
 
-        let listener = UnixListener::bind(socket_path)?;
+        // 权限收紧后才出现在配置的路径上，失败则不提供服务 / Only appears at the configured path once tightened; refuse to serve on failure
+        let listener = SocketPermissions::from_config().bind(socket_path)?;
         info!("Unix Socket server listening on: {:?}", socket_path);
 
         let connection_pool = Arc::new(PluginConnectionPool::new(plugin_manager.clone()));
@@ -720,7 +1055,7 @@ impl UnixSocketServer {
                             let manager = self.plugin_manager.clone();
                             let pool = self.connection_pool.clone();
                             tokio::spawn(async move {
-                                if let Err(e) = Self::handle_connection(stream, manager, pool).await {
+                                if let Err(e) = Self::handle_connection(stream, manager, pool, None).await {
                                     error!("Error handling Unix Socket connection: {}", e);
                                 }
                             });
@@ -743,31 +1078,41 @@ impl UnixSocketServer {
     }
 
     /// 处理连接 / Handle connection
-    async fn handle_connection(
-        stream: UnixStream,
+    ///
+    /// `auth_token` 非空时握手必须携带相同令牌（TCP 传输），否则回复 unauthorized 并断开。
+    /// When `auth_token` is set the handshake must carry the same token (TCP transport);
+    /// otherwise the host replies unauthorized and drops the connection.
+    /// 握手帧超过 `MAX_HANDSHAKE_FRAME` 或 `HANDSHAKE_TIMEOUT` 内未送达时直接断开。
+    /// Handshake frames over `MAX_HANDSHAKE_FRAME`, or not sent within `HANDSHAKE_TIMEOUT`, drop
+    /// the connection.
+    pub(crate) async fn handle_connection<S: PluginIo + 'static>(
+        mut stream: S,
         manager: Arc<PluginRuntimeManager>,
         pool: Arc<PluginConnectionPool>,
+        auth_token: Option<Arc<String>>,
     ) -> Result<()> {
-        let (mut read_half, mut write_half) = stream.into_split();
         let mut plugin_name: Option<String> = None;
         let mut handshake_done = false;
 
         loop {
-            match read_half.read_u32().await {
-                Ok(len) => {
-                    let mut buffer = vec![0u8; len as usize];
-                    if let Err(e) = read_half.read_exact(&mut buffer).await {
-                        error!("Plugin connection read error: {}", e);
-                        break;
-                    }
-
+            let Ok(frame) =
+                tokio::time::timeout(HANDSHAKE_TIMEOUT, read_handshake_frame(&mut stream)).await
+            else {
+                warn!(
+                    "⏱️  插件连接握手超时 / Plugin connection sent no handshake within {:?}",
+                    HANDSHAKE_TIMEOUT
+                );
+                break;
+            };
+            match frame {
+                Ok(buffer) => {
                     // 尝试解析握手消息（支持 Protobuf 和 JSON）
                     // Try to parse handshake message (support both Protobuf and JSON)
                     if !handshake_done {
                         // 处理握手 / Handle handshake
                         handshake_done = true;
 
-                        let (name, version, capabilities, priority) = 
+                        let (name, version, capabilities, priority, protocol_version, presented_token) = 
                             // 先尝试 Protobuf 格式 / Try Protobuf first
                             if let Ok(handshake) = v::plugin::protocol::HandshakeRequest::decode(&buffer[..]) {
                                 (
@@ -775,6 +1120,8 @@ impl UnixSocketServer {
                                     handshake.version,
                                     handshake.capabilities,
                                     handshake.priority,
+                                    handshake.protocol_version,
+                                    handshake.auth_token,
                                 )
                             } else {
                                 // 回退到 JSON 格式（向后兼容）/ Fallback to JSON (backward compatible)
@@ -802,14 +1149,46 @@ impl UnixSocketServer {
                                     .get("priority")
                                     .and_then(|v| v.as_i64())
                                     .unwrap_or(0) as i32;
-                                (name, version, capabilities, priority)
+                                let protocol_version = payload
+                                    .get("protocol_version")
+                                    .and_then(|v| v.as_u64())
+                                    .unwrap_or(0) as u32;
+                                let presented_token = payload
+                                    .get("auth_token")
+                                    .and_then(|v| v.as_str())
+                                    .unwrap_or_default()
+                                    .to_string();
+                                (name, version, capabilities, priority, protocol_version, presented_token)
                             };
 
                         plugin_name = Some(name.clone());
 
+                        // 令牌不匹配：回复 unauthorized 并断开，不注册
+                        // Token mismatch: reply unauthorized, drop, don't register
+                        if let Some(expected) = &auth_token {
+                            if !crate::route_registry::constant_time_eq(&presented_token, expected) {
+                                warn!("🔒 插件 {} 认证失败 / Plugin {} failed authentication", name, name);
+                                let response = v::plugin::protocol::HandshakeResponse {
+                                    status: v::plugin::protocol::HANDSHAKE_UNAUTHORIZED.to_string(),
+                                    message: "invalid auth token".to_string(),
+                                    config: String::new(),
+                                    protocol: String::new(),
+                                    protocol_version: v::plugin::protocol::PROTOCOL_VERSION,
+                                }
+                                .encode_to_vec();
+                                stream.write_u32(response.len() as u32).await?;
+                                stream.write_all(&response).await?;
+                                stream.flush().await?;
+                                return Ok(());
+                            }
+                        }
+
+                        let protocol_version =
+                            v::plugin::protocol::handshake_protocol_version(protocol_version);
+
                         info!(
-                            "🤝 Plugin handshake: {} v{} (priority: {}, capabilities: {:?})",
-                            name, version, priority, capabilities
+                            "🤝 Plugin handshake: {} v{} (priority: {}, protocol v{}, capabilities: {:?})",
+                            name, version, priority, protocol_version, capabilities
                         );
 
                         // 保存插件信息 / Save plugin info
@@ -846,8 +1225,41 @@ impl UnixSocketServer {
                             }
                         }
 
+                        // 协议版本不在支持范围内：回复 incompatible 并断开，不注册
+                        // Protocol version outside the supported range: reply incompatible, drop, don't register
+                        if !v::plugin::protocol::is_supported_protocol_version(protocol_version) {
+                            let message = format!(
+                                "protocol version {} not supported (host supports {}..={})",
+                                protocol_version,
+                                v::plugin::protocol::MIN_PROTOCOL_VERSION,
+                                v::plugin::protocol::PROTOCOL_VERSION
+                            );
+                            error!("❌ 插件 {} 协议不兼容 / Plugin {} incompatible: {}", name, name, message);
+                            if let Some(runtime) = matched_key.as_ref().and_then(|k| manager.plugins.get(k)) {
+                                runtime.set_protocol_version(protocol_version);
+                                runtime.set_status(PluginStatus::Error(message.clone()));
+                            }
+                            let response = v::plugin::protocol::HandshakeResponse {
+                                status: v::plugin::protocol::HANDSHAKE_INCOMPATIBLE.to_string(),
+                                message,
+                                config: String::new(),
+                                protocol: String::new(),
+                                protocol_version: v::plugin::protocol::PROTOCOL_VERSION,
+                            }
+                            .encode_to_vec();
+                            stream.write_u32(response.len() as u32).await?;
+                            stream.write_all(&response).await?;
+                            stream.flush().await?;
+                            return Ok(());
+                        }
+
                         if let Some(ref key) = matched_key {
+                            // 握手声明与 plugin.json 不一致时告警，以握手为准 / Warn when the handshake disagrees with plugin.json; the handshake wins
+                            for mismatch in manager.read_plugin_metadata(key).mismatches(&capabilities, priority) {
+                                warn!("⚠️  插件 {} 声明不一致 / Plugin {} mismatch: {}", key, key, mismatch);
+                            }
                             if let Some(runtime) = manager.plugins.get(key) {
+                                runtime.set_protocol_version(protocol_version);
                                 runtime.set_capabilities(capabilities.clone());
                                 runtime.set_priority(priority);
                                 runtime.set_status(PluginStatus::Running);
@@ -883,15 +1295,25 @@ impl UnixSocketServer {
                             message: "Handshake successful".to_string(),
                             config: String::new(), // 配置通过单独的 config 消息发送
                             protocol: "protobuf".to_string(),
+                            protocol_version,
                         };
                         let response = handshake_response.encode_to_vec();
-                        write_half.write_u32(response.len() as u32).await?;
-                        write_half.write_all(&response).await?;
-                        write_half.flush().await?;
+                        stream.write_u32(response.len() as u32).await?;
+                        stream.write_all(&response).await?;
+                        stream.flush().await?;
 
-                        // 重新组合 stream 并注册到连接池 / Reunite stream and register to pool
-                        let reunited = read_half.reunite(write_half)?;
-                        pool.register(register_name.clone(), reunited);
+                        // 注册到连接池 / Register to pool
+                        pool.register(register_name.clone(), stream);
+
+                        // 重放插件不可用期间缓冲的消息 / Replay messages buffered while the plugin was unavailable
+                        if pool.storage_fallback().has_pending() {
+                            let pool = pool.clone();
+                            tokio::spawn(async move {
+                                if let Err(e) = pool.replay_storage_fallback().await {
+                                    warn!("⚠️  回退消息重放失败 / Fallback replay failed: {}", e);
+                                }
+                            });
+                        }
 
                         info!(
                             "✅ Plugin {} registered to connection pool as '{}'",
@@ -907,6 +1329,8 @@ impl UnixSocketServer {
                             "Plugin {} connection closed gracefully (EOF)",
                             plugin_name.as_deref().unwrap_or("unknown")
                         );
+                    } else if e.kind() == std::io::ErrorKind::InvalidData {
+                        warn!("⚠️  拒绝插件连接 / Plugin connection refused: {}", e);
                     } else {
                         debug!(
                             "Plugin {} connection closed: {}",
@@ -929,10 +1353,161 @@ impl UnixSocketServer {
     }
 }
 
+/// TCP 插件服务器（远程 / sidecar 插件）/ TCP plugin server (remote / sidecar plugins)
+///
+/// 与 Unix Socket 服务器共用连接池；每个连接必须在握手中携带共享令牌。
+/// Shares the connection pool with the Unix Socket server; every connection must
+/// present the shared token in its handshake.
+#[cfg(feature = "plugin_tcp")]
+pub struct TcpPluginServer {
+    listener: tokio::net::TcpListener,
+    plugin_manager: Arc<PluginRuntimeManager>,
+    connection_pool: Arc<PluginConnectionPool>,
+    auth_token: Arc<String>,
+    shutdown_rx: watch::Receiver<bool>,
+}
+
+#[cfg(feature = "plugin_tcp")]
+impl TcpPluginServer {
+    /// 绑定 TCP 地址；令牌为空时拒绝启动
+    /// Bind the TCP address; refuses to start without a token
+    pub async fn new(
+        addr: &str,
+        plugin_manager: Arc<PluginRuntimeManager>,
+        connection_pool: Arc<PluginConnectionPool>,
+        auth_token: &str,
+        shutdown_rx: watch::Receiver<bool>,
+    ) -> Result<Self> {
+        if auth_token.is_empty() {
+            return Err(anyhow!(
+                "TCP 插件传输需要 plugins.tcp.auth_token / TCP plugin transport requires plugins.tcp.auth_token"
+            ));
+        }
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        info!("TCP plugin server listening on: {}", listener.local_addr()?);
+        Ok(Self {
+            listener,
+            plugin_manager,
+            connection_pool,
+            auth_token: Arc::new(auth_token.to_string()),
+            shutdown_rx,
+        })
+    }
+
+    /// 实际监听地址 / Actual listen address
+    pub fn local_addr(&self) -> Result<std::net::SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// 运行服务器 / Run server
+    pub async fn run(&self) -> Result<()> {
+        let mut rx = self.shutdown_rx.clone();
+        loop {
+            tokio::select! {
+                res = self.listener.accept() => {
+                    match res {
+                        Ok((stream, peer)) => {
+                            if let Err(e) = stream.set_nodelay(true) {
+                                debug!("set_nodelay failed for {}: {}", peer, e);
+                            }
+                            let manager = self.plugin_manager.clone();
+                            let pool = self.connection_pool.clone();
+                            let token = self.auth_token.clone();
+                            tokio::spawn(async move {
+                                if let Err(e) = UnixSocketServer::handle_connection(stream, manager, pool, Some(token)).await {
+                                    error!("Error handling TCP plugin connection from {}: {}", peer, e);
+                                }
+                            });
+                        }
+                        Err(e) => {
+                            error!("Error accepting TCP plugin connection: {}", e);
+                        }
+                    }
+                }
+                _ = rx.changed() => {
+                    if *rx.borrow() {
+                        info!("🛑 TCP plugin server shutdown signal received");
+                        break;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+pub use v::plugin::manifest::TOPIC_CAPABILITY_PREFIX;
+
+/// 在单个连接上发送事件并读取响应（长度前缀帧）
+/// Send an event on one connection and read its response (length-prefixed frames)
+async fn exchange_event(
+    conn: &tokio::sync::Mutex<PluginStream>,
+    event: &v::plugin::protocol::EventMessage,
+) -> Result<v::plugin::protocol::EventResponse> {
+    let mut stream = conn.lock().await;
+
+    // 发送 Protobuf 消息 / Send Protobuf message
+    let bytes = event.encode_to_vec();
+    stream.write_u32(bytes.len() as u32).await?;
+    stream.write_all(&bytes).await?;
+    stream.flush().await?;
+
+    // 读取响应 / Read response
+    let resp_len = stream.read_u32().await?;
+    let mut resp_buf = vec![0u8; resp_len as usize];
+    stream.read_exact(&mut resp_buf).await?;
+
+    Ok(v::plugin::protocol::EventResponse::decode(&resp_buf[..])?)
+}
+
+/// 一页消息搜索结果 / One page of message search results
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct MessageSearchPage {
+    /// 按相关度降序的命中（含 `score`）/ Hits, most relevant first (with `score`)
+    pub hits: Vec<Value>,
+    /// 下一页游标，None 表示没有更多 / Next page cursor, None when exhausted
+    pub next_cursor: Option<String>,
+    /// 命中总数 / Total number of matches
+    pub total: usize,
+}
+
+/// 一次消息归档的结果 / Result of one message archive run
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct MessageArchive {
+    /// 归档对象键，恢复时使用 / Archive object key, used for restore
+    pub archive_key: String,
+    /// 导出条数 / Exported count
+    pub count: usize,
+}
+
+/// 一次房间过期消息清理的结果 / Result of purging one room's expired messages
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessagePurge {
+    /// 删除条数 / Deleted count
+    pub deleted: usize,
+    /// 先归档时的对象键 / Archive object key when archived first
+    pub archive_key: Option<String>,
+}
+
 /// 插件连接池 / Plugin connection pool
 pub struct PluginConnectionPool {
-    connections: Arc<DashMap<String, Arc<tokio::sync::Mutex<UnixStream>>>>,
+    connections: Arc<DashMap<String, Arc<tokio::sync::Mutex<PluginStream>>>>,
     manager: Arc<PluginRuntimeManager>,
+    /// 存储插件不可用时的降级策略 / Degradation while the storage plugin is unavailable
+    storage_fallback: Arc<StorageFallback>,
+    /// 没有可用存储插件时使用的内置存储 / Built-in storage used when no storage plugin is usable
+    builtin_storage: std::sync::OnceLock<Arc<BuiltinStorage>>,
+    /// 插件调用耗时与失败计数 / Plugin call latency and failure counts
+    call_metrics: Arc<PluginCallMetrics>,
+}
+
+/// 单次保存消息的结果 / Outcome of one message save attempt
+enum SaveOutcome {
+    Saved,
+    /// 存储插件拒绝或响应无法解析 / The storage plugin refused, or its reply did not parse
+    Rejected,
+    /// 没有可用的存储插件 / No storage plugin is usable
+    Unavailable,
 }
 
 impl PluginConnectionPool {
@@ -940,18 +1515,67 @@ impl PluginConnectionPool {
         Self {
             connections: Arc::new(DashMap::new()),
             manager,
+            storage_fallback: Arc::new(StorageFallback::from_config()),
+            builtin_storage: std::sync::OnceLock::new(),
+            call_metrics: Arc::new(PluginCallMetrics::default()),
+        }
+    }
+
+    /// 启用内置存储；存储插件可用时仍优先使用插件，已启用时忽略
+    /// Enable the built-in storage; a usable storage plugin still takes precedence. Ignored once enabled
+    pub fn enable_builtin_storage(&self, storage: Arc<BuiltinStorage>) {
+        if self.builtin_storage.set(storage).is_ok() {
+            info!("💾 内置存储已启用 / Built-in storage enabled");
         }
     }
 
+    pub fn builtin_storage(&self) -> Option<&Arc<BuiltinStorage>> {
+        self.builtin_storage.get()
+    }
+
+    /// 插件调用指标 / Plugin call metrics
+    pub fn call_metrics(&self) -> &PluginCallMetrics {
+        &self.call_metrics
+    }
+
+    /// 替换降级策略（测试或嵌入时使用）/ Replace the degradation policy (tests or embedders)
+    pub fn with_storage_fallback(mut self, fallback: StorageFallback) -> Self {
+        self.storage_fallback = Arc::new(fallback);
+        self
+    }
+
+    pub fn storage_fallback(&self) -> &Arc<StorageFallback> {
+        &self.storage_fallback
+    }
+
     /// 注册插件连接 / Register plugin connection
-    pub fn register(&self, name: String, stream: UnixStream) {
-        self.connections
-            .insert(name, Arc::new(tokio::sync::Mutex::new(stream)));
+    pub fn register<S: PluginIo + 'static>(&self, name: String, stream: S) {
+        self.connections.insert(
+            name.clone(),
+            Arc::new(tokio::sync::Mutex::new(Box::new(stream) as PluginStream)),
+        );
+        self.manager.mark_ready(&name, true);
     }
 
     /// 移除插件连接 / Remove plugin connection
     pub fn unregister(&self, name: &str) {
         self.connections.remove(name);
+        self.manager.mark_ready(name, false);
+    }
+
+    /// 插件是否已连接到连接池 / Whether a plugin is connected to the pool
+    pub fn is_connected(&self, name: &str) -> bool {
+        self.connections.contains_key(name)
+    }
+
+    /// 是否有可用的存储：已连接的存储插件或内置存储
+    /// Whether storage is usable: a connected storage plugin or the built-in storage
+    pub fn storage_available(&self) -> bool {
+        self.builtin_storage.get().is_some()
+            || self.manager.plugins.iter().any(|entry| {
+                entry.value().capabilities().iter().any(|c| c == "storage")
+                    && self.connections.contains_key(entry.key())
+            })
     }
 
     /// 关闭所有插件连接 / Close all plugin connections
@@ -1002,25 +1626,169 @@ impl PluginConnectionPool {
         plugin_name: &str,
         event: &v::plugin::protocol::EventMessage,
     ) -> Result<v::plugin::protocol::EventResponse> {
-        if let Some(conn) = self.connections.get(plugin_name) {
-            let mut stream = conn.lock().await;
-
-            // 发送 Protobuf 消息 / Send Protobuf message
-            let bytes = event.encode_to_vec();
-            stream.write_u32(bytes.len() as u32).await?;
-            stream.write_all(&bytes).await?;
-            stream.flush().await?;
-
-            // 读取响应 / Read response
-            let resp_len = stream.read_u32().await?;
-            let mut resp_buf = vec![0u8; resp_len as usize];
-            stream.read_exact(&mut resp_buf).await?;
-
-            let response = v::plugin::protocol::EventResponse::decode(&resp_buf[..])?;
-            Ok(response)
-        } else {
-            Err(anyhow::anyhow!("Plugin {} not found", plugin_name))
+        let conn = self
+            .connections
+            .get(plugin_name)
+            .map(|c| c.value().clone())
+            .ok_or_else(|| anyhow::anyhow!("Plugin {} not found", plugin_name))?;
+        let call = self.call_metrics.start(plugin_name, &event.event_type);
+        let result = exchange_event(&conn, event).await;
+        call.finish(&result);
+        result
+    }
+
+    /// 已连接且声明了 `capability` 的插件（按名称）/ Connected plugins declaring `capability`, by name
+    pub fn connected_with_capability(&self, capability: &str) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .manager
+            .plugins
+            .iter()
+            .filter(|entry| entry.value().capabilities().iter().any(|c| c == capability))
+            .filter(|entry| self.connections.contains_key(entry.key()))
+            .map(|entry| entry.key().clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// 发布主题事件到所有订阅插件 / Publish a topic event to all subscribed plugins
+    ///
+    /// 插件通过能力 `topic:<topic>`（如 `topic:message.saved`）订阅，收到的事件类型为
+    /// `topic.<topic>`，载荷为 `{"topic", "payload", "timestamp"}`。
+    /// Plugins subscribe with the capability `topic:<topic>` (e.g. `topic:message.saved`)
+    /// and receive event type `topic.<topic>` with payload `{"topic", "payload", "timestamp"}`.
+    ///
+    /// 投递语义：尽力而为。向每个订阅者并发发送、不等待响应；不同插件之间没有顺序保证，
+    /// 未连接或失败的订阅者只记录日志，不重试。
+    /// Delivery is best-effort: sent to every subscriber concurrently without awaiting
+    /// responses, with no ordering guarantee across plugins; disconnected or failing
+    /// subscribers are logged and not retried.
+    ///
+    /// # 返回值 / Returns
+    /// 已派发的订阅者数量 / Number of subscribers the event was dispatched to
+    pub fn publish_topic(&self, topic: &str, payload: &Value) -> usize {
+        let capability = format!("{}{}", TOPIC_CAPABILITY_PREFIX, topic);
+        let subscribers: Vec<(String, Arc<tokio::sync::Mutex<PluginStream>>)> = self
+            .manager
+            .plugins
+            .iter()
+            .filter(|entry| entry.value().capabilities().contains(&capability))
+            .filter_map(|entry| {
+                let name = entry.key().clone();
+                let conn = self.connections.get(&name)?.value().clone();
+                Some((name, conn))
+            })
+            .collect();
+
+        if subscribers.is_empty() {
+            debug!("📭 主题无订阅者 / No subscribers for topic: {}", topic);
+            return 0;
+        }
+
+        let timestamp = chrono::Utc::now().timestamp_millis();
+        let event = Arc::new(v::plugin::protocol::EventMessage {
+            event_type: format!("topic.{}", topic),
+            payload: serde_json::to_vec(&serde_json::json!({
+                "topic": topic,
+                "payload": payload,
+                "timestamp": timestamp,
+            }))
+            .unwrap_or_default(),
+            timestamp,
+            trace_id: String::new(),
+        });
+
+        let count = subscribers.len();
+        for (name, conn) in subscribers {
+            let event = event.clone();
+            let call_metrics = self.call_metrics.clone();
+            tokio::spawn(async move {
+                let call = call_metrics.start(&name, &event.event_type);
+                let result = exchange_event(&conn, &event).await;
+                call.finish(&result);
+                match result {
+                    Ok(_) => debug!(
+                        "📨 主题事件已投递 / Topic event delivered: {} -> {}",
+                        event.event_type, name
+                    ),
+                    Err(e) => warn!(
+                        "⚠️  主题事件投递失败 / Topic event delivery to {} failed: {}",
+                        name, e
+                    ),
+                }
+            });
+        }
+
+        debug!(
+            "📢 主题 {} 已派发给 {} 个订阅者 / Topic {} dispatched to {} subscribers",
+            topic, count, topic, count
+        );
+        count
+    }
+
+    /// 向所有已连接插件推送配置更新（`config.update`），载荷为各插件的配置子树
+    /// Push a config update (`config.update`) to every connected plugin, each carrying its own subtree
+    ///
+    /// 子树取 `plugins.<名称>`，名称依次尝试运行时名、plugin_no 及去掉前缀的短名；
+    /// 没有对应子树的插件收到空对象。发送在后台进行，失败只记录日志。
+    /// The subtree is `plugins.<name>`, trying the runtime name, plugin_no and the
+    /// prefix-stripped short name in turn; plugins without one receive an empty object.
+    /// Delivery runs in the background and failures are only logged.
+    ///
+    /// # 返回值 / Returns
+    /// 已派发的插件数量 / Number of plugins the update was dispatched to
+    pub fn push_config_update(&self, config: &Value) -> usize {
+        let targets: Vec<(String, Arc<tokio::sync::Mutex<PluginStream>>)> = self
+            .connections
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect();
+
+        let timestamp = chrono::Utc::now().timestamp_millis();
+        for (name, conn) in &targets {
+            let subtree = self.plugin_config_subtree(name, config);
+            let event = v::plugin::protocol::EventMessage {
+                event_type: v::plugin::protocol::CONFIG_UPDATE_EVENT.to_string(),
+                payload: serde_json::to_vec(&subtree).unwrap_or_default(),
+                timestamp,
+                trace_id: String::new(),
+            };
+            let (name, conn) = (name.clone(), conn.clone());
+            tokio::spawn(async move {
+                match exchange_event(&conn, &event).await {
+                    Ok(resp) if resp.status == "ok" => {
+                        debug!("⚙️  配置已推送 / Config pushed to {}", name)
+                    }
+                    Ok(resp) => warn!(
+                        "⚠️  插件 {} 拒绝配置更新 / Plugin {} rejected config update: {}",
+                        name, name, resp.error
+                    ),
+                    Err(e) => warn!(
+                        "⚠️  配置推送失败 / Config push to {} failed: {}",
+                        name, e
+                    ),
+                }
+            });
         }
+        targets.len()
+    }
+
+    /// 查找插件的配置子树 / Find a plugin's config subtree
+    fn plugin_config_subtree(&self, name: &str, config: &Value) -> Value {
+        let Some(plugins) = config.get("plugins") else {
+            return Value::Object(Default::default());
+        };
+        let plugin_no = self.manager.read_plugin_metadata(name).plugin_no;
+        let short = name
+            .strip_prefix("v-connect-im-plugin-")
+            .or_else(|| name.strip_prefix("v.plugin."))
+            .unwrap_or(name);
+        let subtree = [Some(name), plugin_no.as_deref(), Some(short)]
+            .into_iter()
+            .flatten()
+            .find_map(|key| plugins.get(key))
+            .cloned();
+        subtree.unwrap_or_else(|| Value::Object(Default::default()))
     }
 
     /// 向插件发送事件（通用方法，返回 JSON）/ Send event to plugin (generic method, returns JSON)
@@ -1044,17 +1812,30 @@ impl PluginConnectionPool {
                 if response.data.is_empty() {
                     Ok(Some(serde_json::json!({
                         "status": response.status,
-                        "flow": response.flow
+                        "flow": response.flow,
+                        "error": response.error
                     })))
                 } else {
-                    match serde_json::from_slice(&response.data) {
-                        Ok(json) => Ok(Some(json)),
+                    match serde_json::from_slice::<Value>(&response.data) {
+                        Ok(mut json) => {
+                            // 数据中未带流控时补上响应头的 flow/error，拦截原因不会丢失
+                            // Fill in the header flow/error when the data omits them so a block reason is kept
+                            if let Some(obj) = json.as_object_mut() {
+                                for (key, value) in [("flow", &response.flow), ("error", &response.error)] {
+                                    if !value.is_empty() && !obj.contains_key(key) {
+                                        obj.insert(key.to_string(), Value::String(value.clone()));
+                                    }
+                                }
+                            }
+                            Ok(Some(json))
+                        }
                         Err(_) => {
                             // 如果不是 JSON，返回状态
                             // If not JSON, return status
                             Ok(Some(serde_json::json!({
                                 "status": response.status,
-                                "flow": response.flow
+                                "flow": response.flow,
+                                "error": response.error
                             })))
                         }
                     }
@@ -1074,20 +1855,9 @@ impl PluginConnectionPool {
     pub async fn broadcast_message_event(&self, message: &Value) -> Result<Vec<(String, Value)>> {
         let mut responses = Vec::new();
 
-        // 获取所有插件并按优先级排序 / Get all plugins and sort by priority
-        let mut plugins: Vec<_> = self
-            .manager
-            .plugins
-            .iter()
-            .map(|entry| {
-                let runtime = entry.value();
-                (
-                    entry.key().clone(),
-                    runtime.priority(),
-                    runtime.capabilities(),
-                )
-            })
-            .collect();
+        // 获取所有插件，按 (优先级降序, 名称) 与 run_after 排序
+        // Get all plugins ordered by (priority desc, name) and run_after
+        let plugins = self.manager.ordered_plugins();
 
         info!(
             "📋 发现 {} 个已注册插件 / Found {} registered plugins",
@@ -1095,9 +1865,6 @@ impl PluginConnectionPool {
             plugins.len()
         );
 
-        // 按优先级降序排序 / Sort by priority descending
-        plugins.sort_by(|a, b| b.1.cmp(&a.1));
-
         for (name, priority, capabilities) in plugins {
             debug!("🔍 检查插件 {} (优先级: {}, 能力: {:?}) / Checking plugin {} (priority: {}, capabilities: {:?})", 
                    name, priority, capabilities, name, priority, capabilities);
@@ -1262,6 +2029,11 @@ impl PluginConnectionPool {
             }
         }
 
+        if let Some(builtin) = self.builtin_storage() {
+            debug!("💾 由内置存储处理 / Handled by built-in storage: {}", event_type);
+            return builtin.handle(event_type, payload);
+        }
+
         // 根据情况给出不同的警告信息 / Give different warning messages based on the situation
         if found_installed_but_not_ready {
             warn!("⚠️  存储插件已安装但未就绪（未启动或未连接）/ Storage plugin installed but not ready (not started or not connected)");
@@ -1273,6 +2045,12 @@ impl PluginConnectionPool {
     }
 
     /// 保存消息到存储插件 / Save message to storage plugin
+    ///
+    /// 存储插件不可用时按 `storage.on_unavailable` 处理：`fail` 返回错误，`warn` 返回
+    /// `Ok(false)`，`buffer` 写入本地回退库后返回 `Ok(true)`。
+    /// While the storage plugin is unavailable `storage.on_unavailable` applies: `fail`
+    /// returns an error, `warn` returns `Ok(false)`, and `buffer` returns `Ok(true)` once
+    /// the message is in the local fallback spool.
     pub async fn storage_save_message(
         &self,
         message_id: &str,
@@ -1283,16 +2061,120 @@ impl PluginConnectionPool {
         msg_type: &str,
         room_id: Option<&str>,
     ) -> Result<bool> {
+        let record = MessageRecord {
+            message_id: message_id.to_string(),
+            from_client_id: from_uid.to_string(),
+            to_client_id: to_uid.to_string(),
+            content: content.clone(),
+            timestamp,
+            msg_type: msg_type.to_string(),
+            room_id: room_id.map(str::to_string),
+            attachment: None,
+            reply_to: None,
+        };
+        self.storage_save_record(&record).await
+    }
+
+    /// 保存完整的消息记录（含 `reply_to`），语义同 [`Self::storage_save_message`]
+    /// Save a full message record (including `reply_to`); same semantics as [`Self::storage_save_message`]
+    pub async fn storage_save_record(&self, record: &MessageRecord) -> Result<bool> {
+        let message_id = &record.message_id;
+        // 回退库未清空前新消息也进回退库，保持写入顺序
+        // While the spool is not drained new messages join it, keeping write order
+        let outcome = if self.storage_fallback.policy() == OnUnavailable::Buffer
+            && self.storage_fallback.has_pending()
+        {
+            SaveOutcome::Unavailable
+        } else {
+            self.try_save_message(record).await?
+        };
+        match outcome {
+            SaveOutcome::Saved => Ok(true),
+            SaveOutcome::Rejected => Ok(false),
+            SaveOutcome::Unavailable => {
+                self.storage_fallback.record_unavailable();
+                match self.storage_fallback.policy() {
+                    OnUnavailable::Fail => Err(anyhow!(
+                        "存储插件不可用 / Storage plugin unavailable"
+                    )),
+                    OnUnavailable::Warn => {
+                        warn!(
+                            "⚠️  存储插件不可用，消息 {} 未持久化 / Storage plugin unavailable, message {} not persisted",
+                            message_id, message_id
+                        );
+                        Ok(false)
+                    }
+                    OnUnavailable::Buffer => {
+                        self.storage_fallback.buffer(record).await?;
+                        Ok(true)
+                    }
+                }
+            }
+        }
+    }
+
+    /// 按写入顺序把回退库中的消息重放到存储插件，返回重放条数；插件再次不可用时停止
+    /// Replay the fallback spool to the storage plugin in write order and return how many
+    /// messages were replayed; stops as soon as the plugin is unavailable again
+    pub async fn replay_storage_fallback(&self) -> Result<usize> {
+        if !self.storage_fallback.begin_replay() {
+            return Ok(0);
+        }
+        let mut replayed = 0;
+        let result = async {
+            while let Some((key, record)) = self.storage_fallback.peek()? {
+                match self.try_save_message(&record).await? {
+                    SaveOutcome::Saved => replayed += 1,
+                    // 插件明确拒绝的消息重试也不会成功 / A message the plugin refused will not succeed on retry
+                    SaveOutcome::Rejected => warn!(
+                        "⚠️  存储插件拒绝回退消息 {}，已丢弃 / Storage plugin refused spooled message {}, dropped",
+                        record.message_id, record.message_id
+                    ),
+                    SaveOutcome::Unavailable => break,
+                }
+                self.storage_fallback.ack(&key)?;
+            }
+            Ok(replayed)
+        }
+        .await;
+        self.storage_fallback.end_replay();
+        if replayed > 0 {
+            info!(
+                "✅ 已重放 {} 条回退消息 / Replayed {} spooled messages",
+                replayed, replayed
+            );
+        }
+        result
+    }
+
+    async fn try_save_message(&self, record: &MessageRecord) -> Result<SaveOutcome> {
         use prost::Message;
+        let MessageRecord {
+            message_id,
+            from_client_id: from_uid,
+            to_client_id: to_uid,
+            content,
+            timestamp,
+            msg_type,
+            room_id,
+            reply_to,
+            ..
+        } = record;
+        let timestamp = *timestamp;
+        let room_id = room_id.as_deref();
+
         use v::plugin::protocol::{SaveMessageRequest, SaveMessageResponse};
 
         // 构建 Protobuf 请求 / Build Protobuf request
-        // 注意：room_id 暂时不在 Protobuf 定义中，可以放在 content 里
+        // 注意：room_id 与 reply_to 暂时不在 Protobuf 定义中，可以放在 content 里
         let mut content_with_room = content.clone();
-        if let Some(rid) = room_id {
-            if let Some(obj) = content_with_room.as_object_mut() {
+        if let Some(obj) = content_with_room.as_object_mut() {
+            if let Some(rid) = room_id {
                 obj.insert("room_id".to_string(), serde_json::Value::String(rid.to_string()));
             }
+            if let Some(parent) = reply_to {
+                obj.insert("reply_to".to_string(), serde_json::Value::String(parent.clone()));
+            }
         }
         
         let request = SaveMessageRequest {
@@ -1313,7 +2195,7 @@ impl PluginConnectionPool {
             .collect();
 
         if storage_plugins.is_empty() {
-            return Ok(false);
+            return self.save_to_builtin(record);
         }
 
         let event = v::plugin::protocol::EventMessage {
@@ -1329,20 +2211,48 @@ impl PluginConnectionPool {
         {
             Ok(response) => {
                 match SaveMessageResponse::decode(&response.data[..]) {
-                    Ok(resp) => Ok(resp.status == "ok"),
+                    Ok(resp) if resp.status == "ok" => {
+                        self.publish_saved(record);
+                        Ok(SaveOutcome::Saved)
+                    }
+                    Ok(_) => Ok(SaveOutcome::Rejected),
                     Err(e) => {
                         warn!("存储插件响应解析失败 / Failed to parse storage plugin response: {}", e);
-                        Ok(false)
+                        Ok(SaveOutcome::Rejected)
                     }
                 }
             }
             Err(e) => {
                 warn!("存储插件调用失败 / Storage plugin call failed: {}", e);
-                Ok(false)
+                self.save_to_builtin(record)
             }
         }
     }
 
+    /// 写入内置存储；未启用时视为不可用 / Save to the built-in storage; unavailable when it is not enabled
+    fn save_to_builtin(&self, record: &MessageRecord) -> Result<SaveOutcome> {
+        let Some(builtin) = self.builtin_storage() else {
+            return Ok(SaveOutcome::Unavailable);
+        };
+        builtin.append(record)?;
+        self.publish_saved(record);
+        Ok(SaveOutcome::Saved)
+    }
+
+    fn publish_saved(&self, record: &MessageRecord) {
+        self.publish_topic(
+            "message.saved",
+            &serde_json::json!({
+                "message_id": record.message_id,
+                "from_uid": record.from_client_id,
+                "to_uid": record.to_client_id,
+                "timestamp": record.timestamp,
+                "msg_type": record.msg_type,
+                "room_id": record.room_id,
+            }),
+        );
+    }
+
     /// 保存离线消息到存储插件 / Save offline message to storage plugin
     pub async fn storage_save_offline(
         &self,
@@ -1353,6 +2263,7 @@ impl PluginConnectionPool {
         timestamp: i64,
         msg_type: &str,
         room_id: Option<&str>,
+        priority: crate::domain::message::MessagePriority,
     ) -> Result<bool> {
         let payload = serde_json::json!({
             "message_id": message_id,
@@ -1361,7 +2272,8 @@ impl PluginConnectionPool {
             "content": content,
             "timestamp": timestamp,
             "msg_type": msg_type,
-            "room_id": room_id
+            "room_id": room_id,
+            "priority": priority.as_str()
         });
 
         match self
@@ -1443,6 +2355,337 @@ impl PluginConnectionPool {
         }
     }
 
+    /// 搜索消息；游标由存储插件生成，原样回传即可
+    /// Search messages; the cursor is produced by the storage plugin and passed back as is
+    ///
+    /// # 返回值 / Returns
+    /// 没有可用的存储插件时返回 None / None when no storage plugin is available
+    pub async fn storage_search(
+        &self,
+        uid: &str,
+        query: &str,
+        limit: usize,
+        cursor: Option<&str>,
+    ) -> Result<Option<MessageSearchPage>> {
+        let payload = serde_json::json!({
+            "uid": uid,
+            "query": query,
+            "limit": limit,
+            "cursor": cursor.unwrap_or_default()
+        });
+
+        let Some(response) = self
+            .send_storage_event("storage.message.search", &payload)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let data = response.get("data").unwrap_or(&response);
+        // 插件出错（如未实现搜索）时响应只有状态 / On plugin errors (e.g. search unimplemented) only the status comes back
+        if let Some(status) = data.get("status").and_then(|v| v.as_str()) {
+            if status != "ok" {
+                return Err(anyhow!(
+                    "存储插件搜索失败 / Storage plugin search failed: status {}",
+                    status
+                ));
+            }
+        }
+        Ok(Some(MessageSearchPage {
+            hits: data
+                .get("hits")
+                .and_then(|v| v.as_array())
+                .cloned()
+                .unwrap_or_default(),
+            next_cursor: data
+                .get("next_cursor")
+                .and_then(|v| v.as_str())
+                .filter(|c| !c.is_empty())
+                .map(str::to_string),
+            total: data.get("total").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
+        }))
+    }
+
+    /// 按消息ID查询单条消息 / Look up a single message by ID
+    ///
+    /// # 返回值 / Returns
+    /// 没有可用的存储插件时返回 None，消息不存在时返回 `Some(None)`
+    /// None when no storage plugin is available, `Some(None)` when the message does not exist
+    pub async fn storage_get_message(&self, message_id: &str) -> Result<Option<Option<Value>>> {
+        let payload = serde_json::json!({"message_id": message_id});
+        let data = self
+            .storage_call(v::plugin::protocol::MESSAGE_GET_EVENT, &payload)
+            .await?;
+        Ok(data.map(|d| d.get("message").filter(|m| m.is_object()).cloned()))
+    }
+
+    /// 按写入顺序列出回复某条消息的消息（与历史消息字段相同），至多 `limit` 条
+    /// Replies to a message in write order (same fields as history messages), at most `limit`
+    ///
+    /// # 返回值 / Returns
+    /// 没有可用的存储插件时返回 None / None when no storage plugin is available
+    pub async fn storage_list_thread(
+        &self,
+        parent_id: &str,
+        limit: usize,
+    ) -> Result<Option<Vec<Value>>> {
+        let payload = serde_json::json!({"parent_id": parent_id, "limit": limit});
+        let data = self
+            .storage_call(v::plugin::protocol::MESSAGE_THREAD_EVENT, &payload)
+            .await?;
+        Ok(data.map(|d| {
+            d.get("messages")
+                .and_then(|m| m.as_array())
+                .cloned()
+                .unwrap_or_default()
+        }))
+    }
+
+    /// 添加或移除表情回应 / Add or remove a reaction
+    ///
+    /// # 返回值 / Returns
+    /// 没有可用的存储插件时返回 None，否则为是否有变化
+    /// None when no storage plugin is available, otherwise whether anything changed
+    pub async fn storage_set_reaction(
+        &self,
+        message_id: &str,
+        uid: &str,
+        emoji: &str,
+        add: bool,
+    ) -> Result<Option<bool>> {
+        let event = if add {
+            v::plugin::protocol::REACTION_ADD_EVENT
+        } else {
+            v::plugin::protocol::REACTION_REMOVE_EVENT
+        };
+        let payload = serde_json::json!({"message_id": message_id, "uid": uid, "emoji": emoji});
+        let data = self.storage_call(event, &payload).await?;
+        Ok(data.map(|d| d.get("changed").and_then(|c| c.as_bool()).unwrap_or(false)))
+    }
+
+    /// 置顶或取消置顶房间消息 / Pin or unpin a room message
+    ///
+    /// # 返回值 / Returns
+    /// 没有可用的存储插件时返回 None，否则为 `(changed, full)`：`full` 表示已达 `max_pins` 而未置顶
+    /// None when no storage plugin is available, otherwise `(changed, full)` where `full` means the
+    /// room already had `max_pins` pins and nothing was pinned
+    pub async fn storage_set_pin(
+        &self,
+        room_id: &str,
+        message_id: &str,
+        pinned_by: &str,
+        pin: bool,
+        max_pins: usize,
+    ) -> Result<Option<(bool, bool)>> {
+        let (event, payload) = if pin {
+            (
+                v::plugin::protocol::PIN_ADD_EVENT,
+                serde_json::json!({
+                    "room_id": room_id,
+                    "message_id": message_id,
+                    "pinned_by": pinned_by,
+                    "pinned_at": chrono::Utc::now().timestamp_millis(),
+                    "max_pins": max_pins,
+                }),
+            )
+        } else {
+            (
+                v::plugin::protocol::PIN_REMOVE_EVENT,
+                serde_json::json!({"room_id": room_id, "message_id": message_id}),
+            )
+        };
+        let data = self.storage_call(event, &payload).await?;
+        let flag = |d: &Value, key: &str| d.get(key).and_then(|v| v.as_bool()).unwrap_or(false);
+        Ok(data.map(|d| (flag(&d, "changed"), flag(&d, "full"))))
+    }
+
+    /// 房间置顶消息（`[{message_id, pinned_by, pinned_at}]`，按置顶时间排序）
+    /// A room's pinned messages (`[{message_id, pinned_by, pinned_at}]`, ordered by pin time)
+    pub async fn storage_list_pins(&self, room_id: &str) -> Result<Option<Vec<Value>>> {
+        let payload = serde_json::json!({"room_id": room_id});
+        let data = self
+            .storage_call(v::plugin::protocol::PIN_LIST_EVENT, &payload)
+            .await?;
+        Ok(data.map(|d| {
+            d.get("pins")
+                .and_then(|p| p.as_array())
+                .cloned()
+                .unwrap_or_default()
+        }))
+    }
+
+    /// 按表情汇总的回应列表（`[{emoji, count, uids}]`）/ Reactions aggregated per emoji (`[{emoji, count, uids}]`)
+    pub async fn storage_list_reactions(&self, message_id: &str) -> Result<Option<Vec<Value>>> {
+        let payload = serde_json::json!({"message_id": message_id});
+        let data = self
+            .storage_call(v::plugin::protocol::REACTION_LIST_EVENT, &payload)
+            .await?;
+        Ok(data.map(|d| {
+            d.get("reactions")
+                .and_then(|r| r.as_array())
+                .cloned()
+                .unwrap_or_default()
+        }))
+    }
+
+    /// 登记群消息应确认的成员 / Register the members expected to ack a group message
+    ///
+    /// # 返回值 / Returns
+    /// 没有可用的存储插件时返回 false / false when no storage plugin is available
+    pub async fn storage_track_group_ack(
+        &self,
+        message_id: &str,
+        room_id: &str,
+        sender_uid: &str,
+        recipients: &[String],
+    ) -> Result<bool> {
+        let payload = serde_json::json!({
+            "message_id": message_id,
+            "room_id": room_id,
+            "sender_uid": sender_uid,
+            "recipients": recipients,
+        });
+        let data = self
+            .storage_call(v::plugin::protocol::GROUP_ACK_TRACK_EVENT, &payload)
+            .await?;
+        Ok(data.is_some())
+    }
+
+    /// 记录群成员确认，消息未登记、非接收者或没有存储插件时返回 false
+    /// Record a group member ack; false for unknown messages, non-recipients or when no storage
+    /// plugin is available
+    pub async fn storage_add_group_ack(
+        &self,
+        message_id: &str,
+        uid: &str,
+        acked_at: i64,
+    ) -> Result<bool> {
+        let payload = serde_json::json!({"message_id": message_id, "uid": uid, "acked_at": acked_at});
+        let data = self
+            .storage_call(v::plugin::protocol::GROUP_ACK_ADD_EVENT, &payload)
+            .await?;
+        Ok(data
+            .and_then(|d| d.get("recorded").and_then(|r| r.as_bool()))
+            .unwrap_or(false))
+    }
+
+    /// 群消息确认进度（`{room_id, sender_uid, acked, pending}`），消息未登记或没有存储插件时返回 None
+    /// Group ack progress (`{room_id, sender_uid, acked, pending}`); None for unknown messages or
+    /// when no storage plugin is available
+    pub async fn storage_group_ack_status(&self, message_id: &str) -> Result<Option<Value>> {
+        let payload = serde_json::json!({"message_id": message_id});
+        let data = self
+            .storage_call(v::plugin::protocol::GROUP_ACK_STATUS_EVENT, &payload)
+            .await?;
+        Ok(data.filter(|d| d.get("found").and_then(|f| f.as_bool()) == Some(true)))
+    }
+
+    /// 要求存储插件立即落盘，供需要在某一时刻保证持久性的调用方使用（如确认发送方之前）
+    /// Ask the storage plugin to flush now, for callers that need durability at a
+    /// specific point (e.g. before acking the sender)
+    ///
+    /// # 返回值 / Returns
+    /// 插件确认落盘时返回 true / true when the plugin acknowledged the flush
+    pub async fn storage_flush_now(&self) -> Result<bool> {
+        let response = self
+            .send_storage_event(
+                v::plugin::protocol::STORAGE_FLUSH_EVENT,
+                &serde_json::json!({}),
+            )
+            .await?;
+        Ok(response
+            .as_ref()
+            .and_then(|r| r.get("status"))
+            .and_then(|s| s.as_str())
+            == Some("ok"))
+    }
+
+    /// 把 `range`（毫秒时间戳，左闭右开）内的消息归档到存储插件配置的对象存储
+    /// Archive messages in `range` (millisecond timestamps, half-open) to the object
+    /// storage configured on the storage plugin
+    ///
+    /// # 返回值 / Returns
+    /// 没有可用的存储插件时返回 None / None when no storage plugin is available
+    pub async fn storage_archive(&self, range: Range<i64>) -> Result<Option<MessageArchive>> {
+        let payload = serde_json::json!({"since_ts": range.start, "until_ts": range.end});
+        let Some(data) = self
+            .storage_call(v::plugin::protocol::MESSAGE_ARCHIVE_EVENT, &payload)
+            .await?
+        else {
+            return Ok(None);
+        };
+        Ok(Some(MessageArchive {
+            archive_key: data
+                .get("archive_key")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            count: data.get("count").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
+        }))
+    }
+
+    /// 从归档对象恢复消息，返回写回的条数 / Restore messages from an archive object; returns how many were written back
+    ///
+    /// # 返回值 / Returns
+    /// 没有可用的存储插件时返回 None / None when no storage plugin is available
+    pub async fn storage_restore(&self, archive_key: &str) -> Result<Option<usize>> {
+        let payload = serde_json::json!({"archive_key": archive_key});
+        let data = self
+            .storage_call(v::plugin::protocol::MESSAGE_RESTORE_EVENT, &payload)
+            .await?;
+        Ok(data.map(|d| d.get("count").and_then(|v| v.as_u64()).unwrap_or(0) as usize))
+    }
+
+    /// 删除房间内 `until_ts`（毫秒）之前的消息，`archive` 为真时由存储插件先归档
+    /// Delete a room's messages older than `until_ts` (ms), archived first by the storage
+    /// plugin when `archive` is set
+    ///
+    /// # 返回值 / Returns
+    /// 没有可用的存储时返回 None / None when no storage is available
+    pub async fn storage_purge_room(
+        &self,
+        room_id: &str,
+        until_ts: i64,
+        archive: bool,
+    ) -> Result<Option<MessagePurge>> {
+        let payload =
+            serde_json::json!({"room_id": room_id, "until_ts": until_ts, "archive": archive});
+        let data = self
+            .storage_call(v::plugin::protocol::MESSAGE_PURGE_EVENT, &payload)
+            .await?;
+        Ok(data.map(|d| MessagePurge {
+            deleted: d.get("deleted").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
+            archive_key: d
+                .get("archive_key")
+                .and_then(|v| v.as_str())
+                .filter(|k| !k.is_empty())
+                .map(str::to_string),
+        }))
+    }
+
+    /// 发送存储事件并取出响应数据，插件返回非 ok 状态时报错
+    /// Send a storage event and unwrap the response data, failing on a non-ok status
+    async fn storage_call(
+        &self,
+        event_type: &str,
+        payload: &serde_json::Value,
+    ) -> Result<Option<Value>> {
+        let Some(response) = self.send_storage_event(event_type, payload).await? else {
+            return Ok(None);
+        };
+        let data = response.get("data").cloned().unwrap_or(response);
+        if let Some(status) = data.get("status").and_then(|v| v.as_str()) {
+            if status != "ok" {
+                return Err(anyhow!(
+                    "存储插件处理 {} 失败 / Storage plugin failed {}: status {}",
+                    event_type,
+                    event_type,
+                    status
+                ));
+            }
+        }
+        Ok(Some(data))
+    }
+
     /// 确认离线消息 / Acknowledge offline messages
     pub async fn storage_ack_offline(&self, to_uid: &str, message_ids: &[String]) -> Result<usize> {
         let payload = serde_json::json!({
@@ -1809,6 +3052,31 @@ impl PluginConnectionPool {
         }
     }
 
+    /// 一次写入批量加入与移除房间成员，返回更新后的成员；没有存储时为 None
+    /// Add and remove room members in one write, returning the members after the update; None without storage
+    pub async fn storage_update_room_members(
+        &self,
+        room_id: &str,
+        add: &[String],
+        remove: &[String],
+    ) -> Result<Option<Vec<String>>> {
+        let payload = serde_json::json!({"room_id": room_id, "add": add, "remove": remove});
+        let data = self
+            .storage_call(v::plugin::protocol::ROOM_UPDATE_MEMBERS_EVENT, &payload)
+            .await?;
+        Ok(data.map(|d| {
+            d.get("members")
+                .and_then(|v| v.as_array())
+                .map(|members| {
+                    members
+                        .iter()
+                        .filter_map(|m| m.as_str().map(str::to_string))
+                        .collect()
+                })
+                .unwrap_or_default()
+        }))
+    }
+
     /// 列出房间成员 / List room members
     pub async fn storage_list_room_members(&self, room_id: &str) -> Result<Vec<String>> {
         let payload = serde_json::json!({
@@ -1878,4 +3146,710 @@ impl PluginConnectionPool {
             Err(e) => Err(e),
         }
     }
+
+    /// 停机前要求所有已连接的存储插件落盘并等待确认，返回未在 `timeout` 内确认的插件
+    /// Ask every connected storage plugin to flush before shutdown and await the acks;
+    /// returns the plugins that did not ack within `timeout`
+    pub async fn flush_storage_plugins(&self, timeout: Duration) -> Vec<String> {
+        let storage_plugins: Vec<String> = self
+            .list_plugins()
+            .into_iter()
+            .filter(|(name, caps)| {
+                caps.iter().any(|c| c == "storage") && self.connections.contains_key(name)
+            })
+            .map(|(name, _)| name)
+            .collect();
+
+        let flushes = storage_plugins.into_iter().map(|name| async move {
+            let event = v::plugin::protocol::EventMessage {
+                event_type: v::plugin::protocol::STORAGE_FLUSH_EVENT.to_string(),
+                payload: b"{}".to_vec(),
+                timestamp: chrono::Utc::now().timestamp_millis(),
+                trace_id: String::new(),
+            };
+            match tokio::time::timeout(timeout, self.send_event(&name, &event)).await {
+                Ok(Ok(resp)) if resp.status == "ok" => {
+                    info!(
+                        "💾 存储插件 {} 已落盘 / Storage plugin {} flushed",
+                        name, name
+                    );
+                    None
+                }
+                Ok(Ok(resp)) => {
+                    warn!(
+                        "⚠️  存储插件 {} 落盘失败 / Storage plugin {} flush failed: {}",
+                        name, name, resp.error
+                    );
+                    Some(name)
+                }
+                Ok(Err(e)) => {
+                    warn!(
+                        "⚠️  存储插件 {} 落盘失败 / Storage plugin {} flush failed: {}",
+                        name, name, e
+                    );
+                    Some(name)
+                }
+                Err(_) => {
+                    warn!(
+                        "⏰ 存储插件 {} 落盘超时 / Storage plugin {} flush timed out",
+                        name, name
+                    );
+                    Some(name)
+                }
+            }
+        });
+        let unacked = future::join_all(flushes)
+            .await
+            .into_iter()
+            .flatten()
+            .collect();
+        if let Some(builtin) = self.builtin_storage() {
+            if let Err(e) = builtin.flush() {
+                warn!("⚠️  内置存储落盘失败 / Built-in storage flush failed: {}", e);
+            }
+        }
+        unacked
+    }
+}
+
+/// 在 `<plugins_root>/<name>` 下查找插件二进制文件 / Find a plugin's binary under `<plugins_root>/<name>`
+pub fn find_plugin_binary(plugins_root: &Path, name: &str) -> Result<PathBuf> {
+    let plugin_dir = plugins_root.join(name);
+
+    if !plugin_dir.exists() {
+        return Err(anyhow!("Plugin directory not found: {:?}", plugin_dir));
+    }
+
+    // 查找可执行文件 / Find executable
+    let exe_name = if cfg!(target_os = "windows") {
+        format!("{}.exe", name)
+    } else {
+        name.to_string()
+    };
+
+    let exe_path = plugin_dir.join(&exe_name);
+    if exe_path.exists() && exe_path.is_file() {
+        return Ok(exe_path);
+    }
+
+    // 尝试查找其他可能的二进制文件 / Try to find other possible binaries
+    let entries = std::fs::read_dir(&plugin_dir)?;
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        if path.is_file() {
+            // 检查是否有执行权限（Unix）或是否为 .exe（Windows）
+            // Check if executable (Unix) or .exe (Windows)
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                if let Ok(metadata) = path.metadata() {
+                    let perms = metadata.permissions();
+                    if perms.mode() & 0o111 != 0 {
+                        return Ok(path);
+                    }
+                }
+            }
+            #[cfg(windows)]
+            {
+                if path.extension().and_then(|s| s.to_str()) == Some("exe") {
+                    return Ok(path);
+                }
+            }
+        }
+    }
+
+    Err(anyhow!("Plugin binary not found in {:?}", plugin_dir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::UnixStream;
+
+    /// 读取一帧事件并回复 ok / Read one event frame and reply ok
+    async fn recv_event(stream: &mut UnixStream) -> v::plugin::protocol::EventMessage {
+        let len = stream.read_u32().await.unwrap();
+        let mut buf = vec![0u8; len as usize];
+        stream.read_exact(&mut buf).await.unwrap();
+        let resp = v::plugin::protocol::EventResponse {
+            status: "ok".to_string(),
+            flow: "continue".to_string(),
+            data: Vec::new(),
+            error: String::new(),
+        }
+        .encode_to_vec();
+        stream.write_u32(resp.len() as u32).await.unwrap();
+        stream.write_all(&resp).await.unwrap();
+        v::plugin::protocol::EventMessage::decode(&buf[..]).unwrap()
+    }
+
+    #[test]
+    fn test_ordered_plugins_is_deterministic() {
+        let manager = PluginRuntimeManager::new("./plugins", "./plugins");
+        for (name, priority, run_after) in [
+            ("transform-b", 10, vec![]),
+            ("transform-a", 10, vec![]),
+            ("filter", 20, vec!["transform-b".to_string()]),
+            ("audit", 0, vec![]),
+        ] {
+            let mut runtime = PluginRuntime::new(name.to_string(), PathBuf::new(), None, None);
+            runtime.set_priority(priority);
+            runtime.run_after = run_after;
+            manager.plugins.insert(name.to_string(), runtime);
+        }
+
+        let names: Vec<_> = manager
+            .ordered_plugins()
+            .into_iter()
+            .map(|(name, _, _)| name)
+            .collect();
+        assert_eq!(names, ["transform-a", "transform-b", "filter", "audit"]);
+    }
+
+    #[cfg(feature = "plugin_tcp")]
+    #[tokio::test]
+    async fn test_tcp_handshake_requires_auth_token() {
+        use v::plugin::protocol::{HandshakeRequest, HandshakeResponse, PROTOCOL_VERSION};
+
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = Arc::new(PluginConnectionPool::new(manager.clone()));
+        let (_tx, rx) = watch::channel(false);
+        assert!(
+            TcpPluginServer::new("127.0.0.1:0", manager.clone(), pool.clone(), "", rx.clone())
+                .await
+                .is_err()
+        );
+        let server = TcpPluginServer::new("127.0.0.1:0", manager, pool.clone(), "secret", rx)
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move { server.run().await });
+
+        for (name, token, expected) in [("intruder", "guess", "unauthorized"), ("sidecar", "secret", "ok")] {
+            let mut plugin = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let req = HandshakeRequest {
+                name: name.to_string(),
+                version: "1.0.0".to_string(),
+                capabilities: Vec::new(),
+                priority: 0,
+                protocol: "protobuf".to_string(),
+                protocol_version: PROTOCOL_VERSION,
+                auth_token: token.to_string(),
+            }
+            .encode_to_vec();
+            plugin.write_u32(req.len() as u32).await.unwrap();
+            plugin.write_all(&req).await.unwrap();
+
+            let len = plugin.read_u32().await.unwrap();
+            let mut buf = vec![0u8; len as usize];
+            plugin.read_exact(&mut buf).await.unwrap();
+            assert_eq!(HandshakeResponse::decode(&buf[..]).unwrap().status, expected);
+        }
+
+        // 注册在握手响应之后完成 / Registration completes right after the handshake response
+        for _ in 0..50 {
+            if pool.connections.contains_key("sidecar") {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(pool.connections.contains_key("sidecar"));
+        assert!(!pool.connections.contains_key("intruder"));
+    }
+
+    #[tokio::test]
+    async fn test_handshake_rejects_incompatible_protocol_version() {
+        use v::plugin::protocol::{HandshakeRequest, HandshakeResponse, PROTOCOL_VERSION};
+
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        manager.plugins.insert(
+            "old".to_string(),
+            PluginRuntime::new("old".to_string(), PathBuf::new(), None, None),
+        );
+        let pool = Arc::new(PluginConnectionPool::new(manager.clone()));
+
+        for (name, version, expected) in [("old", 1, "incompatible"), ("new", PROTOCOL_VERSION, "ok")]
+        {
+            let (host, mut plugin) = UnixStream::pair().unwrap();
+            let task = tokio::spawn(UnixSocketServer::handle_connection(
+                host,
+                manager.clone(),
+                pool.clone(),
+                None,
+            ));
+            let req = HandshakeRequest {
+                name: name.to_string(),
+                version: "1.0.0".to_string(),
+                capabilities: Vec::new(),
+                priority: 0,
+                protocol: "protobuf".to_string(),
+                protocol_version: version,
+                auth_token: String::new(),
+            }
+            .encode_to_vec();
+            plugin.write_u32(req.len() as u32).await.unwrap();
+            plugin.write_all(&req).await.unwrap();
+
+            let len = plugin.read_u32().await.unwrap();
+            let mut buf = vec![0u8; len as usize];
+            plugin.read_exact(&mut buf).await.unwrap();
+            let resp = HandshakeResponse::decode(&buf[..]).unwrap();
+            assert_eq!(resp.status, expected);
+            task.await.unwrap().unwrap();
+        }
+
+        assert!(!pool.connections.contains_key("old"));
+        assert!(pool.connections.contains_key("new"));
+        let old = manager.plugins.get("old").unwrap();
+        assert!(matches!(old.status(), PluginStatus::Error(_)));
+        assert_eq!(old.protocol_version(), Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_oversized_handshake_frame_is_refused_before_allocation() {
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = Arc::new(PluginConnectionPool::new(manager.clone()));
+        let (host, mut plugin) = UnixStream::pair().unwrap();
+        let task = tokio::spawn(UnixSocketServer::handle_connection(
+            host,
+            manager,
+            pool.clone(),
+            Some(Arc::new("secret".to_string())),
+        ));
+
+        plugin.write_u32(u32::MAX).await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), task)
+            .await
+            .expect("connection should be dropped without waiting for the body")
+            .unwrap()
+            .unwrap();
+        let mut buf = [0u8; 1];
+        assert_eq!(plugin.read(&mut buf).await.unwrap(), 0);
+        assert!(pool.connections.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_start_rejects_invalid_manifest() {
+        let root = std::env::temp_dir().join(format!("vim-plugins-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("broken")).unwrap();
+        std::fs::write(
+            root.join("broken").join("plugin.json"),
+            r#"{"name": "broken", "version": "1.0", "capabilities": ["storage"]}"#,
+        )
+        .unwrap();
+        let manager = PluginRuntimeManager::new(&root, &root);
+
+        let err = manager.start_plugin("broken").await.unwrap_err().to_string();
+        assert!(err.contains("is not valid semver"), "{}", err);
+        assert!(!manager.plugins.contains_key("broken"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn test_summaries_use_plugin_json_before_start() {
+        let root = std::env::temp_dir().join(format!("vim-plugins-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(root.join("audit")).unwrap();
+        std::fs::write(
+            root.join("audit").join("plugin.json"),
+            r#"{"name": "audit", "version": "1.2.0", "capabilities": ["message", "storage"], "priority": 50}"#,
+        )
+        .unwrap();
+        let manager = PluginRuntimeManager::new(&root, &root);
+
+        let summaries = manager.runtime_summaries();
+        assert_eq!(summaries.len(), 1);
+        let audit = &summaries[0];
+        assert_eq!(audit.name, "audit");
+        assert_eq!(audit.status, PluginStatus::Installed);
+        assert_eq!(audit.version.as_deref(), Some("1.2.0"));
+        assert_eq!(audit.capabilities, ["message", "storage"]);
+        assert_eq!(audit.priority, 50);
+
+        // 顺序无关；能力与优先级各报一处不一致 / Order doesn't matter; capabilities and priority each report a mismatch
+        let metadata = manager.read_plugin_metadata("audit");
+        assert!(metadata
+            .mismatches(&["storage".to_string(), "message".to_string()], 50)
+            .is_empty());
+        assert_eq!(metadata.mismatches(&["message".to_string()], 10).len(), 2);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_start_rejects_duplicate_plugin_no() {
+        let root = std::env::temp_dir().join(format!("vim-plugins-{}", uuid::Uuid::new_v4()));
+        for dir in ["storage-a", "storage-b"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+            std::fs::write(
+                root.join(dir).join("plugin.json"),
+                r#"{"plugin_no": "v.plugin.storage", "name": "storage", "version": "1.0.0"}"#,
+            )
+            .unwrap();
+        }
+        let manager = PluginRuntimeManager::new(&root, &root);
+        manager
+            .register_dev_plugin("storage-a".into(), root.join("storage-a"))
+            .unwrap();
+        assert!(manager
+            .register_dev_plugin("storage-a".into(), root.join("storage-a"))
+            .is_err());
+
+        let err = manager.start_plugin("storage-b").await.unwrap_err().to_string();
+        assert!(err.contains("already used by plugin storage-a"), "{}", err);
+        assert!(!manager.plugins.contains_key("storage-b"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_wait_ready_tracks_pool_registration() {
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        for (name, status) in [
+            ("fast", PluginStatus::Running),
+            ("slow", PluginStatus::Running),
+            ("crashed", PluginStatus::Error("exit".to_string())),
+        ] {
+            let runtime = PluginRuntime::new(name.to_string(), PathBuf::new(), None, None);
+            runtime.set_status(status);
+            manager.plugins.insert(name.to_string(), runtime);
+        }
+        let pool = Arc::new(PluginConnectionPool::new(manager.clone()));
+        let names: Vec<String> = ["fast", "slow", "crashed"].map(String::from).to_vec();
+
+        let registrar = {
+            let pool = pool.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                let (host, _plugin) = UnixStream::pair().unwrap();
+                pool.register("fast".to_string(), host);
+            })
+        };
+        let not_ready = manager.wait_ready(&names, Duration::from_millis(300)).await;
+        registrar.await.unwrap();
+        assert_eq!(not_ready, ["slow", "crashed"]);
+
+        // 仅剩已退出的插件时立即返回 / Returns at once when only exited plugins remain
+        let started = tokio::time::Instant::now();
+        let not_ready = manager
+            .wait_ready(&["fast".to_string(), "crashed".to_string()], Duration::from_secs(5))
+            .await;
+        assert_eq!(not_ready, ["crashed"]);
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        pool.unregister("fast");
+        assert!(!manager.plugins.get("fast").unwrap().is_ready());
+    }
+
+    #[tokio::test]
+    async fn test_push_config_update_reaches_connected_plugin() {
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = PluginConnectionPool::new(manager);
+
+        let (host, mut plugin) = UnixStream::pair().unwrap();
+        pool.register("v-connect-im-plugin-sensitive-word".to_string(), host);
+        let (host, mut other) = UnixStream::pair().unwrap();
+        pool.register("other".to_string(), host);
+
+        let config = serde_json::json!({
+            "plugins": {"sensitive-word": {"words_path": "/etc/words.txt"}}
+        });
+        assert_eq!(pool.push_config_update(&config), 2);
+
+        let event = recv_event(&mut plugin).await;
+        assert_eq!(event.event_type, v::plugin::protocol::CONFIG_UPDATE_EVENT);
+        let payload: Value = serde_json::from_slice(&event.payload).unwrap();
+        assert_eq!(payload["words_path"], "/etc/words.txt");
+
+        let event = recv_event(&mut other).await;
+        let payload: Value = serde_json::from_slice(&event.payload).unwrap();
+        assert_eq!(payload, serde_json::json!({}));
+    }
+
+    #[tokio::test]
+    async fn test_publish_topic_reaches_only_subscribers() {
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = PluginConnectionPool::new(manager.clone());
+
+        let mut peers = Vec::new();
+        for (name, caps) in [
+            ("analytics", vec!["topic:message.saved".to_string()]),
+            ("audit", vec!["topic:message.saved".to_string()]),
+            ("other", vec!["topic:user.login".to_string()]),
+        ] {
+            let runtime = PluginRuntime::new(name.to_string(), PathBuf::new(), None, None);
+            runtime.set_capabilities(caps);
+            manager.plugins.insert(name.to_string(), runtime);
+            let (host, plugin) = UnixStream::pair().unwrap();
+            pool.register(name.to_string(), host);
+            peers.push((name, plugin));
+        }
+
+        let sent = pool.publish_topic("message.saved", &serde_json::json!({"message_id": "m1"}));
+        assert_eq!(sent, 2);
+
+        for (name, stream) in peers.iter_mut() {
+            if *name == "other" {
+                let mut byte = [0u8; 1];
+                let idle =
+                    tokio::time::timeout(Duration::from_millis(50), stream.read(&mut byte)).await;
+                assert!(idle.is_err(), "non-subscriber must not receive the topic");
+                continue;
+            }
+            let event = recv_event(stream).await;
+            assert_eq!(event.event_type, "topic.message.saved");
+            let payload: Value = serde_json::from_slice(&event.payload).unwrap();
+            assert_eq!(payload["payload"]["message_id"], "m1");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_flush_storage_plugins_reports_unacked() {
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = PluginConnectionPool::new(manager.clone());
+
+        let mut peers = Vec::new();
+        for (name, caps) in [
+            ("sled", vec!["storage".to_string()]),
+            ("stuck", vec!["storage".to_string()]),
+            ("gateway", vec!["gateway".to_string()]),
+        ] {
+            let runtime = PluginRuntime::new(name.to_string(), PathBuf::new(), None, None);
+            runtime.set_capabilities(caps);
+            manager.plugins.insert(name.to_string(), runtime);
+            let (host, plugin) = UnixStream::pair().unwrap();
+            pool.register(name.to_string(), host);
+            peers.push((name, plugin));
+        }
+        let (_, mut sled) = peers.remove(0);
+        let responder = tokio::spawn(async move { recv_event(&mut sled).await });
+
+        let unflushed = pool.flush_storage_plugins(Duration::from_millis(200)).await;
+        assert_eq!(unflushed, vec!["stuck".to_string()]);
+        assert_eq!(responder.await.unwrap().event_type, "storage.flush");
+
+        // 非存储插件不会收到落盘事件 / Non-storage plugins get no flush event
+        let (_, gateway) = peers.iter_mut().find(|(n, _)| *n == "gateway").unwrap();
+        let mut byte = [0u8; 1];
+        let idle = tokio::time::timeout(Duration::from_millis(50), gateway.read(&mut byte)).await;
+        assert!(idle.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_storage_archive_round_trip_through_plugin() {
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = PluginConnectionPool::new(manager.clone());
+        let runtime = PluginRuntime::new("sled".to_string(), PathBuf::new(), None, None);
+        runtime.set_capabilities(vec!["storage".to_string()]);
+        manager.plugins.insert("sled".to_string(), runtime);
+        let (host, mut plugin) = UnixStream::pair().unwrap();
+        pool.register("sled".to_string(), host);
+
+        let fake = tokio::spawn(async move {
+            let len = plugin.read_u32().await.unwrap();
+            let mut buf = vec![0u8; len as usize];
+            plugin.read_exact(&mut buf).await.unwrap();
+            let event = v::plugin::protocol::EventMessage::decode(&buf[..]).unwrap();
+            let resp = v::plugin::protocol::EventResponse {
+                status: "ok".to_string(),
+                flow: "continue".to_string(),
+                data: serde_json::to_vec(&serde_json::json!({
+                    "status": "ok",
+                    "archive_key": "im-archive/messages-10-20.ndjson",
+                    "count": 3,
+                }))
+                .unwrap(),
+                error: String::new(),
+            }
+            .encode_to_vec();
+            plugin.write_u32(resp.len() as u32).await.unwrap();
+            plugin.write_all(&resp).await.unwrap();
+            event
+        });
+
+        let archive = pool.storage_archive(10..20).await.unwrap().unwrap();
+        assert_eq!(archive.archive_key, "im-archive/messages-10-20.ndjson");
+        assert_eq!(archive.count, 3);
+        let event = fake.await.unwrap();
+        assert_eq!(event.event_type, v::plugin::protocol::MESSAGE_ARCHIVE_EVENT);
+        let payload: Value = serde_json::from_slice(&event.payload).unwrap();
+        assert_eq!(payload, serde_json::json!({"since_ts": 10, "until_ts": 20}));
+    }
+
+    #[tokio::test]
+    async fn test_blocked_message_is_rejected_to_sender() {
+        use crate::testkit::{im, recv_typed};
+
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = Arc::new(PluginConnectionPool::new(manager.clone()));
+        let runtime = PluginRuntime::new("sensitive-word".to_string(), PathBuf::new(), None, None);
+        runtime.set_capabilities(vec!["message".to_string()]);
+        manager.plugins.insert("sensitive-word".to_string(), runtime);
+        let (host, mut plugin) = UnixStream::pair().unwrap();
+        pool.register("sensitive-word".to_string(), host);
+
+        tokio::spawn(async move {
+            while let Ok(len) = plugin.read_u32().await {
+                let mut buf = vec![0u8; len as usize];
+                plugin.read_exact(&mut buf).await.unwrap();
+                let resp = v::plugin::protocol::EventResponse::reject(
+                    "SENSITIVE_WORD",
+                    "message contains a blocked word",
+                )
+                .encode_to_vec();
+                plugin.write_u32(resp.len() as u32).await.unwrap();
+                plugin.write_all(&resp).await.unwrap();
+            }
+        });
+
+        let ts = crate::testkit::TestServer::build(|mut server| {
+            server.plugin_connection_pool = Some(pool.clone());
+            server
+        });
+        let (alice, mut alice_rx) = ts.add_client("alice");
+        let (_, mut bob_rx) = ts.add_client("bob");
+        ts.send(&alice, im("message", serde_json::json!({"text": "..."}), Some("bob")))
+            .await
+            .unwrap();
+
+        let rejected: crate::domain::message::ImMessage = recv_typed(&mut alice_rx).await;
+        assert_eq!(rejected.msg_type, "message_rejected");
+        assert_eq!(rejected.data["code"], "SENSITIVE_WORD");
+        assert_eq!(rejected.data["reason"], "message contains a blocked word");
+        assert_eq!(rejected.data["plugin"], "sensitive-word");
+        assert!(rejected.data["message_id"].is_string());
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), bob_rx.recv())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flush_policy_flushes_after_save() {
+        use crate::domain::message::HttpSendMessageRequest;
+        use crate::service::persistence::PersistencePolicies;
+
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = Arc::new(PluginConnectionPool::new(manager.clone()));
+        let runtime = PluginRuntime::new("sled".to_string(), PathBuf::new(), None, None);
+        runtime.set_capabilities(vec!["storage".to_string()]);
+        manager.plugins.insert("sled".to_string(), runtime);
+        let (host, mut plugin) = UnixStream::pair().unwrap();
+        pool.register("sled".to_string(), host);
+
+        // 保存回 SaveMessageResponse，其余回 {"status":"ok"}
+        // Reply to saves with a SaveMessageResponse and to everything else with {"status":"ok"}
+        let (events_tx, mut events_rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok(len) = plugin.read_u32().await {
+                let mut buf = vec![0u8; len as usize];
+                plugin.read_exact(&mut buf).await.unwrap();
+                let event = v::plugin::protocol::EventMessage::decode(&buf[..]).unwrap();
+                let data = if event.event_type == "storage.message.save" {
+                    v::plugin::protocol::SaveMessageResponse {
+                        status: "ok".to_string(),
+                        ..Default::default()
+                    }
+                    .encode_to_vec()
+                } else {
+                    serde_json::to_vec(&serde_json::json!({"status": "ok"})).unwrap()
+                };
+                let resp = v::plugin::protocol::EventResponse {
+                    status: "ok".to_string(),
+                    flow: "continue".to_string(),
+                    data,
+                    error: String::new(),
+                }
+                .encode_to_vec();
+                plugin.write_u32(resp.len() as u32).await.unwrap();
+                plugin.write_all(&resp).await.unwrap();
+                let _ = events_tx.send(event.event_type);
+            }
+        });
+
+        let overrides = serde_json::from_value(serde_json::json!({"message": {"flush": true}}))
+            .unwrap();
+        let ts = crate::testkit::TestServer::build(|mut server| {
+            server.plugin_connection_pool = Some(pool.clone());
+            server.persistence =
+                Arc::new(PersistencePolicies::default().with_overrides(overrides));
+            server
+        });
+        let (_, _rx) = ts.add_client("bob");
+        let send = |message_type: &str| HttpSendMessageRequest {
+            from_uid: "alice".into(),
+            to_uid: "bob".into(),
+            content: serde_json::json!({"text": "..."}),
+            message_type: Some(message_type.to_string()),
+        };
+
+        // 只有开启 flush 的类型在保存后落盘 / Only types with flush on are flushed after saving
+        assert!(ts.server.http_send_message(send("private_message")).await.success);
+        assert!(ts.server.http_send_message(send("message")).await.success);
+        let mut events = Vec::new();
+        while let Ok(Some(event)) =
+            tokio::time::timeout(Duration::from_millis(200), events_rx.recv()).await
+        {
+            events.push(event);
+        }
+        assert_eq!(
+            events,
+            [
+                "storage.message.save",
+                "storage.message.save",
+                v::plugin::protocol::STORAGE_FLUSH_EVENT,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_storage_fallback_replays_in_order_when_plugin_returns() {
+        use crate::service::storage_fallback::tests::{fallback_dir, pool_with, save};
+        use crate::service::storage_fallback::OnUnavailable;
+
+        let path = fallback_dir("buffer");
+        let (manager, pool) = pool_with(OnUnavailable::Buffer, path.clone());
+        assert!(save(&pool, "m1").await.unwrap());
+        assert!(save(&pool, "m2").await.unwrap());
+        assert!(path.exists());
+        let snap = pool.storage_fallback().snapshot();
+        assert_eq!(snap["buffered"], 2);
+        assert_eq!(snap["pending"], 2);
+
+        let runtime = PluginRuntime::new("sled".to_string(), PathBuf::new(), None, None);
+        runtime.set_capabilities(vec!["storage".to_string()]);
+        manager.plugins.insert("sled".to_string(), runtime);
+        let (host, mut plugin) = UnixStream::pair().unwrap();
+        pool.register("sled".to_string(), host);
+        let (saved_tx, mut saved_rx) = tokio::sync::mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            while let Ok(len) = plugin.read_u32().await {
+                let mut buf = vec![0u8; len as usize];
+                plugin.read_exact(&mut buf).await.unwrap();
+                let event = v::plugin::protocol::EventMessage::decode(&buf[..]).unwrap();
+                let request =
+                    v::plugin::protocol::SaveMessageRequest::decode(&event.payload[..]).unwrap();
+                let resp = v::plugin::protocol::EventResponse {
+                    status: "ok".to_string(),
+                    flow: "continue".to_string(),
+                    data: v::plugin::protocol::SaveMessageResponse {
+                        status: "ok".to_string(),
+                        ..Default::default()
+                    }
+                    .encode_to_vec(),
+                    error: String::new(),
+                }
+                .encode_to_vec();
+                plugin.write_u32(resp.len() as u32).await.unwrap();
+                plugin.write_all(&resp).await.unwrap();
+                let _ = saved_tx.send(request.message_id);
+            }
+        });
+
+        assert_eq!(pool.replay_storage_fallback().await.unwrap(), 2);
+        assert_eq!(saved_rx.recv().await.unwrap(), "m1");
+        assert_eq!(saved_rx.recv().await.unwrap(), "m2");
+        let snap = pool.storage_fallback().snapshot();
+        assert_eq!(snap["replayed"], 2);
+        assert_eq!(snap["pending"], 0);
+    }
 }
@@ -0,0 +1,205 @@
+//! 插件套接字权限 / Plugin socket permissions
+//!
+//! 能连上插件 Unix 套接字的进程就能以插件身份握手，因此套接字先在 `0700` 的临时目录中绑定并收紧权限，
+//! 再改名到配置的路径，权限收紧前其他用户无法连接：默认 `0600`，只有服务端用户可以连接。配置了
+//! `plugins.socket_group` 时权限为 `0660`，该组的用户（例如以独立账户运行的插件）也可以连接；
+//! `plugins.socket_owner` 把属主改为指定用户（通常需要 root）。两者可填名称或数字 ID，无法解析或
+//! 设置失败时套接字服务器不会启动。
+//! Any process that can connect to the plugin Unix socket can handshake as a plugin, so the socket
+//! is bound and tightened inside a `0700` staging directory and only then renamed to the configured
+//! path, leaving no window in which other users can connect: `0600` by default, so only the server
+//! user can connect. With `plugins.socket_group` set the mode is `0660` so members of that group
+//! (e.g. plugins running under their own account) can connect too; `plugins.socket_owner` changes
+//! the owner (usually needs root). Both take a name or a numeric id, and the socket server does
+//! not start when they cannot be resolved or applied.
+
+use anyhow::{anyhow, Context, Result};
+use std::ffi::CString;
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::path::Path;
+use tokio::net::UnixListener;
+
+/// 仅属主可连接 / Owner-only
+pub const OWNER_ONLY_MODE: u32 = 0o600;
+/// 属主与属组可连接 / Owner and group
+pub const OWNER_GROUP_MODE: u32 = 0o660;
+
+/// 插件套接字的属主与属组 / Owner and group of the plugin socket
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SocketPermissions {
+    /// 用户名或 uid / User name or uid
+    pub owner: Option<String>,
+    /// 组名或 gid / Group name or gid
+    pub group: Option<String>,
+}
+
+impl SocketPermissions {
+    /// 读取 `plugins.socket_owner` 与 `plugins.socket_group` / Read `plugins.socket_owner` and `plugins.socket_group`
+    pub fn from_config() -> Self {
+        let Ok(cm) = v::get_global_config_manager() else {
+            return Self::default();
+        };
+        let non_empty = |key: &str| {
+            cm.get::<String>(key)
+                .ok()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        Self {
+            owner: non_empty("plugins.socket_owner"),
+            group: non_empty("plugins.socket_group"),
+        }
+    }
+
+    /// 套接字的权限位 / Permission bits of the socket
+    pub fn mode(&self) -> u32 {
+        if self.group.is_some() {
+            OWNER_GROUP_MODE
+        } else {
+            OWNER_ONLY_MODE
+        }
+    }
+
+    /// 在同级的 `0700` 临时目录中绑定并设置属主与权限，再原子地改名到 `path`
+    /// Bind inside a sibling `0700` staging directory, apply owner and mode, then atomically
+    /// rename the socket to `path`
+    pub fn bind(&self, path: &Path) -> Result<UnixListener> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| anyhow!("invalid socket path {}", path.display()))?;
+        let parent = path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let staging = parent.join(format!(".bind-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&staging);
+        // 已存在时 create 失败，不会复用他人预先创建的目录 / create fails if it exists, so a directory planted by someone else is never reused
+        std::fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&staging)
+            .with_context(|| format!("creating {}", staging.display()))?;
+        let staged = staging.join(name);
+        let bound = UnixListener::bind(&staged)
+            .with_context(|| format!("binding {}", staged.display()))
+            .and_then(|listener| {
+                self.apply(&staged)?;
+                std::fs::rename(&staged, path)
+                    .with_context(|| format!("moving the socket to {}", path.display()))?;
+                Ok(listener)
+            });
+        let _ = std::fs::remove_dir_all(&staging);
+        bound
+    }
+
+    /// 设置属主、属组与权限 / Apply owner, group and mode
+    pub fn apply(&self, path: &Path) -> Result<()> {
+        let uid = self.owner.as_deref().map(lookup_user).transpose()?;
+        let gid = self.group.as_deref().map(lookup_group).transpose()?;
+        if uid.is_some() || gid.is_some() {
+            std::os::unix::fs::chown(path, uid, gid)
+                .with_context(|| format!("changing the owner of {}", path.display()))?;
+        }
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(self.mode()))
+            .with_context(|| format!("changing the mode of {}", path.display()))?;
+        Ok(())
+    }
+}
+
+/// 用户名或 uid 解析为 uid / Resolve a user name or uid to a uid
+fn lookup_user(name: &str) -> Result<u32> {
+    if let Ok(uid) = name.parse() {
+        return Ok(uid);
+    }
+    let c_name = CString::new(name)?;
+    with_lookup_buffer(|buf| {
+        // SAFETY: 所有指针都指向本函数内存活的缓冲区 / every pointer refers to a buffer alive in this scope
+        let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut found = std::ptr::null_mut();
+        let rc = unsafe {
+            libc::getpwnam_r(
+                c_name.as_ptr(),
+                &mut pwd,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut found,
+            )
+        };
+        (rc, (!found.is_null()).then_some(pwd.pw_uid))
+    })?
+    .ok_or_else(|| anyhow!("unknown user {}", name))
+}
+
+/// 组名或 gid 解析为 gid / Resolve a group name or gid to a gid
+fn lookup_group(name: &str) -> Result<u32> {
+    if let Ok(gid) = name.parse() {
+        return Ok(gid);
+    }
+    let c_name = CString::new(name)?;
+    with_lookup_buffer(|buf| {
+        // SAFETY: 所有指针都指向本函数内存活的缓冲区 / every pointer refers to a buffer alive in this scope
+        let mut grp: libc::group = unsafe { std::mem::zeroed() };
+        let mut found = std::ptr::null_mut();
+        let rc = unsafe {
+            libc::getgrnam_r(
+                c_name.as_ptr(),
+                &mut grp,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut found,
+            )
+        };
+        (rc, (!found.is_null()).then_some(grp.gr_gid))
+    })?
+    .ok_or_else(|| anyhow!("unknown group {}", name))
+}
+
+/// 以逐步增大的缓冲区调用 `get*nam_r`，直到不再返回 ERANGE
+/// Call a `get*nam_r` with a growing buffer until it stops returning ERANGE
+fn with_lookup_buffer<T>(
+    mut lookup: impl FnMut(&mut [libc::c_char]) -> (libc::c_int, Option<T>),
+) -> Result<Option<T>> {
+    let mut buf = vec![0 as libc::c_char; 1024];
+    loop {
+        match lookup(&mut buf) {
+            (0, found) => return Ok(found),
+            (libc::ERANGE, _) if buf.len() < 1 << 20 => buf.resize(buf.len() * 2, 0),
+            (rc, _) => return Err(std::io::Error::from_raw_os_error(rc).into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::runtime::{PluginRuntimeManager, UnixSocketServer};
+    use std::os::unix::fs::MetadataExt;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_plugin_socket_is_owner_only() {
+        let dir = std::env::temp_dir().join(format!("vgo-socket-perms-{}", std::process::id()));
+        let path = dir.join("runtime.sock");
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let (_tx, rx) = tokio::sync::watch::channel(false);
+        let _server = UnixSocketServer::new(&path, manager, rx).await.unwrap();
+        let meta = std::fs::metadata(&path).unwrap();
+        assert_eq!(meta.mode() & 0o777, OWNER_ONLY_MODE);
+        // 临时目录已清理 / The staging directory is gone
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+        tokio::net::UnixStream::connect(&path).await.unwrap();
+
+        // 配置属组后组内用户也可连接 / With a group configured its members can connect too
+        let gid = meta.gid();
+        let perms = SocketPermissions {
+            owner: Some(meta.uid().to_string()),
+            group: Some(gid.to_string()),
+        };
+        perms.apply(&path).unwrap();
+        let meta = std::fs::metadata(&path).unwrap();
+        assert_eq!(meta.mode() & 0o777, OWNER_GROUP_MODE);
+        assert_eq!(meta.gid(), gid);
+
+        assert!(lookup_user("no-such-user-vgo").is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,575 @@
+use actix_web::body::BoxBody;
+use actix_web::dev::ServiceRequest;
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::{from_fn, Next};
+use actix_web::{web, HttpResponse};
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use serde_json::json;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use v::response::respond_any;
+
+/// 路由级中间件：通过返回 Ok(()) 放行，返回 Err(resp) 直接短路响应
+/// Per-route middleware: Ok(()) passes through, Err(resp) short-circuits with a response
+pub type RouteMiddleware = Arc<dyn Fn(&ServiceRequest) -> Result<(), HttpResponse> + Send + Sync>;
+
+/// 中间件工厂：每次 configure（每个 worker 一次）时为对应路由生成实例；限流计数等状态随工厂创建，各 worker 共享
+/// Middleware factory: builds an instance per route on each configure (once per worker); state such as
+/// rate-limit counters is created with the factory and shared by every worker
+pub type MiddlewareFactory = Arc<dyn Fn() -> RouteMiddleware + Send + Sync>;
+
+/// 路由注册函数（与 api 模块中的 `register(cfg, path)` 签名一致）
+/// Route register fn (same signature as `register(cfg, path)` in api modules)
+pub type RegisterFn = fn(&mut web::ServiceConfig, &str);
+
+/// 路由信息 / Route info
+#[derive(Clone)]
+pub struct RouteInfo {
+    pub path: &'static str,
+    pub register: RegisterFn,
+    /// 仅作用于本路由作用域的中间件 / Middlewares applied only to this route's scope
+    pub middlewares: Vec<MiddlewareFactory>,
+}
+
+impl RouteInfo {
+    /// 创建无中间件的路由 / Create a route without middleware
+    pub fn new(path: &'static str, register: RegisterFn) -> Self {
+        Self {
+            path,
+            register,
+            middlewares: Vec::new(),
+        }
+    }
+
+    /// 追加路由级中间件 / Attach a per-route middleware
+    pub fn with_middleware(mut self, factory: MiddlewareFactory) -> Self {
+        self.middlewares.push(factory);
+        self
+    }
+}
+
+/// 注册路由表；带中间件的路由包裹在独立 scope 中，更具体的路径优先注册避免前缀遮挡
+/// Register the route table; routes with middleware get their own scope, more specific paths first so prefixes don't shadow them
+pub fn configure(cfg: &mut web::ServiceConfig, routes: &[RouteInfo]) {
+    let mut ordered: Vec<&RouteInfo> = routes.iter().collect();
+    ordered.sort_by_key(|r| std::cmp::Reverse(r.path.trim_end_matches('/').matches('/').count()));
+
+    for route in ordered {
+        if route.middlewares.is_empty() {
+            (route.register)(cfg, route.path);
+            continue;
+        }
+        let chain: Arc<Vec<RouteMiddleware>> =
+            Arc::new(route.middlewares.iter().map(|factory| factory()).collect());
+        let register = route.register;
+        cfg.service(
+            web::scope(route.path)
+                .wrap(from_fn(move |req: ServiceRequest, next: Next<BoxBody>| {
+                    let chain = chain.clone();
+                    async move {
+                        for mw in chain.iter() {
+                            if let Err(resp) = mw(&req) {
+                                return Ok(req.into_response(resp));
+                            }
+                        }
+                        next.call(req).await
+                    }
+                }))
+                .configure(move |scope_cfg| register(scope_cfg, "")),
+        );
+    }
+}
+
+/// 管理员令牌中间件工厂：校验 `X-Admin-Token` 或 `Authorization: Bearer <token>`
+/// Admin-token middleware factory: checks `X-Admin-Token` or `Authorization: Bearer <token>`
+pub fn admin_token(expected: impl Into<String>) -> MiddlewareFactory {
+    let expected: Arc<str> = Arc::from(expected.into());
+    Arc::new(move || {
+        let expected = expected.clone();
+        Arc::new(move |req: &ServiceRequest| {
+            let headers = req.headers();
+            let provided = headers
+                .get("X-Admin-Token")
+                .and_then(|v| v.to_str().ok())
+                .or_else(|| {
+                    headers
+                        .get(actix_web::http::header::AUTHORIZATION)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|v| v.strip_prefix("Bearer "))
+                });
+            match provided {
+                Some(token) if !expected.is_empty() && constant_time_eq(token, &expected) => Ok(()),
+                _ => Err(respond_any(
+                    StatusCode::UNAUTHORIZED,
+                    json!({"message": "admin token required"}),
+                )),
+            }
+        })
+    })
+}
+
+/// 从配置 `server.admin_token` 构建管理员令牌中间件（未配置时返回 None）
+/// Build the admin-token middleware from `server.admin_token` (None when unset)
+pub fn admin_token_from_config() -> Option<MiddlewareFactory> {
+    let token: String = v::get_global_config_manager()
+        .map(|cm| cm.get_or("server.admin_token", String::new()))
+        .unwrap_or_default();
+    if token.is_empty() {
+        None
+    } else {
+        Some(admin_token(token))
+    }
+}
+
+/// 只对写请求生效的中间件：GET 与 HEAD 直接放行
+/// Middleware that only applies to writes: GET and HEAD pass straight through
+pub fn writes_only(inner: MiddlewareFactory) -> MiddlewareFactory {
+    Arc::new(move || {
+        let inner = inner();
+        Arc::new(move |req: &ServiceRequest| {
+            if matches!(*req.method(), Method::GET | Method::HEAD) {
+                Ok(())
+            } else {
+                inner(req)
+            }
+        })
+    })
+}
+
+/// 节点间请求携带共享密钥的请求头 / Header carrying the shared secret on node-to-node requests
+pub const INTERNAL_TOKEN_HEADER: &str = "X-Internal-Token";
+
+/// 集群内部令牌 `cluster.internal_token`（未配置时为空）/ Cluster internal token `cluster.internal_token` (empty when unset)
+pub fn internal_token_from_config() -> String {
+    v::get_global_config_manager()
+        .map(|cm| cm.get_or("cluster.internal_token", String::new()))
+        .unwrap_or_default()
+}
+
+/// 内部接口中间件工厂：校验 `X-Internal-Token`；未配置令牌时拒绝所有请求
+/// Internal-endpoint middleware factory: checks `X-Internal-Token`; rejects everything when no token is configured
+pub fn internal_token(expected: impl Into<String>) -> MiddlewareFactory {
+    header_token(INTERNAL_TOKEN_HEADER, expected, "internal token required")
+}
+
+/// 网关转发请求携带共享密钥的请求头 / Header carrying the shared secret on requests forwarded by the gateway
+pub const GATEWAY_TOKEN_HEADER: &str = "X-Gateway-Token";
+
+/// 网关令牌 `server.gateway_token`（未配置时为空）/ Gateway token `server.gateway_token` (empty when unset)
+pub fn gateway_token_from_config() -> String {
+    v::get_global_config_manager()
+        .map(|cm| cm.get_or("server.gateway_token", String::new()))
+        .unwrap_or_default()
+}
+
+/// 网关接口中间件工厂：校验 `X-Gateway-Token`；未配置令牌时拒绝所有请求
+/// Gateway-endpoint middleware factory: checks `X-Gateway-Token`; rejects everything when no token is configured
+pub fn gateway_token(expected: impl Into<String>) -> MiddlewareFactory {
+    header_token(GATEWAY_TOKEN_HEADER, expected, "gateway token required")
+}
+
+/// 校验指定请求头中的共享密钥；期望值为空时拒绝所有请求
+/// Check a shared secret in the given header; an empty expected value rejects everything
+fn header_token(
+    header: &'static str,
+    expected: impl Into<String>,
+    message: &'static str,
+) -> MiddlewareFactory {
+    let expected: Arc<str> = Arc::from(expected.into());
+    Arc::new(move || {
+        let expected = expected.clone();
+        Arc::new(move |req: &ServiceRequest| {
+            let provided = req.headers().get(header).and_then(|v| v.to_str().ok());
+            match provided {
+                Some(token) if !expected.is_empty() && constant_time_eq(token, &expected) => Ok(()),
+                _ => Err(respond_any(
+                    StatusCode::UNAUTHORIZED,
+                    json!({ "message": message }),
+                )),
+            }
+        })
+    })
+}
+
+/// 与内容无关耗时的比较，避免按时间逐字节猜测令牌
+/// Comparison whose timing does not depend on the content, so the token cannot be guessed byte by byte
+pub(crate) fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// 受信任的反向代理地址 `server.trusted_proxies`（未配置时为空）；仅来自这些地址的请求才采用
+/// `X-Forwarded-For` / `Forwarded` 中的客户端 IP
+/// Trusted reverse proxy addresses `server.trusted_proxies` (empty when unset); only requests
+/// from these addresses have their client IP taken from `X-Forwarded-For` / `Forwarded`
+pub fn trusted_proxies_from_config() -> Vec<IpAddr> {
+    let entries: Vec<String> = v::get_global_config_manager()
+        .map(|cm| cm.get_or("server.trusted_proxies", Vec::new()))
+        .unwrap_or_default();
+    entries
+        .iter()
+        .filter_map(|entry| match entry.parse() {
+            Ok(ip) => Some(ip),
+            Err(_) => {
+                tracing::warn!(
+                    "⚠️  忽略无效的 server.trusted_proxies 项 / Ignoring invalid server.trusted_proxies entry: {}",
+                    entry
+                );
+                None
+            }
+        })
+        .collect()
+}
+
+/// 固定窗口计数；过期窗口每隔一个窗口清理一次
+/// Fixed-window counters; expired windows are dropped once per window
+struct FixedWindows {
+    max_requests: usize,
+    window: Duration,
+    buckets: DashMap<String, (usize, Instant)>,
+    last_sweep: Mutex<Instant>,
+}
+
+impl FixedWindows {
+    fn new(max_requests: usize, window: Duration) -> Self {
+        Self {
+            max_requests,
+            window,
+            buckets: DashMap::new(),
+            last_sweep: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// 记一次请求，超出限额时返回 false / Count one request; false once over the limit
+    fn hit(&self, key: String, now: Instant) -> bool {
+        self.sweep(now);
+        let mut entry = self.buckets.entry(key).or_insert((0, now));
+        if now.duration_since(entry.1) >= self.window {
+            *entry = (0, now);
+        }
+        if entry.0 >= self.max_requests {
+            return false;
+        }
+        entry.0 += 1;
+        true
+    }
+
+    fn sweep(&self, now: Instant) {
+        let Some(mut last) = self.last_sweep.try_lock() else {
+            return;
+        };
+        if now.duration_since(*last) < self.window {
+            return;
+        }
+        *last = now;
+        let window = self.window;
+        self.buckets
+            .retain(|_, (_, started)| now.duration_since(*started) < window);
+    }
+}
+
+/// 限流中间件工厂：按客户端 IP 的固定窗口计数，计数在所有 worker 间共享
+/// Rate-limit middleware factory: fixed-window counter keyed by client IP, shared by all workers
+///
+/// 客户端 IP 取 TCP 对端地址；对端属于 `trusted_proxies` 时才改用转发头中的地址，其余请求无法借
+/// 伪造转发头绕过限流。
+/// The client IP is the TCP peer address; only when the peer is one of `trusted_proxies` is the
+/// address from the forwarding headers used instead, so other callers cannot dodge the limit by
+/// forging those headers.
+pub fn rate_limit(
+    max_requests: usize,
+    window: Duration,
+    trusted_proxies: Vec<IpAddr>,
+) -> MiddlewareFactory {
+    let windows = Arc::new(FixedWindows::new(max_requests, window));
+    let trusted_proxies: Arc<[IpAddr]> = trusted_proxies.into();
+    Arc::new(move || {
+        let windows = windows.clone();
+        let trusted_proxies = trusted_proxies.clone();
+        Arc::new(move |req: &ServiceRequest| {
+            let peer = req.peer_addr().map(|addr| addr.ip());
+            let key = match peer {
+                Some(ip) if trusted_proxies.contains(&ip) => req
+                    .connection_info()
+                    .realip_remote_addr()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| ip.to_string()),
+                Some(ip) => ip.to_string(),
+                None => "unknown".to_string(),
+            };
+            if windows.hit(key, Instant::now()) {
+                Ok(())
+            } else {
+                Err(respond_any(
+                    StatusCode::TOO_MANY_REQUESTS,
+                    json!({"message": "rate limit exceeded"}),
+                ))
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{test, App, HttpResponse};
+
+    fn ok_route(cfg: &mut web::ServiceConfig, path: &str) {
+        cfg.service(
+            web::resource(path)
+                .route(web::get().to(|| async { HttpResponse::Ok().finish() }))
+                .route(web::post().to(|| async { HttpResponse::Ok().finish() })),
+        );
+    }
+
+    fn routes() -> Vec<RouteInfo> {
+        vec![
+            RouteInfo::new("/v1/public", ok_route),
+            RouteInfo::new("/v1/admin", ok_route).with_middleware(admin_token("secret")),
+            RouteInfo::new("/v1/internal", ok_route).with_middleware(internal_token("s3cret")),
+            RouteInfo::new("/v1/gateway", ok_route).with_middleware(gateway_token("g4te")),
+            RouteInfo::new("/v1/meta", ok_route)
+                .with_middleware(writes_only(admin_token("secret"))),
+            RouteInfo::new("/v1/limited", ok_route).with_middleware(rate_limit(
+                1,
+                Duration::from_secs(60),
+                Vec::new(),
+            )),
+            RouteInfo::new("/v1/proxied", ok_route).with_middleware(rate_limit(
+                1,
+                Duration::from_secs(60),
+                vec!["10.0.0.1".parse().unwrap()],
+            )),
+        ]
+    }
+
+    #[actix_web::test]
+    async fn protected_route_requires_admin_token() {
+        let app = test::init_service(App::new().configure(|cfg| configure(cfg, &routes()))).await;
+
+        let resp =
+            test::call_service(&app, test::TestRequest::get().uri("/v1/admin").to_request()).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+        let resp = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/v1/admin")
+                .insert_header(("X-Admin-Token", "secret"))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = test::call_service(
+            &app,
+            test::TestRequest::get().uri("/v1/public").to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn internal_route_requires_internal_token() {
+        let app = test::init_service(App::new().configure(|cfg| configure(cfg, &routes()))).await;
+
+        for token in [None, Some("wrong"), Some("s3cre")] {
+            let mut req = test::TestRequest::get().uri("/v1/internal");
+            if let Some(token) = token {
+                req = req.insert_header((INTERNAL_TOKEN_HEADER, token));
+            }
+            let resp = test::call_service(&app, req.to_request()).await;
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{:?}", token);
+        }
+        let resp = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/v1/internal")
+                .insert_header((INTERNAL_TOKEN_HEADER, "s3cret"))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // 未配置令牌时一律拒绝 / Everything is rejected when no token is configured
+        let open =
+            vec![RouteInfo::new("/v1/internal", ok_route).with_middleware(internal_token(""))];
+        let app = test::init_service(App::new().configure(|cfg| configure(cfg, &open))).await;
+        let resp = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/v1/internal")
+                .insert_header((INTERNAL_TOKEN_HEADER, ""))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn gateway_route_requires_gateway_token() {
+        let app = test::init_service(App::new().configure(|cfg| configure(cfg, &routes()))).await;
+
+        for (header, token) in [
+            (GATEWAY_TOKEN_HEADER, "wrong"),
+            (INTERNAL_TOKEN_HEADER, "g4te"),
+            ("X-Admin-Token", "g4te"),
+        ] {
+            let resp = test::call_service(
+                &app,
+                test::TestRequest::get()
+                    .uri("/v1/gateway")
+                    .insert_header((header, token))
+                    .to_request(),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{}", header);
+        }
+        let resp = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/v1/gateway")
+                .insert_header((GATEWAY_TOKEN_HEADER, "g4te"))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn writes_only_lets_reads_through() {
+        let app = test::init_service(App::new().configure(|cfg| configure(cfg, &routes()))).await;
+
+        let read =
+            test::call_service(&app, test::TestRequest::get().uri("/v1/meta").to_request()).await;
+        assert_eq!(read.status(), StatusCode::OK);
+        let write =
+            test::call_service(&app, test::TestRequest::post().uri("/v1/meta").to_request()).await;
+        assert_eq!(write.status(), StatusCode::UNAUTHORIZED);
+        let write = test::call_service(
+            &app,
+            test::TestRequest::post()
+                .uri("/v1/meta")
+                .insert_header(("X-Admin-Token", "secret"))
+                .to_request(),
+        )
+        .await;
+        assert_eq!(write.status(), StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn rate_limit_applies_only_to_its_route() {
+        let app = test::init_service(App::new().configure(|cfg| configure(cfg, &routes()))).await;
+
+        let first = test::call_service(
+            &app,
+            test::TestRequest::get().uri("/v1/limited").to_request(),
+        )
+        .await;
+        assert_eq!(first.status(), StatusCode::OK);
+        let second = test::call_service(
+            &app,
+            test::TestRequest::get().uri("/v1/limited").to_request(),
+        )
+        .await;
+        assert_eq!(second.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        for _ in 0..3 {
+            let resp = test::call_service(
+                &app,
+                test::TestRequest::get().uri("/v1/public").to_request(),
+            )
+            .await;
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+    }
+
+    #[actix_web::test]
+    async fn rate_limit_is_shared_across_workers() {
+        // 同一路由表配置两个 App，模拟两个 worker / One route table configured into two apps, like two workers
+        let routes = routes();
+        let worker_a =
+            test::init_service(App::new().configure(|cfg| configure(cfg, &routes))).await;
+        let worker_b =
+            test::init_service(App::new().configure(|cfg| configure(cfg, &routes))).await;
+
+        let first = test::call_service(
+            &worker_a,
+            test::TestRequest::get().uri("/v1/limited").to_request(),
+        )
+        .await;
+        assert_eq!(first.status(), StatusCode::OK);
+        let second = test::call_service(
+            &worker_b,
+            test::TestRequest::get().uri("/v1/limited").to_request(),
+        )
+        .await;
+        assert_eq!(second.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    fn from(peer: &str, forwarded_for: &str, uri: &str) -> test::TestRequest {
+        test::TestRequest::get()
+            .uri(uri)
+            .peer_addr(format!("{}:40000", peer).parse().unwrap())
+            .insert_header(("X-Forwarded-For", forwarded_for))
+    }
+
+    #[actix_web::test]
+    async fn rate_limit_ignores_forwarded_for_from_untrusted_peers() {
+        let app = test::init_service(App::new().configure(|cfg| configure(cfg, &routes()))).await;
+
+        let first = test::call_service(
+            &app,
+            from("203.0.113.7", "1.1.1.1", "/v1/limited").to_request(),
+        )
+        .await;
+        assert_eq!(first.status(), StatusCode::OK);
+        // 换一个伪造的转发地址仍计入同一对端 / A different forged address still counts against the same peer
+        let second = test::call_service(
+            &app,
+            from("203.0.113.7", "2.2.2.2", "/v1/limited").to_request(),
+        )
+        .await;
+        assert_eq!(second.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // 受信任代理后的不同客户端各自计数 / Clients behind a trusted proxy are counted separately
+        let first = test::call_service(
+            &app,
+            from("10.0.0.1", "1.1.1.1", "/v1/proxied").to_request(),
+        )
+        .await;
+        assert_eq!(first.status(), StatusCode::OK);
+        let other = test::call_service(
+            &app,
+            from("10.0.0.1", "2.2.2.2", "/v1/proxied").to_request(),
+        )
+        .await;
+        assert_eq!(other.status(), StatusCode::OK);
+        let again = test::call_service(
+            &app,
+            from("10.0.0.1", "1.1.1.1", "/v1/proxied").to_request(),
+        )
+        .await;
+        assert_eq!(again.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[test]
+    fn expired_windows_are_swept() {
+        let windows = FixedWindows::new(1, Duration::from_secs(60));
+        let start = Instant::now();
+        assert!(windows.hit("a".into(), start));
+        assert!(windows.hit("b".into(), start));
+        assert!(!windows.hit("a".into(), start));
+        assert_eq!(windows.buckets.len(), 2);
+
+        // 一个窗口后的请求清理掉过期的 a、b / A request one window later drops the expired a and b
+        assert!(windows.hit("c".into(), start + Duration::from_secs(61)));
+        assert_eq!(windows.buckets.len(), 1);
+        assert!(windows.buckets.contains_key("c"));
+    }
+}
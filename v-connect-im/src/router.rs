@@ -1,11 +1,144 @@
-use actix_web::web;
+use crate::route_registry::{self, RouteInfo};
+use std::time::Duration;
 
-/// 路由配置包装 / Route configuration wrapper
-/// 只保留健康检查接口 / Only keep health check endpoints
-pub fn configure(cfg: &mut web::ServiceConfig) {
-    // 只注册健康检查接口 / Only register health check endpoints
-    crate::api::v1::health::basic::register(cfg, "/v1/health");
-    crate::api::v1::health::live::register(cfg, "/v1/health/live");
-    crate::api::v1::health::ready::register(cfg, "/v1/health/ready");
-    crate::api::v1::health::detailed::register(cfg, "/v1/health/detailed");
+/// 路由表 / Route table
+/// 健康检查与网关插件转发的消息/房间接口；详细健康信息要求管理员令牌并限流
+/// Health checks plus the message/room APIs the gateway plugin forwards to; detailed health gets the admin token and rate limiting
+/// 消息/房间接口的请求体自带发送者 uid，消息查询按请求中的 uid 读取私聊历史，均始终要求 `server.gateway_token`
+/// The message/room APIs take the sender uid from the body and the message lookups read private history for the uid in the request, so they always require `server.gateway_token`
+/// 插件日志、webhook 死信、封禁名单、维护模式等管理接口始终要求管理员令牌，未配置 `server.admin_token` 时一律拒绝
+/// Admin endpoints such as plugin logs, the webhook dead letter, the blocklist and maintenance mode always require the admin token and refuse everything while `server.admin_token` is unset
+/// 状态转储读写服务端文件，只在配置了管理员令牌时注册 / State dumps read and write server files, so they are only registered when an admin token is configured
+/// Prometheus 指标 `/v1/metrics` 同样受管理员令牌保护 / The Prometheus metrics at `/v1/metrics` are guarded by the admin token as well
+pub fn routes() -> Vec<RouteInfo> {
+    // 未配置管理员令牌时拒绝所有请求 / Rejects every request while no admin token is configured
+    let admin = route_registry::admin_token_from_config()
+        .unwrap_or_else(|| route_registry::admin_token(""));
+    let detailed = RouteInfo::new(
+        "/v1/health/detailed",
+        crate::api::v1::health::detailed::register,
+    )
+    .with_middleware(admin.clone());
+    let admin_routes = vec![
+        RouteInfo::new("/v1/metrics", crate::api::v1::metrics::register),
+        RouteInfo::new(
+            "/v1/admin/plugins",
+            crate::api::v1::admin::plugins::list::register,
+        ),
+        RouteInfo::new(
+            "/v1/admin/plugins/{name}/logs",
+            crate::api::v1::admin::plugins::logs::register,
+        ),
+        RouteInfo::new(
+            "/v1/admin/system/message",
+            crate::api::v1::admin::system::message::register,
+        ),
+        RouteInfo::new(
+            "/v1/admin/storage/archive",
+            crate::api::v1::admin::storage::archive::register,
+        ),
+        RouteInfo::new(
+            "/v1/admin/storage/restore",
+            crate::api::v1::admin::storage::restore::register,
+        ),
+        RouteInfo::new(
+            "/v1/admin/webhooks/dead_letter",
+            crate::api::v1::admin::webhooks::dead_letter::register,
+        ),
+        RouteInfo::new(
+            "/v1/admin/blocked_uids",
+            crate::api::v1::admin::blocked_uids::register,
+        ),
+        RouteInfo::new(
+            "/v1/admin/maintenance",
+            crate::api::v1::admin::maintenance::register,
+        ),
+        // 扇出进度含房间规模与投递统计 / Fan-out progress exposes room sizes and delivery counts
+        RouteInfo::new("/v1/room/fanout", crate::api::v1::room::fanout::register),
+    ];
+    // 房间元数据含保留期，写入须管理员令牌 / Room metadata carries the retention window, so writes need the admin token
+    let room_meta = RouteInfo::new("/v1/room/meta", crate::api::v1::room::meta::register)
+        .with_middleware(route_registry::writes_only(admin.clone()));
+    let mut state_routes = Vec::new();
+    if route_registry::admin_token_from_config().is_some() {
+        state_routes.push(
+            RouteInfo::new(
+                "/v1/admin/state/dump",
+                crate::api::v1::admin::state::dump::register,
+            )
+            .with_middleware(admin.clone()),
+        );
+        state_routes.push(
+            RouteInfo::new(
+                "/v1/admin/state/restore",
+                crate::api::v1::admin::state::restore::register,
+            )
+            .with_middleware(admin.clone()),
+        );
+    }
+    let per_minute: usize = v::get_global_config_manager()
+        .map(|cm| cm.get_or("server.detailed_health_rate_limit", 60usize))
+        .unwrap_or(60);
+    let detailed = detailed.with_middleware(route_registry::rate_limit(
+        per_minute,
+        Duration::from_secs(60),
+        route_registry::trusted_proxies_from_config(),
+    ));
+
+    // 节点间接口始终要求 `cluster.internal_token` / Node-to-node endpoints always require `cluster.internal_token`
+    let internal = route_registry::internal_token(route_registry::internal_token_from_config());
+    let gateway = route_registry::gateway_token(route_registry::gateway_token_from_config());
+
+    let mut routes = vec![
+        RouteInfo::new("/v1/health", crate::api::v1::health::basic::register),
+        RouteInfo::new("/v1/health/live", crate::api::v1::health::live::register),
+        RouteInfo::new("/v1/health/ready", crate::api::v1::health::ready::register),
+        detailed,
+        RouteInfo::new("/v1/message/send", crate::api::v1::message::send::register)
+            .with_middleware(gateway.clone()),
+        RouteInfo::new(
+            "/v1/message/search",
+            crate::api::v1::message::search::register,
+        )
+        .with_middleware(gateway.clone()),
+        RouteInfo::new("/v1/message/get", crate::api::v1::message::get::register)
+            .with_middleware(gateway.clone()),
+        RouteInfo::new(
+            "/v1/message/thread",
+            crate::api::v1::message::thread::register,
+        )
+        .with_middleware(gateway.clone()),
+        RouteInfo::new("/v1/room/send", crate::api::v1::room::send::register)
+            .with_middleware(gateway.clone()),
+        RouteInfo::new("/v1/room/join", crate::api::v1::room::join::register)
+            .with_middleware(gateway.clone()),
+        RouteInfo::new("/v1/room/leave", crate::api::v1::room::leave::register)
+            .with_middleware(gateway.clone()),
+        RouteInfo::new("/v1/room/members", crate::api::v1::room::members::register)
+            .with_middleware(gateway.clone()),
+        room_meta,
+        RouteInfo::new("/v1/rooms/nearby", crate::api::v1::room::nearby::register),
+        RouteInfo::new(
+            "/v1/internal/clients_by_uid",
+            crate::api::v1::internal::clients_by_uid::register,
+        )
+        .with_middleware(internal.clone()),
+        RouteInfo::new(
+            "/v1/internal/forward_client",
+            crate::api::v1::internal::forward_client::register,
+        )
+        .with_middleware(internal.clone()),
+        RouteInfo::new(
+            "/v1/internal/forward_batch",
+            crate::api::v1::internal::forward_batch::register,
+        )
+        .with_middleware(internal),
+    ];
+    routes.extend(
+        admin_routes
+            .into_iter()
+            .map(|route| route.with_middleware(admin.clone())),
+    );
+    routes.extend(state_routes);
+    routes
 }
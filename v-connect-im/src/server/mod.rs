@@ -7,7 +7,7 @@ use parking_lot::RwLock;
 use serde_json::Value;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use tokio::sync::oneshot;
 use tokio_tungstenite::tungstenite::Message;
 
 /// 客户端连接信息 / Client Connection Information
@@ -16,9 +16,85 @@ pub struct Connection {
     #[allow(dead_code)]
     pub client_id: String, // 客户端唯一ID（当前未读取）/ Client unique ID (currently not read)
     pub uid: Option<String>,                    // 用户ID / User ID
+    pub device_id: Option<String>,              // 设备ID（认证时协商）/ Device ID (negotiated at auth)
+    pub protocol_version: u32,                  // 升级时协商的消息协议版本 / Message protocol version negotiated at upgrade
+    pub wire_format: crate::ws::protocol::WireFormat, // 升级时协商的帧编码 / Frame encoding negotiated at upgrade
+    pub metadata: Arc<DashMap<String, Value>>,  // 连接级元数据 / Connection-scoped metadata
     pub addr: SocketAddr,                       // 客户端地址 / Client address
-    pub sender: mpsc::UnboundedSender<Message>, // 消息发送器 / Message sender
+    pub sender: crate::ws::send_queue::QueueSender, // 有界优先级发送队列 / Bounded priority send queue
+    pub connected_at: std::time::Instant,       // 建立连接的时间 / When the connection was established
+    pub last_sent: Arc<std::sync::Mutex<std::time::Instant>>, // 客户端最后发送非 ping 消息的时间 / When the client last sent a non-ping message
     pub last_heartbeat: Arc<std::sync::Mutex<std::time::Instant>>, // 最后心跳时间 / Last heartbeat time
+    pub closer: ConnectionCloser, // 直接结束连接任务 / Ends the connection task directly
+}
+
+/// 结束连接任务的句柄：连接任务写出给定的关闭帧后断开，不经过可能已满的发送队列
+/// Handle that ends a connection task: the task writes the given close frame and disconnects,
+/// bypassing a send queue that may be full
+#[derive(Clone, Default)]
+pub struct ConnectionCloser {
+    tx: Arc<parking_lot::Mutex<Option<oneshot::Sender<Message>>>>,
+}
+
+impl ConnectionCloser {
+    /// 创建句柄与连接任务持有的接收端 / Create the handle and the receiver held by the connection task
+    pub fn new() -> (Self, oneshot::Receiver<Message>) {
+        let (tx, rx) = oneshot::channel();
+        (
+            Self {
+                tx: Arc::new(parking_lot::Mutex::new(Some(tx))),
+            },
+            rx,
+        )
+    }
+
+    /// 要求连接任务发送 `close` 后结束；没有连接任务或已结束时返回 false
+    /// Ask the connection task to send `close` and stop; false when there is no task or it already stopped
+    pub fn close(&self, close: Message) -> bool {
+        match self.tx.lock().take() {
+            Some(tx) => tx.send(close).is_ok(),
+            None => false,
+        }
+    }
+}
+
+/// 每个连接最多保存的元数据条目 / Max metadata entries per connection
+pub const MAX_METADATA_ENTRIES: usize = 64;
+
+impl Connection {
+    /// 发送队列中待写出的消息数 / Messages waiting in the send queue
+    pub fn queued(&self) -> usize {
+        self.sender.max_capacity() - self.sender.capacity()
+    }
+
+    /// 写入元数据；新键超过上限时返回 false / Set a metadata entry; false when a new key exceeds the cap
+    pub fn set_meta(&self, key: impl Into<String>, value: Value) -> bool {
+        let key = key.into();
+        if !self.metadata.contains_key(&key) && self.metadata.len() >= MAX_METADATA_ENTRIES {
+            return false;
+        }
+        self.metadata.insert(key, value);
+        true
+    }
+
+    /// 读取元数据 / Read a metadata entry
+    pub fn get_meta(&self, key: &str) -> Option<Value> {
+        self.metadata.get(key).map(|v| v.value().clone())
+    }
+}
+
+/// 默认单连接发送队列容量 / Default per-connection send queue capacity
+pub const DEFAULT_SEND_QUEUE_CAPACITY: usize = 1024;
+
+/// 单连接发送队列容量（`server.send_queue_capacity`）；队列满时新消息被拒绝，慢客户端不会耗尽内存
+/// Per-connection send queue capacity (`server.send_queue_capacity`); new messages are
+/// refused when full so a slow client cannot exhaust memory
+pub fn send_queue_capacity() -> usize {
+    v::get_global_config_manager()
+        .ok()
+        .map(|cm| cm.get_or("server.send_queue_capacity", DEFAULT_SEND_QUEUE_CAPACITY))
+        .unwrap_or(DEFAULT_SEND_QUEUE_CAPACITY)
+        .max(1)
 }
 
 /// 服务端全局状态 / Server Global State
@@ -31,9 +107,31 @@ pub struct VConnectIMServer {
     pub plugin_connection_pool: Option<Arc<crate::plugins::runtime::PluginConnectionPool>>, // 插件连接池 / Plugin connection pool
     pub plugin_config: Arc<RwLock<Value>>, // 插件配置快照 / Plugin config snapshot
     pub acked_ids: Arc<DashMap<String, DashSet<String>>>, // 已确认消息ID / Acked message IDs per client
+    pub ack_windows: Arc<crate::service::ack::AckWindows>, // 确认时限与已关闭的确认窗口 / Ack deadline and closed ack windows
+    pub device_states: Arc<crate::service::device_sync::DeviceSyncTracker>, // 多端投递状态 / Per-device delivery state
+    pub resume_tokens: Arc<crate::service::resume::ResumeTokenStore>, // 断线重连令牌 / Reconnection tokens
+    pub room_guard: Arc<crate::service::room_guard::RoomGuard>, // 房间操作限流 / Room operation limits
+    pub tenants: Arc<crate::service::tenant::Tenants>, // 多租户限额 / Tenant-scoped limits
+    pub webhooks: Arc<crate::service::webhook::WebhookDispatcher>, // Webhook 投递与重试 / Webhook delivery and retries
+    pub event_bus: Arc<crate::service::event_bus::EventBus>, // 进程内事件总线 / In-process event bus
+    pub scheduler: Arc<crate::service::scheduler::MessageScheduler>, // 定时消息 / Scheduled messages
+    pub pin_policy: Arc<crate::service::pins::PinPolicy>, // 置顶上限与房间管理员 / Pin cap and room admins
+    pub retention: Arc<crate::service::retention::RetentionPolicy>, // 消息保留期 / Message retention
+    pub fanout: Arc<crate::service::fanout::FanOutJobs>, // 群消息扇出与后台任务 / Group fan-out and background jobs
+    pub group_dedup: Arc<crate::service::group_dedup::GroupDedup>, // 群消息幂等 / Group message idempotency
+    pub required_plugins: Arc<Vec<String>>, // 就绪前必须连接的插件 / Plugins that must be connected for readiness
+    pub admission: Arc<crate::service::admission::AdmissionPolicy>, // 连接准入 / Connection admission
+    pub auth_guard: Arc<crate::service::auth_guard::AuthGuard>, // 认证尝试限制 / Auth attempt guard
+    pub reaper: Arc<crate::service::reaper::ReaperPolicy>, // 连接寿命与空闲上限 / Connection lifetime and idle caps
+    pub offline_queue: Arc<crate::service::offline_queue::OfflineQueue>, // 离线写入队列 / Offline write queue
+    pub persistence: Arc<crate::service::persistence::PersistencePolicies>, // 按类型的持久化策略 / Per-type persistence policy
+    pub id_gen: Arc<crate::service::id_gen::IdGenerator>, // 消息 ID 生成器 / Message id generator
+    pub metrics: Arc<crate::service::metrics::DeliveryMetrics>, // 投递指标 / Delivery metrics
+    pub maintenance: Arc<crate::service::maintenance::MaintenanceMode>, // 维护模式 / Maintenance mode
     pub node_id: String,                                  // 当前节点ID / Current node ID
     pub directory: Arc<cluster::directory::Directory>,    // 目录服务 / Directory service
     pub broker: cluster::broker::ShardBroker,             // 分片代理 / Shard broker
+    pub internal_rpc: Arc<cluster::rpc::InternalRpcClient>, // 节点间转发 / Node-to-node forwarding
     // storage 字段已移除，使用 plugin_connection_pool.storage_* 方法 / storage field removed, use plugin_connection_pool.storage_* methods
     pub raft: Arc<cluster::raft::RaftCluster>, // Raft集群 / Raft cluster
     pub rooms: Arc<DashMap<String, DashSet<String>>>, // 房间到UID集合 / Room -> UIDs
@@ -45,6 +143,8 @@ pub struct VConnectIMServer {
     pub quic_stream_recv: Arc<std::sync::atomic::AtomicUsize>, // QUIC stream接收计数 / QUIC stream recv count
     pub quic_dgram_recv: Arc<std::sync::atomic::AtomicUsize>, // QUIC datagram接收计数 / QUIC dgram recv count
     pub blocked_uids: Arc<dashmap::DashSet<String>>,          // 封禁UID集合 / Blocked UIDs
+    pub block_store: Arc<crate::service::blocklist::BlockStore>, // 封禁持久化 / Persisted blocks
+    pub room_meta: Arc<crate::service::room_meta::RoomMetaStore>, // 房间元数据与位置索引 / Room metadata and location index
     pub uid_rate_limits: Arc<dashmap::DashMap<String, (usize, usize, i64)>>, // UID限流 (limit, count, window_start_ms)
 }
 
@@ -56,7 +156,7 @@ impl VConnectIMServer {
             directory.clone(),
             "node-local".to_string(),
         ));
-        let plugin_registry = Arc::new(PluginRegistry::new());
+        let plugin_registry = Arc::new(PluginRegistry::from_config());
         Self {
             connections: Arc::new(DashMap::new()),
             // webhook_config: None,  // 已移除 / Removed
@@ -66,9 +166,42 @@ impl VConnectIMServer {
             plugin_connection_pool: None,
             plugin_config: Arc::new(RwLock::new(Value::Null)),
             acked_ids: Arc::new(DashMap::new()),
+            ack_windows: Arc::new(crate::service::ack::AckWindows::new(
+                crate::service::ack::AckPolicy::from_config(),
+            )),
+            device_states: Arc::new(Default::default()),
+            resume_tokens: Arc::new(Default::default()),
+            room_guard: Arc::new(Default::default()),
+            tenants: Arc::new(crate::service::tenant::Tenants::from_config()),
+            webhooks: Arc::new(Default::default()),
+            event_bus: Arc::new(crate::service::event_bus::EventBus::from_config()),
+            scheduler: Arc::new(Default::default()),
+            pin_policy: Arc::new(crate::service::pins::PinPolicy::from_config()),
+            retention: Arc::new(crate::service::retention::RetentionPolicy::from_config()),
+            fanout: Arc::new(crate::service::fanout::FanOutJobs::new(
+                crate::service::fanout::FanOutPolicy::from_config(),
+            )),
+            group_dedup: Arc::new(crate::service::group_dedup::GroupDedup::new(
+                crate::service::group_dedup::GroupDedupPolicy::from_config(),
+            )),
+            required_plugins: Arc::new(crate::service::health::required_plugins_from_config()),
+            admission: Arc::new(crate::service::admission::AdmissionPolicy::from_config()),
+            auth_guard: Arc::new(crate::service::auth_guard::AuthGuard::new(
+                crate::service::auth_guard::AuthGuardPolicy::from_config(),
+            )),
+            reaper: Arc::new(crate::service::reaper::ReaperPolicy::from_config()),
+            offline_queue: Arc::new(crate::service::offline_queue::OfflineQueue::new(
+                crate::service::offline_queue::OfflineQuota::from_config(),
+                None,
+            )),
+            persistence: Arc::new(crate::service::persistence::PersistencePolicies::from_config()),
+            id_gen: Arc::new(crate::service::id_gen::IdGenerator::for_node("node-local")),
+            metrics: Arc::new(Default::default()),
+            maintenance: Arc::new(crate::service::maintenance::MaintenanceMode::from_config()),
             node_id: "node-local".to_string(),
             directory,
             broker: cluster::broker::ShardBroker::new(),
+            internal_rpc: Arc::new(Default::default()),
             raft,
             rooms: Arc::new(DashMap::new()),
             uid_clients: Arc::new(DashMap::new()),
@@ -79,6 +212,8 @@ impl VConnectIMServer {
             quic_stream_recv: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             quic_dgram_recv: Arc::new(std::sync::atomic::AtomicUsize::new(0)),
             blocked_uids: Arc::new(dashmap::DashSet::new()),
+            block_store: Arc::new(Default::default()),
+            room_meta: Arc::new(Default::default()),
             uid_rate_limits: Arc::new(dashmap::DashMap::new()),
         }
     }
@@ -95,9 +230,133 @@ impl VConnectIMServer {
         self
     }
 
+    /// 配置房间操作限制 / Configure room operation limits
+    pub fn with_room_limits(mut self, limits: crate::service::room_guard::RoomLimits) -> Self {
+        self.room_guard = Arc::new(crate::service::room_guard::RoomGuard::new(limits));
+        self
+    }
+
+    /// 配置多租户限额 / Configure tenant-scoped limits
+    pub fn with_tenants(mut self, tenants: crate::service::tenant::Tenants) -> Self {
+        self.tenants = Arc::new(tenants);
+        self
+    }
+
+    /// 替换定时消息存储 / Replace the scheduled message store
+    pub fn with_scheduler(
+        mut self,
+        scheduler: Arc<crate::service::scheduler::MessageScheduler>,
+    ) -> Self {
+        self.scheduler = scheduler;
+        self
+    }
+
+    /// 替换封禁持久化存储 / Replace the persisted block store
+    pub fn with_block_store(mut self, store: Arc<crate::service::blocklist::BlockStore>) -> Self {
+        self.block_store = store;
+        self
+    }
+
+    /// 替换房间元数据存储 / Replace the room metadata store
+    pub fn with_room_meta_store(
+        mut self,
+        store: Arc<crate::service::room_meta::RoomMetaStore>,
+    ) -> Self {
+        self.room_meta = store;
+        self
+    }
+
+    /// 替换置顶策略 / Replace the pin policy
+    pub fn with_pin_policy(mut self, policy: crate::service::pins::PinPolicy) -> Self {
+        self.pin_policy = Arc::new(policy);
+        self
+    }
+
+    /// 替换确认时限 / Replace the ack deadline
+    pub fn with_ack_policy(mut self, policy: crate::service::ack::AckPolicy) -> Self {
+        self.ack_windows = Arc::new(crate::service::ack::AckWindows::new(policy));
+        self
+    }
+
+    /// 替换消息保留策略 / Replace the message retention policy
+    pub fn with_retention_policy(
+        mut self,
+        policy: crate::service::retention::RetentionPolicy,
+    ) -> Self {
+        self.retention = Arc::new(policy);
+        self
+    }
+
+    /// 替换群消息扇出策略 / Replace the group fan-out policy
+    pub fn with_fanout_policy(mut self, policy: crate::service::fanout::FanOutPolicy) -> Self {
+        self.fanout = Arc::new(crate::service::fanout::FanOutJobs::new(policy));
+        self
+    }
+
+    /// 设置群消息去重窗口 / Set the group message dedup window
+    pub fn with_group_dedup_policy(
+        mut self,
+        policy: crate::service::group_dedup::GroupDedupPolicy,
+    ) -> Self {
+        self.group_dedup = Arc::new(crate::service::group_dedup::GroupDedup::new(policy));
+        self
+    }
+
+    /// 设置就绪前必须连接的插件 / Set the plugins that must be connected for readiness
+    pub fn with_required_plugins(mut self, names: Vec<String>) -> Self {
+        self.required_plugins = Arc::new(names);
+        self
+    }
+
+    /// 替换连接准入策略 / Replace the connection admission policy
+    pub fn with_admission_policy(
+        mut self,
+        policy: crate::service::admission::AdmissionPolicy,
+    ) -> Self {
+        self.admission = Arc::new(policy);
+        self
+    }
+
+    /// 替换认证尝试限制策略 / Replace the auth attempt policy
+    pub fn with_auth_guard_policy(
+        mut self,
+        policy: crate::service::auth_guard::AuthGuardPolicy,
+    ) -> Self {
+        self.auth_guard = Arc::new(crate::service::auth_guard::AuthGuard::new(policy));
+        self
+    }
+
+    /// 替换连接回收策略 / Replace the connection reaper policy
+    pub fn with_reaper_policy(mut self, policy: crate::service::reaper::ReaperPolicy) -> Self {
+        self.reaper = Arc::new(policy);
+        self
+    }
+
+    /// 配置离线配额并替换离线存储 / Configure the offline quota and replace the offline store
+    pub fn with_offline_store(
+        mut self,
+        quota: crate::service::offline_queue::OfflineQuota,
+        store: Arc<dyn crate::service::offline_queue::OfflineStore>,
+    ) -> Self {
+        self.offline_queue = Arc::new(crate::service::offline_queue::OfflineQueue::new(
+            quota,
+            Some(store),
+        ));
+        self
+    }
+
+    /// 移除连接并清空其元数据 / Remove a connection and clear its metadata
+    pub fn remove_connection(&self, client_id: &str) -> Option<Connection> {
+        let (_, connection) = self.connections.remove(client_id)?;
+        connection.metadata.clear();
+        Some(connection)
+    }
+
     /// 注册通用插件 / Register generic plugin
     pub fn with_plugin(self, plugin: Arc<dyn Plugin>) -> Self {
-        self.plugin_registry.register(plugin);
+        if let Err(e) = self.plugin_registry.register(plugin) {
+            tracing::error!("❌ 插件注册失败 / Plugin registration failed: {}", e);
+        }
         self
     }
 
@@ -107,6 +366,7 @@ impl VConnectIMServer {
         node_id: String,
         directory: Arc<cluster::directory::Directory>,
     ) -> Self {
+        self.id_gen = Arc::new(crate::service::id_gen::IdGenerator::for_node(&node_id));
         self.node_id = node_id.clone();
         self.directory = directory.clone();
         self.directory.register_node(cluster::router::NodeInfo {
@@ -152,9 +412,31 @@ impl Clone for VConnectIMServer {
             plugin_connection_pool: self.plugin_connection_pool.clone(),
             plugin_config: self.plugin_config.clone(),
             acked_ids: self.acked_ids.clone(),
+            ack_windows: self.ack_windows.clone(),
+            device_states: self.device_states.clone(),
+            resume_tokens: self.resume_tokens.clone(),
+            room_guard: self.room_guard.clone(),
+            tenants: self.tenants.clone(),
+            webhooks: self.webhooks.clone(),
+            event_bus: self.event_bus.clone(),
+            scheduler: self.scheduler.clone(),
+            pin_policy: self.pin_policy.clone(),
+            retention: self.retention.clone(),
+            fanout: self.fanout.clone(),
+            group_dedup: self.group_dedup.clone(),
+            required_plugins: self.required_plugins.clone(),
+            admission: self.admission.clone(),
+            auth_guard: self.auth_guard.clone(),
+            reaper: self.reaper.clone(),
+            offline_queue: self.offline_queue.clone(),
+            persistence: self.persistence.clone(),
+            id_gen: self.id_gen.clone(),
+            metrics: self.metrics.clone(),
+            maintenance: self.maintenance.clone(),
             node_id: self.node_id.clone(),
             directory: self.directory.clone(),
             broker: cluster::broker::ShardBroker::new(),
+            internal_rpc: self.internal_rpc.clone(),
             raft: self.raft.clone(),
             rooms: self.rooms.clone(),
             uid_clients: self.uid_clients.clone(),
@@ -165,6 +447,8 @@ impl Clone for VConnectIMServer {
             quic_stream_recv: self.quic_stream_recv.clone(),
             quic_dgram_recv: self.quic_dgram_recv.clone(),
             blocked_uids: self.blocked_uids.clone(),
+            block_store: self.block_store.clone(),
+            room_meta: self.room_meta.clone(),
             uid_rate_limits: self.uid_rate_limits.clone(),
         }
     }
@@ -178,4 +462,26 @@ impl VConnectIMServer {
     pub fn get_plugin_config(&self) -> Value {
         self.plugin_config.read().clone()
     }
+
+    /// 启动插件：保存配置快照，以各自的子树调用进程内插件的 `on_startup`，再推送给已连接的子进程插件
+    /// Start plugins: store the config snapshot, call in-process `on_startup` with each plugin's
+    /// subtree, then push the config to connected subprocess plugins
+    pub async fn start_plugins(&self, config: Value) -> anyhow::Result<()> {
+        self.set_plugin_config(config.clone());
+        if let Some(pool) = &self.plugin_connection_pool {
+            pool.push_config_update(&config);
+        }
+        self.plugin_registry.emit_startup(self, &config).await
+    }
+
+    /// 更新插件配置：保存快照，推送给已连接的子进程插件，并通知进程内插件
+    /// Update plugin config: store the snapshot, push it to connected subprocess plugins
+    /// and notify in-process plugins
+    pub async fn update_plugin_config(&self, value: Value) -> anyhow::Result<()> {
+        self.set_plugin_config(value.clone());
+        if let Some(pool) = &self.plugin_connection_pool {
+            pool.push_config_update(&value);
+        }
+        self.plugin_registry.emit_config_update(&value).await
+    }
 }
@@ -0,0 +1,339 @@
+//! 客户端投递确认 / Client delivery acks
+//!
+//! `ack` 可携带单个 `message_id`，也可用 `message_ids` 数组批量确认（两者可同时出现），
+//! 每帧最多 `MAX_ACK_BATCH` 个。整批一次处理：更新 `acked_ids`、记录各设备的投递状态，并只调用
+//! 一次存储插件的 `storage.offline.ack`，删除确认到达前已写入离线队列的副本，避免重连时重复补发。
+//! 携带 `room_id` 时同时记为群成员确认。按序号累计确认（“确认到 seq N”）依赖会话序号，
+//! 目前消息只有 ID 没有序号，待序号引入后再支持。
+//! An `ack` carries a single `message_id`, a `message_ids` array for a batch, or both, with at
+//! most `MAX_ACK_BATCH` ids per frame. The batch is handled in one pass: `acked_ids` is updated,
+//! each device's delivery state is recorded, and the storage plugin's `storage.offline.ack` is
+//! called once to drop copies queued offline before the ack arrived, so they are not replayed on
+//! reconnect. With `room_id` the ids also count as group member acks. A cumulative "ack up to
+//! seq N" form needs conversation sequence numbers; messages only have ids today, so it waits
+//! until sequence numbers exist.
+//!
+//! 在线投递的 `message` / `private_message` 携带 `ack_deadline_ms`（`delivery.deadline_ms`），
+//! 发送方的 `message_sent` 与认证成功的 `auth_response.capabilities` 也带有该值。服务端在时限加
+//! `delivery.ack_grace_ms` 宽限后仍未收到确认时写入离线队列并关闭该消息的确认窗口：之后的确认被
+//! 忽略，不会撤销离线写入，重连时照常补发（客户端按 `message_id` 去重）；补发后窗口重新打开。
+//! Messages delivered online as `message` / `private_message` carry `ack_deadline_ms`
+//! (`delivery.deadline_ms`), as do the sender's `message_sent` and the `capabilities` of a
+//! successful `auth_response`. Without an ack by the deadline plus the `delivery.ack_grace_ms`
+//! grace, the server queues the message offline and closes its ack window: later acks are
+//! ignored rather than cancelling the offline write, so it is replayed on reconnect as usual
+//! (clients dedupe by `message_id`); the replay reopens the window.
+
+use crate::server::VConnectIMServer;
+use crate::service::device_sync::DeliveryState;
+use dashmap::DashMap;
+use serde_json::{json, Value};
+use std::collections::HashSet;
+
+/// 每个 `ack` 帧最多处理的消息ID数，超出部分忽略 / Max ids handled per `ack` frame; the rest are ignored
+pub const MAX_ACK_BATCH: usize = 1000;
+
+/// 默认确认时限 / Default ack deadline
+pub const DEFAULT_ACK_DEADLINE_MS: u64 = 500;
+
+/// 默认宽限，覆盖确认在网络上的往返 / Default grace, covering the ack's trip over the network
+pub const DEFAULT_ACK_GRACE_MS: u64 = 200;
+
+/// 已关闭窗口的保留时长，超过后不再记得 / How long a closed window is remembered
+const CLOSED_WINDOW_TTL_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// 确认时限 / Ack deadline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AckPolicy {
+    /// 告知客户端的时限 / Deadline announced to clients
+    pub deadline_ms: u64,
+    /// 时限之后仍接受确认的宽限 / Grace after the deadline during which acks still count
+    pub grace_ms: u64,
+}
+
+impl Default for AckPolicy {
+    fn default() -> Self {
+        Self {
+            deadline_ms: DEFAULT_ACK_DEADLINE_MS,
+            grace_ms: DEFAULT_ACK_GRACE_MS,
+        }
+    }
+}
+
+impl AckPolicy {
+    /// 读取 `delivery.deadline_ms` 与 `delivery.ack_grace_ms` / Read `delivery.deadline_ms` and `delivery.ack_grace_ms`
+    pub fn from_config() -> Self {
+        let defaults = Self::default();
+        match v::get_global_config_manager() {
+            Ok(cm) => Self {
+                deadline_ms: cm.get_or("delivery.deadline_ms", defaults.deadline_ms),
+                grace_ms: cm.get_or("delivery.ack_grace_ms", defaults.grace_ms),
+            },
+            Err(_) => defaults,
+        }
+    }
+
+    /// 认证成功时告知客户端的能力 / Capabilities announced to clients on successful auth
+    pub fn capabilities(&self) -> Value {
+        json!({"ack_deadline_ms": self.deadline_ms, "ack_grace_ms": self.grace_ms})
+    }
+}
+
+/// 确认时限与已关闭的确认窗口（uid → 消息ID → 关闭时间）
+/// Ack deadline and closed ack windows (uid → message id → closed at)
+#[derive(Debug, Default)]
+pub struct AckWindows {
+    pub policy: AckPolicy,
+    closed: DashMap<String, DashMap<String, i64>>,
+}
+
+impl AckWindows {
+    pub fn new(policy: AckPolicy) -> Self {
+        Self {
+            policy,
+            closed: DashMap::new(),
+        }
+    }
+
+    /// 关闭消息的确认窗口，顺带清理该 uid 过期的记录
+    /// Close a message's ack window, pruning the uid's stale entries on the way
+    pub fn close(&self, uid: &str, message_id: &str, now_ms: i64) {
+        let closed = self.closed.entry(uid.to_string()).or_default();
+        closed.retain(|_, at| now_ms - *at < CLOSED_WINDOW_TTL_MS);
+        closed.insert(message_id.to_string(), now_ms);
+    }
+
+    /// 消息的确认窗口是否已关闭 / Whether a message's ack window is closed
+    pub fn is_closed(&self, uid: &str, message_id: &str) -> bool {
+        self.closed
+            .get(uid)
+            .is_some_and(|closed| closed.contains_key(message_id))
+    }
+
+    /// 重新打开确认窗口（离线补发后）/ Reopen ack windows (after an offline replay)
+    pub fn reopen(&self, uid: &str, message_ids: &[String]) {
+        if let Some(closed) = self.closed.get(uid) {
+            for id in message_ids {
+                closed.remove(id);
+            }
+        }
+        self.closed.remove_if(uid, |_, closed| closed.is_empty());
+    }
+}
+
+/// 读取 `ack` 中的 `message_id` 与 `message_ids`，去重并保持顺序
+/// Read `message_id` and `message_ids` from an `ack`, deduplicated in order
+pub fn ack_message_ids(data: &Value) -> Vec<String> {
+    let single = data.get("message_id").and_then(Value::as_str);
+    let batch = data
+        .get("message_ids")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str);
+    let mut seen = HashSet::new();
+    single
+        .into_iter()
+        .chain(batch)
+        .filter(|id| !id.is_empty() && seen.insert(*id))
+        .take(MAX_ACK_BATCH)
+        .map(str::to_string)
+        .collect()
+}
+
+impl VConnectIMServer {
+    /// 处理 `ack`，返回确认的消息数；未认证的客户端不处理
+    /// Handle an `ack`; returns how many messages were acknowledged. Unauthenticated clients are ignored
+    pub async fn handle_ack(&self, client_id: &str, data: &Value) -> usize {
+        let Some(uid) = self.connections.get(client_id).and_then(|c| c.uid.clone()) else {
+            return 0;
+        };
+        // 窗口已关闭的确认不撤销离线写入 / Acks for closed windows do not undo the offline write
+        let (late, ids): (Vec<String>, Vec<String>) = ack_message_ids(data)
+            .into_iter()
+            .partition(|id| self.ack_windows.is_closed(&uid, id));
+        if !late.is_empty() {
+            tracing::debug!(
+                "⏱️  忽略 {} 的 {} 个超时确认 / Ignored {} late acks from {}",
+                uid,
+                late.len(),
+                late.len(),
+                uid
+            );
+        }
+        if ids.is_empty() {
+            return 0;
+        }
+        {
+            let set = self.acked_ids.entry(uid.clone()).or_default();
+            for id in &ids {
+                set.insert(id.clone());
+            }
+        }
+
+        let pool = self.plugin_connection_pool.as_ref();
+        let in_room = data.get("room_id").and_then(Value::as_str).is_some();
+        let timestamp = chrono::Utc::now().timestamp_millis();
+        for id in &ids {
+            // 携带 room_id 时记录群成员确认 / Record member acks when room_id is present
+            if in_room && self.record_group_ack(id, &uid, timestamp).await {
+                if let Some(pool) = pool {
+                    let _ = pool.storage_record_read(&uid, id, timestamp).await;
+                }
+            }
+            self.record_device_state(client_id, id, DeliveryState::Delivered)
+                .await;
+        }
+        if let Some(pool) = pool {
+            if let Err(e) = pool.storage_ack_offline(&uid, &ids).await {
+                tracing::warn!("⚠️  离线确认失败 / Offline ack for {} failed: {}", uid, e);
+            }
+        }
+        tracing::debug!("✅ Ack received from {} for {} messages", uid, ids.len());
+        ids.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::runtime::{PluginConnectionPool, PluginRuntimeManager};
+    use crate::storage::builtin::BuiltinStorage;
+    use crate::storage::OfflineRecord;
+    use crate::testkit::{im, recv_typed, TestServer};
+    use crate::ImMessage;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_ack_ids_are_merged_deduplicated_and_capped() {
+        let data = json!({"message_id": "a", "message_ids": ["b", "a", "", 7, "c"]});
+        assert_eq!(ack_message_ids(&data), ["a", "b", "c"]);
+        assert!(ack_message_ids(&json!({})).is_empty());
+
+        let many: Vec<String> = (0..MAX_ACK_BATCH + 5).map(|i| i.to_string()).collect();
+        let ids = ack_message_ids(&json!({ "message_ids": many }));
+        assert_eq!(ids.len(), MAX_ACK_BATCH);
+    }
+
+    #[tokio::test]
+    async fn test_batched_ack_of_fifty_ids() {
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = Arc::new(PluginConnectionPool::new(manager));
+        let storage = Arc::new(BuiltinStorage::open_temporary().unwrap());
+        pool.enable_builtin_storage(storage.clone());
+        let ts = TestServer::build(|server| server.with_plugin_connection_pool(pool.clone()));
+        let (bob, _bob_rx) = ts.add_client("bob");
+
+        // 确认到达前已写入离线队列的副本 / Copies queued offline before the ack arrived
+        let ids: Vec<String> = (0..50).map(|i| format!("m{:02}", i)).collect();
+        for (i, id) in ids.iter().enumerate() {
+            storage
+                .store_offline(&OfflineRecord {
+                    message_id: id.clone(),
+                    from_uid: Some("alice".into()),
+                    to_uid: "bob".into(),
+                    room_id: None,
+                    content: json!({}),
+                    timestamp: i as i64,
+                    msg_type: "message".into(),
+                    priority: Default::default(),
+                })
+                .unwrap();
+        }
+        storage
+            .store_offline(&OfflineRecord {
+                message_id: "unacked".into(),
+                from_uid: None,
+                to_uid: "bob".into(),
+                room_id: None,
+                content: json!({}),
+                timestamp: 99,
+                msg_type: "message".into(),
+                priority: Default::default(),
+            })
+            .unwrap();
+
+        ts.send(&bob, im("ack", json!({ "message_ids": ids }), None))
+            .await
+            .unwrap();
+        let acked = ts.server.acked_ids.get("bob").unwrap();
+        assert_eq!(acked.len(), 50);
+        assert!(ids.iter().all(|id| acked.contains(id)));
+        drop(acked);
+        assert_eq!(storage.offline_count("bob").unwrap(), 1);
+
+        // 单个 ID 的确认照常工作 / Single-id acks keep working
+        ts.send(&bob, im("ack", json!({"message_id": "unacked"}), None))
+            .await
+            .unwrap();
+        assert!(ts.server.acked_ids.get("bob").unwrap().contains("unacked"));
+        assert_eq!(storage.offline_count("bob").unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_deadline_is_echoed_and_late_acks_are_ignored() {
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = Arc::new(PluginConnectionPool::new(manager));
+        let storage = Arc::new(BuiltinStorage::open_temporary().unwrap());
+        pool.enable_builtin_storage(storage.clone());
+        let policy = AckPolicy {
+            deadline_ms: 30,
+            grace_ms: 20,
+        };
+        let ts = TestServer::build(|server| {
+            server
+                .with_plugin_connection_pool(pool.clone())
+                .with_ack_policy(policy)
+        });
+        let (alice, mut alice_rx) = ts.add_client("alice");
+        let (bob, mut bob_rx) = ts.add_client("bob");
+
+        // 认证时告知时限 / The deadline is announced at auth
+        ts.send(
+            &alice,
+            im("auth", json!({"uid": "alice", "token": "t"}), None),
+        )
+        .await
+        .unwrap();
+        let auth: ImMessage = recv_typed(&mut alice_rx).await;
+        assert_eq!(auth.data["capabilities"], policy.capabilities());
+        assert_eq!(auth.data["capabilities"]["ack_deadline_ms"], 30);
+
+        let send = |text: &str| im("private_message", json!({ "text": text }), Some("bob"));
+        let mut delivered = Vec::new();
+        for text in ["late", "on-time"] {
+            ts.send(&alice, send(text)).await.unwrap();
+            let msg: ImMessage = recv_typed(&mut bob_rx).await;
+            assert_eq!(msg.data["ack_deadline_ms"], 30);
+            let sent = loop {
+                let msg: ImMessage = recv_typed(&mut alice_rx).await;
+                if msg.msg_type == "message_sent" {
+                    break msg;
+                }
+            };
+            assert_eq!(sent.data["ack_deadline_ms"], 30);
+            delivered.push(msg.data["message_id"].as_str().unwrap().to_string());
+        }
+        ts.send(&bob, im("ack", json!({"message_id": delivered[1]}), None))
+            .await
+            .unwrap();
+
+        // 时限加宽限后只有未确认的消息进入离线队列 / After deadline plus grace only the unacked one is queued
+        tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+        assert_eq!(storage.offline_count("bob").unwrap(), 1);
+        assert!(ts.server.ack_windows.is_closed("bob", &delivered[0]));
+
+        // 迟到的确认被忽略，不撤销离线写入 / The late ack is ignored and does not undo the offline write
+        ts.send(&bob, im("ack", json!({"message_id": delivered[0]}), None))
+            .await
+            .unwrap();
+        assert!(!ts
+            .server
+            .acked_ids
+            .get("bob")
+            .unwrap()
+            .contains(&delivered[0]));
+        assert_eq!(storage.offline_count("bob").unwrap(), 1);
+    }
+}
@@ -0,0 +1,309 @@
+//! 连接准入 / Connection admission
+//!
+//! 每个 WebSocket 连接在进入消息循环前经过 [`VConnectIMServer::admit_connection`]：依次检查
+//! `server.ip_denylist`、`server.ip_allowlist`（非空时只放行列表内的地址）、`server.max_connections`
+//! （0 为不限），再调用插件的 `on_connect` 钩子。被拒绝的连接仍完成握手，随后收到带 4xxx 关闭码的
+//! 关闭帧（见 [`RejectReason::close_code`]），不进入连接表、不触发上线事件。名单项为 IP 或 CIDR。
+//! 容量按准入时的连接数判断，同时握手的连接可能使总数略超上限。
+//! Every WebSocket connection passes [`VConnectIMServer::admit_connection`] before its message
+//! loop starts: `server.ip_denylist`, then `server.ip_allowlist` (when non-empty only listed
+//! addresses pass), then `server.max_connections` (0 for no cap), then the plugins' `on_connect`
+//! hook. A rejected connection still completes the handshake and then gets a close frame with a
+//! 4xxx close code (see [`RejectReason::close_code`]); it never enters the connection table or
+//! fires online events. List entries are IPs or CIDRs. Capacity is checked against the
+//! connection count at admission, so connections handshaking at the same time may overshoot the
+//! cap slightly.
+
+use crate::server::VConnectIMServer;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+
+/// 拒绝原因 / Why a connection was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    /// 地址在拒绝名单中 / Address is on the denylist
+    IpDenied,
+    /// 配置了允许名单且地址不在其中 / An allowlist is configured and the address is not on it
+    IpNotAllowed,
+    /// 已达到 `server.max_connections` / `server.max_connections` reached
+    OverCapacity,
+    /// 连接过于频繁（由插件判定）/ Connecting too often (decided by plugins)
+    #[allow(dead_code)]
+    RateLimited,
+    /// 认证失败次数过多，暂时封禁 / Temporarily banned after too many failed auth attempts
+    AuthBanned,
+}
+
+impl RejectReason {
+    /// 关闭帧使用的关闭码 / Close code used in the close frame
+    pub fn close_code(self) -> u16 {
+        match self {
+            RejectReason::IpDenied | RejectReason::IpNotAllowed => 4003,
+            RejectReason::OverCapacity => 4013,
+            RejectReason::RateLimited => 4029,
+            RejectReason::AuthBanned => 4023,
+        }
+    }
+}
+
+/// 拒绝连接的决定 / Decision to reject a connection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectRejection {
+    pub reason: RejectReason,
+    /// 关闭帧中的原因文本 / Reason text in the close frame
+    pub message: String,
+}
+
+impl ConnectRejection {
+    pub fn new(reason: RejectReason, message: impl Into<String>) -> Self {
+        Self {
+            reason,
+            message: message.into(),
+        }
+    }
+}
+
+/// IP 或 CIDR 名单项 / IP or CIDR list entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IpRule {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl FromStr for IpRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.trim().split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s.trim(), None),
+        };
+        let network: IpAddr = addr.parse().map_err(|_| format!("invalid address {}", s))?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(p) => p
+                .parse::<u8>()
+                .ok()
+                .filter(|p| *p <= max)
+                .ok_or_else(|| format!("invalid prefix in {}", s))?,
+            None => max,
+        };
+        Ok(Self { network, prefix })
+    }
+}
+
+impl IpRule {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // IPv4 映射的 IPv6 地址按 IPv4 比较 / IPv4-mapped IPv6 addresses compare as IPv4
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+            v4 => v4,
+        };
+        match (self.network, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// 准入策略 / Admission policy
+#[derive(Debug, Clone, Default)]
+pub struct AdmissionPolicy {
+    /// 全局连接上限，0 为不限 / Global connection cap, 0 for none
+    pub max_connections: usize,
+    /// 非空时只放行这些地址 / When non-empty only these addresses pass
+    pub allow: Vec<IpRule>,
+    pub deny: Vec<IpRule>,
+}
+
+impl AdmissionPolicy {
+    /// 读取 `server.max_connections`、`server.ip_allowlist` 与 `server.ip_denylist`，无效项告警后忽略
+    /// Read `server.max_connections`, `server.ip_allowlist` and `server.ip_denylist`; invalid entries are warned about and skipped
+    pub fn from_config() -> Self {
+        let Ok(cm) = v::get_global_config_manager() else {
+            return Self::default();
+        };
+        let rules = |key: &str| -> Vec<IpRule> {
+            cm.get_or(key, Vec::<String>::new())
+                .iter()
+                .filter_map(|entry| match entry.parse() {
+                    Ok(rule) => Some(rule),
+                    Err(e) => {
+                        tracing::warn!(
+                            "⚠️  忽略 {} 中的无效项 / Ignoring invalid {} entry: {}",
+                            key,
+                            key,
+                            e
+                        );
+                        None
+                    }
+                })
+                .collect()
+        };
+        Self {
+            max_connections: cm.get_or("server.max_connections", 0usize),
+            allow: rules("server.ip_allowlist"),
+            deny: rules("server.ip_denylist"),
+        }
+    }
+
+    /// 按名单与容量判定，`current` 为当前连接数 / Decide by lists and capacity; `current` is the connection count
+    pub fn check(&self, ip: IpAddr, current: usize) -> Option<ConnectRejection> {
+        if self.deny.iter().any(|rule| rule.contains(ip)) {
+            return Some(ConnectRejection::new(
+                RejectReason::IpDenied,
+                "address denied",
+            ));
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|rule| rule.contains(ip)) {
+            return Some(ConnectRejection::new(
+                RejectReason::IpNotAllowed,
+                "address not allowed",
+            ));
+        }
+        if self.max_connections > 0 && current >= self.max_connections {
+            return Some(ConnectRejection::new(
+                RejectReason::OverCapacity,
+                "server at capacity",
+            ));
+        }
+        None
+    }
+}
+
+impl VConnectIMServer {
+    /// 连接准入判定，`None` 为放行；插件钩子出错时放行
+    /// Admission decision, `None` to accept; a failing plugin hook accepts
+    pub async fn admit_connection(&self, peer: SocketAddr) -> Option<ConnectRejection> {
+        if let Some(rejection) = self.admission.check(peer.ip(), self.connections.len()) {
+            return Some(rejection);
+        }
+        if let Some(left) = self.auth_guard.banned_for(peer.ip()) {
+            return Some(ConnectRejection::new(
+                RejectReason::AuthBanned,
+                format!("auth banned for {}s", left.as_secs().max(1)),
+            ));
+        }
+        match self.plugin_registry.emit_connect(peer).await {
+            Ok(rejection) => rejection,
+            Err(e) => {
+                tracing::warn!("on_connect hook failed for {}: {}", peer, e);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::TestServer;
+    use futures_util::StreamExt;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+    use tokio_tungstenite::tungstenite::Message;
+
+    /// 用 `server` 接受一个真实的 WebSocket 连接并返回客户端收到的第一帧
+    /// Accept one real WebSocket connection with `server` and return the first frame the client gets
+    async fn first_frame(server: &TestServer) -> Message {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let accepting = (*server.server).clone();
+        tokio::spawn(async move {
+            let (stream, peer) = listener.accept().await.unwrap();
+            let connections = accepting.connections.clone();
+            let _ = crate::ws::connection::handle_connection(stream, peer, connections, accepting)
+                .await;
+        });
+        let mut request = format!("ws://{}/", addr).into_client_request().unwrap();
+        request
+            .headers_mut()
+            .insert("Sec-WebSocket-Protocol", "vim.v1".parse().unwrap());
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (mut ws, _) = tokio_tungstenite::client_async(request, stream)
+            .await
+            .unwrap();
+        ws.next().await.unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_over_cap_connection_is_refused() {
+        let ts = TestServer::build(|server| {
+            server.with_admission_policy(AdmissionPolicy {
+                max_connections: 1,
+                ..Default::default()
+            })
+        });
+        let (_alice, _alice_rx) = ts.add_client("alice");
+
+        let Message::Close(Some(frame)) = first_frame(&ts).await else {
+            panic!("expected a close frame");
+        };
+        assert_eq!(u16::from(frame.code), 4013);
+        assert_eq!(frame.reason, "server at capacity");
+        assert_eq!(ts.server.connections.len(), 1);
+
+        // 低于上限时照常接受 / Accepted as usual below the cap
+        ts.server.connections.clear();
+        assert!(matches!(first_frame(&ts).await, Message::Text(_)));
+    }
+
+    struct DenyAll;
+
+    #[async_trait::async_trait]
+    impl crate::plugins::Plugin for DenyAll {
+        fn name(&self) -> &'static str {
+            "deny_all"
+        }
+
+        async fn on_connect(&self, _peer: SocketAddr) -> anyhow::Result<Option<ConnectRejection>> {
+            Ok(Some(ConnectRejection::new(
+                RejectReason::RateLimited,
+                "slow down",
+            )))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plugin_hook_can_reject() {
+        let ts = TestServer::new();
+        let peer: SocketAddr = "127.0.0.1:9".parse().unwrap();
+        assert_eq!(ts.server.admit_connection(peer).await, None);
+        ts.server
+            .plugin_registry
+            .register(std::sync::Arc::new(DenyAll))
+            .unwrap();
+        let rejection = ts.server.admit_connection(peer).await.unwrap();
+        assert_eq!(rejection.reason.close_code(), 4029);
+    }
+
+    #[test]
+    fn test_ip_lists() {
+        let rule: IpRule = "10.0.0.0/8".parse().unwrap();
+        assert!(rule.contains("10.1.2.3".parse().unwrap()));
+        assert!(rule.contains("::ffff:10.9.9.9".parse().unwrap()));
+        assert!(!rule.contains("11.0.0.1".parse().unwrap()));
+        assert!("10.0.0.0/33".parse::<IpRule>().is_err());
+        assert!("::/0"
+            .parse::<IpRule>()
+            .unwrap()
+            .contains("2001:db8::1".parse().unwrap()));
+
+        let policy = AdmissionPolicy {
+            allow: vec!["10.0.0.0/8".parse().unwrap()],
+            deny: vec!["10.0.0.66".parse().unwrap()],
+            ..Default::default()
+        };
+        let reason = |ip: &str| policy.check(ip.parse().unwrap(), 0).map(|r| r.reason);
+        assert_eq!(reason("10.0.0.1"), None);
+        assert_eq!(reason("10.0.0.66"), Some(RejectReason::IpDenied));
+        assert_eq!(reason("192.168.1.1"), Some(RejectReason::IpNotAllowed));
+    }
+}
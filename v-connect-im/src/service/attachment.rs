@@ -0,0 +1,163 @@
+//! 消息附件校验 / Message attachment validation
+//!
+//! 消息 `data` 可携带 `attachment` 对象，字段见 `docs/attachments.md`。
+//! A message `data` may carry an `attachment` object; see `docs/attachments.md`.
+
+use crate::server::VConnectIMServer;
+use crate::storage::AttachmentMeta;
+use serde_json::Value;
+
+/// 默认附件大小上限（50 MiB）/ Default max attachment size (50 MiB)
+pub const DEFAULT_MAX_ATTACHMENT_BYTES: u64 = 50 * 1024 * 1024;
+/// 默认内联缩略图上限（16 KiB，解码后）/ Default max inline thumbnail size (16 KiB, decoded)
+pub const DEFAULT_MAX_THUMBNAIL_BYTES: usize = 16 * 1024;
+
+/// 附件限制 / Attachment limits
+#[derive(Debug, Clone, Copy)]
+pub struct AttachmentLimits {
+    pub max_attachment_bytes: u64,
+    pub max_thumbnail_bytes: usize,
+}
+
+impl Default for AttachmentLimits {
+    fn default() -> Self {
+        Self {
+            max_attachment_bytes: DEFAULT_MAX_ATTACHMENT_BYTES,
+            max_thumbnail_bytes: DEFAULT_MAX_THUMBNAIL_BYTES,
+        }
+    }
+}
+
+impl AttachmentLimits {
+    /// 读取 `message.max_attachment_bytes` / `message.max_thumbnail_bytes`
+    /// Read `message.max_attachment_bytes` / `message.max_thumbnail_bytes`
+    pub fn from_config() -> Self {
+        let defaults = Self::default();
+        match v::get_global_config_manager() {
+            Ok(cm) => Self {
+                max_attachment_bytes: cm
+                    .get_or("message.max_attachment_bytes", defaults.max_attachment_bytes),
+                max_thumbnail_bytes: cm
+                    .get_or("message.max_thumbnail_bytes", defaults.max_thumbnail_bytes),
+            },
+            Err(_) => defaults,
+        }
+    }
+}
+
+/// base64 解码后的字节数（不解码）/ Decoded byte length of base64 text (without decoding)
+fn base64_decoded_len(text: &str) -> Option<usize> {
+    let text = text.trim();
+    if text.len() % 4 != 0
+        || !text
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/' || b == b'=')
+    {
+        return None;
+    }
+    let padding = text.bytes().rev().take_while(|b| *b == b'=').count();
+    if padding > 2 {
+        return None;
+    }
+    Some(text.len() / 4 * 3 - padding)
+}
+
+/// 校验消息数据中的 `attachment`；不存在时返回 `Ok(None)`
+/// Validate the `attachment` in message data; returns `Ok(None)` when absent
+pub fn validate_attachment(
+    data: &Value,
+    limits: &AttachmentLimits,
+) -> Result<Option<AttachmentMeta>, String> {
+    let raw = match data.get("attachment") {
+        None | Some(Value::Null) => return Ok(None),
+        Some(v) => v,
+    };
+    let meta: AttachmentMeta = serde_json::from_value(raw.clone())
+        .map_err(|e| format!("invalid attachment: {}", e))?;
+
+    match reqwest::Url::parse(&meta.url) {
+        Ok(u) if u.scheme() == "http" || u.scheme() == "https" => {}
+        _ => return Err("attachment.url must be an http(s) URL".to_string()),
+    }
+    let mime_ok = meta
+        .mime
+        .split_once('/')
+        .map(|(t, s)| !t.is_empty() && !s.is_empty())
+        .unwrap_or(false);
+    if !mime_ok {
+        return Err("attachment.mime must look like type/subtype".to_string());
+    }
+    if meta.size == 0 || meta.size > limits.max_attachment_bytes {
+        return Err(format!(
+            "attachment.size must be between 1 and {} bytes",
+            limits.max_attachment_bytes
+        ));
+    }
+    if meta.sha256.len() != 64 || !meta.sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err("attachment.sha256 must be 64 hex characters".to_string());
+    }
+    if let Some(thumb) = meta.thumbnail_base64.as_deref() {
+        match base64_decoded_len(thumb) {
+            None => return Err("attachment.thumbnail_base64 is not valid base64".to_string()),
+            Some(n) if n > limits.max_thumbnail_bytes => {
+                return Err(format!(
+                    "attachment.thumbnail_base64 exceeds {} bytes",
+                    limits.max_thumbnail_bytes
+                ))
+            }
+            Some(_) => {}
+        }
+    }
+    Ok(Some(AttachmentMeta {
+        sha256: meta.sha256.to_ascii_lowercase(),
+        ..meta
+    }))
+}
+
+impl VConnectIMServer {
+    /// 按当前配置校验消息附件 / Validate a message attachment against current config
+    pub fn check_attachment(&self, data: &Value) -> Result<Option<AttachmentMeta>, String> {
+        validate_attachment(data, &AttachmentLimits::from_config())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn attachment(size: u64, thumb: Option<&str>) -> Value {
+        json!({"attachment": {
+            "url": "https://cdn.example.com/a.png",
+            "mime": "image/png",
+            "size": size,
+            "sha256": "AB".repeat(32),
+            "thumbnail_base64": thumb,
+        }})
+    }
+
+    #[test]
+    fn test_absent_and_valid() {
+        let limits = AttachmentLimits::default();
+        assert_eq!(validate_attachment(&json!({"text": "hi"}), &limits), Ok(None));
+        let meta = validate_attachment(&attachment(1024, Some("aGVsbG8=")), &limits)
+            .unwrap()
+            .unwrap();
+        assert_eq!(meta.sha256, "ab".repeat(32));
+        assert_eq!(base64_decoded_len("aGVsbG8="), Some(5));
+    }
+
+    #[test]
+    fn test_rejects_oversize_and_bad_fields() {
+        let limits = AttachmentLimits {
+            max_attachment_bytes: 100,
+            max_thumbnail_bytes: 4,
+        };
+        assert!(validate_attachment(&attachment(101, None), &limits).is_err());
+        assert!(validate_attachment(&attachment(10, Some("aGVsbG8=")), &limits).is_err());
+        assert!(validate_attachment(&attachment(10, Some("not base64!")), &limits).is_err());
+        let mut bad_url = attachment(10, None);
+        bad_url["attachment"]["url"] = json!("ftp://x/y");
+        assert!(validate_attachment(&bad_url, &limits).is_err());
+    }
+}
@@ -0,0 +1,362 @@
+//! 认证尝试限制 / Auth attempt guard
+//!
+//! `auth` 失败按客户端 IP 与所声明的 uid 分别计数。第 n 次失败后该键进入
+//! `auth.penalty_base_ms × 2^(n-1)` 的惩罚期（上限 `auth.penalty_max_ms`），期间的 `auth` 不再校验令牌，
+//! 直接以 `AUTH_THROTTLED` 失败并给出 `retry_after_ms`。`auth.failure_window_secs` 内失败达到
+//! `auth.ban_after_failures` 次（0 不封禁）时封禁 `auth.ban_secs` 秒：当前连接收到 `AUTH_BANNED` 后以关闭码
+//! 4023 断开，被封禁的 IP 重连时在准入阶段即以 4023 拒绝。认证成功清除该 uid 的计数，IP 的计数随窗口过期。
+//! 失败、限流与封禁次数见 `/v1/health/detailed` 的 `details.delivery.auth`。
+//! Failed `auth` attempts are counted per client IP and per claimed uid. After the n-th failure
+//! the key is locked for `auth.penalty_base_ms × 2^(n-1)` (capped at `auth.penalty_max_ms`);
+//! `auth` during that time fails with `AUTH_THROTTLED` and a `retry_after_ms` without validating
+//! the token. Reaching `auth.ban_after_failures` failures (0 never bans) within
+//! `auth.failure_window_secs` bans the key for `auth.ban_secs`: the current connection gets
+//! `AUTH_BANNED` and is closed with close code 4023, and a banned IP reconnecting is refused at
+//! admission with 4023. A successful auth clears the uid's count; the IP's count expires with the
+//! window. Failures, throttles and bans are reported under `details.delivery.auth` of
+//! `/v1/health/detailed`.
+
+use crate::domain::message::{ErrorCode, ImMessage};
+use crate::server::VConnectIMServer;
+use crate::service::admission::RejectReason;
+use dashmap::DashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+
+/// 计数的键超过该数量时清理过期项 / Expired entries are pruned once this many keys are tracked
+const PRUNE_AT: usize = 4096;
+
+/// 认证尝试限制策略（`[auth]`）/ Auth attempt policy (`[auth]`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuthGuardPolicy {
+    /// 首次失败后的惩罚期，每次失败翻倍 / Lock after the first failure, doubling per failure
+    pub penalty_base: Duration,
+    /// 惩罚期上限 / Longest lock
+    pub penalty_max: Duration,
+    /// 窗口内失败多少次后封禁，0 不封禁 / Failures within the window before a ban, 0 never bans
+    pub ban_after_failures: u32,
+    /// 封禁时长 / Ban duration
+    pub ban: Duration,
+    /// 失败计数窗口 / Failure counting window
+    pub failure_window: Duration,
+}
+
+impl Default for AuthGuardPolicy {
+    fn default() -> Self {
+        Self {
+            penalty_base: Duration::from_millis(500),
+            penalty_max: Duration::from_secs(30),
+            ban_after_failures: 10,
+            ban: Duration::from_secs(300),
+            failure_window: Duration::from_secs(600),
+        }
+    }
+}
+
+impl AuthGuardPolicy {
+    /// 读取 `auth.penalty_base_ms` 等 / Read `auth.penalty_base_ms` and friends
+    pub fn from_config() -> Self {
+        let defaults = Self::default();
+        let Ok(cm) = v::get_global_config_manager() else {
+            return defaults;
+        };
+        let millis = |key: &str, default: Duration| {
+            Duration::from_millis(cm.get_or(key, default.as_millis() as u64))
+        };
+        let secs =
+            |key: &str, default: Duration| Duration::from_secs(cm.get_or(key, default.as_secs()));
+        Self {
+            penalty_base: millis("auth.penalty_base_ms", defaults.penalty_base),
+            penalty_max: millis("auth.penalty_max_ms", defaults.penalty_max),
+            ban_after_failures: cm.get_or("auth.ban_after_failures", defaults.ban_after_failures),
+            ban: secs("auth.ban_secs", defaults.ban),
+            failure_window: secs("auth.failure_window_secs", defaults.failure_window),
+        }
+    }
+
+    /// 第 `failures` 次失败后的惩罚期 / Lock after the `failures`-th failure
+    fn penalty(&self, failures: u32) -> Duration {
+        let factor = 1u32
+            .checked_shl(failures.saturating_sub(1))
+            .unwrap_or(u32::MAX);
+        self.penalty_base
+            .saturating_mul(factor)
+            .min(self.penalty_max)
+    }
+}
+
+/// 拒绝 `auth` 的原因，附剩余时长 / Why an `auth` is refused, with the time remaining
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthRefusal {
+    /// 惩罚期内 / Within a penalty lock
+    Throttled(Duration),
+    /// 已封禁 / Banned
+    Banned(Duration),
+}
+
+impl AuthRefusal {
+    /// 返回给客户端的错误码 / Error code returned to the client
+    pub fn code(self) -> ErrorCode {
+        match self {
+            Self::Throttled(_) => ErrorCode::AuthThrottled,
+            Self::Banned(_) => ErrorCode::AuthBanned,
+        }
+    }
+
+    /// 剩余时长 / Time remaining
+    pub fn retry_after(self) -> Duration {
+        match self {
+            Self::Throttled(d) | Self::Banned(d) => d,
+        }
+    }
+}
+
+/// 单个键的失败记录 / Failure record of one key
+#[derive(Debug, Clone, Copy)]
+struct Attempts {
+    failures: u32,
+    window_start: Instant,
+    locked_until: Instant,
+    banned: bool,
+}
+
+/// 认证尝试守卫 / Auth attempt guard
+#[derive(Debug)]
+pub struct AuthGuard {
+    pub policy: AuthGuardPolicy,
+    attempts: DashMap<String, Attempts>, // "ip:<addr>" / "uid:<uid>" -> 失败记录 / failure record
+}
+
+impl AuthGuard {
+    pub fn new(policy: AuthGuardPolicy) -> Self {
+        Self {
+            policy,
+            attempts: DashMap::new(),
+        }
+    }
+
+    fn keys(ip: IpAddr, uid: Option<&str>) -> Vec<String> {
+        let mut keys = vec![format!("ip:{}", ip)];
+        keys.extend(uid.map(|uid| format!("uid:{}", uid)));
+        keys
+    }
+
+    /// 检查能否尝试认证，封禁优先于惩罚期 / Check whether auth may be attempted; bans take precedence over locks
+    pub fn check(&self, ip: IpAddr, uid: Option<&str>) -> Result<(), AuthRefusal> {
+        let now = Instant::now();
+        let mut refusal = None;
+        for key in Self::keys(ip, uid) {
+            let Some(a) = self.attempts.get(&key).map(|a| *a) else {
+                continue;
+            };
+            if a.locked_until <= now {
+                continue;
+            }
+            let remaining = a.locked_until - now;
+            if a.banned {
+                return Err(AuthRefusal::Banned(remaining));
+            }
+            refusal = Some(AuthRefusal::Throttled(remaining));
+        }
+        refusal.map_or(Ok(()), Err)
+    }
+
+    /// IP 的剩余封禁时长 / Remaining ban of an IP
+    pub fn banned_for(&self, ip: IpAddr) -> Option<Duration> {
+        let a = *self.attempts.get(&format!("ip:{}", ip))?;
+        let now = Instant::now();
+        (a.banned && a.locked_until > now).then(|| a.locked_until - now)
+    }
+
+    /// 记录一次失败；本次失败导致封禁时返回 `Banned`
+    /// Record a failure; returns `Banned` when this failure triggers a ban
+    pub fn record_failure(&self, ip: IpAddr, uid: Option<&str>) -> Option<AuthRefusal> {
+        let now = Instant::now();
+        if self.attempts.len() >= PRUNE_AT {
+            let window = self.policy.failure_window;
+            self.attempts
+                .retain(|_, a| a.locked_until > now || now.duration_since(a.window_start) < window);
+        }
+        let mut banned = None;
+        for key in Self::keys(ip, uid) {
+            let mut a = self.attempts.entry(key).or_insert(Attempts {
+                failures: 0,
+                window_start: now,
+                locked_until: now,
+                banned: false,
+            });
+            if a.locked_until <= now
+                && now.duration_since(a.window_start) >= self.policy.failure_window
+            {
+                *a = Attempts {
+                    failures: 0,
+                    window_start: now,
+                    locked_until: now,
+                    banned: false,
+                };
+            }
+            a.failures += 1;
+            let ban_after = self.policy.ban_after_failures;
+            if ban_after > 0 && a.failures >= ban_after {
+                a.banned = true;
+                a.locked_until = now + self.policy.ban;
+                banned = Some(AuthRefusal::Banned(self.policy.ban));
+            } else {
+                a.locked_until = now + self.policy.penalty(a.failures);
+            }
+        }
+        banned
+    }
+
+    /// 认证成功，清除该 uid 的计数 / Auth succeeded; clear the uid's count
+    pub fn record_success(&self, uid: &str) {
+        self.attempts.remove(&format!("uid:{}", uid));
+    }
+}
+
+impl VConnectIMServer {
+    /// 校验令牌前检查认证尝试限制 / Check the auth attempt guard before validating the token
+    pub fn guard_auth(&self, client_id: &str, uid: Option<&str>) -> Result<(), AuthRefusal> {
+        let Some(ip) = self.connections.get(client_id).map(|c| c.addr.ip()) else {
+            return Ok(());
+        };
+        let checked = self.auth_guard.check(ip, uid);
+        if checked.is_err() {
+            self.metrics.record_auth_throttled();
+        }
+        checked
+    }
+
+    /// 记录认证结果；失败导致封禁时返回 `Banned` / Record an auth outcome; returns `Banned` when a failure triggers a ban
+    pub fn record_auth_result(
+        &self,
+        client_id: &str,
+        uid: Option<&str>,
+        valid: bool,
+    ) -> Option<AuthRefusal> {
+        if valid {
+            if let Some(uid) = uid {
+                self.auth_guard.record_success(uid);
+            }
+            return None;
+        }
+        self.metrics.record_auth_failure();
+        let ip = self.connections.get(client_id).map(|c| c.addr.ip())?;
+        let banned = self.auth_guard.record_failure(ip, uid);
+        if banned.is_some() {
+            tracing::warn!(
+                "🚫 认证失败次数过多，封禁 / Too many failed auth attempts, banning {} (uid {:?})",
+                ip,
+                uid
+            );
+            self.metrics.record_auth_ban();
+        }
+        banned
+    }
+
+    /// 以 `auth_response` 拒绝认证，封禁时随后以 4023 关闭连接
+    /// Refuse an auth with an `auth_response`, then close the connection with 4023 when banned
+    pub async fn refuse_auth(&self, client_id: &str, refusal: AuthRefusal) -> anyhow::Result<()> {
+        let response = ImMessage {
+            msg_type: "auth_response".to_string(),
+            data: serde_json::json!({
+                "status": "failed",
+                "code": refusal.code(),
+                "message": "Too many failed authentication attempts",
+                "retry_after_ms": refusal.retry_after().as_millis() as u64,
+            }),
+            target_uid: None,
+            priority: Default::default(),
+            reply_to: None,
+        };
+        let txt = serde_json::to_string(&response)?;
+        self.send_message_to_client(client_id, Message::Text(txt))
+            .await?;
+        if let AuthRefusal::Banned(_) = refusal {
+            if let Some(conn) = self.connections.get(client_id) {
+                let frame = CloseFrame {
+                    code: CloseCode::from(RejectReason::AuthBanned.close_code()),
+                    reason: "too many failed auth attempts".into(),
+                };
+                let _ = conn.sender.try_send(Message::Close(Some(frame)));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::{im, recv_typed, TestServer};
+
+    #[tokio::test]
+    async fn test_repeated_bad_auth_triggers_temporary_ban() {
+        let policy = AuthGuardPolicy {
+            penalty_base: Duration::from_millis(20),
+            ban_after_failures: 3,
+            ..Default::default()
+        };
+        let ts = TestServer::build(|server| server.with_auth_guard_policy(policy));
+        let (client, mut rx) = ts.add_client("mallory");
+        let bad_auth = || {
+            im(
+                "auth",
+                serde_json::json!({"uid": "alice", "token": ""}),
+                None,
+            )
+        };
+
+        ts.send(&client, bad_auth()).await.unwrap();
+        let resp: ImMessage = recv_typed(&mut rx).await;
+        assert_eq!(resp.data["status"], "failed");
+        assert!(resp.data.get("code").is_none());
+
+        // 惩罚期内立即重试不校验令牌 / An immediate retry inside the lock skips validation
+        ts.send(&client, bad_auth()).await.unwrap();
+        let resp: ImMessage = recv_typed(&mut rx).await;
+        assert_eq!(resp.data["code"], "AUTH_THROTTLED");
+        assert!(resp.data["retry_after_ms"].as_u64().unwrap() <= 20);
+
+        // 第二次失败的惩罚期翻倍 / The lock doubles after the second failure
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        ts.send(&client, bad_auth()).await.unwrap();
+        let _: ImMessage = recv_typed(&mut rx).await;
+        let ip = "127.0.0.1".parse().unwrap();
+        match ts.server.auth_guard.check(ip, None) {
+            Err(AuthRefusal::Throttled(left)) => assert!(left > Duration::from_millis(20)),
+            other => panic!("expected a doubled lock, got {:?}", other),
+        }
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        ts.send(&client, bad_auth()).await.unwrap();
+        let resp: ImMessage = recv_typed(&mut rx).await;
+        assert_eq!(resp.data["code"], "AUTH_BANNED");
+        match rx.try_recv() {
+            Ok(Message::Close(Some(frame))) => assert_eq!(u16::from(frame.code), 4023),
+            other => panic!("expected a close frame, got {:?}", other),
+        }
+
+        // 封禁期间同一 IP 的新连接在准入阶段被拒绝 / New connections from the banned IP are refused at admission
+        let rejection = ts
+            .server
+            .admit_connection("127.0.0.1:4000".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(rejection.reason.close_code(), 4023);
+        assert!(ts.server.guard_auth(&client, Some("bob")).is_err());
+
+        let auth = &ts.server.metrics.snapshot()["auth"];
+        assert_eq!(
+            (
+                auth["failures"].as_u64(),
+                auth["throttled"].as_u64(),
+                auth["bans"].as_u64()
+            ),
+            (Some(3), Some(2), Some(1))
+        );
+    }
+}
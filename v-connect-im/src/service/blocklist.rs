@@ -0,0 +1,209 @@
+//! uid 封禁名单 / uid blocklist
+//!
+//! `blocked_uids` 是热路径上查询的内存集合；`block_uid` / `unblock_uid` 同时写入本地 sled 库
+//! （`blocklist.path`），启动时 `load_blocked` 把库中的封禁重新装回内存，因此手动封禁与房间滥用
+//! 触发的自动封禁都能跨重启保留。管理接口为 `/v1/admin/blocked_uids`。封禁仅本节点，不在集群内复制。
+//! `blocked_uids` is the in-memory set checked on the hot path; `block_uid` / `unblock_uid` also
+//! write a local sled database (`blocklist.path`) and `load_blocked` puts its entries back into
+//! memory at startup, so manual blocks and those triggered by room abuse survive restarts. The
+//! admin API is `/v1/admin/blocked_uids`. Blocks are node-local and not replicated across the cluster.
+
+use crate::server::VConnectIMServer;
+use crate::service::local_store::LazySled;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// 默认库路径 / Default database path
+pub const DEFAULT_BLOCKLIST_PATH: &str = "./data/blocklist";
+
+/// 一条封禁记录 / One block entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockEntry {
+    pub uid: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// 封禁时间（毫秒）；仅在内存中的封禁没有 / Block time in ms; absent for memory-only blocks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked_at: Option<i64>,
+}
+
+/// 封禁记录的持久化 / Persistence for block entries
+pub struct BlockStore {
+    /// 首次封禁时打开，从未封禁过的节点不会创建目录
+    /// Opened on the first block, so nodes that never block create no directory
+    db: LazySled,
+}
+
+impl Default for BlockStore {
+    fn default() -> Self {
+        let path = v::get_global_config_manager()
+            .map(|cm| cm.get_or("blocklist.path", DEFAULT_BLOCKLIST_PATH.to_string()))
+            .unwrap_or_else(|_| DEFAULT_BLOCKLIST_PATH.to_string());
+        Self::new(crate::config::resolve_path(path))
+    }
+}
+
+impl BlockStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            db: LazySled::new(path),
+        }
+    }
+
+    /// 写入或覆盖一条封禁并落盘 / Write or overwrite an entry and flush it to disk
+    pub fn insert(&self, entry: &BlockEntry) -> Result<()> {
+        let (db, tree) = self.open()?;
+        tree.insert(entry.uid.as_bytes(), serde_json::to_vec(entry)?)?;
+        db.flush()?;
+        Ok(())
+    }
+
+    /// 删除一条封禁，返回是否存在 / Remove an entry; returns whether it existed
+    pub fn remove(&self, uid: &str) -> Result<bool> {
+        let Some((db, tree)) = self.existing()? else {
+            return Ok(false);
+        };
+        let existed = tree.remove(uid.as_bytes())?.is_some();
+        db.flush()?;
+        Ok(existed)
+    }
+
+    /// 读取一条封禁 / Read one entry
+    pub fn get(&self, uid: &str) -> Result<Option<BlockEntry>> {
+        let Some((_, tree)) = self.existing()? else {
+            return Ok(None);
+        };
+        match tree.get(uid.as_bytes())? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// 全部封禁（按 uid）/ All entries, by uid
+    pub fn entries(&self) -> Result<Vec<BlockEntry>> {
+        let Some((_, tree)) = self.existing()? else {
+            return Ok(Vec::new());
+        };
+        tree.iter()
+            .map(|item| Ok(serde_json::from_slice(&item?.1)?))
+            .collect()
+    }
+
+    fn open(&self) -> Result<(sled::Db, sled::Tree)> {
+        Self::tree(self.db.open()?)
+    }
+
+    /// 只打开已存在的库 / Open the database only if it exists
+    fn existing(&self) -> Result<Option<(sled::Db, sled::Tree)>> {
+        self.db.existing()?.map(Self::tree).transpose()
+    }
+
+    fn tree(db: sled::Db) -> Result<(sled::Db, sled::Tree)> {
+        let tree = db.open_tree("blocked")?;
+        Ok((db, tree))
+    }
+}
+
+impl VConnectIMServer {
+    /// 封禁 uid 并持久化，返回是否新封禁；写库失败时 uid 仍在内存中保持封禁
+    /// Block a uid and persist it; returns whether it was newly blocked. If the write fails the uid
+    /// stays blocked in memory
+    pub fn block_uid(&self, uid: &str, reason: Option<&str>) -> Result<bool> {
+        let added = self.blocked_uids.insert(uid.to_string());
+        self.block_store.insert(&BlockEntry {
+            uid: uid.to_string(),
+            reason: reason.map(str::to_string),
+            blocked_at: Some(chrono::Utc::now().timestamp_millis()),
+        })?;
+        Ok(added)
+    }
+
+    /// 解除封禁，返回之前是否被封禁 / Unblock a uid; returns whether it was blocked
+    pub fn unblock_uid(&self, uid: &str) -> Result<bool> {
+        let removed = self.blocked_uids.remove(uid).is_some();
+        let persisted = self.block_store.remove(uid)?;
+        Ok(removed || persisted)
+    }
+
+    /// uid 是否被封禁 / Whether the uid is blocked
+    pub fn is_blocked(&self, uid: &str) -> bool {
+        self.blocked_uids.contains(uid)
+    }
+
+    /// 当前全部封禁（按 uid），附带库中的原因与时间
+    /// Every current block, by uid, with the reason and time from the database
+    pub fn list_blocked(&self) -> Result<Vec<BlockEntry>> {
+        let mut uids: Vec<String> = self.blocked_uids.iter().map(|u| u.clone()).collect();
+        uids.sort();
+        uids.into_iter()
+            .map(|uid| {
+                Ok(self.block_store.get(&uid)?.unwrap_or(BlockEntry {
+                    uid,
+                    reason: None,
+                    blocked_at: None,
+                }))
+            })
+            .collect()
+    }
+
+    /// 启动时把库中的封禁装回 `blocked_uids`，返回条数
+    /// Load persisted blocks into `blocked_uids` at startup; returns the count
+    pub fn load_blocked(&self) -> Result<usize> {
+        let entries = self.block_store.entries()?;
+        for entry in &entries {
+            self.blocked_uids.insert(entry.uid.clone());
+        }
+        Ok(entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("vgo-blocklist-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        path
+    }
+
+    #[test]
+    fn test_blocks_survive_restart() {
+        let path = temp_path("restart");
+        {
+            let server = VConnectIMServer::new().with_block_store(Arc::new(BlockStore::new(&path)));
+            assert!(server.block_uid("mallory", Some("spam")).unwrap());
+            assert!(!server.block_uid("mallory", Some("spam")).unwrap());
+            server.block_uid("trudy", None).unwrap();
+            server.block_uid("eve", None).unwrap();
+            assert!(server.unblock_uid("eve").unwrap());
+            assert!(!server.unblock_uid("eve").unwrap());
+        }
+
+        let server = VConnectIMServer::new().with_block_store(Arc::new(BlockStore::new(&path)));
+        assert!(!server.is_blocked("mallory"));
+        assert_eq!(server.load_blocked().unwrap(), 2);
+        assert!(server.is_blocked("mallory"));
+        assert!(server.is_blocked("trudy"));
+        assert!(!server.is_blocked("eve"));
+
+        let listed = server.list_blocked().unwrap();
+        let uids: Vec<&str> = listed.iter().map(|e| e.uid.as_str()).collect();
+        assert_eq!(uids, ["mallory", "trudy"]);
+        assert_eq!(listed[0].reason.as_deref(), Some("spam"));
+        assert!(listed[0].blocked_at.is_some());
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_nothing_is_created_until_a_block() {
+        let path = temp_path("lazy");
+        let server = VConnectIMServer::new().with_block_store(Arc::new(BlockStore::new(&path)));
+        assert_eq!(server.load_blocked().unwrap(), 0);
+        assert!(!server.unblock_uid("nobody").unwrap());
+        assert!(!path.exists());
+    }
+}
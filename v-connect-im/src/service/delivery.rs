@@ -1,8 +1,10 @@
 use tokio_tungstenite::tungstenite::Message;
-use uuid::Uuid;
 
-use crate::domain::message::{HttpSendMessageRequest, HttpSendMessageResponse, ImMessage};
+use crate::domain::message::{
+    HttpSendMessageRequest, HttpSendMessageResponse, ImMessage, PluginRejection,
+};
 use crate::server::VConnectIMServer;
+use crate::service::delivery_log::{DeliveryDrop, DropReason};
 use crate::storage;
 
 impl VConnectIMServer {
@@ -17,12 +19,33 @@ impl VConnectIMServer {
         &self,
         request: HttpSendMessageRequest,
     ) -> HttpSendMessageResponse {
-        let message_id = Uuid::new_v4().to_string();
+        if self.maintenance.is_enabled() {
+            return HttpSendMessageResponse {
+                success: false,
+                message: crate::service::maintenance::MAINTENANCE_MESSAGE.to_string(),
+                message_id: None,
+                delivered_at: None,
+            };
+        }
+        let received_at = std::time::Instant::now();
+        let message_id = self.id_gen.next_str();
         let delivered_at = chrono::Utc::now().timestamp_millis();
         let message_type = request
             .message_type
             .clone()
             .unwrap_or_else(|| "message".to_string());
+        self.metrics.record_received(&message_type);
+        let attachment = match self.check_attachment(&request.content) {
+            Ok(a) => a,
+            Err(reason) => {
+                return HttpSendMessageResponse {
+                    success: false,
+                    message: reason,
+                    message_id: None,
+                    delivered_at: None,
+                }
+            }
+        };
 
         // 调用插件系统处理消息 / Call plugin system to process message
         if let Some(pool) = self.plugin_connection_pool.as_ref() {
@@ -47,28 +70,19 @@ impl VConnectIMServer {
                     );
                     tracing::debug!("插件处理响应详情 / Plugin responses: {:?}", responses);
                     // 检查是否有插件要求停止消息传播 / Check if any plugin wants to stop propagation
-                    for (plugin_name, response) in responses {
-                        tracing::debug!(
-                            "插件 {} 响应 / Plugin {} response: {}",
-                            plugin_name,
-                            plugin_name,
-                            response
+                    if let Some(rejection) = PluginRejection::find(&responses) {
+                        tracing::info!(
+                            "🛑 消息被插件 {} 拦截 / Message stopped by plugin {}: {}",
+                            rejection.plugin,
+                            rejection.plugin,
+                            rejection.reason
                         );
-                        if let Some(flow) = response.get("flow").and_then(|v| v.as_str()) {
-                            if flow == "stop" {
-                                tracing::info!(
-                                    "🛑 消息被插件 {} 拦截 / Message stopped by plugin {}",
-                                    plugin_name,
-                                    plugin_name
-                                );
-                                return HttpSendMessageResponse {
-                                    success: false,
-                                    message: format!("Message blocked by plugin {}", plugin_name),
-                                    message_id: Some(message_id),
-                                    delivered_at: Some(delivered_at),
-                                };
-                            }
-                        }
+                        return HttpSendMessageResponse {
+                            success: false,
+                            message: rejection.reason,
+                            message_id: Some(message_id),
+                            delivered_at: Some(delivered_at),
+                        };
                     }
                 }
                 Err(e) => {
@@ -88,11 +102,20 @@ impl VConnectIMServer {
                 "message_id": message_id
             }),
             target_uid: None,
+            priority: Default::default(),
+            reply_to: None,
         };
         let forward_json = serde_json::to_string(&forward_msg).unwrap_or_default();
 
         // 保存消息到存储插件 / Save message to storage plugin
-        if let Some(pool) = self.plugin_connection_pool.as_ref() {
+        let policy = self.persistence.policy(&message_type);
+        if !policy.persist {
+            tracing::debug!(
+                "⏭️  {} 类型不持久化 / Message type {} is not persisted",
+                message_type,
+                message_type
+            );
+        } else if let Some(pool) = self.plugin_connection_pool.as_ref() {
             match pool
                 .storage_save_message(
                     &message_id,
@@ -107,12 +130,24 @@ impl VConnectIMServer {
             {
                 Ok(true) => {
                     tracing::debug!("💾 消息已保存到存储插件 / Message saved to storage plugin");
+                    if policy.flush && !pool.storage_flush_now().await.unwrap_or(false) {
+                        tracing::warn!("⚠️  存储插件未确认落盘 / Storage plugin did not ack flush");
+                    }
                 }
                 Ok(false) => {
                     tracing::warn!("⚠️  存储插件保存失败 / Storage plugin save failed");
                 }
                 Err(e) => {
+                    // 仅 `storage.on_unavailable = fail` 时出错，拒绝发送
+                    // Only errors under `storage.on_unavailable = fail`; reject the send
                     tracing::error!("❌ 存储插件错误 / Storage plugin error: {}", e);
+                    self.metrics.record_failed(&message_type);
+                    return HttpSendMessageResponse {
+                        success: false,
+                        message: e.to_string(),
+                        message_id: Some(message_id),
+                        delivered_at: None,
+                    };
                 }
             }
         } else {
@@ -128,8 +163,12 @@ impl VConnectIMServer {
             timestamp: delivered_at,
             msg_type: message_type.clone(),
             room_id: None,
+            attachment,
+            reply_to: None,
         };
-        let _ = self.raft.append_entry_as(&self.node_id, &record);
+        if policy.replicate {
+            let _ = self.replicate_record(&record).await;
+        }
 
         let mut in_memory_delivery = false;
         if let Some(clients) = self.uid_clients.get(&request.to_uid) {
@@ -144,17 +183,25 @@ impl VConnectIMServer {
             }
         }
 
-        if !in_memory_delivery {
+        if in_memory_delivery {
+            self.metrics
+                .record_delivered(&message_type, received_at.elapsed());
+        } else {
             let ack_deadline = v::get_global_config_manager()
                 .ok()
                 .map(|cm| cm.get_or("delivery.ack_deadline_ms", 1000_u64))
                 .unwrap_or(1000);
             self.await_ack_or_queue_offline(
-                request.to_uid.clone(),
-                message_id.clone(),
-                None,
-                request.content.clone(),
-                message_type.clone(),
+                storage::OfflineRecord {
+                    message_id: message_id.clone(),
+                    from_uid: None,
+                    to_uid: request.to_uid.clone(),
+                    room_id: None,
+                    content: request.content.clone(),
+                    timestamp: delivered_at,
+                    msg_type: message_type.clone(),
+                    priority: Default::default(),
+                },
                 ack_deadline,
             )
             .await;
@@ -171,67 +218,51 @@ impl VConnectIMServer {
     /// 等待 ACK 或写入离线消息 / Await ACK for a message or enqueue it as offline storage.
     ///
     /// # 参数 Parameters
-    /// * `recipient_uid` - 接收方 UID / Recipient UID.
-    /// * `message_id` - 消息唯一标识 / Unique message identifier.
-    /// * `room_id` - 可选的房间 ID / Optional room id when message targets a room.
-    /// * `content` - 消息内容 JSON / Message payload content.
-    /// * `msg_type` - 消息类型字符串 / Message type label.
-    /// * `deadline_ms` - 等待 ACK 的毫秒数 / Deadline (ms) to wait for ACK before queuing offline.
+    /// * `record` - 未确认时写入的离线记录，`timestamp` 在写入时刷新 / Offline record queued when unacked; `timestamp` is refreshed on queueing.
+    /// * `deadline_ms` - 等待 ACK 的毫秒数，另加 `delivery.ack_grace_ms` 宽限 / Deadline (ms) to wait for ACK before queuing offline, plus the `delivery.ack_grace_ms` grace.
     ///
     /// # 返回 Returns
     /// * `()` - 异步任务内部处理结果，无显式返回 / No direct return value; the spawned task handles persistence.
     pub async fn await_ack_or_queue_offline(
         &self,
-        recipient_uid: String,
-        message_id: String,
-        room_id: Option<String>,
-        content: serde_json::Value,
-        msg_type: String,
+        mut record: crate::storage::OfflineRecord,
         deadline_ms: u64,
     ) {
         let server = self.clone();
+        let wait_ms = deadline_ms + self.ack_windows.policy.grace_ms;
         tokio::spawn(async move {
-            tokio::time::sleep(std::time::Duration::from_millis(deadline_ms)).await;
+            tokio::time::sleep(std::time::Duration::from_millis(wait_ms)).await;
             let acked = server
                 .acked_ids
-                .get(&recipient_uid)
-                .map(|set| set.contains(&message_id))
+                .get(&record.to_uid)
+                .map(|set| set.contains(&record.message_id))
                 .unwrap_or(false);
             if acked {
                 return;
             }
 
-            let _ = server.enforce_offline_quota_for_uid(&recipient_uid).await;
-
-            // 保存离线消息到存储插件 / Save offline message to storage plugin
-            let timestamp = chrono::Utc::now().timestamp_millis();
-            if let Some(pool) = server.plugin_connection_pool.as_ref() {
-                match pool
-                    .storage_save_offline(
-                        &message_id,
-                        None,
-                        &recipient_uid,
-                        &content,
-                        timestamp,
-                        &msg_type,
-                        room_id.as_deref(),
-                    )
-                    .await
-                {
-                    Ok(true) => {
-                        tracing::debug!(
-                            "💾 离线消息已保存 / Offline message saved: {}",
-                            message_id
-                        );
-                    }
-                    Ok(false) => {
-                        tracing::warn!("⚠️  离线消息保存失败 / Offline message save failed");
-                    }
-                    Err(e) => {
-                        tracing::error!("❌ 离线消息保存错误 / Offline message save error: {}", e);
-                    }
-                }
-            }
+            // 接收方仍在线则为确认超时，否则为离线 / Still online means the ack timed out, otherwise offline
+            let online = server
+                .uid_clients
+                .get(&record.to_uid)
+                .is_some_and(|clients| !clients.is_empty());
+            let reason = if online {
+                DropReason::Timeout
+            } else {
+                DropReason::Offline
+            };
+            server.log_delivery_drop(
+                DeliveryDrop::new(reason)
+                    .with_uid(Some(&record.to_uid))
+                    .with_message(Some(&record.message_id)),
+            );
+            // 之后到达的确认不再撤销离线写入 / Acks arriving from now on no longer cancel the offline write
+            record.timestamp = chrono::Utc::now().timestamp_millis();
+            server
+                .ack_windows
+                .close(&record.to_uid, &record.message_id, record.timestamp);
+            // 离线写入与配额裁剪在后台进行 / Offline write and quota trimming run in the background
+            server.queue_offline(record).await;
             // server  // 已移除 / Removed
             //     .send_message_webhook(
             //         &message_id,
@@ -0,0 +1,180 @@
+//! 投递失败日志 / Delivery failure log
+//!
+//! 每次投递失败与连接清理都输出一条结构化 tracing 事件（target `delivery`），字段为
+//! `{client_id, uid, reason, message_id, node}`，缺失的字段不输出；`node` 为尝试投递的节点，跨节点转发
+//! 失败时是对端。原因码固定为 [`DropReason`] 的四种，同时计入 `/v1/health/detailed` 的
+//! `details.delivery.drops`（投递失败）与 `details.delivery.cleanups`（连接清理），可据此对失败激增告警。
+//! Every delivery failure and connection cleanup emits one structured tracing event (target
+//! `delivery`) with the fields `{client_id, uid, reason, message_id, node}`, omitting the missing
+//! ones; `node` is the node delivery was attempted on, the peer for a failed cross-node forward.
+//! Reason codes are the four [`DropReason`]s, and each event is also counted under
+//! `details.delivery.drops` (delivery failures) or `details.delivery.cleanups` (connection
+//! cleanups) of `/v1/health/detailed`, so spikes can be alerted on.
+
+use crate::server::VConnectIMServer;
+
+/// 日志事件的 target / Target of the log events
+pub const DELIVERY_LOG_TARGET: &str = "delivery";
+
+/// 投递失败或连接清理的原因码 / Reason code of a delivery failure or connection cleanup
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropReason {
+    /// 写入发送队列失败（队列满或已关闭）/ The send queue rejected the message (full or closed)
+    SendError,
+    /// 心跳、认证、确认超时或连接被回收 / Heartbeat, auth or ack deadline passed, or the connection was reaped
+    Timeout,
+    /// 跨节点转发失败 / Cross-node forward failed
+    NodeUnreachable,
+    /// 接收方不在线，转为离线消息或未送达 / The recipient is offline; queued offline or not delivered
+    Offline,
+}
+
+impl DropReason {
+    pub const ALL: [DropReason; 4] = [
+        DropReason::SendError,
+        DropReason::Timeout,
+        DropReason::NodeUnreachable,
+        DropReason::Offline,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DropReason::SendError => "send_error",
+            DropReason::Timeout => "timeout",
+            DropReason::NodeUnreachable => "node_unreachable",
+            DropReason::Offline => "offline",
+        }
+    }
+
+    /// 在指标数组中的下标 / Index into the metrics counters
+    pub(crate) fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// 一次投递失败 / One delivery failure
+#[derive(Debug, Clone, Copy)]
+pub struct DeliveryDrop<'a> {
+    pub reason: DropReason,
+    pub client_id: Option<&'a str>,
+    pub uid: Option<&'a str>,
+    pub message_id: Option<&'a str>,
+    /// 默认为当前节点 / Defaults to this node
+    pub node: Option<&'a str>,
+}
+
+impl<'a> DeliveryDrop<'a> {
+    pub fn new(reason: DropReason) -> Self {
+        Self {
+            reason,
+            client_id: None,
+            uid: None,
+            message_id: None,
+            node: None,
+        }
+    }
+
+    pub fn with_client(mut self, client_id: &'a str) -> Self {
+        self.client_id = Some(client_id);
+        self
+    }
+
+    pub fn with_uid(mut self, uid: Option<&'a str>) -> Self {
+        self.uid = uid;
+        self
+    }
+
+    pub fn with_message(mut self, message_id: Option<&'a str>) -> Self {
+        self.message_id = message_id;
+        self
+    }
+
+    pub fn with_node(mut self, node: &'a str) -> Self {
+        self.node = Some(node);
+        self
+    }
+}
+
+impl VConnectIMServer {
+    /// 记录一次投递失败 / Record a delivery failure
+    pub fn log_delivery_drop(&self, drop: DeliveryDrop<'_>) {
+        self.metrics.record_drop(drop.reason);
+        let node = drop.node.unwrap_or(&self.node_id);
+        match drop.reason {
+            // 离线是常态，不作为告警级别 / Being offline is routine and not warning-worthy
+            DropReason::Offline => tracing::info!(
+                target: DELIVERY_LOG_TARGET,
+                reason = drop.reason.as_str(),
+                client_id = drop.client_id,
+                uid = drop.uid,
+                message_id = drop.message_id,
+                node,
+                "delivery dropped"
+            ),
+            _ => tracing::warn!(
+                target: DELIVERY_LOG_TARGET,
+                reason = drop.reason.as_str(),
+                client_id = drop.client_id,
+                uid = drop.uid,
+                message_id = drop.message_id,
+                node,
+                "delivery dropped"
+            ),
+        }
+    }
+
+    /// 记录一次连接清理 / Record a connection cleanup
+    pub fn log_connection_cleanup(&self, reason: DropReason, client_id: &str, uid: Option<&str>) {
+        self.metrics.record_cleanup(reason);
+        tracing::warn!(
+            target: DELIVERY_LOG_TARGET,
+            reason = reason.as_str(),
+            client_id,
+            uid,
+            node = self.node_id.as_str(),
+            "connection cleaned up"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testkit::{im, TestServer};
+    use tokio_tungstenite::tungstenite::Message;
+
+    #[tokio::test]
+    async fn test_failures_are_counted_by_reason() {
+        let ts = TestServer::new();
+        let (alice, _alice_rx) = ts.add_client("alice");
+        let (bob, bob_rx) = ts.add_client("bob");
+        drop(bob_rx);
+
+        // bob 的发送队列已关闭 / bob's send queue is closed
+        ts.send(
+            &alice,
+            im("message", serde_json::json!({"text": "hi"}), Some("bob")),
+        )
+        .await
+        .unwrap();
+        // carol 不在线 / carol is offline
+        ts.send(
+            &alice,
+            im("message", serde_json::json!({"text": "hi"}), Some("carol")),
+        )
+        .await
+        .unwrap();
+
+        ts.server
+            .broadcast_message(Message::Text("{}".into()))
+            .await
+            .unwrap();
+        assert!(!ts.server.connections.contains_key(&bob));
+
+        let snapshot = ts.server.metrics.snapshot();
+        let drops = &snapshot["drops"];
+        assert_eq!(drops["send_error"], 1);
+        assert_eq!(drops["offline"], 1);
+        assert_eq!(drops["node_unreachable"], 0);
+        assert_eq!(snapshot["cleanups"]["send_error"], 1);
+    }
+}
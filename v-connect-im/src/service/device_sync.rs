@@ -0,0 +1,231 @@
+//! 多端投递状态同步 / Multi-device delivery state sync
+//!
+//! 投递状态按 `(uid, device_id, message_id)` 记录；某台设备确认（ack）或已读（read）
+//! 使状态前进时，向同一 uid 的其他设备推送 `sync` 事件。
+//! Delivery state is tracked per `(uid, device_id, message_id)`; when a device's ack or
+//! read advances its state, a `sync` event is pushed to the uid's other devices.
+
+use crate::domain::message::ImMessage;
+use crate::server::VConnectIMServer;
+use dashmap::DashMap;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::VecDeque;
+use tokio_tungstenite::tungstenite::Message;
+
+/// 默认最多跟踪的 `(uid, message_id)` 数 / Default max tracked `(uid, message_id)` pairs
+pub const DEFAULT_MAX_TRACKED: usize = 50_000;
+
+/// 设备ID最大长度 / Max device ID length
+pub const MAX_DEVICE_ID_LEN: usize = 128;
+
+/// 同步事件类型 / Sync event type
+pub const SYNC_EVENT: &str = "sync";
+
+/// 单台设备上的投递状态（只前进不后退）/ Delivery state on one device (only moves forward)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryState {
+    Delivered,
+    Read,
+}
+
+/// 设备投递状态 / Device delivery state
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeviceState {
+    pub device_id: String,
+    pub state: DeliveryState,
+    pub timestamp: i64,
+}
+
+/// 多端状态跟踪器（超出容量时淘汰最早的消息）
+/// Multi-device state tracker (evicts the oldest message beyond capacity)
+pub struct DeviceSyncTracker {
+    states: DashMap<String, Vec<DeviceState>>, // `uid:message_id` -> 各设备状态 / per-device states
+    order: Mutex<VecDeque<String>>,
+    max_tracked: usize,
+}
+
+fn state_key(uid: &str, message_id: &str) -> String {
+    format!("{}:{}", uid, message_id)
+}
+
+impl Default for DeviceSyncTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_TRACKED)
+    }
+}
+
+impl DeviceSyncTracker {
+    pub fn new(max_tracked: usize) -> Self {
+        Self {
+            states: DashMap::new(),
+            order: Mutex::new(VecDeque::new()),
+            max_tracked: max_tracked.max(1),
+        }
+    }
+
+    /// 记录设备状态；状态前进时返回 true（重复或回退的确认返回 false）
+    /// Record a device state; true when the state advanced (duplicate or older acks return false)
+    pub fn record(
+        &self,
+        uid: &str,
+        device_id: &str,
+        message_id: &str,
+        state: DeliveryState,
+        timestamp: i64,
+    ) -> bool {
+        let key = state_key(uid, message_id);
+        let mut is_new = false;
+        let advanced = {
+            let mut devices = self.states.entry(key.clone()).or_insert_with(|| {
+                is_new = true;
+                Vec::new()
+            });
+            match devices.iter_mut().find(|d| d.device_id == device_id) {
+                Some(d) if d.state >= state => false,
+                Some(d) => {
+                    d.state = state;
+                    d.timestamp = timestamp;
+                    true
+                }
+                None => {
+                    devices.push(DeviceState {
+                        device_id: device_id.to_string(),
+                        state,
+                        timestamp,
+                    });
+                    true
+                }
+            }
+        };
+        if is_new {
+            let mut order = self.order.lock();
+            order.push_back(key);
+            while order.len() > self.max_tracked {
+                if let Some(old) = order.pop_front() {
+                    self.states.remove(&old);
+                }
+            }
+        }
+        advanced
+    }
+
+    /// 某 uid 下一条消息在各设备上的状态 / Per-device states of one message for a uid
+    pub fn states(&self, uid: &str, message_id: &str) -> Vec<DeviceState> {
+        self.states
+            .get(&state_key(uid, message_id))
+            .map(|d| d.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl VConnectIMServer {
+    /// 记录客户端所在设备的投递状态，前进时向同 uid 的其他设备推送 `sync`
+    /// Record the delivery state for the client's device and, when it advanced, push
+    /// `sync` to the uid's other devices
+    ///
+    /// # 返回值 / Returns
+    /// 状态是否前进；未认证的客户端返回 false / Whether the state advanced; false for unauthenticated clients
+    pub async fn record_device_state(
+        &self,
+        client_id: &str,
+        message_id: &str,
+        state: DeliveryState,
+    ) -> bool {
+        let Some((uid, device_id)) = self.connections.get(client_id).and_then(|c| {
+            let uid = c.uid.clone()?;
+            let device_id = c.device_id.clone().unwrap_or_else(|| client_id.to_string());
+            Some((uid, device_id))
+        }) else {
+            return false;
+        };
+        let timestamp = chrono::Utc::now().timestamp_millis();
+        if !self
+            .device_states
+            .record(&uid, &device_id, message_id, state, timestamp)
+        {
+            return false;
+        }
+
+        let sync = ImMessage {
+            msg_type: SYNC_EVENT.to_string(),
+            data: serde_json::json!({
+                "message_id": message_id,
+                "device_id": device_id,
+                "state": state,
+                "timestamp": timestamp,
+            }),
+            target_uid: None,
+            priority: Default::default(),
+            reply_to: None,
+        };
+        let Ok(text) = serde_json::to_string(&sync) else {
+            return true;
+        };
+        // 本节点优先，再推送到集群内其他节点上的设备 / This node first, then devices on other nodes
+        let mut servers = vec![(self.node_id.clone(), None)];
+        for node in self.directory.list_nodes() {
+            if node.node_id != self.node_id {
+                if let Some(remote) = self.directory.get_server(&node.node_id) {
+                    servers.push((node.node_id, Some(remote)));
+                }
+            }
+        }
+        for (node_id, remote) in servers {
+            let server = remote.as_deref().unwrap_or(self);
+            let Some(clients) = server.uid_clients.get(&uid).map(|set| {
+                set.iter()
+                    .map(|c| c.clone())
+                    .filter(|c| c != client_id)
+                    .collect::<Vec<_>>()
+            }) else {
+                continue;
+            };
+            for cid in clients {
+                if let Err(e) = server
+                    .send_message_to_client(&cid, Message::Text(text.clone()))
+                    .await
+                {
+                    tracing::debug!("sync to {} on {} failed: {}", cid, node_id, e);
+                }
+            }
+        }
+        true
+    }
+
+    /// 当前用户某条消息在各设备上的状态 / Per-device states of a message for a uid
+    pub fn device_states(&self, uid: &str, message_id: &str) -> Vec<DeviceState> {
+        self.device_states.states(uid, message_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_state_only_advances() {
+        let tracker = DeviceSyncTracker::default();
+        assert!(tracker.record("u1", "phone", "m1", DeliveryState::Delivered, 1));
+        assert!(!tracker.record("u1", "phone", "m1", DeliveryState::Delivered, 2));
+        assert!(tracker.record("u1", "phone", "m1", DeliveryState::Read, 3));
+        assert!(!tracker.record("u1", "phone", "m1", DeliveryState::Delivered, 4));
+        assert!(tracker.record("u1", "desktop", "m1", DeliveryState::Delivered, 5));
+
+        let states = tracker.states("u1", "m1");
+        assert_eq!(states.len(), 2);
+        assert_eq!(states[0].state, DeliveryState::Read);
+        assert_eq!(states[0].timestamp, 3);
+        assert!(tracker.states("u2", "m1").is_empty());
+    }
+
+    #[test]
+    fn test_evicts_oldest() {
+        let tracker = DeviceSyncTracker::new(1);
+        tracker.record("u1", "phone", "m1", DeliveryState::Read, 1);
+        tracker.record("u1", "phone", "m2", DeliveryState::Read, 2);
+        assert!(tracker.states("u1", "m1").is_empty());
+        assert_eq!(tracker.states("u1", "m2").len(), 1);
+    }
+}
@@ -0,0 +1,215 @@
+//! 进程内事件总线 / In-process event bus
+//!
+//! 服务端组件发布事件（认证成功、上下线等），插件转发、指标与 webhook 作为订阅者各自消费，
+//! 发布方无需知道有哪些订阅者。
+//! Server components publish events (authentication, online/offline, ...) and plugin forwarding,
+//! metrics and webhooks consume them as independent subscribers, so publishers do not need to
+//! know who is listening.
+//!
+//! 投递语义 / Delivery semantics:
+//! - 广播：每个订阅者都收到订阅之后发布的每个事件；无订阅者时事件直接丢弃。
+//!   Broadcast: every subscriber sees every event published after it subscribed; events with no
+//!   subscribers are dropped.
+//! - 有损：每个事件类型一个容量为 `event_bus.capacity` 的环形缓冲，落后超过容量的订阅者丢失最旧的事件
+//!   （计入 `lagged`），发布方从不阻塞。
+//!   Lossy: each event type has a ring buffer of `event_bus.capacity`; a subscriber falling further
+//!   behind loses the oldest events (counted in `lagged`) and publishers never block.
+//! - 仅进程内，不持久化，不跨节点。/ In process only: not persisted, not shared across nodes.
+
+use crate::domain::message::WebhookClientStatusData;
+use crate::server::VConnectIMServer;
+use dashmap::DashMap;
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError, Receiver, Sender};
+
+/// 连接认证成功（含断线恢复）/ A connection authenticated (including resumes)
+pub const CONNECTION_AUTHENTICATED: &str = "connection.authenticated";
+/// 客户端上线 / Client connected
+pub const CLIENT_ONLINE: &str = "client.online";
+/// 客户端下线 / Client disconnected
+pub const CLIENT_OFFLINE: &str = "client.offline";
+/// 订阅全部事件类型 / Subscribe to every event type
+pub const ALL_EVENTS: &str = "*";
+
+/// 默认每个事件类型的缓冲容量 / Default buffer capacity per event type
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// 总线事件 / Bus event
+#[derive(Debug, Clone)]
+pub struct ServerEvent {
+    pub event_type: String,
+    pub payload: Arc<Value>,
+    pub timestamp: i64,
+}
+
+/// 事件总线 / Event bus
+#[derive(Debug)]
+pub struct EventBus {
+    capacity: usize,
+    channels: DashMap<String, Sender<ServerEvent>>,
+    published: AtomicU64,
+    lagged: AtomicU64,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            channels: DashMap::new(),
+            published: AtomicU64::new(0),
+            lagged: AtomicU64::new(0),
+        }
+    }
+
+    /// 读取 `event_bus.capacity` / Read `event_bus.capacity`
+    pub fn from_config() -> Self {
+        let capacity = v::get_global_config_manager()
+            .map(|cm| cm.get_or("event_bus.capacity", DEFAULT_CAPACITY))
+            .unwrap_or(DEFAULT_CAPACITY);
+        Self::new(capacity)
+    }
+
+    /// 订阅一个事件类型（[`ALL_EVENTS`] 为全部）/ Subscribe to one event type ([`ALL_EVENTS`] for all)
+    pub fn subscribe(&self, event_type: &str) -> Receiver<ServerEvent> {
+        self.channels
+            .entry(event_type.to_string())
+            .or_insert_with(|| broadcast::channel(self.capacity).0)
+            .subscribe()
+    }
+
+    /// 发布事件，返回收到它的订阅者数 / Publish an event; returns how many subscribers received it
+    pub fn publish(&self, event_type: &str, payload: Value) -> usize {
+        self.published.fetch_add(1, Ordering::Relaxed);
+        let event = ServerEvent {
+            event_type: event_type.to_string(),
+            payload: Arc::new(payload),
+            timestamp: chrono::Utc::now().timestamp_millis(),
+        };
+        [event_type, ALL_EVENTS]
+            .iter()
+            .filter_map(|key| self.channels.get(*key))
+            .map(|tx| tx.send(event.clone()).unwrap_or(0))
+            .sum()
+    }
+
+    /// 接收下一个事件；落后时记录丢失数并继续，总线关闭时返回 `None`
+    /// Receive the next event; when lagging, count the loss and carry on; `None` once closed
+    pub async fn next(&self, rx: &mut Receiver<ServerEvent>) -> Option<ServerEvent> {
+        loop {
+            match rx.recv().await {
+                Ok(event) => return Some(event),
+                Err(RecvError::Lagged(missed)) => {
+                    self.lagged.fetch_add(missed, Ordering::Relaxed);
+                    tracing::warn!(
+                        "⚠️  事件订阅者落后，丢失 {} 个事件 / Event subscriber lagged, {} events lost",
+                        missed,
+                        missed
+                    );
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// 统计快照 / Stats snapshot
+    pub fn snapshot(&self) -> Value {
+        let subscribers: serde_json::Map<String, Value> = self
+            .channels
+            .iter()
+            .map(|e| (e.key().clone(), json!(e.value().receiver_count())))
+            .collect();
+        json!({
+            "capacity": self.capacity,
+            "published": self.published.load(Ordering::Relaxed),
+            "lagged": self.lagged.load(Ordering::Relaxed),
+            "subscribers": subscribers,
+        })
+    }
+}
+
+impl VConnectIMServer {
+    /// 发布客户端上线/下线事件 / Publish a client online/offline event
+    pub fn publish_client_status(
+        &self,
+        online: bool,
+        client_id: &str,
+        uid: Option<String>,
+        addr: &SocketAddr,
+        connected_at: Option<i64>,
+    ) {
+        let now = chrono::Utc::now().timestamp_millis();
+        let data = WebhookClientStatusData {
+            client_id: client_id.to_string(),
+            uid,
+            addr: addr.to_string(),
+            connected_at: if online { Some(now) } else { connected_at },
+            disconnected_at: (!online).then_some(now),
+            online_duration_ms: connected_at
+                .filter(|_| !online)
+                .map(|at| now.saturating_sub(at).max(0) as u64),
+        };
+        self.event_bus.publish(
+            if online {
+                CLIENT_ONLINE
+            } else {
+                CLIENT_OFFLINE
+            },
+            serde_json::to_value(data).unwrap_or_default(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::{im, TestServer};
+
+    #[tokio::test]
+    async fn test_subscriber_receives_auth_event() {
+        let ts = TestServer::new();
+        let mut auth = ts.server.event_bus.subscribe(CONNECTION_AUTHENTICATED);
+        let mut all = ts.server.event_bus.subscribe(ALL_EVENTS);
+        let (client, _rx) = ts.add_client("c1");
+
+        ts.send(
+            &client,
+            im("auth", json!({"uid": "alice", "token": "t"}), None),
+        )
+        .await
+        .unwrap();
+
+        let event = ts.server.event_bus.next(&mut auth).await.unwrap();
+        assert_eq!(event.event_type, CONNECTION_AUTHENTICATED);
+        assert_eq!(event.payload["uid"], "alice");
+        assert_eq!(event.payload["client_id"], "c1");
+        let event = ts.server.event_bus.next(&mut all).await.unwrap();
+        assert_eq!(event.event_type, CONNECTION_AUTHENTICATED);
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscriber_loses_oldest_events() {
+        let bus = EventBus::new(2);
+        assert_eq!(bus.publish(CLIENT_ONLINE, json!(0)), 0);
+        let mut rx = bus.subscribe(CLIENT_ONLINE);
+        for i in 1..=4 {
+            assert_eq!(bus.publish(CLIENT_ONLINE, json!(i)), 1);
+        }
+        // 只剩最新的两个 / Only the newest two remain
+        assert_eq!(*bus.next(&mut rx).await.unwrap().payload, json!(3));
+        assert_eq!(*bus.next(&mut rx).await.unwrap().payload, json!(4));
+        let stats = bus.snapshot();
+        assert_eq!(
+            (stats["published"].as_u64(), stats["lagged"].as_u64()),
+            (Some(5), Some(2))
+        );
+    }
+}
@@ -0,0 +1,218 @@
+//! 大房间扇出 / Fan-out for large rooms
+//!
+//! 群消息以有界并发（`rooms.fanout_parallelism`，默认 256）向成员投递，不再逐个等待。成员数超过
+//! `rooms.fanout_background_threshold`（默认 10000）的房间在持久化后立即返回 `accepted` 与任务 ID
+//! （即消息 ID），扇出在后台进行，进度通过 `GET /v1/room/fanout?job_id=` 查询；完成的任务保留
+//! [`FINISHED_JOB_TTL_MS`]。同一房间的扇出按发送顺序串行，后台任务不会被之后的消息超越。
+//! Group messages are delivered to members with bounded concurrency (`rooms.fanout_parallelism`,
+//! default 256) instead of one send at a time. Rooms with more members than
+//! `rooms.fanout_background_threshold` (default 10000) answer `accepted` with a job id (the message
+//! id) once persisted, and the fan-out runs in the background; poll its progress with
+//! `GET /v1/room/fanout?job_id=`. Finished jobs are kept for [`FINISHED_JOB_TTL_MS`]. Fan-outs to
+//! the same room run one after another in send order, so later messages never overtake a
+//! background job.
+
+use crate::service::group_delivery::FanOut;
+use dashmap::DashMap;
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{Mutex, OwnedMutexGuard};
+
+/// 默认扇出并发数 / Default fan-out parallelism
+pub const DEFAULT_FANOUT_PARALLELISM: usize = 256;
+/// 默认转入后台的成员数阈值 / Default member count above which fan-out runs in the background
+pub const DEFAULT_BACKGROUND_THRESHOLD: usize = 10_000;
+/// 完成的任务保留时长 / How long finished jobs are kept
+pub const FINISHED_JOB_TTL_MS: i64 = 10 * 60 * 1000;
+
+/// 扇出策略 / Fan-out policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FanOutPolicy {
+    /// 同时进行的成员投递数 / Member deliveries in flight at once
+    pub parallelism: usize,
+    /// 成员数超过该值时转入后台 / Fan-out runs in the background above this member count
+    pub background_threshold: usize,
+}
+
+impl Default for FanOutPolicy {
+    fn default() -> Self {
+        Self {
+            parallelism: DEFAULT_FANOUT_PARALLELISM,
+            background_threshold: DEFAULT_BACKGROUND_THRESHOLD,
+        }
+    }
+}
+
+impl FanOutPolicy {
+    /// 读取 `rooms.fanout_parallelism` 与 `rooms.fanout_background_threshold`
+    /// Read `rooms.fanout_parallelism` and `rooms.fanout_background_threshold`
+    pub fn from_config() -> Self {
+        let defaults = Self::default();
+        let Ok(cm) = v::get_global_config_manager() else {
+            return defaults;
+        };
+        Self {
+            parallelism: cm
+                .get_or("rooms.fanout_parallelism", defaults.parallelism)
+                .max(1),
+            background_threshold: cm.get_or(
+                "rooms.fanout_background_threshold",
+                defaults.background_threshold,
+            ),
+        }
+    }
+}
+
+/// 扇出任务进度 / Fan-out job progress
+#[derive(Debug, Default)]
+pub struct FanOutProgress {
+    room_id: String,
+    total: usize,
+    started_at: i64,
+    processed: AtomicUsize,
+    delivered: AtomicUsize,
+    failed: AtomicUsize,
+    offline: AtomicUsize,
+    done: AtomicBool,
+    finished_at: AtomicI64,
+}
+
+impl FanOutProgress {
+    pub fn new(room_id: &str, total: usize, started_at: i64) -> Self {
+        Self {
+            room_id: room_id.to_string(),
+            total,
+            started_at,
+            ..Default::default()
+        }
+    }
+
+    /// 记录一个成员的投递结果 / Record one member's delivery outcome
+    pub fn record(&self, delivered: usize, failed: usize, offline: bool) {
+        self.delivered.fetch_add(delivered, Ordering::Relaxed);
+        self.failed.fetch_add(failed, Ordering::Relaxed);
+        if offline {
+            self.offline.fetch_add(1, Ordering::Relaxed);
+        }
+        self.processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn finish(&self, now_ms: i64) {
+        self.finished_at.store(now_ms, Ordering::Relaxed);
+        self.done.store(true, Ordering::Release);
+    }
+
+    fn finished_before(&self, cutoff_ms: i64) -> bool {
+        self.done.load(Ordering::Acquire) && self.finished_at.load(Ordering::Relaxed) < cutoff_ms
+    }
+
+    /// 当前进度快照 / Snapshot of the current progress
+    pub fn snapshot(&self, job_id: &str) -> FanOutJob {
+        let done = self.done.load(Ordering::Acquire);
+        FanOutJob {
+            job_id: job_id.to_string(),
+            room_id: self.room_id.clone(),
+            total: self.total,
+            processed: self.processed.load(Ordering::Relaxed),
+            delivered_count: self.delivered.load(Ordering::Relaxed),
+            failed_count: self.failed.load(Ordering::Relaxed),
+            offline_count: self.offline.load(Ordering::Relaxed),
+            done,
+            started_at: self.started_at,
+            finished_at: done.then(|| self.finished_at.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// 扇出任务查询结果 / Fan-out job as returned to pollers
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct FanOutJob {
+    pub job_id: String,
+    pub room_id: String,
+    /// 房间成员数 / Room member count
+    pub total: usize,
+    /// 已处理的成员数 / Members processed so far
+    pub processed: usize,
+    pub delivered_count: usize,
+    pub failed_count: usize,
+    pub offline_count: usize,
+    pub done: bool,
+    pub started_at: i64,
+    pub finished_at: Option<i64>,
+}
+
+/// 房间扇出的执行方式 / How a room fan-out was carried out
+#[derive(Debug)]
+pub enum RoomFanOut {
+    /// 已同步完成 / Completed inline
+    Done(FanOut),
+    /// 在后台进行，附任务 ID / Running in the background under this job id
+    Background(String),
+}
+
+/// 扇出策略、后台任务与房间顺序锁 / Fan-out policy, background jobs and per-room ordering locks
+#[derive(Debug, Default)]
+pub struct FanOutJobs {
+    pub policy: FanOutPolicy,
+    jobs: DashMap<String, Arc<FanOutProgress>>,
+    room_locks: DashMap<String, Arc<Mutex<()>>>,
+}
+
+impl FanOutJobs {
+    pub fn new(policy: FanOutPolicy) -> Self {
+        Self {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    /// 该成员数是否转入后台 / Whether a room of this size fans out in the background
+    pub fn runs_in_background(&self, members: usize) -> bool {
+        members > self.policy.background_threshold
+    }
+
+    /// 登记后台任务，并清理过期的已完成任务 / Register a background job, pruning expired finished ones
+    pub fn start(
+        &self,
+        job_id: &str,
+        room_id: &str,
+        total: usize,
+        now_ms: i64,
+    ) -> Arc<FanOutProgress> {
+        let cutoff = now_ms - FINISHED_JOB_TTL_MS;
+        self.jobs.retain(|_, job| !job.finished_before(cutoff));
+        let progress = Arc::new(FanOutProgress::new(room_id, total, now_ms));
+        self.jobs.insert(job_id.to_string(), progress.clone());
+        progress
+    }
+
+    /// 标记任务完成 / Mark a job finished
+    pub fn finish(&self, job_id: &str, now_ms: i64) {
+        if let Some(job) = self.jobs.get(job_id) {
+            job.finish(now_ms);
+        }
+    }
+
+    /// 查询任务进度 / Look up a job's progress
+    pub fn get(&self, job_id: &str) -> Option<FanOutJob> {
+        self.jobs.get(job_id).map(|job| job.snapshot(job_id))
+    }
+
+    /// 按发送顺序排队等待房间扇出 / Queue up, in send order, for a room's fan-out
+    pub async fn lock_room(&self, room_id: &str) -> OwnedMutexGuard<()> {
+        let lock = self
+            .room_locks
+            .entry(room_id.to_string())
+            .or_default()
+            .clone();
+        lock.lock_owned().await
+    }
+
+    /// 释放房间锁，无人等待时移除 / Release a room lock, dropping it when nobody else waits
+    pub fn unlock_room(&self, room_id: &str, guard: OwnedMutexGuard<()>) {
+        drop(guard);
+        self.room_locks
+            .remove_if(room_id, |_, lock| Arc::strong_count(lock) == 1);
+    }
+}
@@ -0,0 +1,142 @@
+//! 群消息逐成员确认 / Per-member acknowledgements for group messages
+//!
+//! 群消息发出时通过 `storage.group_ack.track` 登记应确认的成员（不含发送者），成员确认经
+//! `storage.group_ack.add` 按 `message_id:uid` 写入，发送者查询时由 `storage.group_ack.status`
+//! 从存储读取进度，重启后仍可查询。没有可用存储时不跟踪确认。
+//! When a group message is sent, the members expected to ack it (sender excluded) are registered
+//! through `storage.group_ack.track`; member acks are written keyed by `message_id:uid` through
+//! `storage.group_ack.add`, and the sender's queries read the progress back from storage with
+//! `storage.group_ack.status`, so it survives restarts. Acks are not tracked without storage.
+
+use crate::server::VConnectIMServer;
+use serde::Serialize;
+use serde_json::Value;
+
+/// 群消息确认进度 / Group message ack progress
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GroupAckStatus {
+    pub message_id: String,
+    pub room_id: String,
+    #[serde(skip)]
+    pub sender_uid: Option<String>,
+    pub acked: Vec<String>,
+    pub pending: Vec<String>,
+}
+
+impl GroupAckStatus {
+    fn from_storage(message_id: &str, data: &Value) -> Self {
+        let field = |key: &str| data.get(key).and_then(Value::as_str).unwrap_or_default();
+        let uids = |key: &str| -> Vec<String> {
+            data.get(key)
+                .and_then(|v| serde_json::from_value(v.clone()).ok())
+                .unwrap_or_default()
+        };
+        Self {
+            message_id: message_id.to_string(),
+            room_id: field("room_id").to_string(),
+            sender_uid: Some(field("sender_uid"))
+                .filter(|s| !s.is_empty())
+                .map(str::to_string),
+            acked: uids("acked"),
+            pending: uids("pending"),
+        }
+    }
+}
+
+impl VConnectIMServer {
+    /// 登记群消息及应确认的成员（不含发送者）
+    /// Register a group message and the members expected to ack (sender excluded)
+    pub async fn track_group_acks(
+        &self,
+        message_id: &str,
+        room_id: &str,
+        sender_uid: Option<&str>,
+        members: impl IntoIterator<Item = String>,
+    ) {
+        let Some(pool) = self.plugin_connection_pool.as_ref() else {
+            return;
+        };
+        let recipients: Vec<String> = members
+            .into_iter()
+            .filter(|uid| Some(uid.as_str()) != sender_uid)
+            .collect();
+        let sender_uid = sender_uid.unwrap_or_default();
+        if let Err(e) = pool
+            .storage_track_group_ack(message_id, room_id, sender_uid, &recipients)
+            .await
+        {
+            tracing::warn!(
+                "⚠️  群确认登记失败 / Registering group acks for {} failed: {}",
+                message_id,
+                e
+            );
+        }
+    }
+
+    /// 记录成员确认；消息未登记、非接收者或存储不可用时返回 false
+    /// Record a member ack; false for unknown messages, non-recipients or unavailable storage
+    pub async fn record_group_ack(&self, message_id: &str, uid: &str, timestamp: i64) -> bool {
+        let Some(pool) = self.plugin_connection_pool.as_ref() else {
+            return false;
+        };
+        pool.storage_add_group_ack(message_id, uid, timestamp)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!(
+                    "⚠️  群确认写入失败 / Recording group ack of {} for {} failed: {}",
+                    uid,
+                    message_id,
+                    e
+                );
+                false
+            })
+    }
+
+    /// 群消息确认进度 / Ack progress of a group message
+    pub async fn group_ack_status(&self, message_id: &str) -> Option<GroupAckStatus> {
+        let pool = self.plugin_connection_pool.as_ref()?;
+        match pool.storage_group_ack_status(message_id).await {
+            Ok(data) => data.map(|d| GroupAckStatus::from_storage(message_id, &d)),
+            Err(e) => {
+                tracing::warn!(
+                    "⚠️  群确认查询失败 / Querying group acks for {} failed: {}",
+                    message_id,
+                    e
+                );
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::plugins::runtime::{PluginConnectionPool, PluginRuntimeManager};
+    use crate::storage::builtin::BuiltinStorage;
+    use crate::testkit::TestServer;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_track_record_and_status_through_storage() {
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = Arc::new(PluginConnectionPool::new(manager));
+        pool.enable_builtin_storage(Arc::new(BuiltinStorage::open_temporary().unwrap()));
+        let ts = TestServer::build(|server| server.with_plugin_connection_pool(pool.clone()));
+        let server = &ts.server;
+
+        let members = ["u1", "u2", "u3"].iter().map(|s| s.to_string());
+        server
+            .track_group_acks("m1", "r1", Some("u1"), members)
+            .await;
+        assert!(server.record_group_ack("m1", "u2", 1).await);
+        assert!(!server.record_group_ack("m1", "u1", 1).await);
+        assert!(!server.record_group_ack("m2", "u2", 1).await);
+
+        let status = server.group_ack_status("m1").await.unwrap();
+        assert_eq!(status.room_id, "r1");
+        assert_eq!(status.sender_uid.as_deref(), Some("u1"));
+        assert_eq!(status.acked, vec!["u2".to_string()]);
+        assert_eq!(status.pending, vec!["u3".to_string()]);
+        assert!(server.group_ack_status("m2").await.is_none());
+    }
+}
@@ -0,0 +1,190 @@
+//! 群消息幂等 / Group message idempotency
+//!
+//! WS `group_message` 可带客户端生成的 `client_msg_id`（非空，至多 [`MAX_CLIENT_MSG_ID_LEN`] 字节）。
+//! 同一发送者（uid，未认证时为连接 ID）在同一房间内 `rooms.dedup_window_secs`（默认 60，0 关闭）秒内
+//! 重复使用同一 `client_msg_id` 时不再持久化与扇出，直接返回首次的 `group_message_sent`；首次发送仍在
+//! 进行中时返回 `accepted` 与首次的 `message_id`。持久化失败的发送不占用该 ID，客户端可以原样重试。
+//! 去重表仅在本节点内存中。
+//! A WS `group_message` may carry a client-generated `client_msg_id` (non-empty, at most
+//! [`MAX_CLIENT_MSG_ID_LEN`] bytes). Reusing it from the same sender (the uid, or the connection
+//! id when unauthenticated) in the same room within `rooms.dedup_window_secs` (default 60, 0
+//! disables) skips persistence and fan-out and answers with the original `group_message_sent`;
+//! while the original is still in flight the answer is `accepted` with the original
+//! `message_id`. A send that failed to persist does not claim the id, so clients can retry it
+//! as is. The dedup table lives in this node's memory only.
+
+use crate::domain::message::GroupDeliveryStatus;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+/// `client_msg_id` 最大长度 / Longest accepted `client_msg_id`
+pub const MAX_CLIENT_MSG_ID_LEN: usize = 128;
+
+/// 记录数超过该值时清理过期项 / Expired entries are pruned once this many are kept
+const PRUNE_AT: usize = 4096;
+
+/// 去重策略 / Dedup policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupDedupPolicy {
+    /// 去重窗口，零为关闭 / Dedup window, zero disables
+    pub window: Duration,
+}
+
+impl Default for GroupDedupPolicy {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(60),
+        }
+    }
+}
+
+impl GroupDedupPolicy {
+    /// 读取 `rooms.dedup_window_secs` / Read `rooms.dedup_window_secs`
+    pub fn from_config() -> Self {
+        let defaults = Self::default();
+        let Ok(cm) = v::get_global_config_manager() else {
+            return defaults;
+        };
+        Self {
+            window: Duration::from_secs(
+                cm.get_or("rooms.dedup_window_secs", defaults.window.as_secs()),
+            ),
+        }
+    }
+}
+
+/// 认领 `client_msg_id` 的结果 / Outcome of claiming a `client_msg_id`
+#[derive(Debug, Clone, PartialEq)]
+pub enum DedupClaim {
+    /// 首次出现，照常发送 / First sighting, send as usual
+    New,
+    /// 重复发送，附应返回的 `group_message_sent` 数据 / Duplicate, with the `group_message_sent` data to answer
+    Duplicate(Value),
+}
+
+#[derive(Debug)]
+struct DedupEntry {
+    at: Instant,
+    room_id: String,
+    message_id: String,
+    /// 首次发送完成后的确认 / The original confirmation once the first send completed
+    sent: Option<Value>,
+}
+
+/// 群消息去重表 / Group message dedup table
+#[derive(Debug, Default)]
+pub struct GroupDedup {
+    pub policy: GroupDedupPolicy,
+    entries: DashMap<String, DedupEntry>,
+}
+
+impl GroupDedup {
+    pub fn new(policy: GroupDedupPolicy) -> Self {
+        Self {
+            policy,
+            entries: DashMap::new(),
+        }
+    }
+
+    /// 去重键；未提供合法的 `client_msg_id` 或去重关闭时为 None
+    /// Dedup key; None without a valid `client_msg_id` or when dedup is off
+    pub fn key(&self, from: &str, room_id: &str, data: &Value) -> Option<String> {
+        if self.policy.window.is_zero() {
+            return None;
+        }
+        let client_msg_id = data
+            .get("client_msg_id")
+            .and_then(Value::as_str)
+            .filter(|id| !id.is_empty() && id.len() <= MAX_CLIENT_MSG_ID_LEN)?;
+        Some(format!("{}\u{1f}{}\u{1f}{}", from, room_id, client_msg_id))
+    }
+
+    /// 认领去重键，首次出现时记下 `message_id` / Claim a key, recording `message_id` on first sighting
+    pub fn claim(&self, key: &str, room_id: &str, message_id: &str) -> DedupClaim {
+        let now = Instant::now();
+        let window = self.policy.window;
+        if self.entries.len() >= PRUNE_AT {
+            self.entries
+                .retain(|_, entry| now.duration_since(entry.at) < window);
+        }
+        match self.entries.entry(key.to_string()) {
+            Entry::Occupied(entry) if now.duration_since(entry.get().at) < window => {
+                let entry = entry.get();
+                DedupClaim::Duplicate(entry.sent.clone().unwrap_or_else(|| {
+                    json!({
+                        "room_id": entry.room_id,
+                        "status": GroupDeliveryStatus::Accepted,
+                        "message_id": entry.message_id,
+                    })
+                }))
+            }
+            entry => {
+                entry.insert(DedupEntry {
+                    at: now,
+                    room_id: room_id.to_string(),
+                    message_id: message_id.to_string(),
+                    sent: None,
+                });
+                DedupClaim::New
+            }
+        }
+    }
+
+    /// 记下首次发送的确认 / Record the original confirmation
+    pub fn complete(&self, key: &str, sent: &Value) {
+        if let Some(mut entry) = self.entries.get_mut(key) {
+            entry.sent = Some(sent.clone());
+        }
+    }
+
+    /// 发送失败，释放去重键以便重试 / The send failed; release the key so it can be retried
+    pub fn release(&self, key: &str) {
+        self.entries.remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::message::ImMessage;
+    use crate::testkit::{im, recv_typed, TestServer};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_duplicate_group_send_is_not_fanned_out_again() {
+        let ts = TestServer::new();
+        let (alice, mut alice_rx) = ts.add_client("alice");
+        let (_bob, mut bob_rx) = ts.add_client("bob");
+        for uid in ["alice", "bob"] {
+            ts.server.http_join_room("r1", uid).await;
+        }
+        let send = |client_msg_id: &str| {
+            im(
+                "group_message",
+                json!({"room_id": "r1", "text": "hi", "client_msg_id": client_msg_id}),
+                None,
+            )
+        };
+        ts.send(&alice, send("c-1")).await.unwrap();
+        // alice 作为成员先收到自己的消息 / alice, a member, first receives her own message
+        let _: ImMessage = recv_typed(&mut alice_rx).await;
+        let first: ImMessage = recv_typed(&mut alice_rx).await;
+        assert_eq!(first.msg_type, "group_message_sent");
+        let delivered: ImMessage = recv_typed(&mut bob_rx).await;
+        assert_eq!(delivered.data["message_id"], first.data["message_id"]);
+
+        // 重试返回首次的确认，bob 不会再收到 / A retry answers with the original confirmation and bob gets nothing
+        ts.send(&alice, send("c-1")).await.unwrap();
+        let retried: ImMessage = recv_typed(&mut alice_rx).await;
+        assert_eq!(retried.data, first.data);
+        assert!(bob_rx.try_recv().is_err());
+
+        ts.send(&alice, send("c-2")).await.unwrap();
+        let _: ImMessage = recv_typed(&mut alice_rx).await;
+        let second: ImMessage = recv_typed(&mut alice_rx).await;
+        assert_ne!(second.data["message_id"], first.data["message_id"]);
+        let delivered: ImMessage = recv_typed(&mut bob_rx).await;
+        assert_eq!(delivered.data["message_id"], second.data["message_id"]);
+    }
+}
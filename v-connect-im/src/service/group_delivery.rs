@@ -0,0 +1,533 @@
+//! 群消息投递 / Group message delivery
+//!
+//! 顺序保证：先写存储插件（`persist`）再追加 Raft 日志（`replicate`），两步都成功后才向成员扇出；
+//! 任一步失败立即中止，不投递给任何成员（[`GroupDeliveryStatus::NotPersisted`]）；`storage.on_unavailable = warn`
+//! 时存储不可用不算失败，与单聊一样照常投递。扇出阶段某些成员投递失败不会回滚已持久化的记录
+//! （[`GroupDeliveryStatus::PartiallyDelivered`]），这些成员可通过历史消息拉取补齐。
+//! Ordering guarantee: the storage plugin write (`persist`) and then the Raft append (`replicate`)
+//! both succeed before any member is delivered to; if either fails the send is aborted with no
+//! delivery at all ([`GroupDeliveryStatus::NotPersisted`]); under `storage.on_unavailable = warn`
+//! unavailable storage is not a failure and the message is delivered as 1:1 messages are.
+//! Deliveries failing during fan-out do not roll back the durable record
+//! ([`GroupDeliveryStatus::PartiallyDelivered`]); those members can catch up through history pulls.
+//!
+//! 扇出以有界并发进行，大房间转入后台（[`GroupDeliveryStatus::Accepted`]），见
+//! [`crate::service::fanout`]。
+//! Fan-out runs with bounded concurrency and large rooms move to the background
+//! ([`GroupDeliveryStatus::Accepted`]); see [`crate::service::fanout`].
+
+use crate::domain::message::{ErrorCode, GroupDeliveryStatus, HttpGroupSendResponse};
+use crate::server::VConnectIMServer;
+use crate::service::delivery_log::{DeliveryDrop, DropReason};
+use crate::service::fanout::{FanOutProgress, RoomFanOut};
+use crate::service::persistence::PersistencePolicy;
+use crate::storage::{MessageRecord, OfflineRecord};
+use futures_util::stream::{self, StreamExt};
+use tokio_tungstenite::tungstenite::Message;
+
+/// 群消息未能持久化的原因 / Why a group message was not persisted
+#[derive(Debug)]
+pub enum GroupPersistError {
+    /// 存储插件不可用（`fail` 策略）/ Storage plugin unavailable under `fail`
+    Storage(String),
+    /// Raft 复制未达成 / Raft replication did not succeed
+    Replication(String),
+}
+
+impl GroupPersistError {
+    /// 对应的客户端错误码 / Matching client error code
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            GroupPersistError::Storage(_) => ErrorCode::StorageUnavailable,
+            GroupPersistError::Replication(_) => ErrorCode::ReplicationFailed,
+        }
+    }
+}
+
+impl std::fmt::Display for GroupPersistError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GroupPersistError::Storage(e) => write!(f, "storage: {}", e),
+            GroupPersistError::Replication(e) => write!(f, "replication: {}", e),
+        }
+    }
+}
+
+/// 单个成员的投递结果 / One member's delivery outcome
+struct MemberDelivery {
+    uid: String,
+    delivered: usize,
+    failed: usize,
+    offline: bool,
+}
+
+/// 扇出结果 / Fan-out result
+#[derive(Debug, Default)]
+pub struct FanOut {
+    /// 成功投递的连接数 / Connections delivered to
+    pub delivered: usize,
+    /// 投递失败的连接数 / Connections whose delivery failed
+    pub failed: usize,
+    /// 无在线连接或连接全部投递失败、已转为离线的成员 / Members with no connection, or only failing ones, queued offline
+    pub offline_uids: Vec<String>,
+}
+
+impl FanOut {
+    /// 持久化之后的投递状态 / Delivery status once persisted
+    pub fn status(&self) -> GroupDeliveryStatus {
+        if self.failed > 0 {
+            GroupDeliveryStatus::PartiallyDelivered
+        } else {
+            GroupDeliveryStatus::Delivered
+        }
+    }
+}
+
+impl HttpGroupSendResponse {
+    /// 持久化前被拒绝 / Rejected before persisting
+    pub fn rejected(message_id: String, message: String) -> Self {
+        Self {
+            success: false,
+            status: GroupDeliveryStatus::Rejected,
+            message,
+            message_id: Some(message_id),
+            delivered_count: 0,
+            failed_count: 0,
+            offline_count: 0,
+            job_id: None,
+        }
+    }
+}
+
+impl VConnectIMServer {
+    /// 按持久化策略写存储插件并复制，均成功才返回 `Ok` / Persist and replicate per policy; `Ok` only when both succeed
+    pub async fn persist_group_message(
+        &self,
+        record: &MessageRecord,
+        policy: PersistencePolicy,
+    ) -> Result<(), GroupPersistError> {
+        if let Some(pool) = self
+            .plugin_connection_pool
+            .as_ref()
+            .filter(|_| policy.persist)
+        {
+            let saved = pool.storage_save_record(record).await;
+            match saved {
+                // 仅 `storage.on_unavailable = fail` 时出错 / Only errors under `storage.on_unavailable = fail`
+                Err(e) => return Err(GroupPersistError::Storage(e.to_string())),
+                Ok(true) if policy.flush => {
+                    let _ = pool.storage_flush_now().await;
+                }
+                // 插件拒绝写入或在 `warn` 策略下不可用（已计入 `unavailable` 指标）：与单聊一致，照常投递
+                // Rejected by the plugin or unavailable under `warn` (already counted in the
+                // `unavailable` metric): delivered anyway, like 1:1 messages
+                Ok(_) => {}
+            }
+        }
+        if policy.replicate {
+            self.replicate_record(record)
+                .await
+                .map_err(|e| GroupPersistError::Replication(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// 向房间所有在线成员的每个连接投递 / Deliver to every connection of every online room member
+    ///
+    /// `respect_limits` 为真时跳过被封禁或超出限流的成员（客户端发送），HTTP 接口不做此检查。
+    /// With `respect_limits`, blocked or rate-limited members are skipped (client sends); the HTTP
+    /// API does not check.
+    pub async fn fan_out_to_room(
+        &self,
+        room_id: &str,
+        forward_json: &str,
+        respect_limits: bool,
+    ) -> FanOut {
+        let members = self.room_member_uids(room_id);
+        self.fan_out_members(members, forward_json, respect_limits, None, None)
+            .await
+    }
+
+    /// 投递已持久化的群消息并为离线成员写入离线队列；成员数超过阈值时转入后台并返回任务 ID
+    /// Deliver a persisted group message and queue it for offline members; rooms above the
+    /// threshold fan out in the background and return a job id
+    ///
+    /// `offline` 为离线记录模板，`to_uid` 与 `timestamp` 逐成员填写，其 `message_id` 即任务 ID。
+    /// `offline` is the offline record template, with `to_uid` and `timestamp` filled in per member;
+    /// its `message_id` doubles as the job id.
+    pub async fn deliver_to_room(
+        &self,
+        room_id: &str,
+        forward_json: String,
+        respect_limits: bool,
+        offline: OfflineRecord,
+    ) -> RoomFanOut {
+        // 先排队再取成员，保证同一房间按发送顺序扇出 / Queue up before reading members so a room fans out in send order
+        let guard = self.fanout.lock_room(room_id).await;
+        let members = self.room_member_uids(room_id);
+        if !self.fanout.runs_in_background(members.len()) {
+            let out = self
+                .fan_out_members(members, &forward_json, respect_limits, Some(&offline), None)
+                .await;
+            self.fanout.unlock_room(room_id, guard);
+            return RoomFanOut::Done(out);
+        }
+
+        let job_id = offline.message_id.clone();
+        let progress = self.fanout.start(
+            &job_id,
+            room_id,
+            members.len(),
+            chrono::Utc::now().timestamp_millis(),
+        );
+        let server = self.clone();
+        let room_id = room_id.to_string();
+        let job = job_id.clone();
+        tokio::spawn(async move {
+            let out = server
+                .fan_out_members(
+                    members,
+                    &forward_json,
+                    respect_limits,
+                    Some(&offline),
+                    Some(&progress),
+                )
+                .await;
+            server
+                .fanout
+                .finish(&job, chrono::Utc::now().timestamp_millis());
+            server.fanout.unlock_room(&room_id, guard);
+            tracing::info!(
+                "📣 房间 {} 后台扇出完成 / Background fan-out to room {} finished: job={} delivered={} failed={} offline={}",
+                room_id,
+                room_id,
+                job,
+                out.delivered,
+                out.failed,
+                out.offline_uids.len()
+            );
+        });
+        RoomFanOut::Background(job_id)
+    }
+
+    fn room_member_uids(&self, room_id: &str) -> Vec<String> {
+        self.rooms
+            .get(room_id)
+            .map(|set| set.iter().map(|u| u.clone()).collect())
+            .unwrap_or_default()
+    }
+
+    /// 以有界并发向成员投递 / Deliver to members with bounded concurrency
+    async fn fan_out_members(
+        &self,
+        members: Vec<String>,
+        forward_json: &str,
+        respect_limits: bool,
+        offline: Option<&OfflineRecord>,
+        progress: Option<&FanOutProgress>,
+    ) -> FanOut {
+        stream::iter(members)
+            .map(|uid| self.fan_out_member(uid, forward_json, respect_limits, offline))
+            .buffer_unordered(self.fanout.policy.parallelism)
+            .fold(FanOut::default(), |mut out, member| async move {
+                if let Some(progress) = progress {
+                    progress.record(member.delivered, member.failed, member.offline);
+                }
+                out.delivered += member.delivered;
+                out.failed += member.failed;
+                if member.offline {
+                    out.offline_uids.push(member.uid);
+                }
+                out
+            })
+            .await
+    }
+
+    async fn fan_out_member(
+        &self,
+        uid: String,
+        forward_json: &str,
+        respect_limits: bool,
+        offline: Option<&OfflineRecord>,
+    ) -> MemberDelivery {
+        let mut member = MemberDelivery {
+            uid,
+            delivered: 0,
+            failed: 0,
+            offline: false,
+        };
+        if respect_limits && !self.allow_send_to_uid(&member.uid) {
+            return member;
+        }
+        let clients: Vec<String> = self
+            .uid_clients
+            .get(&member.uid)
+            .map(|clients| clients.iter().map(|c| c.clone()).collect())
+            .unwrap_or_default();
+        for cid in clients {
+            let msg = Message::Text(forward_json.to_string());
+            if self.deliver_to_client(&cid, msg).await.is_ok() {
+                member.delivered += 1;
+            } else {
+                member.failed += 1;
+            }
+        }
+        // 没有连接或连接都已失效（如套接字断开但未清理）时按离线处理
+        // No connection, or only dead ones (e.g. a socket that died without cleanup): treat as offline
+        if member.delivered == 0 {
+            member.offline = true;
+            self.log_delivery_drop(
+                DeliveryDrop::new(DropReason::Offline)
+                    .with_uid(Some(&member.uid))
+                    .with_message(offline.map(|o| o.message_id.as_str())),
+            );
+            if let Some(template) = offline {
+                self.queue_offline(OfflineRecord {
+                    to_uid: member.uid.clone(),
+                    timestamp: chrono::Utc::now().timestamp_millis(),
+                    ..template.clone()
+                })
+                .await;
+            }
+        }
+        member
+    }
+
+    /// 按客户端所在节点投递（本地或转发到远端节点）/ Deliver on the node the client lives on (local or forwarded)
+    pub async fn deliver_to_client(&self, client_id: &str, msg: Message) -> anyhow::Result<()> {
+        match self.directory.locate_client(client_id) {
+            Some(node) if node != self.node_id => match self.directory.get_server(&node) {
+                Some(remote) => remote.send_message_to_client(client_id, msg).await,
+                None => {
+                    self.log_delivery_drop(
+                        DeliveryDrop::new(DropReason::NodeUnreachable)
+                            .with_client(client_id)
+                            .with_node(&node),
+                    );
+                    Err(anyhow::anyhow!("remote node not found"))
+                }
+            },
+            _ => self.send_message_to_client(client_id, msg).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cluster::router::NodeInfo;
+    use crate::domain::message::{ErrorCode, GroupDeliveryStatus, ImMessage};
+    use crate::plugins::runtime::{PluginConnectionPool, PluginRuntimeManager};
+    use crate::service::fanout::FanOutPolicy;
+    use crate::storage::builtin::BuiltinStorage;
+    use crate::testkit::{im, recv_typed, TestServer};
+    use std::sync::Arc;
+
+    async fn room_with(ts: &TestServer, uids: &[&str]) {
+        for uid in uids {
+            ts.server.http_join_room("r1", uid).await;
+        }
+    }
+
+    /// 两节点集群中对端没有服务实例，Raft 达不到多数 / In a two-node cluster whose peer has no server, Raft misses quorum
+    fn break_quorum(ts: &TestServer) {
+        for node_id in [ts.node_id.as_str(), "node-B"] {
+            ts.directory.register_node(NodeInfo {
+                node_id: node_id.into(),
+                weight: 1,
+                is_alive: true,
+            });
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replication_failure_aborts_before_any_delivery() {
+        let ts = TestServer::new();
+        let (alice, mut alice_rx) = ts.add_client("alice");
+        let (_, mut bob_rx) = ts.add_client("bob");
+        room_with(&ts, &["alice", "bob", "carol"]).await;
+        break_quorum(&ts);
+
+        let res = ts
+            .server
+            .http_group_send_message(
+                "r1".into(),
+                "alice".into(),
+                serde_json::json!({"text": "hi"}),
+                None,
+            )
+            .await;
+        assert!(!res.success);
+        assert_eq!(res.status, GroupDeliveryStatus::NotPersisted);
+        assert_eq!((res.delivered_count, res.failed_count), (0, 0));
+        assert!(bob_rx.try_recv().is_err());
+        assert_eq!(ts.server.raft.commit_count(&ts.node_id), 0);
+
+        // 客户端发送同样中止，并收到错误码 / A client send aborts the same way and gets an error code
+        ts.send(
+            &alice,
+            im(
+                "group_message",
+                serde_json::json!({"room_id": "r1", "text": "hi"}),
+                None,
+            ),
+        )
+        .await
+        .unwrap();
+        let err: ImMessage = recv_typed(&mut alice_rx).await;
+        assert_eq!(err.msg_type, "error");
+        assert_eq!(
+            err.data["code"],
+            serde_json::to_value(ErrorCode::ReplicationFailed).unwrap()
+        );
+        assert!(alice_rx.try_recv().is_err());
+        assert!(bob_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unsaved_message_is_still_delivered_under_warn() {
+        use crate::service::storage_fallback::tests::{fallback_dir, pool_with};
+        use crate::service::storage_fallback::OnUnavailable;
+
+        // `warn` 策略下存储不可用时保存返回 false / Saving returns false while storage is down under `warn`
+        let (_, pool) = pool_with(OnUnavailable::Warn, fallback_dir("group-unsaved"));
+        let ts = TestServer::build(|server| server.with_plugin_connection_pool(pool.clone()));
+        let (_, mut bob_rx) = ts.add_client("bob");
+        room_with(&ts, &["alice", "bob"]).await;
+
+        let res = ts
+            .server
+            .http_group_send_message(
+                "r1".into(),
+                "alice".into(),
+                serde_json::json!({"text": "hi"}),
+                None,
+            )
+            .await;
+        assert!(res.success);
+        assert_eq!(res.status, GroupDeliveryStatus::Delivered);
+        let got: ImMessage = recv_typed(&mut bob_rx).await;
+        assert_eq!(got.data["message_id"], res.message_id.unwrap());
+        assert_eq!(ts.server.raft.commit_count(&ts.node_id), 1);
+        assert_eq!(pool.storage_fallback().snapshot()["unavailable"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_persisted_message_reports_failed_deliveries() {
+        let ts = TestServer::new();
+        let (_, mut alice_rx) = ts.add_client("alice");
+        let (_, bob_rx) = ts.add_client("bob");
+        drop(bob_rx);
+        room_with(&ts, &["alice", "bob", "carol"]).await;
+
+        let res = ts
+            .server
+            .http_group_send_message(
+                "r1".into(),
+                "alice".into(),
+                serde_json::json!({"text": "hi"}),
+                None,
+            )
+            .await;
+        assert!(res.success);
+        assert_eq!(res.status, GroupDeliveryStatus::PartiallyDelivered);
+        // bob 的连接已失效，与不在线的 carol 一样转为离线 / bob's dead connection falls through to offline like carol
+        assert_eq!(
+            (res.delivered_count, res.failed_count, res.offline_count),
+            (1, 1, 2)
+        );
+        assert_eq!(ts.server.raft.commit_count(&ts.node_id), 1);
+        let got: ImMessage = recv_typed(&mut alice_rx).await;
+        assert_eq!(got.data["message_id"], res.message_id.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_stale_uid_clients_entry_falls_through_to_offline() {
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = Arc::new(PluginConnectionPool::new(manager));
+        pool.enable_builtin_storage(Arc::new(BuiltinStorage::open_temporary().unwrap()));
+        let ts = TestServer::build(|server| server.with_plugin_connection_pool(pool.clone()));
+        let (_, mut alice_rx) = ts.add_client("alice");
+        room_with(&ts, &["alice", "dave"]).await;
+        // dave 的套接字已断开但 uid_clients 未清理 / dave's socket died but uid_clients was never cleaned up
+        ts.server
+            .uid_clients
+            .entry("dave".into())
+            .or_default()
+            .insert("dave-dead".into());
+
+        let res = ts
+            .server
+            .http_group_send_message(
+                "r1".into(),
+                "alice".into(),
+                serde_json::json!({"text": "hi"}),
+                None,
+            )
+            .await;
+        assert_eq!(
+            (res.delivered_count, res.failed_count, res.offline_count),
+            (1, 1, 1)
+        );
+        let _: ImMessage = recv_typed(&mut alice_rx).await;
+
+        // 离线写入在后台进行 / The offline write happens in the background
+        let started = std::time::Instant::now();
+        while ts.server.offline_count("dave").await.unwrap() == 0 {
+            assert!(started.elapsed() < std::time::Duration::from_secs(10));
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_large_room_fans_out_in_background_in_order() {
+        let ts = TestServer::build(|server| {
+            server.with_fanout_policy(FanOutPolicy {
+                parallelism: 2,
+                background_threshold: 2,
+            })
+        });
+        let (_, mut alice_rx) = ts.add_client("alice");
+        let (_, mut bob_rx) = ts.add_client("bob");
+        room_with(&ts, &["alice", "bob", "carol"]).await;
+
+        let send = |text: &'static str| {
+            ts.server.http_group_send_message(
+                "r1".into(),
+                "alice".into(),
+                serde_json::json!({"text": text}),
+                None,
+            )
+        };
+        let res = send("first").await;
+        assert!(res.success);
+        assert_eq!(res.status, GroupDeliveryStatus::Accepted);
+        let job_id = res.job_id.unwrap();
+        assert_eq!(Some(&job_id), res.message_id.as_ref());
+
+        // 只有 alice 与 bob 时低于阈值，同步投递，但排在后台任务之后
+        // With only alice and bob the room is under the threshold and delivered inline, yet after the background job
+        ts.server.http_leave_room("r1", "carol").await;
+        let res = send("second").await;
+        assert_eq!(res.status, GroupDeliveryStatus::Delivered);
+        assert_eq!(res.job_id, None);
+
+        let job = ts.server.fanout.get(&job_id).unwrap();
+        assert!(job.done);
+        assert_eq!(
+            (
+                job.total,
+                job.processed,
+                job.delivered_count,
+                job.offline_count
+            ),
+            (3, 3, 2, 1)
+        );
+        for rx in [&mut alice_rx, &mut bob_rx] {
+            for text in ["first", "second"] {
+                let got: ImMessage = recv_typed(rx).await;
+                assert_eq!(got.data["content"]["text"], text);
+            }
+        }
+        assert!(ts.server.fanout.get("unknown").is_none());
+    }
+}
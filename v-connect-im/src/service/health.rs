@@ -1,5 +1,23 @@
+//! 健康检查与就绪判定 / Health checks and readiness
+//!
+//! [`HealthAggregator`] 汇总各组件的 [`HealthStatus`]：关键组件不健康时整体为 `unhealthy`，
+//! 非关键组件不健康时为 `degraded`。组件：`im_server`（在线连接未超容量，非关键）、`storage`
+//! （已连接存储插件或启用了内置存储且回退库无积压；`storage.on_unavailable = fail` 时关键，因为
+//! 此时发送会被拒绝）、`cluster`（已知 Leader 且其已在目录注册，关键）。就绪要求整体为
+//! `healthy` 或 `degraded`，且 `plugins.required` 中的插件都已连接。存活检查不看依赖。
+//! [`HealthAggregator`] folds component [`HealthStatus`]es: an unhealthy critical component makes
+//! the whole `unhealthy`, an unhealthy non-critical one makes it `degraded`. Components:
+//! `im_server` (online connections within capacity, non-critical), `storage` (a connected storage
+//! plugin or the built-in storage, with no spool backlog; critical under
+//! `storage.on_unavailable = fail` since sends are rejected then) and `cluster` (a known leader
+//! registered in the directory, critical). Readiness requires `healthy` or `degraded` and every
+//! plugin in `plugins.required` connected. Liveness looks at no dependency.
+
 use crate::server::VConnectIMServer;
+use crate::service::storage_fallback::OnUnavailable;
 use async_trait::async_trait;
+use serde::Serialize;
+use serde_json::{json, Value};
 use v::{HealthCheck, HealthStatus};
 
 // 为 IM 服务实现统一健康检查接口
@@ -22,3 +40,207 @@ impl HealthCheck for VConnectIMServer {
         }
     }
 }
+
+/// 整体健康状态 / Overall health state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthState {
+    Healthy,
+    /// 非关键组件不健康，仍可服务 / A non-critical component is unhealthy; still serving
+    Degraded,
+    /// 关键组件不健康 / A critical component is unhealthy
+    Unhealthy,
+}
+
+/// 组件健康状态的聚合器 / Aggregator of component health
+#[derive(Debug, Default)]
+pub struct HealthAggregator {
+    components: Vec<(HealthStatus, bool)>,
+}
+
+impl HealthAggregator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 加入组件状态，`critical` 决定其不健康时整体是否为 `unhealthy`
+    /// Add a component; `critical` decides whether it being unhealthy makes the whole `unhealthy`
+    pub fn with(mut self, status: HealthStatus, critical: bool) -> Self {
+        self.components.push((status, critical));
+        self
+    }
+
+    pub fn state(&self) -> HealthState {
+        self.components
+            .iter()
+            .filter(|(status, _)| !status.healthy)
+            .fold(HealthState::Healthy, |state, (_, critical)| match state {
+                HealthState::Unhealthy => state,
+                _ if *critical => HealthState::Unhealthy,
+                _ => HealthState::Degraded,
+            })
+    }
+
+    /// 状态与各组件明细 / The state with per-component details
+    pub fn to_json(&self) -> Value {
+        let components: Vec<Value> = self
+            .components
+            .iter()
+            .map(|(status, critical)| {
+                json!({
+                    "component": status.component,
+                    "healthy": status.healthy,
+                    "critical": critical,
+                    "message": status.message,
+                })
+            })
+            .collect();
+        json!({"status": self.state(), "components": components})
+    }
+}
+
+/// 读取 `plugins.required` / Read `plugins.required`
+pub fn required_plugins_from_config() -> Vec<String> {
+    v::get_global_config_manager()
+        .map(|cm| cm.get_or("plugins.required", Vec::<String>::new()))
+        .unwrap_or_default()
+}
+
+fn component(name: &str, healthy: bool, message: String) -> HealthStatus {
+    HealthStatus {
+        component: name.to_string(),
+        healthy,
+        message: Some(message),
+        timestamp: chrono::Utc::now(),
+    }
+}
+
+impl VConnectIMServer {
+    /// 汇总各组件健康状态 / Aggregate component health
+    pub async fn health_aggregator(&self) -> HealthAggregator {
+        let pool = self.plugin_connection_pool.as_ref();
+        let storage = match pool {
+            Some(pool) if !pool.storage_available() => {
+                component("storage", false, "no storage connected".into())
+            }
+            Some(pool) if pool.storage_fallback().has_pending() => {
+                component("storage", false, "fallback spool not drained".into())
+            }
+            Some(_) => component("storage", true, "connected".into()),
+            None => component("storage", false, "no plugin connection pool".into()),
+        };
+        let storage_critical =
+            pool.is_some_and(|pool| pool.storage_fallback().policy() == OnUnavailable::Fail);
+
+        let leader = self.raft.get_leader();
+        let has_leader = !leader.is_empty() && self.directory.get_server(&leader).is_some();
+        let cluster = component("cluster", has_leader, format!("leader={}", leader));
+
+        HealthAggregator::new()
+            .with(self.check_health().await, false)
+            .with(storage, storage_critical)
+            .with(cluster, true)
+    }
+
+    /// 必需但未连接的插件 / Required plugins that are not connected
+    pub fn missing_required_plugins(&self) -> Vec<String> {
+        self.required_plugins
+            .iter()
+            .filter(|name| {
+                !self
+                    .plugin_connection_pool
+                    .as_ref()
+                    .is_some_and(|pool| pool.is_connected(name))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// 就绪判定：整体 `healthy`/`degraded` 且必需插件均已连接
+    /// Readiness: `healthy`/`degraded` overall with every required plugin connected
+    pub async fn readiness(&self) -> (bool, Value) {
+        let aggregator = self.health_aggregator().await;
+        let missing = self.missing_required_plugins();
+        let ready = aggregator.state() != HealthState::Unhealthy && missing.is_empty();
+        let mut report = aggregator.to_json();
+        report["ready"] = json!(ready);
+        report["missing_plugins"] = json!(missing);
+        // 维护模式不影响就绪，供负载均衡自行判断 / Maintenance does not affect readiness; load balancers decide
+        report["maintenance"] = json!(self.maintenance.is_enabled());
+        (ready, report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::runtime::{PluginConnectionPool, PluginRuntimeManager};
+    use crate::service::storage_fallback::StorageFallback;
+    use crate::storage::builtin::BuiltinStorage;
+    use crate::testkit::TestServer;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_aggregator_state() {
+        let ok = || component("a", true, String::new());
+        let bad = || component("b", false, String::new());
+        assert_eq!(HealthAggregator::new().state(), HealthState::Healthy);
+        assert_eq!(
+            HealthAggregator::new()
+                .with(ok(), true)
+                .with(bad(), false)
+                .state(),
+            HealthState::Degraded
+        );
+        assert_eq!(
+            HealthAggregator::new()
+                .with(bad(), true)
+                .with(bad(), false)
+                .state(),
+            HealthState::Unhealthy
+        );
+    }
+
+    #[tokio::test]
+    async fn test_readiness_is_gated_by_dependencies() {
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = Arc::new(PluginConnectionPool::new(manager).with_storage_fallback(
+            StorageFallback::new(OnUnavailable::Fail, "./unused-storage-fallback"),
+        ));
+        let ts = TestServer::build(|server| server.with_plugin_connection_pool(pool.clone()));
+
+        // 存储不可用且策略为 fail：不就绪 / Storage down under the fail policy: not ready
+        let (ready, report) = ts.server.readiness().await;
+        assert!(!ready);
+        assert_eq!(report["status"], "unhealthy");
+
+        pool.enable_builtin_storage(Arc::new(BuiltinStorage::open_temporary().unwrap()));
+        let (ready, report) = ts.server.readiness().await;
+        assert!(ready, "{}", report);
+        assert_eq!(report["status"], "healthy");
+
+        // 必需插件未连接 / A required plugin is not connected
+        let server = ts
+            .server
+            .as_ref()
+            .clone()
+            .with_required_plugins(vec!["auth".into()]);
+        let (ready, report) = server.readiness().await;
+        assert!(!ready);
+        assert_eq!(report["missing_plugins"], json!(["auth"]));
+
+        // 没有 Leader / No leader
+        ts.raft.set_leader("node-Z".into());
+        let (ready, report) = ts.server.readiness().await;
+        assert!(!ready);
+        assert_eq!(report["status"], "unhealthy");
+    }
+
+    #[tokio::test]
+    async fn test_storage_outage_only_degrades_without_fail_policy() {
+        let ts = TestServer::new();
+        let (ready, report) = ts.server.readiness().await;
+        assert!(ready);
+        assert_eq!(report["status"], "degraded");
+    }
+}
@@ -0,0 +1,160 @@
+//! 消息 ID 生成 / Message id generation
+//!
+//! 默认使用 Snowflake 风格的 64 位 ID：41 位毫秒时间戳（自 2024-01-01 起）、10 位节点号、
+//! 12 位序列号。ID 随时间单调递增，可直接用于范围扫描；节点号来自 `cluster.worker_id`，
+//! 未配置时由 `node_id` 哈希得到。`message.id_generator = "uuid"` 可回退为 UUID 字符串。
+//! By default ids are Snowflake-style 64-bit values: a 41-bit millisecond timestamp (since
+//! 2024-01-01), a 10-bit worker id and a 12-bit sequence. Ids increase monotonically and
+//! can be range-scanned directly; the worker id comes from `cluster.worker_id`, or is
+//! hashed from `node_id` when unset. `message.id_generator = "uuid"` falls back to UUID strings.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use uuid::Uuid;
+
+/// 自定义纪元 2024-01-01T00:00:00Z（毫秒）/ Custom epoch 2024-01-01T00:00:00Z (ms)
+pub const EPOCH_MS: u64 = 1_704_067_200_000;
+
+const WORKER_BITS: u32 = 10;
+const SEQUENCE_BITS: u32 = 12;
+const MAX_WORKER: u64 = (1 << WORKER_BITS) - 1;
+const MAX_SEQUENCE: u64 = (1 << SEQUENCE_BITS) - 1;
+const TIMESTAMP_SHIFT: u32 = WORKER_BITS + SEQUENCE_BITS;
+
+/// ID 生成方式 / Id generation scheme
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IdScheme {
+    Snowflake,
+    Uuid,
+}
+
+/// 节点内唯一、跨节点按节点号区分的 ID 生成器
+/// Id generator, unique within a node and across nodes via the worker id bits
+#[derive(Debug)]
+pub struct IdGenerator {
+    scheme: IdScheme,
+    worker_id: u64,
+    /// 上一个 ID，用于保证单调 / Last id handed out, keeps ids monotonic
+    last: AtomicU64,
+}
+
+impl IdGenerator {
+    pub fn new(scheme: IdScheme, worker_id: u64) -> Self {
+        Self {
+            scheme,
+            worker_id: worker_id & MAX_WORKER,
+            last: AtomicU64::new(0),
+        }
+    }
+
+    /// 按配置为节点创建生成器 / Build the generator for a node from config
+    pub fn for_node(node_id: &str) -> Self {
+        let (scheme, worker_id) = match v::get_global_config_manager() {
+            Ok(cm) => {
+                let scheme = match cm
+                    .get_or("message.id_generator", "snowflake".to_string())
+                    .as_str()
+                {
+                    "uuid" => IdScheme::Uuid,
+                    _ => IdScheme::Snowflake,
+                };
+                let worker_id = cm
+                    .get::<u64>("cluster.worker_id")
+                    .unwrap_or_else(|_| worker_id_from_node(node_id));
+                (scheme, worker_id)
+            }
+            Err(_) => (IdScheme::Snowflake, worker_id_from_node(node_id)),
+        };
+        Self::new(scheme, worker_id)
+    }
+
+    /// 下一个数值 ID（Snowflake）/ Next numeric (Snowflake) id
+    pub fn next(&self) -> u64 {
+        self.next_at(now_ms())
+    }
+
+    /// 下一个字符串 ID：Snowflake 为定宽 20 位十进制（字典序即时间序），否则为 UUID
+    /// Next string id: a fixed-width 20-digit decimal for Snowflake (lexicographic order is
+    /// time order), otherwise a UUID
+    pub fn next_str(&self) -> String {
+        match self.scheme {
+            IdScheme::Snowflake => format!("{:020}", self.next()),
+            IdScheme::Uuid => Uuid::new_v4().to_string(),
+        }
+    }
+
+    fn next_at(&self, now_ms: u64) -> u64 {
+        let ts = now_ms.saturating_sub(EPOCH_MS);
+        let mut last = self.last.load(Ordering::Relaxed);
+        loop {
+            let last_ts = last >> TIMESTAMP_SHIFT;
+            let last_seq = last & MAX_SEQUENCE;
+            // 时钟回拨或序列用尽时沿用/借用上一毫秒，保持单调
+            // On clock rollback or sequence exhaustion reuse/borrow the last millisecond to stay monotonic
+            let (ts, seq) = if ts > last_ts {
+                (ts, 0)
+            } else if last_seq < MAX_SEQUENCE {
+                (last_ts, last_seq + 1)
+            } else {
+                (last_ts + 1, 0)
+            };
+            let id = (ts << TIMESTAMP_SHIFT) | (self.worker_id << SEQUENCE_BITS) | seq;
+            match self
+                .last
+                .compare_exchange_weak(last, id, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => return id,
+                Err(actual) => last = actual,
+            }
+        }
+    }
+}
+
+/// 由节点 ID 哈希出节点号（FNV-1a）/ Derive a worker id from the node id (FNV-1a)
+pub fn worker_id_from_node(node_id: &str) -> u64 {
+    let hash = node_id.bytes().fold(0xcbf2_9ce4_8422_2325u64, |h, b| {
+        (h ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    });
+    hash & MAX_WORKER
+}
+
+fn now_ms() -> u64 {
+    u64::try_from(chrono::Utc::now().timestamp_millis()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_ids_are_monotonic_and_carry_worker_bits() {
+        let gen = IdGenerator::new(IdScheme::Snowflake, 7);
+        let t = EPOCH_MS + 1_000;
+        let ids: Vec<u64> = (0..10_000).map(|_| gen.next_at(t)).collect();
+        // 同一毫秒超过 4096 个时借用下一毫秒 / More than 4096 in one ms borrows the next ms
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+        assert!(ids.iter().all(|id| (id >> SEQUENCE_BITS) & MAX_WORKER == 7));
+        assert_eq!(ids[0] >> TIMESTAMP_SHIFT, 1_000);
+
+        // 时钟回拨不产生更小的 ID / A clock rollback never yields a smaller id
+        let last = *ids.last().unwrap();
+        assert!(gen.next_at(t - 500) > last);
+    }
+
+    #[test]
+    fn test_ids_are_unique_across_workers_and_sortable_as_strings() {
+        let a = IdGenerator::new(IdScheme::Snowflake, worker_id_from_node("node-A"));
+        let b = IdGenerator::new(IdScheme::Snowflake, worker_id_from_node("node-B"));
+        assert_ne!(a.worker_id, b.worker_id);
+        let ids: HashSet<u64> = (0..1_000).flat_map(|_| [a.next(), b.next()]).collect();
+        assert_eq!(ids.len(), 2_000);
+
+        let s1 = a.next_str();
+        let s2 = a.next_str();
+        assert_eq!(s1.len(), 20);
+        assert!(s1 < s2);
+
+        let uuid = IdGenerator::new(IdScheme::Uuid, 0).next_str();
+        assert!(Uuid::parse_str(&uuid).is_ok());
+    }
+}
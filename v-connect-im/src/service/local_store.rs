@@ -0,0 +1,72 @@
+//! 按需打开的本地 sled 库 / Local sled databases opened on demand
+//!
+//! 房间元数据、封禁名单与定时消息各自使用一个本地库，首次写入时才打开，从未写入过的节点不会创建目录；
+//! 读取只打开已存在的库（上次运行留下的也算）。每次写入都由调用方显式落盘，因此关闭后台刷盘线程，
+//! 库释放后文件锁随即释放。
+//! Room metadata, the blocklist and scheduled messages each keep a local database that is only
+//! opened on the first write, so nodes that never write create no directory; reads only open a
+//! database that already exists, including one left by a previous run. Callers flush every write
+//! explicitly, so the background flusher is off and the file lock is released as soon as the
+//! database is dropped.
+
+use anyhow::Result;
+use parking_lot::Mutex;
+use std::path::PathBuf;
+
+/// 首次使用时打开的 sled 库 / A sled database opened on first use
+pub struct LazySled {
+    path: PathBuf,
+    db: Mutex<Option<sled::Db>>,
+}
+
+impl LazySled {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            db: Mutex::new(None),
+        }
+    }
+
+    /// 打开库，不存在时创建 / Open the database, creating it if missing
+    pub fn open(&self) -> Result<sled::Db> {
+        let mut guard = self.db.lock();
+        if let Some(db) = guard.as_ref() {
+            return Ok(db.clone());
+        }
+        let db = sled::Config::new()
+            .path(&self.path)
+            .flush_every_ms(None)
+            .open()?;
+        *guard = Some(db.clone());
+        Ok(db)
+    }
+
+    /// 只打开已存在的库 / Open the database only if it exists
+    pub fn existing(&self) -> Result<Option<sled::Db>> {
+        if self.db.lock().is_none() && !self.path.exists() {
+            return Ok(None);
+        }
+        self.open().map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directory_is_created_on_first_open() {
+        let path = std::env::temp_dir().join(format!("vim-lazy-sled-{}", uuid::Uuid::new_v4()));
+        let store = LazySled::new(&path);
+        assert!(store.existing().unwrap().is_none());
+        assert!(!path.exists());
+
+        store.open().unwrap().insert("k", "v").unwrap();
+        assert!(path.exists());
+        let db = store.existing().unwrap().unwrap();
+        assert_eq!(db.get("k").unwrap().as_deref(), Some(&b"v"[..]));
+        drop(db);
+        drop(store);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
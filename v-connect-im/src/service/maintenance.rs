@@ -0,0 +1,84 @@
+//! 维护模式：停止接收新消息，读取类请求照常处理
+//! Maintenance mode: stop accepting new messages while reads keep being served
+//!
+//! 由 `maintenance.enabled` 设定启动时的状态，运行中可通过 `/v1/admin/maintenance` 切换；仅作用于
+//! 本节点，不在集群内复制。开启时 WS 写类消息（[`WRITE_TYPES`]）以 `MAINTENANCE_MODE` 错误拒绝，
+//! HTTP 发送接口（`/v1/message/send`、`/v1/room/send`）以 503 与同一错误码拒绝，到期的定时消息留到
+//! 维护结束后再投递；`ping`、`online_clients`、确认与历史拉取等不受影响；`/v1/health/ready` 中的
+//! `maintenance` 字段供负载均衡判断。
+//! `maintenance.enabled` sets the state at boot and `/v1/admin/maintenance` toggles it at runtime;
+//! it applies to this node only and is not replicated. While on, WS write types ([`WRITE_TYPES`])
+//! are rejected with a `MAINTENANCE_MODE` error, the HTTP send endpoints (`/v1/message/send`,
+//! `/v1/room/send`) answer 503 with the same code, and due scheduled messages wait until
+//! maintenance ends; `ping`, `online_clients`, acks and history pulls are unaffected; the
+//! `maintenance` field of `/v1/health/ready` lets load balancers act.
+
+use crate::domain::message::ErrorCode;
+use actix_web::http::StatusCode;
+use actix_web::HttpResponse;
+use std::sync::atomic::{AtomicBool, Ordering};
+use v::response::respond_any;
+
+/// 维护期间拒绝新消息的说明 / Explanation given when new messages are refused during maintenance
+pub const MAINTENANCE_MESSAGE: &str = "server is in maintenance, new messages are not accepted";
+
+/// 维护期间拒绝的 WS 消息类型 / WS message types rejected during maintenance
+pub const WRITE_TYPES: &[&str] = &[
+    "message",
+    "private_message",
+    "group_message",
+    "schedule_message",
+    "pin_message",
+    "unpin_message",
+    "react",
+    "unreact",
+];
+
+/// 维护模式开关 / Maintenance mode switch
+#[derive(Debug, Default)]
+pub struct MaintenanceMode {
+    enabled: AtomicBool,
+}
+
+impl MaintenanceMode {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled: AtomicBool::new(enabled),
+        }
+    }
+
+    /// 读取 `maintenance.enabled` / Read `maintenance.enabled`
+    pub fn from_config() -> Self {
+        Self::new(
+            v::get_global_config_manager()
+                .map(|cm| cm.get_or("maintenance.enabled", false))
+                .unwrap_or(false),
+        )
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// 设置开关，返回之前的状态 / Set the switch, returning the previous state
+    pub fn set(&self, enabled: bool) -> bool {
+        self.enabled.swap(enabled, Ordering::Relaxed)
+    }
+
+    /// 当前是否应拒绝该类型的消息 / Whether a message of this type is rejected right now
+    pub fn rejects(&self, msg_type: &str) -> bool {
+        self.is_enabled() && WRITE_TYPES.contains(&msg_type)
+    }
+}
+
+/// 维护期间 HTTP 发送接口的 503 应答 / 503 answer of the HTTP send endpoints during maintenance
+pub fn http_rejection() -> HttpResponse {
+    respond_any(
+        StatusCode::SERVICE_UNAVAILABLE,
+        serde_json::json!({
+            "success": false,
+            "code": ErrorCode::MaintenanceMode,
+            "message": MAINTENANCE_MESSAGE,
+        }),
+    )
+}
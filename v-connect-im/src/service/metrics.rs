@@ -0,0 +1,322 @@
+//! 消息投递指标 / Message delivery metrics
+//!
+//! 按消息类型统计接收、送达、转入离线、失败次数，并记录送达延迟直方图，
+//! 通过 `/v1/health/detailed` 的 `details.delivery` 输出。
+//! Per message type counters for received, delivered, offline-queued and failed messages,
+//! plus a delivery latency histogram, reported under `details.delivery` of `/v1/health/detailed`.
+//! 另按类型统计事件总线上的服务端事件（`events`）。
+//! Server events seen on the event bus are counted per type as well (`events`).
+//! 投递失败与连接清理按原因码计数（`drops` / `cleanups`，见 [`crate::service::delivery_log`]）。
+//! Delivery failures and connection cleanups are counted by reason code (`drops` / `cleanups`,
+//! see [`crate::service::delivery_log`]).
+
+use crate::service::delivery_log::DropReason;
+use dashmap::DashMap;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// 最多单独统计的消息类型数，超出的归入 `other`（类型来自客户端输入）
+/// Most message types tracked individually; the rest fall into `other` (types come from client input)
+pub const MAX_TRACKED_TYPES: usize = 64;
+
+/// 延迟直方图桶上界（毫秒）/ Latency histogram bucket upper bounds (ms)
+pub const LATENCY_BUCKETS_MS: &[u64] = &[1, 5, 10, 25, 50, 100, 250, 500, 1000, 2500];
+
+/// 单个消息类型的计数 / Counters for one message type
+#[derive(Debug, Default)]
+struct TypeCounters {
+    received: AtomicU64,
+    delivered: AtomicU64,
+    offline_queued: AtomicU64,
+    failed: AtomicU64,
+}
+
+/// 延迟直方图 / Latency histogram
+#[derive(Debug)]
+pub(crate) struct LatencyHistogram {
+    /// 每个桶一个计数，末尾为 +Inf / One count per bucket, the last one is +Inf
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+    sum_us: AtomicU64,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: (0..=LATENCY_BUCKETS_MS.len())
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+            count: AtomicU64::new(0),
+            sum_us: AtomicU64::new(0),
+        }
+    }
+}
+
+impl LatencyHistogram {
+    pub(crate) fn observe(&self, latency: Duration) {
+        let ms = u64::try_from(latency.as_millis()).unwrap_or(u64::MAX);
+        let idx = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|&le| ms <= le)
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.buckets[idx].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        let us = u64::try_from(latency.as_micros()).unwrap_or(u64::MAX);
+        self.sum_us.fetch_add(us, Ordering::Relaxed);
+    }
+
+    /// 累计桶（与 Prometheus `le` 语义一致）/ Cumulative buckets (Prometheus `le` semantics)
+    fn snapshot(&self) -> Value {
+        let mut cumulative = 0;
+        let buckets: serde_json::Map<String, Value> = self
+            .buckets
+            .iter()
+            .enumerate()
+            .map(|(i, b)| {
+                cumulative += b.load(Ordering::Relaxed);
+                let le = LATENCY_BUCKETS_MS
+                    .get(i)
+                    .map_or_else(|| "+Inf".to_string(), |ms| ms.to_string());
+                (le, json!(cumulative))
+            })
+            .collect();
+        json!({
+            "buckets_ms": buckets,
+            "count": self.count.load(Ordering::Relaxed),
+            "sum_ms": self.sum_us.load(Ordering::Relaxed) as f64 / 1000.0,
+        })
+    }
+
+    /// 以 Prometheus 文本格式写出（秒为单位），`labels` 为已转义的 `k="v"` 列表
+    /// Write in the Prometheus text format (in seconds); `labels` is an escaped `k="v"` list
+    pub(crate) fn write_prometheus(&self, out: &mut String, name: &str, labels: &str) {
+        use std::fmt::Write;
+        let mut cumulative = 0;
+        for (i, b) in self.buckets.iter().enumerate() {
+            cumulative += b.load(Ordering::Relaxed);
+            let le = LATENCY_BUCKETS_MS.get(i).map_or_else(
+                || "+Inf".to_string(),
+                |ms| (*ms as f64 / 1000.0).to_string(),
+            );
+            let _ = writeln!(out, "{name}_bucket{{{labels},le=\"{le}\"}} {cumulative}");
+        }
+        let sum = self.sum_us.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "{name}_sum{{{labels}}} {sum}");
+        let count = self.count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{name}_count{{{labels}}} {count}");
+    }
+}
+
+/// 认证尝试计数 / Auth attempt counters
+#[derive(Debug, Default)]
+struct AuthCounters {
+    failures: AtomicU64,
+    throttled: AtomicU64,
+    bans: AtomicU64,
+}
+
+/// 投递指标 / Delivery metrics
+#[derive(Debug, Default)]
+pub struct DeliveryMetrics {
+    by_type: DashMap<String, TypeCounters>,
+    latency: LatencyHistogram,
+    /// 事件类型来自服务端常量，数量有限 / Event types come from server constants, so they are bounded
+    events: DashMap<String, AtomicU64>,
+    auth: AuthCounters,
+    /// 按 [`DropReason`] 计数 / Counted by [`DropReason`]
+    drops: [AtomicU64; 4],
+    cleanups: [AtomicU64; 4],
+}
+
+impl DeliveryMetrics {
+    pub fn record_received(&self, msg_type: &str) {
+        self.with_type(msg_type, |c| c.received.fetch_add(1, Ordering::Relaxed));
+    }
+
+    /// 记录一次送达及其自接收起的耗时 / Record a delivery and its latency since receipt
+    pub fn record_delivered(&self, msg_type: &str, latency: Duration) {
+        self.with_type(msg_type, |c| c.delivered.fetch_add(1, Ordering::Relaxed));
+        self.latency.observe(latency);
+    }
+
+    pub fn record_offline_queued(&self, msg_type: &str) {
+        self.with_type(msg_type, |c| {
+            c.offline_queued.fetch_add(1, Ordering::Relaxed)
+        });
+    }
+
+    pub fn record_failed(&self, msg_type: &str) {
+        self.with_type(msg_type, |c| c.failed.fetch_add(1, Ordering::Relaxed));
+    }
+
+    /// 记录一个服务端事件 / Record a server event
+    pub fn record_event(&self, event_type: &str) {
+        if let Some(c) = self.events.get(event_type) {
+            c.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        self.events
+            .entry(event_type.to_string())
+            .or_default()
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录一次认证失败 / Record a failed auth
+    pub fn record_auth_failure(&self) {
+        self.auth.failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录一次因惩罚期或封禁被拒绝的认证 / Record an auth refused by a lock or ban
+    pub fn record_auth_throttled(&self) {
+        self.auth.throttled.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录一次封禁 / Record a ban
+    pub fn record_auth_ban(&self) {
+        self.auth.bans.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录一次投递失败 / Record a delivery failure
+    pub fn record_drop(&self, reason: DropReason) {
+        self.drops[reason.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 记录一次连接清理 / Record a connection cleanup
+    pub fn record_cleanup(&self, reason: DropReason) {
+        self.cleanups[reason.index()].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 指标快照（JSON）/ Metrics snapshot as JSON
+    pub fn snapshot(&self) -> Value {
+        let mut totals = [0u64; 4];
+        let by_type: serde_json::Map<String, Value> = self
+            .by_type
+            .iter()
+            .map(|entry| {
+                let c = entry.value();
+                let values = [
+                    c.received.load(Ordering::Relaxed),
+                    c.delivered.load(Ordering::Relaxed),
+                    c.offline_queued.load(Ordering::Relaxed),
+                    c.failed.load(Ordering::Relaxed),
+                ];
+                for (total, v) in totals.iter_mut().zip(values) {
+                    *total += v;
+                }
+                (entry.key().clone(), counters_json(values))
+            })
+            .collect();
+        json!({
+            "total": counters_json(totals),
+            "by_type": by_type,
+            "latency": self.latency.snapshot(),
+            "events": self
+                .events
+                .iter()
+                .map(|e| (e.key().clone(), json!(e.value().load(Ordering::Relaxed))))
+                .collect::<serde_json::Map<_, _>>(),
+            "auth": {
+                "failures": self.auth.failures.load(Ordering::Relaxed),
+                "throttled": self.auth.throttled.load(Ordering::Relaxed),
+                "bans": self.auth.bans.load(Ordering::Relaxed),
+            },
+            "drops": by_reason(&self.drops),
+            "cleanups": by_reason(&self.cleanups),
+        })
+    }
+
+    fn with_type(&self, msg_type: &str, f: impl FnOnce(&TypeCounters) -> u64) {
+        if let Some(c) = self.by_type.get(msg_type) {
+            f(&c);
+            return;
+        }
+        let key = if self.by_type.len() < MAX_TRACKED_TYPES {
+            msg_type
+        } else {
+            "other"
+        };
+        f(&self.by_type.entry(key.to_string()).or_default());
+    }
+}
+
+fn counters_json([received, delivered, offline_queued, failed]: [u64; 4]) -> Value {
+    json!({
+        "received": received,
+        "delivered": delivered,
+        "offline_queued": offline_queued,
+        "failed": failed,
+    })
+}
+
+fn by_reason(counters: &[AtomicU64; 4]) -> Value {
+    DropReason::ALL
+        .iter()
+        .map(|r| {
+            (
+                r.as_str().to_string(),
+                json!(counters[r.index()].load(Ordering::Relaxed)),
+            )
+        })
+        .collect::<serde_json::Map<_, _>>()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::{im, recv_typed, TestServer};
+    use crate::ImMessage;
+
+    #[test]
+    fn test_counters_histogram_and_type_cap() {
+        let metrics = DeliveryMetrics::default();
+        metrics.record_received("message");
+        metrics.record_delivered("message", Duration::from_micros(500));
+        metrics.record_delivered("message", Duration::from_millis(30));
+        metrics.record_failed("private_message");
+        for i in 0..MAX_TRACKED_TYPES + 10 {
+            metrics.record_received(&format!("custom_{}", i));
+        }
+
+        let snap = metrics.snapshot();
+        assert_eq!(snap["by_type"]["message"]["delivered"], 2);
+        assert_eq!(snap["by_type"]["private_message"]["failed"], 1);
+        assert_eq!(
+            snap["by_type"].as_object().unwrap().len(),
+            MAX_TRACKED_TYPES + 1
+        );
+        assert_eq!(snap["total"]["received"], MAX_TRACKED_TYPES as u64 + 11);
+        let latency = &snap["latency"];
+        assert_eq!(latency["count"], 2);
+        assert_eq!(latency["buckets_ms"]["1"], 1);
+        assert_eq!(latency["buckets_ms"]["25"], 1);
+        assert_eq!(latency["buckets_ms"]["50"], 2);
+        assert_eq!(latency["buckets_ms"]["+Inf"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_ws_messages_are_counted_per_type() {
+        let ts = TestServer::new();
+        let (alice, mut alice_rx) = ts.add_client("alice");
+        let (_, mut bob_rx) = ts.add_client("bob");
+
+        let text = serde_json::json!({"text": "hi"});
+        ts.send(&alice, im("private_message", text.clone(), Some("bob")))
+            .await
+            .unwrap();
+        let _: ImMessage = recv_typed(&mut bob_rx).await;
+        let _: ImMessage = recv_typed(&mut alice_rx).await;
+        // 对方不在线，投递失败 / Recipient is offline, so delivery fails
+        ts.send(&alice, im("private_message", text, Some("carol")))
+            .await
+            .unwrap();
+
+        let snap = ts.server.metrics.snapshot();
+        let private = &snap["by_type"]["private_message"];
+        assert_eq!(private["received"], 2);
+        assert_eq!(private["delivered"], 1);
+        assert_eq!(private["failed"], 1);
+        assert_eq!(snap["latency"]["count"], 1);
+    }
+}
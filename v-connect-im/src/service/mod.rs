@@ -1,7 +1,45 @@
 // 服务模块入口
 // Service module entry
 // pub mod auth;  // 不存在 / Does not exist
+pub mod ack;
+pub mod admission;
+pub mod attachment;
+pub mod auth_guard;
+pub mod blocklist;
 pub mod delivery;
+pub mod delivery_log;
+pub mod device_sync;
+pub mod event_bus;
+pub mod fanout;
+pub mod group_delivery;
+pub mod group_ack;
+pub mod group_dedup;
 pub mod health;
+pub mod id_gen;
+pub mod local_store;
+pub mod maintenance;
+pub mod metrics;
 pub mod offline;
+pub mod offline_queue;
+pub mod persistence;
+pub mod pins;
+pub mod push;
+pub mod reaction;
+pub mod reaper;
+pub mod replication;
+pub mod resume;
+pub mod retention;
+pub mod room;
+pub mod room_guard;
+pub mod room_meta;
+pub mod room_membership;
+pub mod scheduler;
+pub mod shutdown;
+pub mod state_dump;
+pub mod storage_fallback;
+pub mod system_message;
+pub mod tenant;
+pub mod token_bucket;
+pub mod translation;
+pub mod webhook;
 // pub mod webhook;  // 已移除 / Removed
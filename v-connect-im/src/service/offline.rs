@@ -1,16 +1,326 @@
+//! 离线消息投递与流控 / Offline delivery and flow control
+//!
+//! 用户重连后从存储插件分批拉取离线消息，按 `offline.max_per_sec` 限速写入连接的有界发送队列；
+//! 队列达到高水位时暂停拉取等待客户端消费，停滞超过 `offline.stall_timeout_ms` 则停止，
+//! 剩余消息留在存储中等待下次重连。
+//! After a user reconnects, offline messages are pulled from the storage plugin in
+//! batches and written to the connection's bounded send queue at `offline.max_per_sec`;
+//! pulling pauses while the queue is above the high watermark, and stops if the client
+//! stalls for longer than `offline.stall_timeout_ms`, leaving the rest in storage for
+//! the next reconnect.
+//!
+//! 存储插件按优先级从高到低返回离线消息，补发的帧保留原消息的 `priority`。写入队列的消息随即在存储中
+//! 确认，因此以不可挤掉的方式入队：之后到达的更高优先级消息不会把它们从队列中挤出。
+//! The storage plugin returns offline messages highest priority first, and replayed frames keep
+//! the original message's `priority`. Queued messages are acknowledged in storage right away, so
+//! they are queued pinned: higher-priority messages arriving later cannot evict them.
+
+use crate::domain::message::{ImMessage, MessagePriority};
 use crate::server::VConnectIMServer;
+use crate::service::system_message::SYSTEM_MESSAGE_TYPE;
 use anyhow::Result;
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use tokio::time::{Interval, MissedTickBehavior};
+use tokio_tungstenite::tungstenite::Message;
+
+/// 默认每连接每秒补发的离线消息数 / Default offline messages per second per connection
+pub const DEFAULT_MAX_PER_SEC: u32 = 200;
+/// 默认每批拉取条数 / Default pull batch size
+pub const DEFAULT_BATCH_SIZE: usize = 100;
+/// 默认高水位（发送队列占用比例）/ Default high watermark (fraction of the send queue)
+pub const DEFAULT_HIGH_WATERMARK: f64 = 0.8;
+/// 默认停滞超时 / Default stall timeout
+pub const DEFAULT_STALL_TIMEOUT_MS: u64 = 30_000;
+
+/// 暂停期间检查队列的间隔 / Queue check interval while paused
+const PAUSE_POLL: Duration = Duration::from_millis(20);
+
+/// 离线补发限速配置（`[offline]`）/ Offline replay pacing config (`[offline]`)
+#[derive(Debug, Clone, Copy)]
+pub struct OfflinePacing {
+    /// 每秒最多补发条数，0 不限速 / Max messages per second, 0 disables pacing
+    pub max_per_sec: u32,
+    pub batch_size: usize,
+    /// 队列占用达到该比例时暂停 / Pause once the queue is this full
+    pub high_watermark: f64,
+    pub stall_timeout: Duration,
+}
+
+impl Default for OfflinePacing {
+    fn default() -> Self {
+        Self {
+            max_per_sec: DEFAULT_MAX_PER_SEC,
+            batch_size: DEFAULT_BATCH_SIZE,
+            high_watermark: DEFAULT_HIGH_WATERMARK,
+            stall_timeout: Duration::from_millis(DEFAULT_STALL_TIMEOUT_MS),
+        }
+    }
+}
+
+impl OfflinePacing {
+    /// 读取 `offline.*` / Read `offline.*`
+    pub fn from_config() -> Self {
+        let defaults = Self::default();
+        match v::get_global_config_manager() {
+            Ok(cm) => Self {
+                max_per_sec: cm.get_or("offline.max_per_sec", defaults.max_per_sec),
+                batch_size: cm.get_or("offline.batch_size", defaults.batch_size).max(1),
+                high_watermark: cm
+                    .get_or("offline.high_watermark", defaults.high_watermark)
+                    .clamp(0.01, 1.0),
+                stall_timeout: Duration::from_millis(
+                    cm.get_or("offline.stall_timeout_ms", DEFAULT_STALL_TIMEOUT_MS),
+                ),
+            },
+            Err(_) => defaults,
+        }
+    }
+}
+
+/// 单次补发的节奏状态（限速器跨批次共享）/ Pacing state for one replay (the limiter spans batches)
+pub struct OfflinePacer {
+    pacing: OfflinePacing,
+    ticker: Option<Interval>,
+}
+
+impl OfflinePacer {
+    pub fn new(pacing: OfflinePacing) -> Self {
+        let ticker = (pacing.max_per_sec > 0).then(|| {
+            let mut t = tokio::time::interval(Duration::from_secs(1) / pacing.max_per_sec);
+            t.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            t
+        });
+        Self { pacing, ticker }
+    }
+}
 
 impl VConnectIMServer {
-    pub async fn deliver_offline_for_uid(&self, _uid: &str, _client_id: &str) -> Result<usize> {
-        // 离线消息投递已迁移到插件 / Offline delivery migrated to plugin
-        // TODO: 使用插件实现 / Use plugin implementation
-        Ok(0)
+    /// 把 uid 的离线消息按限速补发给指定连接，已写入队列的消息在存储中确认
+    /// Replay the uid's offline messages to a connection with pacing; messages written
+    /// to the queue are acknowledged in storage
+    ///
+    /// # 返回值 / Returns
+    /// 补发条数；未配置存储插件时为 0 / Number replayed; 0 without a storage plugin
+    pub async fn deliver_offline_for_uid(&self, uid: &str, client_id: &str) -> Result<usize> {
+        let Some(pool) = self.plugin_connection_pool.clone() else {
+            return Ok(0);
+        };
+        let mut pacer = OfflinePacer::new(OfflinePacing::from_config());
+        let mut delivered = 0;
+        loop {
+            let batch = pool
+                .storage_pull_offline(uid, pacer.pacing.batch_size)
+                .await?;
+            if batch.is_empty() {
+                break;
+            }
+            let sent = self
+                .deliver_offline_batch(client_id, &batch, &mut pacer)
+                .await;
+            // 补发的副本重新接受确认 / Replayed copies accept acks again
+            self.ack_windows.reopen(uid, &sent);
+            if sent.is_empty() || pool.storage_ack_offline(uid, &sent).await? == 0 {
+                break;
+            }
+            delivered += sent.len();
+            // 停滞或断开时其余消息留待下次 / On stall or disconnect the rest waits for next time
+            if sent.len() < batch.len() {
+                break;
+            }
+        }
+        if delivered > 0 {
+            tracing::info!(
+                "📬 补发离线消息 / Replayed {} offline messages to {} ({})",
+                delivered,
+                uid,
+                client_id
+            );
+        }
+        Ok(delivered)
+    }
+
+    /// 按限速与队列水位写入一批离线消息，返回已写入的消息ID
+    /// Write a batch of offline messages honouring the rate and the queue watermark;
+    /// returns the IDs written
+    pub async fn deliver_offline_batch(
+        &self,
+        client_id: &str,
+        batch: &[Value],
+        pacer: &mut OfflinePacer,
+    ) -> Vec<String> {
+        let mut sent = Vec::new();
+        for m in batch {
+            let Some(message_id) = m.get("message_id").and_then(Value::as_str) else {
+                continue;
+            };
+            if !self.wait_for_queue_room(client_id, &pacer.pacing).await {
+                break;
+            }
+            if let Some(ticker) = pacer.ticker.as_mut() {
+                ticker.tick().await;
+            }
+            let priority = m
+                .get("priority")
+                .and_then(Value::as_str)
+                .map(MessagePriority::parse)
+                .unwrap_or_default();
+            // 系统消息保持 `system` 类型 / System messages keep the `system` type
+            let frame = if m.get("msg_type").and_then(Value::as_str) == Some(SYSTEM_MESSAGE_TYPE) {
+                ImMessage {
+                    msg_type: SYSTEM_MESSAGE_TYPE.to_string(),
+                    data: serde_json::json!({
+                        "message_id": message_id,
+                        "content": m.get("content"),
+                        "timestamp": m.get("timestamp"),
+                        "offline": true,
+                    }),
+                    target_uid: None,
+                    priority,
+                    reply_to: None,
+                }
+            } else {
+                ImMessage {
+                    msg_type: "forwarded_message".to_string(),
+                    data: serde_json::json!({
+                        "from": m.get("from_uid"),
+                        "content": m.get("content"),
+                        "timestamp": m.get("timestamp"),
+                        "message_id": message_id,
+                        "offline": true,
+                    }),
+                    target_uid: None,
+                    priority,
+                    reply_to: None,
+                }
+            };
+            let Ok(text) = serde_json::to_string(&frame) else {
+                continue;
+            };
+            // 入队即在存储中确认，因此补发的帧不能被挤掉 / Acked in storage once queued, so replayed frames must not be evicted
+            if self
+                .send_pinned_to_client(client_id, Message::Text(text))
+                .await
+                .is_err()
+            {
+                break;
+            }
+            sent.push(message_id.to_string());
+        }
+        sent
+    }
+
+    /// 等待发送队列降到高水位以下；连接断开或停滞超时返回 false
+    /// Wait until the send queue drops below the high watermark; false if the connection
+    /// is gone or the client stalls past the timeout
+    async fn wait_for_queue_room(&self, client_id: &str, pacing: &OfflinePacing) -> bool {
+        let started = Instant::now();
+        loop {
+            let Some((queued, capacity)) = self
+                .connections
+                .get(client_id)
+                .map(|c| (c.queued(), c.sender.max_capacity()))
+            else {
+                return false;
+            };
+            if (queued as f64) < capacity as f64 * pacing.high_watermark {
+                return true;
+            }
+            if started.elapsed() >= pacing.stall_timeout {
+                tracing::warn!(
+                    "⏸️  客户端 {} 发送队列停滞，暂停离线补发 / Send queue of {} stalled, pausing offline replay",
+                    client_id,
+                    client_id
+                );
+                return false;
+            }
+            tokio::time::sleep(PAUSE_POLL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::{im, recv_typed, TestServer};
+
+    fn offline(ids: &[&str]) -> Vec<Value> {
+        ids.iter()
+            .map(|id| {
+                serde_json::json!({
+                    "message_id": id, "from_uid": "B", "content": {"text": id}, "timestamp": 1,
+                })
+            })
+            .collect()
     }
 
-    pub async fn enforce_offline_quota_for_uid(&self, _uid: &str) -> Result<usize> {
-        // 离线消息配额管理已迁移到插件 / Offline quota migrated to plugin
-        // TODO: 使用插件实现 / Use plugin implementation
-        Ok(0)
+    #[tokio::test]
+    async fn test_replay_is_paced_and_pauses_on_full_queue() {
+        let ts = TestServer::new();
+        let (a_id, mut a_rx) = ts.add_client_with_queue("A", 4);
+
+        // 50 条/秒：5 条至少间隔 4 个 20ms / 50 per second: 5 messages span at least 4 × 20ms
+        let mut pacer = OfflinePacer::new(OfflinePacing {
+            max_per_sec: 50,
+            high_watermark: 1.0,
+            ..Default::default()
+        });
+        let started = Instant::now();
+        let drain = tokio::spawn(async move {
+            for _ in 0..5 {
+                a_rx.recv().await.unwrap();
+            }
+            a_rx
+        });
+        let sent = ts
+            .server
+            .deliver_offline_batch(&a_id, &offline(&["1", "2", "3", "4", "5"]), &mut pacer)
+            .await;
+        assert_eq!(sent.len(), 5);
+        assert!(started.elapsed() >= Duration::from_millis(80));
+        let _a_rx = drain.await.unwrap();
+
+        // 不消费时在高水位（4 × 0.5 = 2 条）停下 / Without draining, stop at the watermark (4 × 0.5 = 2)
+        let mut pacer = OfflinePacer::new(OfflinePacing {
+            max_per_sec: 0,
+            high_watermark: 0.5,
+            stall_timeout: Duration::from_millis(100),
+            ..Default::default()
+        });
+        let sent = ts
+            .server
+            .deliver_offline_batch(&a_id, &offline(&["6", "7", "8"]), &mut pacer)
+            .await;
+        assert_eq!(sent, ["6", "7"]);
+    }
+
+    #[tokio::test]
+    async fn test_replayed_frames_are_not_evicted() {
+        let ts = TestServer::new();
+        let (a_id, mut a_rx) = ts.add_client_with_queue("A", 2);
+        let mut pacer = OfflinePacer::new(OfflinePacing {
+            max_per_sec: 0,
+            high_watermark: 1.0,
+            ..Default::default()
+        });
+        let sent = ts
+            .server
+            .deliver_offline_batch(&a_id, &offline(&["1", "2"]), &mut pacer)
+            .await;
+        assert_eq!(sent, ["1", "2"]);
+
+        // 已确认的补发帧不给更高优先级让位 / Acked replayed frames do not make room for higher priorities
+        let mut urgent = im("message", serde_json::json!({"text": "urgent"}), None);
+        urgent.priority = MessagePriority::High;
+        let text = serde_json::to_string(&urgent).unwrap();
+        assert!(ts
+            .server
+            .send_message_to_client(&a_id, Message::Text(text))
+            .await
+            .is_err());
+        for id in ["1", "2"] {
+            let replayed: ImMessage = recv_typed(&mut a_rx).await;
+            assert_eq!(replayed.data["message_id"], id);
+        }
     }
 }
@@ -0,0 +1,554 @@
+//! 离线消息写入与配额裁剪 / Offline message writes and quota trimming
+//!
+//! 发送路径只把离线记录放入有界通道；后台任务批量写入存储插件，并对每批涉及的 uid 执行
+//! `offline.max_per_uid` 配额（删除最旧的多余消息）。通道容量 `offline.quota_queue_capacity`
+//! 是积压上限：通道满时发送路径退回同步写入并裁剪，积压不会在裁剪前无限增长。配额可按租户覆盖。
+//! The send path only puts offline records on a bounded channel; a background task writes
+//! them to the storage plugin in batches and enforces `offline.max_per_uid` (dropping the
+//! oldest excess messages) for every uid touched by the batch. The channel capacity,
+//! `offline.quota_queue_capacity`, caps the backlog: when it is full the send path falls
+//! back to writing and trimming inline, so the backlog cannot grow before trimming runs.
+//! The quota may be overridden per tenant (see [`crate::service::tenant`]).
+
+use crate::domain::message::MessagePriority;
+use crate::plugins::runtime::PluginConnectionPool;
+use crate::server::VConnectIMServer;
+use crate::service::tenant::Tenants;
+use crate::storage::OfflineRecord;
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::{Arc, OnceLock};
+use tokio::sync::mpsc;
+
+/// 默认每个 uid 最多保留的离线消息数 / Default max offline messages kept per uid
+pub const DEFAULT_MAX_PER_UID: usize = 10_000;
+/// 默认待写入队列容量 / Default pending write queue capacity
+pub const DEFAULT_QUEUE_CAPACITY: usize = 10_000;
+/// 后台任务每批最多处理的记录数 / Max records the background task handles per batch
+const WRITE_BATCH: usize = 256;
+
+/// 离线消息存储 / Offline message storage
+#[async_trait]
+pub trait OfflineStore: Send + Sync {
+    async fn save(&self, record: &OfflineRecord) -> Result<bool>;
+
+    /// 删除 uid 超出 `max` 的最旧消息，返回删除条数
+    /// Delete the oldest messages of a uid beyond `max`; returns how many were deleted
+    async fn enforce_quota(&self, uid: &str, max: usize) -> Result<usize>;
+
+    async fn count(&self, uid: &str) -> Result<usize>;
+
+    /// 删除 uid 的全部离线消息，返回删除条数 / Delete all offline messages of a uid; returns how many were deleted
+    async fn clear(&self, uid: &str) -> Result<usize>;
+}
+
+#[async_trait]
+impl OfflineStore for PluginConnectionPool {
+    async fn save(&self, record: &OfflineRecord) -> Result<bool> {
+        self.storage_save_offline(
+            &record.message_id,
+            record.from_uid.as_deref(),
+            &record.to_uid,
+            &record.content,
+            record.timestamp,
+            &record.msg_type,
+            record.room_id.as_deref(),
+            record.priority,
+        )
+        .await
+    }
+
+    async fn enforce_quota(&self, uid: &str, max: usize) -> Result<usize> {
+        let count = self.storage_count_offline(uid).await?;
+        if count <= max {
+            return Ok(0);
+        }
+        // 拉取按优先级从高到低、同级从旧到新；先删最低优先级中最旧的
+        // Pull returns higher priorities first, oldest first within one; the oldest of the lowest
+        // priority go first
+        let mut records = self.storage_pull_offline(uid, count).await?;
+        records.sort_by_key(|m| {
+            m.get("priority")
+                .and_then(|v| v.as_str())
+                .map(MessagePriority::parse)
+                .unwrap_or_default()
+        });
+        let oldest: Vec<String> = records
+            .iter()
+            .take(count - max)
+            .filter_map(|m| m.get("message_id").and_then(|v| v.as_str()))
+            .map(|id| id.to_string())
+            .collect();
+        if oldest.is_empty() {
+            return Ok(0);
+        }
+        self.storage_delete_offline(uid, &oldest).await
+    }
+
+    async fn count(&self, uid: &str) -> Result<usize> {
+        self.storage_count_offline(uid).await
+    }
+
+    async fn clear(&self, uid: &str) -> Result<usize> {
+        // 空 ID 列表表示删除全部 / An empty id list deletes everything
+        self.storage_delete_offline(uid, &[]).await
+    }
+}
+
+/// 离线配额配置（`[offline]`）/ Offline quota config (`[offline]`)
+#[derive(Debug, Clone, Copy)]
+pub struct OfflineQuota {
+    /// 每个 uid 最多保留条数，0 不限制 / Max messages kept per uid, 0 disables the quota
+    pub max_per_uid: usize,
+    pub queue_capacity: usize,
+}
+
+impl Default for OfflineQuota {
+    fn default() -> Self {
+        Self {
+            max_per_uid: DEFAULT_MAX_PER_UID,
+            queue_capacity: DEFAULT_QUEUE_CAPACITY,
+        }
+    }
+}
+
+impl OfflineQuota {
+    /// 读取 `offline.max_per_uid` 与 `offline.quota_queue_capacity`
+    /// Read `offline.max_per_uid` and `offline.quota_queue_capacity`
+    pub fn from_config() -> Self {
+        let defaults = Self::default();
+        match v::get_global_config_manager() {
+            Ok(cm) => Self {
+                max_per_uid: cm.get_or("offline.max_per_uid", defaults.max_per_uid),
+                queue_capacity: cm
+                    .get_or("offline.quota_queue_capacity", defaults.queue_capacity)
+                    .max(1),
+            },
+            Err(_) => defaults,
+        }
+    }
+}
+
+/// 离线写入队列 / Offline write queue
+pub struct OfflineQueue {
+    quota: OfflineQuota,
+    /// 测试或嵌入时替换存储插件 / Replaces the storage plugin in tests or embedders
+    store: Option<Arc<dyn OfflineStore>>,
+    /// 首次入队时启动后台任务 / The background task starts on the first enqueue
+    sender: OnceLock<mpsc::Sender<OfflineRecord>>,
+}
+
+impl OfflineQueue {
+    pub fn new(quota: OfflineQuota, store: Option<Arc<dyn OfflineStore>>) -> Self {
+        Self {
+            quota,
+            store,
+            sender: OnceLock::new(),
+        }
+    }
+
+    fn sender(
+        &self,
+        store: &Arc<dyn OfflineStore>,
+        tenants: &Arc<Tenants>,
+    ) -> &mpsc::Sender<OfflineRecord> {
+        self.sender.get_or_init(|| {
+            let (tx, rx) = mpsc::channel(self.quota.queue_capacity);
+            tokio::spawn(run_writer(
+                store.clone(),
+                rx,
+                self.quota.max_per_uid,
+                tenants.clone(),
+            ));
+            tx
+        })
+    }
+}
+
+/// uid 的离线配额：租户覆盖优先于全局值 / Offline quota of a uid: the tenant override wins over the global value
+fn max_for(tenants: &Tenants, uid: &str, max_per_uid: usize) -> usize {
+    tenants
+        .overrides_for(uid)
+        .offline_max_per_uid
+        .unwrap_or(max_per_uid)
+}
+
+/// 后台写入：每批先写入，再对涉及的 uid 各裁剪一次
+/// Background writer: save each batch, then trim every uid it touched once
+async fn run_writer(
+    store: Arc<dyn OfflineStore>,
+    mut rx: mpsc::Receiver<OfflineRecord>,
+    max_per_uid: usize,
+    tenants: Arc<Tenants>,
+) {
+    let mut batch = Vec::with_capacity(WRITE_BATCH);
+    while rx.recv_many(&mut batch, WRITE_BATCH).await > 0 {
+        let mut touched: Vec<String> = Vec::new();
+        for record in batch.drain(..) {
+            save_logged(store.as_ref(), &record).await;
+            if !touched.contains(&record.to_uid) {
+                touched.push(record.to_uid);
+            }
+        }
+        for uid in touched {
+            enforce_logged(store.as_ref(), &uid, max_for(&tenants, &uid, max_per_uid)).await;
+        }
+    }
+}
+
+async fn save_logged(store: &dyn OfflineStore, record: &OfflineRecord) {
+    match store.save(record).await {
+        Ok(true) => {
+            tracing::debug!(
+                "💾 离线消息已保存 / Offline message saved: {}",
+                record.message_id
+            );
+        }
+        Ok(false) => {
+            tracing::warn!("⚠️  离线消息保存失败 / Offline message save failed");
+        }
+        Err(e) => {
+            tracing::error!("❌ 离线消息保存错误 / Offline message save error: {}", e);
+        }
+    }
+}
+
+async fn enforce_logged(store: &dyn OfflineStore, uid: &str, max_per_uid: usize) {
+    if max_per_uid == 0 {
+        return;
+    }
+    match store.enforce_quota(uid, max_per_uid).await {
+        Ok(0) => {}
+        Ok(n) => {
+            tracing::debug!(
+                "✂️  {} 超出离线配额，删除最旧 {} 条 / Trimmed {} oldest offline messages of {}",
+                uid,
+                n,
+                n,
+                uid
+            );
+        }
+        Err(e) => {
+            tracing::warn!(
+                "⚠️  离线配额裁剪失败 / Offline quota trim failed for {}: {}",
+                uid,
+                e
+            );
+        }
+    }
+}
+
+impl VConnectIMServer {
+    fn offline_store(&self) -> Option<Arc<dyn OfflineStore>> {
+        self.offline_queue.store.clone().or_else(|| {
+            self.plugin_connection_pool
+                .clone()
+                .map(|pool| pool as Arc<dyn OfflineStore>)
+        })
+    }
+
+    /// 写入离线消息（异步，配额由后台裁剪）；未配置存储时丢弃
+    /// Queue an offline message (written and trimmed in the background); dropped without storage
+    ///
+    /// 队列满时在当前任务内同步写入并裁剪，以此约束积压；不持久化的消息类型不入离线队列。
+    /// When the queue is full the record is written and trimmed inline, which bounds the
+    /// backlog; message types that are not persisted are never queued offline.
+    pub async fn queue_offline(&self, record: OfflineRecord) {
+        if !self.persistence.policy(&record.msg_type).persist {
+            return;
+        }
+        let Some(store) = self.offline_store() else {
+            return;
+        };
+        self.metrics.record_offline_queued(&record.msg_type);
+        let queue = &self.offline_queue;
+        match queue.sender(&store, &self.tenants).try_send(record) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(record)) => {
+                tracing::warn!(
+                    "⚠️  离线写入队列已满，同步写入 / Offline write queue full, writing inline for {}",
+                    record.to_uid
+                );
+                save_logged(store.as_ref(), &record).await;
+                let max = max_for(&self.tenants, &record.to_uid, queue.quota.max_per_uid);
+                enforce_logged(store.as_ref(), &record.to_uid, max).await;
+            }
+            Err(mpsc::error::TrySendError::Closed(record)) => {
+                save_logged(store.as_ref(), &record).await;
+            }
+        }
+    }
+
+    /// uid 当前的离线消息数；未配置存储时为 0 / Offline message count of a uid; 0 without storage
+    pub async fn offline_count(&self, uid: &str) -> Result<usize> {
+        match self.offline_store() {
+            Some(store) => store.count(uid).await,
+            None => Ok(0),
+        }
+    }
+
+    /// 清空 uid 的离线消息（仍在写入队列中的记录稍后照常写入）
+    /// Clear a uid's offline messages (records still on the write queue are written later as usual)
+    pub async fn clear_offline(&self, uid: &str) -> Result<usize> {
+        match self.offline_store() {
+            Some(store) => store.clear(uid).await,
+            None => Ok(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::message::ImMessage;
+    use crate::testkit::{im, recv_typed, TestServer};
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant};
+
+    /// 每次操作都有固定延迟的内存存储；设置 `gate` 时写入等到闸门打开
+    /// In-memory store with a fixed delay per operation; with a `gate` set, writes wait until it opens
+    #[derive(Default)]
+    struct SlowStore {
+        delay: Duration,
+        gate: Option<tokio::sync::watch::Receiver<bool>>,
+        records: Mutex<Vec<OfflineRecord>>,
+    }
+
+    impl SlowStore {
+        fn count(&self, uid: &str) -> usize {
+            let records = self.records.lock().unwrap();
+            records.iter().filter(|r| r.to_uid == uid).count()
+        }
+
+        async fn wait_for(&self, total: usize) {
+            let started = Instant::now();
+            while self.records.lock().unwrap().len() < total {
+                assert!(started.elapsed() < Duration::from_secs(10));
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        }
+    }
+
+    #[async_trait]
+    impl OfflineStore for SlowStore {
+        async fn save(&self, record: &OfflineRecord) -> Result<bool> {
+            if let Some(gate) = &self.gate {
+                let _ = gate.clone().wait_for(|open| *open).await;
+            }
+            tokio::time::sleep(self.delay).await;
+            self.records.lock().unwrap().push(record.clone());
+            Ok(true)
+        }
+
+        async fn enforce_quota(&self, uid: &str, max: usize) -> Result<usize> {
+            tokio::time::sleep(self.delay).await;
+            let mut records = self.records.lock().unwrap();
+            let mut excess = records
+                .iter()
+                .filter(|r| r.to_uid == uid)
+                .count()
+                .saturating_sub(max);
+            let before = records.len();
+            records.retain(|r| {
+                if excess > 0 && r.to_uid == uid {
+                    excess -= 1;
+                    return false;
+                }
+                true
+            });
+            Ok(before - records.len())
+        }
+
+        async fn count(&self, uid: &str) -> Result<usize> {
+            Ok(SlowStore::count(self, uid))
+        }
+
+        async fn clear(&self, uid: &str) -> Result<usize> {
+            let mut records = self.records.lock().unwrap();
+            let before = records.len();
+            records.retain(|r| r.to_uid != uid);
+            Ok(before - records.len())
+        }
+    }
+
+    fn record(to_uid: &str, message_id: &str) -> OfflineRecord {
+        OfflineRecord {
+            message_id: message_id.to_string(),
+            from_uid: None,
+            to_uid: to_uid.to_string(),
+            room_id: None,
+            content: serde_json::json!({}),
+            timestamp: 0,
+            msg_type: "group_message".to_string(),
+            priority: Default::default(),
+        }
+    }
+
+    fn server_with(store: Arc<SlowStore>, quota: OfflineQuota) -> TestServer {
+        TestServer::build(|server| server.with_offline_store(quota, store))
+    }
+
+    #[tokio::test]
+    async fn test_group_send_does_not_wait_for_offline_writes() {
+        const MEMBERS: usize = 200;
+        let (open, gate) = tokio::sync::watch::channel(false);
+        let store = Arc::new(SlowStore {
+            gate: Some(gate),
+            ..Default::default()
+        });
+        let ts = server_with(store.clone(), OfflineQuota::default());
+        let (a, mut rx) = ts.add_client("alice");
+        ts.server.http_join_room("big", "alice").await;
+        for i in 0..MEMBERS {
+            ts.server.http_join_room("big", &format!("u{}", i)).await;
+        }
+
+        // 闸门关闭时存储写不进任何记录，发送仍须完成
+        // With the gate closed the store cannot write anything, yet the send must complete
+        ts.send(
+            &a,
+            im(
+                "group_message",
+                serde_json::json!({"room_id": "big", "text": "hi"}),
+                None,
+            ),
+        )
+        .await
+        .unwrap();
+        // 发送者自己也是成员，先收到转发 / The sender is a member too and gets the forward first
+        let own: ImMessage = recv_typed(&mut rx).await;
+        assert_eq!(own.msg_type, "group_message");
+        let ack: ImMessage = recv_typed(&mut rx).await;
+        assert_eq!(ack.msg_type, "group_message_sent");
+        assert_eq!(store.records.lock().unwrap().len(), 0);
+
+        // 打开闸门后后台任务写完全部离线成员 / Once opened, the background task writes every offline member
+        open.send(true).unwrap();
+        store.wait_for(MEMBERS).await;
+        assert_eq!(store.count("u0"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_quota_is_enforced_and_full_queue_falls_back_inline() {
+        let store = Arc::new(SlowStore {
+            delay: Duration::from_millis(1),
+            ..Default::default()
+        });
+        let ts = server_with(
+            store.clone(),
+            OfflineQuota {
+                max_per_uid: 3,
+                queue_capacity: 2,
+            },
+        );
+        // 容量为 2 的队列放不下 20 条，多出的同步写入 / A queue of 2 cannot hold 20 records; the rest are written inline
+        for i in 0..20 {
+            ts.server
+                .queue_offline(record("bob", &format!("m{}", i)))
+                .await;
+        }
+        assert!(store.count("bob") > 0);
+
+        let started = Instant::now();
+        while store.count("bob") > 3 || started.elapsed() < Duration::from_millis(50) {
+            assert!(started.elapsed() < Duration::from_secs(10));
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert_eq!(store.count("bob"), 3);
+    }
+
+    #[tokio::test]
+    async fn test_offline_status_and_clear_own_queue() {
+        let store = Arc::new(SlowStore::default());
+        let ts = server_with(store.clone(), OfflineQuota::default());
+        let (alice, mut rx) = ts.add_client("alice");
+        for i in 0..3 {
+            ts.server
+                .queue_offline(record("alice", &format!("a{}", i)))
+                .await;
+        }
+        ts.server.queue_offline(record("bob", "b0")).await;
+        store.wait_for(4).await;
+
+        ts.send(&alice, im("offline_status", serde_json::json!({}), None))
+            .await
+            .unwrap();
+        let status: ImMessage = recv_typed(&mut rx).await;
+        assert_eq!(status.msg_type, "offline_status");
+        assert_eq!(status.data["count"], 3);
+
+        // 不能清空他人的队列 / Another uid's queue cannot be cleared
+        ts.send(
+            &alice,
+            im("offline_clear", serde_json::json!({"uid": "bob"}), None),
+        )
+        .await
+        .unwrap();
+        let denied: ImMessage = recv_typed(&mut rx).await;
+        assert_eq!(denied.data["code"], "FORBIDDEN");
+        assert_eq!(store.count("bob"), 1);
+
+        ts.send(&alice, im("offline_clear", serde_json::json!({}), None))
+            .await
+            .unwrap();
+        let cleared: ImMessage = recv_typed(&mut rx).await;
+        assert_eq!(cleared.msg_type, "offline_clear");
+        assert_eq!(cleared.data["cleared"], 3);
+
+        ts.send(&alice, im("offline_status", serde_json::json!({}), None))
+            .await
+            .unwrap();
+        let status: ImMessage = recv_typed(&mut rx).await;
+        assert_eq!(status.data["count"], 0);
+        assert_eq!(store.count("bob"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_tenant_overrides_the_offline_quota() {
+        use crate::service::tenant::{TenantOverrides, Tenants};
+        let store = Arc::new(SlowStore::default());
+        let tenants = Tenants::default().with_override(
+            "free",
+            TenantOverrides {
+                offline_max_per_uid: Some(2),
+                ..Default::default()
+            },
+        );
+        let ts = TestServer::build(|server| {
+            server
+                .with_offline_store(
+                    OfflineQuota {
+                        max_per_uid: 5,
+                        ..Default::default()
+                    },
+                    store.clone(),
+                )
+                .with_tenants(tenants)
+        });
+        for i in 0..8 {
+            for uid in ["free:bob", "bob"] {
+                ts.server
+                    .queue_offline(record(uid, &format!("m{}", i)))
+                    .await;
+            }
+        }
+        // 最旧的被裁掉 / The oldest are trimmed
+        let ids = |uid: &str| -> Vec<String> {
+            let records = store.records.lock().unwrap();
+            records
+                .iter()
+                .filter(|r| r.to_uid == uid)
+                .map(|r| r.message_id.clone())
+                .collect()
+        };
+        let started = Instant::now();
+        while ids("free:bob") != ["m6", "m7"] || ids("bob") != ["m3", "m4", "m5", "m6", "m7"] {
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "{:?}",
+                ids("free:bob")
+            );
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! 按消息类型的持久化策略 / Per message type persistence policy
+//!
+//! `persistence.<msg_type>` 配置是否写入存储插件（`persist`）与是否追加到 Raft 日志（`replicate`）。
+//! 会话消息默认两者都开启；`typing`、`presence` 等瞬时消息默认都关闭，未列出的类型按会话消息处理。
+//! `flush` 默认关闭，开启后保存成功即要求存储插件落盘，再确认发送方。
+//! `persistence.<msg_type>` controls whether a message type is saved to the storage plugin
+//! (`persist`) and appended to the Raft log (`replicate`). Conversation messages default to
+//! both on; ephemeral types such as `typing` and `presence` default to both off, and types
+//! not listed are treated like conversation messages. `flush` is off by default; when on,
+//! the storage plugin is asked to flush after each successful save, before the sender is acked.
+
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// 默认不持久化、不复制的瞬时消息类型 / Ephemeral message types neither persisted nor replicated by default
+pub const EPHEMERAL_TYPES: &[&str] = &["typing", "presence"];
+
+/// 单个消息类型的策略 / Policy for one message type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct PersistencePolicy {
+    #[serde(default = "enabled")]
+    pub persist: bool,
+    #[serde(default = "enabled")]
+    pub replicate: bool,
+    /// 保存后立即落盘（牺牲吞吐换取持久性）/ Flush right after saving (durability over throughput)
+    #[serde(default)]
+    pub flush: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl PersistencePolicy {
+    pub const DURABLE: Self = Self {
+        persist: true,
+        replicate: true,
+        flush: false,
+    };
+    pub const EPHEMERAL: Self = Self {
+        persist: false,
+        replicate: false,
+        flush: false,
+    };
+}
+
+/// 所有消息类型的策略 / Policies for all message types
+#[derive(Debug, Clone)]
+pub struct PersistencePolicies {
+    by_type: HashMap<String, PersistencePolicy>,
+}
+
+impl Default for PersistencePolicies {
+    fn default() -> Self {
+        Self {
+            by_type: EPHEMERAL_TYPES
+                .iter()
+                .map(|t| (t.to_string(), PersistencePolicy::EPHEMERAL))
+                .collect(),
+        }
+    }
+}
+
+impl PersistencePolicies {
+    /// 内置默认值叠加 `[persistence]` 配置 / Built-in defaults overlaid with `[persistence]`
+    pub fn from_config() -> Self {
+        let overrides = v::get_global_config_manager()
+            .ok()
+            .and_then(|cm| {
+                cm.get::<HashMap<String, PersistencePolicy>>("persistence")
+                    .ok()
+            })
+            .unwrap_or_default();
+        Self::default().with_overrides(overrides)
+    }
+
+    pub fn with_overrides(mut self, overrides: HashMap<String, PersistencePolicy>) -> Self {
+        self.by_type.extend(overrides);
+        self
+    }
+
+    pub fn policy(&self, msg_type: &str) -> PersistencePolicy {
+        self.by_type
+            .get(msg_type)
+            .copied()
+            .unwrap_or(PersistencePolicy::DURABLE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::message::HttpSendMessageRequest;
+    use crate::testkit::TestServer;
+
+    #[test]
+    fn test_defaults_and_overrides() {
+        let policies = PersistencePolicies::default();
+        for t in ["message", "private_message", "group_message", "custom"] {
+            assert_eq!(policies.policy(t), PersistencePolicy::DURABLE);
+        }
+        assert_eq!(policies.policy("typing"), PersistencePolicy::EPHEMERAL);
+
+        let overrides: HashMap<String, PersistencePolicy> =
+            serde_json::from_value(serde_json::json!({
+                "typing": {"replicate": true},
+                "group_message": {"replicate": false},
+            }))
+            .unwrap();
+        let policies = policies.with_overrides(overrides);
+        // 未写出的字段默认开启 / Omitted fields default to on
+        assert_eq!(policies.policy("typing"), PersistencePolicy::DURABLE);
+        assert_eq!(
+            policies.policy("group_message"),
+            PersistencePolicy {
+                persist: true,
+                replicate: false,
+                flush: false,
+            }
+        );
+        assert_eq!(policies.policy("presence"), PersistencePolicy::EPHEMERAL);
+    }
+
+    #[tokio::test]
+    async fn test_ephemeral_messages_skip_the_raft_log() {
+        let ts = TestServer::new();
+        let (_, _rx) = ts.add_client("bob");
+        let node_id = ts.server.node_id.clone();
+        let send = |message_type: &str| HttpSendMessageRequest {
+            from_uid: "alice".into(),
+            to_uid: "bob".into(),
+            content: serde_json::json!({"text": "..."}),
+            message_type: Some(message_type.to_string()),
+        };
+
+        ts.server.http_send_message(send("typing")).await;
+        assert_eq!(ts.server.raft.commit_count(&node_id), 0);
+        ts.server.http_send_message(send("message")).await;
+        assert_eq!(ts.server.raft.commit_count(&node_id), 1);
+    }
+}
@@ -0,0 +1,265 @@
+//! 房间置顶消息 / Pinned room messages
+//!
+//! 房间管理员发送 `pin_message` / `unpin_message`（`{room_id, message_id}`）；置顶写入存储插件
+//! （`storage.pin.*`），每个房间最多 `rooms.max_pins` 条，变更后向房间成员推送 `pins_updated`
+//! （`{room_id, pins}`）。管理员来自 `[rooms.admins]`：键为房间ID，`"*"` 对全部房间生效。
+//! Room admins send `pin_message` / `unpin_message` (`{room_id, message_id}`). Pins are written
+//! through the storage plugin (`storage.pin.*`), at most `rooms.max_pins` per room, and every
+//! change pushes `pins_updated` (`{room_id, pins}`) to the room's members. Admins come from
+//! `[rooms.admins]`, keyed by room id, with `"*"` applying to every room.
+
+use crate::domain::message::{ErrorCode, ImMessage};
+use crate::server::VConnectIMServer;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use tokio_tungstenite::tungstenite::Message;
+
+/// 置顶变更事件 / Pin change event
+pub const PINS_UPDATED: &str = "pins_updated";
+
+/// 默认每个房间的置顶上限 / Default max pins per room
+pub const DEFAULT_MAX_PINS: usize = 10;
+
+/// 对全部房间生效的管理员键 / Admin key applying to every room
+pub const ALL_ROOMS: &str = "*";
+
+/// 置顶策略 / Pin policy
+#[derive(Debug, Clone)]
+pub struct PinPolicy {
+    /// 每个房间的置顶上限，0 为不限 / Max pins per room, 0 for unlimited
+    pub max_pins: usize,
+    /// 房间ID（或 `*`）到管理员 uid / Room id (or `*`) to admin uids
+    pub admins: HashMap<String, HashSet<String>>,
+}
+
+impl Default for PinPolicy {
+    fn default() -> Self {
+        Self {
+            max_pins: DEFAULT_MAX_PINS,
+            admins: HashMap::new(),
+        }
+    }
+}
+
+impl PinPolicy {
+    /// 读取 `rooms.max_pins` 与 `[rooms.admins]` / Read `rooms.max_pins` and `[rooms.admins]`
+    pub fn from_config() -> Self {
+        let defaults = Self::default();
+        match v::get_global_config_manager() {
+            Ok(cm) => Self {
+                max_pins: cm.get_or("rooms.max_pins", defaults.max_pins),
+                admins: cm.get_or("rooms.admins", defaults.admins),
+            },
+            Err(_) => defaults,
+        }
+    }
+
+    /// `uid` 是否为房间管理员 / Whether `uid` administers the room
+    pub fn is_admin(&self, room_id: &str, uid: &str) -> bool {
+        [room_id, ALL_ROOMS]
+            .iter()
+            .any(|key| self.admins.get(*key).is_some_and(|set| set.contains(uid)))
+    }
+}
+
+impl VConnectIMServer {
+    /// 处理 `pin_message`（`pin`）或 `unpin_message`，失败时向客户端回 `error`
+    /// Handle `pin_message` (`pin`) or `unpin_message`, answering the client with an `error` on failure
+    pub async fn handle_pin(&self, client_id: &str, data: &Value, pin: bool) -> anyhow::Result<()> {
+        if let Err(err) = self.apply_pin(client_id, data, pin).await {
+            let txt = serde_json::to_string(&err)?;
+            self.send_message_to_client(client_id, Message::Text(txt))
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn apply_pin(&self, client_id: &str, data: &Value, pin: bool) -> Result<(), ImMessage> {
+        let uid = self
+            .connections
+            .get(client_id)
+            .and_then(|c| c.uid.clone())
+            .ok_or_else(|| ImMessage::error(ErrorCode::Unauthenticated, "pin requires auth uid"))?;
+        let field = |key: &str| data.get(key).and_then(Value::as_str).unwrap_or_default();
+        let (room_id, message_id) = (field("room_id"), field("message_id"));
+        if room_id.is_empty() {
+            return Err(ImMessage::error(
+                ErrorCode::MissingRoom,
+                "pin requires room_id",
+            ));
+        }
+        if !self.pin_policy.is_admin(room_id, &uid) {
+            return Err(ImMessage::error(
+                ErrorCode::Forbidden,
+                "only room admins may pin messages",
+            ));
+        }
+        let storage_error = |e: String| {
+            tracing::warn!("pin in {} failed: {}", room_id, e);
+            ImMessage::error(ErrorCode::StorageError, "pin storage unavailable")
+        };
+        let pool = self
+            .plugin_connection_pool
+            .as_ref()
+            .ok_or_else(|| storage_error("no storage".into()))?;
+
+        // 只能置顶本房间的消息 / Only messages of this room can be pinned
+        if pin {
+            let in_room = match pool.storage_get_message(message_id).await {
+                Ok(Some(message)) => message
+                    .and_then(|m| m.get("to_uid").and_then(Value::as_str).map(str::to_string))
+                    .is_some_and(|to| to == room_id),
+                Ok(None) => return Err(storage_error("no storage".into())),
+                Err(e) => return Err(storage_error(e.to_string())),
+            };
+            if !in_room {
+                return Err(ImMessage::error(
+                    ErrorCode::NotFound,
+                    "message not found in room",
+                ));
+            }
+        }
+
+        let (_, full) = pool
+            .storage_set_pin(room_id, message_id, &uid, pin, self.pin_policy.max_pins)
+            .await
+            .map_err(|e| storage_error(e.to_string()))?
+            .ok_or_else(|| storage_error("no storage".into()))?;
+        if full {
+            return Err(ImMessage::error(
+                ErrorCode::PinLimitExceeded,
+                format!(
+                    "room already has {} pinned messages",
+                    self.pin_policy.max_pins
+                ),
+            ));
+        }
+        let pins = pool
+            .storage_list_pins(room_id)
+            .await
+            .map_err(|e| storage_error(e.to_string()))?
+            .unwrap_or_default();
+        let update = ImMessage {
+            msg_type: PINS_UPDATED.to_string(),
+            data: json!({"room_id": room_id, "pins": pins}),
+            target_uid: None,
+            priority: Default::default(),
+            reply_to: None,
+        };
+        let txt = serde_json::to_string(&update).map_err(|e| storage_error(e.to_string()))?;
+        self.fan_out_to_room(room_id, &txt, false).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::runtime::{PluginConnectionPool, PluginRuntimeManager};
+    use crate::storage::builtin::BuiltinStorage;
+    use crate::testkit::{im, recv_typed, TestServer};
+    use crate::ws::send_queue::QueueReceiver;
+    use std::sync::Arc;
+
+    async fn next_pins(rx: &mut QueueReceiver) -> Vec<String> {
+        loop {
+            let msg: ImMessage = recv_typed(rx).await;
+            if msg.msg_type == PINS_UPDATED {
+                return msg.data["pins"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|p| p["message_id"].as_str().unwrap().to_string())
+                    .collect();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_admin_pins_up_to_the_cap() {
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = Arc::new(PluginConnectionPool::new(manager));
+        pool.enable_builtin_storage(Arc::new(BuiltinStorage::open_temporary().unwrap()));
+        let policy = PinPolicy {
+            max_pins: 2,
+            admins: HashMap::from([("r1".to_string(), HashSet::from(["alice".to_string()]))]),
+        };
+        let ts = TestServer::build(|server| {
+            server
+                .with_plugin_connection_pool(pool.clone())
+                .with_pin_policy(policy)
+        });
+        let (alice, mut alice_rx) = ts.add_client("alice");
+        let (bob, mut bob_rx) = ts.add_client("bob");
+        for uid in ["alice", "bob"] {
+            ts.server.http_join_room("r1", uid).await;
+        }
+        let mut ids = Vec::new();
+        for text in ["a", "b", "c"] {
+            let res = ts
+                .server
+                .http_group_send_message("r1".into(), "alice".into(), json!({"text": text}), None)
+                .await;
+            ids.push(res.message_id.unwrap());
+        }
+        let pin = |kind: &str, message_id: &str| {
+            im(
+                kind,
+                json!({"room_id": "r1", "message_id": message_id}),
+                None,
+            )
+        };
+
+        ts.send(&alice, pin("pin_message", &ids[0])).await.unwrap();
+        assert_eq!(next_pins(&mut bob_rx).await, [ids[0].clone()]);
+        ts.send(&alice, pin("pin_message", &ids[1])).await.unwrap();
+        assert_eq!(
+            next_pins(&mut bob_rx).await,
+            [ids[0].clone(), ids[1].clone()]
+        );
+
+        // 达到上限 / The cap is reached
+        ts.send(&alice, pin("pin_message", &ids[2])).await.unwrap();
+        let mut code = Value::Null;
+        while code.is_null() {
+            let msg: ImMessage = recv_typed(&mut alice_rx).await;
+            if msg.msg_type == "error" {
+                code = msg.data["code"].clone();
+            }
+        }
+        assert_eq!(code, json!(ErrorCode::PinLimitExceeded));
+
+        // 非管理员不能置顶 / Non-admins cannot pin
+        ts.send(&bob, pin("unpin_message", &ids[0])).await.unwrap();
+        let err: ImMessage = recv_typed(&mut bob_rx).await;
+        assert_eq!(err.data["code"], json!(ErrorCode::Forbidden));
+
+        ts.send(&alice, pin("unpin_message", &ids[0]))
+            .await
+            .unwrap();
+        assert_eq!(next_pins(&mut bob_rx).await, [ids[1].clone()]);
+        ts.send(&alice, pin("pin_message", &ids[2])).await.unwrap();
+        assert_eq!(
+            next_pins(&mut bob_rx).await,
+            [ids[1].clone(), ids[2].clone()]
+        );
+
+        let pins = pool.storage_list_pins("r1").await.unwrap().unwrap();
+        assert_eq!(pins.len(), 2);
+        assert_eq!(pins[0]["pinned_by"], "alice");
+    }
+
+    #[test]
+    fn test_wildcard_admins_cover_every_room() {
+        let policy = PinPolicy {
+            admins: HashMap::from([
+                (ALL_ROOMS.to_string(), HashSet::from(["ops".to_string()])),
+                ("r1".to_string(), HashSet::from(["alice".to_string()])),
+            ]),
+            ..Default::default()
+        };
+        assert!(policy.is_admin("r1", "alice"));
+        assert!(!policy.is_admin("r2", "alice"));
+        assert!(policy.is_admin("r2", "ops"));
+    }
+}
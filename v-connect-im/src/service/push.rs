@@ -0,0 +1,153 @@
+//! 服务端主动推送 / Server-initiated push
+//!
+//! 向 uid 或房间成员直接下发一条消息，按目录把客户端路由到所在节点（含跨节点）。
+//! 推送不经过上行钩子，但每个接收端仍会执行下行钩子。
+//! Push one message straight to a uid or to a room's members, routing each client to its
+//! node through the directory (including other nodes). Pushes skip the incoming hooks, but
+//! outgoing hooks still run for every recipient.
+
+use crate::domain::message::ImMessage;
+use crate::server::VConnectIMServer;
+use anyhow::Result;
+use tokio_tungstenite::tungstenite::Message;
+
+impl VConnectIMServer {
+    /// 向 uid 的所有在线客户端推送，本节点无客户端时查找其他节点
+    /// Push to every online client of a uid, looking on other nodes when none is local
+    ///
+    /// # 返回值 / Returns
+    /// 成功写入的客户端数 / Number of clients the message was queued for
+    pub async fn push_to_uid(&self, uid: &str, message: &ImMessage) -> Result<usize> {
+        let text = serde_json::to_string(message)?;
+        let local: Vec<String> = self
+            .uid_clients
+            .get(uid)
+            .map(|set| set.iter().map(|c| c.clone()).collect())
+            .unwrap_or_default();
+        if !local.is_empty() {
+            let mut delivered = 0;
+            for cid in &local {
+                if self.route_to_client(cid, &text).await.is_ok() {
+                    delivered += 1;
+                }
+            }
+            return Ok(delivered);
+        }
+
+        for node in self.directory.list_nodes() {
+            if node.node_id == self.node_id {
+                continue;
+            }
+            let Some(remote) = self.directory.get_server(&node.node_id) else {
+                continue;
+            };
+            let clients: Vec<String> = remote
+                .uid_clients
+                .get(uid)
+                .map(|set| set.iter().map(|c| c.clone()).collect())
+                .unwrap_or_default();
+            let mut delivered = 0;
+            for cid in &clients {
+                if remote
+                    .send_message_to_client(cid, Message::Text(text.clone()))
+                    .await
+                    .is_ok()
+                {
+                    delivered += 1;
+                }
+            }
+            if delivered > 0 {
+                return Ok(delivered);
+            }
+        }
+        Ok(0)
+    }
+
+    /// 向房间所有成员推送 / Push to every member of a room
+    ///
+    /// # 返回值 / Returns
+    /// 成功写入的客户端数；房间不存在时为 0 / Number of clients queued; 0 for an unknown room
+    pub async fn push_to_room(&self, room_id: &str, message: &ImMessage) -> Result<usize> {
+        let members: Vec<String> = self
+            .rooms
+            .get(room_id)
+            .map(|set| set.iter().map(|u| u.clone()).collect())
+            .unwrap_or_default();
+        let mut delivered = 0;
+        for uid in &members {
+            delivered += self.push_to_uid(uid, message).await?;
+        }
+        Ok(delivered)
+    }
+
+    /// 按目录把文本帧写入客户端所在节点 / Queue a text frame on the node that holds the client
+    async fn route_to_client(&self, client_id: &str, text: &str) -> Result<()> {
+        match self.directory.locate_client(client_id) {
+            Some(node) if node != self.node_id => match self.directory.get_server(&node) {
+                Some(remote) => {
+                    remote
+                        .send_message_to_client(client_id, Message::Text(text.to_string()))
+                        .await
+                }
+                None => Err(anyhow::anyhow!("remote node not found")),
+            },
+            _ => {
+                self.send_message_to_client(client_id, Message::Text(text.to_string()))
+                    .await
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cluster::router::NodeInfo;
+    use crate::plugins::PluginContext;
+    use crate::testkit::{im, recv_typed, TestServer};
+    use crate::ImMessage;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_plugin_context_pushes_to_uid_and_room_across_nodes() {
+        let a = TestServer::new();
+        let b = a.join("node-B");
+        a.directory.register_node(NodeInfo {
+            node_id: "node-B".into(),
+            weight: 1,
+            is_alive: true,
+        });
+        let (bot_id, mut bot_rx) = a.add_client("bot");
+        let (_, mut c1_rx) = a.add_device("carol", "phone");
+        let (_, mut c2_rx) = a.add_device("carol", "laptop");
+        let (_, mut d_rx) = b.add_client("dave");
+
+        let ctx = PluginContext::new(&a.server, &bot_id);
+        let reply = || im("notice", json!({"text": "hi"}), None);
+        assert_eq!(ctx.send_to_uid("carol", reply()).await.unwrap(), 2);
+        assert_eq!(ctx.send_to_uid("dave", reply()).await.unwrap(), 1);
+        assert_eq!(ctx.send_to_uid("nobody", reply()).await.unwrap(), 0);
+        for rx in [&mut c1_rx, &mut c2_rx, &mut d_rx] {
+            let got: ImMessage = recv_typed(rx).await;
+            assert_eq!(got.data["text"], "hi");
+        }
+
+        for uid in ["carol", "dave"] {
+            a.server.http_join_room("r1", uid).await;
+        }
+        let sent = ctx
+            .send_to_room("r1", im("notice", json!({"text": "all"}), None))
+            .await
+            .unwrap();
+        assert_eq!(sent, 3);
+        let got: ImMessage = recv_typed(&mut d_rx).await;
+        assert_eq!(got.data["text"], "all");
+        // 推送不回显给发起插件的连接 / The plugin's own connection receives nothing
+        assert!(bot_rx.try_recv().is_err());
+
+        let system = PluginContext::system("sys");
+        assert!(system
+            .send_to_uid("carol", im("notice", json!({}), None))
+            .await
+            .is_err());
+    }
+}
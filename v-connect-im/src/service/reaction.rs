@@ -0,0 +1,237 @@
+//! 表情回应 / Message reactions
+//!
+//! 客户端发送 `react` / `unreact`（`{message_id, emoji}`）。消息从存储插件读取以确定会话参与者：
+//! 群消息为房间当前成员，其余为发送方与接收方；非参与者被拒绝。变更写入存储后，按表情汇总的
+//! `reaction_update` 推送给全部参与者的在线连接。
+//! Clients send `react` / `unreact` (`{message_id, emoji}`). The message is read from the storage
+//! plugin to find the conversation's participants: current room members for group messages, the
+//! sender and recipient otherwise; anyone else is rejected. After storing the change, a
+//! `reaction_update` aggregated per emoji is pushed to every online connection of every participant.
+
+use crate::domain::message::{ErrorCode, ImMessage};
+use crate::server::VConnectIMServer;
+use serde_json::{json, Value};
+use tokio_tungstenite::tungstenite::Message;
+
+/// 表情回应更新事件 / Reaction update event
+pub const REACTION_UPDATE: &str = "reaction_update";
+
+/// 表情最大长度（字节）/ Max emoji length in bytes
+pub const MAX_EMOJI_LEN: usize = 64;
+
+impl VConnectIMServer {
+    /// 处理 `react`（`add`）或 `unreact`，失败时向客户端回 `error`
+    /// Handle `react` (`add`) or `unreact`, answering the client with an `error` on failure
+    pub async fn handle_reaction(
+        &self,
+        client_id: &str,
+        data: &Value,
+        add: bool,
+    ) -> anyhow::Result<()> {
+        if let Err(err) = self.apply_reaction(client_id, data, add).await {
+            let txt = serde_json::to_string(&err)?;
+            self.send_message_to_client(client_id, Message::Text(txt))
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn apply_reaction(
+        &self,
+        client_id: &str,
+        data: &Value,
+        add: bool,
+    ) -> Result<(), ImMessage> {
+        let uid = self
+            .connections
+            .get(client_id)
+            .and_then(|c| c.uid.clone())
+            .ok_or_else(|| {
+                ImMessage::error(ErrorCode::Unauthenticated, "react requires auth uid")
+            })?;
+        let field = |key: &str| data.get(key).and_then(Value::as_str).unwrap_or_default();
+        let (message_id, emoji) = (field("message_id"), field("emoji"));
+        if message_id.is_empty() || emoji.is_empty() || emoji.len() > MAX_EMOJI_LEN {
+            return Err(ImMessage::error(
+                ErrorCode::InvalidReaction,
+                format!(
+                    "react requires message_id and an emoji of at most {} bytes",
+                    MAX_EMOJI_LEN
+                ),
+            ));
+        }
+        let storage_error = |e: String| {
+            tracing::warn!("reaction on {} failed: {}", message_id, e);
+            ImMessage::error(ErrorCode::StorageError, "reaction storage unavailable")
+        };
+        let pool = self
+            .plugin_connection_pool
+            .as_ref()
+            .ok_or_else(|| storage_error("no storage".into()))?;
+
+        let message = match pool.storage_get_message(message_id).await {
+            Ok(Some(Some(message))) => message,
+            Ok(Some(None)) => {
+                return Err(ImMessage::error(ErrorCode::NotFound, "message not found"))
+            }
+            Ok(None) => return Err(storage_error("no storage".into())),
+            Err(e) => return Err(storage_error(e.to_string())),
+        };
+        let participants = self.participants_of(&message);
+        if !participants.contains(&uid) {
+            return Err(ImMessage::error(
+                ErrorCode::Forbidden,
+                "only participants of the conversation may react",
+            ));
+        }
+
+        pool.storage_set_reaction(message_id, &uid, emoji, add)
+            .await
+            .map_err(|e| storage_error(e.to_string()))?;
+        let reactions = pool
+            .storage_list_reactions(message_id)
+            .await
+            .map_err(|e| storage_error(e.to_string()))?
+            .unwrap_or_default();
+        let update = ImMessage {
+            msg_type: REACTION_UPDATE.to_string(),
+            data: json!({"message_id": message_id, "reactions": reactions}),
+            target_uid: None,
+            priority: Default::default(),
+            reply_to: None,
+        };
+        let txt = serde_json::to_string(&update).map_err(|e| storage_error(e.to_string()))?;
+        for participant in &participants {
+            let clients: Vec<String> = match self.uid_clients.get(participant) {
+                Some(clients) => clients.iter().map(|c| c.clone()).collect(),
+                None => continue,
+            };
+            for cid in clients {
+                let _ = self
+                    .deliver_to_client(&cid, Message::Text(txt.clone()))
+                    .await;
+            }
+        }
+        Ok(())
+    }
+
+    /// 消息所在会话的参与者 / Participants of the message's conversation
+    ///
+    /// 私聊记录的发送方可能是连接ID，仍在线时换成其 uid。
+    /// A private message's sender may be stored as a connection id; it is mapped to the uid while
+    /// that connection is still online.
+    fn participants_of(&self, message: &Value) -> Vec<String> {
+        let field = |key: &str| message.get(key).and_then(Value::as_str).unwrap_or_default();
+        if field("msg_type") == "group_message" {
+            return self
+                .rooms
+                .get(field("to_uid"))
+                .map(|members| members.iter().map(|u| u.clone()).collect())
+                .unwrap_or_default();
+        }
+        let from = field("from_uid");
+        let sender = self
+            .connections
+            .get(from)
+            .and_then(|c| c.uid.clone())
+            .unwrap_or_else(|| from.to_string());
+        let mut participants = vec![sender, field("to_uid").to_string()];
+        participants.dedup();
+        participants
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::message::HttpSendMessageRequest;
+    use crate::plugins::runtime::{PluginConnectionPool, PluginRuntimeManager};
+    use crate::storage::builtin::BuiltinStorage;
+    use crate::testkit::{im, recv_typed, TestServer};
+    use crate::ws::send_queue::QueueReceiver;
+    use std::sync::Arc;
+
+    async fn next_update(rx: &mut QueueReceiver) -> Value {
+        loop {
+            let msg: ImMessage = recv_typed(rx).await;
+            if msg.msg_type == REACTION_UPDATE {
+                return msg.data["reactions"].clone();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_react_unreact_and_aggregate() {
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = Arc::new(PluginConnectionPool::new(manager));
+        pool.enable_builtin_storage(Arc::new(BuiltinStorage::open_temporary().unwrap()));
+        let ts = TestServer::build(|server| server.with_plugin_connection_pool(pool.clone()));
+        let (alice, mut alice_rx) = ts.add_client("alice");
+        let (bob, mut bob_rx) = ts.add_client("bob");
+        let (carol, mut carol_rx) = ts.add_client("carol");
+
+        let sent = ts
+            .server
+            .http_send_message(HttpSendMessageRequest {
+                from_uid: "alice".into(),
+                to_uid: "bob".into(),
+                content: json!({"text": "lunch?"}),
+                message_type: None,
+            })
+            .await;
+        let message_id = sent.message_id.unwrap();
+        let react = |kind: &str, emoji: &str| {
+            im(
+                kind,
+                json!({"message_id": message_id, "emoji": emoji}),
+                None,
+            )
+        };
+
+        ts.send(&bob, react("react", "👍")).await.unwrap();
+        let expected = json!([{"emoji": "👍", "count": 1, "uids": ["bob"]}]);
+        assert_eq!(next_update(&mut alice_rx).await, expected);
+        assert_eq!(next_update(&mut bob_rx).await, expected);
+
+        ts.send(&alice, react("react", "👍")).await.unwrap();
+        ts.send(&alice, react("react", "🎉")).await.unwrap();
+        ts.send(&bob, react("unreact", "👍")).await.unwrap();
+        for _ in 0..3 {
+            next_update(&mut bob_rx).await;
+        }
+        let aggregated = json!([
+            {"emoji": "🎉", "count": 1, "uids": ["alice"]},
+            {"emoji": "👍", "count": 1, "uids": ["alice"]},
+        ]);
+        assert_eq!(
+            next_update(&mut alice_rx).await,
+            json!([
+                {"emoji": "👍", "count": 2, "uids": ["alice", "bob"]},
+            ])
+        );
+        assert_eq!(
+            pool.storage_list_reactions(&message_id)
+                .await
+                .unwrap()
+                .unwrap(),
+            aggregated.as_array().unwrap().clone()
+        );
+
+        // 非参与者被拒绝，参与者不会收到更新 / Outsiders are rejected and participants see no update
+        ts.send(&carol, react("react", "👀")).await.unwrap();
+        let err: ImMessage = recv_typed(&mut carol_rx).await;
+        assert_eq!(err.msg_type, "error");
+        assert_eq!(err.data["code"], json!(ErrorCode::Forbidden));
+        ts.send(&bob, react("react", "")).await.unwrap();
+        let err: ImMessage = recv_typed(&mut bob_rx).await;
+        assert_eq!(err.data["code"], json!(ErrorCode::InvalidReaction));
+        assert_eq!(
+            pool.storage_list_reactions(&message_id)
+                .await
+                .unwrap()
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+}
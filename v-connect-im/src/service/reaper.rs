@@ -0,0 +1,224 @@
+//! 连接回收 / Connection reaper
+//!
+//! 心跳超时只看最后一次入站消息，持续发送的连接永远不会因此被清理。回收任务与心跳无关，按两条上限
+//! 淘汰连接：连接建立超过 `server.max_connection_lifetime_ms` 时以关闭码 4010 断开；客户端超过
+//! `server.max_idle_ms` 未发送除 `ping` 以外的消息时以关闭码 4008 断开。两者默认为 0（关闭）。
+//! 被回收的连接从 uid 映射中移除并发布 `client.offline`，连接任务经 `ConnectionCloser` 直接写出关闭帧
+//! 后结束；计入 `/v1/health/detailed` 的 `details.delivery.cleanups.timeout`。
+//! The heartbeat timeout only looks at the last inbound message, so a connection that keeps
+//! sending is never cleaned up by it. The reaper runs independently of heartbeats and evicts
+//! connections on two caps: a connection older than `server.max_connection_lifetime_ms` is closed
+//! with close code 4010, and a client that has sent nothing but `ping` for `server.max_idle_ms` is
+//! closed with close code 4008. Both default to 0 (off). A reaped connection is dropped from the
+//! uid lookup, publishes `client.offline`, and its connection task is ended directly through
+//! `ConnectionCloser` after writing the close frame; it is counted under
+//! `details.delivery.cleanups.timeout` of `/v1/health/detailed`.
+
+use crate::server::VConnectIMServer;
+use crate::service::delivery_log::DropReason;
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::tungstenite::Message;
+
+/// 回收策略，零为关闭 / Reaper policy, zero disables a cap
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReaperPolicy {
+    /// 连接最长存活时间 / Longest a connection may live
+    pub max_lifetime: Duration,
+    /// 客户端最长不发送消息的时间 / Longest a client may go without sending a message
+    pub max_idle: Duration,
+}
+
+impl ReaperPolicy {
+    /// 读取 `server.max_connection_lifetime_ms` 与 `server.max_idle_ms`
+    /// Read `server.max_connection_lifetime_ms` and `server.max_idle_ms`
+    pub fn from_config() -> Self {
+        let Ok(cm) = v::get_global_config_manager() else {
+            return Self::default();
+        };
+        Self {
+            max_lifetime: Duration::from_millis(cm.get_or("server.max_connection_lifetime_ms", 0)),
+            max_idle: Duration::from_millis(cm.get_or("server.max_idle_ms", 0)),
+        }
+    }
+
+    /// 扫描间隔，两条上限都关闭时为 None / Sweep interval; None when both caps are off
+    pub fn sweep_interval(&self) -> Option<Duration> {
+        let shortest = [self.max_lifetime, self.max_idle]
+            .into_iter()
+            .filter(|cap| !cap.is_zero())
+            .min()?;
+        Some(if shortest <= Duration::from_secs(1) {
+            (shortest / 2).max(Duration::from_millis(1))
+        } else if shortest <= Duration::from_secs(10) {
+            Duration::from_secs(1)
+        } else {
+            Duration::from_secs(5)
+        })
+    }
+
+    /// 该连接应被回收的原因 / Why this connection should be reaped, if it should
+    pub fn check(&self, connected_at: Instant, last_sent: Instant, now: Instant) -> Option<Reap> {
+        let exceeded = |cap: Duration, since: Instant| !cap.is_zero() && now - since > cap;
+        if exceeded(self.max_lifetime, connected_at) {
+            Some(Reap::Lifetime)
+        } else if exceeded(self.max_idle, last_sent) {
+            Some(Reap::Idle)
+        } else {
+            None
+        }
+    }
+}
+
+/// 回收原因 / Why a connection was reaped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reap {
+    /// 超过最长存活时间 / Past the max lifetime
+    Lifetime,
+    /// 空闲过久 / Idle for too long
+    Idle,
+}
+
+impl Reap {
+    /// 关闭帧使用的关闭码 / Close code used in the close frame
+    pub fn close_code(self) -> u16 {
+        match self {
+            Reap::Lifetime => 4010,
+            Reap::Idle => 4008,
+        }
+    }
+
+    fn reason(self) -> &'static str {
+        match self {
+            Reap::Lifetime => "max connection lifetime reached",
+            Reap::Idle => "idle for too long",
+        }
+    }
+}
+
+impl VConnectIMServer {
+    /// 记录客户端发送了消息 / Record that the client sent a message
+    pub fn touch_last_sent(&self, client_id: &str) {
+        if let Some(connection) = self.connections.get(client_id) {
+            if let Ok(mut last_sent) = connection.last_sent.lock() {
+                *last_sent = Instant::now();
+            }
+        }
+    }
+
+    /// 回收超过寿命或空闲上限的连接，返回回收数 / Reap connections past the lifetime or idle cap; returns how many
+    pub fn reap_connections(&self) -> usize {
+        let now = Instant::now();
+        // 先收集再移除，遍历时不持有连接表的锁 / Collect first so no map guard is held while removing
+        let reaped: Vec<(String, Reap)> = self
+            .connections
+            .iter()
+            .filter_map(|entry| {
+                let last_sent = *entry.last_sent.lock().ok()?;
+                let reap = self.reaper.check(entry.connected_at, last_sent, now)?;
+                Some((entry.key().clone(), reap))
+            })
+            .collect();
+        for (client_id, reap) in &reaped {
+            let Some(conn) = self.remove_connection(client_id) else {
+                continue;
+            };
+            let close = Message::Close(Some(CloseFrame {
+                code: CloseCode::from(reap.close_code()),
+                reason: reap.reason().into(),
+            }));
+            // 连接任务直接写出关闭帧并结束，不受发送队列积压影响；没有连接任务（如 QUIC）时退回发送队列
+            // The connection task writes the close frame and stops regardless of a backed-up send
+            // queue; without a connection task (e.g. QUIC) fall back to the send queue
+            if !conn.closer.close(close.clone()) {
+                let _ = conn.sender.try_send(close);
+            }
+            if let Some(uid) = &conn.uid {
+                if let Some(set) = self.uid_clients.get_mut(uid) {
+                    set.remove(client_id);
+                }
+            }
+            tracing::info!(
+                "✂️  回收连接 / Reaping connection {}: {}",
+                client_id,
+                reap.reason()
+            );
+            let connected_at = chrono::Utc::now().timestamp_millis()
+                - conn.connected_at.elapsed().as_millis() as i64;
+            self.publish_client_status(
+                false,
+                client_id,
+                conn.uid.clone(),
+                &conn.addr,
+                Some(connected_at),
+            );
+            self.log_connection_cleanup(DropReason::Timeout, client_id, conn.uid.as_deref());
+        }
+        reaped.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::ConnectionCloser;
+    use crate::service::event_bus::CLIENT_OFFLINE;
+    use crate::testkit::{im, TestServer};
+
+    #[tokio::test]
+    async fn test_connection_past_max_lifetime_is_reaped() {
+        let policy = ReaperPolicy {
+            max_lifetime: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let ts = TestServer::build(|server| server.with_reaper_policy(policy));
+        let mut offline = ts.server.event_bus.subscribe(CLIENT_OFFLINE);
+        let (old, mut old_rx) = ts.add_client("alice");
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        let (fresh, _fresh_rx) = ts.add_client("bob");
+
+        // 持续发送也不能延长寿命 / Sending keeps the heartbeat fresh but does not extend the lifetime
+        ts.send(&old, im("ping", serde_json::json!({}), None))
+            .await
+            .unwrap();
+        while old_rx.try_recv().is_ok() {}
+
+        assert_eq!(ts.server.reap_connections(), 1);
+        assert!(!ts.server.connections.contains_key(&old));
+        assert!(ts.server.connections.contains_key(&fresh));
+        match old_rx.try_recv() {
+            Ok(Message::Close(Some(frame))) => assert_eq!(u16::from(frame.code), 4010),
+            other => panic!("expected a close frame, got {:?}", other),
+        }
+        assert_eq!(ts.server.metrics.snapshot()["cleanups"]["timeout"], 1);
+        assert!(!ts.server.uid_clients.get("alice").unwrap().contains(&old));
+        let event = ts.server.event_bus.next(&mut offline).await.unwrap();
+        assert_eq!(event.payload["client_id"], old);
+        assert_eq!(event.payload["uid"], "alice");
+    }
+
+    #[tokio::test]
+    async fn test_reaper_ends_the_connection_task_past_a_full_queue() {
+        let policy = ReaperPolicy {
+            max_idle: Duration::from_millis(20),
+            ..Default::default()
+        };
+        let ts = TestServer::build(|server| server.with_reaper_policy(policy));
+        let (client, mut rx) = ts.add_client_with_queue("alice", 1);
+        let (closer, close_rx) = ConnectionCloser::new();
+        ts.server.connections.get_mut(&client).unwrap().closer = closer;
+        let sender = ts.server.connections.get(&client).unwrap().sender.clone();
+        sender.try_send(Message::Text("backlog".into())).unwrap();
+        tokio::time::sleep(Duration::from_millis(30)).await;
+
+        assert_eq!(ts.server.reap_connections(), 1);
+        match close_rx.await {
+            Ok(Message::Close(Some(frame))) => assert_eq!(u16::from(frame.code), 4008),
+            other => panic!("expected a close frame, got {:?}", other),
+        }
+        // 关闭帧没有进入已满的发送队列 / The close frame did not go through the full send queue
+        assert!(matches!(rx.try_recv(), Ok(Message::Text(_))));
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,180 @@
+//! 消息复制 / Message replication
+//!
+//! 所有发送路径经 [`VConnectIMServer::replicate_record`] 追加 Raft 日志。内容超过
+//! `cluster.raft_max_content_bytes` 时先确保消息已写入存储插件（持久化策略已保存的不重复写），
+//! 再追加只带引用的条目；存储不可用时记录告警并内联复制。跟随者用
+//! [`VConnectIMServer::apply_entry`] 还原完整记录。阈值与一致性影响见 [`crate::cluster::raft`]。
+//! Every send path appends to the Raft log through [`VConnectIMServer::replicate_record`]. When
+//! content exceeds `cluster.raft_max_content_bytes` the message is first made present in the
+//! storage plugin (records already saved by the persistence policy are not written twice) and a
+//! reference-only entry is appended; if storage is unavailable it warns and replicates inline.
+//! Followers rebuild the full record with [`VConnectIMServer::apply_entry`]. See
+//! [`crate::cluster::raft`] for the threshold and its consistency implications.
+
+use crate::cluster::raft::LogEntry;
+use crate::server::VConnectIMServer;
+use crate::storage::MessageRecord;
+use anyhow::{anyhow, Result};
+
+impl VConnectIMServer {
+    /// 追加记录到 Raft 日志，超限内容存于带外 / Append a record to the Raft log, keeping oversized content out of band
+    pub async fn replicate_record(&self, record: &MessageRecord) -> Result<LogEntry> {
+        let entry = if self.raft.is_oversized(record) && self.store_out_of_band(record).await {
+            LogEntry::by_reference(record)
+        } else {
+            LogEntry::inline(record)
+        };
+        self.raft.append_entry_as(&self.node_id, &entry)?;
+        tracing::debug!(
+            "Replicated {} ({} bytes, {} total)",
+            record.message_id,
+            entry.encoded_len(),
+            self.raft.replicated_bytes(&self.node_id)
+        );
+        Ok(entry)
+    }
+
+    /// 确保消息内容已在存储插件中 / Make sure the message content is in the storage plugin
+    async fn store_out_of_band(&self, record: &MessageRecord) -> bool {
+        let Some(pool) = self.plugin_connection_pool.as_ref() else {
+            tracing::warn!(
+                "⚠️  无存储插件，超限内容内联复制 / No storage plugin, replicating oversized {} inline",
+                record.message_id
+            );
+            return false;
+        };
+        if let Ok(Some(Some(_))) = pool.storage_get_message(&record.message_id).await {
+            return true;
+        }
+        let saved = pool
+            .storage_save_message(
+                &record.message_id,
+                &record.from_client_id,
+                &record.to_client_id,
+                &record.content,
+                record.timestamp,
+                &record.msg_type,
+                record.room_id.as_deref(),
+            )
+            .await;
+        // 写入回退库不算已存储：跟随者取不到 / A spooled write does not count: followers cannot fetch it
+        let stored = matches!(saved, Ok(true))
+            && matches!(
+                pool.storage_get_message(&record.message_id).await,
+                Ok(Some(Some(_)))
+            );
+        if !stored {
+            tracing::warn!(
+                "⚠️  超限内容写入存储失败，内联复制 / Storing oversized {} failed, replicating inline",
+                record.message_id
+            );
+        }
+        stored
+    }
+
+    /// 应用日志条目，按引用从存储取回内容并校验哈希
+    /// Apply a log entry, fetching referenced content from storage and verifying its hash
+    pub async fn apply_entry(&self, entry: &LogEntry) -> Result<MessageRecord> {
+        let Some(content_ref) = &entry.content_ref else {
+            return Ok(entry.record.clone());
+        };
+        let message_id = &entry.record.message_id;
+        let pool = self
+            .plugin_connection_pool
+            .as_ref()
+            .ok_or_else(|| anyhow!("no storage to resolve content of {}", message_id))?;
+        let content = pool
+            .storage_get_message(message_id)
+            .await?
+            .flatten()
+            .and_then(|m| m.get("content").cloned())
+            .ok_or_else(|| anyhow!("content of {} not found in storage", message_id))?;
+        if !content_ref.matches(&content) {
+            return Err(anyhow!("content of {} does not match its hash", message_id));
+        }
+        Ok(MessageRecord {
+            content,
+            ..entry.record.clone()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::raft::ContentRef;
+    use crate::plugins::runtime::{PluginConnectionPool, PluginRuntimeManager};
+    use crate::storage::builtin::BuiltinStorage;
+    use crate::testkit::TestServer;
+    use serde_json::json;
+    use std::sync::Arc;
+
+    fn record(message_id: &str, text: String) -> MessageRecord {
+        MessageRecord {
+            message_id: message_id.into(),
+            from_client_id: "alice".into(),
+            to_client_id: "bob".into(),
+            content: json!({ "text": text }),
+            timestamp: 1,
+            msg_type: "message".into(),
+            room_id: None,
+            attachment: None,
+            reply_to: None,
+        }
+    }
+
+    /// Raft 条目内联上限为 `max` 的测试节点 / Test node whose Raft entries inline at most `max` bytes
+    fn server_with_limit(max: usize, pool: Option<Arc<PluginConnectionPool>>) -> TestServer {
+        TestServer::build(|server| {
+            let raft = Arc::new((*server.raft).clone().with_max_content_bytes(max));
+            let server = server.with_raft(raft);
+            match pool {
+                Some(pool) => server.with_plugin_connection_pool(pool),
+                None => server,
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_oversized_content_is_replicated_by_reference() {
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = Arc::new(PluginConnectionPool::new(manager));
+        pool.enable_builtin_storage(Arc::new(BuiltinStorage::open_temporary().unwrap()));
+        let ts = server_with_limit(1024, Some(pool));
+        let raft = ts.server.raft.clone();
+
+        let small = record("small", "hi".into());
+        let entry = ts.server.replicate_record(&small).await.unwrap();
+        assert!(entry.content_ref.is_none());
+        assert_eq!(entry.record.content, small.content);
+        let after_small = raft.replicated_bytes("node-A");
+
+        let big = record("big", "x".repeat(100_000));
+        let entry = ts.server.replicate_record(&big).await.unwrap();
+        let content_ref = entry.content_ref.clone().unwrap();
+        assert!(content_ref.size > 100_000);
+        assert!(entry.record.content.is_null());
+        assert!(raft.replicated_bytes("node-A") - after_small < 1024);
+        assert_eq!(raft.commit_count("node-A"), 2);
+
+        // 跟随者按引用取回完整内容 / The follower resolves the full content by reference
+        let applied = ts.server.apply_entry(&entry).await.unwrap();
+        assert_eq!(applied.content, big.content);
+        assert_eq!(applied.message_id, "big");
+
+        // 哈希不符时拒绝应用 / A hash mismatch fails the apply
+        let mut tampered = entry;
+        tampered.content_ref = Some(ContentRef::of(&json!("other")));
+        assert!(ts.server.apply_entry(&tampered).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_oversized_content_without_storage_stays_inline() {
+        let ts = server_with_limit(16, None);
+        let big = record("big", "x".repeat(64));
+        let entry = ts.server.replicate_record(&big).await.unwrap();
+        assert!(entry.content_ref.is_none());
+        let applied = ts.server.apply_entry(&entry).await.unwrap();
+        assert_eq!(applied.content, big.content);
+    }
+}
@@ -0,0 +1,317 @@
+//! 断线重连令牌 / Reconnection (resume) tokens
+//!
+//! 认证成功时为连接签发一次性重连令牌；连接断开后令牌在 `auth.resume_ttl_ms` 内有效，
+//! 客户端重连时以 `resume` 出示令牌即可恢复 uid、设备与连接元数据，并补发断开时尚未写出的消息，
+//! 无需再次认证。令牌只能使用一次，且只对签发时的 uid 有效。
+//! A single-use resume token is issued on successful auth. After the connection closes
+//! the token stays valid for `auth.resume_ttl_ms`; presenting it in a `resume` frame on
+//! reconnect restores the uid, device and connection metadata and replays the frames that
+//! had not been written when the connection dropped, without another auth round trip.
+//! Tokens are single-use and bound to the uid they were issued for.
+
+use crate::domain::message::{ImMessage, MessagePriority};
+use crate::server::VConnectIMServer;
+use dashmap::DashMap;
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::Message;
+
+/// 默认重连窗口 / Default resume window
+pub const DEFAULT_RESUME_TTL_MS: u64 = 60_000;
+
+/// 重连窗口（`auth.resume_ttl_ms`），0 表示不签发令牌
+/// Resume window (`auth.resume_ttl_ms`); 0 disables token issuance
+pub fn resume_ttl() -> Duration {
+    Duration::from_millis(
+        v::get_global_config_manager()
+            .ok()
+            .map(|cm| cm.get_or("auth.resume_ttl_ms", DEFAULT_RESUME_TTL_MS))
+            .unwrap_or(DEFAULT_RESUME_TTL_MS),
+    )
+}
+
+/// 令牌对应的会话 / Session behind a resume token
+#[derive(Debug, Clone)]
+pub struct ResumeSession {
+    pub uid: String,
+    pub device_id: Option<String>,
+    /// 签发令牌的连接 / Connection the token was issued to
+    pub client_id: String,
+    /// 断开时的连接元数据 / Connection metadata at disconnect
+    pub metadata: Vec<(String, Value)>,
+    /// 断开时尚未写出的帧 / Frames not yet written at disconnect
+    pub pending: Vec<Message>,
+    /// 连接断开后才开始计时 / Only set once the connection has closed
+    expires_at: Option<Instant>,
+}
+
+/// 令牌存储（token -> 会话）/ Token store (token -> session)
+#[derive(Default)]
+pub struct ResumeTokenStore {
+    sessions: DashMap<String, ResumeSession>,
+    by_client: DashMap<String, String>, // client_id -> token
+}
+
+impl ResumeTokenStore {
+    /// 为连接签发新令牌，作废该连接之前的令牌 / Issue a token for a connection, revoking its previous one
+    pub fn issue(&self, client_id: &str, uid: &str, device_id: Option<&str>) -> String {
+        self.purge_expired();
+        let token = hex::encode(rand::random::<[u8; 32]>());
+        if let Some(old) = self.by_client.insert(client_id.to_string(), token.clone()) {
+            self.sessions.remove(&old);
+        }
+        self.sessions.insert(
+            token.clone(),
+            ResumeSession {
+                uid: uid.to_string(),
+                device_id: device_id.map(|d| d.to_string()),
+                client_id: client_id.to_string(),
+                metadata: Vec::new(),
+                pending: Vec::new(),
+                expires_at: None,
+            },
+        );
+        token
+    }
+
+    /// 连接断开：保存状态并开始计时 / Connection closed: keep its state and start the window
+    pub fn suspend(
+        &self,
+        client_id: &str,
+        metadata: Vec<(String, Value)>,
+        pending: Vec<Message>,
+        ttl: Duration,
+    ) {
+        let Some((_, token)) = self.by_client.remove(client_id) else {
+            return;
+        };
+        if ttl.is_zero() {
+            self.sessions.remove(&token);
+            return;
+        }
+        if let Some(mut session) = self.sessions.get_mut(&token) {
+            session.metadata = metadata;
+            session.pending = pending;
+            session.expires_at = Some(Instant::now() + ttl);
+        }
+    }
+
+    /// 取出令牌对应的会话（一次性）；uid 不符或已过期时令牌同样作废
+    /// Take the session for a token (single-use); the token is burned as well when the uid
+    /// does not match or it has expired
+    pub fn take(&self, token: &str, uid: &str) -> Option<ResumeSession> {
+        let (_, session) = self.sessions.remove(token)?;
+        self.by_client
+            .remove_if(&session.client_id, |_, t| t.as_str() == token);
+        let expired = session.expires_at.is_some_and(|at| at <= Instant::now());
+        (session.uid == uid && !expired).then_some(session)
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    fn purge_expired(&self) {
+        let now = Instant::now();
+        self.sessions
+            .retain(|_, s| s.expires_at.is_none_or(|at| at > now));
+    }
+}
+
+impl VConnectIMServer {
+    /// 用重连令牌恢复会话到当前连接；成功时返回新令牌与补发的帧数
+    /// Resume a session onto the current connection; on success returns the new token and
+    /// the number of frames replayed
+    ///
+    /// 旧连接若仍在（如移动网络切换后服务端尚未察觉断开），会被关闭并由新连接接管。
+    /// If the old connection is still registered (e.g. the server has not noticed a mobile
+    /// network switch yet), it is closed and the new connection takes over.
+    pub async fn resume_session(
+        &self,
+        client_id: &str,
+        uid: &str,
+        token: &str,
+    ) -> Option<(String, usize)> {
+        let session = self.resume_tokens.take(token, uid)?;
+        if session.client_id != client_id && self.connections.contains_key(&session.client_id) {
+            let _ = self.send_close_message(&session.client_id).await;
+            if let Some(old) = self.remove_connection(&session.client_id) {
+                if let Some(set) = self.uid_clients.get(&old.uid.unwrap_or_default()) {
+                    set.remove(&session.client_id);
+                }
+            }
+        }
+
+        let conn = self.connections.get(client_id).map(|c| c.clone())?;
+        if let Some(mut c) = self.connections.get_mut(client_id) {
+            c.uid = Some(session.uid.clone());
+            c.device_id = session.device_id.clone();
+        }
+        self.uid_clients
+            .entry(session.uid.clone())
+            .or_default()
+            .insert(client_id.to_string());
+        for (key, value) in session.metadata {
+            conn.set_meta(key, value);
+        }
+        let new_token =
+            self.resume_tokens
+                .issue(client_id, &session.uid, session.device_id.as_deref());
+
+        let response = ImMessage {
+            msg_type: "resume_response".to_string(),
+            data: serde_json::json!({
+                "status": "success",
+                "device_id": session.device_id,
+                "resume_token": new_token,
+                "replayed": session.pending.len(),
+            }),
+            target_uid: None,
+            priority: Default::default(),
+            reply_to: None,
+        };
+        let text = serde_json::to_string(&response).ok()?;
+        let _ = self
+            .send_message_to_client(client_id, Message::Text(text))
+            .await;
+        // 这些帧已执行过下行钩子，直接写入队列 / These frames already went through outgoing hooks; queue them directly
+        let mut replayed = 0;
+        for frame in session.pending {
+            let priority = frame_priority(&frame);
+            if conn
+                .sender
+                .try_send_with(conn.wire_format.encode(frame), priority)
+                .is_err()
+            {
+                break;
+            }
+            replayed += 1;
+        }
+        tracing::info!(
+            "🔁 会话已恢复 / Session resumed for {} on {} (replayed {})",
+            session.uid,
+            client_id,
+            replayed
+        );
+        Some((new_token, replayed))
+    }
+}
+
+/// 读取待补发 JSON 帧的 `priority` / Read the `priority` of a pending JSON frame
+fn frame_priority(frame: &Message) -> MessagePriority {
+    match frame {
+        Message::Text(text) => serde_json::from_str::<Value>(text)
+            .ok()
+            .and_then(|v| {
+                v.get("priority")
+                    .and_then(Value::as_str)
+                    .map(MessagePriority::parse)
+            })
+            .unwrap_or_default(),
+        _ => MessagePriority::Normal,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::{im, recv_typed, TestServer};
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn test_token_is_single_use_and_bound_to_uid() {
+        let store = ResumeTokenStore::default();
+        let token = store.issue("c1", "alice", Some("phone"));
+        store.suspend("c1", vec![], vec![], TTL);
+        assert!(store.take(&token, "alice").is_some());
+        assert!(store.take(&token, "alice").is_none());
+
+        // uid 不符时令牌也被作废 / A uid mismatch burns the token too
+        let token = store.issue("c2", "alice", None);
+        assert!(store.take(&token, "mallory").is_none());
+        assert!(store.take(&token, "alice").is_none());
+
+        // 重新签发作废旧令牌 / Re-issuing revokes the previous token
+        let old = store.issue("c3", "bob", None);
+        let new = store.issue("c3", "bob", None);
+        assert!(store.take(&old, "bob").is_none());
+        assert!(store.take(&new, "bob").is_some());
+        assert_eq!(store.len(), 0);
+    }
+
+    #[test]
+    fn test_token_expires_after_disconnect_window() {
+        let store = ResumeTokenStore::default();
+        let token = store.issue("c1", "alice", None);
+        store.suspend("c1", vec![], vec![], Duration::from_millis(1));
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(store.take(&token, "alice").is_none());
+
+        // 窗口为 0 时断开即作废 / A zero window revokes on disconnect
+        let token = store.issue("c2", "alice", None);
+        store.suspend("c2", vec![], vec![], Duration::ZERO);
+        assert!(store.take(&token, "alice").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_resume_restores_binding_metadata_and_pending_frames() {
+        let ts = TestServer::new();
+        let (old_id, old_rx) = ts.add_device("alice", "phone");
+        let token = ts
+            .server
+            .resume_tokens
+            .issue(&old_id, "alice", Some("phone"));
+        let pending = vec![Message::Text(
+            serde_json::to_string(&im(
+                "forwarded_message",
+                serde_json::json!({"text": "missed"}),
+                None,
+            ))
+            .unwrap(),
+        )];
+        drop(old_rx);
+        ts.server.remove_connection(&old_id);
+        ts.server.uid_clients.get("alice").unwrap().remove(&old_id);
+        ts.server.resume_tokens.suspend(
+            &old_id,
+            vec![("locale".into(), serde_json::json!("zh-CN"))],
+            pending,
+            TTL,
+        );
+
+        let (new_id, mut new_rx) = ts.add_unauthenticated("reconnected");
+        let (new_token, replayed) = ts
+            .server
+            .resume_session(&new_id, "alice", &token)
+            .await
+            .unwrap();
+        assert_ne!(new_token, token);
+        assert_eq!(replayed, 1);
+
+        let resp: ImMessage = recv_typed(&mut new_rx).await;
+        assert_eq!(resp.msg_type, "resume_response");
+        assert_eq!(resp.data["device_id"], "phone");
+        let missed: ImMessage = recv_typed(&mut new_rx).await;
+        assert_eq!(missed.data["text"], "missed");
+
+        let conn = ts.server.connections.get(&new_id).unwrap();
+        assert_eq!(conn.uid.as_deref(), Some("alice"));
+        assert_eq!(conn.get_meta("locale"), Some(serde_json::json!("zh-CN")));
+        assert!(ts
+            .server
+            .uid_clients
+            .get("alice")
+            .unwrap()
+            .contains(&new_id));
+        drop(conn);
+
+        // 令牌已被使用 / The token has been used
+        assert!(ts
+            .server
+            .resume_session(&new_id, "alice", &token)
+            .await
+            .is_none());
+    }
+}
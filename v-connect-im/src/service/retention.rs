@@ -0,0 +1,219 @@
+//! 按房间的消息保留期 / Per-room message retention
+//!
+//! 房间在 [`RoomMeta::retention_secs`] 中设置保留秒数，未设置时取 `storage.default_retention_secs`；
+//! 两者都没有（或为 0）的房间永久保留。后台任务每 `storage.retention_sweep_interval_secs` 秒对每个
+//! 房间发送一次 `storage.message.purge`，删除早于保留窗口的消息；`storage.retention_archive = true`
+//! 时由存储插件先归档到对象存储（内置存储不支持归档，此时清理失败并保留消息）。
+//! A room sets its retention in [`RoomMeta::retention_secs`], falling back to
+//! `storage.default_retention_secs`; rooms with neither (or 0) keep messages forever. Every
+//! `storage.retention_sweep_interval_secs` a background task sends `storage.message.purge` for each
+//! room, deleting messages older than the window; with `storage.retention_archive = true` the storage
+//! plugin archives them to object storage first (the built-in storage cannot archive, so the purge
+//! fails and the messages stay).
+
+use crate::plugins::runtime::MessagePurge;
+use crate::server::VConnectIMServer;
+use crate::service::room_meta::RoomMeta;
+use anyhow::Result;
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+
+/// 保留策略 / Retention policy
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// 未单独设置的房间的保留秒数，None 为永久 / Retention for rooms without their own, None keeps forever
+    pub default_secs: Option<u64>,
+    /// 删除前先归档 / Archive before deleting
+    pub archive: bool,
+    /// 清理间隔 / Sweep interval
+    pub interval: Duration,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            default_secs: None,
+            archive: false,
+            interval: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// 读取 `storage.default_retention_secs` 等 / Read `storage.default_retention_secs` and friends
+    pub fn from_config() -> Self {
+        let defaults = Self::default();
+        let Ok(cm) = v::get_global_config_manager() else {
+            return defaults;
+        };
+        Self {
+            default_secs: Some(cm.get_or("storage.default_retention_secs", 0_u64))
+                .filter(|secs| *secs > 0),
+            archive: cm.get_or("storage.retention_archive", defaults.archive),
+            interval: Duration::from_secs(
+                cm.get_or(
+                    "storage.retention_sweep_interval_secs",
+                    defaults.interval.as_secs(),
+                )
+                .max(1),
+            ),
+        }
+    }
+
+    /// 房间的保留秒数，房间设为 0 时永久保留 / A room's retention; a room set to 0 keeps forever
+    pub fn retention_for(&self, meta: Option<&RoomMeta>) -> Option<u64> {
+        meta.and_then(|m| m.retention_secs)
+            .or(self.default_secs)
+            .filter(|secs| *secs > 0)
+    }
+}
+
+impl VConnectIMServer {
+    /// 清理各房间超出保留期的消息，返回有删除的房间
+    /// Purge every room's messages past retention; returns the rooms that had deletions
+    pub async fn sweep_retention(&self, now_ms: i64) -> Result<Vec<(String, MessagePurge)>> {
+        let Some(pool) = self.plugin_connection_pool.as_ref() else {
+            return Ok(Vec::new());
+        };
+        let metas: HashMap<String, RoomMeta> = self
+            .room_meta
+            .list()?
+            .into_iter()
+            .map(|meta| (meta.room_id.clone(), meta))
+            .collect();
+        let mut rooms: BTreeSet<String> = metas.keys().cloned().collect();
+        rooms.extend(self.rooms.iter().map(|entry| entry.key().clone()));
+        if self.retention.default_secs.is_some() {
+            rooms.extend(pool.storage_list_rooms().await?);
+        }
+
+        let mut purged = Vec::new();
+        for room_id in rooms {
+            let Some(secs) = self.retention.retention_for(metas.get(&room_id)) else {
+                continue;
+            };
+            let window_ms = i64::try_from(secs.saturating_mul(1000)).unwrap_or(i64::MAX);
+            let until = now_ms.saturating_sub(window_ms);
+            match pool
+                .storage_purge_room(&room_id, until, self.retention.archive)
+                .await
+            {
+                Ok(Some(purge)) if purge.deleted > 0 => purged.push((room_id, purge)),
+                Ok(Some(_)) => {}
+                // 没有可用的存储，其余房间也无从清理 / No storage available, so no other room can be purged either
+                Ok(None) => break,
+                Err(e) => tracing::warn!(
+                    "⚠️  房间 {} 保留期清理失败 / Retention purge failed for room {}: {}",
+                    room_id,
+                    room_id,
+                    e
+                ),
+            }
+        }
+        Ok(purged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::runtime::{PluginConnectionPool, PluginRuntimeManager};
+    use crate::service::room_meta::RoomMetaStore;
+    use crate::storage::builtin::BuiltinStorage;
+    use crate::storage::MessageRecord;
+    use crate::testkit::TestServer;
+    use std::sync::Arc;
+
+    const HOUR_MS: i64 = 3_600_000;
+
+    fn record(message_id: &str, room_id: &str, timestamp: i64) -> MessageRecord {
+        MessageRecord {
+            message_id: message_id.to_string(),
+            from_client_id: "alice".to_string(),
+            to_client_id: room_id.to_string(),
+            content: serde_json::json!({"text": message_id}),
+            timestamp,
+            msg_type: "group_message".to_string(),
+            room_id: Some(room_id.to_string()),
+            attachment: None,
+            reply_to: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sweeper_respects_each_rooms_retention() {
+        let path = std::env::temp_dir().join(format!("vgo-retention-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        let storage = Arc::new(BuiltinStorage::open_temporary().unwrap());
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = Arc::new(PluginConnectionPool::new(manager));
+        pool.enable_builtin_storage(storage.clone());
+        let ts = TestServer::build(|server| {
+            // 全局默认 30 天 / A 30-day global default
+            server
+                .with_retention_policy(RetentionPolicy {
+                    default_secs: Some(30 * 24 * 3600),
+                    ..Default::default()
+                })
+                .with_room_meta_store(Arc::new(RoomMetaStore::new(&path)))
+                .with_plugin_connection_pool(pool.clone())
+        });
+        let server = &ts.server;
+        server
+            .set_room_meta(&RoomMeta {
+                room_id: "support".into(),
+                retention_secs: Some(7 * 365 * 24 * 3600),
+                ..Default::default()
+            })
+            .unwrap();
+        server
+            .set_room_meta(&RoomMeta {
+                room_id: "ephemeral".into(),
+                retention_secs: Some(24 * 3600),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let now = 1_000 * 24 * HOUR_MS;
+        for (id, room, age_hours) in [
+            ("s-old", "support", 48),
+            ("s-ancient", "support", 8 * 365 * 24),
+            ("e-old", "ephemeral", 48),
+            ("e-new", "ephemeral", 1),
+        ] {
+            storage
+                .append(&record(id, room, now - age_hours * HOUR_MS))
+                .unwrap();
+        }
+
+        let purged = server.sweep_retention(now).await.unwrap();
+        let mut deleted: Vec<(&str, usize)> = purged
+            .iter()
+            .map(|(room, purge)| (room.as_str(), purge.deleted))
+            .collect();
+        deleted.sort();
+        assert_eq!(deleted, [("ephemeral", 1), ("support", 1)]);
+        for (id, kept) in [
+            ("s-old", true),
+            ("s-ancient", false),
+            ("e-old", false),
+            ("e-new", true),
+        ] {
+            assert_eq!(storage.get(id).unwrap().is_some(), kept, "{}", id);
+        }
+
+        // 内置存储不能归档：清理失败，消息保留 / The built-in storage cannot archive: the purge fails and messages stay
+        let archiving = server
+            .as_ref()
+            .clone()
+            .with_retention_policy(RetentionPolicy {
+                archive: true,
+                ..(*server.retention).clone()
+            });
+        let later = now + 48 * HOUR_MS;
+        assert!(archiving.sweep_retention(later).await.unwrap().is_empty());
+        assert!(storage.get("e-new").unwrap().is_some());
+
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
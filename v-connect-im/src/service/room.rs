@@ -0,0 +1,41 @@
+use crate::domain::message::HttpRoomMembersResponse;
+use crate::server::VConnectIMServer;
+
+impl VConnectIMServer {
+    /// 通过 HTTP 加入房间 / Join a room through the HTTP API
+    pub async fn http_join_room(&self, room_id: &str, uid: &str) -> HttpRoomMembersResponse {
+        self.rooms
+            .entry(room_id.to_string())
+            .or_default()
+            .insert(uid.to_string());
+        if let Some(pool) = self.plugin_connection_pool.as_ref() {
+            let _ = pool.storage_add_room_member(room_id, uid).await;
+        }
+        self.room_members(room_id)
+    }
+
+    /// 通过 HTTP 离开房间 / Leave a room through the HTTP API
+    pub async fn http_leave_room(&self, room_id: &str, uid: &str) -> HttpRoomMembersResponse {
+        if let Some(set) = self.rooms.get(room_id) {
+            set.remove(uid);
+        }
+        if let Some(pool) = self.plugin_connection_pool.as_ref() {
+            let _ = pool.storage_remove_room_member(room_id, uid).await;
+        }
+        self.room_members(room_id)
+    }
+
+    /// 房间当前成员（有序）/ Current room members (sorted)
+    pub fn room_members(&self, room_id: &str) -> HttpRoomMembersResponse {
+        let mut members: Vec<String> = self
+            .rooms
+            .get(room_id)
+            .map(|set| set.iter().map(|u| u.clone()).collect())
+            .unwrap_or_default();
+        members.sort();
+        HttpRoomMembersResponse {
+            room_id: room_id.to_string(),
+            members,
+        }
+    }
+}
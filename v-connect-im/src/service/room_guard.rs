@@ -0,0 +1,303 @@
+//! 房间加入/离开的限流与滥用检测 / Rate limiting and abuse detection for room joins
+//!
+//! 客户端的 `join_room` / `leave_room` 按 uid 以令牌桶限流，每个 uid 加入的房间数受
+//! `rooms.max_per_uid` 限制（可按租户覆盖，见 [`crate::service::tenant`]）；窗口内违规达到 `rooms.block_after_violations` 次时封禁该 uid，
+//! 封禁会持久化（见 [`crate::service::blocklist`]）。
+//! Client `join_room` / `leave_room` requests are token-bucket limited per uid and each uid
+//! may be in at most `rooms.max_per_uid` rooms; a uid that violates the limits
+//! `rooms.block_after_violations` times within a window is blocked, persistently (see
+//! [`crate::service::blocklist`]). The room cap may be overridden per tenant (see
+//! [`crate::service::tenant`]).
+
+use crate::domain::message::{ErrorCode, ImMessage};
+use crate::server::VConnectIMServer;
+use crate::service::token_bucket::KeyedTokenBuckets;
+use dashmap::DashMap;
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::Message;
+
+/// 默认每个 uid 最多加入的房间数 / Default max rooms per uid
+pub const DEFAULT_MAX_ROOMS_PER_UID: usize = 200;
+/// 默认每秒允许的加入/离开次数 / Default join/leave operations per second
+pub const DEFAULT_OPS_PER_SEC: f64 = 2.0;
+/// 默认突发容量 / Default burst
+pub const DEFAULT_BURST: u32 = 10;
+/// 违规计数窗口 / Violation counting window
+const VIOLATION_WINDOW: Duration = Duration::from_secs(60);
+
+/// 房间操作限制（`[rooms]`）/ Room operation limits (`[rooms]`)
+#[derive(Debug, Clone, Copy)]
+pub struct RoomLimits {
+    pub max_per_uid: usize,
+    pub ops_per_sec: f64,
+    pub burst: u32,
+    /// 窗口内违规多少次后封禁，0 不封禁 / Violations within the window before blocking, 0 never blocks
+    pub block_after_violations: u32,
+}
+
+impl Default for RoomLimits {
+    fn default() -> Self {
+        Self {
+            max_per_uid: DEFAULT_MAX_ROOMS_PER_UID,
+            ops_per_sec: DEFAULT_OPS_PER_SEC,
+            burst: DEFAULT_BURST,
+            block_after_violations: 0,
+        }
+    }
+}
+
+impl RoomLimits {
+    /// 读取 `rooms.*` / Read `rooms.*`
+    pub fn from_config() -> Self {
+        let defaults = Self::default();
+        match v::get_global_config_manager() {
+            Ok(cm) => Self {
+                max_per_uid: cm.get_or("rooms.max_per_uid", defaults.max_per_uid),
+                ops_per_sec: cm.get_or("rooms.ops_per_sec", defaults.ops_per_sec),
+                burst: cm.get_or("rooms.burst", defaults.burst),
+                block_after_violations: cm.get_or(
+                    "rooms.block_after_violations",
+                    defaults.block_after_violations,
+                ),
+            },
+            Err(_) => defaults,
+        }
+    }
+}
+
+/// 房间操作被拒绝的原因 / Why a room operation was refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoomOpError {
+    Blocked,
+    RateLimited,
+    TooManyRooms,
+}
+
+impl RoomOpError {
+    /// 返回给客户端的错误码 / Error code returned to the client
+    pub fn code(self) -> ErrorCode {
+        match self {
+            Self::Blocked => ErrorCode::UidBlocked,
+            Self::RateLimited => ErrorCode::RoomRateLimited,
+            Self::TooManyRooms => ErrorCode::RoomLimitExceeded,
+        }
+    }
+
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::Blocked => "uid is blocked",
+            Self::RateLimited => "too many room joins/leaves, slow down",
+            Self::TooManyRooms => "room limit per uid reached",
+        }
+    }
+}
+
+/// 房间操作守卫 / Room operation guard
+pub struct RoomGuard {
+    limits: RoomLimits,
+    buckets: KeyedTokenBuckets,
+    violations: DashMap<String, (u32, Instant)>, // uid -> (窗口内违规次数 / count, 窗口起点 / window start)
+}
+
+impl Default for RoomGuard {
+    fn default() -> Self {
+        Self::new(RoomLimits::from_config())
+    }
+}
+
+impl RoomGuard {
+    pub fn new(limits: RoomLimits) -> Self {
+        Self {
+            buckets: KeyedTokenBuckets::new(limits.burst, limits.ops_per_sec),
+            violations: DashMap::new(),
+            limits,
+        }
+    }
+
+    /// 记录一次违规，返回是否应封禁 / Record a violation; returns whether the uid should be blocked
+    fn record_violation(&self, uid: &str) -> bool {
+        let now = Instant::now();
+        let mut entry = self.violations.entry(uid.to_string()).or_insert((0, now));
+        if now.duration_since(entry.1) >= VIOLATION_WINDOW {
+            *entry = (0, now);
+        }
+        entry.0 += 1;
+        self.limits.block_after_violations > 0 && entry.0 >= self.limits.block_after_violations
+    }
+}
+
+impl VConnectIMServer {
+    /// 检查 uid 能否加入（`joining`）或离开房间 / Check whether a uid may join (`joining`) or leave a room
+    pub fn check_room_op(
+        &self,
+        uid: &str,
+        room_id: &str,
+        joining: bool,
+    ) -> Result<(), RoomOpError> {
+        if self.blocked_uids.contains(uid) {
+            return Err(RoomOpError::Blocked);
+        }
+        let guard = &self.room_guard;
+        let result = if !guard.buckets.try_acquire(uid) {
+            Err(RoomOpError::RateLimited)
+        } else if joining
+            && !self.is_room_member(room_id, uid)
+            && self.rooms_of(uid)
+                >= self
+                    .tenants
+                    .overrides_for(uid)
+                    .max_rooms
+                    .unwrap_or(guard.limits.max_per_uid)
+        {
+            Err(RoomOpError::TooManyRooms)
+        } else {
+            Ok(())
+        };
+        if let Err(e) = result {
+            if guard.record_violation(uid) {
+                if let Err(err) = self.block_uid(uid, Some("room abuse")) {
+                    tracing::warn!("⚠️  封禁未能持久化 / Block not persisted: {}", err);
+                }
+                tracing::warn!(
+                    "🚫 uid {} 因频繁违规被封禁 / uid {} blocked after repeated room abuse ({:?})",
+                    uid,
+                    uid,
+                    e.code()
+                );
+            }
+        }
+        result
+    }
+
+    /// 向客户端返回结构化的房间操作错误 / Send a structured room operation error to the client
+    pub async fn send_room_op_error(
+        &self,
+        client_id: &str,
+        room_id: &str,
+        error: RoomOpError,
+    ) -> anyhow::Result<()> {
+        let mut err = ImMessage::error(error.code(), error.message());
+        err.data["room_id"] = serde_json::json!(room_id);
+        let txt = serde_json::to_string(&err)?;
+        self.send_message_to_client(client_id, Message::Text(txt))
+            .await
+    }
+
+    fn is_room_member(&self, room_id: &str, uid: &str) -> bool {
+        self.rooms.get(room_id).is_some_and(|set| set.contains(uid))
+    }
+
+    /// uid 加入的房间数 / Number of rooms the uid is in
+    fn rooms_of(&self, uid: &str) -> usize {
+        self.rooms.iter().filter(|r| r.contains(uid)).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::blocklist::BlockStore;
+    use crate::testkit::{im, recv_typed, TestServer};
+
+    fn server_with(limits: RoomLimits) -> TestServer {
+        TestServer::build(|server| server.with_room_limits(limits))
+    }
+
+    #[tokio::test]
+    async fn test_join_is_capped_per_uid() {
+        let ts = server_with(RoomLimits {
+            max_per_uid: 2,
+            burst: 100,
+            ..Default::default()
+        });
+        let (a, mut rx) = ts.add_client("alice");
+        for room in ["r1", "r2", "r1", "r3"] {
+            ts.send(
+                &a,
+                im("join_room", serde_json::json!({"room_id": room}), None),
+            )
+            .await
+            .unwrap();
+        }
+        for expected in ["join_room_ok", "join_room_ok", "join_room_ok"] {
+            let got: ImMessage = recv_typed(&mut rx).await;
+            assert_eq!(got.msg_type, expected);
+        }
+        let err: ImMessage = recv_typed(&mut rx).await;
+        assert_eq!(err.msg_type, "error");
+        assert_eq!(err.data["code"], "ROOM_LIMIT_EXCEEDED");
+        assert!(!ts
+            .server
+            .rooms
+            .get("r3")
+            .is_some_and(|s| s.contains("alice")));
+
+        // 离开后可加入新房间 / Leaving frees a slot
+        ts.send(
+            &a,
+            im("leave_room", serde_json::json!({"room_id": "r1"}), None),
+        )
+        .await
+        .unwrap();
+        ts.send(
+            &a,
+            im("join_room", serde_json::json!({"room_id": "r3"}), None),
+        )
+        .await
+        .unwrap();
+        let got: ImMessage = recv_typed(&mut rx).await;
+        assert_eq!(got.msg_type, "leave_room_ok");
+        let got: ImMessage = recv_typed(&mut rx).await;
+        assert_eq!(got.msg_type, "join_room_ok");
+    }
+
+    #[tokio::test]
+    async fn test_join_leave_is_rate_limited_and_abuse_blocks() {
+        let path = std::env::temp_dir().join(format!("vgo-room-abuse-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        let store = std::sync::Arc::new(BlockStore::new(&path));
+        let ts = TestServer::build(|server| {
+            server
+                .with_room_limits(RoomLimits {
+                    burst: 3,
+                    ops_per_sec: 0.0,
+                    block_after_violations: 2,
+                    ..Default::default()
+                })
+                .with_block_store(store.clone())
+        });
+        let (a, mut rx) = ts.add_client("mallory");
+        for op in ["join_room", "leave_room", "join_room", "leave_room"] {
+            ts.send(&a, im(op, serde_json::json!({"room_id": "r1"}), None))
+                .await
+                .unwrap();
+        }
+        for _ in 0..3 {
+            let ok: ImMessage = recv_typed(&mut rx).await;
+            assert!(ok.msg_type.ends_with("_ok"));
+        }
+        let err: ImMessage = recv_typed(&mut rx).await;
+        assert_eq!(err.data["code"], "ROOM_RATE_LIMITED");
+        assert!(!ts.server.blocked_uids.contains("mallory"));
+
+        // 第二次违规触发封禁 / The second violation blocks the uid
+        ts.send(
+            &a,
+            im("join_room", serde_json::json!({"room_id": "r2"}), None),
+        )
+        .await
+        .unwrap();
+        let err: ImMessage = recv_typed(&mut rx).await;
+        assert_eq!(err.data["code"], "ROOM_RATE_LIMITED");
+        assert!(ts.server.blocked_uids.contains("mallory"));
+        assert_eq!(
+            ts.server.check_room_op("mallory", "r2", true),
+            Err(RoomOpError::Blocked)
+        );
+        assert_eq!(
+            store.get("mallory").unwrap().unwrap().reason.as_deref(),
+            Some("room abuse")
+        );
+        let _ = std::fs::remove_dir_all(&path);
+    }
+}
@@ -0,0 +1,232 @@
+//! 批量管理房间成员 / Bulk room membership
+//!
+//! 房间管理员（`[rooms.admins]`，与置顶相同）发送 `room_invite` / `room_kick`
+//! （`{room_id, uids}`），成员变更经 `storage.room.update_members` 一次写入存储，随后更新本节点的
+//! 房间成员表，被移出的在线用户立即不再收到该房间的消息。实际变更的用户各收到一条
+//! `room_membership_changed`（`{room_id, action, uid, by}`），管理员收到 `room_invite_ok` /
+//! `room_kick_ok`（`{room_id, uids}`，仅含实际变更的 uid）。
+//! Room admins (`[rooms.admins]`, as for pins) send `room_invite` / `room_kick` (`{room_id, uids}`).
+//! The change is written to storage in one `storage.room.update_members` call, then applied to this
+//! node's room table, so kicked online users stop receiving the room's messages at once. Each user
+//! actually affected gets a `room_membership_changed` (`{room_id, action, uid, by}`), and the admin
+//! gets `room_invite_ok` / `room_kick_ok` (`{room_id, uids}`, only the uids that changed).
+
+use crate::domain::message::{ErrorCode, ImMessage};
+use crate::server::VConnectIMServer;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use tokio_tungstenite::tungstenite::Message;
+
+/// 成员变更通知 / Membership change notification
+pub const ROOM_MEMBERSHIP_CHANGED: &str = "room_membership_changed";
+
+/// 单次请求最多处理的 uid 数 / Max uids handled per request
+pub const MAX_BULK_UIDS: usize = 1000;
+
+impl VConnectIMServer {
+    /// 处理 `room_invite`（`invite`）或 `room_kick`，失败时向客户端回 `error`
+    /// Handle `room_invite` (`invite`) or `room_kick`, answering the client with an `error` on failure
+    pub async fn handle_room_membership(
+        &self,
+        client_id: &str,
+        data: &Value,
+        invite: bool,
+    ) -> anyhow::Result<()> {
+        let reply = match self.apply_room_membership(client_id, data, invite).await {
+            Ok(reply) | Err(reply) => reply,
+        };
+        let txt = serde_json::to_string(&reply)?;
+        self.send_message_to_client(client_id, Message::Text(txt))
+            .await
+    }
+
+    async fn apply_room_membership(
+        &self,
+        client_id: &str,
+        data: &Value,
+        invite: bool,
+    ) -> Result<ImMessage, ImMessage> {
+        let op = if invite { "room_invite" } else { "room_kick" };
+        let admin = self
+            .connections
+            .get(client_id)
+            .and_then(|c| c.uid.clone())
+            .ok_or_else(|| {
+                ImMessage::error(
+                    ErrorCode::Unauthenticated,
+                    format!("{} requires auth uid", op),
+                )
+            })?;
+        let room_id = data
+            .get("room_id")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if room_id.is_empty() {
+            return Err(ImMessage::error(
+                ErrorCode::MissingRoom,
+                format!("{} requires room_id", op),
+            ));
+        }
+        let requested: BTreeSet<String> = data
+            .get("uids")
+            .and_then(Value::as_array)
+            .map(|uids| {
+                uids.iter()
+                    .filter_map(Value::as_str)
+                    .filter(|uid| !uid.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        if requested.is_empty() || requested.len() > MAX_BULK_UIDS {
+            return Err(ImMessage::error(
+                ErrorCode::MissingTarget,
+                format!("{} requires 1 to {} uids", op, MAX_BULK_UIDS),
+            ));
+        }
+        if !self.pin_policy.is_admin(room_id, &admin) {
+            return Err(ImMessage::error(
+                ErrorCode::Forbidden,
+                format!("only room admins may use {}", op),
+            ));
+        }
+
+        // 只处理实际会变化的 uid / Only uids whose membership actually changes
+        let changed: Vec<String> = requested
+            .into_iter()
+            .filter(|uid| invite != self.rooms.get(room_id).is_some_and(|set| set.contains(uid)))
+            .collect();
+        if !changed.is_empty() {
+            if let Some(pool) = self.plugin_connection_pool.as_ref() {
+                let (add, remove): (&[String], &[String]) = if invite {
+                    (&changed, &[])
+                } else {
+                    (&[], &changed)
+                };
+                pool.storage_update_room_members(room_id, add, remove)
+                    .await
+                    .map_err(|e| {
+                        tracing::warn!("{} in {} failed: {}", op, room_id, e);
+                        ImMessage::error(ErrorCode::StorageError, "room membership storage failed")
+                    })?;
+            }
+            if invite {
+                let set = self.rooms.entry(room_id.to_string()).or_default();
+                for uid in &changed {
+                    set.insert(uid.clone());
+                }
+            } else if let Some(set) = self.rooms.get(room_id) {
+                for uid in &changed {
+                    set.remove(uid);
+                }
+            }
+
+            let action = if invite { "invite" } else { "kick" };
+            for uid in &changed {
+                let notice = ImMessage {
+                    msg_type: ROOM_MEMBERSHIP_CHANGED.to_string(),
+                    data: json!({"room_id": room_id, "action": action, "uid": uid, "by": admin}),
+                    target_uid: Some(uid.clone()),
+                    priority: Default::default(),
+                    reply_to: None,
+                };
+                if let Err(e) = self.push_to_uid(uid, &notice).await {
+                    tracing::debug!("{} notice to {} not delivered: {}", op, uid, e);
+                }
+            }
+        }
+
+        Ok(ImMessage {
+            msg_type: format!("{}_ok", op),
+            data: json!({"room_id": room_id, "uids": changed}),
+            target_uid: None,
+            priority: Default::default(),
+            reply_to: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::runtime::{PluginConnectionPool, PluginRuntimeManager};
+    use crate::service::pins::PinPolicy;
+    use crate::storage::builtin::BuiltinStorage;
+    use crate::testkit::{im, recv_typed, TestServer};
+    use std::collections::{HashMap, HashSet};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_admin_invites_and_kicks_in_bulk() {
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = Arc::new(PluginConnectionPool::new(manager));
+        pool.enable_builtin_storage(Arc::new(BuiltinStorage::open_temporary().unwrap()));
+        let policy = PinPolicy {
+            admins: HashMap::from([("r1".to_string(), HashSet::from(["alice".to_string()]))]),
+            ..Default::default()
+        };
+        let ts = TestServer::build(|server| {
+            server
+                .with_plugin_connection_pool(pool.clone())
+                .with_pin_policy(policy)
+        });
+        let (alice, mut alice_rx) = ts.add_client("alice");
+        let (bob, mut bob_rx) = ts.add_client("bob");
+        let (_carol, mut carol_rx) = ts.add_client("carol");
+        ts.server.http_join_room("r1", "alice").await;
+        let members =
+            |kind: &str, uids: &[&str]| im(kind, json!({"room_id": "r1", "uids": uids}), None);
+
+        // 已是成员的 alice 不计入变更 / alice is already a member and is not counted as changed
+        ts.send(
+            &alice,
+            members("room_invite", &["bob", "carol", "alice", "bob"]),
+        )
+        .await
+        .unwrap();
+        let ok: ImMessage = recv_typed(&mut alice_rx).await;
+        assert_eq!(ok.msg_type, "room_invite_ok");
+        assert_eq!(ok.data["uids"], json!(["bob", "carol"]));
+        for rx in [&mut bob_rx, &mut carol_rx] {
+            let notice: ImMessage = recv_typed(rx).await;
+            assert_eq!(notice.msg_type, ROOM_MEMBERSHIP_CHANGED);
+            assert_eq!(
+                (notice.data["action"].as_str(), notice.data["by"].as_str()),
+                (Some("invite"), Some("alice"))
+            );
+        }
+        let mut stored = pool.storage_list_room_members("r1").await.unwrap();
+        stored.sort();
+        assert_eq!(stored, ["alice", "bob", "carol"]);
+
+        // 非管理员不能踢人 / Non-admins cannot kick
+        ts.send(&bob, members("room_kick", &["carol"]))
+            .await
+            .unwrap();
+        let err: ImMessage = recv_typed(&mut bob_rx).await;
+        assert_eq!(err.data["code"], json!(ErrorCode::Forbidden));
+
+        ts.send(&alice, members("room_kick", &["bob", "dave"]))
+            .await
+            .unwrap();
+        let ok: ImMessage = recv_typed(&mut alice_rx).await;
+        assert_eq!(
+            (ok.msg_type.as_str(), ok.data["uids"].clone()),
+            ("room_kick_ok", json!(["bob"]))
+        );
+        let notice: ImMessage = recv_typed(&mut bob_rx).await;
+        assert_eq!(notice.data["action"], "kick");
+        assert_eq!(
+            ts.server.room_members("r1").members,
+            ["alice".to_string(), "carol".to_string()]
+        );
+
+        // 被踢的 bob 不再收到房间消息 / The kicked bob no longer receives room messages
+        ts.server
+            .http_group_send_message("r1".into(), "alice".into(), json!({"text": "hi"}), None)
+            .await;
+        let msg: ImMessage = recv_typed(&mut carol_rx).await;
+        assert_eq!(msg.data["content"]["text"], "hi");
+        assert!(bob_rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,350 @@
+//! 房间元数据与附近房间 / Room metadata and nearby rooms
+//!
+//! 房间可在 [`RoomMeta`] 中带一个位置。[`RoomMetaStore`] 是本地 sled 库（`rooms.meta_path`）：`meta`
+//! 树按房间保存元数据，`geo` 树以 `{12 位 geohash}:{room_id}` 为键索引有位置的房间。附近查询按半径
+//! 选 geohash 精度（见 [`v::geohash_precision_for_radius`]），前缀扫描中心单元与 8 个邻居作为预筛，
+//! 再用 haversine 距离精确过滤并按距离排序；半径大到没有合适精度（或靠近两极）时退化为全量扫描。
+//! 元数据仅本节点，不在集群内复制。
+//! A room may carry a location in its [`RoomMeta`]. [`RoomMetaStore`] is a local sled database
+//! (`rooms.meta_path`): the `meta` tree holds metadata per room and the `geo` tree indexes located
+//! rooms under `{12-char geohash}:{room_id}`. A nearby query picks a geohash precision for the
+//! radius (see [`v::geohash_precision_for_radius`]), prefix-scans the center cell and its 8
+//! neighbors as a prefilter, then refines by haversine distance and sorts by it; when no precision
+//! fits the radius (or near the poles) it falls back to a full scan. Metadata is node-local and not
+//! replicated across the cluster.
+
+use crate::server::VConnectIMServer;
+use crate::service::local_store::LazySled;
+use anyhow::{anyhow, Result};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// 默认库路径 / Default database path
+pub const DEFAULT_ROOM_META_PATH: &str = "./data/room-meta";
+
+/// 附近查询的最大半径（米）/ Max radius of a nearby query in meters
+pub const MAX_NEARBY_RADIUS_M: f64 = 100_000.0;
+
+/// 经纬度 / Latitude and longitude
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GeoPoint {
+    pub lat: f64,
+    pub lng: f64,
+}
+
+impl GeoPoint {
+    /// 坐标是否在有效范围内 / Whether the coordinates are in range
+    pub fn is_valid(&self) -> bool {
+        (-90.0..=90.0).contains(&self.lat) && (-180.0..=180.0).contains(&self.lng)
+    }
+
+    pub fn distance_m(&self, other: &GeoPoint) -> f64 {
+        v::haversine_m(self.lat, self.lng, other.lat, other.lng)
+    }
+}
+
+/// 房间元数据 / Room metadata
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoomMeta {
+    pub room_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<GeoPoint>,
+    /// 为该房间的消息开启自动翻译（见 [`crate::service::translation`]）
+    /// Auto-translate this room's messages (see [`crate::service::translation`])
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub auto_translate: bool,
+    /// 消息保留秒数，缺省取 `storage.default_retention_secs`（见 [`crate::service::retention`]）
+    /// Message retention in seconds, defaulting to `storage.default_retention_secs` (see [`crate::service::retention`])
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retention_secs: Option<u64>,
+}
+
+/// 附近查询结果 / One nearby query result
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NearbyRoom {
+    #[serde(flatten)]
+    pub meta: RoomMeta,
+    pub distance_m: f64,
+}
+
+/// 房间元数据与位置索引的持久化 / Persistence for room metadata and its location index
+pub struct RoomMetaStore {
+    /// 首次写入时打开，从未设置过元数据的节点不会创建目录
+    /// Opened on the first write, so nodes that never set metadata create no directory
+    db: LazySled,
+    /// 串行化写入，保证 `meta` 与 `geo` 一致 / Serializes writes so `meta` and `geo` stay consistent
+    write_lock: Mutex<()>,
+}
+
+impl Default for RoomMetaStore {
+    fn default() -> Self {
+        let path = v::get_global_config_manager()
+            .map(|cm| cm.get_or("rooms.meta_path", DEFAULT_ROOM_META_PATH.to_string()))
+            .unwrap_or_else(|_| DEFAULT_ROOM_META_PATH.to_string());
+        Self::new(crate::config::resolve_path(path))
+    }
+}
+
+fn geo_key(location: &GeoPoint, room_id: &str) -> String {
+    format!(
+        "{}:{}",
+        v::geohash_encode(location.lat, location.lng, v::GEOHASH_MAX_PRECISION),
+        room_id
+    )
+}
+
+impl RoomMetaStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            db: LazySled::new(path),
+            write_lock: Mutex::new(()),
+        }
+    }
+
+    /// 写入或覆盖元数据并更新位置索引 / Write or overwrite metadata and update the location index
+    pub fn put(&self, meta: &RoomMeta) -> Result<()> {
+        if meta.location.is_some_and(|location| !location.is_valid()) {
+            return Err(anyhow!("location out of range"));
+        }
+        let _guard = self.write_lock.lock();
+        let (db, metas, geo) = self.open()?;
+        let previous = match metas.get(meta.room_id.as_bytes())? {
+            Some(value) => serde_json::from_slice::<RoomMeta>(&value)?.location,
+            None => None,
+        };
+        if let Some(previous) = previous.filter(|p| Some(*p) != meta.location) {
+            geo.remove(geo_key(&previous, &meta.room_id).as_bytes())?;
+        }
+        if let Some(location) = meta.location {
+            geo.insert(geo_key(&location, &meta.room_id).as_bytes(), &[])?;
+        }
+        metas.insert(meta.room_id.as_bytes(), serde_json::to_vec(meta)?)?;
+        db.flush()?;
+        Ok(())
+    }
+
+    /// 读取元数据 / Read metadata
+    pub fn get(&self, room_id: &str) -> Result<Option<RoomMeta>> {
+        let Some((_, metas, _)) = self.existing()? else {
+            return Ok(None);
+        };
+        match metas.get(room_id.as_bytes())? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// 全部房间元数据 / Every room's metadata
+    pub fn list(&self) -> Result<Vec<RoomMeta>> {
+        let Some((_, metas, _)) = self.existing()? else {
+            return Ok(Vec::new());
+        };
+        metas
+            .iter()
+            .values()
+            .map(|value| Ok(serde_json::from_slice(&value?)?))
+            .collect()
+    }
+
+    /// 半径内的房间，按距离升序，最多 `limit` 个
+    /// Rooms within the radius, nearest first, at most `limit`
+    pub fn nearby(&self, center: GeoPoint, radius_m: f64, limit: usize) -> Result<Vec<NearbyRoom>> {
+        let Some((_, metas, geo)) = self.existing()? else {
+            return Ok(Vec::new());
+        };
+        let candidates: Vec<String> = match v::geohash_precision_for_radius(radius_m, center.lat) {
+            0 => metas
+                .iter()
+                .keys()
+                .map(|key| Ok(String::from_utf8_lossy(&key?).into_owned()))
+                .collect::<Result<_>>()?,
+            precision => {
+                let cell = v::geohash_encode(center.lat, center.lng, precision);
+                let mut cells = v::geohash_neighbors(&cell);
+                cells.push(cell);
+                let mut ids = Vec::new();
+                for cell in cells {
+                    for key in geo.scan_prefix(cell.as_bytes()).keys() {
+                        let key = key?;
+                        if let Some((_, room_id)) = std::str::from_utf8(&key)?.split_once(':') {
+                            ids.push(room_id.to_string());
+                        }
+                    }
+                }
+                ids
+            }
+        };
+
+        let mut rooms = Vec::new();
+        for room_id in candidates {
+            let Some(value) = metas.get(room_id.as_bytes())? else {
+                continue;
+            };
+            let meta: RoomMeta = serde_json::from_slice(&value)?;
+            let Some(distance_m) = meta.location.map(|l| center.distance_m(&l)) else {
+                continue;
+            };
+            if distance_m <= radius_m {
+                rooms.push(NearbyRoom { meta, distance_m });
+            }
+        }
+        rooms.sort_by(|a, b| {
+            a.distance_m
+                .total_cmp(&b.distance_m)
+                .then_with(|| a.meta.room_id.cmp(&b.meta.room_id))
+        });
+        rooms.truncate(limit);
+        Ok(rooms)
+    }
+
+    fn open(&self) -> Result<(sled::Db, sled::Tree, sled::Tree)> {
+        Self::trees(self.db.open()?)
+    }
+
+    /// 只打开已存在的库 / Open the database only if it exists
+    fn existing(&self) -> Result<Option<(sled::Db, sled::Tree, sled::Tree)>> {
+        self.db.existing()?.map(Self::trees).transpose()
+    }
+
+    fn trees(db: sled::Db) -> Result<(sled::Db, sled::Tree, sled::Tree)> {
+        let metas = db.open_tree("meta")?;
+        let geo = db.open_tree("geo")?;
+        Ok((db, metas, geo))
+    }
+}
+
+impl VConnectIMServer {
+    /// 设置房间元数据 / Set a room's metadata
+    pub fn set_room_meta(&self, meta: &RoomMeta) -> Result<()> {
+        self.room_meta.put(meta)
+    }
+
+    /// 房间元数据 / A room's metadata
+    pub fn room_meta(&self, room_id: &str) -> Result<Option<RoomMeta>> {
+        self.room_meta.get(room_id)
+    }
+
+    /// 附近的房间 / Nearby rooms
+    pub fn nearby_rooms(
+        &self,
+        center: GeoPoint,
+        radius_m: f64,
+        limit: usize,
+    ) -> Result<Vec<NearbyRoom>> {
+        self.room_meta.nearby(center, radius_m, limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("vgo-room-meta-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        path
+    }
+
+    fn room(room_id: &str, lat: f64, lng: f64) -> RoomMeta {
+        RoomMeta {
+            room_id: room_id.into(),
+            location: Some(GeoPoint { lat, lng }),
+            ..Default::default()
+        }
+    }
+
+    fn ids(rooms: &[NearbyRoom]) -> Vec<&str> {
+        rooms.iter().map(|r| r.meta.room_id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_nearby_rooms_by_distance() {
+        let path = temp_path("nearby");
+        let server =
+            VConnectIMServer::new().with_room_meta_store(Arc::new(RoomMetaStore::new(&path)));
+        // 上海人民广场附近 / Around People's Square, Shanghai
+        let center = GeoPoint {
+            lat: 31.2304,
+            lng: 121.4737,
+        };
+        server
+            .set_room_meta(&room("bund", 31.2400, 121.4900))
+            .unwrap(); // ~1.9km
+        server
+            .set_room_meta(&room("square", 31.2310, 121.4740))
+            .unwrap(); // ~70m
+        server
+            .set_room_meta(&room("pudong", 31.2200, 121.5440))
+            .unwrap(); // ~6.8km
+        server
+            .set_room_meta(&room("beijing", 39.9042, 116.4074))
+            .unwrap();
+        server
+            .set_room_meta(&RoomMeta {
+                room_id: "nowhere".into(),
+                name: Some("no location".into()),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let near = server.nearby_rooms(center, 3_000.0, 10).unwrap();
+        assert_eq!(ids(&near), ["square", "bund"]);
+        assert!(near[0].distance_m < 100.0);
+        assert_eq!(
+            ids(&server.nearby_rooms(center, 10_000.0, 10).unwrap()),
+            ["square", "bund", "pudong"]
+        );
+        assert_eq!(
+            ids(&server.nearby_rooms(center, 10_000.0, 1).unwrap()),
+            ["square"]
+        );
+        let far = server
+            .nearby_rooms(center, MAX_NEARBY_RADIUS_M, 10)
+            .unwrap();
+        assert_eq!(far.len(), 3);
+
+        // 移动房间会替换旧的索引项 / Moving a room replaces its old index entry
+        server
+            .set_room_meta(&room("square", 39.9050, 116.4080))
+            .unwrap();
+        assert_eq!(
+            ids(&server.nearby_rooms(center, 3_000.0, 10).unwrap()),
+            ["bund"]
+        );
+        let beijing = GeoPoint {
+            lat: 39.9042,
+            lng: 116.4074,
+        };
+        assert_eq!(
+            ids(&server.nearby_rooms(beijing, 1_000.0, 10).unwrap()),
+            ["beijing", "square"]
+        );
+        assert_eq!(
+            server
+                .room_meta("nowhere")
+                .unwrap()
+                .unwrap()
+                .name
+                .as_deref(),
+            Some("no location")
+        );
+
+        assert!(server.set_room_meta(&room("bad", 91.0, 0.0)).is_err());
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_nothing_is_created_until_a_write() {
+        let path = temp_path("lazy");
+        let store = RoomMetaStore::new(&path);
+        let center = GeoPoint { lat: 0.0, lng: 0.0 };
+        assert!(store.nearby(center, 1_000.0, 10).unwrap().is_empty());
+        assert_eq!(store.get("r1").unwrap(), None);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,561 @@
+//! 定时消息 / Scheduled messages
+//!
+//! 客户端发送 `schedule_message`（`{deliver_at, content}`，私聊带 `target_id`，群消息带 `room_id`），
+//! 服务端写入本地 sled 库（`scheduler.path`）的 `pending` 树，键为 `deliver_at` 加序号，按到期时间有序；
+//! 后台任务每 `scheduler.poll_interval_ms` 扫描到期消息，经与 HTTP 发送相同的路径投递
+//! （私聊同 `POST /v1/message/send`，群消息先持久化并复制再扇出）。`cancel_scheduled(schedule_id)` 可在
+//! 投递前取消，仅限创建者。
+//! Clients send `schedule_message` (`{deliver_at, content}` with `target_id` for a private message or
+//! `room_id` for a group one). The server writes it to the `pending` tree of a local sled database
+//! (`scheduler.path`), keyed by `deliver_at` plus a sequence so entries are ordered by due time; a
+//! background task scans due messages every `scheduler.poll_interval_ms` and delivers them through
+//! the same path as the HTTP API (private messages as `POST /v1/message/send`, group messages
+//! persisted and replicated before fan-out). `cancel_scheduled(schedule_id)` cancels before
+//! delivery, for the creator only.
+//!
+//! 投递语义 / Delivery semantics:
+//! - 至少一次：投递成功后才删除记录，投递与删除之间崩溃会在重启后再次投递（消息ID不同，
+//!   `content` 相同）；投递失败时每 `scheduler.retry_ms` 重试，最多 `scheduler.max_attempts` 次后丢弃。
+//!   At least once: the entry is removed only after delivery, so a crash in between delivers it
+//!   again after restart (with a new message id and the same `content`); failed deliveries are
+//!   retried every `scheduler.retry_ms` and dropped after `scheduler.max_attempts` attempts.
+//! - 重启恢复：库落盘，启动时重新打开并扫描，停机期间到期的消息在首轮立即投递。
+//!   Restart recovery: the database is on disk and re-scanned at startup; messages that fell due
+//!   while the node was down are delivered in the first round.
+//! - 仅本节点：定时消息由接收请求的节点保存与投递，不在集群内复制。
+//!   Node-local: a scheduled message is stored and delivered by the node that accepted it and is
+//!   not replicated across the cluster.
+
+use crate::domain::message::{ErrorCode, HttpSendMessageRequest, ImMessage};
+use crate::server::VConnectIMServer;
+use crate::service::local_store::LazySled;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+
+/// 默认库路径 / Default database path
+pub const DEFAULT_SCHEDULER_PATH: &str = "./data/scheduled-messages";
+
+/// 定时器配置（`[scheduler]`）/ Scheduler config (`[scheduler]`)
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    pub path: PathBuf,
+    pub poll_interval_ms: u64,
+    /// 最远可预约的时间（相对当前）/ How far ahead a message may be scheduled
+    pub max_delay_ms: i64,
+    /// 包含首次投递在内的最多尝试次数 / Max attempts including the first delivery
+    pub max_attempts: u32,
+    pub retry_ms: u64,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from(DEFAULT_SCHEDULER_PATH),
+            poll_interval_ms: 1000,
+            max_delay_ms: 30 * 24 * 3600 * 1000,
+            max_attempts: 5,
+            retry_ms: 5000,
+        }
+    }
+}
+
+impl SchedulerConfig {
+    /// 读取 `scheduler.*` / Read `scheduler.*`
+    pub fn from_config() -> Self {
+        let defaults = Self::default();
+        let Ok(cm) = v::get_global_config_manager() else {
+            return defaults;
+        };
+        Self {
+            path: crate::config::resolve_path(
+                cm.get_or("scheduler.path", DEFAULT_SCHEDULER_PATH.to_string()),
+            ),
+            poll_interval_ms: cm
+                .get_or("scheduler.poll_interval_ms", defaults.poll_interval_ms)
+                .max(1),
+            max_delay_ms: cm.get_or("scheduler.max_delay_ms", defaults.max_delay_ms),
+            max_attempts: cm
+                .get_or("scheduler.max_attempts", defaults.max_attempts)
+                .max(1),
+            retry_ms: cm.get_or("scheduler.retry_ms", defaults.retry_ms),
+        }
+    }
+}
+
+/// 一条定时消息 / One scheduled message
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledMessage {
+    pub schedule_id: String,
+    pub from_uid: String,
+    /// 私聊目标 / Private message target
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to_uid: Option<String>,
+    /// 群消息房间 / Group message room
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub room_id: Option<String>,
+    pub content: Value,
+    pub deliver_at: i64,
+    pub created_at: i64,
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+/// 定时消息存储与调度 / Scheduled message store and scheduler
+pub struct MessageScheduler {
+    config: SchedulerConfig,
+    /// 首次预约时打开，从未预约过的节点不会创建目录
+    /// Opened on the first schedule, so nodes that never schedule create no directory
+    db: LazySled,
+    delivered: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl Default for MessageScheduler {
+    fn default() -> Self {
+        Self::new(SchedulerConfig::from_config())
+    }
+}
+
+impl MessageScheduler {
+    pub fn new(config: SchedulerConfig) -> Self {
+        Self {
+            db: LazySled::new(config.path.clone()),
+            config,
+            delivered: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    pub fn config(&self) -> &SchedulerConfig {
+        &self.config
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.config.poll_interval_ms)
+    }
+
+    /// 保存一条定时消息并落盘 / Store a scheduled message and flush it to disk
+    pub async fn schedule(&self, msg: &ScheduledMessage) -> Result<()> {
+        let (db, pending, index) = self.open()?;
+        let key = pending_key(msg.deliver_at, db.generate_id()?);
+        pending.insert(&key, serde_json::to_vec(msg)?)?;
+        index.insert(msg.schedule_id.as_bytes(), key)?;
+        db.flush_async().await?;
+        Ok(())
+    }
+
+    /// 取消 `uid` 创建的定时消息；不存在、已投递或不属于 `uid` 时返回 false
+    /// Cancel a message scheduled by `uid`; false when missing, already delivered or owned by someone else
+    pub async fn cancel(&self, schedule_id: &str, uid: &str) -> Result<bool> {
+        let Some((db, pending, index)) = self.existing()? else {
+            return Ok(false);
+        };
+        let Some(key) = index.get(schedule_id.as_bytes())? else {
+            return Ok(false);
+        };
+        let owned = match pending.get(&key)? {
+            Some(value) => serde_json::from_slice::<ScheduledMessage>(&value)?.from_uid == uid,
+            None => false,
+        };
+        if !owned {
+            return Ok(false);
+        }
+        pending.remove(&key)?;
+        index.remove(schedule_id.as_bytes())?;
+        db.flush_async().await?;
+        Ok(true)
+    }
+
+    /// 本轮开始时已到期的消息（按到期时间）/ Messages due at the start of the round, by due time
+    pub fn due(&self, now_ms: i64) -> Result<Vec<(sled::IVec, ScheduledMessage)>> {
+        let Some((_, pending, _)) = self.existing()? else {
+            return Ok(Vec::new());
+        };
+        pending
+            .range(..pending_key(now_ms, u64::MAX))
+            .map(|item| {
+                let (key, value) = item?;
+                Ok((key, serde_json::from_slice(&value)?))
+            })
+            .collect()
+    }
+
+    /// 记录一次投递的结果：成功或用尽次数时删除，否则推迟 `retry_ms` 重试；投递期间被取消的不再重试
+    /// Record a delivery outcome: remove on success or when attempts run out, otherwise retry after
+    /// `retry_ms`; entries cancelled during delivery are not retried
+    pub async fn settle(
+        &self,
+        key: &[u8],
+        mut msg: ScheduledMessage,
+        delivered: bool,
+        now_ms: i64,
+    ) -> Result<()> {
+        let (db, pending, index) = self.open()?;
+        pending.remove(key)?;
+        let still_scheduled = index.get(msg.schedule_id.as_bytes())?.as_deref() == Some(key);
+        msg.attempts += 1;
+        if delivered {
+            self.delivered.fetch_add(1, Ordering::Relaxed);
+            index.remove(msg.schedule_id.as_bytes())?;
+        } else if !still_scheduled {
+            // 已取消 / Cancelled meanwhile
+        } else if msg.attempts >= self.config.max_attempts {
+            tracing::warn!(
+                "☠️  定时消息 {} 投递 {} 次失败后丢弃 / Scheduled message {} dropped after {} failed attempts",
+                msg.schedule_id,
+                msg.attempts,
+                msg.schedule_id,
+                msg.attempts
+            );
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            index.remove(msg.schedule_id.as_bytes())?;
+        } else {
+            let retry_at = now_ms.saturating_add(self.config.retry_ms as i64);
+            let key = pending_key(retry_at, db.generate_id()?);
+            pending.insert(&key, serde_json::to_vec(&msg)?)?;
+            index.insert(msg.schedule_id.as_bytes(), key)?;
+        }
+        db.flush_async().await?;
+        Ok(())
+    }
+
+    /// 启动时重新打开上次运行留下的库，返回待投递数
+    /// Reopen the database left by a previous run at startup; returns the pending count
+    pub fn recover(&self) -> Result<usize> {
+        Ok(self.existing()?.map_or(0, |(_, pending, _)| pending.len()))
+    }
+
+    /// 指标快照（JSON）/ Metrics snapshot as JSON
+    pub fn snapshot(&self) -> Value {
+        let pending = match self.existing() {
+            Ok(Some((_, pending, _))) => pending.len(),
+            _ => 0,
+        };
+        json!({
+            "pending": pending,
+            "delivered": self.delivered.load(Ordering::Relaxed),
+            "dropped": self.dropped.load(Ordering::Relaxed),
+        })
+    }
+
+    fn open(&self) -> Result<(sled::Db, sled::Tree, sled::Tree)> {
+        Self::trees(self.db.open()?)
+    }
+
+    /// 只打开已存在的库 / Open the database only if it exists
+    fn existing(&self) -> Result<Option<(sled::Db, sled::Tree, sled::Tree)>> {
+        self.db.existing()?.map(Self::trees).transpose()
+    }
+
+    fn trees(db: sled::Db) -> Result<(sled::Db, sled::Tree, sled::Tree)> {
+        let pending = db.open_tree("pending")?;
+        let index = db.open_tree("index")?;
+        Ok((db, pending, index))
+    }
+}
+
+/// 待投递键：到期时间（大端，负值按 0）加序号 / Pending key: due time (big endian, negatives as 0) plus a sequence
+fn pending_key(deliver_at: i64, seq: u64) -> Vec<u8> {
+    let mut key = (deliver_at.max(0) as u64).to_be_bytes().to_vec();
+    key.extend_from_slice(&seq.to_be_bytes());
+    key
+}
+
+impl VConnectIMServer {
+    /// 处理 `schedule_message` / Handle `schedule_message`
+    pub async fn handle_schedule_message(&self, client_id: &str, msg: &ImMessage) -> Result<()> {
+        let resp = match self.accept_schedule(client_id, msg).await {
+            Ok(scheduled) => ImMessage {
+                msg_type: "message_scheduled".to_string(),
+                data: json!({
+                    "schedule_id": scheduled.schedule_id,
+                    "deliver_at": scheduled.deliver_at,
+                }),
+                target_uid: None,
+                priority: Default::default(),
+                reply_to: None,
+            },
+            Err(err) => err,
+        };
+        let txt = serde_json::to_string(&resp)?;
+        self.send_message_to_client(client_id, Message::Text(txt))
+            .await
+    }
+
+    async fn accept_schedule(
+        &self,
+        client_id: &str,
+        msg: &ImMessage,
+    ) -> Result<ScheduledMessage, ImMessage> {
+        let uid = self
+            .connections
+            .get(client_id)
+            .and_then(|c| c.uid.clone())
+            .ok_or_else(|| {
+                ImMessage::error(
+                    ErrorCode::Unauthenticated,
+                    "schedule_message requires auth uid",
+                )
+            })?;
+        let room_id = msg
+            .data
+            .get("room_id")
+            .and_then(Value::as_str)
+            .filter(|r| !r.is_empty())
+            .map(str::to_string);
+        if msg.target_uid.is_none() && room_id.is_none() {
+            return Err(ImMessage::error(
+                ErrorCode::MissingTarget,
+                "schedule_message requires target_id or room_id",
+            ));
+        }
+        let now = chrono::Utc::now().timestamp_millis();
+        let deliver_at = msg
+            .data
+            .get("deliver_at")
+            .and_then(Value::as_i64)
+            .filter(|at| *at <= now.saturating_add(self.scheduler.config().max_delay_ms))
+            .ok_or_else(|| {
+                ImMessage::error(
+                    ErrorCode::InvalidSchedule,
+                    format!(
+                        "deliver_at must be a millisecond timestamp at most {} ms ahead",
+                        self.scheduler.config().max_delay_ms
+                    ),
+                )
+            })?;
+        let scheduled = ScheduledMessage {
+            schedule_id: uuid::Uuid::new_v4().to_string(),
+            from_uid: uid,
+            to_uid: if room_id.is_some() {
+                None
+            } else {
+                msg.target_uid.clone()
+            },
+            room_id,
+            content: msg.data.get("content").cloned().unwrap_or(Value::Null),
+            deliver_at,
+            created_at: now,
+            attempts: 0,
+        };
+        self.scheduler.schedule(&scheduled).await.map_err(|e| {
+            tracing::warn!("schedule_message failed: {}", e);
+            ImMessage::error(ErrorCode::StorageError, "scheduler storage unavailable")
+        })?;
+        Ok(scheduled)
+    }
+
+    /// 处理 `cancel_scheduled` / Handle `cancel_scheduled`
+    pub async fn handle_cancel_scheduled(&self, client_id: &str, data: &Value) -> Result<()> {
+        let schedule_id = data
+            .get("schedule_id")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let uid = self.connections.get(client_id).and_then(|c| c.uid.clone());
+        let resp = match uid {
+            None => ImMessage::error(
+                ErrorCode::Unauthenticated,
+                "cancel_scheduled requires auth uid",
+            ),
+            Some(uid) => match self.scheduler.cancel(schedule_id, &uid).await {
+                Ok(true) => ImMessage {
+                    msg_type: "scheduled_cancelled".to_string(),
+                    data: json!({"schedule_id": schedule_id}),
+                    target_uid: None,
+                    priority: Default::default(),
+                    reply_to: None,
+                },
+                Ok(false) => ImMessage::error(ErrorCode::NotFound, "scheduled message not found"),
+                Err(e) => {
+                    tracing::warn!("cancel_scheduled failed: {}", e);
+                    ImMessage::error(ErrorCode::StorageError, "scheduler storage unavailable")
+                }
+            },
+        };
+        let txt = serde_json::to_string(&resp)?;
+        self.send_message_to_client(client_id, Message::Text(txt))
+            .await
+    }
+
+    /// 投递到期的定时消息，返回本轮处理数；维护期间不投递也不消耗重试次数
+    /// Deliver due scheduled messages; returns how many were processed. Nothing is delivered, and
+    /// no attempt is used up, during maintenance
+    pub async fn run_due_scheduled(&self, now_ms: i64) -> Result<usize> {
+        if self.maintenance.is_enabled() {
+            return Ok(0);
+        }
+        let due = self.scheduler.due(now_ms)?;
+        for (key, msg) in &due {
+            let delivered = self.deliver_scheduled(msg).await;
+            self.scheduler
+                .settle(key, msg.clone(), delivered, now_ms)
+                .await?;
+        }
+        Ok(due.len())
+    }
+
+    /// 走正常发送路径投递一条定时消息 / Deliver one scheduled message through the normal send path
+    async fn deliver_scheduled(&self, msg: &ScheduledMessage) -> bool {
+        match (&msg.room_id, &msg.to_uid) {
+            (Some(room_id), _) => {
+                self.http_group_send_message(
+                    room_id.clone(),
+                    msg.from_uid.clone(),
+                    msg.content.clone(),
+                    Some("group_message".to_string()),
+                )
+                .await
+                .success
+            }
+            (None, Some(to_uid)) => {
+                self.http_send_message(HttpSendMessageRequest {
+                    from_uid: msg.from_uid.clone(),
+                    to_uid: to_uid.clone(),
+                    content: msg.content.clone(),
+                    message_type: None,
+                })
+                .await
+                .success
+            }
+            (None, None) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::{im, recv_typed, TestServer};
+    use std::sync::Arc;
+
+    fn config(name: &str) -> SchedulerConfig {
+        let path =
+            std::env::temp_dir().join(format!("vgo-scheduler-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        SchedulerConfig {
+            path,
+            retry_ms: 10,
+            max_attempts: 2,
+            ..Default::default()
+        }
+    }
+
+    fn scheduled(id: &str, deliver_at: i64) -> ScheduledMessage {
+        ScheduledMessage {
+            schedule_id: id.into(),
+            from_uid: "alice".into(),
+            to_uid: Some("bob".into()),
+            room_id: None,
+            content: json!({"text": id}),
+            deliver_at,
+            created_at: 0,
+            attempts: 0,
+        }
+    }
+
+    fn ids(due: &[(sled::IVec, ScheduledMessage)]) -> Vec<&str> {
+        due.iter().map(|(_, m)| m.schedule_id.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_due_order_cancel_and_restart_recovery() {
+        let config = config("recovery");
+        let scheduler = MessageScheduler::new(config.clone());
+        assert!(scheduler.due(i64::MAX).unwrap().is_empty());
+        for (id, at) in [("late", 300), ("early", 100), ("mid", 200)] {
+            scheduler.schedule(&scheduled(id, at)).await.unwrap();
+        }
+        assert_eq!(ids(&scheduler.due(250).unwrap()), ["early", "mid"]);
+
+        // 只有创建者能取消 / Only the creator can cancel
+        assert!(!scheduler.cancel("mid", "mallory").await.unwrap());
+        assert!(scheduler.cancel("mid", "alice").await.unwrap());
+        assert!(!scheduler.cancel("mid", "alice").await.unwrap());
+        drop(scheduler);
+
+        // 重启后从磁盘恢复 / Recovered from disk after a restart
+        let scheduler = MessageScheduler::new(config);
+        assert_eq!(scheduler.recover().unwrap(), 2);
+        let due = scheduler.due(1000).unwrap();
+        assert_eq!(ids(&due), ["early", "late"]);
+
+        // 失败重试一次后丢弃，成功即删除 / A failure retries once then drops; success removes
+        let (key, msg) = due[0].clone();
+        scheduler.settle(&key, msg, false, 1000).await.unwrap();
+        let (key, msg) = due[1].clone();
+        scheduler.settle(&key, msg, true, 1000).await.unwrap();
+        assert!(scheduler.due(1000).unwrap().is_empty());
+        let retry = scheduler.due(1010).unwrap();
+        assert_eq!(ids(&retry), ["early"]);
+        assert_eq!(retry[0].1.attempts, 1);
+        let (key, msg) = retry[0].clone();
+        scheduler.settle(&key, msg, false, 1010).await.unwrap();
+        assert_eq!(
+            scheduler.snapshot(),
+            json!({"pending": 0, "delivered": 1, "dropped": 1})
+        );
+        assert!(!scheduler.cancel("early", "alice").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_message_delivered_when_due() {
+        let scheduler = Arc::new(MessageScheduler::new(config("deliver")));
+        let ts = TestServer::build(|server| server.with_scheduler(scheduler.clone()));
+        let (alice, mut alice_rx) = ts.add_client("alice");
+        let (_, mut bob_rx) = ts.add_client("bob");
+
+        let deliver_at = chrono::Utc::now().timestamp_millis() + 60_000;
+        let schedule = |text: &str| {
+            im(
+                "schedule_message",
+                json!({"deliver_at": deliver_at, "content": {"text": text}}),
+                Some("bob"),
+            )
+        };
+        ts.send(&alice, schedule("later")).await.unwrap();
+        let ack: ImMessage = recv_typed(&mut alice_rx).await;
+        assert_eq!(ack.msg_type, "message_scheduled");
+        ts.send(&alice, schedule("never")).await.unwrap();
+        let cancelled: ImMessage = recv_typed(&mut alice_rx).await;
+        let cancel = im(
+            "cancel_scheduled",
+            json!({"schedule_id": cancelled.data["schedule_id"]}),
+            None,
+        );
+        ts.send(&alice, cancel).await.unwrap();
+        let resp: ImMessage = recv_typed(&mut alice_rx).await;
+        assert_eq!(resp.msg_type, "scheduled_cancelled");
+
+        // 未到期不投递 / Nothing is delivered before it is due
+        assert_eq!(
+            ts.server.run_due_scheduled(deliver_at - 1).await.unwrap(),
+            0
+        );
+        assert!(bob_rx.try_recv().is_err());
+
+        // 维护期间留待之后投递 / Held back during maintenance
+        ts.server.maintenance.set(true);
+        assert_eq!(ts.server.run_due_scheduled(deliver_at).await.unwrap(), 0);
+        assert!(bob_rx.try_recv().is_err());
+        ts.server.maintenance.set(false);
+
+        assert_eq!(ts.server.run_due_scheduled(deliver_at).await.unwrap(), 1);
+        let got: ImMessage = recv_typed(&mut bob_rx).await;
+        assert_eq!(got.data["content"]["text"], "later");
+        assert_eq!(scheduler.snapshot()["pending"], 0);
+
+        let too_far = im(
+            "schedule_message",
+            json!({"deliver_at": i64::MAX, "content": {}}),
+            Some("bob"),
+        );
+        ts.send(&alice, too_far).await.unwrap();
+        let err: ImMessage = recv_typed(&mut alice_rx).await;
+        assert_eq!(err.data["code"], json!(ErrorCode::InvalidSchedule));
+    }
+}
@@ -0,0 +1,133 @@
+//! 优雅停机 / Graceful shutdown
+//!
+//! 各阶段的等待时间均可配置：`shutdown.http_grace_ms`（HTTP 排空在途请求）、`shutdown.socket_grace_ms`
+//! （插件 Unix Socket / TCP 服务器退出）、`shutdown.plugin_grace_ms`（停止插件进程）。整个停机过程受
+//! `shutdown.total_budget_ms` 约束：超出预算时记录仍未完成的阶段并强制退出进程，卡住的插件不会让
+//! 停机无限挂起。
+//! Every shutdown wait is configurable: `shutdown.http_grace_ms` (HTTP drains in-flight requests),
+//! `shutdown.socket_grace_ms` (the plugin Unix socket / TCP servers exit) and
+//! `shutdown.plugin_grace_ms` (plugin processes stop). The whole shutdown is bounded by
+//! `shutdown.total_budget_ms`: past the budget the steps still pending are logged and the process
+//! is aborted, so a stuck plugin cannot hang shutdown forever.
+
+use parking_lot::Mutex;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 停机各阶段的等待时间 / Waits for each shutdown stage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShutdownConfig {
+    pub http_grace: Duration,
+    pub socket_grace: Duration,
+    pub plugin_grace: Duration,
+    /// 整个停机过程的上限 / Upper bound for the whole shutdown
+    pub total_budget: Duration,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self {
+            http_grace: Duration::from_secs(30),
+            socket_grace: Duration::from_secs(2),
+            plugin_grace: Duration::from_secs(5),
+            total_budget: Duration::from_secs(60),
+        }
+    }
+}
+
+impl ShutdownConfig {
+    /// 读取 `[shutdown]` / Read `[shutdown]`
+    pub fn from_config() -> Self {
+        let defaults = Self::default();
+        let Ok(cm) = v::get_global_config_manager() else {
+            return defaults;
+        };
+        let ms = |key: &str, default: Duration| {
+            Duration::from_millis(cm.get_or(key, default.as_millis() as u64))
+        };
+        Self {
+            http_grace: ms("shutdown.http_grace_ms", defaults.http_grace),
+            socket_grace: ms("shutdown.socket_grace_ms", defaults.socket_grace),
+            plugin_grace: ms("shutdown.plugin_grace_ms", defaults.plugin_grace),
+            total_budget: ms("shutdown.total_budget_ms", defaults.total_budget),
+        }
+    }
+
+    /// actix 的停机超时以秒为单位，向上取整 / actix takes its shutdown timeout in seconds, rounded up
+    pub fn http_grace_secs(&self) -> u64 {
+        self.http_grace.as_millis().div_ceil(1000) as u64
+    }
+}
+
+/// 记录尚未完成的停机阶段 / Tracks the shutdown steps not finished yet
+#[derive(Debug, Clone, Default)]
+pub struct ShutdownProgress {
+    pending: Arc<Mutex<Vec<String>>>,
+}
+
+impl ShutdownProgress {
+    /// 执行一个阶段；被强制中止时该阶段保留在待完成列表中
+    /// Run one step; if it is aborted the step stays in the pending list
+    pub async fn step<T>(&self, name: &str, fut: impl Future<Output = T>) -> T {
+        self.pending.lock().push(name.to_string());
+        let out = fut.await;
+        self.pending.lock().retain(|step| step != name);
+        out
+    }
+
+    pub fn pending(&self) -> Vec<String> {
+        self.pending.lock().clone()
+    }
+
+    /// 在预算内运行停机流程，超时返回仍未完成的阶段
+    /// Run the shutdown within the budget; on timeout returns the steps still pending
+    pub async fn run_within(
+        &self,
+        budget: Duration,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<(), Vec<String>> {
+        tokio::time::timeout(budget, shutdown)
+            .await
+            .map_err(|_| self.pending())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_http_grace_rounds_up_to_seconds() {
+        let config = ShutdownConfig {
+            http_grace: Duration::from_millis(1_500),
+            ..Default::default()
+        };
+        assert_eq!(config.http_grace_secs(), 2);
+        assert_eq!(ShutdownConfig::default().http_grace_secs(), 30);
+    }
+
+    #[tokio::test]
+    async fn test_stuck_step_is_reported_at_the_budget() {
+        let progress = ShutdownProgress::default();
+        let shutdown = async {
+            progress.step("close_plugin_connections", async {}).await;
+            progress
+                .step("stop_plugins", std::future::pending::<()>())
+                .await;
+        };
+        let started = std::time::Instant::now();
+        let result = progress
+            .run_within(Duration::from_millis(50), shutdown)
+            .await;
+        assert_eq!(result, Err(vec!["stop_plugins".to_string()]));
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        let quick = ShutdownProgress::default();
+        let done = quick
+            .run_within(Duration::from_secs(1), quick.step("flush", async {}))
+            .await;
+        assert_eq!(done, Ok(()));
+        assert!(quick.pending().is_empty());
+    }
+}
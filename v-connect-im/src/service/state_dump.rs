@@ -0,0 +1,342 @@
+//! 节点状态转储 / Node state dump
+//!
+//! 用于在本地复现线上问题：`state dump <name>`（`POST /v1/admin/state/dump`）把节点状态写成单个 JSON
+//! 归档，`state restore <name>`（`POST /v1/admin/state/restore`）把它装入一个新节点。这两个接口读写服务端文件，
+//! 只在配置了 `server.admin_token` 时注册，且只接受 `server.state_dump_dir` 下的相对路径，拒绝绝对路径与 `..`。
+//! 归档包含：房间成员（`rooms`）、uid 到连接 ID 的映射（`uid_clients`，不含发送端）、目录服务的节点与客户端
+//! 位置（`directory`）、封禁名单及原因（`blocked_uids`），以及存储插件对全部消息的归档（`storage`，为对象键与
+//! 条数，消息本身在插件的对象存储中）。归档不内嵌消息：恢复节点的存储插件必须能访问转储节点所用的对象存储桶，
+//! 否则存储恢复失败，其余状态不受影响。
+//! 不包含：在线连接与其发送队列、离线队列、待确认消息、定时消息、断线重连令牌、限流与认证计数、指标，以及
+//! 插件自己的状态。内置存储不支持归档，此时 `storage` 为空，`storage_error` 说明原因。
+//! 恢复只接受没有房间与连接的新节点：房间成员经正常的加入流程写回（同时写入存储），封禁写回内存与封禁库，
+//! 存储归档交给存储插件恢复。`uid_clients` 与转储节点上的客户端位置只用于查看，不会恢复，因为它们指向的连接
+//! 已不存在；其他节点上的客户端位置与节点列表照常恢复。
+//! For reproducing production issues locally: `state dump <name>` (`POST /v1/admin/state/dump`)
+//! writes the node's state to a single JSON archive and `state restore <name>`
+//! (`POST /v1/admin/state/restore`) loads it into a fresh node. Both read and write files on the
+//! server, so they are only registered when `server.admin_token` is set, and only take paths
+//! relative to `server.state_dump_dir`; absolute paths and `..` are rejected.
+//! Captured: room members (`rooms`), the uid to connection id map (`uid_clients`, without
+//! senders), the directory's nodes and client locations (`directory`), the blocklist with reasons
+//! (`blocked_uids`), and the storage plugin's archive of every message (`storage`: the object key
+//! and count, the messages themselves stay in the plugin's object storage). The archive does not
+//! embed the messages: the restoring node's storage plugin must reach the dumped node's object
+//! storage bucket, otherwise the storage restore fails while the rest of the state still loads.
+//! Not captured: live connections and their send queues, offline queues, pending acks, scheduled
+//! messages, resume tokens, rate limit and auth counters, metrics, and plugins' own state. The
+//! built-in storage cannot archive, in which case `storage` is empty and `storage_error` says why.
+//! Restore only accepts a fresh node without rooms or connections: room members go through the
+//! regular join (so storage gets them too), blocks are written back to memory and the block
+//! database, and the storage archive is handed to the storage plugin to restore. `uid_clients`
+//! and the client locations on the dumped node are for inspection only and are not restored,
+//! since the connections they name are gone; client locations on other nodes and the node list
+//! are restored as they were.
+
+use crate::cluster::router::NodeInfo;
+use crate::plugins::runtime::MessageArchive;
+use crate::server::VConnectIMServer;
+use crate::service::blocklist::BlockEntry;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+/// 归档格式版本 / Archive format version
+pub const STATE_DUMP_VERSION: u32 = 1;
+
+/// 缺省的归档目录 / Default archive directory
+pub const DEFAULT_STATE_DUMP_DIR: &str = "./data/state-dumps";
+
+/// 配置 `server.state_dump_dir` 指定的归档目录 / Archive directory from `server.state_dump_dir`
+pub fn state_dump_dir_from_config() -> PathBuf {
+    let dir: String = v::get_global_config_manager()
+        .map(|cm| cm.get_or("server.state_dump_dir", DEFAULT_STATE_DUMP_DIR.to_string()))
+        .unwrap_or_else(|_| DEFAULT_STATE_DUMP_DIR.to_string());
+    crate::config::resolve_path(dir)
+}
+
+/// 把客户端给出的归档名解析到 `dir` 下，拒绝空名、绝对路径与 `..`
+/// Resolve a client supplied archive name under `dir`, rejecting empty names, absolute paths and `..`
+pub fn resolve_dump_path(dir: &Path, name: &str) -> Result<PathBuf> {
+    let relative = Path::new(name);
+    if name.trim().is_empty()
+        || !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+    {
+        bail!(
+            "state dump path must be relative to the dump directory without `..`: {}",
+            name
+        );
+    }
+    Ok(dir.join(relative))
+}
+
+/// 节点状态归档 / Node state archive
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateDump {
+    pub version: u32,
+    pub node_id: String,
+    pub created_at: i64,
+    /// 房间 -> 成员 uid / Room -> member uids
+    pub rooms: BTreeMap<String, Vec<String>>,
+    /// uid -> 连接 ID，仅供查看 / uid -> connection ids, for inspection only
+    pub uid_clients: BTreeMap<String, Vec<String>>,
+    pub directory: DirectoryDump,
+    pub blocked_uids: Vec<BlockEntry>,
+    /// 存储插件的全量归档 / The storage plugin's archive of every message
+    pub storage: Option<MessageArchive>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub storage_error: Option<String>,
+}
+
+/// 目录服务的内容 / Directory contents
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DirectoryDump {
+    pub nodes: Vec<NodeInfo>,
+    /// 客户端 -> 节点 / Client -> node
+    pub clients: BTreeMap<String, String>,
+}
+
+/// 转储或恢复的条目数 / What a dump or restore covered
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct StateSummary {
+    pub rooms: usize,
+    pub room_members: usize,
+    pub uid_clients: usize,
+    pub directory_nodes: usize,
+    pub directory_clients: usize,
+    pub blocked_uids: usize,
+    /// 转储时为归档条数，恢复时为写回条数 / Archived count on dump, restored count on restore
+    pub storage_messages: Option<usize>,
+}
+
+impl StateDump {
+    fn summary(&self) -> StateSummary {
+        StateSummary {
+            rooms: self.rooms.len(),
+            room_members: self.rooms.values().map(Vec::len).sum(),
+            uid_clients: self.uid_clients.len(),
+            directory_nodes: self.directory.nodes.len(),
+            directory_clients: self.directory.clients.len(),
+            blocked_uids: self.blocked_uids.len(),
+            storage_messages: self.storage.as_ref().map(|a| a.count),
+        }
+    }
+}
+
+impl VConnectIMServer {
+    /// 把节点状态写入 `path`，并让存储插件归档全部消息
+    /// Write the node's state to `path`, asking the storage plugin to archive every message
+    pub async fn dump_state(&self, path: &Path) -> Result<StateSummary> {
+        let sorted = |set: &dashmap::DashSet<String>| {
+            let mut items: Vec<String> = set.iter().map(|s| s.clone()).collect();
+            items.sort();
+            items
+        };
+        let (storage, storage_error) = match self.plugin_connection_pool.as_ref() {
+            Some(pool) => match pool.storage_archive(0..i64::MAX).await {
+                Ok(Some(archive)) => (Some(archive), None),
+                Ok(None) => (None, Some("storage plugin unavailable".to_string())),
+                Err(e) => (None, Some(e.to_string())),
+            },
+            None => (None, Some("plugin runtime unavailable".to_string())),
+        };
+        if let Some(e) = &storage_error {
+            tracing::warn!(
+                "⚠️  状态转储未包含存储归档 / State dump has no storage archive: {}",
+                e
+            );
+        }
+        let dump = StateDump {
+            version: STATE_DUMP_VERSION,
+            node_id: self.node_id.clone(),
+            created_at: chrono::Utc::now().timestamp_millis(),
+            rooms: self
+                .rooms
+                .iter()
+                .map(|r| (r.key().clone(), sorted(r.value())))
+                .collect(),
+            uid_clients: self
+                .uid_clients
+                .iter()
+                .map(|u| (u.key().clone(), sorted(u.value())))
+                .collect(),
+            directory: DirectoryDump {
+                nodes: self.directory.list_nodes(),
+                clients: self
+                    .directory
+                    .clients
+                    .iter()
+                    .map(|c| (c.key().clone(), c.value().clone()))
+                    .collect(),
+            },
+            blocked_uids: self.list_blocked()?,
+            storage,
+            storage_error,
+        };
+        let bytes = serde_json::to_vec_pretty(&dump)?;
+        let path = path.to_path_buf();
+        // 先写临时文件再改名，不会留下半个归档 / Write a temp file and rename so no half archive is left
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("creating {}", parent.display()))?;
+            }
+            let tmp = path.with_extension("tmp");
+            std::fs::write(&tmp, bytes).with_context(|| format!("writing {}", tmp.display()))?;
+            std::fs::rename(&tmp, &path).with_context(|| format!("renaming to {}", path.display()))
+        })
+        .await??;
+        Ok(dump.summary())
+    }
+
+    /// 把 `path` 处的归档装入本节点，本节点必须没有房间与连接
+    /// Load the archive at `path` into this node, which must have no rooms or connections
+    pub async fn restore_state(&self, path: &Path) -> Result<StateSummary> {
+        let owned = path.to_path_buf();
+        let bytes = tokio::task::spawn_blocking(move || {
+            std::fs::read(&owned).with_context(|| format!("reading {}", owned.display()))
+        })
+        .await??;
+        let dump: StateDump = serde_json::from_slice(&bytes)
+            .with_context(|| format!("parsing {}", path.display()))?;
+        if dump.version != STATE_DUMP_VERSION {
+            bail!(
+                "unsupported state dump version {} (expected {})",
+                dump.version,
+                STATE_DUMP_VERSION
+            );
+        }
+        if !self.rooms.is_empty() || !self.connections.is_empty() {
+            bail!("state can only be restored into a fresh node without rooms or connections");
+        }
+
+        let mut summary = StateSummary {
+            rooms: dump.rooms.len(),
+            ..Default::default()
+        };
+        for (room_id, members) in &dump.rooms {
+            for uid in members {
+                self.http_join_room(room_id, uid).await;
+                summary.room_members += 1;
+            }
+        }
+        for node in &dump.directory.nodes {
+            self.directory.register_node(node.clone());
+            summary.directory_nodes += 1;
+        }
+        // 转储节点上的连接已不存在 / Connections on the dumped node are gone
+        for (client_id, node_id) in &dump.directory.clients {
+            if *node_id != dump.node_id {
+                self.directory.register_client_location(client_id, node_id);
+                summary.directory_clients += 1;
+            }
+        }
+        for entry in &dump.blocked_uids {
+            self.blocked_uids.insert(entry.uid.clone());
+            self.block_store.insert(entry)?;
+            summary.blocked_uids += 1;
+        }
+        if let Some(archive) = &dump.storage {
+            let Some(pool) = self.plugin_connection_pool.as_ref() else {
+                bail!("the dump has a storage archive but the plugin runtime is unavailable");
+            };
+            let restored = pool
+                .storage_restore(&archive.archive_key)
+                .await?
+                .context("storage plugin unavailable")?;
+            summary.storage_messages = Some(restored);
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::blocklist::BlockStore;
+    use crate::testkit::TestServer;
+    use std::sync::Arc;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("vgo-state-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_dump_paths_stay_inside_the_dump_directory() {
+        let dir = Path::new("/var/lib/vim/dumps");
+        assert_eq!(
+            resolve_dump_path(dir, "incident/node-a.json").unwrap(),
+            dir.join("incident/node-a.json")
+        );
+        for name in [
+            "",
+            "/etc/passwd",
+            "../node-a.json",
+            "incident/../../x",
+            "./a.json",
+        ] {
+            assert!(resolve_dump_path(dir, name).is_err(), "{:?}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dumped_state_restores_into_a_fresh_node() {
+        let archive = temp_path("archive.json");
+        let blocks = temp_path("blocks-a");
+        let ts = TestServer::build(|s| s.with_block_store(Arc::new(BlockStore::new(&blocks))));
+        let (alice, _alice_rx) = ts.add_client("alice");
+        for uid in ["alice", "bob"] {
+            ts.server.http_join_room("r1", uid).await;
+        }
+        ts.server.block_uid("mallory", Some("spam")).unwrap();
+        ts.server.directory.register_node(NodeInfo {
+            node_id: "node-b".to_string(),
+            weight: 1,
+            is_alive: true,
+        });
+        ts.server
+            .directory
+            .register_client_location("carol-1", "node-b");
+
+        let dumped = ts.server.dump_state(&archive).await.unwrap();
+        assert_eq!((dumped.rooms, dumped.room_members), (1, 2));
+        assert_eq!(dumped.uid_clients, 1);
+        assert_eq!(dumped.storage_messages, None);
+        let dump: StateDump = serde_json::from_slice(&std::fs::read(&archive).unwrap()).unwrap();
+        assert_eq!(dump.uid_clients["alice"], [alice.clone()]);
+        assert!(dump.storage_error.is_some());
+
+        // 已有房间的节点拒绝恢复 / A node with rooms refuses the restore
+        assert!(ts.server.restore_state(&archive).await.is_err());
+
+        let restored_blocks = temp_path("blocks-b");
+        let fresh =
+            TestServer::build(|s| s.with_block_store(Arc::new(BlockStore::new(&restored_blocks))));
+        let restored = fresh.server.restore_state(&archive).await.unwrap();
+        assert_eq!((restored.rooms, restored.room_members), (1, 2));
+        assert_eq!(fresh.server.room_members("r1").members, ["alice", "bob"]);
+        assert!(fresh.server.is_blocked("mallory"));
+        assert_eq!(
+            fresh.server.list_blocked().unwrap()[0].reason.as_deref(),
+            Some("spam")
+        );
+        assert_eq!(
+            fresh.server.directory.locate_client("carol-1").as_deref(),
+            Some("node-b")
+        );
+        // 在线连接不会恢复 / Live connections are not restored
+        assert!(fresh.server.directory.locate_client(&alice).is_none());
+        assert!(fresh.server.uid_clients.is_empty());
+
+        for path in [archive, blocks, restored_blocks] {
+            let _ = std::fs::remove_dir_all(&path);
+            let _ = std::fs::remove_file(&path);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! 存储插件不可用时的降级策略 / Degradation while the storage plugin is unavailable
+//!
+//! `storage.on_unavailable` 决定没有可用存储插件（未连接或调用失败）时如何处理待保存的消息：
+//! `fail` 拒绝发送；`warn` 照常投递但不持久化，并计入 `unavailable` 指标；`buffer`（默认）
+//! 写入本地回退 sled（`storage.fallback_path`），存储插件重新连接后按写入顺序重放。
+//! 启用内置存储（`storage.use_builtin`）时由内置存储接管，不会进入这里。
+//! 指标通过 `/v1/health/detailed` 的 `details.storage_fallback` 输出。
+//! `storage.on_unavailable` decides what happens to a message that should be saved while no
+//! storage plugin is usable (not connected, or the call failed): `fail` rejects the send;
+//! `warn` delivers without persisting and counts it in the `unavailable` metric; `buffer`
+//! (the default) spools it to a local fallback sled (`storage.fallback_path`) that is
+//! replayed in write order once a storage plugin reconnects. With the built-in storage
+//! enabled (`storage.use_builtin`) it takes over instead. Metrics are reported under
+//! `details.storage_fallback` of `/v1/health/detailed`.
+
+use crate::storage::MessageRecord;
+use anyhow::Result;
+use parking_lot::Mutex;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// 默认回退库路径 / Default fallback database path
+pub const DEFAULT_FALLBACK_PATH: &str = "./data/storage-fallback";
+
+/// 存储插件不可用时的处理方式 / What to do while the storage plugin is unavailable
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnUnavailable {
+    /// 拒绝发送 / Reject the send
+    Fail,
+    /// 照常投递，只计数告警 / Deliver anyway, only count and warn
+    Warn,
+    /// 写入本地回退库，插件恢复后重放 / Spool locally and replay when the plugin returns
+    #[default]
+    Buffer,
+}
+
+/// 本地回退库与降级指标 / Local fallback spool and degradation metrics
+pub struct StorageFallback {
+    policy: OnUnavailable,
+    path: PathBuf,
+    /// 首次需要时打开，未降级过的节点不会创建目录
+    /// Opened on first use, so nodes that never degrade create no directory
+    spool: Mutex<Option<sled::Db>>,
+    /// 保证同一时间只有一个重放任务 / Ensures a single replay at a time
+    replaying: AtomicBool,
+    unavailable: AtomicU64,
+    buffered: AtomicU64,
+    replayed: AtomicU64,
+}
+
+impl Default for StorageFallback {
+    fn default() -> Self {
+        Self::new(OnUnavailable::default(), DEFAULT_FALLBACK_PATH)
+    }
+}
+
+impl StorageFallback {
+    pub fn new(policy: OnUnavailable, path: impl Into<PathBuf>) -> Self {
+        Self {
+            policy,
+            path: path.into(),
+            spool: Mutex::new(None),
+            replaying: AtomicBool::new(false),
+            unavailable: AtomicU64::new(0),
+            buffered: AtomicU64::new(0),
+            replayed: AtomicU64::new(0),
+        }
+    }
+
+    /// 读取 `storage.on_unavailable` 与 `storage.fallback_path`
+    /// Read `storage.on_unavailable` and `storage.fallback_path`
+    pub fn from_config() -> Self {
+        let Ok(cm) = v::get_global_config_manager() else {
+            return Self::default();
+        };
+        Self::new(
+            cm.get_or("storage.on_unavailable", OnUnavailable::default()),
+            crate::config::resolve_path(
+                cm.get_or("storage.fallback_path", DEFAULT_FALLBACK_PATH.to_string()),
+            ),
+        )
+    }
+
+    pub fn policy(&self) -> OnUnavailable {
+        self.policy
+    }
+
+    /// 记录一次存储插件不可用 / Record one save that found the storage plugin unavailable
+    pub fn record_unavailable(&self) {
+        self.unavailable.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// 写入回退库并落盘 / Append to the fallback spool and flush it
+    pub async fn buffer(&self, record: &MessageRecord) -> Result<()> {
+        let db = self.open()?;
+        let key = db.generate_id()?.to_be_bytes();
+        db.insert(key, serde_json::to_vec(record)?)?;
+        db.flush_async().await?;
+        self.buffered.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// 回退库中是否有待重放的消息 / Whether the spool holds messages to replay
+    pub fn has_pending(&self) -> bool {
+        self.pending() > 0
+    }
+
+    /// 最早写入的消息及其键 / The oldest spooled message and its key
+    pub(crate) fn peek(&self) -> Result<Option<(sled::IVec, MessageRecord)>> {
+        match self.existing()? {
+            Some(db) => match db.first()? {
+                Some((key, value)) => Ok(Some((key, serde_json::from_slice(&value)?))),
+                None => Ok(None),
+            },
+            None => Ok(None),
+        }
+    }
+
+    /// 重放成功后移除 / Remove after a successful replay
+    pub(crate) fn ack(&self, key: &[u8]) -> Result<()> {
+        if let Some(db) = self.existing()? {
+            db.remove(key)?;
+            self.replayed.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// 尝试成为唯一的重放者 / Try to become the only replayer
+    pub(crate) fn begin_replay(&self) -> bool {
+        self.replaying
+            .compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire)
+            .is_ok()
+    }
+
+    pub(crate) fn end_replay(&self) {
+        self.replaying.store(false, Ordering::Release);
+    }
+
+    /// 指标快照（JSON）/ Metrics snapshot as JSON
+    pub fn snapshot(&self) -> Value {
+        json!({
+            "policy": format!("{:?}", self.policy).to_lowercase(),
+            "unavailable": self.unavailable.load(Ordering::Relaxed),
+            "buffered": self.buffered.load(Ordering::Relaxed),
+            "replayed": self.replayed.load(Ordering::Relaxed),
+            "pending": self.pending(),
+        })
+    }
+
+    fn pending(&self) -> usize {
+        match self.existing() {
+            Ok(Some(db)) => db.len(),
+            _ => 0,
+        }
+    }
+
+    fn open(&self) -> Result<sled::Db> {
+        let mut spool = self.spool.lock();
+        if let Some(db) = spool.as_ref() {
+            return Ok(db.clone());
+        }
+        let db = sled::open(&self.path)?;
+        *spool = Some(db.clone());
+        Ok(db)
+    }
+
+    /// 只打开已存在的回退库（上次运行留下的也算）
+    /// Open the spool only if it exists, including one left by a previous run
+    fn existing(&self) -> Result<Option<sled::Db>> {
+        if self.spool.lock().is_none() && !self.path.exists() {
+            return Ok(None);
+        }
+        self.open().map(Some)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::plugins::runtime::{PluginConnectionPool, PluginRuntimeManager};
+    use std::sync::Arc;
+
+    pub(crate) fn fallback_dir(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "vgo-storage-fallback-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        path
+    }
+
+    pub(crate) fn pool_with(
+        policy: OnUnavailable,
+        path: PathBuf,
+    ) -> (Arc<PluginRuntimeManager>, Arc<PluginConnectionPool>) {
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = PluginConnectionPool::new(manager.clone())
+            .with_storage_fallback(StorageFallback::new(policy, path));
+        (manager, Arc::new(pool))
+    }
+
+    pub(crate) async fn save(pool: &PluginConnectionPool, id: &str) -> Result<bool> {
+        pool.storage_save_message(id, "alice", "bob", &json!({"text": id}), 1, "message", None)
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_fail_and_warn_policies() {
+        let (_, pool) = pool_with(OnUnavailable::Fail, fallback_dir("fail"));
+        assert!(save(&pool, "m1").await.is_err());
+
+        let path = fallback_dir("warn");
+        let (_, pool) = pool_with(OnUnavailable::Warn, path.clone());
+        assert!(!save(&pool, "m1").await.unwrap());
+        let snap = pool.storage_fallback().snapshot();
+        assert_eq!(snap["unavailable"], 1);
+        assert_eq!(snap["buffered"], 0);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,368 @@
+//! 系统消息（运维公告）/ System messages (operator announcements)
+//!
+//! 系统消息以 `system` 类型下发，不作为会话消息持久化；范围为 `uid` 或 `room` 时，
+//! 可选地为没有在线客户端的用户写入离线队列。
+//! System messages are delivered with the `system` type and are not persisted as
+//! conversation messages; for the `uid` and `room` scopes they can optionally be queued
+//! offline for users without an online client.
+//!
+//! 系统消息为 `high` 优先级，发送队列满时会挤掉较低优先级的消息，重连时先于其他离线消息补发。
+//! System messages have `high` priority: they evict lower-priority messages from a full send
+//! queue and are replayed before other offline messages on reconnect.
+
+use crate::domain::message::{ImMessage, MessagePriority};
+use crate::server::VConnectIMServer;
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::str::FromStr;
+use tokio_tungstenite::tungstenite::Message;
+
+/// 系统消息类型 / System message type
+pub const SYSTEM_MESSAGE_TYPE: &str = "system";
+
+/// 系统消息范围 / System message scope
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SystemScope {
+    /// 所有节点上的在线用户 / Online users on every node
+    All,
+    Room(String),
+    Uid(String),
+}
+
+/// 解析 `all`、`room:<id>` 或 `uid:<id>` / Parse `all`, `room:<id>` or `uid:<id>`
+impl FromStr for SystemScope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let scope = match s.split_once(':') {
+            None if s == "all" => Self::All,
+            Some(("room", id)) if !id.is_empty() => Self::Room(id.to_string()),
+            Some(("uid", id)) if !id.is_empty() => Self::Uid(id.to_string()),
+            _ => anyhow::bail!("invalid scope {:?}, expected all, room:<id> or uid:<id>", s),
+        };
+        Ok(scope)
+    }
+}
+
+impl fmt::Display for SystemScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::All => write!(f, "all"),
+            Self::Room(id) => write!(f, "room:{}", id),
+            Self::Uid(id) => write!(f, "uid:{}", id),
+        }
+    }
+}
+
+/// 系统消息投递结果 / System message delivery result
+#[derive(Debug, Serialize)]
+pub struct SystemMessageResult {
+    pub message_id: String,
+    /// 写入发送队列的客户端数 / Clients the message was queued for
+    pub delivered: usize,
+    /// 没有在线客户端的用户 / Users without an online client
+    pub offline_uids: Vec<String>,
+    /// 已写入离线队列的用户数 / Users the message was queued offline for
+    pub queued_offline: usize,
+}
+
+impl VConnectIMServer {
+    /// 按范围下发系统消息；`queue_offline` 为真时为离线用户写入离线队列（`all` 范围忽略）
+    /// Deliver a system message to a scope; with `queue_offline`, offline users get it
+    /// queued offline (ignored for the `all` scope)
+    pub async fn send_system_message(
+        &self,
+        scope: SystemScope,
+        content: Value,
+        queue_offline: bool,
+    ) -> Result<SystemMessageResult> {
+        let message_id = self.id_gen.next_str();
+        let timestamp = chrono::Utc::now().timestamp_millis();
+        let frame = ImMessage {
+            msg_type: SYSTEM_MESSAGE_TYPE.to_string(),
+            data: serde_json::json!({
+                "message_id": message_id,
+                "scope": scope.to_string(),
+                "content": content,
+                "timestamp": timestamp,
+            }),
+            target_uid: None,
+            // 运维公告优先于会话消息 / Operator announcements go ahead of conversation messages
+            priority: MessagePriority::High,
+            reply_to: None,
+        };
+
+        let mut delivered = 0;
+        let mut offline_uids = Vec::new();
+        let room_id = match &scope {
+            SystemScope::All => {
+                delivered = self.push_to_all_online(&frame).await?;
+                None
+            }
+            SystemScope::Uid(uid) => {
+                delivered = self.push_to_uid(uid, &frame).await?;
+                if delivered == 0 {
+                    offline_uids.push(uid.clone());
+                }
+                None
+            }
+            SystemScope::Room(room_id) => {
+                let mut members: Vec<String> = self
+                    .rooms
+                    .get(room_id)
+                    .map(|set| set.iter().map(|u| u.clone()).collect())
+                    .unwrap_or_default();
+                members.sort();
+                for uid in members {
+                    match self.push_to_uid(&uid, &frame).await? {
+                        0 => offline_uids.push(uid),
+                        n => delivered += n,
+                    }
+                }
+                Some(room_id.as_str())
+            }
+        };
+
+        let mut queued_offline = 0;
+        if queue_offline && scope != SystemScope::All {
+            if let Some(pool) = self.plugin_connection_pool.as_ref() {
+                for uid in &offline_uids {
+                    match pool
+                        .storage_save_offline(
+                            &message_id,
+                            None,
+                            uid,
+                            &content,
+                            timestamp,
+                            SYSTEM_MESSAGE_TYPE,
+                            room_id,
+                            MessagePriority::High,
+                        )
+                        .await
+                    {
+                        Ok(true) => queued_offline += 1,
+                        Ok(false) => {
+                            tracing::warn!("⚠️  离线消息保存失败 / Offline message save failed")
+                        }
+                        Err(e) => {
+                            tracing::error!(
+                                "❌ 离线消息保存错误 / Offline message save error: {}",
+                                e
+                            )
+                        }
+                    }
+                }
+            }
+        }
+
+        tracing::info!(
+            "📢 系统消息 {} 已下发 / System message {} sent to {}: delivered={}, offline={}",
+            message_id,
+            message_id,
+            scope,
+            delivered,
+            offline_uids.len()
+        );
+        Ok(SystemMessageResult {
+            message_id,
+            delivered,
+            offline_uids,
+            queued_offline,
+        })
+    }
+
+    /// 推送给本节点及其他节点上所有已认证的连接 / Push to every authenticated connection on this and other nodes
+    async fn push_to_all_online(&self, message: &ImMessage) -> Result<usize> {
+        let text = serde_json::to_string(message)?;
+        let mut delivered = push_to_authenticated(self, &text).await;
+        for node in self.directory.list_nodes() {
+            if node.node_id == self.node_id {
+                continue;
+            }
+            if let Some(remote) = self.directory.get_server(&node.node_id) {
+                delivered += push_to_authenticated(&remote, &text).await;
+            }
+        }
+        Ok(delivered)
+    }
+}
+
+/// 写入单个节点上所有已认证连接 / Queue a frame on every authenticated connection of one node
+async fn push_to_authenticated(server: &VConnectIMServer, text: &str) -> usize {
+    let clients: Vec<String> = server
+        .connections
+        .iter()
+        .filter(|c| c.uid.is_some())
+        .map(|c| c.key().clone())
+        .collect();
+    let mut delivered = 0;
+    for cid in clients {
+        if server
+            .send_message_to_client(&cid, Message::Text(text.to_string()))
+            .await
+            .is_ok()
+        {
+            delivered += 1;
+        }
+    }
+    delivered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::router::NodeInfo;
+    use crate::testkit::{recv_typed, TestServer};
+
+    #[test]
+    fn test_scope_parsing() {
+        assert_eq!("all".parse::<SystemScope>().unwrap(), SystemScope::All);
+        assert_eq!(
+            "room:r1".parse::<SystemScope>().unwrap(),
+            SystemScope::Room("r1".into())
+        );
+        assert_eq!(
+            "uid:u1".parse::<SystemScope>().unwrap().to_string(),
+            "uid:u1"
+        );
+        for bad in ["", "everyone", "room:", "user:u1"] {
+            assert!(bad.parse::<SystemScope>().is_err(), "{}", bad);
+        }
+    }
+
+    async fn expect_system(rx: &mut crate::ws::send_queue::QueueReceiver, text: &str) {
+        let got: ImMessage = recv_typed(rx).await;
+        assert_eq!(got.msg_type, SYSTEM_MESSAGE_TYPE);
+        assert_eq!(got.data["content"]["text"], text);
+    }
+
+    #[tokio::test]
+    async fn test_system_message_scope_all_reaches_every_node() {
+        let a = TestServer::new();
+        let b = a.join("node-B");
+        a.directory.register_node(NodeInfo {
+            node_id: "node-B".into(),
+            weight: 1,
+            is_alive: true,
+        });
+        let (_, mut a_rx) = a.add_client("alice");
+        let (_, mut b_rx) = b.add_client("bob");
+
+        let res = a
+            .server
+            .send_system_message(
+                SystemScope::All,
+                serde_json::json!({"text": "down at 2am"}),
+                true,
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.delivered, 2);
+        assert!(res.offline_uids.is_empty());
+        expect_system(&mut a_rx, "down at 2am").await;
+        expect_system(&mut b_rx, "down at 2am").await;
+    }
+
+    #[tokio::test]
+    async fn test_system_message_scope_room_reports_offline_members() {
+        let ts = TestServer::new();
+        let (_, mut a_rx) = ts.add_client("alice");
+        let (_, mut out_rx) = ts.add_client("outsider");
+        for uid in ["alice", "carol"] {
+            ts.server.http_join_room("r1", uid).await;
+        }
+
+        let res = ts
+            .server
+            .send_system_message(
+                SystemScope::Room("r1".into()),
+                serde_json::json!({"text": "room closing"}),
+                true,
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.delivered, 1);
+        assert_eq!(res.offline_uids, ["carol"]);
+        // 未配置存储插件时不写离线 / Nothing is queued without a storage plugin
+        assert_eq!(res.queued_offline, 0);
+        expect_system(&mut a_rx, "room closing").await;
+        assert!(out_rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_system_message_scope_uid_reaches_all_devices() {
+        let ts = TestServer::new();
+        let (_, mut phone_rx) = ts.add_device("alice", "phone");
+        let (_, mut laptop_rx) = ts.add_device("alice", "laptop");
+
+        let res = ts
+            .server
+            .send_system_message(
+                SystemScope::Uid("alice".into()),
+                serde_json::json!({"text": "password changed"}),
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.delivered, 2);
+        expect_system(&mut phone_rx, "password changed").await;
+        expect_system(&mut laptop_rx, "password changed").await;
+
+        let res = ts
+            .server
+            .send_system_message(SystemScope::Uid("bob".into()), serde_json::json!({}), false)
+            .await
+            .unwrap();
+        assert_eq!(
+            (res.delivered, res.offline_uids),
+            (0, vec!["bob".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_system_message_preempts_a_full_queue() {
+        let ts = TestServer::new();
+        let (alice, mut rx) = ts.add_client_with_queue("alice", 2);
+        let low = |text: &str| {
+            let msg = ImMessage {
+                msg_type: "typing".to_string(),
+                data: serde_json::json!({"text": text}),
+                target_uid: None,
+                priority: MessagePriority::Low,
+                reply_to: None,
+            };
+            Message::Text(serde_json::to_string(&msg).unwrap())
+        };
+        for text in ["t1", "t2"] {
+            ts.server
+                .send_message_to_client(&alice, low(text))
+                .await
+                .unwrap();
+        }
+        assert!(ts
+            .server
+            .send_message_to_client(&alice, low("t3"))
+            .await
+            .is_err());
+
+        // 高优先级挤掉最新的低优先级消息并先出队
+        // High priority evicts the newest low-priority message and is dequeued first
+        let res = ts
+            .server
+            .send_system_message(
+                SystemScope::Uid("alice".into()),
+                serde_json::json!({"text": "maintenance"}),
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(res.delivered, 1);
+        expect_system(&mut rx, "maintenance").await;
+        let kept: ImMessage = recv_typed(&mut rx).await;
+        assert_eq!(kept.data["text"], "t1");
+        assert_eq!(kept.priority, MessagePriority::Low);
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,207 @@
+//! 多租户限额 / Tenant-scoped limits
+//!
+//! 租户解析顺序：认证时连接元数据中的 `tenants.meta_key`（默认 `tenant`）字段，其次是 uid 中
+//! `tenants.uid_separator`（默认 `:`）之前的前缀（`acme:alice` 属于 `acme`），两者都没有时不属于任何租户。
+//! 元数据指定的租户按 uid 记住，离线后仍然适用。`[tenants.overrides.<租户>]` 可覆盖每秒投递给
+//! 单个 uid 的消息数（`msg_per_sec`）、离线配额（`offline_max_per_uid`）与房间数上限（`max_rooms`），
+//! 未覆盖的项使用全局配置 `message.max_per_sec_per_uid`、`offline.max_per_uid`、`rooms.max_per_uid`。
+//! A uid's tenant is taken from the `tenants.meta_key` (default `tenant`) field of the connection
+//! metadata sent with auth, otherwise from the uid prefix before `tenants.uid_separator`
+//! (default `:`, so `acme:alice` belongs to `acme`); with neither the uid has no tenant. A
+//! tenant set through metadata is remembered per uid and still applies while it is offline.
+//! `[tenants.overrides.<tenant>]` may override messages per second delivered to a uid
+//! (`msg_per_sec`), the offline quota (`offline_max_per_uid`) and the room cap (`max_rooms`);
+//! anything not overridden falls back to the global `message.max_per_sec_per_uid`,
+//! `offline.max_per_uid` and `rooms.max_per_uid`.
+
+use dashmap::DashMap;
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// 默认 uid 租户前缀分隔符 / Default uid tenant prefix separator
+pub const DEFAULT_UID_SEPARATOR: &str = ":";
+/// 默认携带租户的元数据键 / Default metadata key carrying the tenant
+pub const DEFAULT_META_KEY: &str = "tenant";
+
+/// 单个租户的覆盖项，未设置的使用全局值 / Overrides for one tenant; unset fields use the global value
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct TenantOverrides {
+    /// 每秒投递给单个 uid 的消息数，0 不限制 / Messages per second delivered to a uid, 0 disables
+    pub msg_per_sec: Option<usize>,
+    /// 每个 uid 最多保留的离线消息数，0 不限制 / Max offline messages kept per uid, 0 disables
+    pub offline_max_per_uid: Option<usize>,
+    /// 每个 uid 最多加入的房间数 / Max rooms per uid
+    pub max_rooms: Option<usize>,
+}
+
+/// 租户解析与覆盖配置（`[tenants]`）/ Tenant resolution and overrides (`[tenants]`)
+#[derive(Debug)]
+pub struct Tenants {
+    /// 空字符串关闭前缀解析 / An empty separator disables prefix resolution
+    uid_separator: String,
+    meta_key: String,
+    /// 全局每秒投递给单个 uid 的消息数，0 不限制 / Global messages per second per uid, 0 disables
+    msg_per_sec: usize,
+    overrides: HashMap<String, TenantOverrides>,
+    /// 通过连接元数据指定的租户 / Tenants assigned through connection metadata
+    assigned: DashMap<String, String>,
+}
+
+impl Default for Tenants {
+    fn default() -> Self {
+        Self {
+            uid_separator: DEFAULT_UID_SEPARATOR.to_string(),
+            meta_key: DEFAULT_META_KEY.to_string(),
+            msg_per_sec: 0,
+            overrides: HashMap::new(),
+            assigned: DashMap::new(),
+        }
+    }
+}
+
+impl Tenants {
+    /// 读取 `tenants.*` 与 `message.max_per_sec_per_uid` / Read `tenants.*` and `message.max_per_sec_per_uid`
+    pub fn from_config() -> Self {
+        let defaults = Self::default();
+        match v::get_global_config_manager() {
+            Ok(cm) => Self {
+                uid_separator: cm.get_or("tenants.uid_separator", defaults.uid_separator),
+                meta_key: cm.get_or("tenants.meta_key", defaults.meta_key),
+                msg_per_sec: cm.get_or("message.max_per_sec_per_uid", defaults.msg_per_sec),
+                overrides: cm
+                    .get::<HashMap<String, TenantOverrides>>("tenants.overrides")
+                    .unwrap_or_default(),
+                assigned: DashMap::new(),
+            },
+            Err(_) => defaults,
+        }
+    }
+
+    pub fn with_msg_per_sec(mut self, msg_per_sec: usize) -> Self {
+        self.msg_per_sec = msg_per_sec;
+        self
+    }
+
+    pub fn with_override(mut self, tenant: impl Into<String>, overrides: TenantOverrides) -> Self {
+        self.overrides.insert(tenant.into(), overrides);
+        self
+    }
+
+    pub fn meta_key(&self) -> &str {
+        &self.meta_key
+    }
+
+    /// 记录元数据中的租户 / Remember the tenant given in connection metadata
+    pub fn assign(&self, uid: &str, tenant: &str) {
+        self.assigned.insert(uid.to_string(), tenant.to_string());
+    }
+
+    /// 解析 uid 所属租户 / Resolve the tenant of a uid
+    pub fn resolve(&self, uid: &str) -> Option<String> {
+        if let Some(tenant) = self.assigned.get(uid) {
+            return Some(tenant.clone());
+        }
+        if self.uid_separator.is_empty() {
+            return None;
+        }
+        uid.split_once(self.uid_separator.as_str())
+            .map(|(prefix, _)| prefix)
+            .filter(|prefix| !prefix.is_empty())
+            .map(str::to_string)
+    }
+
+    /// uid 所属租户的覆盖项 / Overrides of the uid's tenant
+    pub fn overrides_for(&self, uid: &str) -> TenantOverrides {
+        self.resolve(uid)
+            .and_then(|tenant| self.overrides.get(&tenant).copied())
+            .unwrap_or_default()
+    }
+
+    /// 每秒投递给 uid 的消息上限，0 不限制 / Messages per second delivered to the uid, 0 disables
+    pub fn msg_per_sec(&self, uid: &str) -> usize {
+        self.overrides_for(uid)
+            .msg_per_sec
+            .unwrap_or(self.msg_per_sec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::message::ImMessage;
+    use crate::service::room_guard::{RoomLimits, RoomOpError};
+    use crate::testkit::{im, recv_typed, TestServer};
+
+    fn tenants() -> Tenants {
+        Tenants::default()
+            .with_msg_per_sec(100)
+            .with_override(
+                "free",
+                TenantOverrides {
+                    msg_per_sec: Some(2),
+                    max_rooms: Some(1),
+                    ..Default::default()
+                },
+            )
+            .with_override(
+                "pro",
+                TenantOverrides {
+                    msg_per_sec: Some(0),
+                    ..Default::default()
+                },
+            )
+    }
+
+    #[test]
+    fn test_resolution_and_fallback() {
+        let t = tenants();
+        assert_eq!(t.resolve("free:alice").as_deref(), Some("free"));
+        assert_eq!(t.resolve("alice"), None);
+        assert_eq!(t.resolve(":alice"), None);
+        assert_eq!(t.msg_per_sec("free:alice"), 2);
+        assert_eq!(t.msg_per_sec("pro:alice"), 0);
+        // 未配置的租户与无租户的 uid 使用全局值 / Unknown tenants and tenantless uids use the global value
+        assert_eq!(t.msg_per_sec("acme:alice"), 100);
+        assert_eq!(t.msg_per_sec("alice"), 100);
+
+        // 元数据优先于前缀 / Metadata wins over the prefix
+        t.assign("free:bob", "pro");
+        assert_eq!(t.msg_per_sec("free:bob"), 0);
+        assert_eq!(t.overrides_for("free:bob").max_rooms, None);
+    }
+
+    #[tokio::test]
+    async fn test_send_rate_and_rooms_follow_the_tenant() {
+        let ts = TestServer::build(|server| {
+            server.with_tenants(tenants()).with_room_limits(RoomLimits {
+                burst: 100,
+                ..Default::default()
+            })
+        });
+        let allowed = |uid: &str| (0..5).filter(|_| ts.server.allow_send_to_uid(uid)).count();
+        assert_eq!(allowed("free:alice"), 2);
+        assert_eq!(allowed("pro:alice"), 5);
+        assert_eq!(allowed("alice"), 5);
+
+        // 认证元数据指定的租户 / Tenant assigned by auth metadata
+        let (b, mut rx) = ts.add_client("bob");
+        ts.server.tenants.assign("bob", "free");
+        for room in ["r1", "r2"] {
+            ts.send(
+                &b,
+                im("join_room", serde_json::json!({"room_id": room}), None),
+            )
+            .await
+            .unwrap();
+        }
+        let ok: ImMessage = recv_typed(&mut rx).await;
+        assert_eq!(ok.msg_type, "join_room_ok");
+        let err: ImMessage = recv_typed(&mut rx).await;
+        assert_eq!(err.data["code"], "ROOM_LIMIT_EXCEEDED");
+        assert_eq!(ts.server.check_room_op("pro:alice", "r2", true), Ok(()));
+        assert_eq!(
+            ts.server.check_room_op("bob", "r3", true),
+            Err(RoomOpError::TooManyRooms)
+        );
+    }
+}
@@ -0,0 +1,73 @@
+//! 按键的令牌桶限流 / Keyed token-bucket rate limiting
+//!
+//! 每个键一个桶：容量为 `burst`，每秒补充 `rate_per_sec` 个令牌。
+//! One bucket per key: holds up to `burst` tokens and refills at `rate_per_sec`.
+
+use dashmap::DashMap;
+use std::time::Instant;
+
+/// 按键的令牌桶集合 / A set of token buckets keyed by string
+pub struct KeyedTokenBuckets {
+    burst: f64,
+    rate_per_sec: f64,
+    buckets: DashMap<String, (f64, Instant)>, // key -> (剩余令牌 / tokens left, 上次补充 / last refill)
+}
+
+impl KeyedTokenBuckets {
+    pub fn new(burst: u32, rate_per_sec: f64) -> Self {
+        Self {
+            burst: f64::from(burst.max(1)),
+            rate_per_sec: rate_per_sec.max(0.0),
+            buckets: DashMap::new(),
+        }
+    }
+
+    /// 尝试取一个令牌 / Try to take one token
+    pub fn try_acquire(&self, key: &str) -> bool {
+        self.try_acquire_at(key, Instant::now())
+    }
+
+    fn try_acquire_at(&self, key: &str, now: Instant) -> bool {
+        let mut entry = self
+            .buckets
+            .entry(key.to_string())
+            .or_insert((self.burst, now));
+        let (tokens, last) = *entry;
+        let refilled = (tokens
+            + now.saturating_duration_since(last).as_secs_f64() * self.rate_per_sec)
+            .min(self.burst);
+        if refilled >= 1.0 {
+            *entry = (refilled - 1.0, now);
+            true
+        } else {
+            *entry = (refilled, now);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_bucket_allows_burst_then_refills() {
+        let buckets = KeyedTokenBuckets::new(3, 2.0);
+        let t0 = Instant::now();
+        assert!((0..3).all(|_| buckets.try_acquire_at("u1", t0)));
+        assert!(!buckets.try_acquire_at("u1", t0));
+        // 其他键互不影响 / Other keys are independent
+        assert!(buckets.try_acquire_at("u2", t0));
+        // 0.5 秒补充 1 个令牌 / Half a second refills one token
+        let t1 = t0 + Duration::from_millis(500);
+        assert!(buckets.try_acquire_at("u1", t1));
+        assert!(!buckets.try_acquire_at("u1", t1));
+        // 补充不超过容量 / Refill is capped at the burst
+        let t2 = t1 + Duration::from_secs(60);
+        assert_eq!(
+            (0..5).filter(|_| buckets.try_acquire_at("u1", t2)).count(),
+            3
+        );
+    }
+}
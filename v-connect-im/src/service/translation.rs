@@ -0,0 +1,394 @@
+//! 消息自动翻译 / Automatic message translation
+//!
+//! [`TranslationPlugin`] 是一个进程内下行钩子（见 [`crate::plugins::PluginRegistry::emit_outgoing`]），
+//! 按接收连接逐个执行：当消息内容带 `lang`、接收连接在认证时协商了 `locale`，且两者主语言不同时，
+//! 把 `content.text` 译为接收方语言，写入 `data.translation = {"lang", "text"}`，原文保持不变。
+//! 翻译需显式开启：房间元数据 `auto_translate = true`（对该房间的所有消息），或接收连接的元数据
+//! `auto_translate = true`（对该用户收到的所有消息）。译文按 (内容哈希, 目标语言) 缓存。
+//! 翻译由可替换的 [`TranslationProvider`] 完成；默认的 [`PluginTranslationProvider`] 把
+//! `message.translate` 事件发给声明了 `translate` 能力的插件。翻译失败时照常投递原文。
+//! [`TranslationPlugin`] is an in-process outgoing hook (see
+//! [`crate::plugins::PluginRegistry::emit_outgoing`]) run once per receiving connection: when the
+//! content carries a `lang`, the connection negotiated a `locale` at auth and their primary
+//! languages differ, `content.text` is translated into the recipient's language and written to
+//! `data.translation = {"lang", "text"}`, leaving the original untouched. Translation is opt-in:
+//! room metadata `auto_translate = true` (every message in that room) or connection metadata
+//! `auto_translate = true` (every message that user receives). Translations are cached by
+//! (content hash, target language). The work is done by a replaceable [`TranslationProvider`]; the
+//! default [`PluginTranslationProvider`] sends a `message.translate` event to a plugin declaring
+//! the `translate` capability. A failed translation delivers the original as usual.
+
+use crate::domain::message::ImMessage;
+use crate::plugins::runtime::PluginConnectionPool;
+use crate::plugins::{Plugin, PluginContext, PluginFlow};
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use parking_lot::Mutex;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+/// 发给翻译插件的事件类型 / Event type sent to translation plugins
+pub const TRANSLATE_EVENT: &str = "message.translate";
+
+/// 翻译插件声明的能力 / Capability declared by translation plugins
+pub const TRANSLATE_CAPABILITY: &str = "translate";
+
+/// 默认缓存条数 / Default cache size
+pub const DEFAULT_CACHE_CAPACITY: usize = 10_000;
+
+/// 翻译服务 / Translation provider
+#[async_trait]
+pub trait TranslationProvider: Send + Sync {
+    /// 把 `text` 从 `source_lang` 译为 `target_lang` / Translate `text` from `source_lang` to `target_lang`
+    async fn translate(&self, text: &str, source_lang: &str, target_lang: &str) -> Result<String>;
+}
+
+/// 通过插件翻译：发送 `message.translate`，载荷 `{"text", "source_lang", "target_lang"}`，
+/// 期望响应 `{"text": "<译文>"}`
+/// Translates through a plugin: sends `message.translate` with payload
+/// `{"text", "source_lang", "target_lang"}` and expects `{"text": "<translation>"}` back
+pub struct PluginTranslationProvider {
+    pool: Arc<PluginConnectionPool>,
+}
+
+impl PluginTranslationProvider {
+    pub fn new(pool: Arc<PluginConnectionPool>) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl TranslationProvider for PluginTranslationProvider {
+    async fn translate(&self, text: &str, source_lang: &str, target_lang: &str) -> Result<String> {
+        let plugin = self
+            .pool
+            .connected_with_capability(TRANSLATE_CAPABILITY)
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("no plugin with the translate capability is connected"))?;
+        let payload = serde_json::to_vec(&json!({
+            "text": text,
+            "source_lang": source_lang,
+            "target_lang": target_lang,
+        }))?;
+        let response = self
+            .pool
+            .send_event_with_payload(&plugin, TRANSLATE_EVENT, payload)
+            .await?
+            .ok_or_else(|| anyhow!("translation plugin {} not connected", plugin))?;
+        response
+            .get("text")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("translation plugin {} returned no text", plugin))
+    }
+}
+
+/// 按 (内容哈希, 目标语言) 缓存译文，满时淘汰最早写入的条目
+/// Translations cached by (content hash, target language); the oldest entry is evicted when full
+pub struct TranslationCache {
+    capacity: usize,
+    entries: Mutex<CacheEntries>,
+}
+
+/// (内容哈希, 目标语言) / (content hash, target language)
+type CacheKey = (String, String);
+
+#[derive(Default)]
+struct CacheEntries {
+    map: HashMap<CacheKey, String>,
+    /// 写入顺序，用于淘汰 / Insertion order, for eviction
+    order: VecDeque<CacheKey>,
+}
+
+impl TranslationCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: Mutex::new(CacheEntries::default()),
+        }
+    }
+
+    fn key(text: &str, target_lang: &str) -> CacheKey {
+        (
+            hex::encode(Sha256::digest(text.as_bytes())),
+            target_lang.to_string(),
+        )
+    }
+
+    pub fn get(&self, text: &str, target_lang: &str) -> Option<String> {
+        self.entries
+            .lock()
+            .map
+            .get(&Self::key(text, target_lang))
+            .cloned()
+    }
+
+    pub fn insert(&self, text: &str, target_lang: &str, translation: String) {
+        let key = Self::key(text, target_lang);
+        let mut entries = self.entries.lock();
+        if entries.map.insert(key.clone(), translation).is_none() {
+            entries.order.push_back(key);
+            while entries.order.len() > self.capacity {
+                if let Some(oldest) = entries.order.pop_front() {
+                    entries.map.remove(&oldest);
+                }
+            }
+        }
+    }
+}
+
+/// 两个语言标签的主语言是否相同（`zh-CN` 与 `zh` 相同）
+/// Whether two language tags share their primary language (`zh-CN` matches `zh`)
+pub fn same_language(a: &str, b: &str) -> bool {
+    let primary = |tag: &str| {
+        tag.split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+    };
+    primary(a) == primary(b)
+}
+
+/// 下行翻译钩子 / Outgoing translation hook
+pub struct TranslationPlugin {
+    provider: Arc<dyn TranslationProvider>,
+    cache: TranslationCache,
+}
+
+impl TranslationPlugin {
+    pub fn new(provider: Arc<dyn TranslationProvider>) -> Self {
+        Self {
+            provider,
+            cache: TranslationCache::new(DEFAULT_CACHE_CAPACITY),
+        }
+    }
+
+    /// 设置缓存条数 / Set the cache size
+    pub fn with_cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache = TranslationCache::new(capacity);
+        self
+    }
+
+    /// 读取 `[translation]`；未开启时返回 None / Read `[translation]`; None when not enabled
+    pub fn from_config(pool: Arc<PluginConnectionPool>) -> Option<Self> {
+        let cm = v::get_global_config_manager().ok()?;
+        if !cm.get_or("translation.enabled", false) {
+            return None;
+        }
+        Some(
+            Self::new(Arc::new(PluginTranslationProvider::new(pool))).with_cache_capacity(
+                cm.get_or("translation.cache_capacity", DEFAULT_CACHE_CAPACITY),
+            ),
+        )
+    }
+
+    /// 房间或接收连接是否开启了自动翻译 / Whether the room or the receiving connection opted in
+    fn opted_in(ctx: &PluginContext<'_>, message: &ImMessage) -> bool {
+        if ctx.get_meta("auto_translate") == Some(Value::Bool(true)) {
+            return true;
+        }
+        let (Some(server), Some(room_id)) = (
+            ctx.server,
+            message.data.get("room_id").and_then(Value::as_str),
+        ) else {
+            return false;
+        };
+        server
+            .room_meta(room_id)
+            .ok()
+            .flatten()
+            .is_some_and(|meta| meta.auto_translate)
+    }
+
+    async fn translate_cached(&self, text: &str, source: &str, target: &str) -> Result<String> {
+        if let Some(hit) = self.cache.get(text, target) {
+            return Ok(hit);
+        }
+        let translated = self.provider.translate(text, source, target).await?;
+        self.cache.insert(text, target, translated.clone());
+        Ok(translated)
+    }
+}
+
+#[async_trait]
+impl Plugin for TranslationPlugin {
+    fn name(&self) -> &'static str {
+        "translation"
+    }
+
+    /// 在其他下行钩子之后执行，被拦截或改写的内容不会白白翻译
+    /// Runs after other outgoing hooks so suppressed or rewritten content is not translated in vain
+    fn priority(&self) -> u8 {
+        250
+    }
+
+    async fn on_message_outgoing(
+        &self,
+        ctx: &PluginContext<'_>,
+        message: &mut ImMessage,
+    ) -> Result<PluginFlow> {
+        let content = message.data.get("content");
+        let text = content.and_then(|c| c.get("text")).and_then(Value::as_str);
+        let lang = content.and_then(|c| c.get("lang")).and_then(Value::as_str);
+        let locale = ctx.get_meta("locale");
+        let locale = locale.as_ref().and_then(Value::as_str);
+        let (Some(text), Some(lang), Some(locale)) = (text, lang, locale) else {
+            return Ok(PluginFlow::Continue);
+        };
+        if same_language(lang, locale) || !Self::opted_in(ctx, message) {
+            return Ok(PluginFlow::Continue);
+        }
+        match self.translate_cached(text, lang, locale).await {
+            Ok(translated) => {
+                message.data["translation"] = json!({"lang": locale, "text": translated});
+            }
+            Err(e) => tracing::warn!(
+                "⚠️  翻译失败，投递原文 / Translation to {} failed, delivering the original: {}",
+                locale,
+                e
+            ),
+        }
+        Ok(PluginFlow::Continue)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::VConnectIMServer;
+    use crate::service::room_meta::{RoomMeta, RoomMetaStore};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// 记录调用次数的假翻译 / Fake provider that counts its calls
+    #[derive(Default)]
+    struct Upper {
+        calls: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl TranslationProvider for Upper {
+        async fn translate(&self, text: &str, _source: &str, target: &str) -> Result<String> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(format!("[{}] {}", target, text.to_uppercase()))
+        }
+    }
+
+    /// 把一条房间消息经钩子投递给 `client` / Run one room message through the hook for `client`
+    async fn deliver(
+        plugin: &TranslationPlugin,
+        server: &VConnectIMServer,
+        client: &str,
+    ) -> ImMessage {
+        let mut message = ImMessage {
+            msg_type: "group_message".into(),
+            data: json!({"from": "c0", "room_id": "r1", "content": {"text": "hello", "lang": "en"}}),
+            target_uid: None,
+            priority: Default::default(),
+            reply_to: None,
+        };
+        let ctx = PluginContext::new(server, client);
+        plugin
+            .on_message_outgoing(&ctx, &mut message)
+            .await
+            .unwrap();
+        message
+    }
+
+    #[tokio::test]
+    async fn test_translates_for_opted_in_recipients_and_caches() {
+        let path = std::env::temp_dir().join(format!("vgo-translation-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        let ts = crate::testkit::TestServer::build(|server| {
+            server.with_room_meta_store(Arc::new(RoomMetaStore::new(&path)))
+        });
+        let (alice, _alice_rx) = ts.add_client("alice");
+        let (bob, _bob_rx) = ts.add_client("bob");
+        let server: &VConnectIMServer = &ts.server;
+        server
+            .connections
+            .get(&alice)
+            .unwrap()
+            .set_meta("locale", json!("en-US"));
+        server
+            .connections
+            .get(&bob)
+            .unwrap()
+            .set_meta("locale", json!("zh-CN"));
+
+        let provider = Arc::new(Upper::default());
+        let plugin = TranslationPlugin::new(provider.clone());
+
+        // 未开启：原样投递 / Not opted in: delivered as is
+        assert!(deliver(&plugin, server, &bob)
+            .await
+            .data
+            .get("translation")
+            .is_none());
+
+        // 房间开启后只翻译语言不同的接收方 / With the room opted in only other-language recipients get a translation
+        server
+            .set_room_meta(&RoomMeta {
+                room_id: "r1".into(),
+                auto_translate: true,
+                ..Default::default()
+            })
+            .unwrap();
+        let to_bob = deliver(&plugin, server, &bob).await;
+        assert_eq!(to_bob.data["content"]["text"], "hello");
+        assert_eq!(
+            to_bob.data["translation"],
+            json!({"lang": "zh-CN", "text": "[zh-CN] HELLO"})
+        );
+        assert!(deliver(&plugin, server, &alice)
+            .await
+            .data
+            .get("translation")
+            .is_none());
+
+        // 命中缓存不再调用翻译服务 / A cache hit skips the provider
+        deliver(&plugin, server, &bob).await;
+        assert_eq!(provider.calls.load(Ordering::SeqCst), 1);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[tokio::test]
+    async fn test_user_opt_in_without_room() {
+        let ts = crate::testkit::TestServer::new();
+        let (bob, _bob_rx) = ts.add_client("bob");
+        let conn = ts.server.connections.get(&bob).unwrap().clone();
+        conn.set_meta("locale", json!("fr"));
+        let plugin = TranslationPlugin::new(Arc::new(Upper::default()));
+        let mut direct = ImMessage {
+            msg_type: "forwarded_message".into(),
+            data: json!({"from": "alice", "content": {"text": "hi", "lang": "en"}}),
+            target_uid: None,
+            priority: Default::default(),
+            reply_to: None,
+        };
+        let ctx = PluginContext::new(&ts.server, &bob);
+        plugin.on_message_outgoing(&ctx, &mut direct).await.unwrap();
+        assert!(direct.data.get("translation").is_none());
+
+        conn.set_meta("auto_translate", json!(true));
+        plugin.on_message_outgoing(&ctx, &mut direct).await.unwrap();
+        assert_eq!(direct.data["translation"]["text"], "[fr] HI");
+    }
+
+    #[test]
+    fn test_cache_evicts_oldest_and_language_match() {
+        let cache = TranslationCache::new(2);
+        cache.insert("a", "fr", "A".into());
+        cache.insert("b", "fr", "B".into());
+        cache.insert("a", "de", "A2".into());
+        assert_eq!(cache.get("a", "fr"), None);
+        assert_eq!(cache.get("b", "fr").as_deref(), Some("B"));
+        assert_eq!(cache.get("a", "de").as_deref(), Some("A2"));
+
+        assert!(same_language("zh-CN", "zh"));
+        assert!(same_language("en_US", "EN-gb"));
+        assert!(!same_language("en", "fr"));
+    }
+}
@@ -0,0 +1,596 @@
+//! Webhook 投递与重试 / Webhook delivery and retries
+//!
+//! 配置 `webhook.url`（或 `[[webhook.endpoints]]` 多个地址）后，客户端上线/下线事件以 JSON POST
+//! 到每个订阅了该类别的地址：`events` 可选 `ClientStatus`、`Message`，为空时接收全部；配置了
+//! `secret` 时附带签名，见 [`crate::domain::webhook`]。首次投递失败（网络错误或非 2xx）的事件写入本地 sled
+//! 重试队列（`webhook.queue_path`），后台任务按指数退避（`webhook.retry_base_ms` 起翻倍，
+//! 最多 `webhook.retry_max_ms`）重试，尝试 `webhook.max_attempts` 次仍失败则移入死信，
+//! 可通过 `/v1/admin/webhooks/dead_letter` 查看。队列落盘，重启后继续重试，因此接收方可能收到
+//! 重复事件（至少一次），应按 `event_id` 去重。指标通过 `/v1/health/detailed` 的
+//! `details.webhooks` 输出。
+//! With `webhook.url` (or several `[[webhook.endpoints]]`) configured, client online/offline
+//! events are POSTed as JSON to every endpoint subscribed to their category: `events` takes
+//! `ClientStatus` and `Message` and receives everything when empty; each endpoint signs with
+//! its own `secret` when set (see [`crate::domain::webhook`]). Events whose first
+//! delivery fails (network error or non-2xx) go to a local sled retry queue
+//! (`webhook.queue_path`) that a background task retries with exponential backoff (starting at
+//! `webhook.retry_base_ms`, doubling up to `webhook.retry_max_ms`); after
+//! `webhook.max_attempts` failed attempts an event moves to the dead letter, listed by
+//! `/v1/admin/webhooks/dead_letter`. The queue is on disk and survives restarts, so receivers
+//! may see duplicates (at-least-once) and should dedupe by `event_id`. Metrics are reported
+//! under `details.webhooks` of `/v1/health/detailed`.
+
+use crate::domain::message::{WebhookEvent, WebhookEventType};
+use crate::service::event_bus::{self, ServerEvent};
+use anyhow::{anyhow, Result};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// 默认重试队列路径 / Default retry queue path
+pub const DEFAULT_QUEUE_PATH: &str = "./data/webhook-queue";
+
+/// 可订阅的事件类别 / Event categories an endpoint can subscribe to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum WebhookEventFilter {
+    /// `client_online` / `client_offline`
+    ClientStatus,
+    /// `message_sent` / `message_delivered` / `message_failed`
+    Message,
+}
+
+impl WebhookEventFilter {
+    pub fn matches(self, event_type: &WebhookEventType) -> bool {
+        match self {
+            Self::ClientStatus => matches!(
+                event_type,
+                WebhookEventType::ClientOnline | WebhookEventType::ClientOffline
+            ),
+            Self::Message => matches!(
+                event_type,
+                WebhookEventType::MessageSent
+                    | WebhookEventType::MessageDelivered
+                    | WebhookEventType::MessageFailed
+            ),
+        }
+    }
+}
+
+/// 单个接收地址 / One webhook endpoint
+#[derive(Debug, Clone, Deserialize)]
+pub struct WebhookEndpoint {
+    pub url: String,
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// 订阅的类别，为空时接收全部 / Subscribed categories; empty receives everything
+    #[serde(default)]
+    pub events: Vec<WebhookEventFilter>,
+}
+
+impl WebhookEndpoint {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            secret: None,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn accepts(&self, event_type: &WebhookEventType) -> bool {
+        self.events.is_empty() || self.events.iter().any(|f| f.matches(event_type))
+    }
+}
+
+/// Webhook 配置（`[webhook]`）/ Webhook config (`[webhook]`)
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// 为空时不投递 / Nothing is delivered when empty
+    pub endpoints: Vec<WebhookEndpoint>,
+    pub timeout_ms: u64,
+    /// 包含首次投递在内的最多尝试次数 / Max attempts including the first delivery
+    pub max_attempts: u32,
+    pub retry_base_ms: u64,
+    pub retry_max_ms: u64,
+    pub queue_path: PathBuf,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            endpoints: Vec::new(),
+            timeout_ms: 5000,
+            max_attempts: 8,
+            retry_base_ms: 1000,
+            retry_max_ms: 300_000,
+            queue_path: PathBuf::from(DEFAULT_QUEUE_PATH),
+        }
+    }
+}
+
+impl WebhookConfig {
+    /// 读取 `webhook.*`：`webhook.url` / `secret` / `events` 是第一个接收地址，
+    /// `[[webhook.endpoints]]` 追加更多
+    /// Read `webhook.*`: `webhook.url` / `secret` / `events` form the first endpoint and
+    /// `[[webhook.endpoints]]` adds more
+    pub fn from_config() -> Self {
+        let defaults = Self::default();
+        let Ok(cm) = v::get_global_config_manager() else {
+            return defaults;
+        };
+        let primary = cm
+            .get::<String>("webhook.url")
+            .ok()
+            .filter(|u| !u.is_empty())
+            .map(|url| WebhookEndpoint {
+                url,
+                secret: cm
+                    .get::<String>("webhook.secret")
+                    .ok()
+                    .filter(|s| !s.is_empty()),
+                events: cm.get_or("webhook.events", Vec::new()),
+            });
+        let endpoints = primary
+            .into_iter()
+            .chain(cm.get_or("webhook.endpoints", Vec::<WebhookEndpoint>::new()))
+            .collect();
+        Self {
+            endpoints,
+            timeout_ms: cm.get_or("webhook.timeout_ms", defaults.timeout_ms),
+            max_attempts: cm
+                .get_or("webhook.max_attempts", defaults.max_attempts)
+                .max(1),
+            retry_base_ms: cm.get_or("webhook.retry_base_ms", defaults.retry_base_ms),
+            retry_max_ms: cm.get_or("webhook.retry_max_ms", defaults.retry_max_ms),
+            queue_path: crate::config::resolve_path(
+                cm.get_or("webhook.queue_path", DEFAULT_QUEUE_PATH.to_string()),
+            ),
+        }
+    }
+
+    /// 第 `attempts` 次失败后的等待时间 / Delay after the `attempts`-th failure
+    fn backoff_ms(&self, attempts: u32) -> u64 {
+        let factor = 1u64 << attempts.saturating_sub(1).min(32);
+        self.retry_base_ms
+            .saturating_mul(factor)
+            .min(self.retry_max_ms)
+    }
+}
+
+/// 队列中的事件 / A queued event
+#[derive(Debug, Serialize, Deserialize)]
+struct QueuedWebhook {
+    /// 目标地址（重试时据此查找密钥）/ Target URL (its secret is looked up on retry)
+    endpoint: String,
+    event: WebhookEvent,
+    attempts: u32,
+    last_error: String,
+    /// 移入死信的时间 / When it moved to the dead letter
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dead_at: Option<i64>,
+}
+
+/// Webhook 投递器 / Webhook dispatcher
+pub struct WebhookDispatcher {
+    config: WebhookConfig,
+    http: reqwest::Client,
+    /// 首次失败时打开，未失败过的节点不会创建目录
+    /// Opened on the first failure, so nodes that never fail create no directory
+    queue: Mutex<Option<sled::Db>>,
+    sent: AtomicU64,
+    failed_attempts: AtomicU64,
+}
+
+impl Default for WebhookDispatcher {
+    fn default() -> Self {
+        Self::new(WebhookConfig::from_config())
+    }
+}
+
+impl WebhookDispatcher {
+    pub fn new(config: WebhookConfig) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_millis(config.timeout_ms))
+            .build()
+            .unwrap_or_default();
+        Self {
+            config,
+            http,
+            queue: Mutex::new(None),
+            sent: AtomicU64::new(0),
+            failed_attempts: AtomicU64::new(0),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.config.endpoints.is_empty()
+    }
+
+    /// 把总线上的上下线事件转为 webhook 投递 / Turn an online/offline bus event into a webhook delivery
+    pub async fn emit_client_status(&self, event: &ServerEvent) {
+        let event_type = match event.event_type.as_str() {
+            event_bus::CLIENT_ONLINE => WebhookEventType::ClientOnline,
+            event_bus::CLIENT_OFFLINE => WebhookEventType::ClientOffline,
+            _ => return,
+        };
+        self.emit(WebhookEvent {
+            event_type,
+            event_id: uuid::Uuid::new_v4().to_string(),
+            timestamp: event.timestamp,
+            data: (*event.payload).clone(),
+            retry_count: None,
+        })
+        .await
+    }
+
+    /// 投递给订阅了该事件的每个地址，失败的写入重试队列
+    /// Deliver to every endpoint subscribed to the event, queueing failures for retry
+    pub async fn emit(&self, event: WebhookEvent) {
+        let mut failed = false;
+        for endpoint in self
+            .config
+            .endpoints
+            .iter()
+            .filter(|e| e.accepts(&event.event_type))
+        {
+            let result = self.post(endpoint, &event).await;
+            failed |= result.is_err();
+            if let Err(e) = self.settle(&endpoint.url, event.clone(), 0, result) {
+                tracing::error!(
+                    "❌ webhook 重试队列写入失败 / Failed to queue webhook retry: {}",
+                    e
+                );
+            }
+        }
+        if failed {
+            if let Ok(Some((db, _, _))) = self.existing() {
+                let _ = db.flush_async().await;
+            }
+        }
+    }
+
+    /// 重试到期的事件，返回本轮处理数 / Retry due events; returns how many were processed
+    pub async fn retry_due(&self, now_ms: i64) -> Result<usize> {
+        let Some((db, pending, _)) = self.existing()? else {
+            return Ok(0);
+        };
+        // 只处理本轮开始时已到期的，重新入队的留到下一轮 / Only events due at the start; requeued ones wait for the next round
+        let now = now_ms.max(0) as u64;
+        let due: Vec<(sled::IVec, sled::IVec)> = pending
+            .iter()
+            .take_while(|item| item.as_ref().map_or(true, |(key, _)| due_at(key) <= now))
+            .collect::<Result<_, _>>()?;
+        for (key, value) in &due {
+            let mut queued: QueuedWebhook = serde_json::from_slice(value)?;
+            queued.event.retry_count = Some(queued.attempts);
+            // 已从配置中移除的地址不再重试 / Endpoints removed from the config are not retried
+            let (result, attempts) = match self.endpoint(&queued.endpoint) {
+                Some(endpoint) => (self.post(endpoint, &queued.event).await, queued.attempts),
+                None => (
+                    Err(anyhow!("webhook endpoint no longer configured")),
+                    self.config.max_attempts,
+                ),
+            };
+            // 先写入新状态再移除旧键，崩溃时最多重复投递 / Record the outcome before removing the old key, so a crash only duplicates
+            self.settle(&queued.endpoint, queued.event, attempts, result)?;
+            pending.remove(key)?;
+        }
+        if !due.is_empty() {
+            db.flush_async().await?;
+        }
+        Ok(due.len())
+    }
+
+    /// 死信列表（最早的在前）/ Dead-letter entries, oldest first
+    pub fn dead_letters(&self, limit: usize) -> Result<Vec<Value>> {
+        let Some((_, _, dead)) = self.existing()? else {
+            return Ok(Vec::new());
+        };
+        dead.iter()
+            .values()
+            .take(limit)
+            .map(|v| Ok(serde_json::from_slice(&v?)?))
+            .collect()
+    }
+
+    /// 指标快照（JSON）/ Metrics snapshot as JSON
+    pub fn snapshot(&self) -> Value {
+        let (pending, dead) = match self.existing() {
+            Ok(Some((_, pending, dead))) => (pending.len(), dead.len()),
+            _ => (0, 0),
+        };
+        json!({
+            "enabled": self.enabled(),
+            "sent": self.sent.load(Ordering::Relaxed),
+            "failed_attempts": self.failed_attempts.load(Ordering::Relaxed),
+            "pending": pending,
+            "dead": dead,
+        })
+    }
+
+    fn endpoint(&self, url: &str) -> Option<&WebhookEndpoint> {
+        self.config.endpoints.iter().find(|e| e.url == url)
+    }
+
+    async fn post(&self, endpoint: &WebhookEndpoint, event: &WebhookEvent) -> Result<()> {
+        let mut req = self
+            .http
+            .post(&endpoint.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+        req = match &endpoint.secret {
+            Some(secret) => {
+                let signed = event.sign(secret)?;
+                for (name, value) in signed.headers() {
+                    req = req.header(name, value);
+                }
+                req.body(signed.body)
+            }
+            None => req.body(serde_json::to_vec(event)?),
+        };
+        let resp = req.send().await?;
+        if !resp.status().is_success() {
+            return Err(anyhow!("webhook receiver returned {}", resp.status()));
+        }
+        Ok(())
+    }
+
+    /// 记录一次尝试的结果：失败时按退避重新入队或移入死信
+    /// Record the outcome of an attempt: on failure requeue with backoff or move to the dead letter
+    fn settle(
+        &self,
+        endpoint: &str,
+        event: WebhookEvent,
+        previous: u32,
+        result: Result<()>,
+    ) -> Result<()> {
+        let error = match result {
+            Ok(()) => {
+                self.sent.fetch_add(1, Ordering::Relaxed);
+                return Ok(());
+            }
+            Err(e) => e,
+        };
+        self.failed_attempts.fetch_add(1, Ordering::Relaxed);
+        let attempts = (previous + 1).min(self.config.max_attempts);
+        let (db, pending, dead) = self.open()?;
+        let id = db.generate_id()?;
+        let mut queued = QueuedWebhook {
+            endpoint: endpoint.to_string(),
+            event,
+            attempts,
+            last_error: error.to_string(),
+            dead_at: None,
+        };
+        if attempts >= self.config.max_attempts {
+            tracing::warn!(
+                "☠️  webhook {} -> {} 重试 {} 次后移入死信 / webhook {} -> {} moved to the dead letter after {} attempts: {}",
+                queued.event.event_id,
+                endpoint,
+                attempts,
+                queued.event.event_id,
+                endpoint,
+                attempts,
+                error
+            );
+            queued.dead_at = Some(chrono::Utc::now().timestamp_millis());
+            dead.insert(id.to_be_bytes(), serde_json::to_vec(&queued)?)?;
+        } else {
+            let due = chrono::Utc::now().timestamp_millis().max(0) as u64
+                + self.config.backoff_ms(attempts);
+            let mut key = due.to_be_bytes().to_vec();
+            key.extend_from_slice(&id.to_be_bytes());
+            pending.insert(key, serde_json::to_vec(&queued)?)?;
+        }
+        Ok(())
+    }
+
+    fn open(&self) -> Result<(sled::Db, sled::Tree, sled::Tree)> {
+        let mut queue = self.queue.lock();
+        let db = match queue.as_ref() {
+            Some(db) => db.clone(),
+            None => {
+                let db = sled::open(&self.config.queue_path)?;
+                *queue = Some(db.clone());
+                db
+            }
+        };
+        let pending = db.open_tree("pending")?;
+        let dead = db.open_tree("dead")?;
+        Ok((db, pending, dead))
+    }
+
+    /// 只打开已存在的队列（上次运行留下的也算）
+    /// Open the queue only if it exists, including one left by a previous run
+    fn existing(&self) -> Result<Option<(sled::Db, sled::Tree, sled::Tree)>> {
+        if self.queue.lock().is_none() && !self.config.queue_path.exists() {
+            return Ok(None);
+        }
+        self.open().map(Some)
+    }
+}
+
+/// 待重试键的前 8 字节为到期时间 / The first 8 bytes of a pending key are its due time
+fn due_at(key: &[u8]) -> u64 {
+    key.get(..8)
+        .and_then(|b| b.try_into().ok())
+        .map(u64::from_be_bytes)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::webhook::{verify_signature, DEFAULT_TOLERANCE_MS, SIGNATURE_HEADER};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// 依次以给定状态码应答的接收方，返回地址与收到的请求
+    /// A receiver answering with the given status codes in turn; returns its URL and the requests
+    async fn receiver(statuses: Vec<u16>) -> (String, tokio::sync::mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        tokio::spawn(async move {
+            for status in statuses {
+                let (mut sock, _) = listener.accept().await.unwrap();
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                // 读到完整的请求头与请求体 / Read the full head and body
+                loop {
+                    let n = sock.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..n]);
+                    let text = String::from_utf8_lossy(&buf).to_string();
+                    if let Some(head_end) = text.find("\r\n\r\n") {
+                        let len = text[..head_end]
+                            .lines()
+                            .find_map(|l| {
+                                l.to_lowercase()
+                                    .strip_prefix("content-length:")
+                                    .map(|v| v.trim().parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if buf.len() >= head_end + 4 + len || n == 0 {
+                            break;
+                        }
+                    }
+                }
+                let reply = format!(
+                    "HTTP/1.1 {} X\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                    status
+                );
+                sock.write_all(reply.as_bytes()).await.unwrap();
+                tx.send(String::from_utf8_lossy(&buf).to_string())
+                    .await
+                    .unwrap();
+            }
+        });
+        (url, rx)
+    }
+
+    fn dispatcher(url: String, name: &str, max_attempts: u32) -> WebhookDispatcher {
+        let queue_path =
+            std::env::temp_dir().join(format!("vgo-webhook-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&queue_path);
+        WebhookDispatcher::new(WebhookConfig {
+            endpoints: vec![WebhookEndpoint {
+                secret: Some("s3cret".into()),
+                ..WebhookEndpoint::new(url)
+            }],
+            max_attempts,
+            retry_base_ms: 10,
+            queue_path,
+            ..Default::default()
+        })
+    }
+
+    fn event(id: &str) -> WebhookEvent {
+        WebhookEvent {
+            event_type: WebhookEventType::ClientOnline,
+            event_id: id.into(),
+            timestamp: 0,
+            data: json!({"client_id": "c1"}),
+            retry_count: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_delivery_is_retried_with_backoff() {
+        let (url, mut requests) = receiver(vec![503, 200]).await;
+        let hooks = dispatcher(url, "retry", 5);
+        hooks.emit(event("e1")).await;
+        requests.recv().await.unwrap();
+        let snap = hooks.snapshot();
+        assert_eq!(
+            (snap["pending"].clone(), snap["failed_attempts"].clone()),
+            (json!(1), json!(1))
+        );
+
+        // 退避未到期时不重试 / Nothing is retried before the backoff elapses
+        assert_eq!(hooks.retry_due(0).await.unwrap(), 0);
+        assert_eq!(hooks.retry_due(i64::MAX).await.unwrap(), 1);
+        let retried = requests.recv().await.unwrap();
+        assert!(retried.contains("\"retry_count\":1"));
+        let (head, body) = retried.split_once("\r\n\r\n").unwrap();
+        let header = |name: &str| {
+            head.lines()
+                .find_map(|l| {
+                    l.split_once(": ")
+                        .filter(|(k, _)| k.eq_ignore_ascii_case(name))
+                })
+                .map(|(_, v)| v.to_string())
+                .unwrap()
+        };
+        let ts: i64 = header("x-signature-timestamp").parse().unwrap();
+        verify_signature(
+            "s3cret",
+            body.as_bytes(),
+            ts,
+            &header(SIGNATURE_HEADER),
+            DEFAULT_TOLERANCE_MS,
+            ts,
+        )
+        .unwrap();
+        let snap = hooks.snapshot();
+        assert_eq!(
+            (snap["sent"].clone(), snap["pending"].clone()),
+            (json!(1), json!(0))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_retries_move_to_dead_letter() {
+        // 关闭的端口：连接被拒绝 / A closed port: connections are refused
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let hooks = dispatcher(format!("http://127.0.0.1:{}/hook", port), "dead", 2);
+        hooks.emit(event("e1")).await;
+        assert_eq!(hooks.retry_due(i64::MAX).await.unwrap(), 1);
+        assert_eq!(hooks.retry_due(i64::MAX).await.unwrap(), 0);
+
+        let snap = hooks.snapshot();
+        assert_eq!(
+            (snap["pending"].clone(), snap["dead"].clone()),
+            (json!(0), json!(1))
+        );
+        let dead = hooks.dead_letters(10).unwrap();
+        assert_eq!(dead[0]["event"]["event_id"], "e1");
+        assert_eq!(dead[0]["attempts"], 2);
+        assert!(dead[0]["dead_at"].is_i64());
+    }
+
+    #[tokio::test]
+    async fn test_filtered_out_event_is_not_delivered() {
+        let (status_url, mut status_requests) = receiver(vec![200]).await;
+        let (message_url, mut message_requests) = receiver(vec![200]).await;
+        let mut hooks = dispatcher(status_url, "filter", 3);
+        hooks.config.endpoints[0].events = vec![WebhookEventFilter::ClientStatus];
+        hooks.config.endpoints.push(WebhookEndpoint {
+            events: vec![WebhookEventFilter::Message],
+            ..WebhookEndpoint::new(message_url)
+        });
+
+        hooks.emit(event("e1")).await;
+        let got = status_requests.recv().await.unwrap();
+        assert!(got.contains("\"event_id\":\"e1\""));
+        // 消息端点没有订阅上线事件 / The message endpoint is not subscribed to client status
+        assert!(message_requests.try_recv().is_err());
+        let snap = hooks.snapshot();
+        assert_eq!(
+            (snap["sent"].clone(), snap["pending"].clone()),
+            (json!(1), json!(0))
+        );
+
+        let endpoints: Vec<WebhookEndpoint> = serde_json::from_value(json!([
+            {"url": "http://a", "events": ["ClientStatus", "Message"]},
+            {"url": "http://b"},
+        ]))
+        .unwrap();
+        assert!(endpoints[0].accepts(&WebhookEventType::MessageFailed));
+        assert!(endpoints[1].accepts(&WebhookEventType::ClientOffline));
+    }
+}
@@ -0,0 +1,920 @@
+//! 内置 sled 存储 / Built-in sled storage
+//!
+//! 单二进制部署没有存储插件时的持久化：`storage.use_builtin = true`，或未配置该项且没有安装
+//! 存储插件时启用，数据位于 `storage.path`。连接池仍优先使用已连接的存储插件，只有插件
+//! 不可用时才由这里处理，并以插件相同的 JSON 格式应答存储事件；搜索与归档不支持。
+//! 写入由 sled 周期性落盘，`storage.flush` 事件（按类型的 `flush` 策略与停机时发送）立即落盘。
+//! Persistence for single-binary deployments without a storage plugin: enabled by
+//! `storage.use_builtin = true`, or when that key is unset and no storage plugin is
+//! installed, with data under `storage.path`. The connection pool still prefers a connected
+//! storage plugin and only falls back to this store when none is usable; storage events
+//! are answered in the same JSON shape the plugin uses. Search and archiving are not
+//! supported. Writes are flushed periodically by sled; the `storage.flush` event (sent by
+//! the per-type `flush` policy and at shutdown) flushes immediately.
+//!
+//! wal 与 offline 键中的时间戳取自 `MonotonicClock`，系统时钟回拨时也不回退，记录体保留真实时间。
+//! Timestamps in wal and offline keys come from a `MonotonicClock`, so they never go back when the
+//! system clock does; record bodies keep the real time.
+
+use super::{MessageRecord, OfflineRecord, ReadReceipt};
+use crate::domain::message::MessagePriority;
+use anyhow::Result;
+use serde_json::{json, Value};
+use sled::Tree;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use v::comm::clock::MonotonicClock;
+use v::plugin::protocol::{
+    GROUP_ACK_ADD_EVENT, GROUP_ACK_STATUS_EVENT, GROUP_ACK_TRACK_EVENT, MESSAGE_GET_EVENT,
+    MESSAGE_PURGE_EVENT, MESSAGE_THREAD_EVENT, PIN_ADD_EVENT, PIN_LIST_EVENT, PIN_REMOVE_EVENT,
+    REACTION_ADD_EVENT, REACTION_LIST_EVENT, REACTION_REMOVE_EVENT, ROOM_UPDATE_MEMBERS_EVENT,
+    STORAGE_FLUSH_EVENT,
+};
+
+/// 默认数据目录 / Default data directory
+pub const DEFAULT_BUILTIN_PATH: &str = "./data/v-connect-im-node-local";
+
+/// 内置存储 / Built-in storage
+#[derive(Clone)]
+pub struct BuiltinStorage {
+    db: sled::Db,
+    wal: Tree,
+    offline: Tree,
+    room_members: Tree,
+    reads: Tree,
+    reactions: Tree,
+    pins: Tree,
+    /// 回复索引（parent_id:wal 键 -> wal 键）/ Reply index (parent_id:wal key -> wal key)
+    threads: Tree,
+    /// 群消息应确认成员（message_id -> [room_id, sender_uid, recipients]）/ Group ack recipients (message_id -> [room_id, sender_uid, recipients])
+    group_ack_messages: Tree,
+    /// 群成员确认（message_id:uid -> acked_at）/ Group member acks (message_id:uid -> acked_at)
+    group_acks: Tree,
+    /// wal 与 offline 键的时间戳，时钟回拨时也不回退 / Timestamps for wal and offline keys; never go back, even when the clock does
+    key_clock: Arc<MonotonicClock>,
+}
+
+impl BuiltinStorage {
+    pub fn open(path: &str) -> Result<Self> {
+        Self::with_db(sled::open(path)?)
+    }
+
+    /// 临时库，关闭后删除（测试用）/ Temporary database removed on drop (for tests)
+    pub fn open_temporary() -> Result<Self> {
+        Self::with_db(sled::Config::new().temporary(true).open()?)
+    }
+
+    fn with_db(db: sled::Db) -> Result<Self> {
+        let wal = db.open_tree("wal")?;
+        // 从最新的 wal 键继续，重启前后键同样单调 / Continue after the newest wal key so keys stay monotonic across restarts
+        let key_clock = MonotonicClock::new();
+        if let Some((key, _)) = wal.last()? {
+            let ts = String::from_utf8_lossy(&key)
+                .split(':')
+                .next()
+                .and_then(|ts| ts.parse().ok());
+            if let Some(ts) = ts {
+                key_clock.resume_after(ts);
+            }
+        }
+        Ok(Self {
+            wal,
+            offline: db.open_tree("offline")?,
+            room_members: db.open_tree("room_members")?,
+            reads: db.open_tree("reads")?,
+            reactions: db.open_tree("reactions")?,
+            pins: db.open_tree("pins")?,
+            threads: db.open_tree("threads")?,
+            group_ack_messages: db.open_tree("group_ack_messages")?,
+            group_acks: db.open_tree("group_acks")?,
+            key_clock: Arc::new(key_clock),
+            db,
+        })
+    }
+
+    /// 按存储插件的事件约定处理；不支持的事件返回 None
+    /// Handle a storage event the way the storage plugin does; None for unsupported events
+    pub fn handle(&self, event_type: &str, payload: &Value) -> Result<Option<Value>> {
+        let str_of = |key: &str| payload.get(key).and_then(Value::as_str).unwrap_or_default();
+        let limit = payload.get("limit").and_then(Value::as_u64).unwrap_or(100) as usize;
+        let ids: Vec<String> = payload
+            .get("message_ids")
+            .and_then(|v| serde_json::from_value(v.clone()).ok())
+            .unwrap_or_default();
+
+        let response = match event_type {
+            "storage.offline.save" => {
+                let rec: OfflineRecord = serde_json::from_value(payload.clone())?;
+                self.store_offline(&rec)?;
+                json!({"status": "ok", "message_id": rec.message_id})
+            }
+            "storage.offline.pull" => {
+                let messages = self.pull_offline(str_of("to_uid"), limit)?;
+                json!({"status": "ok", "total": messages.len(), "messages": messages})
+            }
+            "storage.offline.ack" => {
+                let removed = self.ack_offline(str_of("to_uid"), &ids)?;
+                json!({"status": "ok", "count": removed, "removed": removed})
+            }
+            "storage.offline.count" => {
+                json!({"status": "ok", "count": self.offline_count(str_of("to_uid"))?})
+            }
+            "storage.offline.delete" => {
+                let deleted = self.delete_offline(str_of("to_uid"), &ids)?;
+                json!({"status": "ok", "count": deleted, "deleted": deleted})
+            }
+            "storage.message.history" => {
+                let since = payload.get("since_ts").and_then(Value::as_i64);
+                let until = payload.get("until_ts").and_then(Value::as_i64);
+                let peer = payload.get("peer").and_then(Value::as_str);
+                let messages: Vec<Value> = self
+                    .list_messages_by_user(str_of("uid"), peer, since, until, limit)?
+                    .iter()
+                    .map(history_json)
+                    .collect();
+                json!({"status": "ok", "total": messages.len(), "messages": messages})
+            }
+            MESSAGE_GET_EVENT => {
+                let message = self.get(str_of("message_id"))?;
+                json!({
+                    "status": "ok",
+                    "found": message.is_some(),
+                    "message": message.as_ref().map(history_json),
+                })
+            }
+            MESSAGE_THREAD_EVENT => {
+                let parent_id = payload
+                    .get("parent_id")
+                    .and_then(Value::as_str)
+                    .unwrap_or_else(|| str_of("message_id"));
+                let messages: Vec<Value> = self
+                    .list_thread(parent_id)?
+                    .iter()
+                    .take(limit)
+                    .map(history_json)
+                    .collect();
+                json!({"status": "ok", "total": messages.len(), "messages": messages})
+            }
+            "storage.room.add_member" => {
+                self.add_room_member(str_of("room_id"), str_of("uid"))?;
+                json!({"status": "ok"})
+            }
+            "storage.room.remove_member" => {
+                self.remove_room_member(str_of("room_id"), str_of("uid"))?;
+                json!({"status": "ok"})
+            }
+            ROOM_UPDATE_MEMBERS_EVENT => {
+                let uids = |key: &str| -> Vec<String> {
+                    payload
+                        .get(key)
+                        .and_then(Value::as_array)
+                        .map(|ids| {
+                            ids.iter()
+                                .filter_map(Value::as_str)
+                                .map(str::to_string)
+                                .collect()
+                        })
+                        .unwrap_or_default()
+                };
+                let room_id = str_of("room_id");
+                self.update_room_members(room_id, &uids("add"), &uids("remove"))?;
+                json!({"status": "ok", "members": self.list_room_members(room_id)?})
+            }
+            "storage.room.list_members" => {
+                json!({"status": "ok", "members": self.list_room_members(str_of("room_id"))?})
+            }
+            "storage.room.list" => json!({"status": "ok", "rooms": self.list_rooms()?}),
+            "storage.read.record" => {
+                self.record_read(&serde_json::from_value(payload.clone())?)?;
+                json!({"status": "ok"})
+            }
+            REACTION_ADD_EVENT => {
+                let changed =
+                    self.add_reaction(str_of("message_id"), str_of("uid"), str_of("emoji"))?;
+                json!({"status": "ok", "changed": changed})
+            }
+            REACTION_REMOVE_EVENT => {
+                let changed =
+                    self.remove_reaction(str_of("message_id"), str_of("uid"), str_of("emoji"))?;
+                json!({"status": "ok", "changed": changed})
+            }
+            REACTION_LIST_EVENT => {
+                let reactions: Vec<Value> = self
+                    .list_reactions(str_of("message_id"))?
+                    .into_iter()
+                    .map(|(emoji, count, uids)| json!({"emoji": emoji, "count": count, "uids": uids}))
+                    .collect();
+                json!({"status": "ok", "reactions": reactions})
+            }
+            PIN_ADD_EVENT => {
+                let pinned_at = payload
+                    .get("pinned_at")
+                    .and_then(Value::as_i64)
+                    .unwrap_or(0);
+                let max_pins = payload.get("max_pins").and_then(Value::as_u64).unwrap_or(0);
+                let (changed, full) = self.add_pin(
+                    str_of("room_id"),
+                    str_of("message_id"),
+                    str_of("pinned_by"),
+                    pinned_at,
+                    max_pins as usize,
+                )?;
+                json!({"status": "ok", "changed": changed, "full": full})
+            }
+            PIN_REMOVE_EVENT => {
+                let changed = self.remove_pin(str_of("room_id"), str_of("message_id"))?;
+                json!({"status": "ok", "changed": changed})
+            }
+            PIN_LIST_EVENT => {
+                let pins: Vec<Value> = self
+                    .list_pins(str_of("room_id"))?
+                    .into_iter()
+                    .map(|(message_id, pinned_by, pinned_at)| {
+                        json!({"message_id": message_id, "pinned_by": pinned_by, "pinned_at": pinned_at})
+                    })
+                    .collect();
+                json!({"status": "ok", "pins": pins})
+            }
+            GROUP_ACK_TRACK_EVENT => {
+                let recipients: Vec<String> = payload
+                    .get("recipients")
+                    .and_then(|v| serde_json::from_value(v.clone()).ok())
+                    .unwrap_or_default();
+                self.track_group_ack(
+                    str_of("message_id"),
+                    str_of("room_id"),
+                    str_of("sender_uid"),
+                    recipients,
+                )?;
+                json!({"status": "ok"})
+            }
+            GROUP_ACK_ADD_EVENT => {
+                let acked_at = payload.get("acked_at").and_then(Value::as_i64).unwrap_or(0);
+                let recorded = self.add_group_ack(str_of("message_id"), str_of("uid"), acked_at)?;
+                json!({"status": "ok", "recorded": recorded})
+            }
+            GROUP_ACK_STATUS_EVENT => self.group_ack_status(str_of("message_id"))?,
+            MESSAGE_PURGE_EVENT => {
+                if payload.get("archive").and_then(Value::as_bool) == Some(true) {
+                    anyhow::bail!(
+                        "内置存储不支持归档 / The built-in storage does not support archiving"
+                    );
+                }
+                let until = payload.get("until_ts").and_then(Value::as_i64).unwrap_or(0);
+                let deleted = self.purge_room(str_of("room_id"), until)?;
+                json!({"status": "ok", "deleted": deleted, "archive_key": ""})
+            }
+            STORAGE_FLUSH_EVENT => {
+                self.flush()?;
+                json!({"status": "ok"})
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(response))
+    }
+
+    /// 键时间取自单调时钟，记录体保留原时间 / Key times come from the monotonic clock; the body keeps the original time
+    pub fn append(&self, rec: &MessageRecord) -> Result<()> {
+        let key = format!("{}:{}", self.key_clock.issue(rec.timestamp), rec.message_id);
+        self.wal.insert(key.as_bytes(), serde_json::to_vec(rec)?)?;
+        if let Some(parent_id) = &rec.reply_to {
+            self.threads
+                .insert(format!("{}:{}", parent_id, key).as_bytes(), key.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, message_id: &str) -> Result<Option<MessageRecord>> {
+        let suffix = format!(":{}", message_id);
+        for item in self.wal.iter() {
+            let (k, v) = item?;
+            if k.ends_with(suffix.as_bytes()) {
+                return Ok(Some(serde_json::from_slice(&v)?));
+            }
+        }
+        Ok(None)
+    }
+
+    /// 回复某条消息的消息，按写入顺序 / Replies to a message, in write order
+    pub fn list_thread(&self, parent_id: &str) -> Result<Vec<MessageRecord>> {
+        let mut keys = Vec::new();
+        for item in self
+            .threads
+            .scan_prefix(format!("{}:", parent_id).as_bytes())
+        {
+            keys.push(item?.1);
+        }
+        // wal 键以时间戳开头，按数值排序 / wal keys start with the timestamp; order numerically
+        keys.sort_by_key(|key| {
+            String::from_utf8_lossy(key)
+                .split(':')
+                .next()
+                .and_then(|ts| ts.parse::<i64>().ok())
+        });
+        let mut replies = Vec::new();
+        for key in keys {
+            if let Some(v) = self.wal.get(&key)? {
+                replies.push(serde_json::from_slice(&v)?);
+            }
+        }
+        Ok(replies)
+    }
+
+    /// 按用户查询历史消息（按时间过滤与限制）/ List message history by user with time filters
+    pub fn list_messages_by_user(
+        &self,
+        user: &str,
+        peer: Option<&str>,
+        since_ts: Option<i64>,
+        until_ts: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<MessageRecord>> {
+        let mut res = Vec::new();
+        for item in self.wal.iter() {
+            let (_k, v) = item?;
+            let rec: MessageRecord = serde_json::from_slice(&v)?;
+            let involves = |uid: &str| rec.to_client_id == uid || rec.from_client_id == uid;
+            if !involves(user)
+                || peer.is_some_and(|p| !involves(p))
+                || since_ts.is_some_and(|since| rec.timestamp < since)
+                || until_ts.is_some_and(|until| rec.timestamp > until)
+            {
+                continue;
+            }
+            res.push(rec);
+            if res.len() >= limit {
+                break;
+            }
+        }
+        Ok(res)
+    }
+
+    /// 键与 sled 存储插件一致：`high` 加 `!:`、`low` 加 `~:` 段，拉取时高优先级在前
+    /// Keys match the sled storage plugin: `high` adds a `!:` segment and `low` a `~:` one, so
+    /// pulls return higher priorities first
+    pub fn store_offline(&self, rec: &OfflineRecord) -> Result<()> {
+        let band = match rec.priority {
+            MessagePriority::High => "!:",
+            MessagePriority::Normal => "",
+            MessagePriority::Low => "~:",
+        };
+        let key = format!(
+            "{}:{}{}:{}",
+            rec.to_uid,
+            band,
+            self.key_clock.issue(rec.timestamp),
+            rec.message_id
+        );
+        self.offline
+            .insert(key.as_bytes(), serde_json::to_vec(rec)?)?;
+        Ok(())
+    }
+
+    /// 按优先级从高到低、同级按时间从旧到新拉取 / Pull highest priority first, oldest first within one
+    pub fn pull_offline(&self, to_uid: &str, limit: usize) -> Result<Vec<OfflineRecord>> {
+        let prefix = format!("{}:", to_uid);
+        let mut res = Vec::new();
+        for item in self.offline.scan_prefix(prefix.as_bytes()).take(limit) {
+            let (_k, v) = item?;
+            res.push(serde_json::from_slice(&v)?);
+        }
+        Ok(res)
+    }
+
+    pub fn ack_offline(&self, to_uid: &str, message_ids: &[String]) -> Result<usize> {
+        if message_ids.is_empty() {
+            return Ok(0);
+        }
+        self.delete_offline(to_uid, message_ids)
+    }
+
+    /// 删除指定离线消息，ID 列表为空时删除全部 / Delete the given offline messages, or all of them for an empty id list
+    pub fn delete_offline(&self, to_uid: &str, message_ids: &[String]) -> Result<usize> {
+        let prefix = format!("{}:", to_uid);
+        let mut removed = 0;
+        for item in self.offline.scan_prefix(prefix.as_bytes()) {
+            let (k, v) = item?;
+            let rec: OfflineRecord = serde_json::from_slice(&v)?;
+            if message_ids.is_empty() || message_ids.contains(&rec.message_id) {
+                self.offline.remove(k)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    pub fn offline_count(&self, to_uid: &str) -> Result<usize> {
+        let prefix = format!("{}:", to_uid);
+        Ok(self.offline.scan_prefix(prefix.as_bytes()).count())
+    }
+
+    pub fn add_room_member(&self, room_id: &str, uid: &str) -> Result<()> {
+        let key = format!("{}:{}", room_id, uid);
+        self.room_members.insert(key.as_bytes(), b"1")?;
+        Ok(())
+    }
+
+    pub fn remove_room_member(&self, room_id: &str, uid: &str) -> Result<()> {
+        let key = format!("{}:{}", room_id, uid);
+        self.room_members.remove(key.as_bytes())?;
+        Ok(())
+    }
+
+    /// 一次批量写入加入与移除成员 / Add and remove members in one batched write
+    pub fn update_room_members(
+        &self,
+        room_id: &str,
+        add: &[String],
+        remove: &[String],
+    ) -> Result<()> {
+        let mut batch = sled::Batch::default();
+        for uid in add {
+            batch.insert(format!("{}:{}", room_id, uid).as_bytes(), b"1");
+        }
+        for uid in remove {
+            batch.remove(format!("{}:{}", room_id, uid).as_bytes());
+        }
+        self.room_members.apply_batch(batch)?;
+        Ok(())
+    }
+
+    pub fn list_room_members(&self, room_id: &str) -> Result<Vec<String>> {
+        let prefix = format!("{}:", room_id);
+        let mut res = Vec::new();
+        for item in self.room_members.scan_prefix(prefix.as_bytes()) {
+            let (k, _v) = item?;
+            res.push(String::from_utf8_lossy(&k[prefix.len()..]).to_string());
+        }
+        Ok(res)
+    }
+
+    pub fn list_rooms(&self) -> Result<Vec<String>> {
+        let mut rooms = BTreeSet::new();
+        for item in self.room_members.iter() {
+            let (k, _v) = item?;
+            if let Some((rid, _uid)) = String::from_utf8_lossy(&k).split_once(':') {
+                rooms.insert(rid.to_string());
+            }
+        }
+        Ok(rooms.into_iter().collect())
+    }
+
+    pub fn record_read(&self, rr: &ReadReceipt) -> Result<()> {
+        let key = format!("{}:{}", rr.uid, rr.message_id);
+        self.reads.insert(key.as_bytes(), serde_json::to_vec(rr)?)?;
+        Ok(())
+    }
+
+    /// 删除房间内 `until_ts` 之前的消息及其表情回应、置顶、回复索引与群确认，返回删除条数
+    /// Delete a room's messages older than `until_ts` with their reactions, pins, reply index
+    /// entries and group acks; returns how many
+    pub fn purge_room(&self, room_id: &str, until_ts: i64) -> Result<usize> {
+        let mut deleted = 0;
+        for item in self.wal.iter() {
+            let (key, value) = item?;
+            let rec: MessageRecord = serde_json::from_slice(&value)?;
+            if rec.timestamp >= until_ts || rec.room_id.as_deref() != Some(room_id) {
+                continue;
+            }
+            self.wal.remove(&key)?;
+            for reaction in self.reactions.scan_prefix(format!("{}:", rec.message_id)) {
+                self.reactions.remove(reaction?.0)?;
+            }
+            self.pins
+                .remove(format!("{}:{}", room_id, rec.message_id).as_bytes())?;
+            if let Some(parent_id) = &rec.reply_to {
+                let mut thread_key = format!("{}:", parent_id).into_bytes();
+                thread_key.extend_from_slice(&key);
+                self.threads.remove(thread_key)?;
+            }
+            if self
+                .group_ack_messages
+                .remove(rec.message_id.as_bytes())?
+                .is_some()
+            {
+                for ack in self.group_acks.scan_prefix(format!("{}:", rec.message_id)) {
+                    self.group_acks.remove(ack?.0)?;
+                }
+            }
+            deleted += 1;
+        }
+        Ok(deleted)
+    }
+
+    /// 添加表情回应，已存在时返回 false / Add a reaction; false when it already existed
+    pub fn add_reaction(&self, message_id: &str, uid: &str, emoji: &str) -> Result<bool> {
+        let key = format!("{}:{}:{}", message_id, uid, emoji);
+        let value = serde_json::to_vec(&(uid, emoji))?;
+        Ok(self.reactions.insert(key.as_bytes(), value)?.is_none())
+    }
+
+    /// 移除表情回应，不存在时返回 false / Remove a reaction; false when it did not exist
+    pub fn remove_reaction(&self, message_id: &str, uid: &str, emoji: &str) -> Result<bool> {
+        let key = format!("{}:{}:{}", message_id, uid, emoji);
+        Ok(self.reactions.remove(key.as_bytes())?.is_some())
+    }
+
+    /// 按表情汇总：`(emoji, count, uids)`，表情与 uid 均排序
+    /// Aggregate per emoji as `(emoji, count, uids)`, with emojis and uids sorted
+    pub fn list_reactions(&self, message_id: &str) -> Result<Vec<(String, usize, Vec<String>)>> {
+        // uid 与表情都可能含 ':'，从值中解析 / uids and emojis may contain ':', so parse the value
+        let prefix = format!("{}:", message_id);
+        let mut by_emoji: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for item in self.reactions.scan_prefix(prefix.as_bytes()) {
+            let (_k, v) = item?;
+            let (uid, emoji): (String, String) = serde_json::from_slice(&v)?;
+            by_emoji.entry(emoji).or_default().insert(uid);
+        }
+        Ok(by_emoji
+            .into_iter()
+            .map(|(emoji, uids)| (emoji, uids.len(), uids.into_iter().collect()))
+            .collect())
+    }
+
+    /// 置顶房间消息，返回 `(changed, full)`；`max_pins` 为 0 时不限
+    /// Pin a room message and return `(changed, full)`; `max_pins` of 0 means unlimited
+    pub fn add_pin(
+        &self,
+        room_id: &str,
+        message_id: &str,
+        pinned_by: &str,
+        pinned_at: i64,
+        max_pins: usize,
+    ) -> Result<(bool, bool)> {
+        let key = format!("{}:{}", room_id, message_id);
+        if self.pins.contains_key(key.as_bytes())? {
+            return Ok((false, false));
+        }
+        let prefix = format!("{}:", room_id);
+        if max_pins > 0 && self.pins.scan_prefix(prefix.as_bytes()).count() >= max_pins {
+            return Ok((false, true));
+        }
+        let value = serde_json::to_vec(&(message_id, pinned_by, pinned_at))?;
+        self.pins.insert(key.as_bytes(), value)?;
+        Ok((true, false))
+    }
+
+    /// 取消置顶，未置顶时返回 false / Unpin a room message; false when it was not pinned
+    pub fn remove_pin(&self, room_id: &str, message_id: &str) -> Result<bool> {
+        let key = format!("{}:{}", room_id, message_id);
+        Ok(self.pins.remove(key.as_bytes())?.is_some())
+    }
+
+    /// 房间置顶 `(message_id, pinned_by, pinned_at)`，按置顶时间排序
+    /// A room's pins as `(message_id, pinned_by, pinned_at)`, ordered by pin time
+    pub fn list_pins(&self, room_id: &str) -> Result<Vec<(String, String, i64)>> {
+        let prefix = format!("{}:", room_id);
+        let mut pins = Vec::new();
+        for item in self.pins.scan_prefix(prefix.as_bytes()) {
+            let (_k, v) = item?;
+            pins.push(serde_json::from_slice::<(String, String, i64)>(&v)?);
+        }
+        pins.sort_by(|a, b| (a.2, &a.0).cmp(&(b.2, &b.0)));
+        Ok(pins)
+    }
+
+    /// 登记群消息应确认的成员 / Register the members expected to ack a group message
+    pub fn track_group_ack(
+        &self,
+        message_id: &str,
+        room_id: &str,
+        sender_uid: &str,
+        mut recipients: Vec<String>,
+    ) -> Result<()> {
+        recipients.sort();
+        recipients.dedup();
+        let value = serde_json::to_vec(&(room_id, sender_uid, recipients))?;
+        self.group_ack_messages
+            .insert(message_id.as_bytes(), value)?;
+        Ok(())
+    }
+
+    /// 记录成员确认并保留首次确认时间；消息未登记或非接收者时返回 false
+    /// Record a member ack keeping the first ack time; false for unknown messages or non-recipients
+    pub fn add_group_ack(&self, message_id: &str, uid: &str, acked_at: i64) -> Result<bool> {
+        let is_recipient = self
+            .group_ack_message(message_id)?
+            .map(|(_, _, recipients)| recipients.iter().any(|r| r == uid))
+            .unwrap_or(false);
+        if is_recipient {
+            let key = format!("{}:{}", message_id, uid);
+            if !self.group_acks.contains_key(key.as_bytes())? {
+                self.group_acks
+                    .insert(key.as_bytes(), serde_json::to_vec(&acked_at)?)?;
+            }
+        }
+        Ok(is_recipient)
+    }
+
+    /// 群消息确认进度，按存储插件的应答格式 / Group ack progress in the storage plugin's response shape
+    pub fn group_ack_status(&self, message_id: &str) -> Result<Value> {
+        let Some((room_id, sender_uid, recipients)) = self.group_ack_message(message_id)? else {
+            return Ok(json!({"status": "ok", "found": false}));
+        };
+        let mut acked = Vec::new();
+        let mut pending = Vec::new();
+        for uid in recipients {
+            let key = format!("{}:{}", message_id, uid);
+            if self.group_acks.contains_key(key.as_bytes())? {
+                acked.push(uid);
+            } else {
+                pending.push(uid);
+            }
+        }
+        Ok(json!({
+            "status": "ok",
+            "found": true,
+            "room_id": room_id,
+            "sender_uid": sender_uid,
+            "acked": acked,
+            "pending": pending,
+        }))
+    }
+
+    fn group_ack_message(&self, message_id: &str) -> Result<Option<(String, String, Vec<String>)>> {
+        match self.group_ack_messages.get(message_id.as_bytes())? {
+            Some(v) => Ok(Some(serde_json::from_slice(&v)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        self.db.flush()?;
+        Ok(())
+    }
+}
+
+/// 与存储插件历史消息相同的字段 / Same fields as the storage plugin's history messages
+fn history_json(rec: &MessageRecord) -> Value {
+    json!({
+        "message_id": rec.message_id,
+        "from_uid": rec.from_client_id,
+        "to_uid": rec.to_client_id,
+        "content": rec.content,
+        "timestamp": rec.timestamp,
+        "msg_type": rec.msg_type,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::message::HttpSendMessageRequest;
+    use crate::plugins::runtime::{PluginConnectionPool, PluginRuntimeManager};
+    use crate::testkit::{im, recv_typed, TestServer};
+    use crate::ws::send_queue::QueueReceiver;
+    use crate::ImMessage;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_rooms_reads_and_unsupported_events() {
+        let storage = BuiltinStorage::open_temporary().unwrap();
+        for (room, uid) in [("r2", "a"), ("r1", "a"), ("r1", "b")] {
+            let payload = json!({"room_id": room, "uid": uid});
+            storage.handle("storage.room.add_member", &payload).unwrap();
+        }
+        storage
+            .handle(
+                "storage.room.remove_member",
+                &json!({"room_id": "r1", "uid": "a"}),
+            )
+            .unwrap();
+        let members = storage
+            .handle("storage.room.list_members", &json!({"room_id": "r1"}))
+            .unwrap()
+            .unwrap();
+        assert_eq!(members["members"], json!(["b"]));
+        let rooms = storage.handle("storage.room.list", &json!({})).unwrap();
+        assert_eq!(rooms.unwrap()["rooms"], json!(["r1", "r2"]));
+        let updated = storage
+            .handle(
+                ROOM_UPDATE_MEMBERS_EVENT,
+                &json!({"room_id": "r1", "add": ["c", "d"], "remove": ["b"]}),
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(updated["members"], json!(["c", "d"]));
+
+        let read = json!({"uid": "a", "message_id": "m1", "timestamp": 1});
+        let resp = storage.handle("storage.read.record", &read).unwrap();
+        assert_eq!(resp.unwrap()["status"], "ok");
+        assert!(storage
+            .handle("storage.message.search", &json!({"query": "x"}))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_group_acks_survive_a_reopen() {
+        let path = std::env::temp_dir().join(format!("vgo-group-acks-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        {
+            let storage = BuiltinStorage::open(path.to_str().unwrap()).unwrap();
+            let track = json!({"message_id": "m1", "room_id": "r1", "sender_uid": "a", "recipients": ["c", "b"]});
+            storage.handle(GROUP_ACK_TRACK_EVENT, &track).unwrap();
+            for (uid, recorded) in [("b", true), ("a", false)] {
+                let ack = json!({"message_id": "m1", "uid": uid, "acked_at": 1});
+                let resp = storage.handle(GROUP_ACK_ADD_EVENT, &ack).unwrap().unwrap();
+                assert_eq!(resp["recorded"], recorded);
+            }
+            storage.flush().unwrap();
+        }
+        let storage = BuiltinStorage::open(path.to_str().unwrap()).unwrap();
+        let status = storage
+            .handle(GROUP_ACK_STATUS_EVENT, &json!({"message_id": "m1"}))
+            .unwrap()
+            .unwrap();
+        assert_eq!(status["sender_uid"], "a");
+        assert_eq!(status["acked"], json!(["b"]));
+        assert_eq!(status["pending"], json!(["c"]));
+        let missing = storage
+            .handle(GROUP_ACK_STATUS_EVENT, &json!({"message_id": "m2"}))
+            .unwrap()
+            .unwrap();
+        assert_eq!(missing["found"], false);
+        drop(storage);
+        let _ = std::fs::remove_dir_all(&path);
+    }
+
+    #[test]
+    fn test_offline_pull_is_highest_priority_first() {
+        let storage = BuiltinStorage::open_temporary().unwrap();
+        for (id, ts, priority) in [("l", 1, "low"), ("h1", 2, "high"), ("n", 3, "normal")] {
+            let rec = json!({
+                "message_id": id, "from_uid": null, "to_uid": "bob", "room_id": null,
+                "content": {}, "timestamp": ts, "msg_type": "message", "priority": priority,
+            });
+            storage.handle("storage.offline.save", &rec).unwrap();
+        }
+        // 旧记录没有 priority，按 normal 处理 / Older records without a priority count as normal
+        let old = json!({
+            "message_id": "old", "from_uid": null, "to_uid": "bob", "room_id": null,
+            "content": {}, "timestamp": 4, "msg_type": "message",
+        });
+        storage.handle("storage.offline.save", &old).unwrap();
+        let rec = json!({
+            "message_id": "h2", "from_uid": null, "to_uid": "bob", "room_id": null,
+            "content": {}, "timestamp": 9, "msg_type": "message", "priority": "high",
+        });
+        storage.handle("storage.offline.save", &rec).unwrap();
+
+        let ids: Vec<String> = storage
+            .pull_offline("bob", 10)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.message_id)
+            .collect();
+        assert_eq!(ids, ["h1", "h2", "n", "old", "l"]);
+        assert_eq!(storage.delete_offline("bob", &["l".into()]).unwrap(), 1);
+        assert_eq!(storage.offline_count("bob").unwrap(), 4);
+    }
+
+    #[test]
+    fn test_history_and_offline_follow_write_order_across_a_clock_jump() {
+        let storage = BuiltinStorage::open_temporary().unwrap();
+        // 第二条之后时钟回拨 / The clock steps back after the second message
+        for (id, ts) in [("m0", 5_000), ("m1", 5_001), ("m2", 4_000)] {
+            let rec = json!({
+                "message_id": id, "from_client_id": "a", "to_client_id": "b", "content": {},
+                "timestamp": ts, "msg_type": "message", "room_id": null,
+            });
+            storage
+                .append(&serde_json::from_value(rec).unwrap())
+                .unwrap();
+            let rec = json!({
+                "message_id": id, "from_uid": "a", "to_uid": "b", "room_id": null,
+                "content": {}, "timestamp": ts, "msg_type": "message",
+            });
+            storage.handle("storage.offline.save", &rec).unwrap();
+        }
+
+        let history = storage
+            .list_messages_by_user("b", None, None, None, 10)
+            .unwrap();
+        let ids: Vec<&str> = history.iter().map(|r| r.message_id.as_str()).collect();
+        assert_eq!(ids, ["m0", "m1", "m2"]);
+        assert_eq!(history[2].timestamp, 4_000);
+        let offline = storage.pull_offline("b", 10).unwrap();
+        let ids: Vec<&str> = offline.iter().map(|r| r.message_id.as_str()).collect();
+        assert_eq!(ids, ["m0", "m1", "m2"]);
+        assert_eq!(offline[2].timestamp, 4_000);
+        assert_eq!(storage.get("m2").unwrap().unwrap().timestamp, 4_000);
+    }
+
+    #[tokio::test]
+    async fn test_offline_flow_with_only_builtin_storage() {
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = Arc::new(PluginConnectionPool::new(manager));
+        pool.enable_builtin_storage(Arc::new(BuiltinStorage::open_temporary().unwrap()));
+        let ts = TestServer::build(|server| server.with_plugin_connection_pool(pool.clone()));
+
+        // bob 离线：消息保存后在 ACK 截止时间后写入离线队列
+        // bob is offline: the message is saved, then queued offline after the ack deadline
+        let sent = ts
+            .server
+            .http_send_message(HttpSendMessageRequest {
+                from_uid: "alice".into(),
+                to_uid: "bob".into(),
+                content: json!({"text": "while you were away"}),
+                message_type: None,
+            })
+            .await;
+        assert!(sent.success);
+        let message_id = sent.message_id.unwrap();
+        let started = Instant::now();
+        while ts.server.offline_count("bob").await.unwrap() == 0 {
+            assert!(started.elapsed() < Duration::from_secs(10));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        // bob 上线后补发并确认 / bob comes online; the message is replayed and acknowledged
+        let (bob, mut bob_rx) = ts.add_client("bob");
+        assert_eq!(
+            ts.server
+                .deliver_offline_for_uid("bob", &bob)
+                .await
+                .unwrap(),
+            1
+        );
+        let replayed: ImMessage = recv_typed(&mut bob_rx).await;
+        assert_eq!(replayed.msg_type, "forwarded_message");
+        assert_eq!(replayed.data["message_id"], message_id);
+        assert_eq!(replayed.data["content"]["text"], "while you were away");
+        assert_eq!(ts.server.offline_count("bob").await.unwrap(), 0);
+
+        // 历史与按 ID 查询同样由内置存储应答 / History and lookup by id are served by the built-in store too
+        let history = pool
+            .storage_query_history(Some("bob"), Some("alice"), None, None, 10)
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0]["message_id"], message_id);
+        let found = pool.storage_get_message(&message_id).await.unwrap();
+        assert_eq!(found.unwrap().unwrap()["from_uid"], "alice");
+        assert!(pool.storage_flush_now().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_replies_are_forwarded_with_reply_to_and_listed_as_a_thread() {
+        let manager = Arc::new(PluginRuntimeManager::new("./plugins", "./plugins"));
+        let pool = Arc::new(PluginConnectionPool::new(manager));
+        pool.enable_builtin_storage(Arc::new(BuiltinStorage::open_temporary().unwrap()));
+        let ts = TestServer::build(|server| server.with_plugin_connection_pool(pool.clone()));
+        let (alice, mut alice_rx) = ts.add_client("alice");
+        let (bob, mut bob_rx) = ts.add_client("bob");
+        // 跳过发送确认等帧 / Skip sender confirmations and other frames
+        async fn next_forwarded(rx: &mut QueueReceiver) -> ImMessage {
+            loop {
+                let msg: ImMessage = recv_typed(rx).await;
+                if msg.msg_type == "forwarded_message" {
+                    return msg;
+                }
+            }
+        }
+        let send = |text: &str, to: &str, reply_to: Option<&str>| {
+            let mut msg = im("message", json!({"text": text}), Some(to));
+            msg.reply_to = reply_to.map(str::to_string);
+            msg
+        };
+
+        ts.send(&alice, send("lunch?", "bob", None)).await.unwrap();
+        let parent = next_forwarded(&mut bob_rx).await;
+        assert_eq!(parent.reply_to, None);
+        let parent_id = parent.data["message_id"].as_str().unwrap().to_string();
+
+        ts.send(&bob, send("sure", "alice", Some(&parent_id)))
+            .await
+            .unwrap();
+        let first = next_forwarded(&mut alice_rx).await;
+        assert_eq!(first.reply_to.as_deref(), Some(parent_id.as_str()));
+        ts.send(&alice, send("noon then", "bob", Some(&parent_id)))
+            .await
+            .unwrap();
+        let second = next_forwarded(&mut bob_rx).await;
+        assert_eq!(second.reply_to.as_deref(), Some(parent_id.as_str()));
+
+        let thread = pool.storage_list_thread(&parent_id, 10).await.unwrap();
+        let texts: Vec<&str> = thread
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|m| m["content"]["text"].as_str().unwrap())
+            .collect();
+        assert_eq!(texts, ["sure", "noon then"]);
+        let builtin = pool.builtin_storage().unwrap();
+        let replies = builtin.list_thread(&parent_id).unwrap();
+        assert_eq!(replies[1].message_id, second.data["message_id"]);
+        assert!(replies
+            .iter()
+            .all(|r| r.reply_to.as_deref() == Some(parent_id.as_str())));
+        assert_eq!(
+            pool.storage_list_thread(&parent_id, 1)
+                .await
+                .unwrap()
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+}
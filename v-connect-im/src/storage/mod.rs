@@ -1,14 +1,15 @@
-//! 存储模块 - 数据结构定义
-//! Storage Module - Data Structure Definitions
+//! 存储模块 - 数据结构定义与内置存储
+//! Storage Module - Data Structure Definitions and Built-in Storage
 //!
-//! ⚠️ 存储功能已完全迁移到存储插件
-//! ⚠️ Storage functionality has been fully migrated to storage plugin
+//! 存储功能由存储插件提供；没有存储插件的单二进制部署使用 [`builtin`] 中的内置 sled 存储
+//! Storage is provided by the storage plugin; single-binary deployments without one use
+//! the built-in sled storage in [`builtin`]
 //!
-//! 此模块仅保留数据结构定义，用于类型共享和序列化
-//! This module only retains data structure definitions for type sharing and serialization
-//!
-//! 使用存储功能请调用 `PluginConnectionPool::storage_*` 方法
-//! To use storage functionality, call `PluginConnectionPool::storage_*` methods
+//! 使用存储功能请调用 `PluginConnectionPool::storage_*` 方法，两者由连接池统一调度
+//! To use storage functionality, call `PluginConnectionPool::storage_*` methods; the pool
+//! routes each call to the plugin or the built-in storage
+
+pub mod builtin;
 
 // ============================================================================
 // 数据结构定义 / Data Structure Definitions
@@ -27,6 +28,26 @@ pub struct MessageRecord {
     pub timestamp: i64,
     pub msg_type: String,
     pub room_id: Option<String>,
+    /// 附件元数据（旧记录无此字段）/ Attachment metadata (absent in older records)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachment: Option<AttachmentMeta>,
+    /// 所回复的消息ID（旧记录无此字段）/ Parent message_id this replies to (absent in older records)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<String>,
+}
+
+/// 附件元数据 / Attachment Metadata
+///
+/// 附件本体通过 URL 引用，服务端只校验并保存元数据
+/// The blob itself is referenced by URL; the server only validates and keeps metadata
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AttachmentMeta {
+    pub url: String,
+    pub mime: String,
+    pub size: u64,
+    pub sha256: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail_base64: Option<String>,
 }
 
 /// 离线消息记录 / Offline Message Record
@@ -42,6 +63,9 @@ pub struct OfflineRecord {
     pub content: serde_json::Value,
     pub timestamp: i64,
     pub msg_type: String,
+    /// 重连补发时高优先级先送达 / Higher priorities are replayed first on reconnect
+    #[serde(default)]
+    pub priority: crate::domain::message::MessagePriority,
 }
 
 /// 已读回执 / Read Receipt
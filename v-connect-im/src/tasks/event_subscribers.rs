@@ -0,0 +1,82 @@
+use crate::server::VConnectIMServer;
+use crate::service::event_bus::{self, ServerEvent};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::{broadcast, watch};
+
+/// 启动进程内事件订阅者：插件转发、指标计数，以及配置了地址时的上下线 webhook
+/// Start the in-process event subscribers: plugin forwarding, metrics counting and, when
+/// endpoints are configured, online/offline webhooks
+pub fn spawn_event_subscribers(server: Arc<VConnectIMServer>, shutdown_rx: watch::Receiver<bool>) {
+    let bus = server.event_bus.clone();
+
+    let registry = server.plugin_registry.clone();
+    consume(
+        &server,
+        bus.subscribe(event_bus::CONNECTION_AUTHENTICATED),
+        shutdown_rx.clone(),
+        move |event| {
+            let registry = registry.clone();
+            async move {
+                if let Err(e) = registry
+                    .emit_custom(&event.event_type, &event.payload)
+                    .await
+                {
+                    tracing::warn!("plugin {} event error: {}", event.event_type, e);
+                }
+            }
+        },
+    );
+
+    let metrics = server.metrics.clone();
+    consume(
+        &server,
+        bus.subscribe(event_bus::ALL_EVENTS),
+        shutdown_rx.clone(),
+        move |event| {
+            metrics.record_event(&event.event_type);
+            std::future::ready(())
+        },
+    );
+
+    if server.webhooks.enabled() {
+        for event_type in [event_bus::CLIENT_ONLINE, event_bus::CLIENT_OFFLINE] {
+            let webhooks = server.webhooks.clone();
+            consume(
+                &server,
+                bus.subscribe(event_type),
+                shutdown_rx.clone(),
+                move |event| {
+                    let webhooks = webhooks.clone();
+                    async move { webhooks.emit_client_status(&event).await }
+                },
+            );
+        }
+    }
+}
+
+/// 逐个处理订阅到的事件，直到总线关闭或停机 / Handle subscribed events one by one until the bus closes or shutdown
+fn consume<F, Fut>(
+    server: &VConnectIMServer,
+    mut rx: broadcast::Receiver<ServerEvent>,
+    mut shutdown_rx: watch::Receiver<bool>,
+    mut handle: F,
+) where
+    F: FnMut(ServerEvent) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send,
+{
+    let bus = server.event_bus.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                event = bus.next(&mut rx) => match event {
+                    Some(event) => handle(event).await,
+                    None => break,
+                },
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() { break; }
+                }
+            }
+        }
+    });
+}
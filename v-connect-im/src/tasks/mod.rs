@@ -1 +1,6 @@
+pub mod event_subscribers;
 pub mod heartbeat;
+pub mod reaper;
+pub mod retention;
+pub mod scheduler;
+pub mod webhook_retry;
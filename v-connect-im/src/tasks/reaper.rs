@@ -0,0 +1,31 @@
+use crate::server::VConnectIMServer;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::time::interval;
+
+/// 后台按寿命与空闲上限回收连接，两条上限都关闭时不启动
+/// Reap connections past the lifetime or idle cap in the background; not started when both caps are off
+pub fn spawn_reaper_task(server: Arc<VConnectIMServer>, mut shutdown_rx: watch::Receiver<bool>) {
+    let Some(sweep_every) = server.reaper.sweep_interval() else {
+        return;
+    };
+    tokio::spawn(async move {
+        tracing::info!(
+            "⏰ Reaper interval set to {:?} (max lifetime {:?}, max idle {:?})",
+            sweep_every,
+            server.reaper.max_lifetime,
+            server.reaper.max_idle
+        );
+        let mut sweep_interval = interval(sweep_every);
+        loop {
+            tokio::select! {
+                _ = sweep_interval.tick() => {
+                    server.reap_connections();
+                }
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() { break; }
+                }
+            }
+        }
+    });
+}
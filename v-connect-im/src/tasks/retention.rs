@@ -0,0 +1,32 @@
+use crate::server::VConnectIMServer;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::time::interval;
+
+/// 后台按保留期清理各房间的历史消息 / Purge room history past its retention in the background
+pub fn spawn_retention_task(server: Arc<VConnectIMServer>, mut shutdown_rx: watch::Receiver<bool>) {
+    tokio::spawn(async move {
+        let mut sweep_interval = interval(server.retention.interval);
+        loop {
+            tokio::select! {
+                _ = sweep_interval.tick() => {
+                    let now = chrono::Utc::now().timestamp_millis();
+                    match server.sweep_retention(now).await {
+                        Ok(purged) => {
+                            for (room_id, purge) in purged {
+                                tracing::info!(
+                                    "🧹 房间 {} 清理 {} 条过期消息 / Purged {} expired messages from room {} (archive: {:?})",
+                                    room_id, purge.deleted, purge.deleted, room_id, purge.archive_key
+                                );
+                            }
+                        }
+                        Err(e) => tracing::warn!("⚠️  保留期清理失败 / Retention sweep failed: {}", e),
+                    }
+                }
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() { break; }
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,37 @@
+use crate::server::VConnectIMServer;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::time::interval;
+
+/// 后台投递到期的定时消息；启动时先恢复上次运行留下的待投递消息
+/// Deliver due scheduled messages in the background, first recovering those left by the previous run
+pub fn spawn_scheduler_task(server: Arc<VConnectIMServer>, mut shutdown_rx: watch::Receiver<bool>) {
+    match server.scheduler.recover() {
+        Ok(0) => {}
+        Ok(pending) => tracing::info!(
+            "⏰ 恢复 {} 条定时消息 / Recovered {} scheduled messages",
+            pending,
+            pending
+        ),
+        Err(e) => tracing::warn!(
+            "⚠️  定时消息恢复失败 / Scheduled message recovery failed: {}",
+            e
+        ),
+    }
+    tokio::spawn(async move {
+        let mut poll_interval = interval(server.scheduler.poll_interval());
+        loop {
+            tokio::select! {
+                _ = poll_interval.tick() => {
+                    let now = chrono::Utc::now().timestamp_millis();
+                    if let Err(e) = server.run_due_scheduled(now).await {
+                        tracing::warn!("⚠️  定时消息投递失败 / Scheduled delivery failed: {}", e);
+                    }
+                }
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() { break; }
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,31 @@
+use crate::server::VConnectIMServer;
+use std::sync::Arc;
+use tokio::sync::watch;
+use tokio::time::{interval, Duration};
+
+/// 重试队列轮询间隔 / Retry queue polling interval
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 后台重试失败的 webhook（未配置 `webhook.url` 时不启动）
+/// Retry failed webhooks in the background (not started without `webhook.url`)
+pub fn spawn_retry_task(server: Arc<VConnectIMServer>, mut shutdown_rx: watch::Receiver<bool>) {
+    if !server.webhooks.enabled() {
+        return;
+    }
+    tokio::spawn(async move {
+        let mut retry_interval = interval(RETRY_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = retry_interval.tick() => {
+                    let now = chrono::Utc::now().timestamp_millis();
+                    if let Err(e) = server.webhooks.retry_due(now).await {
+                        tracing::warn!("⚠️  webhook 重试失败 / Webhook retry failed: {}", e);
+                    }
+                }
+                _ = shutdown_rx.changed() => {
+                    if *shutdown_rx.borrow() { break; }
+                }
+            }
+        }
+    });
+}
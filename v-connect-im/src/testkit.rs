@@ -0,0 +1,195 @@
+//! 测试工具：内存中的 VConnectIMServer 与伪造连接
+//! Test kit: in-memory VConnectIMServer with fake connections
+
+use crate::cluster::directory::Directory;
+use crate::cluster::raft::RaftCluster;
+use crate::server::DEFAULT_SEND_QUEUE_CAPACITY;
+use crate::ws::protocol::WireFormat;
+use crate::ws::send_queue::{self, QueueReceiver};
+use crate::{Connection, ImMessage, VConnectIMServer};
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::Message;
+
+/// 等待消息的默认超时 / Default timeout when waiting for a message
+const RECV_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 测试服务器：封装目录、Raft 与服务实例
+/// Test server: wraps directory, raft and the server instance
+pub struct TestServer {
+    pub node_id: String,
+    pub directory: Arc<Directory>,
+    pub raft: Arc<RaftCluster>,
+    pub server: Arc<VConnectIMServer>,
+}
+
+impl TestServer {
+    /// 创建单节点测试服务器（节点 node-A 作为 leader）
+    /// Create a single-node test server (node-A as leader)
+    pub fn new() -> Self {
+        Self::build(|server| server)
+    }
+
+    /// 创建单节点测试服务器，并在注册前调整服务实例（如替换限制）
+    /// Create a single-node test server, adjusting the server before it is registered (e.g. limits)
+    pub fn build(configure: impl FnOnce(VConnectIMServer) -> VConnectIMServer) -> Self {
+        let directory = Arc::new(Directory::new());
+        let raft = Arc::new(RaftCluster::new(directory.clone(), "node-A".into()));
+        Self::with_cluster("node-A", directory, raft, configure)
+    }
+
+    /// 自成一个集群的节点（不共享目录，其他节点只能经 HTTP 访问）
+    /// A node in a cluster of its own (no shared directory; other nodes reach it only over HTTP)
+    #[cfg(feature = "cluster-it")]
+    pub fn isolated(node_id: &str) -> Self {
+        let directory = Arc::new(Directory::new());
+        let raft = Arc::new(RaftCluster::new(directory.clone(), node_id.into()));
+        Self::with_cluster(node_id, directory, raft, |server| server)
+    }
+
+    /// 在同一目录与 Raft 集群中加入新节点 / Join a new node sharing this directory and raft cluster
+    pub fn join(&self, node_id: &str) -> Self {
+        Self::with_cluster(
+            node_id,
+            self.directory.clone(),
+            self.raft.clone(),
+            |server| server,
+        )
+    }
+
+    fn with_cluster(
+        node_id: &str,
+        directory: Arc<Directory>,
+        raft: Arc<RaftCluster>,
+        configure: impl FnOnce(VConnectIMServer) -> VConnectIMServer,
+    ) -> Self {
+        let server = Arc::new(configure(
+            VConnectIMServer::new()
+                .with_node(node_id.to_string(), directory.clone())
+                .with_raft(raft.clone()),
+        ));
+        directory.register_server(node_id, server.clone());
+        Self {
+            node_id: node_id.to_string(),
+            directory,
+            raft,
+            server,
+        }
+    }
+
+    /// 添加已认证的伪造客户端（client_id 与 uid 相同）
+    /// Add an authenticated fake client (client_id equals uid)
+    pub fn add_client(&self, uid: &str) -> (String, QueueReceiver) {
+        self.connect(
+            uid.to_string(),
+            Some(uid),
+            None,
+            DEFAULT_SEND_QUEUE_CAPACITY,
+        )
+    }
+
+    /// 添加尚未认证的客户端 / Add a client that has not authenticated yet
+    pub fn add_unauthenticated(&self, client_id: &str) -> (String, QueueReceiver) {
+        self.connect(
+            client_id.to_string(),
+            None,
+            None,
+            DEFAULT_SEND_QUEUE_CAPACITY,
+        )
+    }
+
+    /// 添加发送队列容量为 `capacity` 的客户端 / Add a client whose send queue holds `capacity` messages
+    pub fn add_client_with_queue(&self, uid: &str, capacity: usize) -> (String, QueueReceiver) {
+        self.connect(uid.to_string(), Some(uid), None, capacity)
+    }
+
+    /// 添加协商了指定帧编码的客户端 / Add a client that negotiated the given frame encoding
+    pub fn add_client_with_format(&self, uid: &str, format: WireFormat) -> (String, QueueReceiver) {
+        let (client_id, rx) = self.add_client(uid);
+        if let Some(mut conn) = self.server.connections.get_mut(&client_id) {
+            conn.wire_format = format;
+        }
+        (client_id, rx)
+    }
+
+    /// 为同一 uid 添加一台设备（client_id 为 `uid/device_id`）
+    /// Add a device for a uid (client_id is `uid/device_id`)
+    pub fn add_device(&self, uid: &str, device_id: &str) -> (String, QueueReceiver) {
+        self.connect(
+            format!("{}/{}", uid, device_id),
+            Some(uid),
+            Some(device_id),
+            DEFAULT_SEND_QUEUE_CAPACITY,
+        )
+    }
+
+    fn connect(
+        &self,
+        client_id: String,
+        uid: Option<&str>,
+        device_id: Option<&str>,
+        capacity: usize,
+    ) -> (String, QueueReceiver) {
+        let (tx, rx) = send_queue::channel(capacity);
+        self.server.connections.insert(
+            client_id.clone(),
+            Connection {
+                client_id: client_id.clone(),
+                uid: uid.map(|u| u.to_string()),
+                device_id: device_id.map(|d| d.to_string()),
+                protocol_version: crate::ws::protocol::CURRENT_PROTOCOL_VERSION,
+                wire_format: WireFormat::Json,
+                metadata: Default::default(),
+                addr: "127.0.0.1:0".parse().unwrap(),
+                sender: tx,
+                connected_at: std::time::Instant::now(),
+                last_sent: Arc::new(std::sync::Mutex::new(std::time::Instant::now())),
+                last_heartbeat: Arc::new(std::sync::Mutex::new(Instant::now())),
+                closer: Default::default(),
+            },
+        );
+        if let Some(uid) = uid {
+            self.server
+                .uid_clients
+                .entry(uid.to_string())
+                .or_default()
+                .insert(client_id.clone());
+        }
+        self.directory
+            .register_client_location(&client_id, &self.node_id);
+        (client_id, rx)
+    }
+
+    /// 以指定客户端身份发送消息 / Send a message as the given client
+    pub async fn send(&self, client_id: &str, msg: ImMessage) -> anyhow::Result<()> {
+        let text = serde_json::to_string(&msg)?;
+        self.server
+            .handle_incoming_message(Message::Text(text), client_id, &self.server.connections)
+            .await
+    }
+}
+
+/// 构造 IM 消息 / Build an IM message
+pub fn im(msg_type: &str, data: serde_json::Value, target_uid: Option<&str>) -> ImMessage {
+    ImMessage {
+        msg_type: msg_type.to_string(),
+        data,
+        target_uid: target_uid.map(|s| s.to_string()),
+        priority: Default::default(),
+        reply_to: None,
+    }
+}
+
+/// 接收下一条文本帧并反序列化为指定类型（超时即 panic）
+/// Receive the next text frame and deserialize it (panics on timeout)
+pub async fn recv_typed<T: DeserializeOwned>(rx: &mut QueueReceiver) -> T {
+    let msg = tokio::time::timeout(RECV_TIMEOUT, rx.recv())
+        .await
+        .expect("timed out waiting for message")
+        .expect("channel closed");
+    match msg {
+        Message::Text(t) => serde_json::from_str(&t).expect("invalid json frame"),
+        other => panic!("expected text frame, got {:?}", other),
+    }
+}
@@ -3,13 +3,17 @@ use futures_util::{SinkExt, StreamExt};
 use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::net::TcpStream;
-use tokio::sync::mpsc;
-use tokio_tungstenite::{accept_async, tungstenite::Message};
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+use tokio_tungstenite::tungstenite::http::header::USER_AGENT;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+use tokio_tungstenite::{accept_hdr_async, tungstenite::Message};
 use uuid::Uuid;
 
 use crate::server::{Connection, VConnectIMServer};
 
 /// 处理新连接 / Handle new connection
+#[allow(clippy::result_large_err)] // 握手回调的错误类型由 tungstenite 决定 / handshake callback error type is tungstenite's
 pub async fn handle_connection(
     stream: TcpStream,
     peer_addr: SocketAddr,
@@ -18,15 +22,65 @@ pub async fn handle_connection(
 ) -> Result<()> {
     tracing::info!("📨 New connection from: {}", peer_addr);
 
-    let ws_stream = accept_async(stream).await?;
+    // 协商消息协议版本，不支持的客户端在握手阶段被拒绝 / Negotiate the protocol version; unsupported clients are rejected at handshake
+    let mut protocol_version = None;
+    let mut wire_format = crate::ws::protocol::WireFormat::default();
+    let mut user_agent = None;
+    let ws_stream = accept_hdr_async(stream, |req: &Request, resp: Response| {
+        let (resp, version, format) = crate::ws::protocol::select_subprotocol(req, resp)?;
+        protocol_version = Some(version);
+        wire_format = format;
+        user_agent = req
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
+        Ok(resp)
+    })
+    .await?;
+    // 准入判定：拒绝时发送带关闭码的关闭帧，不进入连接表 / Admission: a rejection gets a close frame with its code and never enters the connection table
+    if let Some(rejection) = server.admit_connection(peer_addr).await {
+        tracing::warn!(
+            "🚫 Rejected connection from {}: {:?} ({})",
+            peer_addr,
+            rejection.reason,
+            rejection.message
+        );
+        let mut ws_stream = ws_stream;
+        let frame = CloseFrame {
+            code: CloseCode::from(rejection.reason.close_code()),
+            reason: rejection.message.into(),
+        };
+        let _ = ws_stream.close(Some(frame)).await;
+        return Ok(());
+    }
+    let protocol_version =
+        protocol_version.unwrap_or(crate::ws::protocol::CURRENT_PROTOCOL_VERSION);
     let (mut ws_sender, mut ws_receiver) = ws_stream.split();
 
-    let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+    let (tx, mut rx) = crate::ws::send_queue::channel(crate::server::send_queue_capacity());
     let client_id = Uuid::new_v4().to_string();
 
     let client_id_clone = client_id.clone();
+    // 停止时交还接收端，以便保存未写出的帧供重连补发；携带关闭帧时先写出再断开
+    // Hand the receiver back on stop so unwritten frames can be kept for resume; a close frame
+    // passed along is written before disconnecting
+    let (stop_tx, mut stop_rx) = tokio::sync::oneshot::channel::<Option<Message>>();
     let send_task = tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
+        loop {
+            let msg = tokio::select! {
+                stop = &mut stop_rx => {
+                    if let Ok(Some(close)) = stop {
+                        let _ = ws_sender.send(close).await;
+                        let _ = ws_sender.close().await;
+                    }
+                    break;
+                }
+                msg = rx.recv() => match msg {
+                    Some(msg) => msg,
+                    None => break,
+                },
+            };
             let is_close = matches!(&msg, Message::Close(_));
             if let Err(e) = ws_sender.send(msg).await {
                 tracing::error!("Failed to send message to {}: {}", client_id_clone, e);
@@ -37,23 +91,40 @@ pub async fn handle_connection(
                 break;
             }
         }
+        rx
     });
 
+    let (closer, mut close_rx) = crate::server::ConnectionCloser::new();
     let connection = Connection {
         client_id: client_id.clone(),
         uid: None,
+        device_id: None,
+        protocol_version,
+        wire_format,
+        metadata: Default::default(),
         addr: peer_addr,
         sender: tx,
+        connected_at: std::time::Instant::now(),
+        last_sent: Arc::new(std::sync::Mutex::new(std::time::Instant::now())),
         last_heartbeat: Arc::new(std::sync::Mutex::new(std::time::Instant::now())),
+        closer,
     };
+    if let Some(ua) = user_agent {
+        connection.set_meta("user_agent", serde_json::Value::String(ua));
+    }
     connections.insert(client_id.clone(), connection);
     server
         .directory
         .register_client_location(&client_id, &server.node_id);
-    tracing::info!("✅ Client {} connected from {}", client_id, peer_addr);
+    tracing::info!(
+        "✅ Client {} connected from {} (protocol v{}, {:?})",
+        client_id,
+        peer_addr,
+        protocol_version,
+        wire_format
+    );
 
-    // crate::service::webhook::send_client_online_webhook(&server, &client_id, &None, &peer_addr)  // 已移除 / Removed
-    //     .await;
+    server.publish_client_status(true, &client_id, None, &peer_addr, None);
     let welcome_text = "Welcome to v-connect-im Server".to_string();
     let welcome_msg = crate::domain::message::ConnectResponse {
         status: "connected".to_string(),
@@ -76,20 +147,43 @@ pub async fn handle_connection(
         let watchdog_server = server.clone();
         tokio::spawn(async move {
             tokio::time::sleep(std::time::Duration::from_millis(auth_deadline_ms)).await;
-            if let Some(conn) = watchdog_connections.get(&watchdog_client) {
-                if conn.uid.is_none() {
-                    let _ = watchdog_server.send_close_message(&watchdog_client).await;
-                    watchdog_connections.remove(&watchdog_client);
-                    tracing::warn!(
-                        "disconnecting unauthenticated client_id={}",
-                        watchdog_client
-                    );
-                }
+            // 先释放读锁再移除，避免同一分片上的死锁 / Release the read guard before removing to avoid a shard deadlock
+            let unauthenticated = watchdog_connections
+                .get(&watchdog_client)
+                .is_some_and(|conn| conn.uid.is_none());
+            if unauthenticated {
+                let _ = watchdog_server.send_close_message(&watchdog_client).await;
+                watchdog_connections.remove(&watchdog_client);
+                watchdog_server.log_connection_cleanup(
+                    crate::service::delivery_log::DropReason::Timeout,
+                    &watchdog_client,
+                    None,
+                );
             }
         });
     }
 
-    while let Some(msg) = ws_receiver.next().await {
+    // 回收等场景下由 `ConnectionCloser` 直接结束 / Ended directly through `ConnectionCloser`, e.g. by the reaper
+    let mut close = None;
+    let mut closer_live = true;
+    loop {
+        let msg = tokio::select! {
+            msg = ws_receiver.next() => match msg {
+                Some(msg) => msg,
+                None => break,
+            },
+            frame = &mut close_rx, if closer_live => match frame {
+                Ok(frame) => {
+                    close = Some(frame);
+                    break;
+                }
+                // 句柄随连接表项一起丢弃时照常等待客户端断开 / When the handle goes away with the table entry, wait for the client as before
+                Err(_) => {
+                    closer_live = false;
+                    continue;
+                }
+            },
+        };
         match msg {
             Ok(message) => {
                 if let Err(e) = server
@@ -106,10 +200,39 @@ pub async fn handle_connection(
         }
     }
 
-    let connection_info = connections.remove(&client_id);
-    send_task.abort();
+    let metadata: Vec<(String, serde_json::Value)> = connections
+        .get(&client_id)
+        .map(|c| {
+            c.metadata
+                .iter()
+                .map(|e| (e.key().clone(), e.value().clone()))
+                .collect()
+        })
+        .unwrap_or_default();
+    let connection_info = server.remove_connection(&client_id);
+    let _ = stop_tx.send(close);
+    let abort = send_task.abort_handle();
+    let mut pending = Vec::new();
+    match tokio::time::timeout(std::time::Duration::from_secs(1), send_task).await {
+        Ok(Ok(mut rx)) => {
+            // 以 JSON 文本保存，重连后按新连接的编码重新编码 / Kept as JSON text and re-encoded for the resumed connection
+            while let Ok(msg) = rx.try_recv() {
+                if let Ok(msg @ Message::Text(_)) = wire_format.decode(msg) {
+                    pending.push(msg);
+                }
+            }
+        }
+        _ => abort.abort(),
+    }
+    // 开始重连窗口 / Start the resume window
+    server.resume_tokens.suspend(
+        &client_id,
+        metadata,
+        pending,
+        crate::service::resume::resume_ttl(),
+    );
     tracing::info!("👋 Client {} disconnected", client_id);
-    if let Some((_, connection)) = connection_info {
+    if let Some(connection) = connection_info {
         let connected_at = chrono::Utc::now().timestamp_millis()
             - connection
                 .last_heartbeat
@@ -117,14 +240,13 @@ pub async fn handle_connection(
                 .unwrap()
                 .elapsed()
                 .as_millis() as i64;
-        // crate::service::webhook::send_client_offline_webhook(  // 已移除 / Removed
-        //     &server,
-        //     &client_id,
-        //     &connection.uid,
-        //     &connection.addr,
-        //     connected_at,
-        // )
-        // .await;
+        server.publish_client_status(
+            false,
+            &client_id,
+            connection.uid.clone(),
+            &connection.addr,
+            Some(connected_at),
+        );
         if let Some(uid) = &connection.uid {
             if let Some(set) = server.uid_clients.get_mut(uid) {
                 set.remove(&client_id);
@@ -1,3 +1,5 @@
 pub mod connection;
+pub mod protocol;
+pub mod send_queue;
 pub mod sender;
 pub mod server;
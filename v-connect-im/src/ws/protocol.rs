@@ -0,0 +1,314 @@
+//! WebSocket 子协议协商 / WebSocket subprotocol negotiation
+//!
+//! 客户端在升级请求的 `Sec-WebSocket-Protocol` 中声明所支持的消息协议版本（如 `vim.v1`），
+//! 服务端按客户端顺序选择第一个受支持的版本并回显；未提供受支持版本的升级请求以 400 拒绝。
+//! Clients list the message-protocol versions they speak in `Sec-WebSocket-Protocol`
+//! (e.g. `vim.v1`) on the upgrade request; the server picks the first supported one in
+//! the client's order and echoes it back. Upgrades offering no supported version get 400.
+//!
+//! `vim.v1.msgpack` 与 `vim.v1` 版本相同，但消息以 MessagePack 编码的二进制帧收发；默认仍为 JSON 文本帧。
+//! `vim.v1.msgpack` is the same version as `vim.v1` but exchanges messages as MessagePack
+//! binary frames; JSON text frames remain the default.
+//!
+//! `vim.v1.proto` 同样为版本 1，消息以 protobuf 编码的 `v.im.ImMessage`（`v/proto/im/im.proto`）二进制帧
+//! 收发，`data` 仍为 JSON 字节。
+//! `vim.v1.proto` is version 1 as well, exchanging messages as protobuf-encoded `v.im.ImMessage`
+//! (`v/proto/im/im.proto`) binary frames whose `data` stays JSON bytes.
+
+use prost::Message as _;
+use serde_json::Value;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::header::SEC_WEBSOCKET_PROTOCOL;
+use tokio_tungstenite::tungstenite::http::{HeaderValue, StatusCode};
+use tokio_tungstenite::tungstenite::Message;
+
+/// 消息帧编码 / Message frame encoding
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WireFormat {
+    /// JSON 文本帧 / JSON text frames
+    #[default]
+    Json,
+    /// MessagePack 二进制帧 / MessagePack binary frames
+    MessagePack,
+    /// protobuf 二进制帧 / Protobuf binary frames
+    Protobuf,
+}
+
+impl WireFormat {
+    /// 把 JSON 文本帧编码为本格式；非 JSON 文本与其他帧原样返回
+    /// Encode a JSON text frame in this format; non-JSON text and other frames pass through
+    pub fn encode(self, message: Message) -> Message {
+        let Message::Text(text) = message else {
+            return message;
+        };
+        let encoded = match self {
+            WireFormat::Json => None,
+            WireFormat::MessagePack => serde_json::from_str::<Value>(&text)
+                .ok()
+                .and_then(|value| rmp_serde::to_vec(&value).ok()),
+            WireFormat::Protobuf => serde_json::from_str::<Value>(&text)
+                .ok()
+                .and_then(|value| to_proto(&value))
+                .map(|msg| msg.encode_to_vec()),
+        };
+        match encoded {
+            Some(bytes) => Message::Binary(bytes),
+            None => Message::Text(text),
+        }
+    }
+
+    /// 把本格式的数据帧还原为 JSON 文本帧；JSON 连接的帧原样返回
+    /// Turn a data frame in this format back into a JSON text frame; JSON connections pass through
+    pub fn decode(self, message: Message) -> anyhow::Result<Message> {
+        match (self, message) {
+            (WireFormat::MessagePack, Message::Binary(bytes)) => {
+                let value: Value = rmp_serde::from_slice(&bytes)?;
+                Ok(Message::Text(value.to_string()))
+            }
+            (WireFormat::Protobuf, Message::Binary(bytes)) => {
+                let msg = v::plugin::proto::im::ImMessage::decode(&bytes[..])?;
+                Ok(Message::Text(from_proto(msg)?.to_string()))
+            }
+            (_, message) => Ok(message),
+        }
+    }
+}
+
+/// JSON 消息转为 protobuf；没有 `type` 的 JSON 不是 ImMessage，返回 None
+/// JSON message to protobuf; JSON without a `type` is not an ImMessage and yields None
+fn to_proto(value: &Value) -> Option<v::plugin::proto::im::ImMessage> {
+    let field = |key: &str| value.get(key).and_then(Value::as_str).unwrap_or_default();
+    Some(v::plugin::proto::im::ImMessage {
+        r#type: value.get("type")?.as_str()?.to_string(),
+        data: serde_json::to_vec(value.get("data").unwrap_or(&Value::Null)).ok()?,
+        target_uid: field("target_uid").to_string(),
+        priority: field("priority").to_string(),
+        reply_to: field("reply_to").to_string(),
+    })
+}
+
+/// protobuf 消息转为 JSON，空字段按缺省处理 / Protobuf message to JSON, treating empty fields as absent
+fn from_proto(msg: v::plugin::proto::im::ImMessage) -> serde_json::Result<Value> {
+    let data = if msg.data.is_empty() {
+        Value::Object(Default::default())
+    } else {
+        serde_json::from_slice(&msg.data)?
+    };
+    let mut value = serde_json::json!({"type": msg.r#type, "data": data});
+    if !msg.target_uid.is_empty() {
+        value["target_uid"] = Value::String(msg.target_uid);
+    }
+    if !msg.priority.is_empty() {
+        value["priority"] = Value::String(msg.priority);
+    }
+    if !msg.reply_to.is_empty() {
+        value["reply_to"] = Value::String(msg.reply_to);
+    }
+    Ok(value)
+}
+
+/// 受支持的子协议、版本号与帧编码 / Supported subprotocols with their versions and frame encodings
+pub const SUPPORTED_SUBPROTOCOLS: &[(&str, u32, WireFormat)] = &[
+    ("vim.v1", 1, WireFormat::Json),
+    ("vim.v1.msgpack", 1, WireFormat::MessagePack),
+    ("vim.v1.proto", 1, WireFormat::Protobuf),
+];
+
+/// 当前协议版本（QUIC 等无升级握手的传输默认使用）
+/// Current protocol version (the default for transports without an upgrade handshake)
+pub const CURRENT_PROTOCOL_VERSION: u32 = 1;
+
+/// 在客户端提供的子协议中选择第一个受支持的 / Pick the first supported subprotocol the client offers
+pub fn negotiate<'a>(
+    offered: impl IntoIterator<Item = &'a str>,
+) -> Option<(&'static str, u32, WireFormat)> {
+    offered.into_iter().map(str::trim).find_map(|p| {
+        SUPPORTED_SUBPROTOCOLS
+            .iter()
+            .find(|(name, _, _)| *name == p)
+            .copied()
+    })
+}
+
+/// 升级握手：回显选中的子协议并返回其版本与帧编码，不支持时拒绝
+/// Upgrade handshake: echo the chosen subprotocol and return its version and frame encoding, or reject
+// 错误类型由 tungstenite 的握手回调签名决定 / The error type is dictated by tungstenite's callback signature
+#[allow(clippy::result_large_err)]
+pub fn select_subprotocol(
+    req: &Request,
+    mut resp: Response,
+) -> Result<(Response, u32, WireFormat), ErrorResponse> {
+    let offered = req
+        .headers()
+        .get_all(SEC_WEBSOCKET_PROTOCOL)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','));
+    match negotiate(offered) {
+        Some((name, version, format)) => {
+            resp.headers_mut()
+                .insert(SEC_WEBSOCKET_PROTOCOL, HeaderValue::from_static(name));
+            Ok((resp, version, format))
+        }
+        None => {
+            let supported: Vec<&str> = SUPPORTED_SUBPROTOCOLS.iter().map(|(n, _, _)| *n).collect();
+            let mut err = ErrorResponse::new(Some(format!(
+                "unsupported or missing Sec-WebSocket-Protocol; supported: {}",
+                supported.join(", ")
+            )));
+            *err.status_mut() = StatusCode::BAD_REQUEST;
+            Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testkit::{im, recv_typed, TestServer};
+    use crate::ImMessage;
+
+    fn upgrade(protocols: &[&str]) -> Request {
+        let mut req = Request::builder().uri("/ws");
+        for p in protocols {
+            req = req.header(SEC_WEBSOCKET_PROTOCOL, *p);
+        }
+        req.body(()).unwrap()
+    }
+
+    #[test]
+    fn test_selects_first_supported_and_echoes_it() {
+        let req = upgrade(&["vim.v9, vim.v1", "chat"]);
+        let (resp, version, format) = select_subprotocol(&req, Response::new(())).unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(format, WireFormat::Json);
+        assert_eq!(resp.headers()[SEC_WEBSOCKET_PROTOCOL], "vim.v1");
+
+        let req = upgrade(&["vim.v1.msgpack, vim.v1"]);
+        let (resp, _, format) = select_subprotocol(&req, Response::new(())).unwrap();
+        assert_eq!(format, WireFormat::MessagePack);
+        assert_eq!(resp.headers()[SEC_WEBSOCKET_PROTOCOL], "vim.v1.msgpack");
+    }
+
+    #[test]
+    fn test_rejects_missing_or_unsupported() {
+        for offered in [&[][..], &["vim.v0"][..]] {
+            let err = select_subprotocol(&upgrade(offered), Response::new(())).unwrap_err();
+            assert_eq!(err.status(), StatusCode::BAD_REQUEST);
+            assert!(err.body().as_deref().unwrap().contains("vim.v1"));
+        }
+    }
+
+    fn decode_binary(msg: Message) -> ImMessage {
+        match msg {
+            Message::Binary(bytes) => rmp_serde::from_slice(&bytes).expect("msgpack frame"),
+            other => panic!("expected binary frame, got {:?}", other),
+        }
+    }
+
+    async fn next(rx: &mut crate::ws::send_queue::QueueReceiver) -> Message {
+        tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv())
+            .await
+            .expect("timed out waiting for message")
+            .expect("channel closed")
+    }
+
+    #[tokio::test]
+    async fn test_private_message_round_trips_over_msgpack() {
+        let ts = TestServer::new();
+        let (alice, mut alice_rx) = ts.add_client_with_format("alice", WireFormat::MessagePack);
+        let (_, mut bob_rx) = ts.add_client_with_format("bob", WireFormat::MessagePack);
+        let (_, mut carol_rx) = ts.add_client("carol");
+
+        let msg = im(
+            "private_message",
+            serde_json::json!({"text": "hi", "n": 7}),
+            Some("bob"),
+        );
+        let frame = Message::Binary(rmp_serde::to_vec(&msg).unwrap());
+        ts.server
+            .handle_incoming_message(frame, &alice, &ts.server.connections)
+            .await
+            .unwrap();
+
+        let got = decode_binary(next(&mut bob_rx).await);
+        assert_eq!(got.msg_type, "private_message");
+        assert_eq!(got.data["from"], "alice");
+        assert_eq!(
+            got.data["content"],
+            serde_json::json!({"text": "hi", "n": 7})
+        );
+        let sent = decode_binary(next(&mut alice_rx).await);
+        assert_eq!(sent.msg_type, "message_sent");
+
+        // JSON 连接不受影响 / JSON connections are unaffected
+        ts.send(
+            &alice,
+            im(
+                "private_message",
+                serde_json::json!({"text": "yo"}),
+                Some("carol"),
+            ),
+        )
+        .await
+        .unwrap();
+        let got: ImMessage = recv_typed(&mut carol_rx).await;
+        assert_eq!(got.data["content"]["text"], "yo");
+        let _ = decode_binary(next(&mut alice_rx).await);
+
+        let garbage = Message::Binary(vec![0xc1]);
+        ts.server
+            .handle_incoming_message(garbage, &alice, &ts.server.connections)
+            .await
+            .unwrap();
+        let err = decode_binary(next(&mut alice_rx).await);
+        assert_eq!(err.data["code"], "INVALID_FRAME");
+    }
+
+    fn decode_proto(msg: Message) -> v::plugin::proto::im::ImMessage {
+        match msg {
+            Message::Binary(bytes) => {
+                v::plugin::proto::im::ImMessage::decode(&bytes[..]).expect("protobuf frame")
+            }
+            other => panic!("expected binary frame, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ping_pong_round_trips_over_protobuf() {
+        let req = upgrade(&["vim.v1.proto"]);
+        let (_, _, format) = select_subprotocol(&req, Response::new(())).unwrap();
+        assert_eq!(format, WireFormat::Protobuf);
+
+        let ts = TestServer::new();
+        let (alice, mut alice_rx) = ts.add_client_with_format("alice", WireFormat::Protobuf);
+        let ping = v::plugin::proto::im::ImMessage {
+            r#type: "ping".into(),
+            ..Default::default()
+        };
+        ts.server
+            .handle_incoming_message(
+                Message::Binary(ping.encode_to_vec()),
+                &alice,
+                &ts.server.connections,
+            )
+            .await
+            .unwrap();
+
+        let pong = decode_proto(next(&mut alice_rx).await);
+        assert_eq!(pong.r#type, "pong");
+        let data: serde_json::Value = serde_json::from_slice(&pong.data).unwrap();
+        assert_eq!(data["client_id"], alice.as_str());
+        assert!(pong.target_uid.is_empty() && pong.priority.is_empty() && pong.reply_to.is_empty());
+
+        let garbage = Message::Binary(vec![0xff]);
+        ts.server
+            .handle_incoming_message(garbage, &alice, &ts.server.connections)
+            .await
+            .unwrap();
+        let err = decode_proto(next(&mut alice_rx).await);
+        assert_eq!(err.r#type, "error");
+        let data: serde_json::Value = serde_json::from_slice(&err.data).unwrap();
+        assert_eq!(data["code"], "INVALID_FRAME");
+    }
+}
@@ -0,0 +1,321 @@
+//! 按优先级出队的有界发送队列 / Bounded send queue that dequeues by priority
+//!
+//! 每个连接一个队列，容量为 `server.send_queue_capacity`，三个优先级共享容量。出队时先取高优先级，
+//! 同一优先级内先进先出。队列满时的溢出策略：新消息挤掉队列中优先级严格更低的最新一条
+//! （先挤 `low` 再挤 `normal`）；没有更低优先级的消息时拒绝新消息，与原先的慢客户端处理一致。
+//! One queue per connection, holding `server.send_queue_capacity` messages shared by the three
+//! priorities. Higher priorities are dequeued first, FIFO within a priority. Overflow policy on a
+//! full queue: the new message evicts the most recently queued message of a strictly lower
+//! priority (`low` before `normal`); when there is none the new message is rejected, as slow
+//! consumers were handled before.
+//!
+//! 以 `try_send_pinned` 入队的消息（补发的离线消息，入队后即在存储中确认）不会被挤掉，只能被写出。
+//! Messages queued with `try_send_pinned` (replayed offline messages, acknowledged in storage once
+//! queued) are never evicted, only written out.
+//!
+//! 接口与 `tokio::sync::mpsc` 的有界通道一致，错误类型也沿用其 `TrySendError` / `TryRecvError`。
+//! The API mirrors tokio's bounded `mpsc` channel and reuses its `TrySendError` / `TryRecvError`.
+
+use crate::domain::message::MessagePriority;
+use parking_lot::Mutex;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::mpsc::error::{TryRecvError, TrySendError};
+use tokio::sync::Notify;
+use tokio_tungstenite::tungstenite::Message;
+
+/// 创建容量为 `capacity` 的发送队列 / Create a send queue holding `capacity` messages
+pub fn channel(capacity: usize) -> (QueueSender, QueueReceiver) {
+    let shared = Arc::new(Shared {
+        capacity: capacity.max(1),
+        state: Mutex::new(State {
+            lanes: Default::default(),
+            senders: 1,
+            closed: false,
+        }),
+        notify: Notify::new(),
+    });
+    (
+        QueueSender {
+            shared: shared.clone(),
+        },
+        QueueReceiver { shared },
+    )
+}
+
+struct Shared {
+    capacity: usize,
+    state: Mutex<State>,
+    notify: Notify,
+}
+
+/// 队列中的消息 / A queued message
+struct Queued {
+    message: Message,
+    /// 可被更高优先级的消息挤掉 / May be evicted by a higher-priority message
+    evictable: bool,
+}
+
+struct State {
+    /// 按 `MessagePriority::rank` 索引 / Indexed by `MessagePriority::rank`
+    lanes: [VecDeque<Queued>; 3],
+    senders: usize,
+    /// 接收端已丢弃 / The receiver was dropped
+    closed: bool,
+}
+
+impl State {
+    fn len(&self) -> usize {
+        self.lanes.iter().map(VecDeque::len).sum()
+    }
+
+    fn pop(&mut self) -> Option<Message> {
+        self.lanes
+            .iter_mut()
+            .rev()
+            .find_map(VecDeque::pop_front)
+            .map(|q| q.message)
+    }
+
+    /// 挤掉优先级低于 `rank` 的最新一条可挤消息 / Evict the newest evictable message ranked below `rank`
+    fn evict_below(&mut self, rank: usize) -> Option<Message> {
+        self.lanes[..rank].iter_mut().find_map(|lane| {
+            let i = lane.iter().rposition(|q| q.evictable)?;
+            lane.remove(i).map(|q| q.message)
+        })
+    }
+}
+
+/// 发送端 / Sending half
+pub struct QueueSender {
+    shared: Arc<Shared>,
+}
+
+impl Clone for QueueSender {
+    fn clone(&self) -> Self {
+        self.shared.state.lock().senders += 1;
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl Drop for QueueSender {
+    fn drop(&mut self) {
+        let last = {
+            let mut state = self.shared.state.lock();
+            state.senders -= 1;
+            state.senders == 0
+        };
+        if last {
+            self.shared.notify.notify_one();
+        }
+    }
+}
+
+impl QueueSender {
+    /// 以 `normal` 优先级入队 / Enqueue at `normal` priority
+    pub fn try_send(&self, message: Message) -> Result<(), TrySendError<Message>> {
+        self.try_send_with(message, MessagePriority::Normal)
+            .map(|_| ())
+    }
+
+    /// 按优先级入队；队列满时按溢出策略挤掉一条更低优先级的消息并返回它
+    /// Enqueue with a priority; on a full queue a lower-priority message is evicted per the
+    /// overflow policy and returned
+    pub fn try_send_with(
+        &self,
+        message: Message,
+        priority: MessagePriority,
+    ) -> Result<Option<Message>, TrySendError<Message>> {
+        self.push(message, priority, true)
+    }
+
+    /// 同 `try_send_with`，但入队后不会被挤掉 / Like `try_send_with`, but never evicted once queued
+    pub fn try_send_pinned(
+        &self,
+        message: Message,
+        priority: MessagePriority,
+    ) -> Result<Option<Message>, TrySendError<Message>> {
+        self.push(message, priority, false)
+    }
+
+    fn push(
+        &self,
+        message: Message,
+        priority: MessagePriority,
+        evictable: bool,
+    ) -> Result<Option<Message>, TrySendError<Message>> {
+        let evicted = {
+            let mut state = self.shared.state.lock();
+            if state.closed {
+                return Err(TrySendError::Closed(message));
+            }
+            let evicted = if state.len() < self.shared.capacity {
+                None
+            } else {
+                match state.evict_below(priority.rank()) {
+                    Some(evicted) => Some(evicted),
+                    None => return Err(TrySendError::Full(message)),
+                }
+            };
+            state.lanes[priority.rank()].push_back(Queued { message, evictable });
+            evicted
+        };
+        self.shared.notify.notify_one();
+        Ok(evicted)
+    }
+
+    /// 队列容量 / Queue capacity
+    pub fn max_capacity(&self) -> usize {
+        self.shared.capacity
+    }
+
+    /// 剩余空位 / Free slots left
+    pub fn capacity(&self) -> usize {
+        self.shared.capacity - self.shared.state.lock().len()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.shared.state.lock().closed
+    }
+}
+
+/// 接收端 / Receiving half
+pub struct QueueReceiver {
+    shared: Arc<Shared>,
+}
+
+impl QueueReceiver {
+    /// 取下一条消息；发送端全部丢弃且队列为空时返回 None
+    /// Take the next message; None once every sender is gone and the queue is empty
+    pub async fn recv(&mut self) -> Option<Message> {
+        loop {
+            match self.try_recv() {
+                Ok(message) => return Some(message),
+                Err(TryRecvError::Disconnected) => return None,
+                // 单一接收端，`notify_one` 在无人等待时保留许可，不会丢失唤醒
+                // Single receiver: `notify_one` keeps a permit when nobody waits, so no wakeup is lost
+                Err(TryRecvError::Empty) => self.shared.notify.notified().await,
+            }
+        }
+    }
+
+    pub fn try_recv(&mut self) -> Result<Message, TryRecvError> {
+        let mut state = self.shared.state.lock();
+        match state.pop() {
+            Some(message) => Ok(message),
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+impl Drop for QueueReceiver {
+    fn drop(&mut self) {
+        self.shared.state.lock().closed = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Message {
+        Message::Text(s.to_string())
+    }
+
+    fn drain(rx: &mut QueueReceiver) -> Vec<String> {
+        std::iter::from_fn(|| rx.try_recv().ok())
+            .map(|m| m.into_text().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_high_priority_preempts_on_full_queue() {
+        let (tx, mut rx) = channel(3);
+        tx.try_send_with(text("low-1"), MessagePriority::Low)
+            .unwrap();
+        tx.try_send(text("normal-1")).unwrap();
+        tx.try_send_with(text("low-2"), MessagePriority::Low)
+            .unwrap();
+        assert_eq!(tx.capacity(), 0);
+
+        // 挤掉最新的 low / Evicts the newest low
+        let evicted = tx.try_send_with(text("high-1"), MessagePriority::High);
+        assert_eq!(evicted.unwrap(), Some(text("low-2")));
+        let evicted = tx.try_send_with(text("high-2"), MessagePriority::High);
+        assert_eq!(evicted.unwrap(), Some(text("low-1")));
+        // 没有 low 时挤掉 normal / Without lows a normal is evicted
+        let evicted = tx.try_send_with(text("high-3"), MessagePriority::High);
+        assert_eq!(evicted.unwrap(), Some(text("normal-1")));
+
+        // 同级或更低优先级不能挤 / Equal or lower priorities cannot evict
+        assert!(matches!(
+            tx.try_send_with(text("high-4"), MessagePriority::High),
+            Err(TrySendError::Full(_))
+        ));
+        assert!(matches!(
+            tx.try_send(text("normal-2")),
+            Err(TrySendError::Full(_))
+        ));
+        assert_eq!(drain(&mut rx), ["high-1", "high-2", "high-3"]);
+    }
+
+    #[test]
+    fn test_pinned_messages_are_not_evicted() {
+        let (tx, mut rx) = channel(3);
+        tx.try_send_pinned(text("offline-1"), MessagePriority::Low)
+            .unwrap();
+        tx.try_send_with(text("low"), MessagePriority::Low).unwrap();
+        tx.try_send_pinned(text("offline-2"), MessagePriority::Normal)
+            .unwrap();
+
+        // 只有未固定的 low 可被挤掉 / Only the unpinned low can be evicted
+        let evicted = tx.try_send_with(text("high-1"), MessagePriority::High);
+        assert_eq!(evicted.unwrap(), Some(text("low")));
+        assert!(matches!(
+            tx.try_send_with(text("high-2"), MessagePriority::High),
+            Err(TrySendError::Full(_))
+        ));
+        assert_eq!(drain(&mut rx), ["high-1", "offline-2", "offline-1"]);
+    }
+
+    #[test]
+    fn test_dequeues_by_priority_then_fifo() {
+        let (tx, mut rx) = channel(8);
+        tx.try_send_with(text("low"), MessagePriority::Low).unwrap();
+        tx.try_send(text("normal-1")).unwrap();
+        tx.try_send_with(text("high"), MessagePriority::High)
+            .unwrap();
+        tx.try_send(text("normal-2")).unwrap();
+        assert_eq!(drain(&mut rx), ["high", "normal-1", "normal-2", "low"]);
+    }
+
+    #[tokio::test]
+    async fn test_recv_wakes_and_ends_with_senders() {
+        let (tx, mut rx) = channel(2);
+        let reader = tokio::spawn(async move {
+            let mut got = Vec::new();
+            while let Some(m) = rx.recv().await {
+                got.push(m.into_text().unwrap());
+            }
+            got
+        });
+        tokio::task::yield_now().await;
+        tx.try_send(text("a")).unwrap();
+        let tx2 = tx.clone();
+        drop(tx);
+        tx2.try_send(text("b")).unwrap();
+        drop(tx2);
+        assert_eq!(reader.await.unwrap(), ["a", "b"]);
+
+        let (tx, rx) = channel(1);
+        drop(rx);
+        assert!(tx.is_closed());
+        assert!(matches!(
+            tx.try_send(text("x")),
+            Err(TrySendError::Closed(_))
+        ));
+    }
+}
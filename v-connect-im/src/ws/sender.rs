@@ -1,42 +1,101 @@
 use anyhow::Result;
+use tokio::sync::mpsc::error::TrySendError;
 use tokio_tungstenite::tungstenite::Message;
 use tracing::{debug, error, warn};
 
-use crate::domain::message::ImMessage;
+use crate::domain::message::{ImMessage, MessagePriority};
 use crate::plugins::{PluginContext, PluginFlow};
 use crate::server::VConnectIMServer;
+use crate::service::delivery_log::{DeliveryDrop, DropReason};
 
 /// 向指定客户端发送消息 / Send message to specific client
 impl VConnectIMServer {
     pub async fn send_message_to_client(&self, client_id: &str, message: Message) -> Result<()> {
+        self.enqueue_for_client(client_id, message, false).await
+    }
+
+    /// 发送入队后不会被更高优先级挤掉的消息，用于入队即确认的离线补发
+    /// Send a message that higher priorities cannot evict once queued, for offline replay that is
+    /// acknowledged as soon as it is queued
+    pub async fn send_pinned_to_client(&self, client_id: &str, message: Message) -> Result<()> {
+        self.enqueue_for_client(client_id, message, true).await
+    }
+
+    async fn enqueue_for_client(
+        &self,
+        client_id: &str,
+        message: Message,
+        pinned: bool,
+    ) -> Result<()> {
         let mut message = message;
+        let mut priority = MessagePriority::Normal;
+        let mut message_id = None;
         if let Message::Text(ref mut text) = message {
             if let Ok(mut outgoing) = serde_json::from_str::<ImMessage>(text) {
-                let ctx = PluginContext::new(self, client_id);
-                match self
-                    .plugin_registry
-                    .emit_outgoing(&ctx, &mut outgoing)
-                    .await
-                {
-                    Ok(PluginFlow::Continue) => {
-                        *text = serde_json::to_string(&outgoing)?;
-                    }
-                    Ok(PluginFlow::Stop) => {
-                        debug!("message suppressed by plugin for client {}", client_id);
-                        return Ok(());
-                    }
-                    Err(e) => {
-                        error!("plugin outgoing error for client {}: {}", client_id, e);
-                        return Err(e);
+                message_id = outgoing
+                    .data
+                    .get("message_id")
+                    .and_then(|id| id.as_str())
+                    .map(str::to_string);
+                priority = outgoing.priority;
+                // 每个接收连接都会经过这里：没有插件时不运行钩子也不重新序列化
+                // Runs once per receiving connection: without plugins skip the hooks and re-serialization
+                if !self.plugin_registry.is_empty() {
+                    let ctx = PluginContext::new(self, client_id);
+                    match self
+                        .plugin_registry
+                        .emit_outgoing(&ctx, &mut outgoing)
+                        .await
+                    {
+                        Ok(PluginFlow::Continue) => {
+                            priority = outgoing.priority;
+                            *text = serde_json::to_string(&outgoing)?;
+                        }
+                        Ok(PluginFlow::Stop) => {
+                            debug!("message suppressed by plugin for client {}", client_id);
+                            return Ok(());
+                        }
+                        Err(e) => {
+                            error!("plugin outgoing error for client {}: {}", client_id, e);
+                            return Err(e);
+                        }
                     }
                 }
             }
         }
         if let Some(connection) = self.connections.get(client_id) {
-            connection
-                .sender
-                .send(message)
-                .map_err(|e| anyhow::anyhow!("Failed to send message: {}", e))?;
+            let message = connection.wire_format.encode(message);
+            // 队列满说明客户端消费过慢：更低优先级的消息让位，否则丢弃而不是无限堆积
+            // A full queue means a slow client: lower priorities make room, otherwise drop instead of piling up
+            let queued = if pinned {
+                connection.sender.try_send_pinned(message, priority)
+            } else {
+                connection.sender.try_send_with(message, priority)
+            };
+            let evicted = queued
+                .inspect_err(|_| {
+                    self.log_delivery_drop(
+                        DeliveryDrop::new(DropReason::SendError)
+                            .with_client(client_id)
+                            .with_uid(connection.uid.as_deref())
+                            .with_message(message_id.as_deref()),
+                    );
+                })
+                .map_err(|e| match e {
+                    TrySendError::Full(_) => {
+                        anyhow::anyhow!("Send queue full for client {} (slow consumer)", client_id)
+                    }
+                    TrySendError::Closed(_) => {
+                        anyhow::anyhow!("Failed to send message: channel closed")
+                    }
+                })?;
+            if evicted.is_some() {
+                debug!(
+                    "send queue full for client {}, dropped a lower-priority message for a {} one",
+                    client_id,
+                    priority.as_str()
+                );
+            }
             debug!("📤 Sent message to client {}", client_id);
             Ok(())
         } else {
@@ -48,7 +107,7 @@ impl VConnectIMServer {
     /// 发送关闭消息 / Send close message
     pub async fn send_close_message(&self, client_id: &str) -> Result<()> {
         if let Some(connection) = self.connections.get(client_id) {
-            connection.sender.send(Message::Close(Some(tokio_tungstenite::tungstenite::protocol::CloseFrame {
+            connection.sender.try_send(Message::Close(Some(tokio_tungstenite::tungstenite::protocol::CloseFrame {
                 code: tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode::Normal,
                 reason: std::borrow::Cow::Borrowed("Connection timeout"),
             }))).map_err(|e| anyhow::anyhow!("Failed to send close message: {}", e))?;
@@ -72,16 +131,18 @@ impl VConnectIMServer {
         for entry in self.connections.iter() {
             let client_id = entry.key().clone();
             let connection = entry.value();
-            if connection
+            // 队列已满的慢客户端只跳过本条 / Slow clients with a full queue just miss this one
+            if let Err(TrySendError::Closed(_)) = connection
                 .sender
-                .send(Message::Text(message_str.clone()))
-                .is_err()
+                .try_send(Message::Text(message_str.clone()))
             {
                 disconnected_clients.push(client_id);
             }
         }
         for client_id in disconnected_clients {
-            self.connections.remove(&client_id);
+            if let Some(conn) = self.remove_connection(&client_id) {
+                self.log_connection_cleanup(DropReason::SendError, &client_id, conn.uid.as_deref());
+            }
         }
         Ok(())
     }
@@ -0,0 +1,78 @@
+//! 调用方认证 / Caller authentication
+//!
+//! 转发前以 `Authorization: Bearer <token>` 调用 `auth_url`（`GET <auth_url>?token=<token>`，与认证插件
+//! 使用的 SSO 接口相同）校验令牌，成功响应须在 JSON 的 `uid` 或 `data.uid` 中给出用户 ID。未配置
+//! `auth_url`、缺少令牌、令牌无效或响应中没有 uid 时返回 401，请求不会转发，也不会附上网关令牌。
+//! Before forwarding, the `Authorization: Bearer <token>` token is checked against `auth_url`
+//! (`GET <auth_url>?token=<token>`, the same SSO endpoint the auth plugin uses); a successful
+//! response must name the user in `uid` or `data.uid` of its JSON body. Without `auth_url`, a
+//! token, a valid token or a uid in the response the caller gets 401, and nothing is forwarded
+//! or sent with the gateway token.
+
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::HttpRequest;
+use serde_json::Value;
+use std::time::Duration;
+use v::warn;
+
+/// 令牌校验服务 / Token validation service
+#[derive(Clone)]
+pub struct Authenticator {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl Authenticator {
+    pub fn new(url: &str, timeout: Duration) -> Self {
+        Self {
+            url: url.trim().to_string(),
+            client: reqwest::Client::builder()
+                .timeout(timeout)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// 是否配置了校验地址 / Whether a validation URL is configured
+    pub fn enabled(&self) -> bool {
+        !self.url.is_empty()
+    }
+
+    /// 校验请求携带的令牌并返回其用户 ID / Validate the request's token and return its user id
+    pub async fn identify(&self, req: &HttpRequest) -> Option<String> {
+        if !self.enabled() {
+            return None;
+        }
+        let token = bearer_token(req)?;
+        let resp = match self
+            .client
+            .get(&self.url)
+            .query(&[("token", token)])
+            .send()
+            .await
+        {
+            Ok(resp) if resp.status().is_success() => resp,
+            Ok(_) => return None,
+            Err(e) => {
+                warn!("⚠️  令牌校验失败 / Token validation failed: {}", e);
+                return None;
+            }
+        };
+        let body: Value = resp.json().await.ok()?;
+        body.get("uid")
+            .or_else(|| body.get("data").and_then(|d| d.get("uid")))
+            .and_then(Value::as_str)
+            .filter(|uid| !uid.is_empty())
+            .map(str::to_string)
+    }
+}
+
+/// `Authorization: Bearer <token>` 中的令牌 / Token from `Authorization: Bearer <token>`
+fn bearer_token(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+}
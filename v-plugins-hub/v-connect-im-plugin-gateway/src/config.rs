@@ -2,8 +2,14 @@
 
 use serde::{Deserialize, Serialize};
 
+/// 可暴露的路由分组 / Route groups that can be exposed
+pub const CAP_HEALTH: &str = "health";
+pub const CAP_MESSAGE: &str = "message";
+pub const CAP_ROOM: &str = "room";
+
 /// 网关配置 / Gateway Configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct GatewayConfig {
     /// 监听地址 / Listen host
     pub host: String,
@@ -19,16 +25,52 @@ pub struct GatewayConfig {
 
     /// 主服务地址 / Main service address
     pub main_service_url: String,
+
+    /// 暴露的路由分组（health / message / room）/ Exposed route groups (health / message / room)
+    pub expose: Vec<String>,
+
+    /// 转发到主服务的超时（毫秒）/ Upstream forward timeout (ms)
+    pub upstream_timeout_ms: u64,
+
+    /// 转发时携带的 `X-Gateway-Token`，须与主服务的 `server.gateway_token` 一致
+    /// `X-Gateway-Token` sent on forwards, must match the main service's `server.gateway_token`
+    /// 不出现在 `gateway.status` 中 / Left out of `gateway.status`
+    #[serde(skip_serializing)]
+    pub upstream_token: String,
+
+    /// 校验调用方令牌的地址（`GET <auth_url>?token=<token>`），为空时拒绝所有转发
+    /// Caller token validation URL (`GET <auth_url>?token=<token>`); empty refuses every forward
+    pub auth_url: String,
 }
 
 impl Default for GatewayConfig {
     fn default() -> Self {
         Self {
             host: "0.0.0.0".to_string(),
-            port: 8080,
+            port: 8081,
             workers: 4,
             enable_openapi: true,
-            main_service_url: "http://localhost:9000".to_string(),
+            main_service_url: "http://127.0.0.1:8080".to_string(),
+            expose: vec![
+                CAP_HEALTH.to_string(),
+                CAP_MESSAGE.to_string(),
+                CAP_ROOM.to_string(),
+            ],
+            upstream_timeout_ms: 5000,
+            upstream_token: String::new(),
+            auth_url: "http://127.0.0.1:8090/v1/sso/auth".to_string(),
         }
     }
 }
+
+impl GatewayConfig {
+    /// 是否暴露指定分组 / Whether a route group is exposed
+    pub fn exposes(&self, cap: &str) -> bool {
+        self.expose.iter().any(|c| c == cap)
+    }
+
+    /// 监听地址 / Bind address
+    pub fn bind_addr(&self) -> String {
+        format!("{}:{}", self.host, self.port)
+    }
+}
@@ -16,52 +16,67 @@
 // 模块声明 / Module Declarations
 // ============================================================================
 
+mod auth;
+mod config;
+mod proxy;
+mod router;
+
 // ============================================================================
 // 依赖导入 / Dependencies
 // ============================================================================
 
+use actix_web::dev::Server;
+use actix_web::{web, App, HttpServer};
 use anyhow::Result;
-use v::info;
-use v::plugin::pdk::{Context, Plugin};
+use std::time::Duration;
+use v::plugin::pdk::run_gateway_server;
+use v::{info, warn};
+
+use auth::Authenticator;
+use config::GatewayConfig;
+use proxy::Upstream;
 
 // ============================================================================
-// 插件主结构 / Plugin Main Structure
+// HTTP 服务 / HTTP Server
 // ============================================================================
 
-/// 网关插件主结构 / Gateway plugin main structure
-///
-/// 负责启动和管理 HTTP API 服务器
-/// Responsible for starting and managing HTTP API server
-struct GatewayPlugin {
-    // 待实现：配置和服务器
-    // TODO: Implement config and server
-}
-
-impl Plugin for GatewayPlugin {
-    type Config = ();
-
-    /// 创建新的网关插件实例 / Create new gateway plugin instance
-    fn new() -> Self {
-        info!("🌐 初始化网关插件 / Initializing Gateway Plugin");
-        info!("✅ 网关插件初始化完成 / Gateway Plugin initialized");
-
-        Self {}
+/// 构建并绑定网关 HTTP 服务 / Build and bind the gateway HTTP server
+fn build_server(config: GatewayConfig) -> std::io::Result<Server> {
+    let upstream = web::Data::new(Upstream::new(
+        &config.main_service_url,
+        &config.upstream_token,
+        Duration::from_millis(config.upstream_timeout_ms),
+    ));
+    let auth = web::Data::new(Authenticator::new(
+        &config.auth_url,
+        Duration::from_millis(config.upstream_timeout_ms),
+    ));
+    if !auth.enabled() {
+        warn!("⚠️  未配置 auth_url，消息与房间接口将拒绝所有请求 / auth_url is not set; the message and room endpoints will reject every request");
     }
+    let addr = config.bind_addr();
+    info!(
+        "🌐 网关监听 / Gateway listening on http://{} -> {} (expose: {:?})",
+        addr, config.main_service_url, config.expose
+    );
+    let workers = config.workers.max(1);
+    Ok(HttpServer::new(move || {
+        let config = config.clone();
+        App::new()
+            .app_data(upstream.clone())
+            .app_data(auth.clone())
+            .configure(move |cfg| router::configure(cfg, &config))
+    })
+    .workers(workers)
+    .bind(addr)?
+    .disable_signals()
+    .run())
+}
 
-    /// 接收并处理网关事件 / Receive and handle gateway events
-    fn receive(&mut self, ctx: &mut Context) -> Result<()> {
-        // 网关插件主要通过 HTTP 服务器处理请求
-        // Gateway plugin mainly handles requests through HTTP server
-        // 这里可以处理来自主服务的特殊事件
-        // Here we can handle special events from main service
-
-        v::debug!(
-            "网关插件收到事件 / Gateway plugin received event: {}",
-            ctx.event_type()
-        );
-
-        Ok(())
-    }
+/// 启动网关 HTTP 服务，直到服务停止 / Run the gateway HTTP server until it stops
+async fn serve(config: GatewayConfig) -> Result<()> {
+    build_server(config)?.await?;
+    Ok(())
 }
 
 // ============================================================================
@@ -72,6 +87,149 @@ impl Plugin for GatewayPlugin {
 #[tokio::main]
 async fn main() -> Result<()> {
     // 启动网关插件服务器 / Start gateway plugin server
-    // 插件元信息从 plugin.json 自动读取 / Plugin metadata is automatically read from plugin.json
-    v::plugin::pdk::run::<GatewayPlugin>().await
+    // 插件元信息与配置从 plugin.json 自动读取 / Metadata and config are read from plugin.json
+    run_gateway_server::<GatewayConfig, _, _>(serve).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_serves_health_on_configured_port() {
+        let (port, listener) = v::comm::port::reserve_free_port().unwrap();
+        drop(listener);
+        let config = GatewayConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            workers: 1,
+            main_service_url: "http://127.0.0.1:1".to_string(),
+            expose: vec![config::CAP_HEALTH.to_string()],
+            ..GatewayConfig::default()
+        };
+        let server = tokio::spawn(serve(config));
+
+        let client = reqwest::Client::new();
+        let url = format!("http://127.0.0.1:{}", port);
+        let mut health = None;
+        for _ in 0..50 {
+            if let Ok(resp) = client.get(format!("{}/health", url)).send().await {
+                health = Some(resp);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        let health = health.expect("gateway did not start");
+        assert!(health.status().is_success());
+
+        // 主服务不可达时未就绪 / Not ready while the main service is unreachable
+        let ready = client
+            .get(format!("{}/health/ready", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(ready.status().as_u16(), 503);
+
+        // 未暴露的分组不注册 / Unexposed groups are not registered
+        let send = client
+            .post(format!("{}/v1/message/send", url))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(send.status().as_u16(), 404);
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_forwards_carry_the_upstream_token_and_the_caller_identity() {
+        // 上游只接受带正确网关令牌的请求，并回显发送者；同时充当令牌校验服务
+        // The upstream only accepts the right gateway token and echoes the sender; it also validates tokens
+        let (upstream_port, upstream_listener) = v::comm::port::reserve_free_port().unwrap();
+        let upstream = HttpServer::new(|| {
+            App::new()
+                .route(
+                    "/v1/message/send",
+                    web::post().to(
+                        |req: actix_web::HttpRequest, body: web::Json<serde_json::Value>| async move {
+                            match req.headers().get(proxy::GATEWAY_TOKEN_HEADER) {
+                                Some(token) if token == "g4te" => {
+                                    actix_web::HttpResponse::Ok().json(body.into_inner())
+                                }
+                                _ => actix_web::HttpResponse::Unauthorized().finish(),
+                            }
+                        },
+                    ),
+                )
+                .route(
+                    "/v1/sso/auth",
+                    web::get().to(|req: actix_web::HttpRequest| async move {
+                        if req.query_string() == "token=t0k" {
+                            actix_web::HttpResponse::Ok().json(serde_json::json!({"uid": "alice"}))
+                        } else {
+                            actix_web::HttpResponse::Unauthorized().finish()
+                        }
+                    }),
+                )
+        })
+        .workers(1)
+        .listen(upstream_listener)
+        .unwrap()
+        .disable_signals()
+        .run();
+        let upstream_handle = upstream.handle();
+        tokio::spawn(upstream);
+
+        let (port, listener) = v::comm::port::reserve_free_port().unwrap();
+        drop(listener);
+        let upstream_url = format!("http://127.0.0.1:{}", upstream_port);
+        let config = GatewayConfig {
+            host: "127.0.0.1".to_string(),
+            port,
+            workers: 1,
+            main_service_url: upstream_url.clone(),
+            expose: vec![config::CAP_MESSAGE.to_string()],
+            upstream_token: "g4te".to_string(),
+            auth_url: format!("{}/v1/sso/auth", upstream_url),
+            ..GatewayConfig::default()
+        };
+        let server = tokio::spawn(serve(config));
+
+        let client = reqwest::Client::new();
+        let url = format!("http://127.0.0.1:{}/v1/message/send", port);
+        let body = serde_json::json!({"from_uid": "mallory", "to_uid": "bob", "content": {}});
+        let mut send = None;
+        for _ in 0..50 {
+            if let Ok(resp) = client
+                .post(&url)
+                .bearer_auth("t0k")
+                .json(&body)
+                .send()
+                .await
+            {
+                send = Some(resp);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        let send = send.expect("gateway did not start");
+        assert_eq!(send.status().as_u16(), 200);
+        // 发送者以认证身份覆盖 / The sender is replaced by the authenticated identity
+        let echoed: serde_json::Value = send.json().await.unwrap();
+        assert_eq!(echoed["from_uid"], "alice");
+
+        // 无令牌或令牌无效时不转发 / Nothing is forwarded without a valid token
+        let anonymous = client.post(&url).json(&body).send().await.unwrap();
+        assert_eq!(anonymous.status().as_u16(), 401);
+        let forged = client
+            .post(&url)
+            .bearer_auth("wrong")
+            .json(&body)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(forged.status().as_u16(), 401);
+
+        server.abort();
+        upstream_handle.stop(false).await;
+    }
 }
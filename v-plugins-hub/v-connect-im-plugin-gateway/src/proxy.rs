@@ -0,0 +1,131 @@
+//! 主服务转发 / Forwarding to the main service
+
+use crate::auth::Authenticator;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde_json::{json, Value};
+use std::time::Duration;
+use v::warn;
+
+/// 主服务校验网关令牌的请求头 / Header the main service checks for the gateway token
+pub const GATEWAY_TOKEN_HEADER: &str = "X-Gateway-Token";
+
+/// 上游主服务 / Upstream main service
+#[derive(Clone)]
+pub struct Upstream {
+    base_url: String,
+    token: String,
+    client: reqwest::Client,
+}
+
+impl Upstream {
+    pub fn new(base_url: &str, token: &str, timeout: Duration) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token: token.to_string(),
+            client: reqwest::Client::builder()
+                .timeout(timeout)
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// 拼接上游 URL / Build the upstream URL
+    pub fn url(&self, path: &str, query: &str) -> String {
+        if query.is_empty() {
+            format!("{}{}", self.base_url, path)
+        } else {
+            format!("{}{}?{}", self.base_url, path, query)
+        }
+    }
+
+    /// 上游是否可用（探测 `/v1/health/live`）/ Whether upstream is up (probes `/v1/health/live`)
+    pub async fn is_live(&self) -> bool {
+        self.client
+            .get(self.url("/v1/health/live", ""))
+            .send()
+            .await
+            .map(|r| r.status().is_success())
+            .unwrap_or(false)
+    }
+}
+
+/// 以认证身份覆盖的请求体字段 / Body field overwritten with the authenticated identity
+fn identity_field(path: &str) -> Option<&'static str> {
+    match path {
+        "/v1/message/send" | "/v1/room/send" => Some("from_uid"),
+        "/v1/room/join" | "/v1/room/leave" => Some("uid"),
+        _ => None,
+    }
+}
+
+/// 认证调用方后转发请求（方法、路径、查询串、Content-Type 与请求体）到主服务并附上网关令牌；
+/// 请求体中的发送者或成员 uid 以认证身份覆盖
+/// Authenticate the caller, then forward the request (method, path, query, Content-Type and body)
+/// to the main service with the gateway token; the sender or member uid in the body is replaced
+/// by the authenticated identity
+pub async fn forward(
+    req: HttpRequest,
+    body: web::Bytes,
+    upstream: web::Data<Upstream>,
+    auth: web::Data<Authenticator>,
+) -> HttpResponse {
+    let Some(uid) = auth.identify(&req).await else {
+        return HttpResponse::Unauthorized()
+            .json(json!({"message": "valid bearer token required"}));
+    };
+    let body = match identity_field(req.path()) {
+        Some(field) => match serde_json::from_slice::<Value>(&body) {
+            Ok(Value::Object(mut object)) => {
+                object.insert(field.to_string(), Value::String(uid));
+                Value::Object(object).to_string().into_bytes()
+            }
+            _ => {
+                return HttpResponse::BadRequest()
+                    .json(json!({"message": "JSON object body required"}))
+            }
+        },
+        None => body.to_vec(),
+    };
+    let url = upstream.url(req.path(), req.query_string());
+    let method = match reqwest::Method::from_bytes(req.method().as_str().as_bytes()) {
+        Ok(m) => m,
+        Err(_) => return HttpResponse::MethodNotAllowed().finish(),
+    };
+    let mut builder = upstream
+        .client
+        .request(method, &url)
+        .header(GATEWAY_TOKEN_HEADER, &upstream.token)
+        .body(body);
+    if let Some(ct) = req
+        .headers()
+        .get(actix_web::http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+    {
+        builder = builder.header(reqwest::header::CONTENT_TYPE, ct);
+    }
+    match builder.send().await {
+        Ok(resp) => {
+            let status =
+                StatusCode::from_u16(resp.status().as_u16()).unwrap_or(StatusCode::BAD_GATEWAY);
+            let content_type = resp
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("application/json")
+                .to_string();
+            match resp.bytes().await {
+                Ok(bytes) => HttpResponse::build(status)
+                    .content_type(content_type)
+                    .body(bytes),
+                Err(e) => bad_gateway(&url, e),
+            }
+        }
+        Err(e) => bad_gateway(&url, e),
+    }
+}
+
+fn bad_gateway(url: &str, e: reqwest::Error) -> HttpResponse {
+    warn!("⚠️  转发失败 / Forward failed: {} ({})", url, e);
+    HttpResponse::BadGateway().json(json!({"message": "upstream unavailable"}))
+}
@@ -0,0 +1,52 @@
+//! 网关路由 / Gateway routes
+//!
+//! 健康检查由网关本地响应，消息与房间接口转发到主服务
+//! Health checks are answered locally; message and room APIs are forwarded to the main service
+
+use crate::config::{GatewayConfig, CAP_HEALTH, CAP_MESSAGE, CAP_ROOM};
+use crate::proxy::{forward, Upstream};
+use actix_web::{web, HttpResponse, Responder};
+use serde_json::json;
+
+/// 消息接口（POST，转发）/ Message APIs (POST, forwarded)
+pub const MESSAGE_ROUTES: &[&str] = &["/v1/message/send"];
+/// 房间接口（POST，转发）/ Room APIs (POST, forwarded)
+pub const ROOM_POST_ROUTES: &[&str] = &["/v1/room/join", "/v1/room/leave", "/v1/room/send"];
+/// 房间接口（GET，转发）/ Room APIs (GET, forwarded)
+pub const ROOM_GET_ROUTES: &[&str] = &["/v1/room/members"];
+
+/// 按配置注册路由 / Register routes according to config
+pub fn configure(cfg: &mut web::ServiceConfig, config: &GatewayConfig) {
+    if config.exposes(CAP_HEALTH) {
+        cfg.route("/health", web::get().to(health))
+            .route("/health/live", web::get().to(health))
+            .route("/health/ready", web::get().to(ready));
+    }
+    if config.exposes(CAP_MESSAGE) {
+        for path in MESSAGE_ROUTES {
+            cfg.route(path, web::post().to(forward));
+        }
+    }
+    if config.exposes(CAP_ROOM) {
+        for path in ROOM_POST_ROUTES {
+            cfg.route(path, web::post().to(forward));
+        }
+        for path in ROOM_GET_ROUTES {
+            cfg.route(path, web::get().to(forward));
+        }
+    }
+}
+
+/// 网关自身存活 / Gateway liveness
+async fn health() -> impl Responder {
+    HttpResponse::Ok().json(json!({"status": "ok", "service": "gateway"}))
+}
+
+/// 就绪：主服务可达 / Readiness: main service reachable
+async fn ready(upstream: web::Data<Upstream>) -> impl Responder {
+    if upstream.is_live().await {
+        HttpResponse::Ok().json(json!({"status": "ready"}))
+    } else {
+        HttpResponse::ServiceUnavailable().json(json!({"status": "upstream unavailable"}))
+    }
+}
@@ -0,0 +1,284 @@
+//! # 键值存储后端 / Key-Value Storage Backends
+//!
+//! 存储监听器只通过 [`KvBackend`] / [`KvTree`] 访问数据，后端由配置 `backend` 选择：
+//! 默认 `sled`，或以 `rocksdb` 特性编译后可选 `rocksdb`。离线消息、房间成员等上层逻辑与后端无关。
+//! The storage listener only touches data through [`KvBackend`] / [`KvTree`]; the backend
+//! is picked by the `backend` config field: `sled` by default, or `rocksdb` when built
+//! with the `rocksdb` feature. Higher-level logic (offline messages, room members, ...)
+//! is backend-agnostic.
+
+#[cfg(feature = "rocksdb")]
+mod rocksdb_backend;
+mod sled_backend;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::sled_listener::SledStorageConfig;
+
+/// 键值对 / Key-value pair
+pub type KvPair = (Vec<u8>, Vec<u8>);
+
+/// 按键升序（或降序）的迭代器 / Iterator in ascending (or descending) key order
+pub type KvIter<'a> = Box<dyn Iterator<Item = Result<KvPair>> + 'a>;
+
+/// 存储后端类型 / Storage backend kind
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// sled 嵌入式数据库 / sled embedded database
+    #[default]
+    Sled,
+    /// RocksDB（需以 `rocksdb` 特性编译）/ RocksDB (needs the `rocksdb` feature)
+    Rocksdb,
+}
+
+impl Backend {
+    /// 本次构建是否包含该后端 / Whether this build includes the backend
+    pub fn is_compiled(self) -> bool {
+        match self {
+            Backend::Sled => true,
+            Backend::Rocksdb => cfg!(feature = "rocksdb"),
+        }
+    }
+
+    /// 打开 `config.db_path` 处的数据库 / Open the database at `config.db_path`
+    pub fn open(self, config: &SledStorageConfig) -> Result<Arc<dyn KvBackend>> {
+        match self {
+            Backend::Sled => Ok(Arc::new(sled_backend::SledBackend::open(config)?)),
+            #[cfg(feature = "rocksdb")]
+            Backend::Rocksdb => Ok(Arc::new(rocksdb_backend::RocksBackend::open(config)?)),
+            #[cfg(not(feature = "rocksdb"))]
+            Backend::Rocksdb => Err(anyhow::anyhow!(
+                "未以 rocksdb 特性编译 / Built without the rocksdb feature"
+            )),
+        }
+    }
+}
+
+/// 跨树的原子批量写入 / Atomic batch of inserts across trees
+#[derive(Debug, Default)]
+pub struct KvBatch {
+    /// (树名, 键值对) / (tree name, key-value pair)
+    pub inserts: Vec<(String, KvPair)>,
+}
+
+impl KvBatch {
+    pub fn insert(&mut self, tree: &str, key: impl Into<Vec<u8>>, value: impl Into<Vec<u8>>) {
+        self.inserts
+            .push((tree.to_string(), (key.into(), value.into())));
+    }
+}
+
+/// 键值数据库 / Key-value database
+pub trait KvBackend: Send + Sync {
+    /// 打开（不存在则创建）命名树 / Open a named tree, creating it if missing
+    fn open_tree(&self, name: &str) -> Result<Box<dyn KvTree>>;
+
+    /// 原子地应用批量写（可跨树）/ Apply a batch atomically (may span trees)
+    fn apply_batch(&self, batch: KvBatch) -> Result<()>;
+
+    /// 把已写入的数据持久化到磁盘 / Make everything written so far durable on disk
+    fn flush(&self) -> Result<()>;
+}
+
+/// 有序键值树 / Ordered key-value tree
+pub trait KvTree: Send + Sync {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>>;
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<()>;
+
+    /// 删除键，返回键此前是否存在 / Remove a key; returns whether it existed
+    fn remove(&self, key: &[u8]) -> Result<bool>;
+
+    /// 升序遍历 `[start, end)`，`end` 为 None 时直到末尾
+    /// Ascending over `[start, end)`, to the last key when `end` is None
+    fn range(&self, start: &[u8], end: Option<&[u8]>) -> KvIter<'_>;
+
+    /// 降序遍历 `[start, end)` / Descending over `[start, end)`
+    fn range_rev(&self, start: &[u8], end: Option<&[u8]>) -> KvIter<'_>;
+
+    fn contains_key(&self, key: &[u8]) -> Result<bool> {
+        Ok(self.get(key)?.is_some())
+    }
+
+    /// 升序遍历以 `prefix` 开头的键 / Ascending over keys starting with `prefix`
+    fn scan_prefix(&self, prefix: &[u8]) -> KvIter<'_> {
+        self.range(prefix, prefix_end(prefix).as_deref())
+    }
+
+    fn iter(&self) -> KvIter<'_> {
+        self.range(&[], None)
+    }
+
+    fn iter_rev(&self) -> KvIter<'_> {
+        self.range_rev(&[], None)
+    }
+
+    /// 删除全部键（测试用于模拟数据丢失）/ Remove every key (tests use it to simulate lost data)
+    #[cfg(test)]
+    fn clear(&self) -> Result<()> {
+        let keys = self
+            .iter()
+            .map(|r| r.map(|(k, _)| k))
+            .collect::<Result<Vec<_>>>()?;
+        for key in keys {
+            self.remove(&key)?;
+        }
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// 键数量（需要遍历）/ Number of keys (walks the tree)
+    fn len(&self) -> usize {
+        self.iter().count()
+    }
+}
+
+/// 大于所有以 `prefix` 开头的键的最小键；全为 0xFF 时没有上界
+/// The smallest key greater than every key starting with `prefix`; none when it is all 0xFF
+fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(last) = end.pop() {
+        if last < u8::MAX {
+            end.push(last + 1);
+            return Some(end);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 本次构建包含的所有后端，各自使用独立目录
+    /// Every backend in this build, each in its own directory
+    fn backends(name: &str) -> Vec<(Backend, SledStorageConfig)> {
+        [Backend::Sled, Backend::Rocksdb]
+            .into_iter()
+            .filter(|b| b.is_compiled())
+            .map(|backend| {
+                let path = std::env::temp_dir().join(format!(
+                    "vgo-kv-{}-{:?}-{}",
+                    name,
+                    backend,
+                    std::process::id()
+                ));
+                let _ = std::fs::remove_dir_all(&path);
+                let config = SledStorageConfig {
+                    db_path: path.to_string_lossy().to_string(),
+                    backend,
+                    ..SledStorageConfig::default()
+                };
+                (backend, config)
+            })
+            .collect()
+    }
+
+    /// 重新打开已释放的数据库。sled 的后台刷盘线程与 IO 线程池在最后一个句柄释放后仍可能短暂持有
+    /// 文件锁（上面已关闭刷盘线程），因此在限定时间内重试
+    /// Reopen a database whose handles were all dropped. sled's background flusher and I/O
+    /// threadpool may hold the file lock for a moment after the last handle goes away (the
+    /// flusher is turned off above), so retry for a bounded time
+    fn reopen(backend: Backend, config: &SledStorageConfig) -> Arc<dyn KvBackend> {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        loop {
+            match backend.open(config) {
+                Ok(db) => return db,
+                Err(_) if std::time::Instant::now() < deadline => {
+                    std::thread::sleep(std::time::Duration::from_millis(20));
+                }
+                Err(e) => panic!("{:?} did not reopen: {}", backend, e),
+            }
+        }
+    }
+
+    fn keys(iter: KvIter<'_>) -> Vec<String> {
+        iter.map(|r| String::from_utf8(r.unwrap().0).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_prefix_end() {
+        assert_eq!(prefix_end(b"ab"), Some(b"ac".to_vec()));
+        assert_eq!(prefix_end(b"a\xff"), Some(b"b".to_vec()));
+        assert_eq!(prefix_end(b"\xff\xff"), None);
+        assert_eq!(prefix_end(b""), None);
+    }
+
+    #[test]
+    fn test_tree_operations_on_every_backend() {
+        for (backend, config) in backends("tree") {
+            let db = backend.open(&config).unwrap();
+            let tree = db.open_tree("t").unwrap();
+            assert!(tree.is_empty(), "{:?}", backend);
+            for key in ["a:1", "a:2", "a:\u{ff}", "b:1", "c"] {
+                tree.insert(key.as_bytes(), key.as_bytes()).unwrap();
+            }
+
+            assert_eq!(tree.get(b"a:2").unwrap(), Some(b"a:2".to_vec()));
+            assert_eq!(tree.get(b"zz").unwrap(), None);
+            assert!(tree.contains_key(b"c").unwrap());
+            assert_eq!(keys(tree.scan_prefix(b"a:")), ["a:1", "a:2", "a:\u{ff}"]);
+            assert_eq!(
+                keys(tree.range(b"a:2", Some(b"c"))),
+                ["a:2", "a:\u{ff}", "b:1"]
+            );
+            assert_eq!(
+                keys(tree.range_rev(b"a:2", Some(b"c"))),
+                ["b:1", "a:\u{ff}", "a:2"]
+            );
+            assert_eq!(
+                keys(tree.iter_rev()),
+                ["c", "b:1", "a:\u{ff}", "a:2", "a:1"]
+            );
+            assert_eq!(tree.len(), 5);
+
+            assert!(tree.remove(b"b:1").unwrap(), "{:?}", backend);
+            assert!(!tree.remove(b"b:1").unwrap(), "{:?}", backend);
+            tree.clear().unwrap();
+            assert!(tree.is_empty(), "{:?}", backend);
+        }
+    }
+
+    #[test]
+    fn test_batches_span_trees_and_survive_reopen() {
+        for (backend, mut config) in backends("batch") {
+            config.flush_every_ms = 0;
+            let db = backend.open(&config).unwrap();
+            let (left, right) = (
+                db.open_tree("left").unwrap(),
+                db.open_tree("right").unwrap(),
+            );
+            let mut batch = KvBatch::default();
+            batch.insert("left", "k", "1");
+            batch.insert("right", "k", "2");
+            db.apply_batch(batch).unwrap();
+            db.flush().unwrap();
+            drop((left, right, db));
+
+            let db = reopen(backend, &config);
+            let (left, right) = (
+                db.open_tree("left").unwrap(),
+                db.open_tree("right").unwrap(),
+            );
+            assert_eq!(
+                left.get(b"k").unwrap(),
+                Some(b"1".to_vec()),
+                "{:?}",
+                backend
+            );
+            assert_eq!(
+                right.get(b"k").unwrap(),
+                Some(b"2".to_vec()),
+                "{:?}",
+                backend
+            );
+        }
+    }
+}
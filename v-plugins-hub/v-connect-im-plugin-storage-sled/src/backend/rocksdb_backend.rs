@@ -0,0 +1,161 @@
+//! # RocksDB 后端 / RocksDB Backend
+//!
+//! 每棵树对应一个列族，批量写使用跨列族的 `WriteBatch`；落盘即同步 WAL。
+//! `flush_every_ms > 0` 时由后台线程按间隔同步 WAL，0 时每次写入后同步。
+//! Each tree is a column family and batches are cross-column-family `WriteBatch`es;
+//! flushing means syncing the WAL. With `flush_every_ms > 0` a background thread syncs
+//! the WAL on that interval; with 0 every write is followed by a sync.
+
+use anyhow::{anyhow, Result};
+use rocksdb::{
+    BlockBasedOptions, BoundColumnFamily, Cache, ColumnFamilyDescriptor, DBWithThreadMode,
+    Direction, IteratorMode, MultiThreaded, Options, WriteBatch,
+};
+use std::sync::Arc;
+use std::time::Duration;
+use v::warn;
+
+use super::{KvBackend, KvBatch, KvIter, KvTree};
+use crate::sled_listener::SledStorageConfig;
+
+type Db = DBWithThreadMode<MultiThreaded>;
+
+pub struct RocksBackend {
+    db: Arc<Db>,
+    options: Options,
+}
+
+impl RocksBackend {
+    pub fn open(config: &SledStorageConfig) -> Result<Self> {
+        let mut table = BlockBasedOptions::default();
+        table.set_block_cache(&Cache::new_lru_cache(config.cache_capacity_bytes as usize));
+        let mut options = Options::default();
+        options.create_if_missing(true);
+        options.create_missing_column_families(true);
+        options.set_block_based_table_factory(&table);
+
+        // 新库尚无列族列表，只打开默认列族 / A new database has no column family list yet; only the default opens
+        let existing = Db::list_cf(&options, &config.db_path).unwrap_or_default();
+        let descriptors = existing
+            .into_iter()
+            .map(|name| ColumnFamilyDescriptor::new(name, options.clone()));
+        let db = Arc::new(Db::open_cf_descriptors(
+            &options,
+            &config.db_path,
+            descriptors,
+        )?);
+
+        if config.flush_every_ms > 0 {
+            let weak = Arc::downgrade(&db);
+            let every = Duration::from_millis(config.flush_every_ms);
+            std::thread::spawn(move || loop {
+                std::thread::sleep(every);
+                let Some(db) = weak.upgrade() else { break };
+                if let Err(e) = db.flush_wal(true) {
+                    warn!("⚠️  RocksDB WAL 同步失败 / RocksDB WAL sync failed: {}", e);
+                }
+            });
+        }
+
+        Ok(Self { db, options })
+    }
+}
+
+fn column_family<'a>(db: &'a Db, name: &str) -> Result<Arc<BoundColumnFamily<'a>>> {
+    db.cf_handle(name)
+        .ok_or_else(|| anyhow!("列族不存在 / Missing column family: {}", name))
+}
+
+impl KvBackend for RocksBackend {
+    fn open_tree(&self, name: &str) -> Result<Box<dyn KvTree>> {
+        if self.db.cf_handle(name).is_none() {
+            self.db.create_cf(name, &self.options)?;
+        }
+        Ok(Box::new(RocksTree {
+            db: self.db.clone(),
+            name: name.to_string(),
+        }))
+    }
+
+    fn apply_batch(&self, batch: KvBatch) -> Result<()> {
+        let mut write = WriteBatch::default();
+        for (name, (k, v)) in &batch.inserts {
+            write.put_cf(&column_family(&self.db, name)?, k, v);
+        }
+        self.db.write(write)?;
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.db.flush_wal(true)?;
+        Ok(())
+    }
+}
+
+struct RocksTree {
+    db: Arc<Db>,
+    name: String,
+}
+
+impl RocksTree {
+    fn iter_from(&self, mode: IteratorMode) -> KvIter<'_> {
+        match column_family(&self.db, &self.name) {
+            Ok(cf) => Box::new(self.db.iterator_cf(&cf, mode).map(|item| {
+                let (k, v) = item?;
+                Ok((k.into_vec(), v.into_vec()))
+            })),
+            Err(e) => Box::new(std::iter::once(Err(e))),
+        }
+    }
+}
+
+impl KvTree for RocksTree {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let cf = column_family(&self.db, &self.name)?;
+        Ok(self.db.get_cf(&cf, key)?)
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        let cf = column_family(&self.db, &self.name)?;
+        self.db.put_cf(&cf, key, value)?;
+        Ok(())
+    }
+
+    fn remove(&self, key: &[u8]) -> Result<bool> {
+        let cf = column_family(&self.db, &self.name)?;
+        let existed = self.db.get_cf(&cf, key)?.is_some();
+        self.db.delete_cf(&cf, key)?;
+        Ok(existed)
+    }
+
+    fn range(&self, start: &[u8], end: Option<&[u8]>) -> KvIter<'_> {
+        let end = end.map(<[u8]>::to_vec);
+        Box::new(
+            self.iter_from(IteratorMode::From(start, Direction::Forward))
+                .take_while(move |item| match (item, &end) {
+                    (Ok((k, _)), Some(end)) => k < end,
+                    _ => true,
+                }),
+        )
+    }
+
+    fn range_rev(&self, start: &[u8], end: Option<&[u8]>) -> KvIter<'_> {
+        let start = start.to_vec();
+        let iter: KvIter<'_> = match end {
+            // 反向定位到 <= end 的最后一个键，需跳过 end 本身
+            // Reverse seek lands on the last key <= end, so skip end itself
+            Some(end) => {
+                let end = end.to_vec();
+                Box::new(
+                    self.iter_from(IteratorMode::From(&end, Direction::Reverse))
+                        .skip_while(move |item| matches!(item, Ok((k, _)) if *k >= end)),
+                )
+            }
+            None => self.iter_from(IteratorMode::End),
+        };
+        Box::new(iter.take_while(move |item| match item {
+            Ok((k, _)) => *k >= start,
+            Err(_) => true,
+        }))
+    }
+}
@@ -0,0 +1,112 @@
+//! # sled 后端 / sled Backend
+
+use anyhow::{anyhow, Result};
+use sled::transaction::ConflictableTransactionError;
+use sled::Transactional;
+use std::ops::Bound;
+
+use super::{KvBackend, KvBatch, KvIter, KvTree};
+use crate::sled_listener::SledStorageConfig;
+
+pub struct SledBackend {
+    db: sled::Db,
+}
+
+impl SledBackend {
+    pub fn open(config: &SledStorageConfig) -> Result<Self> {
+        Ok(Self {
+            db: config.sled_config().open()?,
+        })
+    }
+}
+
+impl KvBackend for SledBackend {
+    fn open_tree(&self, name: &str) -> Result<Box<dyn KvTree>> {
+        Ok(Box::new(SledTree(self.db.open_tree(name)?)))
+    }
+
+    /// 按树分组后在一个多树事务中应用 / Grouped per tree and applied in one multi-tree transaction
+    fn apply_batch(&self, batch: KvBatch) -> Result<()> {
+        let mut trees: Vec<(String, sled::Batch)> = Vec::new();
+        for (name, (k, v)) in batch.inserts {
+            let idx = match trees.iter().position(|(n, _)| *n == name) {
+                Some(idx) => idx,
+                None => {
+                    trees.push((name, sled::Batch::default()));
+                    trees.len() - 1
+                }
+            };
+            trees[idx].1.insert(k, v);
+        }
+        let handles = trees
+            .iter()
+            .map(|(name, _)| self.db.open_tree(name))
+            .collect::<sled::Result<Vec<_>>>()?;
+        handles
+            .as_slice()
+            .transaction(|views| {
+                for (view, (_, batch)) in views.iter().zip(&trees) {
+                    view.apply_batch(batch)?;
+                }
+                Ok::<_, ConflictableTransactionError<sled::Error>>(())
+            })
+            .map_err(|e| anyhow!("sled 事务失败 / sled transaction failed: {}", e))
+    }
+
+    fn flush(&self) -> Result<()> {
+        self.db.flush()?;
+        Ok(())
+    }
+}
+
+struct SledTree(sled::Tree);
+
+impl SledTree {
+    fn bounds<'k>(start: &'k [u8], end: Option<&'k [u8]>) -> (Bound<&'k [u8]>, Bound<&'k [u8]>) {
+        (
+            Bound::Included(start),
+            end.map_or(Bound::Unbounded, Bound::Excluded),
+        )
+    }
+}
+
+fn pair(item: sled::Result<(sled::IVec, sled::IVec)>) -> Result<(Vec<u8>, Vec<u8>)> {
+    let (k, v) = item?;
+    Ok((k.to_vec(), v.to_vec()))
+}
+
+impl KvTree for SledTree {
+    fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self.0.get(key)?.map(|v| v.to_vec()))
+    }
+
+    fn insert(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.0.insert(key, value)?;
+        Ok(())
+    }
+
+    fn remove(&self, key: &[u8]) -> Result<bool> {
+        Ok(self.0.remove(key)?.is_some())
+    }
+
+    fn range(&self, start: &[u8], end: Option<&[u8]>) -> KvIter<'_> {
+        Box::new(self.0.range::<&[u8], _>(Self::bounds(start, end)).map(pair))
+    }
+
+    fn range_rev(&self, start: &[u8], end: Option<&[u8]>) -> KvIter<'_> {
+        Box::new(
+            self.0
+                .range::<&[u8], _>(Self::bounds(start, end))
+                .rev()
+                .map(pair),
+        )
+    }
+
+    fn contains_key(&self, key: &[u8]) -> Result<bool> {
+        Ok(self.0.contains_key(key)?)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
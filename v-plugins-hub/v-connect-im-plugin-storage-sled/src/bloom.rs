@@ -0,0 +1,86 @@
+//! # 布隆过滤器 / Bloom Filter
+//!
+//! 内存中的消息ID布隆过滤器，让不存在的ID无需读索引树即可判定缺失。
+//! 只增不删（WAL 不删除消息），启动时从索引树重建，不落盘。
+//! In-memory bloom filter over message IDs, so lookups of unknown IDs are answered
+//! without reading the index tree. Insert-only (the WAL never deletes messages),
+//! rebuilt from the index tree on open and never persisted.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// 每个元素占用的位数（约 1% 误判率）/ Bits per element (about a 1% false positive rate)
+const BITS_PER_ITEM: usize = 10;
+/// 哈希函数个数 / Number of hash functions
+const HASHES: u64 = 7;
+
+/// 布隆过滤器 / Bloom filter
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    capacity: usize,
+    len: usize,
+}
+
+impl BloomFilter {
+    /// 按预期元素数创建 / Create for the expected number of elements
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            bits: vec![0; (capacity * BITS_PER_ITEM).div_ceil(64)],
+            capacity,
+            len: 0,
+        }
+    }
+
+    pub fn insert(&mut self, key: &[u8]) {
+        for bit in self.bit_positions(key) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+        self.len += 1;
+    }
+
+    /// 返回 false 时一定不存在 / false means definitely absent
+    pub fn may_contain(&self, key: &[u8]) -> bool {
+        self.bit_positions(key)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// 超过预期元素数后误判率上升，应扩容重建 / Past capacity the false positive rate climbs; rebuild larger
+    pub fn is_full(&self) -> bool {
+        self.len >= self.capacity
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// 双重哈希得到各位置 / Bit positions via double hashing
+    fn bit_positions(&self, key: &[u8]) -> impl Iterator<Item = usize> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let h1 = hasher.finish();
+        h1.hash(&mut hasher);
+        let h2 = hasher.finish() | 1;
+        let m = (self.bits.len() * 64) as u64;
+        (0..HASHES).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % m) as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_false_negatives_and_low_false_positive_rate() {
+        let mut filter = BloomFilter::with_capacity(10_000);
+        for i in 0..10_000 {
+            filter.insert(format!("msg-{}", i).as_bytes());
+        }
+        assert!(filter.is_full());
+        assert!((0..10_000).all(|i| filter.may_contain(format!("msg-{}", i).as_bytes())));
+        let false_positives = (0..10_000)
+            .filter(|i| filter.may_contain(format!("other-{}", i).as_bytes()))
+            .count();
+        assert!(false_positives < 300, "{} false positives", false_positives);
+    }
+}
@@ -10,7 +10,13 @@
 //! - ✅ 房间成员管理 / Room member management
 //! - ✅ 已读回执存储 / Read receipt storage
 //! - ✅ 高性能嵌入式数据库 / High-performance embedded database
+//! - ✅ 消息归档到 S3 兼容存储 / Message archival to S3-compatible storage
+//! - ✅ 按消息ID索引查询 / Message lookup by ID through an index
+//! - ✅ 可选 RocksDB 后端（`rocksdb` 特性）/ Optional RocksDB backend (`rocksdb` feature)
 
+mod archive;
+mod backend;
+mod bloom;
 mod sled_listener;
 
 use anyhow::Result;
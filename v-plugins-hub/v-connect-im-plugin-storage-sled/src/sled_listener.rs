@@ -3,14 +3,20 @@
 //! 基于 Sled 嵌入式数据库的存储事件监听器实现
 //! Storage event listener implementation based on Sled embedded database
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use std::sync::Arc;
+use v::comm::clock::MonotonicClock;
 use v::plugin::pdk::StorageEventListener;
 use v::plugin::protocol::*;
 use v::{debug, info, warn};
 
+use crate::archive::{ArchiveConfig, ObjectStore, S3Store};
+use crate::backend::{Backend, KvBackend, KvBatch, KvTree};
+use crate::bloom::BloomFilter;
+
 // ============================================================================
 // 常量定义 / Constants
 // ============================================================================
@@ -18,6 +24,16 @@ use v::{debug, info, warn};
 /// 成功响应状态 / Success response status
 const STATUS_OK: &str = "ok";
 
+/// 搜索未给 limit 时的每页条数 / Search page size when no limit is given
+const DEFAULT_SEARCH_LIMIT: usize = 20;
+
+/// 消息ID布隆过滤器的最小容量 / Minimum capacity of the message ID bloom filter
+const MIN_FILTER_CAPACITY: usize = 1 << 16;
+
+/// 树名 / Tree names
+const WAL_TREE: &str = "wal";
+const MESSAGE_INDEX_TREE: &str = "message_index";
+
 // ============================================================================
 // 配置结构 / Configuration Structure
 // ============================================================================
@@ -25,6 +41,10 @@ const STATUS_OK: &str = "ok";
 /// Sled 存储配置 / Sled storage configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SledStorageConfig {
+    /// 存储后端 / Storage backend
+    #[serde(default)]
+    pub backend: Backend,
+
     /// 数据库路径 / Database path
     #[serde(default = "default_db_path")]
     pub db_path: String,
@@ -33,9 +53,22 @@ pub struct SledStorageConfig {
     #[serde(default = "default_max_offline")]
     pub max_offline_messages: usize,
 
-    /// 是否启用压缩 / Enable compression
+    /// 页缓存容量（字节）/ Page cache capacity in bytes
+    #[serde(default = "default_cache_capacity")]
+    pub cache_capacity_bytes: u64,
+
+    /// 后台批量落盘间隔（毫秒），0 表示每次写入后同步落盘
+    /// Background batched flush interval in ms; 0 flushes synchronously after every write
+    #[serde(default = "default_flush_every_ms")]
+    pub flush_every_ms: u64,
+
+    /// 是否启用 zstd 压缩（暂不支持，见 `validate`）/ Enable zstd compression (not supported yet, see `validate`)
+    #[serde(default, alias = "enable_compression")]
+    pub compression: bool,
+
+    /// 消息归档目标（未配置时不支持归档）/ Message archive target (archival unsupported when absent)
     #[serde(default)]
-    pub enable_compression: bool,
+    pub archive: Option<ArchiveConfig>,
 }
 
 fn default_db_path() -> String {
@@ -46,12 +79,24 @@ fn default_max_offline() -> usize {
     10000
 }
 
+fn default_cache_capacity() -> u64 {
+    1024 * 1024 * 1024
+}
+
+fn default_flush_every_ms() -> u64 {
+    500
+}
+
 impl Default for SledStorageConfig {
     fn default() -> Self {
         Self {
+            backend: Backend::default(),
             db_path: default_db_path(),
             max_offline_messages: default_max_offline(),
-            enable_compression: false,
+            cache_capacity_bytes: default_cache_capacity(),
+            flush_every_ms: default_flush_every_ms(),
+            compression: false,
+            archive: None,
         }
     }
 }
@@ -63,6 +108,14 @@ impl SledStorageConfig {
             anyhow::bail!("db_path 不能为空 / db_path cannot be empty");
         }
 
+        if !self.backend.is_compiled() {
+            anyhow::bail!(
+                "后端 {:?} 未编译进本插件（需启用同名特性）/ Backend {:?} is not compiled in (enable the feature of the same name)",
+                self.backend,
+                self.backend
+            );
+        }
+
         if self.max_offline_messages == 0 {
             anyhow::bail!(
                 "max_offline_messages 必须大于 0 / max_offline_messages must be greater than 0"
@@ -73,8 +126,35 @@ impl SledStorageConfig {
             warn!("⚠️  max_offline_messages 过大可能影响性能 / Large max_offline_messages may affect performance: {}", self.max_offline_messages);
         }
 
+        // sled 的 compression 特性依赖的 zstd-sys 与 actix-web 的版本在同一工作空间中冲突
+        // sled's compression feature needs a zstd-sys that conflicts with actix-web's in this workspace
+        if self.compression {
+            anyhow::bail!(
+                "compression 暂不支持（sled 未启用 compression 特性）/ compression is not supported yet (sled is built without its compression feature)"
+            );
+        }
+
+        if self.cache_capacity_bytes == 0 {
+            anyhow::bail!(
+                "cache_capacity_bytes 必须大于 0 / cache_capacity_bytes must be greater than 0"
+            );
+        }
+
+        if let Some(archive) = &self.archive {
+            archive.validate()?;
+        }
+
         Ok(())
     }
+
+    /// 对应的 sled 配置 / The matching sled configuration
+    pub fn sled_config(&self) -> sled::Config {
+        sled::Config::new()
+            .path(&self.db_path)
+            .cache_capacity(self.cache_capacity_bytes)
+            .flush_every_ms((self.flush_every_ms > 0).then_some(self.flush_every_ms))
+            .use_compression(self.compression)
+    }
 }
 
 // ============================================================================
@@ -92,6 +172,8 @@ pub struct StorageStats {
     pub offline_pulled: u64,
     /// 已确认离线消息数 / Offline messages acknowledged
     pub offline_acked: u64,
+    /// 已删除离线消息数 / Offline messages deleted
+    pub offline_deleted: u64,
 }
 
 // ============================================================================
@@ -100,12 +182,36 @@ pub struct StorageStats {
 
 /// Sled 存储事件监听器 / Sled storage event listener
 pub struct SledStorageEventListener {
+    /// 存储后端（批量写与落盘用）/ Storage backend (for batches and flushing)
+    backend: Arc<dyn KvBackend>,
     /// WAL 树（消息日志）/ WAL tree (message log)
-    wal: sled::Tree,
+    wal: Box<dyn KvTree>,
+    /// 消息ID索引树（message_id -> WAL 键）/ Message ID index tree (message_id -> WAL key)
+    message_index: Box<dyn KvTree>,
+    /// 已索引消息ID的布隆过滤器 / Bloom filter over indexed message IDs
+    message_filter: BloomFilter,
     /// 离线消息树 / Offline messages tree
-    offline: sled::Tree,
+    offline: Box<dyn KvTree>,
     /// 房间成员树 / Room members tree
-    rooms: sled::Tree,
+    rooms: Box<dyn KvTree>,
+    /// 附件索引树（message_id -> 元数据）/ Attachment index tree (message_id -> metadata)
+    attachments: Box<dyn KvTree>,
+    /// 已归档范围树（archive_key -> 范围与条数）/ Archived ranges tree (archive_key -> range and count)
+    archives: Box<dyn KvTree>,
+    /// 表情回应树（message_id:uid:emoji -> [uid, emoji]）/ Reactions tree (message_id:uid:emoji -> [uid, emoji])
+    reactions: Box<dyn KvTree>,
+    /// 置顶树（room_id:message_id -> [message_id, pinned_by, pinned_at]）/ Pins tree (room_id:message_id -> [message_id, pinned_by, pinned_at])
+    pins: Box<dyn KvTree>,
+    /// 回复索引树（parent_id:WAL 键 -> WAL 键）/ Reply index tree (parent_id:WAL key -> WAL key)
+    threads: Box<dyn KvTree>,
+    /// 群消息应确认成员树（message_id -> [room_id, sender_uid, recipients]）/ Group ack recipients tree (message_id -> [room_id, sender_uid, recipients])
+    group_ack_messages: Box<dyn KvTree>,
+    /// 群成员确认树（message_id:uid -> acked_at）/ Group member acks tree (message_id:uid -> acked_at)
+    group_acks: Box<dyn KvTree>,
+    /// 归档对象存储 / Archive object store
+    archive_store: Option<Arc<dyn ObjectStore>>,
+    /// WAL 与离线键的时间戳，时钟回拨时也不回退 / Timestamps for WAL and offline keys; never go back, even when the clock does
+    key_clock: MonotonicClock,
     /// 配置 / Configuration
     pub config: SledStorageConfig,
     /// 统计信息 / Statistics
@@ -115,59 +221,384 @@ pub struct SledStorageEventListener {
 impl SledStorageEventListener {
     /// 创建新实例 / Create new instance
     pub fn new(config: SledStorageConfig) -> Result<Self> {
-        info!("🚀 初始化 Sled 存储 / Initializing Sled storage");
+        info!(
+            "🚀 初始化存储（{:?} 后端）/ Initializing storage ({:?} backend)",
+            config.backend, config.backend
+        );
 
         // 打开数据库 / Open database
-        let db = sled::open(&config.db_path)?;
+        let db = config.backend.open(&config)?;
 
         // 打开树 / Open trees
-        let wal = db.open_tree("wal")?;
+        let wal = db.open_tree(WAL_TREE)?;
+        let message_index = db.open_tree(MESSAGE_INDEX_TREE)?;
+        // 旧数据没有索引时从 WAL 重建 / Rebuild the index from the WAL for data that predates it
+        if message_index.is_empty() && !wal.is_empty() {
+            let count = rebuild_message_index(db.as_ref(), wal.as_ref())?;
+            info!(
+                "🔁 已从 WAL 重建消息ID索引 / Rebuilt message ID index from the WAL: {} messages",
+                count
+            );
+        }
+        let message_filter = load_message_filter(message_index.as_ref(), MIN_FILTER_CAPACITY)?;
+        // 从最新的 WAL 键继续，重启前后键同样单调 / Continue after the newest WAL key so keys stay monotonic across restarts
+        let key_clock = MonotonicClock::new();
+        if let Some(item) = wal.iter_rev().next() {
+            if let Some(ts) = key_timestamp(&item?.0) {
+                key_clock.resume_after(ts);
+            }
+        }
         let offline = db.open_tree("offline")?;
         let rooms = db.open_tree("rooms")?;
+        let attachments = db.open_tree("attachments")?;
+        let archives = db.open_tree("archives")?;
+        let reactions = db.open_tree("reactions")?;
+        let pins = db.open_tree("pins")?;
+        let threads = db.open_tree("threads")?;
+        let group_ack_messages = db.open_tree("group_ack_messages")?;
+        let group_acks = db.open_tree("group_acks")?;
+        let archive_store = config
+            .archive
+            .clone()
+            .map(|c| Arc::new(S3Store::new(c)) as Arc<dyn ObjectStore>);
 
         info!(
-            "✅ Sled 存储初始化完成 / Sled storage initialized: {}",
-            config.db_path
+            "✅ 存储初始化完成 / Storage initialized: {} (cache {} bytes, flush every {} ms, compression {})",
+            config.db_path, config.cache_capacity_bytes, config.flush_every_ms, config.compression
         );
 
         Ok(Self {
+            backend: db,
             wal,
+            message_index,
+            message_filter,
             offline,
             rooms,
+            attachments,
+            archives,
+            reactions,
+            pins,
+            threads,
+            group_ack_messages,
+            group_acks,
+            archive_store,
+            key_clock,
             config,
             stats: StorageStats::default(),
         })
     }
 
+    /// 未启用后台批量落盘时同步落盘 / Flush synchronously unless background batched flushing is on
+    fn flush_if_sync(&self) -> Result<()> {
+        if self.config.flush_every_ms == 0 {
+            self.backend.flush()?;
+        }
+        Ok(())
+    }
+
+    /// 写入 WAL 并在同一事务中更新消息ID索引 / Write to the WAL and update the message ID index in one transaction
+    fn append_message(&mut self, key: &str, message_id: &str, value: &[u8]) -> Result<()> {
+        let mut batch = KvBatch::default();
+        batch.insert(WAL_TREE, key, value);
+        batch.insert(MESSAGE_INDEX_TREE, message_id, key);
+        self.backend.apply_batch(batch)?;
+        if self.message_filter.is_full() {
+            let capacity = self.message_filter.capacity() * 2;
+            self.message_filter = load_message_filter(self.message_index.as_ref(), capacity)?;
+        }
+        self.message_filter.insert(message_id.as_bytes());
+        Ok(())
+    }
+
+    /// 索引附件元数据（不含缩略图）/ Index attachment metadata (without thumbnail)
+    fn index_attachment(&self, message_id: &str, content: &str) -> Result<()> {
+        if let Some(mut attachment) = serde_json::from_str::<serde_json::Value>(content)
+            .ok()
+            .and_then(|c| c.get("attachment").cloned())
+            .filter(|a| a.is_object())
+        {
+            if let Some(obj) = attachment.as_object_mut() {
+                obj.remove("thumbnail_base64");
+            }
+            self.attachments
+                .insert(message_id.as_bytes(), &serde_json::to_vec(&attachment)?)?;
+        }
+        Ok(())
+    }
+
+    /// 回复消息按父消息ID索引 / Index a reply under its parent message ID
+    fn index_thread(&self, key: &str, content: &str) -> Result<()> {
+        if let Some(parent_id) = content_reply_to(content) {
+            self.threads
+                .insert(format!("{}:{}", parent_id, key).as_bytes(), key.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// 已配置的归档存储 / The configured archive store
+    fn archive_store(&self) -> Result<Arc<dyn ObjectStore>> {
+        self.archive_store.clone().ok_or_else(|| {
+            anyhow!("未配置消息归档（config.archive）/ Message archival is not configured (config.archive)")
+        })
+    }
+
+    /// 一条消息的表情回应按表情汇总，uid 与表情都排序
+    /// A message's reactions aggregated by emoji, with emojis and uids sorted
+    ///
+    /// uid 与表情都可能含 `:`，因此从值而不是键中解析 / Both uid and emoji may contain `:`, so they are read from the value rather than the key
+    fn list_reactions(&self, message_id: &str) -> Result<Vec<ReactionSummary>> {
+        let prefix = format!("{}:", message_id);
+        let mut by_emoji: std::collections::BTreeMap<String, Vec<String>> = Default::default();
+        for item in self.reactions.scan_prefix(prefix.as_bytes()) {
+            let (_k, v) = item?;
+            let (uid, emoji): (String, String) = serde_json::from_slice(&v)?;
+            by_emoji.entry(emoji).or_default().push(uid);
+        }
+        Ok(by_emoji
+            .into_iter()
+            .map(|(emoji, mut uids)| {
+                uids.sort();
+                ReactionSummary {
+                    emoji,
+                    count: uids.len() as i32,
+                    uids,
+                }
+            })
+            .collect())
+    }
+
+    /// 房间置顶消息，按置顶时间排序 / A room's pinned messages, ordered by pin time
+    /// 读取已登记的群消息 (room_id, sender_uid, recipients) / Read a registered group message as (room_id, sender_uid, recipients)
+    fn group_ack_message(&self, message_id: &str) -> Result<Option<(String, String, Vec<String>)>> {
+        match self.group_ack_messages.get(message_id.as_bytes())? {
+            Some(v) => Ok(Some(serde_json::from_slice(&v)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn list_pins(&self, room_id: &str) -> Result<Vec<PinnedMessage>> {
+        let prefix = format!("{}:", room_id);
+        let mut pins = Vec::new();
+        for item in self.pins.scan_prefix(prefix.as_bytes()) {
+            let (_k, v) = item?;
+            let (message_id, pinned_by, pinned_at): (String, String, i64) =
+                serde_json::from_slice(&v)?;
+            pins.push(PinnedMessage {
+                message_id,
+                pinned_by,
+                pinned_at,
+            });
+        }
+        pins.sort_by(|a, b| (a.pinned_at, &a.message_id).cmp(&(b.pinned_at, &b.message_id)));
+        Ok(pins)
+    }
+
     /// 统计离线消息数量 / Count offline messages
     fn count_offline_messages(&self, uid: &str) -> Result<usize> {
         let prefix = format!("{}:", uid);
         Ok(self.offline.scan_prefix(prefix.as_bytes()).count())
     }
 
-    /// 移除最旧的离线消息 / Remove oldest offline messages
+    /// 移除最旧的离线消息，先移除低优先级 / Remove the oldest offline messages, lowest priority first
     fn remove_oldest_offline(&self, uid: &str, count: usize) -> Result<()> {
         let prefix = format!("{}:", uid);
-        let keys: Vec<_> = self
+        let mut keys: Vec<_> = self
             .offline
             .scan_prefix(prefix.as_bytes())
-            .take(count)
             .filter_map(|r| r.ok().map(|(k, _)| k))
             .collect();
+        // 稳定排序：低优先级段在前，段内仍从旧到新 / Stable sort: low band first, still oldest first within a band
+        keys.sort_by_key(|key| offline_band(&key[prefix.len()..]));
+        keys.truncate(count);
 
         for key in keys {
-            self.offline.remove(key)?;
+            self.offline.remove(&key)?;
         }
 
         Ok(())
     }
 
+    /// 按消息ID移除离线消息，`message_ids` 为空时移除全部
+    /// Remove offline messages by ID; an empty `message_ids` removes all
+    ///
+    /// 键格式 / Key format: `uid:[!:|~:]timestamp:message_id`（见 [`offline_key`] / see [`offline_key`]）
+    fn remove_offline(&self, uid: &str, message_ids: &[String]) -> Result<i32> {
+        let prefix = format!("{}:", uid);
+        let keys: Vec<_> = self
+            .offline
+            .scan_prefix(prefix.as_bytes())
+            .filter_map(|r| r.ok().map(|(k, _)| k))
+            .filter(|key| {
+                message_ids.is_empty()
+                    || String::from_utf8_lossy(key)
+                        .rsplit_once(':')
+                        .is_some_and(|(_, id)| message_ids.iter().any(|m| m == id))
+            })
+            .collect();
+
+        let mut count = 0;
+        for key in keys {
+            if self.offline.remove(&key)? {
+                count += 1;
+            }
+        }
+        self.flush_if_sync()?;
+
+        Ok(count)
+    }
+
     /// 获取统计信息 / Get statistics
     pub fn stats(&self) -> &StorageStats {
         &self.stats
     }
 }
 
+/// 离线消息键：`high` 加 `!:`、`low` 加 `~:` 段，使高优先级排在普通之前、低优先级排在之后；
+/// 普通消息沿用原键格式 `to_uid:timestamp:message_id`
+/// Offline key: `high` adds a `!:` segment and `low` a `~:` one, so high priority sorts before
+/// normal and low after it; normal messages keep the original `to_uid:timestamp:message_id`
+fn offline_key(to_uid: &str, priority: &str, timestamp: i64, message_id: &str) -> String {
+    let band = match priority {
+        "high" => "!:",
+        "low" => "~:",
+        _ => "",
+    };
+    format!("{}:{}{}:{}", to_uid, band, timestamp, message_id)
+}
+
+/// 去掉 `to_uid:` 前缀后的键所属的优先级段，0 为低 / Priority band of a key without its `to_uid:` prefix, 0 is low
+fn offline_band(rest: &[u8]) -> u8 {
+    match rest.first() {
+        Some(b'~') => 0,
+        Some(b'!') => 2,
+        _ => 1,
+    }
+}
+
+/// WAL 键 `timestamp:message_id` 中的时间戳 / The timestamp of a `timestamp:message_id` WAL key
+fn key_timestamp(key: &[u8]) -> Option<i64> {
+    let end = key.iter().position(|b| *b == b':')?;
+    std::str::from_utf8(&key[..end]).ok()?.parse().ok()
+}
+
+/// 表情回应键 / Reaction key: `message_id:uid:emoji`
+fn reaction_key(message_id: &str, uid: &str, emoji: &str) -> String {
+    format!("{}:{}:{}", message_id, uid, emoji)
+}
+
+/// 历史消息过滤：时间窗口、参与者与对端 / History filter: time window, participant and peer
+fn history_matches(req: &MessageHistoryRequest, m: &HistoryMessage) -> bool {
+    if req.since_ts > 0 && m.timestamp < req.since_ts {
+        return false;
+    }
+    if req.until_ts > 0 && m.timestamp > req.until_ts {
+        return false;
+    }
+    let involves = |uid: &str| m.from_uid == uid || m.to_uid == uid;
+    match (req.uid.is_empty(), req.peer_uid.is_empty()) {
+        (true, true) => true,
+        (false, true) => involves(&req.uid),
+        (true, false) => involves(&req.peer_uid),
+        (false, false) => {
+            (m.from_uid == req.uid && m.to_uid == req.peer_uid)
+                || (m.from_uid == req.peer_uid && m.to_uid == req.uid)
+        }
+    }
+}
+
+/// 从 WAL 重建消息ID索引（单个批次原子写入），返回索引的消息数
+/// Rebuild the message ID index from the WAL (written atomically as one batch);
+/// returns the number of indexed messages
+fn rebuild_message_index(db: &dyn KvBackend, wal: &dyn KvTree) -> Result<usize> {
+    let mut batch = KvBatch::default();
+    let mut count = 0;
+    for item in wal.iter() {
+        let (key, value) = item?;
+        if let Some(m) = wal_message(&value) {
+            batch.insert(MESSAGE_INDEX_TREE, m.message_id, key);
+            count += 1;
+        }
+    }
+    db.apply_batch(batch)?;
+    Ok(count)
+}
+
+/// 从索引树加载布隆过滤器，容量至少为 `min_capacity` 且为现有条数的两倍
+/// Load the bloom filter from the index tree, sized to at least `min_capacity` and
+/// twice the current entry count
+fn load_message_filter(index: &dyn KvTree, min_capacity: usize) -> Result<BloomFilter> {
+    let mut filter = BloomFilter::with_capacity(min_capacity.max(index.len() * 2));
+    for item in index.iter() {
+        filter.insert(&item?.0);
+    }
+    Ok(filter)
+}
+
+/// 解析 WAL 中的一条消息 / Parse one message from the WAL
+fn wal_message(v: &[u8]) -> Option<HistoryMessage> {
+    let val = serde_json::from_slice::<serde_json::Value>(v).ok()?;
+    Some(HistoryMessage {
+        message_id: val.get("message_id")?.as_str()?.to_string(),
+        from_uid: val.get("from_uid")?.as_str()?.to_string(),
+        to_uid: val.get("to_uid")?.as_str()?.to_string(),
+        content: val.get("content")?.as_str()?.to_string(),
+        timestamp: val.get("timestamp")?.as_i64()?,
+        msg_type: val
+            .get("msg_type")
+            .and_then(|t| t.as_str())
+            .unwrap_or_default()
+            .to_string(),
+    })
+}
+
+/// 消息所属房间：宿主把 `room_id` 放在 JSON 内容中 / The message's room: the host puts `room_id` in the JSON content
+fn content_room_id(content: &str) -> Option<String> {
+    serde_json::from_str::<serde_json::Value>(content)
+        .ok()?
+        .get("room_id")?
+        .as_str()
+        .map(str::to_string)
+}
+
+/// 所回复的消息：宿主把 `reply_to` 放在 JSON 内容中 / The parent message: the host puts `reply_to` in the JSON content
+fn content_reply_to(content: &str) -> Option<String> {
+    serde_json::from_str::<serde_json::Value>(content)
+        .ok()?
+        .get("reply_to")?
+        .as_str()
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
+}
+
+/// 可搜索的文本：JSON 内容取 `text` 字段，否则使用原始内容
+/// Searchable text: the `text` field of JSON content, otherwise the raw content
+fn searchable_text(content: &str) -> String {
+    let text = match serde_json::from_str::<serde_json::Value>(content) {
+        Ok(serde_json::Value::Object(obj)) => obj
+            .get("text")
+            .and_then(|t| t.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        Ok(serde_json::Value::String(s)) => s,
+        _ => content.to_string(),
+    };
+    text.to_lowercase()
+}
+
+/// 相关度：各关键词出现次数之和，任一关键词缺失时不命中
+/// Relevance: total occurrences of all terms; no match if any term is missing
+fn search_score(terms: &[String], content: &str) -> Option<f64> {
+    let text = searchable_text(content);
+    let mut score = 0;
+    for term in terms {
+        match text.matches(term.as_str()).count() {
+            0 => return None,
+            n => score += n,
+        }
+    }
+    Some(score as f64)
+}
+
 // ============================================================================
 // 实现 StorageEventListener Trait / Implement StorageEventListener Trait
 // ============================================================================
@@ -184,8 +615,9 @@ impl StorageEventListener for SledStorageEventListener {
             req.message_id, req.from_uid, req.to_uid
         );
 
-        // 构建键：timestamp:message_id / Build key: timestamp:message_id
-        let key = format!("{}:{}", req.timestamp, req.message_id);
+        // 构建键：timestamp:message_id，时间戳取自单调时钟，记录体保留原时间
+        // Build key: timestamp:message_id, with the timestamp from the monotonic clock; the body keeps the original time
+        let key = format!("{}:{}", self.key_clock.issue(req.timestamp), req.message_id);
 
         // 序列化消息数据 / Serialize message data
         let value = serde_json::json!({
@@ -199,8 +631,11 @@ impl StorageEventListener for SledStorageEventListener {
         let val = serde_json::to_vec(&value)?;
 
         // 保存到 WAL / Save to WAL
-        self.wal.insert(key.as_bytes(), val)?;
-        self.wal.flush()?;
+        self.append_message(&key, &req.message_id, &val)?;
+        self.flush_if_sync()?;
+
+        self.index_attachment(&req.message_id, &req.content)?;
+        self.index_thread(&key, &req.content)?;
 
         self.stats.messages_saved += 1;
 
@@ -232,8 +667,12 @@ impl StorageEventListener for SledStorageEventListener {
             self.remove_oldest_offline(&req.to_uid, 1)?;
         }
 
-        // 构建键：to_uid:timestamp:message_id / Build key
-        let key = format!("{}:{}:{}", req.to_uid, req.timestamp, req.message_id);
+        let key = offline_key(
+            &req.to_uid,
+            &req.priority,
+            self.key_clock.issue(req.timestamp),
+            &req.message_id,
+        );
 
         // 序列化消息数据 / Serialize message data
         let value = serde_json::json!({
@@ -242,12 +681,13 @@ impl StorageEventListener for SledStorageEventListener {
             "from_uid": req.from_uid,
             "content": req.content,
             "timestamp": req.timestamp,
+            "priority": req.priority,
         });
         let val = serde_json::to_vec(&value)?;
 
         // 保存到离线消息树 / Save to offline tree
-        self.offline.insert(key.as_bytes(), val)?;
-        self.offline.flush()?;
+        self.offline.insert(key.as_bytes(), &val)?;
+        self.flush_if_sync()?;
 
         self.stats.offline_saved += 1;
 
@@ -287,6 +727,11 @@ impl StorageEventListener for SledStorageEventListener {
                             from_uid: val.get("from_uid")?.as_str()?.to_string(),
                             content: val.get("content")?.as_str()?.to_string(),
                             timestamp: val.get("timestamp")?.as_i64()?,
+                            priority: val
+                                .get("priority")
+                                .and_then(|p| p.as_str())
+                                .unwrap_or_default()
+                                .to_string(),
                         })
                     })
             })
@@ -318,24 +763,12 @@ impl StorageEventListener for SledStorageEventListener {
             req.message_ids.len()
         );
 
-        let mut count = 0;
-        for message_id in &req.message_ids {
-            // 查找并删除消息 / Find and delete message
-            let prefix = format!("{}:", req.uid);
-            for result in self.offline.scan_prefix(prefix.as_bytes()) {
-                if let Ok((key, _)) = result {
-                    if let Ok(key_str) = String::from_utf8(key.to_vec()) {
-                        if key_str.ends_with(message_id) {
-                            self.offline.remove(&key)?;
-                            count += 1;
-                            break;
-                        }
-                    }
-                }
-            }
-        }
-
-        self.offline.flush()?;
+        // 空列表不代表“全部已读” / An empty list does not mean "all read"
+        let count = if req.message_ids.is_empty() {
+            0
+        } else {
+            self.remove_offline(&req.uid, &req.message_ids)?
+        };
         self.stats.offline_acked += count as u64;
 
         info!(
@@ -372,6 +805,569 @@ impl StorageEventListener for SledStorageEventListener {
         })
     }
 
+    /// 删除离线消息 / Delete offline messages
+    async fn storage_offline_delete(
+        &mut self,
+        req: &DeleteOfflineMessagesRequest,
+    ) -> Result<DeleteOfflineMessagesResponse> {
+        debug!(
+            "🗑️  删除离线消息 / Deleting offline messages for {}: {} ids",
+            req.uid,
+            req.message_ids.len()
+        );
+
+        let count = self.remove_offline(&req.uid, &req.message_ids)?;
+        self.stats.offline_deleted += count as u64;
+
+        info!(
+            "✅ 已删除 {} 条离线消息 / Deleted {} offline messages for {}",
+            count, count, req.uid
+        );
+
+        Ok(DeleteOfflineMessagesResponse {
+            status: STATUS_OK.to_string(),
+            count,
+        })
+    }
+
+    /// 停机前刷盘（覆盖所有树）/ Flush before shutdown (covers every tree)
+    async fn storage_flush(&mut self) -> Result<()> {
+        let backend = self.backend.clone();
+        tokio::task::spawn_blocking(move || backend.flush()).await??;
+        info!("💾 已刷盘 / Flushed to disk");
+        Ok(())
+    }
+
+    /// 查询历史消息（WAL 按时间倒序扫描，取最近 limit 条）
+    /// Query message history (scan the WAL newest first, keep the latest `limit`)
+    async fn storage_message_history(
+        &mut self,
+        req: &MessageHistoryRequest,
+    ) -> Result<MessageHistoryResponse> {
+        debug!(
+            "📜 查询历史消息 / Querying history: uid={} peer={} [{}, {}] limit={}",
+            req.uid, req.peer_uid, req.since_ts, req.until_ts, req.limit
+        );
+
+        let limit = req.limit.max(0) as usize;
+        let mut messages: Vec<HistoryMessage> = self
+            .wal
+            .iter_rev()
+            .filter_map(|r| r.ok())
+            .filter_map(|(_, v)| wal_message(&v))
+            .filter(|m| history_matches(req, m))
+            .take(limit)
+            .collect();
+        messages.reverse();
+
+        let total = messages.len() as i32;
+
+        info!(
+            "✅ 历史消息 {} 条 / {} history messages for uid={} peer={}",
+            total, total, req.uid, req.peer_uid
+        );
+
+        Ok(MessageHistoryResponse {
+            status: STATUS_OK.to_string(),
+            messages,
+            total,
+        })
+    }
+
+    /// 归档时间范围内的消息：WAL 原始记录逐行写成 NDJSON 对象，本地数据保留
+    /// Archive messages in a time range: raw WAL records written line by line as one
+    /// NDJSON object; local data is kept
+    async fn storage_message_archive(
+        &mut self,
+        req: &ArchiveMessagesRequest,
+    ) -> Result<ArchiveMessagesResponse> {
+        if req.until_ts <= req.since_ts {
+            anyhow::bail!(
+                "until_ts 必须大于 since_ts / until_ts must be greater than since_ts: [{}, {})",
+                req.since_ts,
+                req.until_ts
+            );
+        }
+        let store = self.archive_store()?;
+        let archive = self.config.archive.as_ref().expect("store implies config");
+        let archive_key = archive.object_key(req.since_ts, req.until_ts);
+
+        // WAL 键为 `timestamp:message_id` 字符串，不按数值有序，需全量扫描
+        // WAL keys are `timestamp:message_id` strings, not numerically ordered, so scan everything
+        let mut body = Vec::new();
+        let mut count = 0;
+        for item in self.wal.iter() {
+            let (_, v) = item?;
+            let in_range = wal_message(&v)
+                .is_some_and(|m| m.timestamp >= req.since_ts && m.timestamp < req.until_ts);
+            if in_range {
+                body.extend_from_slice(&v);
+                body.push(b'\n');
+                count += 1;
+            }
+        }
+
+        store.put(&archive_key, body).await?;
+        self.archives.insert(
+            archive_key.as_bytes(),
+            &serde_json::to_vec(&serde_json::json!({
+                "since_ts": req.since_ts,
+                "until_ts": req.until_ts,
+                "count": count,
+                "archived_at": chrono::Utc::now().timestamp_millis(),
+            }))?,
+        )?;
+        info!(
+            "📦 已归档 {} 条消息 / Archived {} messages to {}",
+            count, count, archive_key
+        );
+
+        Ok(ArchiveMessagesResponse {
+            status: STATUS_OK.to_string(),
+            archive_key,
+            count,
+        })
+    }
+
+    /// 按消息ID查询：布隆过滤器排除不存在的ID，否则读一次索引与一次 WAL
+    /// Look up by message ID: the bloom filter rules out unknown IDs, otherwise one
+    /// index read and one WAL read
+    async fn storage_message_get(&mut self, req: &GetMessageRequest) -> Result<GetMessageResponse> {
+        let message = if self.message_filter.may_contain(req.message_id.as_bytes()) {
+            match self.message_index.get(req.message_id.as_bytes())? {
+                Some(key) => self.wal.get(&key)?.and_then(|v| wal_message(&v)),
+                None => None,
+            }
+        } else {
+            None
+        };
+
+        Ok(GetMessageResponse {
+            status: STATUS_OK.to_string(),
+            found: message.is_some(),
+            message,
+        })
+    }
+
+    /// 按写入顺序列出回复某条消息的消息 / List the replies to a message in write order
+    async fn storage_message_thread(
+        &mut self,
+        req: &ListThreadRequest,
+    ) -> Result<ListThreadResponse> {
+        let mut keys = Vec::new();
+        for item in self
+            .threads
+            .scan_prefix(format!("{}:", req.parent_id).as_bytes())
+        {
+            keys.push(item?.1);
+        }
+        // 键中的时间戳按数值排序 / Order by the numeric timestamp in the key
+        keys.sort_by_key(|key| key_timestamp(key));
+        let mut messages = Vec::new();
+        for key in keys.iter().take(req.limit.max(0) as usize) {
+            if let Some(m) = self.wal.get(key)?.and_then(|v| wal_message(&v)) {
+                messages.push(m);
+            }
+        }
+
+        Ok(ListThreadResponse {
+            status: STATUS_OK.to_string(),
+            total: messages.len() as i32,
+            messages,
+        })
+    }
+
+    /// 从归档对象恢复消息，WAL 中已存在的消息跳过
+    /// Restore messages from an archive object, skipping those already in the WAL
+    async fn storage_message_restore(
+        &mut self,
+        req: &RestoreMessagesRequest,
+    ) -> Result<RestoreMessagesResponse> {
+        let body = self.archive_store()?.get(&req.archive_key).await?;
+
+        let mut count = 0;
+        for line in body.split(|b| *b == b'\n').filter(|l| !l.is_empty()) {
+            let m = wal_message(line).ok_or_else(|| {
+                anyhow!(
+                    "归档 {} 含无效记录 / Archive {} has an invalid record",
+                    req.archive_key,
+                    req.archive_key
+                )
+            })?;
+            // 经索引查 WAL 去重：键时间可能与记录体中的时间不同
+            // Dedupe through the index: the key time may differ from the body's
+            if let Some(indexed) = self.message_index.get(m.message_id.as_bytes())? {
+                if self.wal.contains_key(&indexed)? {
+                    continue;
+                }
+            }
+            // 恢复的消息回到原来的时间位置 / Restored messages go back to their original place in time
+            let key = format!("{}:{}", m.timestamp, m.message_id);
+            self.append_message(&key, &m.message_id, line)?;
+            self.index_attachment(&m.message_id, &m.content)?;
+            self.index_thread(&key, &m.content)?;
+            count += 1;
+        }
+        self.flush_if_sync()?;
+        info!(
+            "♻️  已从 {} 恢复 {} 条消息 / Restored {} messages from {}",
+            req.archive_key, count, count, req.archive_key
+        );
+
+        Ok(RestoreMessagesResponse {
+            status: STATUS_OK.to_string(),
+            count,
+        })
+    }
+
+    /// 删除房间内 `until_ts` 之前的消息及其索引、附件、表情回应、置顶与回复索引；`archive` 为真时先上传归档
+    /// Delete a room's messages older than `until_ts` along with their index, attachment,
+    /// reaction, pin and reply index entries; with `archive` set they are uploaded to the archive first
+    async fn storage_message_purge(
+        &mut self,
+        req: &PurgeMessagesRequest,
+    ) -> Result<PurgeMessagesResponse> {
+        if req.room_id.is_empty() {
+            anyhow::bail!("room_id 不能为空 / room_id is required");
+        }
+        // 归档需要存储，先检查，避免未归档就删除 / Archiving needs a store; check first so nothing is deleted unarchived
+        let store = if req.archive {
+            Some(self.archive_store()?)
+        } else {
+            None
+        };
+
+        // WAL 键不按数值有序，需全量扫描 / WAL keys are not numerically ordered, so scan everything
+        let mut expired = Vec::new();
+        for item in self.wal.iter() {
+            let (key, v) = item?;
+            let Some(m) = wal_message(&v) else {
+                continue;
+            };
+            if m.timestamp < req.until_ts
+                && content_room_id(&m.content).as_deref() == Some(req.room_id.as_str())
+            {
+                let parent_id = content_reply_to(&m.content);
+                expired.push((key, m.message_id, parent_id, v));
+            }
+        }
+        if expired.is_empty() {
+            return Ok(PurgeMessagesResponse {
+                status: STATUS_OK.to_string(),
+                deleted: 0,
+                archive_key: String::new(),
+            });
+        }
+
+        let mut archive_key = String::new();
+        if let Some(store) = store {
+            let archive = self.config.archive.as_ref().expect("store implies config");
+            archive_key = archive.room_object_key(&req.room_id, req.until_ts);
+            let mut body = Vec::new();
+            for (_, _, _, v) in &expired {
+                body.extend_from_slice(v);
+                body.push(b'\n');
+            }
+            store.put(&archive_key, body).await?;
+            self.archives.insert(
+                archive_key.as_bytes(),
+                &serde_json::to_vec(&serde_json::json!({
+                    "room_id": req.room_id,
+                    "until_ts": req.until_ts,
+                    "count": expired.len(),
+                    "archived_at": chrono::Utc::now().timestamp_millis(),
+                }))?,
+            )?;
+        }
+
+        for (key, message_id, parent_id, _) in &expired {
+            self.wal.remove(key)?;
+            self.message_index.remove(message_id.as_bytes())?;
+            self.attachments.remove(message_id.as_bytes())?;
+            let reaction_keys: Vec<Vec<u8>> = self
+                .reactions
+                .scan_prefix(format!("{}:", message_id).as_bytes())
+                .map(|item| item.map(|(k, _)| k))
+                .collect::<Result<_>>()?;
+            for reaction_key in reaction_keys {
+                self.reactions.remove(&reaction_key)?;
+            }
+            self.pins
+                .remove(format!("{}:{}", req.room_id, message_id).as_bytes())?;
+            if self.group_ack_messages.remove(message_id.as_bytes())? {
+                let ack_keys: Vec<Vec<u8>> = self
+                    .group_acks
+                    .scan_prefix(format!("{}:", message_id).as_bytes())
+                    .map(|item| item.map(|(k, _)| k))
+                    .collect::<Result<_>>()?;
+                for ack_key in ack_keys {
+                    self.group_acks.remove(&ack_key)?;
+                }
+            }
+            if let Some(parent_id) = parent_id {
+                let mut thread_key = format!("{}:", parent_id).into_bytes();
+                thread_key.extend_from_slice(key);
+                self.threads.remove(&thread_key)?;
+            }
+        }
+        self.flush_if_sync()?;
+        info!(
+            "🧹 房间 {} 清理 {} 条过期消息 / Purged {} expired messages from room {}",
+            req.room_id,
+            expired.len(),
+            expired.len(),
+            req.room_id
+        );
+
+        Ok(PurgeMessagesResponse {
+            status: STATUS_OK.to_string(),
+            deleted: expired.len() as i32,
+            archive_key,
+        })
+    }
+
+    /// 搜索消息（线性扫描 WAL，按相关度降序、同分按时间倒序；游标为结果偏移量）
+    /// Search messages (linear WAL scan, ranked by relevance then newest first; the
+    /// cursor is the offset into the ranked results)
+    async fn storage_message_search(
+        &mut self,
+        req: &SearchMessagesRequest,
+    ) -> Result<SearchMessagesResponse> {
+        debug!(
+            "🔍 搜索消息 / Searching messages: uid={} query={:?} cursor={:?} limit={}",
+            req.uid, req.query, req.cursor, req.limit
+        );
+
+        if req.uid.is_empty() {
+            anyhow::bail!("uid 不能为空 / uid is required");
+        }
+        let terms: Vec<String> = req
+            .query
+            .split_whitespace()
+            .map(|t| t.to_lowercase())
+            .collect();
+        if terms.is_empty() {
+            anyhow::bail!("query 不能为空 / query is required");
+        }
+        let offset = match req.cursor.as_str() {
+            "" => 0,
+            c => c
+                .parse::<usize>()
+                .map_err(|_| anyhow::anyhow!("无效的游标 / Invalid cursor: {}", c))?,
+        };
+        let limit = match req.limit {
+            l if l > 0 => l as usize,
+            _ => DEFAULT_SEARCH_LIMIT,
+        };
+
+        let mut hits: Vec<SearchHit> = self
+            .wal
+            .iter()
+            .filter_map(|r| r.ok())
+            .filter_map(|(_, v)| wal_message(&v))
+            .filter(|m| m.from_uid == req.uid || m.to_uid == req.uid)
+            .filter_map(|m| {
+                let score = search_score(&terms, &m.content)?;
+                Some(SearchHit {
+                    message: Some(m),
+                    score,
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| {
+            let (ma, mb) = (a.message.as_ref(), b.message.as_ref());
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| mb.map(|m| m.timestamp).cmp(&ma.map(|m| m.timestamp)))
+                .then_with(|| ma.map(|m| &m.message_id).cmp(&mb.map(|m| &m.message_id)))
+        });
+
+        let total = hits.len();
+        let page: Vec<SearchHit> = hits.into_iter().skip(offset).take(limit).collect();
+        let next = offset + page.len();
+        let next_cursor = if next < total {
+            next.to_string()
+        } else {
+            String::new()
+        };
+
+        info!(
+            "✅ 搜索命中 {} 条 / {} search hits for uid={}",
+            total, total, req.uid
+        );
+
+        Ok(SearchMessagesResponse {
+            status: STATUS_OK.to_string(),
+            hits: page,
+            next_cursor,
+            total: total as i32,
+        })
+    }
+
+    /// 添加表情回应，键为 `message_id:uid:emoji` / Add a reaction keyed by `message_id:uid:emoji`
+    async fn storage_reaction_add(
+        &mut self,
+        req: &AddReactionRequest,
+    ) -> Result<AddReactionResponse> {
+        let key = reaction_key(&req.message_id, &req.uid, &req.emoji);
+        let changed = !self.reactions.contains_key(key.as_bytes())?;
+        if changed {
+            let val = serde_json::to_vec(&(&req.uid, &req.emoji))?;
+            self.reactions.insert(key.as_bytes(), &val)?;
+            self.flush_if_sync()?;
+        }
+        Ok(AddReactionResponse {
+            status: STATUS_OK.to_string(),
+            changed,
+        })
+    }
+
+    /// 移除表情回应 / Remove a reaction
+    async fn storage_reaction_remove(
+        &mut self,
+        req: &RemoveReactionRequest,
+    ) -> Result<RemoveReactionResponse> {
+        let key = reaction_key(&req.message_id, &req.uid, &req.emoji);
+        let changed = self.reactions.remove(key.as_bytes())?;
+        if changed {
+            self.flush_if_sync()?;
+        }
+        Ok(RemoveReactionResponse {
+            status: STATUS_OK.to_string(),
+            changed,
+        })
+    }
+
+    /// 按表情汇总一条消息的回应 / List a message's reactions aggregated by emoji
+    async fn storage_reaction_list(
+        &mut self,
+        req: &ListReactionsRequest,
+    ) -> Result<ListReactionsResponse> {
+        Ok(ListReactionsResponse {
+            status: STATUS_OK.to_string(),
+            reactions: self.list_reactions(&req.message_id)?,
+        })
+    }
+
+    /// 置顶房间消息，键为 `room_id:message_id`，超出 `max_pins` 时不写入
+    /// Pin a room message keyed by `room_id:message_id`; nothing is written beyond `max_pins`
+    async fn storage_pin_add(&mut self, req: &AddPinRequest) -> Result<AddPinResponse> {
+        let key = format!("{}:{}", req.room_id, req.message_id);
+        if self.pins.contains_key(key.as_bytes())? {
+            return Ok(AddPinResponse {
+                status: STATUS_OK.to_string(),
+                changed: false,
+                full: false,
+            });
+        }
+        let prefix = format!("{}:", req.room_id);
+        let pinned = self.pins.scan_prefix(prefix.as_bytes()).count();
+        let full = req.max_pins > 0 && pinned >= req.max_pins as usize;
+        if !full {
+            let val = serde_json::to_vec(&(&req.message_id, &req.pinned_by, req.pinned_at))?;
+            self.pins.insert(key.as_bytes(), &val)?;
+            self.flush_if_sync()?;
+        }
+        Ok(AddPinResponse {
+            status: STATUS_OK.to_string(),
+            changed: !full,
+            full,
+        })
+    }
+
+    /// 取消置顶 / Unpin a room message
+    async fn storage_pin_remove(&mut self, req: &RemovePinRequest) -> Result<RemovePinResponse> {
+        let key = format!("{}:{}", req.room_id, req.message_id);
+        let changed = self.pins.remove(key.as_bytes())?;
+        if changed {
+            self.flush_if_sync()?;
+        }
+        Ok(RemovePinResponse {
+            status: STATUS_OK.to_string(),
+            changed,
+        })
+    }
+
+    /// 列出房间置顶消息 / List a room's pinned messages
+    async fn storage_pin_list(&mut self, req: &ListPinsRequest) -> Result<ListPinsResponse> {
+        Ok(ListPinsResponse {
+            status: STATUS_OK.to_string(),
+            pins: self.list_pins(&req.room_id)?,
+        })
+    }
+
+    /// 登记群消息应确认的成员 / Register the members expected to ack a group message
+    async fn storage_group_ack_track(
+        &mut self,
+        req: &TrackGroupAckRequest,
+    ) -> Result<TrackGroupAckResponse> {
+        let mut recipients = req.recipients.clone();
+        recipients.sort();
+        recipients.dedup();
+        let val = serde_json::to_vec(&(&req.room_id, &req.sender_uid, &recipients))?;
+        self.group_ack_messages
+            .insert(req.message_id.as_bytes(), &val)?;
+        self.flush_if_sync()?;
+        Ok(TrackGroupAckResponse {
+            status: STATUS_OK.to_string(),
+        })
+    }
+
+    /// 记录成员确认，仅保留首次确认时间；消息未登记或非接收者时不写入
+    /// Record a member ack keeping the first ack time; nothing is written for unknown messages or non-recipients
+    async fn storage_group_ack_add(
+        &mut self,
+        req: &AddGroupAckRequest,
+    ) -> Result<AddGroupAckResponse> {
+        let recorded = self
+            .group_ack_message(&req.message_id)?
+            .is_some_and(|(_, _, recipients)| recipients.contains(&req.uid));
+        if recorded {
+            let key = format!("{}:{}", req.message_id, req.uid);
+            if !self.group_acks.contains_key(key.as_bytes())? {
+                self.group_acks
+                    .insert(key.as_bytes(), &serde_json::to_vec(&req.acked_at)?)?;
+                self.flush_if_sync()?;
+            }
+        }
+        Ok(AddGroupAckResponse {
+            status: STATUS_OK.to_string(),
+            recorded,
+        })
+    }
+
+    /// 查询群消息确认进度 / Query the ack progress of a group message
+    async fn storage_group_ack_status(
+        &mut self,
+        req: &GroupAckStatusRequest,
+    ) -> Result<GroupAckStatusResponse> {
+        let Some((room_id, sender_uid, recipients)) = self.group_ack_message(&req.message_id)?
+        else {
+            return Ok(GroupAckStatusResponse {
+                status: STATUS_OK.to_string(),
+                ..Default::default()
+            });
+        };
+        let mut acked = Vec::new();
+        let mut pending = Vec::new();
+        for uid in recipients {
+            let key = format!("{}:{}", req.message_id, uid);
+            if self.group_acks.contains_key(key.as_bytes())? {
+                acked.push(uid);
+            } else {
+                pending.push(uid);
+            }
+        }
+        Ok(GroupAckStatusResponse {
+            status: STATUS_OK.to_string(),
+            found: true,
+            room_id,
+            sender_uid,
+            acked,
+            pending,
+        })
+    }
+
     /// 添加房间成员 / Add room member
     async fn storage_room_add_member(
         &mut self,
@@ -396,8 +1392,8 @@ impl StorageEventListener for SledStorageEventListener {
 
         // 保存更新后的成员列表 / Save updated members
         let val = serde_json::to_vec(&members)?;
-        self.rooms.insert(key.as_bytes(), val)?;
-        self.rooms.flush()?;
+        self.rooms.insert(key.as_bytes(), &val)?;
+        self.flush_if_sync()?;
 
         info!(
             "✅ 成员已添加 / Member added: {} to room {}",
@@ -433,8 +1429,8 @@ impl StorageEventListener for SledStorageEventListener {
 
         // 保存更新后的成员列表 / Save updated members
         let val = serde_json::to_vec(&members)?;
-        self.rooms.insert(key.as_bytes(), val)?;
-        self.rooms.flush()?;
+        self.rooms.insert(key.as_bytes(), &val)?;
+        self.flush_if_sync()?;
 
         info!(
             "✅ 成员已移除 / Member removed: {} from room {}",
@@ -446,6 +1442,47 @@ impl StorageEventListener for SledStorageEventListener {
         })
     }
 
+    /// 批量更新房间成员，一次读改写 / Bulk room membership update in one read-modify-write
+    async fn storage_room_update_members(
+        &mut self,
+        req: &UpdateRoomMembersRequest,
+    ) -> Result<UpdateRoomMembersResponse> {
+        debug!(
+            "👥 批量更新房间成员 / Updating room {} members: +{} -{}",
+            req.room_id,
+            req.add.len(),
+            req.remove.len()
+        );
+
+        let key = format!("{}:members", req.room_id);
+        let mut members: HashSet<String> = if let Some(data) = self.rooms.get(key.as_bytes())? {
+            serde_json::from_slice(&data).unwrap_or_default()
+        } else {
+            HashSet::new()
+        };
+        members.extend(req.add.iter().cloned());
+        for uid in &req.remove {
+            members.remove(uid);
+        }
+
+        let val = serde_json::to_vec(&members)?;
+        self.rooms.insert(key.as_bytes(), &val)?;
+        self.flush_if_sync()?;
+
+        let mut members: Vec<String> = members.into_iter().collect();
+        members.sort();
+        info!(
+            "✅ 房间成员已更新 / Room members updated: {} now has {}",
+            req.room_id,
+            members.len()
+        );
+
+        Ok(UpdateRoomMembersResponse {
+            status: STATUS_OK.to_string(),
+            members,
+        })
+    }
+
     /// 获取房间成员列表 / Get room members
     async fn storage_room_list_members(
         &mut self,
@@ -475,3 +1512,760 @@ impl StorageEventListener for SledStorageEventListener {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+    use v::plugin::pdk::dispatch_storage_event;
+
+    fn listener(name: &str) -> SledStorageEventListener {
+        let path =
+            std::env::temp_dir().join(format!("vgo-storage-sled-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        SledStorageEventListener::new(SledStorageConfig {
+            db_path: path.to_string_lossy().to_string(),
+            ..SledStorageConfig::default()
+        })
+        .unwrap()
+    }
+
+    fn event(event_type: &str, payload: Vec<u8>) -> EventMessage {
+        EventMessage {
+            event_type: event_type.to_string(),
+            payload,
+            timestamp: 0,
+            trace_id: String::new(),
+        }
+    }
+
+    async fn json_call(
+        l: &mut SledStorageEventListener,
+        event_type: &str,
+        payload: serde_json::Value,
+    ) -> serde_json::Value {
+        let resp = dispatch_storage_event(l, &event(event_type, payload.to_string().into_bytes()))
+            .await
+            .unwrap();
+        serde_json::from_slice(&resp.data).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_offline_events_over_json() {
+        let mut l = listener("offline");
+        for (i, id) in ["m1", "m2", "m3"].iter().enumerate() {
+            let resp = json_call(
+                &mut l,
+                "storage.offline.save",
+                serde_json::json!({
+                    "message_id": id, "from_uid": "a", "to_uid": "b",
+                    "content": {"text": id}, "timestamp": 1000 + i as i64,
+                }),
+            )
+            .await;
+            assert_eq!(resp["status"], "ok");
+        }
+
+        let pulled = json_call(
+            &mut l,
+            "storage.offline.pull",
+            serde_json::json!({"to_uid": "b", "limit": 10}),
+        )
+        .await;
+        assert_eq!(pulled["messages"][0]["content"]["text"], "m1");
+
+        let acked = json_call(
+            &mut l,
+            "storage.offline.ack",
+            serde_json::json!({"to_uid": "b", "message_ids": ["m1"]}),
+        )
+        .await;
+        assert_eq!(acked["removed"], 1);
+
+        let deleted = json_call(
+            &mut l,
+            "storage.offline.delete",
+            serde_json::json!({"to_uid": "b", "message_ids": []}),
+        )
+        .await;
+        assert_eq!(deleted["deleted"], 2);
+
+        let count = json_call(
+            &mut l,
+            "storage.offline.count",
+            serde_json::json!({"to_uid": "b"}),
+        )
+        .await;
+        assert_eq!(count["count"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_offline_pull_and_eviction_follow_priority() {
+        let mut l = listener("offline-priority");
+        l.config.max_offline_messages = 3;
+        for (i, (id, priority)) in [("n1", "normal"), ("l1", "low"), ("h1", "high"), ("n2", "")]
+            .iter()
+            .enumerate()
+        {
+            json_call(
+                &mut l,
+                "storage.offline.save",
+                serde_json::json!({
+                    "message_id": id, "from_uid": "a", "to_uid": "b", "content": id,
+                    "timestamp": 1000 + i as i64, "priority": priority,
+                }),
+            )
+            .await;
+        }
+
+        // 满额时先挤掉低优先级，拉取时高优先级在前 / The low one is evicted when full and high comes first
+        let pulled = json_call(
+            &mut l,
+            "storage.offline.pull",
+            serde_json::json!({"to_uid": "b", "limit": 10}),
+        )
+        .await;
+        let ids: Vec<&str> = pulled["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["message_id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, ["h1", "n1", "n2"]);
+        assert_eq!(pulled["messages"][0]["priority"], "high");
+
+        let acked = json_call(
+            &mut l,
+            "storage.offline.ack",
+            serde_json::json!({"to_uid": "b", "message_ids": ["h1"]}),
+        )
+        .await;
+        assert_eq!(acked["removed"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_keys_stay_monotonic_across_a_backward_clock_jump() {
+        let mut l = listener("clock-jump");
+        // 第三条之后主机时钟回拨 10 秒 / The host clock steps back 10s after the third message
+        let times = [1_700_000_000_000_i64, 1_700_000_000_001, 1_700_000_000_002];
+        let jumped = [1_699_999_990_000_i64, 1_699_999_990_001];
+        for (i, ts) in times.iter().chain(&jumped).enumerate() {
+            let id = format!("m{}", i);
+            json_call(
+                &mut l,
+                "storage.message.save",
+                serde_json::json!({
+                    "message_id": id, "from_uid": "a", "to_uid": "b", "content": id,
+                    "timestamp": ts, "msg_type": "text",
+                }),
+            )
+            .await;
+            json_call(
+                &mut l,
+                "storage.offline.save",
+                serde_json::json!({
+                    "message_id": id, "from_uid": "a", "to_uid": "b", "content": id, "timestamp": ts,
+                }),
+            )
+            .await;
+        }
+
+        // 键按写入顺序排列，记录体保留真实时间 / Keys follow write order and bodies keep the real time
+        let wal: Vec<(i64, HistoryMessage)> = l
+            .wal
+            .iter()
+            .map(|item| {
+                let (k, v) = item.unwrap();
+                (key_timestamp(&k).unwrap(), wal_message(&v).unwrap())
+            })
+            .collect();
+        let ids: Vec<&str> = wal.iter().map(|(_, m)| m.message_id.as_str()).collect();
+        assert_eq!(ids, ["m0", "m1", "m2", "m3", "m4"]);
+        assert!(wal.windows(2).all(|w| w[0].0 < w[1].0));
+        assert_eq!(wal[3].1.timestamp, jumped[0]);
+
+        let pulled = json_call(
+            &mut l,
+            "storage.offline.pull",
+            serde_json::json!({"to_uid": "b", "limit": 10}),
+        )
+        .await;
+        let offline: Vec<&str> = pulled["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["message_id"].as_str().unwrap())
+            .collect();
+        assert_eq!(offline, ["m0", "m1", "m2", "m3", "m4"]);
+        assert_eq!(pulled["messages"][4]["timestamp"], jumped[1]);
+
+        // 重启后从最新的 WAL 键继续 / After a restart the clock continues after the newest WAL key
+        let newest = wal.last().unwrap().0;
+        let config = l.config.clone();
+        drop(l);
+        let l = SledStorageEventListener::new(config).unwrap();
+        assert_eq!(l.key_clock.issue(jumped[0]), newest + 1);
+    }
+
+    #[tokio::test]
+    async fn test_config_from_host_json_is_applied() {
+        let config: SledStorageConfig = serde_json::from_value(serde_json::json!({
+            "db_path": std::env::temp_dir()
+                .join(format!("vgo-storage-sled-config-{}", std::process::id())),
+            "cache_capacity_bytes": 8 * 1024 * 1024,
+            "flush_every_ms": 0,
+        }))
+        .unwrap();
+        config.validate().unwrap();
+        assert_eq!(config.cache_capacity_bytes, 8 * 1024 * 1024);
+        assert_eq!(config.max_offline_messages, default_max_offline());
+        let defaults: SledStorageConfig = serde_json::from_str("{}").unwrap();
+        assert_eq!(defaults.flush_every_ms, 500);
+        let legacy: SledStorageConfig =
+            serde_json::from_value(serde_json::json!({"enable_compression": true})).unwrap();
+        assert!(legacy.compression);
+        assert!(legacy.validate().is_err());
+
+        let _ = std::fs::remove_dir_all(&config.db_path);
+        let mut l = SledStorageEventListener::new(config.clone()).unwrap();
+        json_call(
+            &mut l,
+            "storage.room.add_member",
+            serde_json::json!({"room_id": "r1", "uid": "a"}),
+        )
+        .await;
+        // 同步落盘模式下无需 storage.flush / No storage.flush needed in synchronous mode
+        drop(l);
+        let mut reopened = SledStorageEventListener::new(config).unwrap();
+        let members = json_call(
+            &mut reopened,
+            "storage.room.list_members",
+            serde_json::json!({"room_id": "r1"}),
+        )
+        .await;
+        assert_eq!(members["members"], serde_json::json!(["a"]));
+    }
+
+    #[tokio::test]
+    async fn test_flush_acks_and_data_survives_reopen() {
+        let mut l = listener("flush");
+        let config = l.config.clone();
+        json_call(
+            &mut l,
+            "storage.room.add_member",
+            serde_json::json!({"room_id": "r1", "uid": "a"}),
+        )
+        .await;
+        let resp = json_call(&mut l, "storage.flush", serde_json::json!({})).await;
+        assert_eq!(resp["status"], "ok");
+
+        drop(l);
+        let mut reopened = SledStorageEventListener::new(config).unwrap();
+        let members = json_call(
+            &mut reopened,
+            "storage.room.list_members",
+            serde_json::json!({"room_id": "r1"}),
+        )
+        .await;
+        assert_eq!(members["members"], serde_json::json!(["a"]));
+    }
+
+    #[tokio::test]
+    async fn test_message_get_uses_index_rebuilt_on_open() {
+        let mut l = listener("get");
+        let config = l.config.clone();
+        for (i, id) in ["m1", "m2"].iter().enumerate() {
+            json_call(
+                &mut l,
+                "storage.message.save",
+                serde_json::json!({
+                    "message_id": id, "from_uid": "a", "to_uid": "b",
+                    "content": {"text": id}, "timestamp": 1000 + i as i64,
+                }),
+            )
+            .await;
+        }
+        let get = |id: &str| serde_json::json!({"message_id": id});
+        let resp = json_call(&mut l, MESSAGE_GET_EVENT, get("m2")).await;
+        assert_eq!(resp["found"], true);
+        assert_eq!(resp["message"]["timestamp"], 1001);
+        let resp = json_call(&mut l, MESSAGE_GET_EVENT, get("missing")).await;
+        assert_eq!(
+            resp,
+            serde_json::json!({"status": "ok", "found": false, "message": null})
+        );
+
+        // 模拟索引出现之前的数据 / Simulate data written before the index existed
+        l.message_index.clear().unwrap();
+        drop(l);
+        let mut reopened = SledStorageEventListener::new(config).unwrap();
+        assert_eq!(reopened.message_index.len(), 2);
+        let resp = json_call(&mut reopened, MESSAGE_GET_EVENT, get("m1")).await;
+        assert_eq!(
+            resp["message"]["content"],
+            serde_json::json!({"text": "m1"})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_thread_lists_replies_in_order() {
+        let mut l = listener("thread");
+        for (i, (id, content)) in [
+            ("m1", serde_json::json!({"text": "parent"})),
+            ("m2", serde_json::json!({"text": "first", "reply_to": "m1"})),
+            ("m3", serde_json::json!({"text": "other", "reply_to": "m9"})),
+            ("m4", serde_json::json!({"text": "again", "reply_to": "m1"})),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            json_call(
+                &mut l,
+                "storage.message.save",
+                serde_json::json!({
+                    "message_id": id, "from_uid": "a", "to_uid": "b",
+                    "content": content, "timestamp": 1000 + i as i64,
+                }),
+            )
+            .await;
+        }
+        let thread = |limit: u64| serde_json::json!({"parent_id": "m1", "limit": limit});
+        let resp = json_call(&mut l, MESSAGE_THREAD_EVENT, thread(10)).await;
+        let ids: Vec<&str> = resp["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["message_id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, ["m2", "m4"]);
+        assert_eq!(resp["messages"][1]["content"]["reply_to"], "m1");
+        let resp = json_call(&mut l, MESSAGE_THREAD_EVENT, thread(1)).await;
+        assert_eq!(resp["total"], 1);
+        assert_eq!(resp["messages"][0]["message_id"], "m2");
+    }
+
+    #[tokio::test]
+    async fn test_message_history_over_protobuf() {
+        let mut l = listener("history");
+        for (ts, id, from, to) in [
+            (1000, "h1", "a", "b"),
+            (1001, "h2", "a", "c"),
+            (1002, "h3", "b", "a"),
+            (1003, "h4", "a", "b"),
+        ] {
+            let req = SaveMessageRequest {
+                message_id: id.to_string(),
+                from_uid: from.to_string(),
+                to_uid: to.to_string(),
+                content: "{}".to_string(),
+                timestamp: ts,
+                msg_type: "text".to_string(),
+            };
+            let resp =
+                dispatch_storage_event(&mut l, &event("storage.message.save", req.encode_to_vec()))
+                    .await
+                    .unwrap();
+            assert_eq!(
+                SaveMessageResponse::decode(&resp.data[..]).unwrap().status,
+                "ok"
+            );
+        }
+
+        let req = MessageHistoryRequest {
+            uid: "a".to_string(),
+            peer_uid: "b".to_string(),
+            since_ts: 0,
+            until_ts: 0,
+            limit: 2,
+        };
+        let resp = dispatch_storage_event(
+            &mut l,
+            &event("storage.message.history", req.encode_to_vec()),
+        )
+        .await
+        .unwrap();
+        let history = MessageHistoryResponse::decode(&resp.data[..]).unwrap();
+        let ids: Vec<_> = history
+            .messages
+            .iter()
+            .map(|m| m.message_id.as_str())
+            .collect();
+        assert_eq!(ids, ["h3", "h4"]);
+    }
+
+    #[tokio::test]
+    async fn test_room_members_update_in_bulk() {
+        let mut l = listener("room-bulk");
+        json_call(
+            &mut l,
+            "storage.room.add_member",
+            serde_json::json!({"room_id": "r1", "uid": "a"}),
+        )
+        .await;
+        let resp = json_call(
+            &mut l,
+            ROOM_UPDATE_MEMBERS_EVENT,
+            serde_json::json!({"room_id": "r1", "add": ["c", "b", "d"], "remove": ["a", "d"]}),
+        )
+        .await;
+        assert_eq!(resp["members"], serde_json::json!(["b", "c"]));
+
+        let req = GetRoomMembersRequest {
+            room_id: "r1".to_string(),
+        };
+        let resp = dispatch_storage_event(
+            &mut l,
+            &event("storage.room.list_members", req.encode_to_vec()),
+        )
+        .await
+        .unwrap();
+        let mut members = GetRoomMembersResponse::decode(&resp.data[..])
+            .unwrap()
+            .members;
+        members.sort();
+        assert_eq!(members, ["b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn test_reactions_aggregate_per_emoji() {
+        let mut l = listener("reactions");
+        let react = |uid: &str, emoji: &str| serde_json::json!({"message_id": "m1", "uid": uid, "emoji": emoji});
+        for (uid, emoji) in [("b", "👍"), ("a", "👍"), ("acme:c", ":tada:")] {
+            let resp = json_call(&mut l, REACTION_ADD_EVENT, react(uid, emoji)).await;
+            assert_eq!(resp["changed"], true);
+        }
+        let resp = json_call(&mut l, REACTION_ADD_EVENT, react("a", "👍")).await;
+        assert_eq!(resp["changed"], false);
+        json_call(
+            &mut l,
+            REACTION_ADD_EVENT,
+            serde_json::json!({"message_id": "m10", "uid": "a", "emoji": "👍"}),
+        )
+        .await;
+
+        let list = json_call(
+            &mut l,
+            REACTION_LIST_EVENT,
+            serde_json::json!({"message_id": "m1"}),
+        )
+        .await;
+        assert_eq!(
+            list["reactions"],
+            serde_json::json!([
+                {"emoji": ":tada:", "count": 1, "uids": ["acme:c"]},
+                {"emoji": "👍", "count": 2, "uids": ["a", "b"]},
+            ])
+        );
+
+        let resp = json_call(&mut l, REACTION_REMOVE_EVENT, react("b", "👍")).await;
+        assert_eq!(resp["changed"], true);
+        let resp = json_call(&mut l, REACTION_REMOVE_EVENT, react("b", "👍")).await;
+        assert_eq!(resp["changed"], false);
+        let req = ListReactionsRequest {
+            message_id: "m1".to_string(),
+        };
+        let resp = dispatch_storage_event(&mut l, &event(REACTION_LIST_EVENT, req.encode_to_vec()))
+            .await
+            .unwrap();
+        let list = ListReactionsResponse::decode(&resp.data[..]).unwrap();
+        let counts: Vec<_> = list
+            .reactions
+            .iter()
+            .map(|r| (r.emoji.as_str(), r.count))
+            .collect();
+        assert_eq!(counts, [(":tada:", 1), ("👍", 1)]);
+    }
+
+    #[tokio::test]
+    async fn test_pins_are_capped_per_room() {
+        let mut l = listener("pins");
+        let pin = |room: &str, message_id: &str, at: i64| {
+            serde_json::json!({
+                "room_id": room,
+                "message_id": message_id,
+                "pinned_by": "admin",
+                "pinned_at": at,
+                "max_pins": 2,
+            })
+        };
+        for (message_id, at) in [("m2", 20), ("m1", 10)] {
+            let resp = json_call(&mut l, PIN_ADD_EVENT, pin("r1", message_id, at)).await;
+            assert_eq!(
+                (resp["changed"].as_bool(), resp["full"].as_bool()),
+                (Some(true), Some(false))
+            );
+        }
+        let resp = json_call(&mut l, PIN_ADD_EVENT, pin("r1", "m1", 30)).await;
+        assert_eq!(resp["changed"], false);
+        let resp = json_call(&mut l, PIN_ADD_EVENT, pin("r1", "m3", 30)).await;
+        assert_eq!(
+            (resp["changed"].as_bool(), resp["full"].as_bool()),
+            (Some(false), Some(true))
+        );
+        // 上限按房间计 / The cap is per room
+        let resp = json_call(&mut l, PIN_ADD_EVENT, pin("r10", "m3", 30)).await;
+        assert_eq!(resp["changed"], true);
+
+        let list = json_call(&mut l, PIN_LIST_EVENT, serde_json::json!({"room_id": "r1"})).await;
+        assert_eq!(
+            list["pins"],
+            serde_json::json!([
+                {"message_id": "m1", "pinned_by": "admin", "pinned_at": 10},
+                {"message_id": "m2", "pinned_by": "admin", "pinned_at": 20},
+            ])
+        );
+
+        let unpin = serde_json::json!({"room_id": "r1", "message_id": "m1"});
+        let resp = json_call(&mut l, PIN_REMOVE_EVENT, unpin.clone()).await;
+        assert_eq!(resp["changed"], true);
+        let resp = json_call(&mut l, PIN_REMOVE_EVENT, unpin).await;
+        assert_eq!(resp["changed"], false);
+        let resp = json_call(&mut l, PIN_ADD_EVENT, pin("r1", "m3", 30)).await;
+        assert_eq!(resp["changed"], true);
+    }
+
+    #[tokio::test]
+    async fn test_group_acks_persist_per_recipient() {
+        let mut l = listener("group_acks");
+        json_call(
+            &mut l,
+            GROUP_ACK_TRACK_EVENT,
+            serde_json::json!({
+                "message_id": "m1",
+                "room_id": "r1",
+                "sender_uid": "a",
+                "recipients": ["c", "b", "c"],
+            }),
+        )
+        .await;
+        let ack = |uid: &str, at: i64| serde_json::json!({"message_id": "m1", "uid": uid, "acked_at": at});
+        let resp = json_call(&mut l, GROUP_ACK_ADD_EVENT, ack("b", 10)).await;
+        assert_eq!(resp["recorded"], true);
+        // 非接收者与未登记的消息不记录 / Non-recipients and unknown messages are not recorded
+        let resp = json_call(&mut l, GROUP_ACK_ADD_EVENT, ack("a", 10)).await;
+        assert_eq!(resp["recorded"], false);
+        let resp = json_call(
+            &mut l,
+            GROUP_ACK_ADD_EVENT,
+            serde_json::json!({"message_id": "m2", "uid": "b", "acked_at": 10}),
+        )
+        .await;
+        assert_eq!(resp["recorded"], false);
+
+        let status = json_call(
+            &mut l,
+            GROUP_ACK_STATUS_EVENT,
+            serde_json::json!({"message_id": "m1"}),
+        )
+        .await;
+        assert_eq!(status["found"], true);
+        assert_eq!(status["sender_uid"], "a");
+        assert_eq!(status["acked"], serde_json::json!(["b"]));
+        assert_eq!(status["pending"], serde_json::json!(["c"]));
+        let missing = json_call(
+            &mut l,
+            GROUP_ACK_STATUS_EVENT,
+            serde_json::json!({"message_id": "m2"}),
+        )
+        .await;
+        assert_eq!(missing["found"], false);
+    }
+
+    #[tokio::test]
+    async fn test_message_search_ranks_and_paginates() {
+        let mut l = listener("search");
+        for (ts, id, from, to, text) in [
+            (1000, "s1", "a", "b", "Lunch at noon?"),
+            (1001, "s2", "b", "a", "lunch lunch, then the lunch meeting"),
+            (1002, "s3", "c", "d", "lunch for c and d only"),
+            (1003, "s4", "a", "c", "LUNCH tomorrow"),
+            (1004, "s5", "a", "b", "dinner"),
+        ] {
+            json_call(
+                &mut l,
+                "storage.message.save",
+                serde_json::json!({
+                    "message_id": id, "from_uid": from, "to_uid": to,
+                    "content": {"text": text}, "timestamp": ts, "msg_type": "text",
+                }),
+            )
+            .await;
+        }
+
+        let page = json_call(
+            &mut l,
+            "storage.message.search",
+            serde_json::json!({"uid": "a", "query": "lunch", "limit": 2}),
+        )
+        .await;
+        let ids = |page: &serde_json::Value| -> Vec<String> {
+            page["hits"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|h| h["message_id"].as_str().unwrap().to_string())
+                .collect()
+        };
+        assert_eq!(page["total"], 3);
+        assert_eq!(ids(&page), ["s2", "s4"]);
+        assert_eq!(page["hits"][0]["score"], 3.0);
+        assert_eq!(
+            page["hits"][0]["content"]["text"],
+            "lunch lunch, then the lunch meeting"
+        );
+
+        let next = json_call(
+            &mut l,
+            "storage.message.search",
+            serde_json::json!({
+                "uid": "a", "query": "lunch", "limit": 2, "cursor": page["next_cursor"],
+            }),
+        )
+        .await;
+        assert_eq!(ids(&next), ["s1"]);
+        assert_eq!(next["next_cursor"], "");
+
+        // 所有关键词都须命中 / Every term must match
+        let both = json_call(
+            &mut l,
+            "storage.message.search",
+            serde_json::json!({"uid": "a", "query": "lunch NOON"}),
+        )
+        .await;
+        assert_eq!(ids(&both), ["s1"]);
+    }
+
+    #[tokio::test]
+    async fn test_archive_and_restore_round_trip() {
+        let mut l = listener("archive");
+        let store = Arc::new(crate::archive::MemoryStore::default());
+        l.config.archive = Some(ArchiveConfig {
+            endpoint: "http://127.0.0.1:9000".to_string(),
+            bucket: "im".to_string(),
+            region: "us-east-1".to_string(),
+            access_key: "ak".to_string(),
+            secret_key: "sk".to_string(),
+            prefix: "archive/".to_string(),
+        });
+        l.archive_store = Some(store.clone());
+        for (ts, id) in [(1000, "a1"), (2000, "a2"), (3000, "a3")] {
+            json_call(
+                &mut l,
+                "storage.message.save",
+                serde_json::json!({
+                    "message_id": id, "from_uid": "a", "to_uid": "b",
+                    "content": {"text": id}, "timestamp": ts, "msg_type": "text",
+                }),
+            )
+            .await;
+        }
+
+        let archived = json_call(
+            &mut l,
+            MESSAGE_ARCHIVE_EVENT,
+            serde_json::json!({"since_ts": 1000, "until_ts": 3000}),
+        )
+        .await;
+        assert_eq!(archived["count"], 2);
+        assert_eq!(archived["archive_key"], "archive/messages-1000-3000.ndjson");
+        let object = store.objects.lock().unwrap()["archive/messages-1000-3000.ndjson"].clone();
+        assert_eq!(
+            object
+                .split(|b| *b == b'\n')
+                .filter(|l| !l.is_empty())
+                .count(),
+            2
+        );
+
+        // 模拟压缩后恢复 / Restore after a simulated compaction
+        l.wal.clear().unwrap();
+        let restore = serde_json::json!({"archive_key": archived["archive_key"]});
+        let restored = json_call(&mut l, MESSAGE_RESTORE_EVENT, restore.clone()).await;
+        assert_eq!(restored["count"], 2);
+        let again = json_call(&mut l, MESSAGE_RESTORE_EVENT, restore).await;
+        assert_eq!(again["count"], 0);
+
+        let history = json_call(
+            &mut l,
+            "storage.message.history",
+            serde_json::json!({"uid": "a", "limit": 10}),
+        )
+        .await;
+        let ids: Vec<_> = history["messages"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|m| m["message_id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, ["a1", "a2"]);
+
+        let bad_range = dispatch_storage_event(
+            &mut l,
+            &event(
+                MESSAGE_ARCHIVE_EVENT,
+                serde_json::json!({"since_ts": 5, "until_ts": 5})
+                    .to_string()
+                    .into_bytes(),
+            ),
+        )
+        .await;
+        assert!(bad_range.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_purge_only_touches_the_room_before_cutoff() {
+        let mut l = listener("purge");
+        let store = Arc::new(crate::archive::MemoryStore::default());
+        l.config.archive = Some(ArchiveConfig {
+            endpoint: "http://127.0.0.1:9000".to_string(),
+            bucket: "im".to_string(),
+            region: "us-east-1".to_string(),
+            access_key: "ak".to_string(),
+            secret_key: "sk".to_string(),
+            prefix: "archive/".to_string(),
+        });
+        for (ts, id, room) in [(1000, "p1", "r1"), (1000, "p2", "r2"), (3000, "p3", "r1")] {
+            json_call(
+                &mut l,
+                "storage.message.save",
+                serde_json::json!({
+                    "message_id": id, "from_uid": "a", "to_uid": room,
+                    "content": {"text": id, "room_id": room}, "timestamp": ts, "msg_type": "group_message",
+                }),
+            )
+            .await;
+        }
+        let purge = serde_json::json!({"room_id": "r1", "until_ts": 2000, "archive": true});
+
+        // 未配置归档存储时不删除 / Nothing is deleted while the archive store is missing
+        let refused = dispatch_storage_event(
+            &mut l,
+            &event(MESSAGE_PURGE_EVENT, purge.to_string().into_bytes()),
+        )
+        .await;
+        assert!(refused.is_err());
+
+        l.archive_store = Some(store.clone());
+        let purged = json_call(&mut l, MESSAGE_PURGE_EVENT, purge).await;
+        assert_eq!(purged["deleted"], 1);
+        assert_eq!(
+            purged["archive_key"],
+            "archive/rooms/r1/messages-until-2000.ndjson"
+        );
+        assert!(store
+            .objects
+            .lock()
+            .unwrap()
+            .contains_key("archive/rooms/r1/messages-until-2000.ndjson"));
+
+        for (id, found) in [("p1", false), ("p2", true), ("p3", true)] {
+            let got = json_call(
+                &mut l,
+                MESSAGE_GET_EVENT,
+                serde_json::json!({"message_id": id}),
+            )
+            .await;
+            assert_eq!(got["found"], found, "{}", id);
+        }
+    }
+}
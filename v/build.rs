@@ -14,6 +14,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             "proto/storage/storage.proto", // 存储插件协议
             "proto/auth/auth.proto",       // 认证插件协议
             "proto/gateway/gateway.proto", // 网关插件协议
+            "proto/im/im.proto",           // 客户端消息协议
         ];
 
         prost_build::Config::new()
@@ -0,0 +1,108 @@
+//! 单调时间戳 / Monotonic timestamps
+//!
+//! 存储键中嵌入的毫秒时间戳必须单调递增：系统时钟被 NTP 向回校正时，按墙上时钟生成的键会排到
+//! 旧记录之前，破坏分页游标与“最近”排序。不变式：`MonotonicClock::issue` 返回
+//! `max(墙上时钟, 上次签发 + 1)`，同一时钟签发的值严格递增；它只用于键，记录体中仍保存真实的
+//! 墙上时钟时间。突发写入超过每毫秒一条时，键时间会暂时领先墙上时钟，写入放缓后追平。
+//! Millisecond timestamps embedded in storage keys must be monotonic: when NTP steps the system
+//! clock backward, keys built from the wall clock sort before older records and break pagination
+//! cursors and "recent" ordering. Invariant: `MonotonicClock::issue` returns
+//! `max(wall clock, last issued + 1)`, so values issued by one clock strictly increase. It is used
+//! for keys only; record bodies keep the real wall-clock time. Bursts above one write per
+//! millisecond run key times ahead of the wall clock until writes slow down and it catches up.
+
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 当前墙上时钟（Unix 毫秒）/ Current wall-clock time in Unix milliseconds
+pub fn wall_clock_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// 永不回退的毫秒时间戳源 / Millisecond timestamp source that never goes backward
+#[derive(Debug, Default)]
+pub struct MonotonicClock {
+    last: AtomicI64,
+}
+
+impl MonotonicClock {
+    pub const fn new() -> Self {
+        Self {
+            last: AtomicI64::new(0),
+        }
+    }
+
+    /// 从已签发的时间戳之后继续，例如重启时取库中最新的键
+    /// Continue after an already issued timestamp, e.g. the newest key in a database on restart
+    pub fn resume_after(&self, issued_ms: i64) {
+        self.last.fetch_max(issued_ms, Ordering::SeqCst);
+    }
+
+    /// 为墙上时钟时间 `wall_ms` 签发键时间戳 / Issue a key timestamp for wall-clock time `wall_ms`
+    pub fn issue(&self, wall_ms: i64) -> i64 {
+        let next = |last: i64| wall_ms.max(last.saturating_add(1));
+        let prev = self
+            .last
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| Some(next(last)))
+            .unwrap_or_else(|last| last);
+        next(prev)
+    }
+
+    /// 按当前墙上时钟签发 / Issue for the current wall-clock time
+    pub fn now(&self) -> i64 {
+        self.issue(wall_clock_ms())
+    }
+
+    /// 最近签发的时间戳，尚未签发时为 0 / The last issued timestamp, 0 before any
+    pub fn last_issued(&self) -> i64 {
+        self.last.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_backward_clock_jump_keeps_keys_monotonic() {
+        let clock = MonotonicClock::new();
+        assert_eq!(clock.issue(1_000), 1_000);
+        assert_eq!(clock.issue(1_005), 1_005);
+        // NTP 把时钟拨回 100ms / NTP steps the clock back by 100ms
+        assert_eq!(clock.issue(905), 1_006);
+        assert_eq!(clock.issue(906), 1_007);
+        // 同一毫秒内仍严格递增 / Still strictly increasing within one millisecond
+        assert_eq!(clock.issue(1_007), 1_008);
+        // 墙上时钟追上后恢复跟随 / Follows the wall clock again once it catches up
+        assert_eq!(clock.issue(2_000), 2_000);
+        assert_eq!(clock.last_issued(), 2_000);
+
+        let restarted = MonotonicClock::new();
+        restarted.resume_after(2_000);
+        restarted.resume_after(1_500);
+        assert_eq!(restarted.issue(1_900), 2_001);
+    }
+
+    #[test]
+    fn test_concurrent_issues_are_unique() {
+        let clock = Arc::new(MonotonicClock::new());
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let clock = clock.clone();
+                std::thread::spawn(move || (0..1_000).map(|_| clock.issue(42)).collect::<Vec<_>>())
+            })
+            .collect();
+        let mut all: Vec<i64> = handles
+            .into_iter()
+            .flat_map(|h| h.join().unwrap())
+            .collect();
+        all.sort_unstable();
+        all.dedup();
+        assert_eq!(all.len(), 4_000);
+        assert!(clock.now() > 4_042);
+    }
+}
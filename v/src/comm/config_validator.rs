@@ -0,0 +1,309 @@
+//! 声明式配置校验：一次性列出全部问题
+//! Declarative config validation: reports every problem at once
+
+use crate::comm::config::ConfigManager;
+use serde_json::Value;
+use std::fmt;
+
+/// 配置值类型 / Config value type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    String,
+    Integer,
+    Float,
+    Bool,
+    Array,
+    Table,
+    /// 合法的绝对 URL / A valid absolute URL
+    Url,
+}
+
+/// 单个配置问题 / A single config issue
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigIssue {
+    pub key: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+/// 字段规则 / Field rule
+#[derive(Debug, Clone)]
+pub struct FieldRule {
+    key: String,
+    required: bool,
+    required_if: Option<(String, Value)>,
+    ty: Option<ValueType>,
+    range: Option<(f64, f64)>,
+    one_of: Vec<String>,
+}
+
+impl FieldRule {
+    /// 必填字段 / Required field
+    pub fn required(key: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            required: true,
+            required_if: None,
+            ty: None,
+            range: None,
+            one_of: Vec::new(),
+        }
+    }
+
+    /// 可选字段（存在时才校验） / Optional field (validated only when present)
+    pub fn optional(key: &str) -> Self {
+        Self {
+            required: false,
+            ..Self::required(key)
+        }
+    }
+
+    /// 当 `other` 等于 `value` 时必填 / Required when `other` equals `value`
+    pub fn required_when(mut self, other: &str, value: impl Into<Value>) -> Self {
+        self.required_if = Some((other.to_string(), value.into()));
+        self
+    }
+
+    /// 期望类型 / Expected type
+    pub fn of_type(mut self, ty: ValueType) -> Self {
+        self.ty = Some(ty);
+        self
+    }
+
+    /// 数值闭区间 / Inclusive numeric range
+    pub fn range(mut self, min: f64, max: f64) -> Self {
+        self.range = Some((min, max));
+        self
+    }
+
+    /// 枚举取值 / Allowed values
+    pub fn one_of(mut self, values: &[&str]) -> Self {
+        self.one_of = values.iter().map(|v| v.to_string()).collect();
+        self
+    }
+}
+
+/// 配置模式 / Config schema
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    fields: Vec<FieldRule>,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 追加字段规则 / Add a field rule
+    pub fn field(mut self, rule: FieldRule) -> Self {
+        self.fields.push(rule);
+        self
+    }
+
+    /// 追加若干必填键 / Add plain required keys
+    pub fn required_keys(mut self, keys: &[&str]) -> Self {
+        self.fields
+            .extend(keys.iter().map(|k| FieldRule::required(k)));
+        self
+    }
+}
+
+/// 环境变量来源的值为字符串，按需宽松解析
+/// Env-sourced values are strings, so parse leniently
+fn as_f64(v: &Value) -> Option<f64> {
+    match v {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn as_bool(v: &Value) -> Option<bool> {
+    match v {
+        Value::Bool(b) => Some(*b),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn as_text(v: &Value) -> String {
+    match v {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn type_matches(ty: ValueType, v: &Value) -> bool {
+    match ty {
+        ValueType::String => v.is_string(),
+        ValueType::Integer => match v {
+            Value::Number(n) => n.is_i64() || n.is_u64(),
+            Value::String(s) => s.trim().parse::<i64>().is_ok(),
+            _ => false,
+        },
+        ValueType::Float => as_f64(v).is_some(),
+        ValueType::Bool => as_bool(v).is_some(),
+        ValueType::Array => v.is_array(),
+        ValueType::Table => v.is_object(),
+        ValueType::Url => v
+            .as_str()
+            .map(|s| reqwest::Url::parse(s).is_ok())
+            .unwrap_or(false),
+    }
+}
+
+/// 条件是否成立（布尔按宽松比较） / Whether a condition holds (booleans compared leniently)
+fn condition_holds(config: &ConfigManager, key: &str, expected: &Value) -> bool {
+    match config.get::<Value>(key) {
+        Ok(actual) => match expected {
+            Value::Bool(b) => as_bool(&actual) == Some(*b),
+            other => as_text(&actual) == as_text(other),
+        },
+        Err(_) => false,
+    }
+}
+
+/// 按模式校验整个配置，汇总所有问题 / Validate the whole config, aggregating all issues
+pub fn validate_schema(config: &ConfigManager, schema: &Schema) -> Result<(), Vec<ConfigIssue>> {
+    let mut issues = Vec::new();
+    for rule in &schema.fields {
+        let mut issue = |message: String| {
+            issues.push(ConfigIssue {
+                key: rule.key.clone(),
+                message,
+            })
+        };
+        let required = rule.required
+            || rule
+                .required_if
+                .as_ref()
+                .map(|(k, v)| condition_holds(config, k, v))
+                .unwrap_or(false);
+        let value = match config.get::<Value>(&rule.key) {
+            Ok(v) if !v.is_null() => v,
+            _ => {
+                if required {
+                    issue("缺少必填配置 / missing required key".to_string());
+                }
+                continue;
+            }
+        };
+        if let Some(ty) = rule.ty {
+            if !type_matches(ty, &value) {
+                issue(format!(
+                    "类型应为 {:?}，实际为 {} / expected {:?}",
+                    ty, value, ty
+                ));
+                continue;
+            }
+        }
+        if let Some((min, max)) = rule.range {
+            match as_f64(&value) {
+                Some(n) if n >= min && n <= max => {}
+                _ => issue(format!(
+                    "取值 {} 超出范围 {}..={} / out of range",
+                    value, min, max
+                )),
+            }
+        }
+        if !rule.one_of.is_empty() {
+            let text = as_text(&value);
+            if !rule.one_of.iter().any(|v| v == &text) {
+                issue(format!(
+                    "取值 {} 不在 {:?} 中 / not one of the allowed values",
+                    text, rule.one_of
+                ));
+            }
+        }
+    }
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(issues)
+    }
+}
+
+/// 校验必填键（基于 `validate_schema`，一次返回全部缺失项）
+/// Validate required keys (built on `validate_schema`, returns every missing key)
+pub fn validate_required_config(
+    config: &ConfigManager,
+    required_keys: &[&str],
+) -> Result<(), Vec<ConfigIssue>> {
+    validate_schema(config, &Schema::new().required_keys(required_keys))
+}
+
+/// 将问题列表格式化为一条可读错误 / Format issues into one readable message
+pub fn format_issues(issues: &[ConfigIssue]) -> String {
+    issues
+        .iter()
+        .map(|i| format!("  - {}", i))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::comm::config::ConfigSource;
+    use config::FileFormat;
+
+    fn manager(toml: &str) -> ConfigManager {
+        ConfigManager::with_sources(vec![ConfigSource::String {
+            content: toml.to_string(),
+            format: FileFormat::Toml,
+        }])
+        .unwrap()
+    }
+
+    fn schema() -> Schema {
+        Schema::new()
+            .field(
+                FieldRule::required("server.http_port")
+                    .of_type(ValueType::Integer)
+                    .range(1.0, 65535.0),
+            )
+            .field(
+                FieldRule::optional("auth.center_url")
+                    .required_when("auth.enabled", true)
+                    .of_type(ValueType::Url),
+            )
+            .field(FieldRule::optional("logging.level").one_of(&["debug", "info", "warn", "error"]))
+    }
+
+    #[test]
+    fn test_valid_config_passes() {
+        let cm = manager(
+            "[server]\nhttp_port = 8080\n[auth]\nenabled = true\ncenter_url = \"http://127.0.0.1:8090\"\n[logging]\nlevel = \"info\"\n",
+        );
+        assert!(validate_schema(&cm, &schema()).is_ok());
+    }
+
+    #[test]
+    fn test_collects_all_issues() {
+        let cm = manager(
+            "[server]\nhttp_port = 70000\n[auth]\nenabled = true\ncenter_url = \"not a url\"\n[logging]\nlevel = \"loud\"\n",
+        );
+        let issues = validate_schema(&cm, &schema()).unwrap_err();
+        let keys: Vec<&str> = issues.iter().map(|i| i.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            vec!["server.http_port", "auth.center_url", "logging.level"]
+        );
+    }
+
+    #[test]
+    fn test_conditional_required_and_required_keys() {
+        let cm = manager("[auth]\nenabled = false\n");
+        let issues = validate_schema(&cm, &schema()).unwrap_err();
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].key, "server.http_port");
+
+        let issues = validate_required_config(&cm, &["a.b", "auth.enabled", "c"]).unwrap_err();
+        assert_eq!(issues.len(), 2);
+    }
+}
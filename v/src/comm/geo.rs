@@ -221,3 +221,165 @@ pub async fn get_region_by_ip(ip: Option<&str>) -> Result<RegionInfo, GeoError>
 
     Ok(region)
 }
+
+// 地球平均半径（米）/ Mean Earth radius in meters
+pub const EARTH_RADIUS_M: f64 = 6_371_008.8;
+
+// 纬度每度的最短距离（米，赤道处）/ Shortest distance per degree of latitude in meters (at the equator)
+const METERS_PER_DEG_LAT: f64 = 110_574.0;
+// 赤道处经度每度的距离（米）/ Distance per degree of longitude at the equator in meters
+const METERS_PER_DEG_LNG: f64 = 111_320.0;
+
+const GEOHASH_BASE32: &[u8; 32] = b"0123456789bcdefghjkmnpqrstuvwxyz";
+
+/// geohash 最大精度 / Max geohash precision
+pub const GEOHASH_MAX_PRECISION: usize = 12;
+
+/// 两点间的大圆距离（米）/ Great-circle distance between two points in meters
+pub fn haversine_m(lat1: f64, lng1: f64, lat2: f64, lng2: f64) -> f64 {
+    let (phi1, phi2) = (lat1.to_radians(), lat2.to_radians());
+    let d_phi = (lat2 - lat1).to_radians();
+    let d_lambda = (lng2 - lng1).to_radians();
+    let a = (d_phi / 2.0).sin().powi(2) + phi1.cos() * phi2.cos() * (d_lambda / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * a.sqrt().min(1.0).asin()
+}
+
+/// 编码 geohash，精度为 1..=12 个字符 / Encode a geohash of 1..=12 characters
+pub fn geohash_encode(lat: f64, lng: f64, precision: usize) -> String {
+    let precision = precision.clamp(1, GEOHASH_MAX_PRECISION);
+    let (mut lat_range, mut lng_range) = ((-90.0, 90.0), (-180.0, 180.0));
+    let mut hash = String::with_capacity(precision);
+    let (mut bits, mut ch, mut even) = (0, 0usize, true);
+    while hash.len() < precision {
+        let (range, value) = if even {
+            (&mut lng_range, lng)
+        } else {
+            (&mut lat_range, lat)
+        };
+        let mid = (range.0 + range.1) / 2.0;
+        ch <<= 1;
+        if value >= mid {
+            ch |= 1;
+            range.0 = mid;
+        } else {
+            range.1 = mid;
+        }
+        even = !even;
+        bits += 1;
+        if bits == 5 {
+            hash.push(GEOHASH_BASE32[ch] as char);
+            bits = 0;
+            ch = 0;
+        }
+    }
+    hash
+}
+
+/// geohash 单元的范围 `(min_lat, max_lat, min_lng, max_lng)`，含非法字符时为 None
+/// Bounds of a geohash cell as `(min_lat, max_lat, min_lng, max_lng)`; None for invalid characters
+pub fn geohash_bounds(hash: &str) -> Option<(f64, f64, f64, f64)> {
+    let (mut lat_range, mut lng_range) = ((-90.0_f64, 90.0_f64), (-180.0_f64, 180.0_f64));
+    let mut even = true;
+    for c in hash.bytes() {
+        let idx = GEOHASH_BASE32.iter().position(|b| *b == c)?;
+        for shift in (0..5).rev() {
+            let range = if even { &mut lng_range } else { &mut lat_range };
+            let mid = (range.0 + range.1) / 2.0;
+            if (idx >> shift) & 1 == 1 {
+                range.0 = mid;
+            } else {
+                range.1 = mid;
+            }
+            even = !even;
+        }
+    }
+    Some((lat_range.0, lat_range.1, lng_range.0, lng_range.1))
+}
+
+/// 周围 8 个同精度单元（两极处更少）/ The 8 surrounding cells of the same precision (fewer at the poles)
+pub fn geohash_neighbors(hash: &str) -> Vec<String> {
+    let Some((min_lat, max_lat, min_lng, max_lng)) = geohash_bounds(hash) else {
+        return Vec::new();
+    };
+    let (height, width) = (max_lat - min_lat, max_lng - min_lng);
+    let (lat, lng) = ((min_lat + max_lat) / 2.0, (min_lng + max_lng) / 2.0);
+    let mut out = Vec::with_capacity(8);
+    for dy in [-1.0, 0.0, 1.0] {
+        for dx in [-1.0, 0.0, 1.0] {
+            let n_lat = lat + dy * height;
+            if (dy == 0.0 && dx == 0.0) || !(-90.0..=90.0).contains(&n_lat) {
+                continue;
+            }
+            // 经度跨越 ±180° 时回绕 / Longitude wraps around ±180°
+            let n_lng = (lng + dx * width + 540.0).rem_euclid(360.0) - 180.0;
+            let neighbor = geohash_encode(n_lat, n_lng, hash.len());
+            if neighbor != hash && !out.contains(&neighbor) {
+                out.push(neighbor);
+            }
+        }
+    }
+    out
+}
+
+/// 单元宽高都不小于 `radius_m` 的最大精度，以 `lat` 处的经度宽度计；0 表示没有合适精度（如靠近两极）
+/// The largest precision whose cell is at least `radius_m` tall and wide, measuring width at `lat`;
+/// 0 when none fits (e.g. near the poles)
+///
+/// 以该精度的中心单元加 8 个邻居即可覆盖半径内的所有点
+/// The center cell plus its 8 neighbors at this precision cover every point within the radius
+pub fn geohash_precision_for_radius(radius_m: f64, lat: f64) -> usize {
+    let lng_scale = lat.to_radians().cos().abs();
+    (1..=GEOHASH_MAX_PRECISION)
+        .rev()
+        .find(|&precision| {
+            let bits = 5 * precision as i32;
+            let (lng_bits, lat_bits) = ((bits + 1) / 2, bits / 2);
+            let height = 180.0 / 2f64.powi(lat_bits) * METERS_PER_DEG_LAT;
+            let width = 360.0 / 2f64.powi(lng_bits) * METERS_PER_DEG_LNG * lng_scale;
+            height >= radius_m && width >= radius_m
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_geohash_round_trip() {
+        assert_eq!(geohash_encode(57.64911, 10.40744, 11), "u4pruydqqvj");
+        let (min_lat, max_lat, min_lng, max_lng) = geohash_bounds("u4pruydqqvj").unwrap();
+        assert!((min_lat..=max_lat).contains(&57.64911));
+        assert!((min_lng..=max_lng).contains(&10.40744));
+        assert!(geohash_bounds("u4a").is_none());
+    }
+
+    #[test]
+    fn test_neighbors_surround_the_cell() {
+        let hash = geohash_encode(31.2304, 121.4737, 6);
+        let neighbors = geohash_neighbors(&hash);
+        assert_eq!(neighbors.len(), 8);
+        let (min_lat, max_lat, _, max_lng) = geohash_bounds(&hash).unwrap();
+        let east = geohash_encode((min_lat + max_lat) / 2.0, max_lng + 1e-6, 6);
+        assert!(neighbors.contains(&east));
+
+        // 跨越日期变更线 / Across the antimeridian
+        let west_edge = geohash_encode(0.0, -179.9999, 4);
+        let across = geohash_encode(0.0, 179.9999, 4);
+        assert!(geohash_neighbors(&west_edge).contains(&across));
+    }
+
+    #[test]
+    fn test_haversine_and_precision() {
+        let paris_london = haversine_m(48.8566, 2.3522, 51.5074, -0.1278);
+        assert!((paris_london - 343_500.0).abs() < 1_000.0);
+        assert_eq!(haversine_m(10.0, 10.0, 10.0, 10.0), 0.0);
+
+        // 5 位单元约 4.9km x 4.9km，6 位约 1.2km x 0.6km
+        // Precision 5 cells are about 4.9km x 4.9km, precision 6 about 1.2km x 0.6km
+        assert_eq!(geohash_precision_for_radius(500.0, 0.0), 6);
+        assert_eq!(geohash_precision_for_radius(1_000.0, 0.0), 5);
+        assert_eq!(geohash_precision_for_radius(5_000.0, 0.0), 4);
+        assert_eq!(geohash_precision_for_radius(1_000.0, 89.999), 0);
+    }
+}
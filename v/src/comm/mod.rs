@@ -1,4 +1,8 @@
+pub mod clock;
 pub mod config;
+pub mod config_validator;
 pub mod generator;
 pub mod geo;
+pub mod path;
+pub mod port;
 pub mod tracing;
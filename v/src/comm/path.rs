@@ -0,0 +1,89 @@
+use std::path::{Path, PathBuf};
+
+// 路径工具：统一处理 ~ 展开与相对配置文件的路径解析
+// Path helpers: consistent `~` expansion and config-relative resolution
+
+// 用户主目录（读取 HOME，Windows 回退 USERPROFILE）
+// User home directory (HOME, falling back to USERPROFILE on Windows)
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .filter(|h| !h.is_empty())
+        .map(PathBuf::from)
+}
+
+// 展开开头的 `~` 或 `~/`；无法确定主目录时原样返回
+// Expand a leading `~` or `~/`; returns the input unchanged when home is unknown
+pub fn expand_home(p: impl AsRef<Path>) -> PathBuf {
+    let p = p.as_ref();
+    let Some(s) = p.to_str() else {
+        return p.to_path_buf();
+    };
+    let rest = if s == "~" {
+        ""
+    } else if let Some(rest) = s.strip_prefix("~/") {
+        rest
+    } else {
+        return p.to_path_buf();
+    };
+    match home_dir() {
+        Some(home) if rest.is_empty() => home,
+        Some(home) => home.join(rest),
+        None => p.to_path_buf(),
+    }
+}
+
+// 相对路径以配置文件所在目录为基准解析（先展开 `~`，绝对路径原样返回）
+// Resolve relative paths against the config file's directory (expands `~` first; absolute paths pass through)
+pub fn resolve_relative_to_config(p: impl AsRef<Path>, config_path: impl AsRef<Path>) -> PathBuf {
+    let expanded = expand_home(p);
+    if expanded.is_absolute() {
+        return expanded;
+    }
+    let base = config_path
+        .as_ref()
+        .parent()
+        .filter(|d| !d.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    base.join(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_home() {
+        let home = home_dir().expect("HOME must be set for tests");
+        assert_eq!(expand_home("~"), home);
+        assert_eq!(expand_home("~/vp/sockets"), home.join("vp/sockets"));
+        // 仅展开开头的 `~/` / Only a leading `~/` is expanded
+        assert_eq!(expand_home("~user/x"), PathBuf::from("~user/x"));
+        assert_eq!(expand_home("a/~/b"), PathBuf::from("a/~/b"));
+    }
+
+    #[test]
+    fn test_resolve_absolute_passthrough() {
+        assert_eq!(
+            resolve_relative_to_config("/var/run/im.sock", "config/default.toml"),
+            PathBuf::from("/var/run/im.sock")
+        );
+    }
+
+    #[test]
+    fn test_resolve_relative_and_home() {
+        assert_eq!(
+            resolve_relative_to_config("./plugins", "/etc/im/default.toml"),
+            PathBuf::from("/etc/im/./plugins")
+        );
+        assert_eq!(
+            resolve_relative_to_config("data", "default.toml"),
+            PathBuf::from("./data")
+        );
+        let home = home_dir().unwrap();
+        assert_eq!(
+            resolve_relative_to_config("~/vp", "/etc/im/default.toml"),
+            home.join("vp")
+        );
+    }
+}
@@ -0,0 +1,68 @@
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener};
+
+// 端口工具：用于测试服务器与插件自动分配端口
+// Port helpers: ephemeral port selection for test servers and plugins
+
+// 在 [start, end] 区间内查找可用端口（逐个尝试绑定）
+// Find a free port within [start, end] (tries binding each in turn)
+//
+// 注意：返回后监听已释放，调用方绑定前可能被占用；需要无竞争时请用 `reserve_free_port_in_range`
+// Note: the listener is released on return, so the port may be taken before the caller binds;
+// use `reserve_free_port_in_range` when that race matters
+pub fn find_free_port_in_range(start: u16, end: u16) -> Option<u16> {
+    reserve_free_port_in_range(start, end).map(|(port, _listener)| port)
+}
+
+// 在区间内绑定首个可用端口，并返回端口与持有中的监听器
+// Bind the first free port in range and hand back the port with the held listener
+pub fn reserve_free_port_in_range(start: u16, end: u16) -> Option<(u16, TcpListener)> {
+    if start == 0 || start > end {
+        return None;
+    }
+    (start..=end).find_map(|port| {
+        TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, port)))
+            .ok()
+            .map(|l| (port, l))
+    })
+}
+
+// 由系统分配临时端口并保持绑定，避免 TOCTOU 竞争
+// Let the OS assign an ephemeral port and keep it bound (no TOCTOU race)
+//
+// 示例 / Example:
+// let (port, listener) = v::comm::port::reserve_free_port()?;
+// listener.set_nonblocking(true)?;
+// let listener = tokio::net::TcpListener::from_std(listener)?;
+pub fn reserve_free_port() -> io::Result<(u16, TcpListener)> {
+    let listener = TcpListener::bind(SocketAddr::from((Ipv4Addr::LOCALHOST, 0)))?;
+    let port = listener.local_addr()?.port();
+    Ok((port, listener))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_concurrent_reservations_differ() {
+        let handles: Vec<_> = (0..8)
+            .map(|_| std::thread::spawn(|| reserve_free_port().unwrap()))
+            .collect();
+        let reserved: Vec<(u16, TcpListener)> =
+            handles.into_iter().map(|h| h.join().unwrap()).collect();
+        let mut ports: Vec<u16> = reserved.iter().map(|(p, _)| *p).collect();
+        ports.sort_unstable();
+        ports.dedup();
+        assert_eq!(ports.len(), reserved.len());
+    }
+
+    #[test]
+    fn test_find_in_range_skips_taken_port() {
+        let (taken, _held) = reserve_free_port().unwrap();
+        let found = find_free_port_in_range(taken, taken.saturating_add(50));
+        assert!(matches!(found, Some(p) if p != taken));
+        assert_eq!(find_free_port_in_range(taken, taken), None);
+        assert_eq!(find_free_port_in_range(10, 5), None);
+    }
+}
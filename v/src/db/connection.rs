@@ -1,76 +1,23 @@
-use std::collections::HashMap;
-use std::time::Duration;
-use tokio::sync::RwLock;
-
 use async_trait::async_trait;
-use sqlx::postgres::PgPoolOptions;
 use sqlx::{Pool, Postgres};
 
-use crate::comm::config::get_global_config_manager;
 use crate::db::error::{DbError, Result};
+use crate::db::manager::database_manager;
 use crate::{HealthCheck, HealthStatus};
 
-lazy_static::lazy_static! {
-    static ref POOLS: RwLock<HashMap<String, Pool<Postgres>>> = RwLock::new(HashMap::new());
-}
-
 /// 获取指定分组的 PostgreSQL 连接池（自动懒加载）
 /// Get PostgreSQL pool for a group (lazy init)
 ///
 /// 参数 / Params:
 /// - `group`: 分库组名称 / database group name
 ///
-/// 返回 / Returns: `Pool<Postgres>`
+/// 返回 / Returns: `Pool<Postgres>`；分组配置为其他后端时返回配置错误
+/// / config error when the group uses another backend (see `get_any_pool_by_group`)
 pub async fn get_pool(group: &str) -> Result<Pool<Postgres>> {
-    if let Some(p) = POOLS.read().await.get(group).cloned() {
-        return Ok(p);
-    }
-    let pool = build_pool(group).await?;
-    POOLS.write().await.insert(group.to_string(), pool.clone());
-    Ok(pool)
-}
-
-/// 根据配置构建连接池 / Build pool from configuration
-///
-/// 读取配置键 / Reads config keys:
-/// - `database.<group>.url` 或 `host/port/user/pass/name/maxOpen`
-async fn build_pool(group: &str) -> Result<Pool<Postgres>> {
-    let mgr = get_global_config_manager().map_err(|e| DbError::Config(e.to_string()))?;
-    let typ: String = mgr
-        .get_or(
-            &format!("database.{}.type", group),
-            "postgresql".to_string(),
-        )
-        .to_lowercase();
-    if typ != "postgresql" && typ != "postgres" {
-        return Err(DbError::Config(format!(
-            "不支持的数据库类型: {} (group={})",
-            typ, group
-        )));
-    }
-
-    let url_opt: Option<String> = mgr.get(&format!("database.{}.url", group)).ok();
-    let max_open: u32 = mgr
-        .get(&format!("database.{}.maxOpen", group))
-        .map(|v: i64| v as u32)
-        .unwrap_or(10);
-    let host: String = mgr.get_or(&format!("database.{}.host", group), "127.0.0.1".to_string());
-    let port: String = mgr.get_or(&format!("database.{}.port", group), "5432".to_string());
-    let user: String = mgr.get_or(&format!("database.{}.user", group), "postgres".to_string());
-    let pass: String = mgr.get_or(&format!("database.{}.pass", group), "".to_string());
-    let name: String = mgr.get_or(&format!("database.{}.name", group), "postgres".to_string());
-    let url = url_opt.unwrap_or_else(|| build_postgres_url(&host, &port, &user, &pass, &name));
-
-    let pool = PgPoolOptions::new()
-        .max_connections(max_open)
-        .min_connections(1)
-        .max_lifetime(Some(Duration::from_secs(1800)))
-        .idle_timeout(Some(Duration::from_secs(300)))
-        .acquire_timeout(Duration::from_secs(3))
-        .connect(&url)
-        .await
-        .map_err(DbError::from)?;
-    Ok(pool)
+    database_manager()
+        .get_any_pool_by_group(group)
+        .await?
+        .into_postgres()
 }
 
 /// 构建 PostgreSQL 连接 URL / Build PostgreSQL URL
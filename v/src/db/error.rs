@@ -9,15 +9,87 @@ pub enum DbError {
     #[error("连接池错误: {0}")]
     Pool(String),
     #[error("SQLx 错误: {0}")]
-    Sqlx(#[from] sqlx::Error),
+    Sqlx(#[source] sqlx::Error),
     #[error("未找到记录")]
     NotFound,
+    #[error("唯一约束冲突: {0}")]
+    UniqueViolation(String),
+    #[error("外键约束冲突: {0}")]
+    ForeignKeyViolation(String),
+    #[error("连接池获取超时")]
+    PoolTimeout,
+    #[error("事务序列化失败: {0}")]
+    SerializationFailure(String),
     #[error("事务错误: {0}")]
     Tx(String),
+    /// 乐观锁冲突：记录的 `version` 已被其他写入推进 / Optimistic lock conflict: the row's `version` moved
+    #[error("版本冲突: {0}")]
+    Conflict(String),
     #[error("序列化错误: {0}")]
     Serde(#[from] serde_json::Error),
 }
 
+/// 按数据库错误码归类（Postgres SQLSTATE / MySQL / SQLite 扩展码）
+/// Classify by database error code (Postgres SQLSTATE / MySQL / SQLite extended codes)
+fn classify_code(code: &str, message: &str) -> Option<DbError> {
+    match code {
+        // Postgres 23505, MySQL 1062 (ER_DUP_ENTRY), SQLite 2067/1555 (UNIQUE/PRIMARYKEY)
+        "23505" | "1062" | "2067" | "1555" => Some(DbError::UniqueViolation(message.to_string())),
+        // Postgres 23503, MySQL 1451/1452, SQLite 787 (FOREIGNKEY)
+        "23503" | "1451" | "1452" | "787" => {
+            Some(DbError::ForeignKeyViolation(message.to_string()))
+        }
+        // Postgres 40001/40P01, MySQL 1213 (deadlock)/1205 (lock wait timeout)
+        "40001" | "40P01" | "1213" | "1205" => {
+            Some(DbError::SerializationFailure(message.to_string()))
+        }
+        _ => None,
+    }
+}
+
+impl From<sqlx::Error> for DbError {
+    fn from(err: sqlx::Error) -> Self {
+        match err {
+            sqlx::Error::RowNotFound => DbError::NotFound,
+            sqlx::Error::PoolTimedOut => DbError::PoolTimeout,
+            sqlx::Error::PoolClosed => DbError::Pool("连接池已关闭 / pool closed".to_string()),
+            sqlx::Error::Database(db) => {
+                let classified = db
+                    .code()
+                    .and_then(|code| classify_code(&code, db.message()));
+                match classified {
+                    Some(e) => e,
+                    None if db.is_unique_violation() => {
+                        DbError::UniqueViolation(db.message().to_string())
+                    }
+                    None if db.is_foreign_key_violation() => {
+                        DbError::ForeignKeyViolation(db.message().to_string())
+                    }
+                    None => DbError::Sqlx(sqlx::Error::Database(db)),
+                }
+            }
+            other => DbError::Sqlx(other),
+        }
+    }
+}
+
+impl DbError {
+    /// 是否为可重试的瞬时错误 / Whether this is a transient, retryable error
+    ///
+    /// 瞬时 / Transient: `PoolTimeout`、`SerializationFailure`（含死锁 / incl. deadlock）、
+    /// 以及 SQLx 的 I/O 错误 / and SQLx I/O errors。
+    /// 逻辑错误（唯一/外键冲突、版本冲突、未找到、配置、序列化）永不重试
+    /// Logical errors (unique/FK violation, version conflict, not found, config, serde) are never retried
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            DbError::PoolTimeout
+                | DbError::SerializationFailure(_)
+                | DbError::Sqlx(sqlx::Error::Io(_))
+        )
+    }
+}
+
 // 保留统一错误描述函数，避免在各层重复构建错误字符串
 
 /// 获取详细错误描述（中英文） / Get detailed error description (CN/EN)
@@ -27,7 +99,127 @@ pub fn describe_error(e: &DbError) -> String {
         DbError::Pool(msg) => format!("连接池错误 / Pool error: {}", msg),
         DbError::Sqlx(err) => format!("SQLx 错误 / SQLx error: {}", err),
         DbError::NotFound => "未找到记录 / Record not found".to_string(),
+        DbError::UniqueViolation(msg) => {
+            format!("唯一约束冲突 / Unique violation: {}", msg)
+        }
+        DbError::ForeignKeyViolation(msg) => {
+            format!("外键约束冲突 / Foreign key violation: {}", msg)
+        }
+        DbError::PoolTimeout => "连接池获取超时 / Pool acquire timed out".to_string(),
+        DbError::SerializationFailure(msg) => {
+            format!("事务序列化失败 / Serialization failure: {}", msg)
+        }
         DbError::Tx(msg) => format!("事务错误 / Transaction error: {}", msg),
+        DbError::Conflict(msg) => format!("版本冲突 / Version conflict: {}", msg),
         DbError::Serde(msg) => format!("序列化错误 / Serialization error: {}", msg),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::error::{DatabaseError, ErrorKind};
+    use std::borrow::Cow;
+
+    /// 模拟驱动返回的数据库错误 / Fake driver database error
+    #[derive(Debug)]
+    struct FakeDbError {
+        code: &'static str,
+        unique: bool,
+    }
+
+    impl std::fmt::Display for FakeDbError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "fake error {}", self.code)
+        }
+    }
+
+    impl std::error::Error for FakeDbError {}
+
+    impl DatabaseError for FakeDbError {
+        fn message(&self) -> &str {
+            "fake"
+        }
+        fn code(&self) -> Option<Cow<'_, str>> {
+            Some(Cow::Borrowed(self.code))
+        }
+        fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+        fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+        fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+            self
+        }
+        fn kind(&self) -> ErrorKind {
+            if self.unique {
+                ErrorKind::UniqueViolation
+            } else {
+                ErrorKind::Other
+            }
+        }
+    }
+
+    fn db_err(code: &'static str) -> DbError {
+        DbError::from(sqlx::Error::from(FakeDbError {
+            code,
+            unique: false,
+        }))
+    }
+
+    #[test]
+    fn classifies_not_found_and_pool_timeout() {
+        assert!(matches!(
+            DbError::from(sqlx::Error::RowNotFound),
+            DbError::NotFound
+        ));
+        assert!(matches!(
+            DbError::from(sqlx::Error::PoolTimedOut),
+            DbError::PoolTimeout
+        ));
+        assert!(matches!(
+            DbError::from(sqlx::Error::PoolClosed),
+            DbError::Pool(_)
+        ));
+    }
+
+    #[test]
+    fn classifies_constraint_violations_across_backends() {
+        for code in ["23505", "1062", "2067"] {
+            assert!(
+                matches!(db_err(code), DbError::UniqueViolation(_)),
+                "{}",
+                code
+            );
+        }
+        for code in ["23503", "1452", "787"] {
+            assert!(
+                matches!(db_err(code), DbError::ForeignKeyViolation(_)),
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn classifies_serialization_failures() {
+        for code in ["40001", "40P01", "1213"] {
+            assert!(
+                matches!(db_err(code), DbError::SerializationFailure(_)),
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn falls_back_to_error_kind_then_sqlx() {
+        let e = DbError::from(sqlx::Error::from(FakeDbError {
+            code: "99999",
+            unique: true,
+        }));
+        assert!(matches!(e, DbError::UniqueViolation(_)));
+        assert!(matches!(db_err("42P01"), DbError::Sqlx(_)));
+    }
+}
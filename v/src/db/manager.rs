@@ -0,0 +1,387 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+use sqlx::postgres::PgPoolOptions;
+use sqlx::{Pool, Postgres};
+
+use crate::comm::config::{get_global_config_manager, ConfigManager};
+use crate::db::connection::build_postgres_url;
+use crate::db::error::{DbError, Result};
+
+/// 多后端连接池 / Multi-backend connection pool
+///
+/// 每个分组可独立选择后端；SQLite/MySQL 需开启 `sqlite`/`mysql` feature
+/// Each group picks its own backend; SQLite/MySQL require the `sqlite`/`mysql` features
+#[derive(Clone, Debug)]
+pub enum DbPool {
+    Postgres(Pool<Postgres>),
+    #[cfg(feature = "sqlite")]
+    Sqlite(Pool<sqlx::Sqlite>),
+    #[cfg(feature = "mysql")]
+    MySql(Pool<sqlx::MySql>),
+}
+
+impl DbPool {
+    /// 后端名称 / Backend name
+    pub fn backend(&self) -> &'static str {
+        match self {
+            DbPool::Postgres(_) => "postgresql",
+            #[cfg(feature = "sqlite")]
+            DbPool::Sqlite(_) => "sqlite",
+            #[cfg(feature = "mysql")]
+            DbPool::MySql(_) => "mysql",
+        }
+    }
+
+    /// 取出 PostgreSQL 连接池（后端不匹配时报配置错误）
+    /// Take the PostgreSQL pool (config error on backend mismatch)
+    pub fn into_postgres(self) -> Result<Pool<Postgres>> {
+        match self {
+            DbPool::Postgres(p) => Ok(p),
+            #[allow(unreachable_patterns)]
+            other => Err(DbError::Config(format!(
+                "分组后端为 {}，非 postgresql / group backend is {}, not postgresql",
+                other.backend(),
+                other.backend()
+            ))),
+        }
+    }
+
+    /// 执行原始 SQL 并返回影响行数 / Execute raw SQL and return affected rows
+    pub async fn execute(&self, sql: &str) -> Result<u64> {
+        let rows = match self {
+            DbPool::Postgres(p) => sqlx::query(sql).execute(p).await?.rows_affected(),
+            #[cfg(feature = "sqlite")]
+            DbPool::Sqlite(p) => sqlx::query(sql).execute(p).await?.rows_affected(),
+            #[cfg(feature = "mysql")]
+            DbPool::MySql(p) => sqlx::query(sql).execute(p).await?.rows_affected(),
+        };
+        Ok(rows)
+    }
+
+    /// 关闭连接池 / Close the pool
+    pub async fn close(&self) {
+        match self {
+            DbPool::Postgres(p) => p.close().await,
+            #[cfg(feature = "sqlite")]
+            DbPool::Sqlite(p) => p.close().await,
+            #[cfg(feature = "mysql")]
+            DbPool::MySql(p) => p.close().await,
+        }
+    }
+}
+
+/// 分组连接池参数 / Per-group pool settings
+///
+/// - `maxOpen`（默认 10）：最大连接数 / max connections
+/// - `minIdle`（PostgreSQL 默认 1，其余 0）：常驻空闲连接数，建池时即全部建立（预热），
+///   避免首批请求承担建连延迟 / idle connections kept open and all opened at creation (warmup),
+///   so the first requests don't pay connection setup latency
+/// - `acquireTimeoutMs`（默认 3000）：获取连接的最长等待，超时返回 `DbError::PoolTimeout`
+///   而不是无限阻塞 / max wait to acquire a connection; fails fast with `DbError::PoolTimeout`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolSettings {
+    pub max_open: u32,
+    pub min_idle: u32,
+    pub acquire_timeout: Duration,
+}
+
+impl PoolSettings {
+    /// 从 `database.<group>.*` 读取 / Read from `database.<group>.*`
+    pub fn from_config(mgr: &ConfigManager, group: &str, default_min_idle: u32) -> Self {
+        let key = |name: &str| format!("database.{}.{}", group, name);
+        let max_open = mgr
+            .get(&key("maxOpen"))
+            .map(|v: i64| v.max(1) as u32)
+            .unwrap_or(10);
+        let min_idle = mgr
+            .get(&key("minIdle"))
+            .map(|v: i64| v.max(0) as u32)
+            .unwrap_or(default_min_idle)
+            .min(max_open);
+        let acquire_timeout = Duration::from_millis(
+            mgr.get(&key("acquireTimeoutMs"))
+                .map(|v: i64| v.max(1) as u64)
+                .unwrap_or(3000),
+        );
+        Self {
+            max_open,
+            min_idle,
+            acquire_timeout,
+        }
+    }
+}
+
+/// 预热：同时持有 `n` 个连接迫使连接池建立它们，释放后留作空闲连接
+/// Warmup: hold `n` connections at once so the pool opens them, then release them as idle
+async fn warm_up<DB: sqlx::Database>(pool: &Pool<DB>, n: u32) -> Result<()> {
+    let mut held = Vec::with_capacity(n as usize);
+    for _ in 0..n {
+        held.push(pool.acquire().await?);
+    }
+    Ok(())
+}
+
+/// 数据库管理器：按分组名维护连接池注册表
+/// Database manager: registry of pools keyed by group name
+///
+/// 读取配置键 / Reads config keys:
+/// - `database.<group>.type`: `postgresql` | `sqlite` | `mysql`
+/// - `database.<group>.url` 或 `host/port/user/pass/name/maxOpen`
+/// - `database.<group>.minIdle` / `acquireTimeoutMs`：见 [`PoolSettings`]
+pub struct DatabaseManager {
+    config: Option<Arc<ConfigManager>>,
+    pools: RwLock<HashMap<String, DbPool>>,
+}
+
+lazy_static::lazy_static! {
+    static ref DATABASE_MANAGER: DatabaseManager = DatabaseManager::new();
+}
+
+/// 全局数据库管理器（使用全局配置） / Global database manager (uses global config)
+pub fn database_manager() -> &'static DatabaseManager {
+    &DATABASE_MANAGER
+}
+
+/// 获取指定分组的连接池（任意后端） / Get the pool for a group (any backend)
+pub async fn get_any_pool_by_group(group: &str) -> Result<DbPool> {
+    database_manager().get_any_pool_by_group(group).await
+}
+
+impl Default for DatabaseManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DatabaseManager {
+    /// 使用全局配置创建 / Create using the global config
+    pub fn new() -> Self {
+        Self {
+            config: None,
+            pools: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// 使用指定配置创建 / Create with a specific config
+    pub fn with_config(config: Arc<ConfigManager>) -> Self {
+        Self {
+            config: Some(config),
+            pools: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// 手动注册分组连接池（覆盖已有） / Register a group pool manually (replaces existing)
+    pub async fn register(&self, group: &str, pool: DbPool) {
+        self.pools.write().await.insert(group.to_string(), pool);
+    }
+
+    /// 已注册的分组 / Registered groups
+    pub async fn groups(&self) -> Vec<String> {
+        self.pools.read().await.keys().cloned().collect()
+    }
+
+    /// 获取分组连接池（自动懒加载） / Get a group's pool (lazy init)
+    pub async fn get_any_pool_by_group(&self, group: &str) -> Result<DbPool> {
+        if let Some(p) = self.pools.read().await.get(group).cloned() {
+            return Ok(p);
+        }
+        let pool = self.build_pool(group).await?;
+        // 并发构建时保留先写入者 / Keep the first writer on concurrent builds
+        let mut pools = self.pools.write().await;
+        Ok(pools.entry(group.to_string()).or_insert(pool).clone())
+    }
+
+    fn config(&self) -> Result<Arc<ConfigManager>> {
+        match &self.config {
+            Some(c) => Ok(c.clone()),
+            None => get_global_config_manager().map_err(|e| DbError::Config(e.to_string())),
+        }
+    }
+
+    /// 根据配置构建连接池 / Build pool from configuration
+    async fn build_pool(&self, group: &str) -> Result<DbPool> {
+        let mgr = self.config()?;
+        let typ: String = mgr
+            .get_or(
+                &format!("database.{}.type", group),
+                "postgresql".to_string(),
+            )
+            .to_lowercase();
+        let url_opt: Option<String> = mgr.get(&format!("database.{}.url", group)).ok();
+
+        match typ.as_str() {
+            "postgresql" | "postgres" => {
+                let host: String =
+                    mgr.get_or(&format!("database.{}.host", group), "127.0.0.1".to_string());
+                let port: String =
+                    mgr.get_or(&format!("database.{}.port", group), "5432".to_string());
+                let user: String =
+                    mgr.get_or(&format!("database.{}.user", group), "postgres".to_string());
+                let pass: String = mgr.get_or(&format!("database.{}.pass", group), "".to_string());
+                let name: String =
+                    mgr.get_or(&format!("database.{}.name", group), "postgres".to_string());
+                let url = url_opt
+                    .unwrap_or_else(|| build_postgres_url(&host, &port, &user, &pass, &name));
+                let settings = PoolSettings::from_config(&mgr, group, 1);
+                let pool = PgPoolOptions::new()
+                    .max_connections(settings.max_open)
+                    .min_connections(settings.min_idle)
+                    .max_lifetime(Some(Duration::from_secs(1800)))
+                    .idle_timeout(Some(Duration::from_secs(300)))
+                    .acquire_timeout(settings.acquire_timeout)
+                    .connect(&url)
+                    .await?;
+                warm_up(&pool, settings.min_idle).await?;
+                Ok(DbPool::Postgres(pool))
+            }
+            #[cfg(feature = "sqlite")]
+            "sqlite" => {
+                let url = url_opt.unwrap_or_else(|| "sqlite::memory:".to_string());
+                let settings = PoolSettings::from_config(&mgr, group, 0);
+                let pool = sqlx::sqlite::SqlitePoolOptions::new()
+                    .max_connections(settings.max_open)
+                    .min_connections(settings.min_idle)
+                    .acquire_timeout(settings.acquire_timeout)
+                    .connect(&url)
+                    .await?;
+                warm_up(&pool, settings.min_idle).await?;
+                Ok(DbPool::Sqlite(pool))
+            }
+            #[cfg(feature = "mysql")]
+            "mysql" => {
+                let url = url_opt.ok_or_else(|| {
+                    DbError::Config(format!("缺少 database.{}.url (mysql)", group))
+                })?;
+                let settings = PoolSettings::from_config(&mgr, group, 0);
+                let pool = sqlx::mysql::MySqlPoolOptions::new()
+                    .max_connections(settings.max_open)
+                    .min_connections(settings.min_idle)
+                    .acquire_timeout(settings.acquire_timeout)
+                    .connect(&url)
+                    .await?;
+                warm_up(&pool, settings.min_idle).await?;
+                Ok(DbPool::MySql(pool))
+            }
+            other => Err(DbError::Config(format!(
+                "不支持的数据库类型: {} (group={})",
+                other, group
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::comm::config::ConfigSource;
+    use config::FileFormat;
+
+    fn manager(toml: &str) -> DatabaseManager {
+        let cm = ConfigManager::with_sources(vec![ConfigSource::String {
+            content: toml.to_string(),
+            format: FileFormat::Toml,
+        }])
+        .unwrap();
+        DatabaseManager::with_config(Arc::new(cm))
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_backend_is_config_error() {
+        let m = manager("[database.bad]\ntype = \"oracle\"\n");
+        let err = m.get_any_pool_by_group("bad").await.unwrap_err();
+        assert!(matches!(err, DbError::Config(_)));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_two_groups_two_backends() {
+        let m = manager(
+            "[database.default]\ntype = \"postgresql\"\n\n[database.analytics]\ntype = \"sqlite\"\nurl = \"sqlite::memory:\"\nmaxOpen = 1\n",
+        );
+        // 只有设置了 DATABASE_URL 才要求 Postgres 可连 / Postgres must be reachable only when DATABASE_URL is set
+        let database_url = std::env::var("DATABASE_URL").ok();
+        m.register(
+            "default",
+            DbPool::Postgres(
+                PgPoolOptions::new()
+                    .acquire_timeout(Duration::from_secs(1))
+                    .connect_lazy(
+                        database_url
+                            .as_deref()
+                            .unwrap_or("postgres://postgres:@127.0.0.1:5432/postgres"),
+                    )
+                    .unwrap(),
+            ),
+        )
+        .await;
+
+        let analytics = m.get_any_pool_by_group("analytics").await.unwrap();
+        assert_eq!(analytics.backend(), "sqlite");
+        analytics
+            .execute("CREATE TABLE events (id INTEGER PRIMARY KEY)")
+            .await
+            .unwrap();
+        assert_eq!(
+            analytics
+                .execute("INSERT INTO events (id) VALUES (1)")
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(analytics.clone().into_postgres().is_err());
+
+        let default = m.get_any_pool_by_group("default").await.unwrap();
+        assert_eq!(default.backend(), "postgresql");
+        if database_url.is_some() {
+            assert_eq!(default.execute("SELECT 1").await.unwrap(), 1);
+        }
+    }
+
+    #[test]
+    fn test_pool_settings_defaults_and_clamping() {
+        let m = manager("[database.a]\nmaxOpen = 4\nminIdle = 9\nacquireTimeoutMs = 250\n");
+        let cm = m.config().unwrap();
+        assert_eq!(
+            PoolSettings::from_config(&cm, "a", 1),
+            PoolSettings {
+                max_open: 4,
+                min_idle: 4,
+                acquire_timeout: Duration::from_millis(250),
+            }
+        );
+        assert_eq!(
+            PoolSettings::from_config(&cm, "missing", 1),
+            PoolSettings {
+                max_open: 10,
+                min_idle: 1,
+                acquire_timeout: Duration::from_secs(3),
+            }
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_warmup_and_acquire_timeout() {
+        let m = manager(
+            "[database.warm]\ntype = \"sqlite\"\nurl = \"sqlite::memory:\"\nmaxOpen = 2\nminIdle = 2\nacquireTimeoutMs = 100\n",
+        );
+        let DbPool::Sqlite(pool) = m.get_any_pool_by_group("warm").await.unwrap() else {
+            unreachable!()
+        };
+        // 建池即打开 minIdle 个连接 / minIdle connections are open right after creation
+        assert_eq!(pool.size(), 2);
+
+        let _a = pool.acquire().await.unwrap();
+        let _b = pool.acquire().await.unwrap();
+        let started = std::time::Instant::now();
+        let err = DbPool::Sqlite(pool.clone())
+            .execute("SELECT 1")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DbError::PoolTimeout));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,299 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use clap::Parser;
+use sqlx::Executor;
+use tracing::info;
+
+use crate::db::error::{DbError, Result};
+use crate::db::manager::{database_manager, DbPool};
+
+/// 迁移记录表名 / Migration bookkeeping table
+pub const MIGRATIONS_TABLE: &str = "__schema_migrations";
+
+/// 单个迁移 / A single migration
+#[derive(Debug, Clone)]
+pub struct Migration {
+    pub version: i64,
+    pub name: String,
+    pub sql: String,
+}
+
+/// 迁移执行器：按版本顺序应用 SQL，每个版本在独立事务中执行并记录
+/// Migrator: applies SQL in version order, each version in its own transaction and recorded
+///
+/// 目录布局 / Directory layout: `<base>/<group>/<backend>/<version>_<name>.sql`
+/// 其中 backend 为 `postgresql` | `sqlite` | `mysql` / where backend is one of these
+#[derive(Debug, Clone, Default)]
+pub struct Migrator {
+    migrations: Vec<Migration>,
+}
+
+impl Migrator {
+    /// 创建空迁移器 / Create an empty migrator
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 添加迁移（可配合 `include_str!` 内嵌） / Add a migration (works with `include_str!`)
+    ///
+    /// 版本已存在时 panic：同一版本只会记录一次，另一条 SQL 将永远不会执行
+    /// Panics if the version is already registered: a version is recorded once, so the other SQL
+    /// would silently never run
+    pub fn add(mut self, version: i64, name: &str, sql: &str) -> Self {
+        if let Some(existing) = self.migrations.iter().find(|m| m.version == version) {
+            panic!(
+                "重复的迁移版本 / duplicate migration version {}: {} and {}",
+                version, existing.name, name
+            );
+        }
+        self.migrations.push(Migration {
+            version,
+            name: name.to_string(),
+            sql: sql.to_string(),
+        });
+        self.migrations.sort_by_key(|m| m.version);
+        self
+    }
+
+    /// 从目录加载 `<version>_<name>.sql` 文件 / Load `<version>_<name>.sql` files from a directory
+    pub fn from_dir(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| DbError::Config(format!("读取迁移目录失败 {}: {}", dir.display(), e)))?;
+        let mut migrator = Self::new();
+        let mut seen = HashSet::new();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("sql") {
+                continue;
+            }
+            let stem = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default()
+                .to_string();
+            let (version, name) = parse_file_stem(&stem).ok_or_else(|| {
+                DbError::Config(format!(
+                    "迁移文件名需为 <version>_<name>.sql: {}",
+                    path.display()
+                ))
+            })?;
+            if !seen.insert(version) {
+                return Err(DbError::Config(format!("重复的迁移版本: {}", version)));
+            }
+            let sql = std::fs::read_to_string(&path).map_err(|e| {
+                DbError::Config(format!("读取迁移文件失败 {}: {}", path.display(), e))
+            })?;
+            migrator = migrator.add(version, name, &sql);
+        }
+        Ok(migrator)
+    }
+
+    /// 分组在指定后端下的迁移目录 / Migration directory of a group for a backend
+    pub fn group_dir(base: impl AsRef<Path>, group: &str, backend: &str) -> PathBuf {
+        base.as_ref().join(group).join(backend)
+    }
+
+    /// 已加载的迁移 / Loaded migrations
+    pub fn migrations(&self) -> &[Migration] {
+        &self.migrations
+    }
+
+    /// 已应用的版本 / Applied versions
+    pub async fn applied_versions(&self, pool: &DbPool) -> Result<Vec<i64>> {
+        ensure_table(pool).await?;
+        let sql = format!("SELECT version FROM {} ORDER BY version", MIGRATIONS_TABLE);
+        let versions = match pool {
+            DbPool::Postgres(p) => sqlx::query_scalar::<_, i64>(&sql).fetch_all(p).await?,
+            #[cfg(feature = "sqlite")]
+            DbPool::Sqlite(p) => sqlx::query_scalar::<_, i64>(&sql).fetch_all(p).await?,
+            #[cfg(feature = "mysql")]
+            DbPool::MySql(p) => sqlx::query_scalar::<_, i64>(&sql).fetch_all(p).await?,
+        };
+        Ok(versions)
+    }
+
+    /// 应用未执行的迁移，返回本次应用的版本（幂等）
+    /// Apply pending migrations and return the versions applied now (idempotent)
+    pub async fn run(&self, pool: &DbPool) -> Result<Vec<i64>> {
+        let applied: HashSet<i64> = self.applied_versions(pool).await?.into_iter().collect();
+        let mut done = Vec::new();
+        for m in self
+            .migrations
+            .iter()
+            .filter(|m| !applied.contains(&m.version))
+        {
+            let record = format!(
+                "INSERT INTO {} (version, name) VALUES ({}, '{}')",
+                MIGRATIONS_TABLE,
+                m.version,
+                m.name.replace('\'', "''")
+            );
+            match pool {
+                DbPool::Postgres(p) => {
+                    let mut tx = p.begin().await?;
+                    (&mut *tx).execute(m.sql.as_str()).await?;
+                    (&mut *tx).execute(record.as_str()).await?;
+                    tx.commit().await?;
+                }
+                #[cfg(feature = "sqlite")]
+                DbPool::Sqlite(p) => {
+                    let mut tx = p.begin().await?;
+                    (&mut *tx).execute(m.sql.as_str()).await?;
+                    (&mut *tx).execute(record.as_str()).await?;
+                    tx.commit().await?;
+                }
+                #[cfg(feature = "mysql")]
+                DbPool::MySql(p) => {
+                    let mut tx = p.begin().await?;
+                    (&mut *tx).execute(m.sql.as_str()).await?;
+                    (&mut *tx).execute(record.as_str()).await?;
+                    tx.commit().await?;
+                }
+            }
+            info!(
+                "迁移已应用 / migration applied: {} {} ({})",
+                m.version,
+                m.name,
+                pool.backend()
+            );
+            done.push(m.version);
+        }
+        Ok(done)
+    }
+}
+
+/// 解析 `0001_create_users` → (1, "create_users") / Parse a migration file stem
+fn parse_file_stem(stem: &str) -> Option<(i64, &str)> {
+    let (version, name) = stem.split_once('_')?;
+    Some((version.parse().ok()?, name))
+}
+
+/// 创建迁移记录表 / Create the bookkeeping table
+async fn ensure_table(pool: &DbPool) -> Result<()> {
+    let sql = format!(
+        "CREATE TABLE IF NOT EXISTS {} (version BIGINT PRIMARY KEY, name VARCHAR(255) NOT NULL, applied_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP)",
+        MIGRATIONS_TABLE
+    );
+    pool.execute(&sql).await.map(|_| ())
+}
+
+/// `migrate` 子命令 / `migrate` subcommand
+#[derive(Parser, Debug)]
+#[command(name = "migrate", about = "执行数据库迁移 / Run database migrations")]
+pub struct MigrateCommand {
+    /// 迁移根目录 / Migrations root directory
+    #[arg(short = 'd', long = "dir", default_value = "migrations")]
+    pub dir: String,
+    /// 分组（可重复） / Groups (repeatable)
+    #[arg(short = 'g', long = "group", default_value = "default")]
+    pub groups: Vec<String>,
+    /// 仅显示状态不执行 / Only show status
+    #[arg(long = "status")]
+    pub status: bool,
+}
+
+impl MigrateCommand {
+    /// 对每个分组执行迁移 / Run migrations for each group
+    pub async fn run(&self) -> Result<()> {
+        for group in &self.groups {
+            let pool = database_manager().get_any_pool_by_group(group).await?;
+            let dir = Migrator::group_dir(&self.dir, group, pool.backend());
+            let migrator = Migrator::from_dir(&dir)?;
+            if self.status {
+                let applied = migrator.applied_versions(&pool).await?;
+                info!(
+                    "group={} backend={} applied={:?} available={}",
+                    group,
+                    pool.backend(),
+                    applied,
+                    migrator.migrations().len()
+                );
+            } else {
+                let done = migrator.run(&pool).await?;
+                info!("group={} 本次应用 / applied now: {:?}", group, done);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 命令行第一个参数为 `migrate` 时执行迁移并返回 true
+/// When the first CLI argument is `migrate`, run migrations and return true
+///
+/// 用法 / Usage: `server migrate --group default --dir migrations`
+pub async fn run_if_requested() -> Result<bool> {
+    let mut args = std::env::args();
+    let bin = args.next().unwrap_or_default();
+    match args.next() {
+        Some(sub) if sub == "migrate" => {
+            let cmd = MigrateCommand::parse_from(std::iter::once(bin).chain(args));
+            cmd.run().await?;
+            Ok(true)
+        }
+        _ => Ok(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_file_stem() {
+        assert_eq!(
+            parse_file_stem("0001_create_users"),
+            Some((1, "create_users"))
+        );
+        assert_eq!(parse_file_stem("create_users"), None);
+    }
+
+    #[test]
+    fn test_from_dir_orders_versions() {
+        let dir = std::env::temp_dir().join(format!("v_migrate_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("0002_b.sql"), "SELECT 2;").unwrap();
+        std::fs::write(dir.join("0001_a.sql"), "SELECT 1;").unwrap();
+        std::fs::write(dir.join("README.md"), "ignored").unwrap();
+        let m = Migrator::from_dir(&dir).unwrap();
+        let versions: Vec<i64> = m.migrations().iter().map(|m| m.version).collect();
+        assert_eq!(versions, vec![1, 2]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate migration version 1")]
+    fn test_add_rejects_duplicate_versions() {
+        let _ = Migrator::new().add(1, "create_users", "SELECT 1;").add(
+            1,
+            "create_orders",
+            "SELECT 2;",
+        );
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_run_is_idempotent_on_sqlite() {
+        let pool = DbPool::Sqlite(
+            sqlx::sqlite::SqlitePoolOptions::new()
+                .max_connections(1)
+                .connect("sqlite::memory:")
+                .await
+                .unwrap(),
+        );
+        let migrator = Migrator::new()
+            .add(2, "add_email", "ALTER TABLE users ADD COLUMN email TEXT;")
+            .add(
+                1,
+                "create_users",
+                "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT);",
+            );
+        assert_eq!(migrator.run(&pool).await.unwrap(), vec![1, 2]);
+        assert!(migrator.run(&pool).await.unwrap().is_empty());
+        assert_eq!(migrator.applied_versions(&pool).await.unwrap(), vec![1, 2]);
+        pool.execute("INSERT INTO users (id, name, email) VALUES (1, 'a', 'a@x')")
+            .await
+            .unwrap();
+    }
+}
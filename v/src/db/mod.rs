@@ -1,4 +1,7 @@
 pub mod connection;
 pub mod error;
+pub mod manager;
+pub mod migrate;
 pub mod model;
 pub mod query;
+pub mod retry;
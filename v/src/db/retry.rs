@@ -0,0 +1,154 @@
+use std::future::Future;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::db::error::Result;
+
+/// 重试策略（指数退避 + 抖动） / Retry policy (exponential backoff + jitter)
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// 最大尝试次数（含首次） / Max attempts (including the first)
+    pub max_attempts: u32,
+    /// 初始退避 / Initial backoff
+    pub base_delay: Duration,
+    /// 退避上限 / Backoff cap
+    pub max_delay: Duration,
+    /// 是否加入随机抖动 / Whether to add random jitter
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(50),
+            max_delay: Duration::from_secs(2),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 第 `attempt` 次失败后的等待时长（从 1 开始） / Delay after the `attempt`-th failure (1-based)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(1u32 << attempt.saturating_sub(1).min(16));
+        let capped = exp.min(self.max_delay);
+        if !self.jitter || capped.is_zero() {
+            return capped;
+        }
+        // 在 [capped/2, capped] 区间内取抖动 / Jitter within [capped/2, capped]
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos() as u64)
+            .unwrap_or(0);
+        let half = capped / 2;
+        let span = (capped - half).as_nanos().max(1) as u64;
+        half + Duration::from_nanos(nanos % span)
+    }
+}
+
+/// 对瞬时数据库错误自动重试 / Retry an operation on transient database errors
+///
+/// 仅当 `DbError::is_transient()` 为真时重试，其余错误立即返回
+/// Retries only when `DbError::is_transient()` holds; other errors return immediately
+///
+/// 示例 / Example:
+/// ```ignore
+/// let user = with_retry(&RetryPolicy::default(), || repo.read_one(id)).await?;
+/// ```
+pub async fn with_retry<T, F, Fut>(policy: &RetryPolicy, mut op: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let max_attempts = policy.max_attempts.max(1);
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        match op().await {
+            Ok(v) => return Ok(v),
+            Err(e) if e.is_transient() && attempt < max_attempts => {
+                let delay = policy.backoff(attempt);
+                tracing::warn!(
+                    "数据库瞬时错误，{}ms 后重试 ({}/{}) / transient db error, retrying: {}",
+                    delay.as_millis(),
+                    attempt,
+                    max_attempts,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::error::DbError;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn fast_policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(5),
+            jitter: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_transient_then_succeeds() {
+        let calls = AtomicU32::new(0);
+        let res = with_retry(&fast_policy(3), || {
+            let n = calls.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if n < 2 {
+                    Err(DbError::PoolTimeout)
+                } else {
+                    Ok(42)
+                }
+            }
+        })
+        .await;
+        assert_eq!(res.unwrap(), 42);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn never_retries_logical_errors() {
+        let calls = AtomicU32::new(0);
+        let res: Result<()> = with_retry(&fast_policy(5), || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Err(DbError::UniqueViolation("dup".into())) }
+        })
+        .await;
+        assert!(matches!(res, Err(DbError::UniqueViolation(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let calls = AtomicU32::new(0);
+        let res: Result<()> = with_retry(&fast_policy(2), || {
+            calls.fetch_add(1, Ordering::SeqCst);
+            async { Err(DbError::SerializationFailure("40001".into())) }
+        })
+        .await;
+        assert!(matches!(res, Err(DbError::SerializationFailure(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        let p = RetryPolicy {
+            jitter: false,
+            ..fast_policy(10)
+        };
+        assert_eq!(p.backoff(1), Duration::from_millis(1));
+        assert_eq!(p.backoff(2), Duration::from_millis(2));
+        assert_eq!(p.backoff(8), Duration::from_millis(5));
+    }
+}
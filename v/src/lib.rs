@@ -8,6 +8,7 @@ pub use crate::comm::tracing::init_tracing;
 pub mod db;
 pub use crate::db::connection::*;
 pub use crate::db::error::*;
+pub use crate::db::manager::{get_any_pool_by_group, DatabaseManager, DbPool};
 pub use crate::db::model::*;
 pub use crate::db::query::*;
 pub mod response;
@@ -2,17 +2,39 @@
 
 use anyhow::Result;
 use prost::Message;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::UnixStream;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpStream, UnixStream};
 use tokio::sync::watch;
 use tokio::time::{sleep, Duration};
 use tracing::{debug, error, info, warn};
 
 use super::protocol::{
-    negotiate_protocol, EventMessage, EventResponse, HandshakeRequest, HandshakeResponse,
-    ProtocolFormat,
+    negotiate_protocol, tcp_address, EventMessage, EventResponse, HandshakeRequest,
+    HandshakeResponse, ProtocolFormat, CONFIG_UPDATE_EVENT, HANDSHAKE_INCOMPATIBLE,
+    HANDSHAKE_UNAUTHORIZED, PROTOCOL_VERSION,
 };
 
+/// 插件连接的字节流（Unix socket 或 TCP）/ Byte stream of a plugin connection (Unix socket or TCP)
+pub trait PluginIo: AsyncRead + AsyncWrite + Unpin + Send + Sync {}
+
+impl<T: AsyncRead + AsyncWrite + Unpin + Send + Sync> PluginIo for T {}
+
+/// 类型擦除的插件连接 / Type-erased plugin connection
+pub type PluginStream = Box<dyn PluginIo>;
+
+/// 宿主拒绝了插件的协议版本，重连无意义
+/// The host rejected the plugin's protocol version; reconnecting is pointless
+#[derive(Debug)]
+pub struct IncompatibleProtocolError(pub String);
+
+impl std::fmt::Display for IncompatibleProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "incompatible plugin protocol: {}", self.0)
+    }
+}
+
+impl std::error::Error for IncompatibleProtocolError {}
+
 /// 插件事件处理接口 / Plugin event handler interface
 pub trait PluginHandler {
     /// 插件名称 / Plugin name
@@ -33,6 +55,11 @@ pub trait PluginHandler {
     fn config(&mut self, _cfg: &str) -> Result<()> {
         Ok(())
     }
+    /// 运行时配置更新（`config.update` 事件，载荷为该插件的配置子树）
+    /// Live config update (`config.update` event; payload is this plugin's config subtree)
+    fn on_config_update(&mut self, config: &serde_json::Value) -> Result<()> {
+        self.config(&config.to_string())
+    }
     /// 处理事件并返回响应 / Handle event and return response
     fn on_event(&mut self, event: &EventMessage) -> Result<EventResponse>;
 }
@@ -46,6 +73,7 @@ pub struct PluginClient<H: PluginHandler> {
     shutdown_tx: watch::Sender<bool>,   // 关闭信号发送器 / Shutdown signal sender
     shutdown_rx: watch::Receiver<bool>, // 关闭信号接收器 / Shutdown signal receiver
     protocol: ProtocolFormat,           // 当前使用的协议 / Current protocol
+    auth_token: Option<String>,         // TCP 认证令牌 / TCP auth token
 }
 
 impl<H: PluginHandler> PluginClient<H> {
@@ -69,9 +97,16 @@ impl<H: PluginHandler> PluginClient<H> {
             shutdown_tx: tx,
             shutdown_rx: rx,
             protocol,
+            auth_token: None,
         }
     }
 
+    /// 设置 TCP 握手认证令牌 / Set the auth token sent in the TCP handshake
+    pub fn with_auth_token(mut self, token: Option<String>) -> Self {
+        self.auth_token = token.filter(|t| !t.is_empty());
+        self
+    }
+
     /// 触发关闭信号 / Trigger shutdown signal
     pub fn shutdown(&self) {
         let _ = self.shutdown_tx.send(true);
@@ -103,6 +138,10 @@ impl<H: PluginHandler> PluginClient<H> {
                     info!("[plugin:{}] session finished, reconnecting", self.ident);
                     backoff = self.reconnect_backoff.0;
                 }
+                Err(e) if e.downcast_ref::<IncompatibleProtocolError>().is_some() => {
+                    error!("[plugin:{}] {}", self.ident, e);
+                    return Err(e);
+                }
                 Err(e) => {
                     warn!("[plugin:{}] session error: {}", self.ident, e);
                     tokio::select! {
@@ -131,8 +170,11 @@ impl<H: PluginHandler> PluginClient<H> {
         self.listen_loop(&mut stream).await
     }
 
-    /// 等待 socket 文件 / Wait for socket file
+    /// 等待 socket 文件（TCP 地址无需等待）/ Wait for socket file (nothing to wait for over TCP)
     async fn wait_for_socket(&mut self) -> Result<()> {
+        if tcp_address(&self.socket_path).is_some() {
+            return Ok(());
+        }
         let mut retries = 120u32;
         while !std::path::Path::new(&self.socket_path).exists() {
             if retries == 0 {
@@ -161,13 +203,13 @@ impl<H: PluginHandler> PluginClient<H> {
     }
 
     /// 带重试的连接（处理连接拒绝）/ Connect with retry (handle ECONNREFUSED)
-    async fn connect_with_retry(&mut self) -> Result<UnixStream> {
+    async fn connect_with_retry(&mut self) -> Result<PluginStream> {
         use std::io::ErrorKind;
         let mut rx = self.shutdown_rx.clone();
         let mut backoff = self.reconnect_backoff.0.min(500);
         loop {
             tokio::select! {
-                res = UnixStream::connect(&self.socket_path) => {
+                res = Self::connect(&self.socket_path) => {
                     match res {
                         Ok(stream) => return Ok(stream),
                         Err(e) => {
@@ -194,14 +236,29 @@ impl<H: PluginHandler> PluginClient<H> {
         }
     }
 
+    /// 按地址连接：`tcp://host:port` 走 TCP，否则为 Unix socket 路径
+    /// Connect by address: `tcp://host:port` uses TCP, anything else is a Unix socket path
+    async fn connect(address: &str) -> std::io::Result<PluginStream> {
+        match tcp_address(address) {
+            Some(addr) => {
+                let stream = TcpStream::connect(addr).await?;
+                stream.set_nodelay(true)?;
+                Ok(Box::new(stream))
+            }
+            None => Ok(Box::new(UnixStream::connect(address).await?)),
+        }
+    }
+
     /// 发送握手信息 / Send handshake info
-    async fn send_handshake(&mut self, stream: &mut UnixStream) -> Result<()> {
+    async fn send_handshake(&mut self, stream: &mut PluginStream) -> Result<()> {
         let handshake = HandshakeRequest {
             name: self.handler.name().to_string(),
             version: self.handler.version().to_string(),
             capabilities: self.handler.capabilities(),
             priority: self.handler.priority(),
             protocol: format!("{:?}", self.protocol).to_lowercase(),
+            protocol_version: PROTOCOL_VERSION,
+            auth_token: self.auth_token.clone().unwrap_or_default(),
         };
 
         // 使用 prost 编码握手消息 / Encode handshake using prost
@@ -220,7 +277,10 @@ impl<H: PluginHandler> PluginClient<H> {
         info!("  Plugin ID      : {}", handshake.name);
         info!("  Version        : {}", handshake.version);
         info!("  Priority       : {}", handshake.priority);
-        info!("  Protocol       : {:?}", self.protocol);
+        info!(
+            "  Protocol       : {:?} v{}",
+            self.protocol, handshake.protocol_version
+        );
         info!("  Capabilities   : [{}]", handshake.capabilities.join(", "));
         info!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
         info!("");
@@ -247,6 +307,14 @@ impl<H: PluginHandler> PluginClient<H> {
                     self.protocol = negotiated;
                 }
             }
+        } else if resp_val.status == HANDSHAKE_INCOMPATIBLE {
+            return Err(IncompatibleProtocolError(format!(
+                "{} (plugin v{}, host v{})",
+                resp_val.message, PROTOCOL_VERSION, resp_val.protocol_version
+            ))
+            .into());
+        } else if resp_val.status == HANDSHAKE_UNAUTHORIZED {
+            anyhow::bail!("Handshake rejected / 握手被拒绝: {}", resp_val.message);
         } else {
             warn!("⚠️  Handshake response: {:?}", resp_val);
         }
@@ -259,8 +327,35 @@ impl<H: PluginHandler> PluginClient<H> {
         Ok(())
     }
 
+    /// 应用宿主推送的配置更新 / Apply a config update pushed by the host
+    fn apply_config_update(handler: &mut H, ident: &str, event: &EventMessage) -> EventResponse {
+        let result = serde_json::from_slice::<serde_json::Value>(&event.payload)
+            .map_err(anyhow::Error::from)
+            .and_then(|config| handler.on_config_update(&config));
+        match result {
+            Ok(()) => {
+                info!("[plugin:{}] config update applied", ident);
+                EventResponse {
+                    status: "ok".to_string(),
+                    flow: "continue".to_string(),
+                    data: Vec::new(),
+                    error: String::new(),
+                }
+            }
+            Err(e) => {
+                warn!("[plugin:{}] config update rejected: {}", ident, e);
+                EventResponse {
+                    status: "error".to_string(),
+                    flow: "continue".to_string(),
+                    data: Vec::new(),
+                    error: e.to_string(),
+                }
+            }
+        }
+    }
+
     /// 事件循环 / Event loop
-    async fn listen_loop(&mut self, stream: &mut UnixStream) -> Result<()> {
+    async fn listen_loop(&mut self, stream: &mut PluginStream) -> Result<()> {
         loop {
             tokio::select! {
                 _ = self.shutdown_rx.changed() => {
@@ -283,8 +378,12 @@ impl<H: PluginHandler> PluginClient<H> {
                         self.ident, event.event_type, event.payload.len()
                     );
 
-                    // 处理事件 / Handle event
-                    let response = self.handler.on_event(&event)?;
+                    // 处理事件（配置更新由 SDK 直接处理）/ Handle event (config updates are handled by the SDK)
+                    let response = if event.event_type == CONFIG_UPDATE_EVENT {
+                        Self::apply_config_update(&mut self.handler, &self.ident, &event)
+                    } else {
+                        self.handler.on_event(&event)?
+                    };
 
                     // 使用 prost 编码响应 / Encode response using prost
                     let resp_bytes = response.encode_to_vec();
@@ -114,4 +114,12 @@ pub trait AuthEventListener: Send + Sync {
         &mut self,
         req: &ValidateTokenRequest,
     ) -> Result<ValidateTokenResponse>;
+
+    /// 运行时配置更新（默认忽略）/ Live config update (ignored by default)
+    ///
+    /// # 参数 / Parameters
+    /// - `config`: 宿主下发的本插件配置子树 / This plugin's config subtree pushed by the host
+    async fn on_config_update(&mut self, _config: &serde_json::Value) -> Result<()> {
+        Ok(())
+    }
 }
@@ -5,13 +5,24 @@
 
 use anyhow::Result;
 use async_trait::async_trait;
+use serde_json::{json, Value};
 
 use crate::plugin::protocol::{
-    AckOfflineMessagesRequest, AckOfflineMessagesResponse, AddRoomMemberRequest,
-    AddRoomMemberResponse, CountOfflineMessagesRequest, CountOfflineMessagesResponse,
-    GetRoomMembersRequest, GetRoomMembersResponse, PullOfflineMessagesRequest,
-    PullOfflineMessagesResponse, RemoveRoomMemberRequest, RemoveRoomMemberResponse,
-    SaveMessageRequest, SaveMessageResponse, SaveOfflineMessageRequest, SaveOfflineMessageResponse,
+    AckOfflineMessagesRequest, AckOfflineMessagesResponse, AddGroupAckRequest, AddGroupAckResponse,
+    AddPinRequest, AddPinResponse, AddReactionRequest, AddReactionResponse, AddRoomMemberRequest,
+    AddRoomMemberResponse, ArchiveMessagesRequest, ArchiveMessagesResponse,
+    CountOfflineMessagesRequest, CountOfflineMessagesResponse, DeleteOfflineMessagesRequest,
+    DeleteOfflineMessagesResponse, GetMessageRequest, GetMessageResponse, GetRoomMembersRequest,
+    GetRoomMembersResponse, GroupAckStatusRequest, GroupAckStatusResponse, HistoryMessage,
+    ListPinsRequest, ListPinsResponse, ListReactionsRequest, ListReactionsResponse,
+    ListThreadRequest, ListThreadResponse, MessageHistoryRequest, MessageHistoryResponse,
+    OfflineMessage, PullOfflineMessagesRequest, PullOfflineMessagesResponse, PurgeMessagesRequest,
+    PurgeMessagesResponse, RemovePinRequest, RemovePinResponse, RemoveReactionRequest,
+    RemoveReactionResponse, RemoveRoomMemberRequest, RemoveRoomMemberResponse,
+    RestoreMessagesRequest, RestoreMessagesResponse, SaveMessageRequest, SaveMessageResponse,
+    SaveOfflineMessageRequest, SaveOfflineMessageResponse, SearchHit, SearchMessagesRequest,
+    SearchMessagesResponse, TrackGroupAckRequest, TrackGroupAckResponse, UpdateRoomMembersRequest,
+    UpdateRoomMembersResponse,
 };
 
 // ============================================================================
@@ -108,6 +119,140 @@ pub trait StorageEventListener: Send + Sync {
         req: &CountOfflineMessagesRequest,
     ) -> Result<CountOfflineMessagesResponse>;
 
+    /// 删除离线消息（不计入已读）/ Delete offline messages (not counted as read)
+    ///
+    /// # 参数 / Parameters
+    /// - `req`: 删除离线消息请求，`message_ids` 为空时删除全部 / Delete request, empty `message_ids` deletes all
+    ///
+    /// # 返回 / Returns
+    /// - `Result<DeleteOfflineMessagesResponse>`: 删除离线消息响应 / Delete offline messages response
+    async fn storage_offline_delete(
+        &mut self,
+        req: &DeleteOfflineMessagesRequest,
+    ) -> Result<DeleteOfflineMessagesResponse>;
+
+    /// 查询历史消息 / Query message history
+    ///
+    /// # 参数 / Parameters
+    /// - `req`: 查询历史消息请求 / Query message history request
+    ///
+    /// # 返回 / Returns
+    /// - `Result<MessageHistoryResponse>`: 按时间升序的最近消息 / Most recent messages, ascending by time
+    async fn storage_message_history(
+        &mut self,
+        req: &MessageHistoryRequest,
+    ) -> Result<MessageHistoryResponse>;
+
+    /// 搜索消息（默认不支持）/ Search messages (unsupported by default)
+    ///
+    /// `cursor` 对宿主不透明，由实现自行编码；倒排索引等实现可覆盖此方法。
+    /// `cursor` is opaque to the host and encoded by the implementation, so an
+    /// inverted-index backend can override this without changing the contract.
+    ///
+    /// # 参数 / Parameters
+    /// - `req`: 搜索消息请求 / Search messages request
+    ///
+    /// # 返回 / Returns
+    /// - `Result<SearchMessagesResponse>`: 按相关度降序的一页结果 / One page of hits, most relevant first
+    async fn storage_message_search(
+        &mut self,
+        _req: &SearchMessagesRequest,
+    ) -> Result<SearchMessagesResponse> {
+        Err(anyhow::anyhow!(
+            "storage.message.search 不受支持 / storage.message.search is not supported"
+        ))
+    }
+
+    /// 按消息ID查询单条消息（默认不支持）/ Look up a single message by ID (unsupported by default)
+    ///
+    /// # 参数 / Parameters
+    /// - `req`: 按消息ID查询请求 / Get message by ID request
+    ///
+    /// # 返回 / Returns
+    /// - `Result<GetMessageResponse>`: 不存在时 `found` 为 false / `found` is false when absent
+    async fn storage_message_get(
+        &mut self,
+        _req: &GetMessageRequest,
+    ) -> Result<GetMessageResponse> {
+        Err(anyhow::anyhow!(
+            "storage.message.get 不受支持 / storage.message.get is not supported"
+        ))
+    }
+
+    /// 按写入顺序列出回复某条消息的消息（默认不支持）
+    /// List the replies to a message in write order (unsupported by default)
+    ///
+    /// 宿主把所回复的消息ID放在保存消息内容的 `reply_to` 字段中。
+    /// The host puts the parent message ID in the `reply_to` field of the saved message content.
+    ///
+    /// # 参数 / Parameters
+    /// - `req`: 列出消息回复请求 / List thread replies request
+    ///
+    /// # 返回 / Returns
+    /// - `Result<ListThreadResponse>`: 至多 `limit` 条回复 / At most `limit` replies
+    async fn storage_message_thread(
+        &mut self,
+        _req: &ListThreadRequest,
+    ) -> Result<ListThreadResponse> {
+        Err(anyhow::anyhow!(
+            "storage.message.thread 不受支持 / storage.message.thread is not supported"
+        ))
+    }
+
+    /// 归档 `[since_ts, until_ts)` 内的消息到对象存储（默认不支持）
+    /// Archive messages in `[since_ts, until_ts)` to object storage (unsupported by default)
+    ///
+    /// 归档只导出，不删除本地消息；压缩本地存储是单独的操作。
+    /// Archiving only exports; local messages are kept and compacting them is a separate step.
+    ///
+    /// # 参数 / Parameters
+    /// - `req`: 归档消息请求 / Archive messages request
+    ///
+    /// # 返回 / Returns
+    /// - `Result<ArchiveMessagesResponse>`: 归档对象键与导出条数 / Archive object key and exported count
+    async fn storage_message_archive(
+        &mut self,
+        _req: &ArchiveMessagesRequest,
+    ) -> Result<ArchiveMessagesResponse> {
+        Err(anyhow::anyhow!(
+            "storage.message.archive 不受支持 / storage.message.archive is not supported"
+        ))
+    }
+
+    /// 从对象存储恢复归档消息（默认不支持）/ Restore archived messages from object storage (unsupported by default)
+    ///
+    /// # 参数 / Parameters
+    /// - `req`: 恢复归档请求 / Restore archive request
+    ///
+    /// # 返回 / Returns
+    /// - `Result<RestoreMessagesResponse>`: 写回的条数 / Messages written back
+    async fn storage_message_restore(
+        &mut self,
+        _req: &RestoreMessagesRequest,
+    ) -> Result<RestoreMessagesResponse> {
+        Err(anyhow::anyhow!(
+            "storage.message.restore 不受支持 / storage.message.restore is not supported"
+        ))
+    }
+
+    /// 删除房间内 `until_ts` 之前的消息，`archive` 为真时先归档（默认不支持）
+    /// Delete a room's messages older than `until_ts`, archiving them first when `archive`
+    /// is set (unsupported by default)
+    ///
+    /// # 参数 / Parameters
+    /// - `req`: 清理请求 / Purge request
+    ///
+    /// # 返回 / Returns
+    /// - `Result<PurgeMessagesResponse>`: 删除条数与归档对象键 / Deleted count and archive object key
+    async fn storage_message_purge(
+        &mut self,
+        _req: &PurgeMessagesRequest,
+    ) -> Result<PurgeMessagesResponse> {
+        Err(anyhow::anyhow!(
+            "storage.message.purge 不受支持 / storage.message.purge is not supported"
+        ))
+    }
+
     /// 添加房间成员 / Add room member
     ///
     /// # 参数 / Parameters
@@ -132,6 +277,48 @@ pub trait StorageEventListener: Send + Sync {
         req: &RemoveRoomMemberRequest,
     ) -> Result<RemoveRoomMemberResponse>;
 
+    /// 批量加入与移除房间成员，返回更新后的成员
+    /// Add and remove room members in bulk, returning the members after the update
+    ///
+    /// 默认逐个调用 `storage_room_add_member` / `storage_room_remove_member`；能在一次写入中
+    /// 完成的存储应覆盖此方法。
+    /// Defaults to calling `storage_room_add_member` / `storage_room_remove_member` per uid;
+    /// storages that can apply it in one write should override it.
+    ///
+    /// # 参数 / Parameters
+    /// - `req`: 批量更新请求 / Bulk update request
+    ///
+    /// # 返回 / Returns
+    /// - `Result<UpdateRoomMembersResponse>`: 更新后的成员列表 / Members after the update
+    async fn storage_room_update_members(
+        &mut self,
+        req: &UpdateRoomMembersRequest,
+    ) -> Result<UpdateRoomMembersResponse> {
+        for uid in &req.add {
+            self.storage_room_add_member(&AddRoomMemberRequest {
+                room_id: req.room_id.clone(),
+                uid: uid.clone(),
+            })
+            .await?;
+        }
+        for uid in &req.remove {
+            self.storage_room_remove_member(&RemoveRoomMemberRequest {
+                room_id: req.room_id.clone(),
+                uid: uid.clone(),
+            })
+            .await?;
+        }
+        let listed = self
+            .storage_room_list_members(&GetRoomMembersRequest {
+                room_id: req.room_id.clone(),
+            })
+            .await?;
+        Ok(UpdateRoomMembersResponse {
+            status: listed.status,
+            members: listed.members,
+        })
+    }
+
     /// 列出房间的所有成员 / List all members of a room
     ///
     /// # 参数 / Parameters
@@ -143,4 +330,723 @@ pub trait StorageEventListener: Send + Sync {
         &mut self,
         req: &GetRoomMembersRequest,
     ) -> Result<GetRoomMembersResponse>;
+
+    /// 添加表情回应（默认不支持）/ Add a reaction (unsupported by default)
+    ///
+    /// 同一 `message_id` + `uid` + `emoji` 只记一次。
+    /// Each `message_id` + `uid` + `emoji` is recorded once.
+    ///
+    /// # 参数 / Parameters
+    /// - `req`: 添加表情回应请求 / Add reaction request
+    ///
+    /// # 返回 / Returns
+    /// - `Result<AddReactionResponse>`: 已存在时 `changed` 为 false / `changed` is false when it already existed
+    async fn storage_reaction_add(
+        &mut self,
+        _req: &AddReactionRequest,
+    ) -> Result<AddReactionResponse> {
+        Err(anyhow::anyhow!(
+            "storage.reaction.add 不受支持 / storage.reaction.add is not supported"
+        ))
+    }
+
+    /// 移除表情回应（默认不支持）/ Remove a reaction (unsupported by default)
+    ///
+    /// # 参数 / Parameters
+    /// - `req`: 移除表情回应请求 / Remove reaction request
+    ///
+    /// # 返回 / Returns
+    /// - `Result<RemoveReactionResponse>`: 不存在时 `changed` 为 false / `changed` is false when it did not exist
+    async fn storage_reaction_remove(
+        &mut self,
+        _req: &RemoveReactionRequest,
+    ) -> Result<RemoveReactionResponse> {
+        Err(anyhow::anyhow!(
+            "storage.reaction.remove 不受支持 / storage.reaction.remove is not supported"
+        ))
+    }
+
+    /// 按表情汇总一条消息的回应（默认不支持）/ List a message's reactions aggregated by emoji (unsupported by default)
+    ///
+    /// # 参数 / Parameters
+    /// - `req`: 列出表情回应请求 / List reactions request
+    ///
+    /// # 返回 / Returns
+    /// - `Result<ListReactionsResponse>`: 按表情排序的汇总 / Aggregates sorted by emoji
+    async fn storage_reaction_list(
+        &mut self,
+        _req: &ListReactionsRequest,
+    ) -> Result<ListReactionsResponse> {
+        Err(anyhow::anyhow!(
+            "storage.reaction.list 不受支持 / storage.reaction.list is not supported"
+        ))
+    }
+
+    /// 置顶房间消息（默认不支持）/ Pin a room message (unsupported by default)
+    ///
+    /// 房间置顶数达到 `max_pins`（大于 0 时）时不写入并返回 `full`。
+    /// When the room already has `max_pins` pins (if above 0), nothing is written and `full` is set.
+    ///
+    /// # 参数 / Parameters
+    /// - `req`: 置顶消息请求 / Pin message request
+    ///
+    /// # 返回 / Returns
+    /// - `Result<AddPinResponse>`: 已置顶时 `changed` 为 false / `changed` is false when already pinned
+    async fn storage_pin_add(&mut self, _req: &AddPinRequest) -> Result<AddPinResponse> {
+        Err(anyhow::anyhow!(
+            "storage.pin.add 不受支持 / storage.pin.add is not supported"
+        ))
+    }
+
+    /// 取消置顶（默认不支持）/ Unpin a room message (unsupported by default)
+    ///
+    /// # 参数 / Parameters
+    /// - `req`: 取消置顶请求 / Unpin message request
+    ///
+    /// # 返回 / Returns
+    /// - `Result<RemovePinResponse>`: 未置顶时 `changed` 为 false / `changed` is false when not pinned
+    async fn storage_pin_remove(&mut self, _req: &RemovePinRequest) -> Result<RemovePinResponse> {
+        Err(anyhow::anyhow!(
+            "storage.pin.remove 不受支持 / storage.pin.remove is not supported"
+        ))
+    }
+
+    /// 列出房间置顶消息（默认不支持）/ List a room's pinned messages (unsupported by default)
+    ///
+    /// # 参数 / Parameters
+    /// - `req`: 列出置顶请求 / List pins request
+    ///
+    /// # 返回 / Returns
+    /// - `Result<ListPinsResponse>`: 按置顶时间排序 / Sorted by pin time
+    async fn storage_pin_list(&mut self, _req: &ListPinsRequest) -> Result<ListPinsResponse> {
+        Err(anyhow::anyhow!(
+            "storage.pin.list 不受支持 / storage.pin.list is not supported"
+        ))
+    }
+
+    /// 登记群消息应确认的成员（默认不支持）/ Register the members expected to ack a group message (unsupported by default)
+    ///
+    /// 记录以 `message_id` 为键，重复登记时覆盖。
+    /// Keyed by `message_id`; registering again replaces the entry.
+    ///
+    /// # 参数 / Parameters
+    /// - `req`: 登记请求 / Registration request
+    ///
+    /// # 返回 / Returns
+    /// - `Result<TrackGroupAckResponse>`: 登记结果 / Registration result
+    async fn storage_group_ack_track(
+        &mut self,
+        _req: &TrackGroupAckRequest,
+    ) -> Result<TrackGroupAckResponse> {
+        Err(anyhow::anyhow!(
+            "storage.group_ack.track 不受支持 / storage.group_ack.track is not supported"
+        ))
+    }
+
+    /// 记录群成员确认（默认不支持）/ Record a group member's ack (unsupported by default)
+    ///
+    /// 确认以 `message_id:uid` 为键，只记第一次。
+    /// Acks are keyed by `message_id:uid`; only the first one is kept.
+    ///
+    /// # 参数 / Parameters
+    /// - `req`: 确认请求 / Ack request
+    ///
+    /// # 返回 / Returns
+    /// - `Result<AddGroupAckResponse>`: 消息未登记或 uid 不是应确认成员时 `recorded` 为 false
+    ///   / `recorded` is false for unregistered messages or uids not expected to ack
+    async fn storage_group_ack_add(
+        &mut self,
+        _req: &AddGroupAckRequest,
+    ) -> Result<AddGroupAckResponse> {
+        Err(anyhow::anyhow!(
+            "storage.group_ack.add 不受支持 / storage.group_ack.add is not supported"
+        ))
+    }
+
+    /// 查询群消息确认进度（默认不支持）/ Read a group message's ack progress (unsupported by default)
+    ///
+    /// # 参数 / Parameters
+    /// - `req`: 查询请求 / Status request
+    ///
+    /// # 返回 / Returns
+    /// - `Result<GroupAckStatusResponse>`: 消息未登记时 `found` 为 false / `found` is false for unregistered messages
+    async fn storage_group_ack_status(
+        &mut self,
+        _req: &GroupAckStatusRequest,
+    ) -> Result<GroupAckStatusResponse> {
+        Err(anyhow::anyhow!(
+            "storage.group_ack.status 不受支持 / storage.group_ack.status is not supported"
+        ))
+    }
+
+    /// 停机前将未落盘的写入刷到磁盘（默认无操作）/ Flush pending writes to disk before shutdown (no-op by default)
+    ///
+    /// 宿主在停止插件进程前、以及持久化策略要求同步落盘时发送 `storage.flush` 并等待响应；
+    /// 带写缓冲的实现应覆盖此方法。
+    /// The host sends `storage.flush` and awaits the reply before stopping the plugin
+    /// process and whenever a persistence policy asks for a synchronous flush;
+    /// implementations that buffer writes should override this.
+    async fn storage_flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// 运行时配置更新（默认忽略）/ Live config update (ignored by default)
+    ///
+    /// # 参数 / Parameters
+    /// - `config`: 宿主下发的本插件配置子树 / This plugin's config subtree pushed by the host
+    async fn on_config_update(&mut self, _config: &serde_json::Value) -> Result<()> {
+        Ok(())
+    }
+}
+
+// ============================================================================
+// 宿主 JSON 载荷 / Host JSON Payloads
+// ============================================================================
+//
+// 宿主的 `send_storage_event` 以 JSON 发送请求并按 JSON 解析响应，
+// 这里把 JSON 映射到 Protobuf 请求/响应类型，字段名与宿主保持一致。
+// The host's `send_storage_event` sends JSON requests and parses JSON responses;
+// these map JSON onto the Protobuf request/response types using the host's field names.
+
+/// 从宿主 JSON 构造请求 / Build a request from host JSON
+pub(crate) trait FromHostJson: Sized {
+    fn from_host_json(v: &Value) -> Self;
+}
+
+/// 响应转为宿主 JSON / Convert a response to host JSON
+pub(crate) trait ToHostJson {
+    fn to_host_json(&self) -> Value;
+}
+
+/// 取第一个存在的字符串字段 / First present string field
+fn str_of(v: &Value, keys: &[&str]) -> String {
+    keys.iter()
+        .find_map(|k| v.get(*k).and_then(Value::as_str))
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn i64_of(v: &Value, key: &str) -> i64 {
+    v.get(key).and_then(Value::as_i64).unwrap_or_default()
+}
+
+/// 宿主的 content 可能是 JSON 对象，统一存为字符串
+/// Host content may be a JSON object; always stored as a string
+fn content_of(v: &Value) -> String {
+    match v.get("content") {
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// 存储的 content 能解析为 JSON 时按 JSON 返回 / Return stored content as JSON when it parses
+fn content_json(content: &str) -> Value {
+    serde_json::from_str(content).unwrap_or_else(|_| Value::String(content.to_string()))
+}
+
+fn ids_of(v: &Value) -> Vec<String> {
+    strs_of(v, "message_ids")
+}
+
+fn strs_of(v: &Value, key: &str) -> Vec<String> {
+    v.get(key)
+        .and_then(Value::as_array)
+        .map(|ids| {
+            ids.iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 宿主未给 limit 时的默认条数 / Default limit when the host omits one
+const DEFAULT_JSON_LIMIT: i32 = 100;
+
+fn limit_of(v: &Value) -> i32 {
+    v.get("limit")
+        .and_then(Value::as_i64)
+        .map(|l| l.clamp(0, i32::MAX as i64) as i32)
+        .unwrap_or(DEFAULT_JSON_LIMIT)
+}
+
+impl FromHostJson for SaveMessageRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            message_id: str_of(v, &["message_id"]),
+            from_uid: str_of(v, &["from_uid"]),
+            to_uid: str_of(v, &["to_uid"]),
+            content: content_of(v),
+            timestamp: i64_of(v, "timestamp"),
+            msg_type: str_of(v, &["msg_type"]),
+        }
+    }
+}
+
+impl FromHostJson for SaveOfflineMessageRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            message_id: str_of(v, &["message_id"]),
+            to_uid: str_of(v, &["to_uid"]),
+            from_uid: str_of(v, &["from_uid"]),
+            content: content_of(v),
+            timestamp: i64_of(v, "timestamp"),
+            priority: str_of(v, &["priority"]),
+        }
+    }
+}
+
+impl FromHostJson for PullOfflineMessagesRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            uid: str_of(v, &["uid", "to_uid"]),
+            limit: limit_of(v),
+        }
+    }
+}
+
+impl FromHostJson for AckOfflineMessagesRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            uid: str_of(v, &["uid", "to_uid"]),
+            message_ids: ids_of(v),
+        }
+    }
+}
+
+impl FromHostJson for CountOfflineMessagesRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            uid: str_of(v, &["uid", "to_uid"]),
+        }
+    }
+}
+
+impl FromHostJson for DeleteOfflineMessagesRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            uid: str_of(v, &["uid", "to_uid"]),
+            message_ids: ids_of(v),
+        }
+    }
+}
+
+impl FromHostJson for MessageHistoryRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            uid: str_of(v, &["uid"]),
+            peer_uid: str_of(v, &["peer_uid", "peer"]),
+            since_ts: i64_of(v, "since_ts"),
+            until_ts: i64_of(v, "until_ts"),
+            limit: limit_of(v),
+        }
+    }
+}
+
+impl FromHostJson for SearchMessagesRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            uid: str_of(v, &["uid"]),
+            query: str_of(v, &["query", "q"]),
+            limit: limit_of(v),
+            cursor: str_of(v, &["cursor"]),
+        }
+    }
+}
+
+impl FromHostJson for GetMessageRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            message_id: str_of(v, &["message_id"]),
+        }
+    }
+}
+
+impl FromHostJson for ListThreadRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            parent_id: str_of(v, &["parent_id", "message_id"]),
+            limit: limit_of(v),
+        }
+    }
+}
+
+impl FromHostJson for ArchiveMessagesRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            since_ts: i64_of(v, "since_ts"),
+            until_ts: i64_of(v, "until_ts"),
+        }
+    }
+}
+
+impl FromHostJson for RestoreMessagesRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            archive_key: str_of(v, &["archive_key"]),
+        }
+    }
+}
+
+impl FromHostJson for PurgeMessagesRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            room_id: str_of(v, &["room_id"]),
+            until_ts: i64_of(v, "until_ts"),
+            archive: v
+                .get("archive")
+                .and_then(Value::as_bool)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl FromHostJson for AddRoomMemberRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            room_id: str_of(v, &["room_id"]),
+            uid: str_of(v, &["uid"]),
+        }
+    }
+}
+
+impl FromHostJson for RemoveRoomMemberRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            room_id: str_of(v, &["room_id"]),
+            uid: str_of(v, &["uid"]),
+        }
+    }
+}
+
+impl FromHostJson for UpdateRoomMembersRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            room_id: str_of(v, &["room_id"]),
+            add: strs_of(v, "add"),
+            remove: strs_of(v, "remove"),
+        }
+    }
+}
+
+impl FromHostJson for GetRoomMembersRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            room_id: str_of(v, &["room_id"]),
+        }
+    }
+}
+
+impl FromHostJson for AddReactionRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            message_id: str_of(v, &["message_id"]),
+            uid: str_of(v, &["uid"]),
+            emoji: str_of(v, &["emoji"]),
+        }
+    }
+}
+
+impl FromHostJson for RemoveReactionRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            message_id: str_of(v, &["message_id"]),
+            uid: str_of(v, &["uid"]),
+            emoji: str_of(v, &["emoji"]),
+        }
+    }
+}
+
+impl FromHostJson for ListReactionsRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            message_id: str_of(v, &["message_id"]),
+        }
+    }
+}
+
+impl FromHostJson for AddPinRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            room_id: str_of(v, &["room_id"]),
+            message_id: str_of(v, &["message_id"]),
+            pinned_by: str_of(v, &["pinned_by"]),
+            pinned_at: i64_of(v, "pinned_at"),
+            max_pins: i64_of(v, "max_pins").clamp(0, i32::MAX as i64) as i32,
+        }
+    }
+}
+
+impl FromHostJson for RemovePinRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            room_id: str_of(v, &["room_id"]),
+            message_id: str_of(v, &["message_id"]),
+        }
+    }
+}
+
+impl FromHostJson for ListPinsRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            room_id: str_of(v, &["room_id"]),
+        }
+    }
+}
+
+impl FromHostJson for TrackGroupAckRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            message_id: str_of(v, &["message_id"]),
+            room_id: str_of(v, &["room_id"]),
+            sender_uid: str_of(v, &["sender_uid"]),
+            recipients: strs_of(v, "recipients"),
+        }
+    }
+}
+
+impl FromHostJson for AddGroupAckRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            message_id: str_of(v, &["message_id"]),
+            uid: str_of(v, &["uid"]),
+            acked_at: i64_of(v, "acked_at"),
+        }
+    }
+}
+
+impl FromHostJson for GroupAckStatusRequest {
+    fn from_host_json(v: &Value) -> Self {
+        Self {
+            message_id: str_of(v, &["message_id"]),
+        }
+    }
+}
+
+impl ToHostJson for SaveMessageResponse {
+    fn to_host_json(&self) -> Value {
+        json!({"status": self.status, "message_id": self.message_id})
+    }
+}
+
+impl ToHostJson for SaveOfflineMessageResponse {
+    fn to_host_json(&self) -> Value {
+        json!({"status": self.status, "message_id": self.message_id})
+    }
+}
+
+fn offline_json(m: &OfflineMessage) -> Value {
+    json!({
+        "message_id": m.message_id,
+        "from_uid": m.from_uid,
+        "content": content_json(&m.content),
+        "timestamp": m.timestamp,
+        "priority": m.priority,
+    })
+}
+
+fn history_json(m: &HistoryMessage) -> Value {
+    json!({
+        "message_id": m.message_id,
+        "from_uid": m.from_uid,
+        "to_uid": m.to_uid,
+        "content": content_json(&m.content),
+        "timestamp": m.timestamp,
+        "msg_type": m.msg_type,
+    })
+}
+
+impl ToHostJson for PullOfflineMessagesResponse {
+    fn to_host_json(&self) -> Value {
+        json!({
+            "status": self.status,
+            "messages": self.messages.iter().map(offline_json).collect::<Vec<_>>(),
+            "total": self.total,
+        })
+    }
+}
+
+impl ToHostJson for AckOfflineMessagesResponse {
+    fn to_host_json(&self) -> Value {
+        json!({"status": self.status, "count": self.count, "removed": self.count})
+    }
+}
+
+impl ToHostJson for CountOfflineMessagesResponse {
+    fn to_host_json(&self) -> Value {
+        json!({"status": self.status, "count": self.count})
+    }
+}
+
+impl ToHostJson for DeleteOfflineMessagesResponse {
+    fn to_host_json(&self) -> Value {
+        json!({"status": self.status, "count": self.count, "deleted": self.count})
+    }
+}
+
+impl ToHostJson for MessageHistoryResponse {
+    fn to_host_json(&self) -> Value {
+        json!({
+            "status": self.status,
+            "messages": self.messages.iter().map(history_json).collect::<Vec<_>>(),
+            "total": self.total,
+        })
+    }
+}
+
+fn hit_json(h: &SearchHit) -> Value {
+    let mut hit = h
+        .message
+        .as_ref()
+        .map(history_json)
+        .unwrap_or_else(|| json!({}));
+    hit["score"] = json!(h.score);
+    hit
+}
+
+impl ToHostJson for SearchMessagesResponse {
+    fn to_host_json(&self) -> Value {
+        json!({
+            "status": self.status,
+            "hits": self.hits.iter().map(hit_json).collect::<Vec<_>>(),
+            "next_cursor": self.next_cursor,
+            "total": self.total,
+        })
+    }
+}
+
+impl ToHostJson for GetMessageResponse {
+    fn to_host_json(&self) -> Value {
+        json!({
+            "status": self.status,
+            "found": self.found,
+            "message": self.message.as_ref().filter(|_| self.found).map(history_json),
+        })
+    }
+}
+
+impl ToHostJson for ListThreadResponse {
+    fn to_host_json(&self) -> Value {
+        json!({
+            "status": self.status,
+            "messages": self.messages.iter().map(history_json).collect::<Vec<_>>(),
+            "total": self.total,
+        })
+    }
+}
+
+impl ToHostJson for ArchiveMessagesResponse {
+    fn to_host_json(&self) -> Value {
+        json!({"status": self.status, "archive_key": self.archive_key, "count": self.count})
+    }
+}
+
+impl ToHostJson for RestoreMessagesResponse {
+    fn to_host_json(&self) -> Value {
+        json!({"status": self.status, "count": self.count})
+    }
+}
+
+impl ToHostJson for PurgeMessagesResponse {
+    fn to_host_json(&self) -> Value {
+        json!({"status": self.status, "deleted": self.deleted, "archive_key": self.archive_key})
+    }
+}
+
+impl ToHostJson for AddRoomMemberResponse {
+    fn to_host_json(&self) -> Value {
+        json!({"status": self.status})
+    }
+}
+
+impl ToHostJson for RemoveRoomMemberResponse {
+    fn to_host_json(&self) -> Value {
+        json!({"status": self.status})
+    }
+}
+
+impl ToHostJson for UpdateRoomMembersResponse {
+    fn to_host_json(&self) -> Value {
+        json!({"status": self.status, "members": self.members})
+    }
+}
+
+impl ToHostJson for GetRoomMembersResponse {
+    fn to_host_json(&self) -> Value {
+        json!({"status": self.status, "members": self.members})
+    }
+}
+
+impl ToHostJson for AddReactionResponse {
+    fn to_host_json(&self) -> Value {
+        json!({"status": self.status, "changed": self.changed})
+    }
+}
+
+impl ToHostJson for RemoveReactionResponse {
+    fn to_host_json(&self) -> Value {
+        json!({"status": self.status, "changed": self.changed})
+    }
+}
+
+impl ToHostJson for ListReactionsResponse {
+    fn to_host_json(&self) -> Value {
+        let reactions: Vec<Value> = self
+            .reactions
+            .iter()
+            .map(|r| json!({"emoji": r.emoji, "count": r.count, "uids": r.uids}))
+            .collect();
+        json!({"status": self.status, "reactions": reactions})
+    }
+}
+
+impl ToHostJson for AddPinResponse {
+    fn to_host_json(&self) -> Value {
+        json!({"status": self.status, "changed": self.changed, "full": self.full})
+    }
+}
+
+impl ToHostJson for RemovePinResponse {
+    fn to_host_json(&self) -> Value {
+        json!({"status": self.status, "changed": self.changed})
+    }
+}
+
+impl ToHostJson for ListPinsResponse {
+    fn to_host_json(&self) -> Value {
+        let pins: Vec<Value> = self
+            .pins
+            .iter()
+            .map(|p| {
+                json!({"message_id": p.message_id, "pinned_by": p.pinned_by, "pinned_at": p.pinned_at})
+            })
+            .collect();
+        json!({"status": self.status, "pins": pins})
+    }
+}
+
+impl ToHostJson for TrackGroupAckResponse {
+    fn to_host_json(&self) -> Value {
+        json!({"status": self.status})
+    }
+}
+
+impl ToHostJson for AddGroupAckResponse {
+    fn to_host_json(&self) -> Value {
+        json!({"status": self.status, "recorded": self.recorded})
+    }
+}
+
+impl ToHostJson for GroupAckStatusResponse {
+    fn to_host_json(&self) -> Value {
+        json!({
+            "status": self.status,
+            "found": self.found,
+            "room_id": self.room_id,
+            "sender_uid": self.sender_uid,
+            "acked": self.acked,
+            "pending": self.pending,
+        })
+    }
 }
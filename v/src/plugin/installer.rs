@@ -8,6 +8,8 @@ use std::path::{Path, PathBuf};
 use tar::Archive;
 use tracing::{debug, info, warn};
 
+use super::manifest::PluginManifest;
+
 /// 插件安装器 / Plugin installer
 pub struct PluginInstaller {
     plugin_dir: PathBuf,
@@ -142,6 +144,25 @@ impl PluginInstaller {
         // 查找插件信息文件 / Find plugin info file
         let plugin_name = self.find_plugin_name(&temp_dir)?;
         let plugin_dir = self.plugin_dir.join(&plugin_name);
+        let candidate_subdir = temp_dir.join(&plugin_name);
+        let package_root = if candidate_subdir.is_dir() {
+            &candidate_subdir
+        } else {
+            &temp_dir
+        };
+
+        // 校验 plugin.json，无效的包不会替换已安装版本
+        // Validate plugin.json; an invalid package leaves the installed version alone
+        let manifest = match PluginManifest::load(package_root) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&temp_dir);
+                return Err(anyhow!("Invalid plugin package {}: {}", plugin_name, e));
+            }
+        };
+        for warning in manifest.warnings() {
+            warn!("Plugin {}: {}", plugin_name, warning);
+        }
 
         // 如果已存在，先删除 / Remove if exists
         if plugin_dir.exists() {
@@ -153,8 +174,7 @@ impl PluginInstaller {
         }
 
         // 如果解压目录下存在同名子目录，则移动子目录，否则移动整个解压目录
-        let candidate_subdir = temp_dir.join(&plugin_name);
-        if candidate_subdir.exists() && candidate_subdir.is_dir() {
+        if candidate_subdir.is_dir() {
             fs::rename(&candidate_subdir, &plugin_dir)?;
             let _ = fs::remove_dir_all(&temp_dir);
         } else {
@@ -0,0 +1,267 @@
+//! # plugin.json 清单校验 / plugin.json manifest validation
+//!
+//! 安装与启动插件时严格校验 `plugin.json`：必填字段、字段类型、semver 版本号。
+//! 未知的能力名称与顶层字段只产生警告，便于尽早发现拼写错误（如 `"storge"`）。
+//! `plugin.json` is validated strictly at install and start: required fields, field
+//! types and a semver version. Unknown capability names and top-level fields only
+//! produce warnings so typos (e.g. `"storge"`) surface early.
+
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+use std::path::Path;
+
+/// 清单文件名 / Manifest file name
+pub const MANIFEST_FILE: &str = "plugin.json";
+
+/// 主题订阅能力前缀 / Capability prefix for topic subscriptions
+pub const TOPIC_CAPABILITY_PREFIX: &str = "topic:";
+
+/// 宿主识别的能力名称 / Capability names known to the host
+pub const KNOWN_CAPABILITIES: &[&str] = &[
+    "auth",
+    "storage",
+    "message",
+    "room",
+    "user",
+    "connection",
+    "webhook",
+    "gateway.http_server",
+    "gateway.route_handler",
+    "gateway.api_proxy",
+];
+
+/// 清单中允许的顶层字段 / Top-level fields allowed in the manifest
+const KNOWN_FIELDS: &[&str] = &[
+    "plugin_no",
+    "name",
+    "version",
+    "description",
+    "author",
+    "capabilities",
+    "priority",
+    "run_after",
+    "config",
+];
+
+/// 已校验的插件清单 / Validated plugin manifest
+#[derive(Debug, Clone)]
+pub struct PluginManifest {
+    pub plugin_no: Option<String>,
+    pub name: String,
+    pub version: String,
+    pub description: Option<String>,
+    pub author: Option<String>,
+    pub capabilities: Vec<String>,
+    pub priority: Option<i32>,
+    pub run_after: Vec<String>,
+    pub config: Value,
+    /// 未识别的顶层字段 / Unrecognised top-level fields
+    pub unknown_fields: Vec<String>,
+}
+
+impl PluginManifest {
+    /// 读取并校验 `<dir>/plugin.json` / Read and validate `<dir>/plugin.json`
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| anyhow!("无法读取 / Cannot read {}: {}", path.display(), e))?;
+        Self::parse(&content)
+            .map_err(|e| anyhow!("{} 无效 / invalid {}: {}", MANIFEST_FILE, path.display(), e))
+    }
+
+    /// 解析并校验清单内容 / Parse and validate manifest content
+    pub fn parse(content: &str) -> Result<Self> {
+        let value: Value =
+            serde_json::from_str(content).map_err(|e| anyhow!("malformed JSON: {}", e))?;
+        let obj = value
+            .as_object()
+            .ok_or_else(|| anyhow!("top level must be an object"))?;
+
+        let name = required_str(obj, "name")?;
+        let version = required_str(obj, "version")?;
+        if let Err(e) = semver::Version::parse(&version) {
+            return Err(anyhow!(
+                "`version` {:?} is not valid semver: {}",
+                version,
+                e
+            ));
+        }
+
+        let capabilities = match obj.get("capabilities") {
+            Some(v) => str_array(v, "capabilities")?,
+            None => return Err(anyhow!("missing required field `capabilities`")),
+        };
+        let run_after = match obj.get("run_after") {
+            Some(v) => str_array(v, "run_after")?,
+            None => Vec::new(),
+        };
+        let priority = match obj.get("priority") {
+            None => None,
+            Some(v) => Some(
+                v.as_i64()
+                    .and_then(|p| i32::try_from(p).ok())
+                    .ok_or_else(|| anyhow!("`priority` must be a 32-bit integer, got {}", v))?,
+            ),
+        };
+        let config = match obj.get("config") {
+            None => Value::Object(Map::new()),
+            Some(v @ Value::Object(_)) => v.clone(),
+            Some(v) => return Err(anyhow!("`config` must be an object, got {}", v)),
+        };
+
+        Ok(Self {
+            plugin_no: optional_str(obj, "plugin_no")?,
+            name,
+            version,
+            description: optional_str(obj, "description")?,
+            author: optional_str(obj, "author")?,
+            capabilities,
+            priority,
+            run_after,
+            config,
+            unknown_fields: obj
+                .keys()
+                .filter(|k| !KNOWN_FIELDS.contains(&k.as_str()))
+                .cloned()
+                .collect(),
+        })
+    }
+
+    /// 非致命问题（未知能力/字段）/ Non-fatal issues (unknown capabilities / fields)
+    pub fn warnings(&self) -> Vec<String> {
+        let capabilities = self
+            .capabilities
+            .iter()
+            .filter(|c| !is_known_capability(c))
+            .map(|c| match suggest(c, KNOWN_CAPABILITIES) {
+                Some(s) => format!("unknown capability {:?} (did you mean {:?}?)", c, s),
+                None => format!("unknown capability {:?}", c),
+            });
+        let fields = self
+            .unknown_fields
+            .iter()
+            .map(|f| match suggest(f, KNOWN_FIELDS) {
+                Some(s) => format!("unknown field {:?} (did you mean {:?}?)", f, s),
+                None => format!("unknown field {:?}", f),
+            });
+        capabilities.chain(fields).collect()
+    }
+}
+
+/// 能力名称是否被宿主识别 / Whether the host recognises a capability name
+pub fn is_known_capability(capability: &str) -> bool {
+    KNOWN_CAPABILITIES.contains(&capability)
+        || capability
+            .strip_prefix(TOPIC_CAPABILITY_PREFIX)
+            .is_some_and(|topic| !topic.is_empty())
+}
+
+fn required_str(obj: &Map<String, Value>, field: &str) -> Result<String> {
+    match optional_str(obj, field)? {
+        Some(s) => Ok(s),
+        None => Err(anyhow!("missing required field `{}`", field)),
+    }
+}
+
+fn optional_str(obj: &Map<String, Value>, field: &str) -> Result<Option<String>> {
+    match obj.get(field) {
+        None => Ok(None),
+        Some(Value::String(s)) if s.trim().is_empty() => {
+            Err(anyhow!("`{}` must not be empty", field))
+        }
+        Some(Value::String(s)) => Ok(Some(s.clone())),
+        Some(v) => Err(anyhow!("`{}` must be a string, got {}", field, v)),
+    }
+}
+
+fn str_array(value: &Value, field: &str) -> Result<Vec<String>> {
+    let items = value
+        .as_array()
+        .ok_or_else(|| anyhow!("`{}` must be an array of strings, got {}", field, value))?;
+    items
+        .iter()
+        .enumerate()
+        .map(|(i, item)| match item {
+            Value::String(s) if !s.is_empty() => Ok(s.clone()),
+            other => Err(anyhow!(
+                "`{}[{}]` must be a non-empty string, got {}",
+                field,
+                i,
+                other
+            )),
+        })
+        .collect()
+}
+
+/// 编辑距离不超过 2 的最接近候选 / Closest candidate within edit distance 2
+fn suggest<'a>(word: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|c| (edit_distance(word, c), *c))
+        .filter(|(d, _)| *d <= 2)
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_manifest_and_capability_typo() {
+        let manifest = PluginManifest::parse(
+            r#"{
+                "plugin_no": "v.plugin.storage-sled",
+                "name": "v-connect-im-plugin-storage-sled",
+                "version": "0.1.0-beta.1",
+                "capabilities": ["storge", "topic:room.created"],
+                "priority": 900,
+                "run_afer": ["auth"]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.priority, Some(900));
+        assert_eq!(
+            manifest.warnings(),
+            [
+                r#"unknown capability "storge" (did you mean "storage"?)"#,
+                r#"unknown field "run_afer" (did you mean "run_after"?)"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_violations_are_precise() {
+        let err = |content: &str| PluginManifest::parse(content).unwrap_err().to_string();
+        assert_eq!(
+            err(r#"{"version": "1.0.0", "capabilities": []}"#),
+            "missing required field `name`"
+        );
+        assert!(
+            err(r#"{"name": "a", "version": "1.0", "capabilities": []}"#)
+                .starts_with(r#"`version` "1.0" is not valid semver"#)
+        );
+        assert_eq!(
+            err(r#"{"name": "a", "version": "1.0.0", "capabilities": ["auth", 3]}"#),
+            "`capabilities[1]` must be a non-empty string, got 3"
+        );
+        assert_eq!(
+            err(r#"{"name": "a", "version": "1.0.0"}"#),
+            "missing required field `capabilities`"
+        );
+    }
+}
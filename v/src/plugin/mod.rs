@@ -11,6 +11,7 @@
 pub mod client;
 pub mod events;
 pub mod installer;
+pub mod manifest;
 pub mod pdk;
 #[cfg(feature = "protobuf")]
 pub mod proto;
@@ -9,6 +9,7 @@ use serde::de::DeserializeOwned;
 use tracing::info;
 
 use super::client::{PluginClient, PluginHandler};
+use super::events::storage::{FromHostJson, ToHostJson};
 
 // 重新导出事件监听器 / Re-export event listeners
 pub use super::events::{AuthEventListener, StorageEventListener};
@@ -17,7 +18,7 @@ pub use super::events::{AuthEventListener, StorageEventListener};
 #[derive(Parser, Debug)]
 #[command(about = "v-connect-im plugin")]
 struct PluginArgs {
-    /// Unix Socket 路径 / Unix Socket path
+    /// Unix Socket 路径或 `tcp://host:port` / Unix Socket path or `tcp://host:port`
     #[arg(long)]
     socket: Option<String>,
 
@@ -38,6 +39,9 @@ struct PluginConfig {
     priority: i32,
     #[serde(default)]
     capabilities: Vec<String>,
+    /// 插件自定义配置段 / Plugin-specific config section
+    #[serde(default)]
+    config: serde_json::Value,
 }
 
 // ============================================================================
@@ -48,98 +52,136 @@ struct PluginConfig {
 // Only the following plugin types are supported:
 // - 存储插件 (Storage Plugin): 使用 run_storage_server
 // - 认证插件 (Auth Plugin): 使用 run_auth_server
+// - 网关插件 (Gateway Plugin): 使用 run_gateway_server
 //
 // ============================================================================
 
 /// 分发存储事件到对应的监听器方法 / Dispatch storage event to listener method
 ///
-/// 自动解码 Protobuf 消息并调用对应的方法
-/// Automatically decodes Protobuf message and calls corresponding method
+/// 自动解码 Protobuf 消息并调用对应的方法；宿主以 JSON 发送的载荷按 JSON 解码，
+/// 响应也以 JSON 返回
+/// Automatically decodes Protobuf message and calls corresponding method; payloads
+/// the host sends as JSON are decoded as JSON and answered with JSON
 pub async fn dispatch_storage_event(
     listener: &mut dyn StorageEventListener,
     event: &crate::plugin::protocol::EventMessage,
 ) -> Result<crate::plugin::protocol::EventResponse> {
     use crate::plugin::protocol::*;
-    use prost::Message;
+
+    let payload = event.payload.as_slice();
+    let json = is_json_payload(payload);
 
     match event.event_type.as_str() {
         "storage.message.save" => {
-            let req = SaveMessageRequest::decode(event.payload.as_slice())?;
-            let resp = listener.storage_message_save(&req).await?;
-            Ok(EventResponse {
-                status: "ok".to_string(),
-                flow: "continue".to_string(),
-                data: resp.encode_to_vec(),
-                error: String::new(),
-            })
+            let req: SaveMessageRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_message_save(&req).await?, json)
+        }
+        "storage.message.history" => {
+            let req: MessageHistoryRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_message_history(&req).await?, json)
+        }
+        "storage.message.search" => {
+            let req: SearchMessagesRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_message_search(&req).await?, json)
+        }
+        MESSAGE_GET_EVENT => {
+            let req: GetMessageRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_message_get(&req).await?, json)
+        }
+        MESSAGE_THREAD_EVENT => {
+            let req: ListThreadRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_message_thread(&req).await?, json)
+        }
+        MESSAGE_ARCHIVE_EVENT => {
+            let req: ArchiveMessagesRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_message_archive(&req).await?, json)
+        }
+        MESSAGE_RESTORE_EVENT => {
+            let req: RestoreMessagesRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_message_restore(&req).await?, json)
+        }
+        MESSAGE_PURGE_EVENT => {
+            let req: PurgeMessagesRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_message_purge(&req).await?, json)
         }
         "storage.offline.save" => {
-            let req = SaveOfflineMessageRequest::decode(event.payload.as_slice())?;
-            let resp = listener.storage_offline_save(&req).await?;
-            Ok(EventResponse {
-                status: "ok".to_string(),
-                flow: "continue".to_string(),
-                data: resp.encode_to_vec(),
-                error: String::new(),
-            })
+            let req: SaveOfflineMessageRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_offline_save(&req).await?, json)
         }
         "storage.offline.pull" => {
-            let req = PullOfflineMessagesRequest::decode(event.payload.as_slice())?;
-            let resp = listener.storage_offline_pull(&req).await?;
-            Ok(EventResponse {
-                status: "ok".to_string(),
-                flow: "continue".to_string(),
-                data: resp.encode_to_vec(),
-                error: String::new(),
-            })
+            let req: PullOfflineMessagesRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_offline_pull(&req).await?, json)
         }
         "storage.offline.ack" => {
-            let req = AckOfflineMessagesRequest::decode(event.payload.as_slice())?;
-            let resp = listener.storage_offline_ack(&req).await?;
-            Ok(EventResponse {
-                status: "ok".to_string(),
-                flow: "continue".to_string(),
-                data: resp.encode_to_vec(),
-                error: String::new(),
-            })
+            let req: AckOfflineMessagesRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_offline_ack(&req).await?, json)
         }
         "storage.offline.count" => {
-            let req = CountOfflineMessagesRequest::decode(event.payload.as_slice())?;
-            let resp = listener.storage_offline_count(&req).await?;
-            Ok(EventResponse {
-                status: "ok".to_string(),
-                flow: "continue".to_string(),
-                data: resp.encode_to_vec(),
-                error: String::new(),
-            })
+            let req: CountOfflineMessagesRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_offline_count(&req).await?, json)
+        }
+        "storage.offline.delete" => {
+            let req: DeleteOfflineMessagesRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_offline_delete(&req).await?, json)
         }
         "storage.room.add_member" => {
-            let req = AddRoomMemberRequest::decode(event.payload.as_slice())?;
-            let resp = listener.storage_room_add_member(&req).await?;
-            Ok(EventResponse {
-                status: "ok".to_string(),
-                flow: "continue".to_string(),
-                data: resp.encode_to_vec(),
-                error: String::new(),
-            })
+            let req: AddRoomMemberRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_room_add_member(&req).await?, json)
         }
         "storage.room.remove_member" => {
-            let req = RemoveRoomMemberRequest::decode(event.payload.as_slice())?;
-            let resp = listener.storage_room_remove_member(&req).await?;
-            Ok(EventResponse {
-                status: "ok".to_string(),
-                flow: "continue".to_string(),
-                data: resp.encode_to_vec(),
-                error: String::new(),
-            })
+            let req: RemoveRoomMemberRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_room_remove_member(&req).await?, json)
+        }
+        ROOM_UPDATE_MEMBERS_EVENT => {
+            let req: UpdateRoomMembersRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_room_update_members(&req).await?, json)
         }
         "storage.room.list_members" => {
-            let req = GetRoomMembersRequest::decode(event.payload.as_slice())?;
-            let resp = listener.storage_room_list_members(&req).await?;
-            Ok(EventResponse {
+            let req: GetRoomMembersRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_room_list_members(&req).await?, json)
+        }
+        REACTION_ADD_EVENT => {
+            let req: AddReactionRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_reaction_add(&req).await?, json)
+        }
+        REACTION_REMOVE_EVENT => {
+            let req: RemoveReactionRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_reaction_remove(&req).await?, json)
+        }
+        REACTION_LIST_EVENT => {
+            let req: ListReactionsRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_reaction_list(&req).await?, json)
+        }
+        PIN_ADD_EVENT => {
+            let req: AddPinRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_pin_add(&req).await?, json)
+        }
+        PIN_REMOVE_EVENT => {
+            let req: RemovePinRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_pin_remove(&req).await?, json)
+        }
+        PIN_LIST_EVENT => {
+            let req: ListPinsRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_pin_list(&req).await?, json)
+        }
+        GROUP_ACK_TRACK_EVENT => {
+            let req: TrackGroupAckRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_group_ack_track(&req).await?, json)
+        }
+        GROUP_ACK_ADD_EVENT => {
+            let req: AddGroupAckRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_group_ack_add(&req).await?, json)
+        }
+        GROUP_ACK_STATUS_EVENT => {
+            let req: GroupAckStatusRequest = decode_storage_request(payload)?;
+            storage_ok(&listener.storage_group_ack_status(&req).await?, json)
+        }
+        STORAGE_FLUSH_EVENT => {
+            listener.storage_flush().await?;
+            Ok(crate::plugin::protocol::EventResponse {
                 status: "ok".to_string(),
                 flow: "continue".to_string(),
-                data: resp.encode_to_vec(),
+                data: br#"{"status":"ok"}"#.to_vec(),
                 error: String::new(),
             })
         }
@@ -150,6 +192,43 @@ pub async fn dispatch_storage_event(
     }
 }
 
+/// 载荷是否为 JSON 对象（存储消息的 Protobuf 编码不会以 `{` 开头）
+/// Whether the payload is a JSON object (Protobuf-encoded storage messages never start with `{`)
+fn is_json_payload(payload: &[u8]) -> bool {
+    payload.first() == Some(&b'{')
+}
+
+/// 按载荷格式解码存储请求 / Decode a storage request according to the payload format
+fn decode_storage_request<T>(payload: &[u8]) -> Result<T>
+where
+    T: prost::Message + Default + FromHostJson,
+{
+    if is_json_payload(payload) {
+        let value: serde_json::Value = serde_json::from_slice(payload)?;
+        Ok(T::from_host_json(&value))
+    } else {
+        Ok(T::decode(payload)?)
+    }
+}
+
+/// 以请求相同的格式编码成功响应 / Encode a success response in the request's format
+fn storage_ok<T>(resp: &T, json: bool) -> Result<crate::plugin::protocol::EventResponse>
+where
+    T: prost::Message + ToHostJson,
+{
+    let data = if json {
+        serde_json::to_vec(&resp.to_host_json())?
+    } else {
+        resp.encode_to_vec()
+    };
+    Ok(crate::plugin::protocol::EventResponse {
+        status: "ok".to_string(),
+        flow: "continue".to_string(),
+        data,
+        error: String::new(),
+    })
+}
+
 // ============================================================================
 // 通用插件运行器 / Generic Plugin Runner
 // ============================================================================
@@ -161,7 +240,9 @@ struct PluginMetadata {
     priority: i32,
     capabilities: Vec<String>,
     socket_path: String,
+    auth_token: Option<String>,
     protocol: crate::plugin::protocol::ProtocolFormat,
+    config: serde_json::Value,
 }
 
 /// 初始化插件运行环境 / Initialize plugin runtime environment
@@ -178,7 +259,9 @@ fn init_plugin_runtime() -> Result<PluginMetadata> {
         anyhow::anyhow!("Failed to read plugin.json: {}. Path: {:?}", e, config_path)
     })?;
 
-    eprintln!("📄 plugin.json content:\n{}", config_content);
+    // 不输出文件内容：`config` 段可能含密钥，而插件 stderr 会经管理接口暴露
+    // The content is not printed: the `config` section may hold secrets, and plugin stderr is
+    // served through the admin logs endpoint
 
     let plugin_config: PluginConfig = serde_json::from_str(&config_content)
         .map_err(|e| anyhow::anyhow!("Failed to parse plugin.json: {}", e))?;
@@ -187,6 +270,7 @@ fn init_plugin_runtime() -> Result<PluginMetadata> {
     let version = plugin_config.version;
     let priority = plugin_config.priority;
     let capabilities = plugin_config.capabilities;
+    let config = plugin_config.config;
     let args = PluginArgs::parse();
 
     // 初始化日志 / Initialize logging
@@ -236,7 +320,9 @@ fn init_plugin_runtime() -> Result<PluginMetadata> {
         priority,
         capabilities,
         socket_path,
+        auth_token: std::env::var(crate::plugin::protocol::AUTH_TOKEN_ENV).ok(),
         protocol,
+        config,
     })
 }
 
@@ -252,7 +338,8 @@ fn init_plugin_runtime() -> Result<PluginMetadata> {
 /// # 类型参数 / Type Parameters
 ///
 /// * `L` - 实现了 `StorageEventListener` trait 的监听器类型
-/// * `C` - 配置类型，必须实现 Default 和 DeserializeOwned
+/// * `C` - 配置类型，必须实现 Default 和 DeserializeOwned；从 plugin.json 的 `config` 段解析
+///   Config type implementing Default and DeserializeOwned, parsed from the `config` section of plugin.json
 ///
 /// # 示例 / Example
 ///
@@ -275,7 +362,7 @@ where
     let metadata = init_plugin_runtime()?;
 
     // 创建监听器 / Create listener
-    let user_config = C::default();
+    let user_config: C = storage_config(&metadata.config)?;
     let listener = create_listener(user_config)?;
 
     let wrapper = StoragePluginWrapper {
@@ -287,10 +374,25 @@ where
         protocol: metadata.protocol,
     };
 
-    let mut client = PluginClient::new(metadata.socket_path, wrapper);
+    let mut client =
+        PluginClient::new(metadata.socket_path, wrapper).with_auth_token(metadata.auth_token);
     client.run_forever_with_ctrlc().await
 }
 
+/// 解析 plugin.json 的 `config` 段，缺省时使用默认值
+/// Parse the `config` section of plugin.json, using defaults when it is absent
+///
+/// 与网关不同，解析失败直接报错：回退到默认值会让存储插件打开错误的数据目录。
+/// Unlike the gateway, a parse failure is an error: falling back to defaults would
+/// make a storage plugin open the wrong data directory.
+fn storage_config<C: Default + DeserializeOwned>(config: &serde_json::Value) -> Result<C> {
+    if config.is_null() {
+        return Ok(C::default());
+    }
+    serde_json::from_value(config.clone())
+        .map_err(|e| anyhow::anyhow!("存储插件配置无效 / Invalid storage plugin config: {}", e))
+}
+
 /// 存储插件包装器 / Storage plugin wrapper
 struct StoragePluginWrapper {
     listener: Box<dyn StorageEventListener>,
@@ -324,6 +426,12 @@ impl PluginHandler for StoragePluginWrapper {
         Ok(())
     }
 
+    fn on_config_update(&mut self, config: &serde_json::Value) -> Result<()> {
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(self.listener.on_config_update(config))
+        })
+    }
+
     fn on_event(
         &mut self,
         event: &crate::plugin::protocol::EventMessage,
@@ -388,7 +496,8 @@ where
         protocol: metadata.protocol,
     };
 
-    let mut client = PluginClient::new(metadata.socket_path, wrapper);
+    let mut client =
+        PluginClient::new(metadata.socket_path, wrapper).with_auth_token(metadata.auth_token);
     client.run_forever_with_ctrlc().await
 }
 
@@ -425,6 +534,12 @@ impl PluginHandler for AuthPluginWrapper {
         Ok(())
     }
 
+    fn on_config_update(&mut self, config: &serde_json::Value) -> Result<()> {
+        tokio::task::block_in_place(|| {
+            tokio::runtime::Handle::current().block_on(self.listener.on_config_update(config))
+        })
+    }
+
     fn on_event(
         &mut self,
         event: &crate::plugin::protocol::EventMessage,
@@ -524,3 +639,122 @@ pub async fn dispatch_auth_event(
         _ => Err(anyhow::anyhow!("Unknown auth event: {}", event.event_type)),
     }
 }
+
+// ============================================================================
+// 网关插件专用运行器 / Gateway Plugin Runner
+// ============================================================================
+
+/// 运行网关插件服务器 / Run gateway plugin server
+///
+/// 使用 plugin.json 的 `config` 段（解析失败时取默认值）启动 HTTP 服务，
+/// 同时保持与主服务的插件连接以响应 `gateway.*` 事件
+/// Starts the HTTP service with the `config` section of plugin.json (defaults on
+/// parse failure) while keeping the plugin connection to answer `gateway.*` events
+///
+/// # 示例 / Example
+///
+/// ```no_run
+/// use v::plugin::pdk::run_gateway_server;
+/// # #[derive(Default, serde::Serialize, serde::Deserialize)]
+/// # struct MyConfig {
+/// #     port: u16,
+/// # }
+/// # async fn serve(_config: MyConfig) -> anyhow::Result<()> {
+/// #     Ok(())
+/// # }
+///
+/// #[tokio::main]
+/// async fn main() -> anyhow::Result<()> {
+///     run_gateway_server::<MyConfig, _, _>(|config| serve(config)).await
+/// }
+/// ```
+pub async fn run_gateway_server<C, F, Fut>(start_http: F) -> Result<()>
+where
+    C: Default + DeserializeOwned + serde::Serialize,
+    F: FnOnce(C) -> Fut,
+    Fut: std::future::Future<Output = Result<()>> + Send + 'static,
+{
+    let metadata = init_plugin_runtime()?;
+
+    let user_config: C = if metadata.config.is_null() {
+        C::default()
+    } else {
+        serde_json::from_value(metadata.config.clone()).unwrap_or_else(|e| {
+            tracing::warn!(
+                "⚠️  网关配置解析失败，使用默认值 / Invalid gateway config, using defaults: {}",
+                e
+            );
+            C::default()
+        })
+    };
+    let status = serde_json::to_vec(&user_config)?;
+
+    let http = tokio::spawn(start_http(user_config));
+
+    let wrapper = GatewayPluginWrapper {
+        name: Box::leak(metadata.plugin_no.into_boxed_str()),
+        version: Box::leak(metadata.version.into_boxed_str()),
+        priority: metadata.priority,
+        capabilities: metadata.capabilities,
+        protocol: metadata.protocol,
+        status,
+    };
+
+    let mut client =
+        PluginClient::new(metadata.socket_path, wrapper).with_auth_token(metadata.auth_token);
+    tokio::select! {
+        res = client.run_forever_with_ctrlc() => res,
+        res = http => res?,
+    }
+}
+
+/// 网关插件包装器 / Gateway plugin wrapper
+struct GatewayPluginWrapper {
+    name: &'static str,
+    version: &'static str,
+    priority: i32,
+    capabilities: Vec<String>,
+    protocol: crate::plugin::protocol::ProtocolFormat,
+    /// `gateway.status` 返回的生效配置（JSON）/ Effective config returned by `gateway.status` (JSON)
+    status: Vec<u8>,
+}
+
+impl PluginHandler for GatewayPluginWrapper {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn version(&self) -> &'static str {
+        self.version
+    }
+
+    fn capabilities(&self) -> Vec<String> {
+        self.capabilities.clone()
+    }
+
+    fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    fn on_event(
+        &mut self,
+        event: &crate::plugin::protocol::EventMessage,
+    ) -> Result<crate::plugin::protocol::EventResponse> {
+        // 网关通过 HTTP 服务处理请求，插件通道只用于状态查询
+        // The gateway serves requests over HTTP; the plugin channel only answers status queries
+        let data = match event.event_type.as_str() {
+            "gateway.status" => self.status.clone(),
+            _ => Vec::new(),
+        };
+        Ok(crate::plugin::protocol::EventResponse {
+            status: "ok".to_string(),
+            flow: "continue".to_string(),
+            data,
+            error: String::new(),
+        })
+    }
+
+    fn protocol(&self) -> crate::plugin::protocol::ProtocolFormat {
+        self.protocol
+    }
+}
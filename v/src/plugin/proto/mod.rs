@@ -5,3 +5,8 @@ include!("v.plugin.base.rs");
 include!("v.plugin.storage.rs");
 include!("v.plugin.auth.rs");
 include!("v.plugin.gateway.rs");
+
+/// 客户端消息协议（`vim.v1.proto`），与插件协议分开命名 / Client message protocol (`vim.v1.proto`), named apart from the plugin protocol
+pub mod im {
+    include!("v.im.rs");
+}
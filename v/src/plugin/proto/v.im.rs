@@ -0,0 +1,21 @@
+// This file is @generated by prost-build.
+/// 与 JSON 的 ImMessage 一一对应；`data` 为任意 JSON，按 UTF-8 JSON 编码放在 bytes 中
+/// Maps one-to-one to the JSON ImMessage; `data` is arbitrary JSON, carried as UTF-8 JSON bytes
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ImMessage {
+    /// 消息类型 / Message type
+    #[prost(string, tag = "1")]
+    pub r#type: ::prost::alloc::string::String,
+    /// JSON 编码的 data，空为 {} / JSON-encoded data, empty means {}
+    #[prost(bytes = "vec", tag = "2")]
+    pub data: ::prost::alloc::vec::Vec<u8>,
+    /// 目标 uid，空为无 / Target uid, empty for none
+    #[prost(string, tag = "3")]
+    pub target_uid: ::prost::alloc::string::String,
+    /// low / normal / high，空为 normal / low / normal / high, empty means normal
+    #[prost(string, tag = "4")]
+    pub priority: ::prost::alloc::string::String,
+    /// 所回复的消息ID，空为无 / Parent message_id this replies to, empty for none
+    #[prost(string, tag = "5")]
+    pub reply_to: ::prost::alloc::string::String,
+}
@@ -17,11 +17,17 @@ pub struct HandshakeRequest {
     /// 支持的协议: "protobuf" / Supported protocol
     #[prost(string, tag = "5")]
     pub protocol: ::prost::alloc::string::String,
+    /// 协议版本，缺省视为 1 / Protocol version, absent means 1
+    #[prost(uint32, tag = "6")]
+    pub protocol_version: u32,
+    /// TCP 传输的认证令牌 / Auth token for the TCP transport
+    #[prost(string, tag = "7")]
+    pub auth_token: ::prost::alloc::string::String,
 }
 /// 握手响应 / Handshake response
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct HandshakeResponse {
-    /// 状态: "ok" 或 "error" / Status: "ok" or "error"
+    /// 状态: "ok"、"error"、"incompatible" 或 "unauthorized" / Status: "ok", "error", "incompatible" or "unauthorized"
     #[prost(string, tag = "1")]
     pub status: ::prost::alloc::string::String,
     /// 可选的消息 / Optional message
@@ -33,6 +39,9 @@ pub struct HandshakeResponse {
     /// 协商后的协议 / Negotiated protocol
     #[prost(string, tag = "4")]
     pub protocol: ::prost::alloc::string::String,
+    /// 宿主协议版本 / Host protocol version
+    #[prost(uint32, tag = "5")]
+    pub protocol_version: u32,
 }
 /// 事件消息 / Event message
 #[derive(Clone, PartialEq, ::prost::Message)]
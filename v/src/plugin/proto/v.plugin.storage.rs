@@ -49,6 +49,9 @@ pub struct SaveOfflineMessageRequest {
     /// 时间戳 / Timestamp
     #[prost(int64, tag = "5")]
     pub timestamp: i64,
+    /// 优先级 low/normal/high，空为 normal / Priority low/normal/high, empty means normal
+    #[prost(string, tag = "6")]
+    pub priority: ::prost::alloc::string::String,
 }
 /// 保存离线消息响应 / Save offline message response
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -75,6 +78,9 @@ pub struct OfflineMessage {
     /// 时间戳 / Timestamp
     #[prost(int64, tag = "4")]
     pub timestamp: i64,
+    /// 优先级 / Priority
+    #[prost(string, tag = "5")]
+    pub priority: ::prost::alloc::string::String,
 }
 /// 拉取离线消息请求 / Pull offline messages request
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -136,6 +142,231 @@ pub struct CountOfflineMessagesResponse {
     #[prost(int32, tag = "2")]
     pub count: i32,
 }
+/// 删除离线消息请求 / Delete offline messages request
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DeleteOfflineMessagesRequest {
+    /// 用户UID / User UID
+    #[prost(string, tag = "1")]
+    pub uid: ::prost::alloc::string::String,
+    /// 消息ID列表，为空时删除全部 / Message ID list, empty deletes all
+    #[prost(string, repeated, tag = "2")]
+    pub message_ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// 删除离线消息响应 / Delete offline messages response
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DeleteOfflineMessagesResponse {
+    /// 状态 / Status
+    #[prost(string, tag = "1")]
+    pub status: ::prost::alloc::string::String,
+    /// 删除数量 / Deleted count
+    #[prost(int32, tag = "2")]
+    pub count: i32,
+}
+/// 历史消息 / History message
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct HistoryMessage {
+    /// 消息ID / Message ID
+    #[prost(string, tag = "1")]
+    pub message_id: ::prost::alloc::string::String,
+    /// 发送者UID / Sender UID
+    #[prost(string, tag = "2")]
+    pub from_uid: ::prost::alloc::string::String,
+    /// 接收者UID / Receiver UID
+    #[prost(string, tag = "3")]
+    pub to_uid: ::prost::alloc::string::String,
+    /// 消息内容 / Message content
+    #[prost(string, tag = "4")]
+    pub content: ::prost::alloc::string::String,
+    /// 时间戳 / Timestamp
+    #[prost(int64, tag = "5")]
+    pub timestamp: i64,
+    /// 消息类型 / Message type
+    #[prost(string, tag = "6")]
+    pub msg_type: ::prost::alloc::string::String,
+}
+/// 查询历史消息请求 / Query message history request
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MessageHistoryRequest {
+    /// 用户UID，为空不过滤 / User UID, empty means any
+    #[prost(string, tag = "1")]
+    pub uid: ::prost::alloc::string::String,
+    /// 对端UID，为空不过滤 / Peer UID, empty means any
+    #[prost(string, tag = "2")]
+    pub peer_uid: ::prost::alloc::string::String,
+    /// 起始时间戳，0 不限 / Start timestamp, 0 means unbounded
+    #[prost(int64, tag = "3")]
+    pub since_ts: i64,
+    /// 截止时间戳，0 不限 / End timestamp, 0 means unbounded
+    #[prost(int64, tag = "4")]
+    pub until_ts: i64,
+    /// 返回最近的条数 / Number of most recent messages
+    #[prost(int32, tag = "5")]
+    pub limit: i32,
+}
+/// 查询历史消息响应 / Query message history response
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MessageHistoryResponse {
+    /// 状态 / Status
+    #[prost(string, tag = "1")]
+    pub status: ::prost::alloc::string::String,
+    /// 按时间升序 / Ascending by timestamp
+    #[prost(message, repeated, tag = "2")]
+    pub messages: ::prost::alloc::vec::Vec<HistoryMessage>,
+    /// 返回数量 / Returned count
+    #[prost(int32, tag = "3")]
+    pub total: i32,
+}
+/// 按消息ID查询消息请求 / Get message by ID request
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetMessageRequest {
+    /// 消息ID / Message ID
+    #[prost(string, tag = "1")]
+    pub message_id: ::prost::alloc::string::String,
+}
+/// 按消息ID查询消息响应 / Get message by ID response
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetMessageResponse {
+    /// 状态 / Status
+    #[prost(string, tag = "1")]
+    pub status: ::prost::alloc::string::String,
+    /// 是否存在 / Whether the message exists
+    #[prost(bool, tag = "2")]
+    pub found: bool,
+    /// 消息，不存在时为空 / The message, empty when not found
+    #[prost(message, optional, tag = "3")]
+    pub message: ::core::option::Option<HistoryMessage>,
+}
+/// 列出消息回复请求 / List thread replies request
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListThreadRequest {
+    /// 父消息ID / Parent message ID
+    #[prost(string, tag = "1")]
+    pub parent_id: ::prost::alloc::string::String,
+    /// 最多返回条数 / Max replies returned
+    #[prost(int32, tag = "2")]
+    pub limit: i32,
+}
+/// 列出消息回复响应 / List thread replies response
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListThreadResponse {
+    /// 状态 / Status
+    #[prost(string, tag = "1")]
+    pub status: ::prost::alloc::string::String,
+    /// 按写入顺序 / In write order
+    #[prost(message, repeated, tag = "2")]
+    pub messages: ::prost::alloc::vec::Vec<HistoryMessage>,
+    /// 返回数量 / Returned count
+    #[prost(int32, tag = "3")]
+    pub total: i32,
+}
+/// 搜索消息请求 / Search messages request
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SearchMessagesRequest {
+    /// 用户UID（发送或接收），必填 / User UID (sender or receiver), required
+    #[prost(string, tag = "1")]
+    pub uid: ::prost::alloc::string::String,
+    /// 关键词，空白分隔，须全部命中 / Whitespace-separated terms, all must match
+    #[prost(string, tag = "2")]
+    pub query: ::prost::alloc::string::String,
+    /// 每页条数 / Page size
+    #[prost(int32, tag = "3")]
+    pub limit: i32,
+    /// 上一页返回的游标，为空从第一页开始 / Cursor from the previous page, empty for the first page
+    #[prost(string, tag = "4")]
+    pub cursor: ::prost::alloc::string::String,
+}
+/// 搜索命中 / Search hit
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SearchHit {
+    /// 命中的消息 / Matched message
+    #[prost(message, optional, tag = "1")]
+    pub message: ::core::option::Option<HistoryMessage>,
+    /// 相关度，越大越相关 / Relevance, higher is better
+    #[prost(double, tag = "2")]
+    pub score: f64,
+}
+/// 搜索消息响应 / Search messages response
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SearchMessagesResponse {
+    /// 状态 / Status
+    #[prost(string, tag = "1")]
+    pub status: ::prost::alloc::string::String,
+    /// 按相关度降序 / Descending by relevance
+    #[prost(message, repeated, tag = "2")]
+    pub hits: ::prost::alloc::vec::Vec<SearchHit>,
+    /// 下一页游标，为空表示没有更多 / Next page cursor, empty when exhausted
+    #[prost(string, tag = "3")]
+    pub next_cursor: ::prost::alloc::string::String,
+    /// 命中总数 / Total number of matches
+    #[prost(int32, tag = "4")]
+    pub total: i32,
+}
+/// 归档消息请求：导出 [since_ts, until_ts) 内的消息 / Archive request: export messages in [since_ts, until_ts)
+#[derive(Clone, Copy, PartialEq, ::prost::Message)]
+pub struct ArchiveMessagesRequest {
+    /// 起始时间戳（含）/ Start timestamp, inclusive
+    #[prost(int64, tag = "1")]
+    pub since_ts: i64,
+    /// 截止时间戳（不含），必须大于 since_ts / End timestamp, exclusive, must exceed since_ts
+    #[prost(int64, tag = "2")]
+    pub until_ts: i64,
+}
+/// 归档消息响应 / Archive messages response
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ArchiveMessagesResponse {
+    /// 状态 / Status
+    #[prost(string, tag = "1")]
+    pub status: ::prost::alloc::string::String,
+    /// 归档对象键，恢复时使用 / Archive object key, used for restore
+    #[prost(string, tag = "2")]
+    pub archive_key: ::prost::alloc::string::String,
+    /// 导出条数 / Exported count
+    #[prost(int32, tag = "3")]
+    pub count: i32,
+}
+/// 恢复归档请求 / Restore archive request
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RestoreMessagesRequest {
+    /// 归档对象键 / Archive object key
+    #[prost(string, tag = "1")]
+    pub archive_key: ::prost::alloc::string::String,
+}
+/// 恢复归档响应 / Restore archive response
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RestoreMessagesResponse {
+    /// 状态 / Status
+    #[prost(string, tag = "1")]
+    pub status: ::prost::alloc::string::String,
+    /// 写回的条数（已存在的不计）/ Messages written back (existing ones not counted)
+    #[prost(int32, tag = "2")]
+    pub count: i32,
+}
+/// 清理房间过期消息：删除房间内时间戳早于 until_ts 的消息 / Purge a room's expired messages: delete those older than until_ts
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PurgeMessagesRequest {
+    /// 房间ID / Room ID
+    #[prost(string, tag = "1")]
+    pub room_id: ::prost::alloc::string::String,
+    /// 截止时间戳（不含）/ Cutoff timestamp, exclusive
+    #[prost(int64, tag = "2")]
+    pub until_ts: i64,
+    /// 删除前先归档到对象存储 / Archive to object storage before deleting
+    #[prost(bool, tag = "3")]
+    pub archive: bool,
+}
+/// 清理房间过期消息响应 / Purge messages response
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PurgeMessagesResponse {
+    /// 状态 / Status
+    #[prost(string, tag = "1")]
+    pub status: ::prost::alloc::string::String,
+    /// 删除条数 / Deleted count
+    #[prost(int32, tag = "2")]
+    pub deleted: i32,
+    /// 归档对象键，未归档时为空 / Archive object key, empty when nothing was archived
+    #[prost(string, tag = "3")]
+    pub archive_key: ::prost::alloc::string::String,
+}
 /// 添加房间成员请求 / Add room member request
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct AddRoomMemberRequest {
@@ -170,6 +401,29 @@ pub struct RemoveRoomMemberResponse {
     #[prost(string, tag = "1")]
     pub status: ::prost::alloc::string::String,
 }
+/// 批量更新房间成员请求：一次写入加入 add、移除 remove / Bulk room membership update: add and remove in one write
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateRoomMembersRequest {
+    /// 房间ID / Room ID
+    #[prost(string, tag = "1")]
+    pub room_id: ::prost::alloc::string::String,
+    /// 加入的用户UID / UIDs to add
+    #[prost(string, repeated, tag = "2")]
+    pub add: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// 移除的用户UID / UIDs to remove
+    #[prost(string, repeated, tag = "3")]
+    pub remove: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// 批量更新房间成员响应 / Bulk room membership update response
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateRoomMembersResponse {
+    /// 状态 / Status
+    #[prost(string, tag = "1")]
+    pub status: ::prost::alloc::string::String,
+    /// 更新后的成员列表 / Members after the update
+    #[prost(string, repeated, tag = "2")]
+    pub members: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
 /// 获取房间成员请求 / Get room members request
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct GetRoomMembersRequest {
@@ -187,3 +441,236 @@ pub struct GetRoomMembersResponse {
     #[prost(string, repeated, tag = "2")]
     pub members: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
 }
+/// 添加表情回应请求 / Add reaction request
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AddReactionRequest {
+    /// 消息ID / Message ID
+    #[prost(string, tag = "1")]
+    pub message_id: ::prost::alloc::string::String,
+    /// 回应者UID / Reacting UID
+    #[prost(string, tag = "2")]
+    pub uid: ::prost::alloc::string::String,
+    /// 表情 / Emoji
+    #[prost(string, tag = "3")]
+    pub emoji: ::prost::alloc::string::String,
+}
+/// 添加表情回应响应 / Add reaction response
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AddReactionResponse {
+    /// 状态 / Status
+    #[prost(string, tag = "1")]
+    pub status: ::prost::alloc::string::String,
+    /// 此前不存在 / Whether it was not there before
+    #[prost(bool, tag = "2")]
+    pub changed: bool,
+}
+/// 移除表情回应请求 / Remove reaction request
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RemoveReactionRequest {
+    /// 消息ID / Message ID
+    #[prost(string, tag = "1")]
+    pub message_id: ::prost::alloc::string::String,
+    /// 回应者UID / Reacting UID
+    #[prost(string, tag = "2")]
+    pub uid: ::prost::alloc::string::String,
+    /// 表情 / Emoji
+    #[prost(string, tag = "3")]
+    pub emoji: ::prost::alloc::string::String,
+}
+/// 移除表情回应响应 / Remove reaction response
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RemoveReactionResponse {
+    /// 状态 / Status
+    #[prost(string, tag = "1")]
+    pub status: ::prost::alloc::string::String,
+    /// 此前存在 / Whether it was there before
+    #[prost(bool, tag = "2")]
+    pub changed: bool,
+}
+/// 单个表情的汇总 / Aggregate for one emoji
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ReactionSummary {
+    /// 表情 / Emoji
+    #[prost(string, tag = "1")]
+    pub emoji: ::prost::alloc::string::String,
+    /// 回应人数 / Number of reacting users
+    #[prost(int32, tag = "2")]
+    pub count: i32,
+    /// 回应者，按 UID 排序 / Reacting UIDs, sorted
+    #[prost(string, repeated, tag = "3")]
+    pub uids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// 列出表情回应请求 / List reactions request
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListReactionsRequest {
+    /// 消息ID / Message ID
+    #[prost(string, tag = "1")]
+    pub message_id: ::prost::alloc::string::String,
+}
+/// 列出表情回应响应 / List reactions response
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListReactionsResponse {
+    /// 状态 / Status
+    #[prost(string, tag = "1")]
+    pub status: ::prost::alloc::string::String,
+    /// 按表情排序 / Sorted by emoji
+    #[prost(message, repeated, tag = "2")]
+    pub reactions: ::prost::alloc::vec::Vec<ReactionSummary>,
+}
+/// 置顶消息请求 / Pin message request
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AddPinRequest {
+    /// 房间ID / Room ID
+    #[prost(string, tag = "1")]
+    pub room_id: ::prost::alloc::string::String,
+    /// 消息ID / Message ID
+    #[prost(string, tag = "2")]
+    pub message_id: ::prost::alloc::string::String,
+    /// 操作者UID / Pinning UID
+    #[prost(string, tag = "3")]
+    pub pinned_by: ::prost::alloc::string::String,
+    /// 置顶时间（毫秒）/ Pin time (milliseconds)
+    #[prost(int64, tag = "4")]
+    pub pinned_at: i64,
+    /// 每个房间的置顶上限，0 为不限 / Per-room pin cap, 0 for unlimited
+    #[prost(int32, tag = "5")]
+    pub max_pins: i32,
+}
+/// 置顶消息响应 / Pin message response
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AddPinResponse {
+    /// 状态 / Status
+    #[prost(string, tag = "1")]
+    pub status: ::prost::alloc::string::String,
+    /// 此前未置顶 / Whether it was not pinned before
+    #[prost(bool, tag = "2")]
+    pub changed: bool,
+    /// 已达上限而未置顶 / Not pinned because the cap was reached
+    #[prost(bool, tag = "3")]
+    pub full: bool,
+}
+/// 取消置顶请求 / Unpin message request
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RemovePinRequest {
+    /// 房间ID / Room ID
+    #[prost(string, tag = "1")]
+    pub room_id: ::prost::alloc::string::String,
+    /// 消息ID / Message ID
+    #[prost(string, tag = "2")]
+    pub message_id: ::prost::alloc::string::String,
+}
+/// 取消置顶响应 / Unpin message response
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RemovePinResponse {
+    /// 状态 / Status
+    #[prost(string, tag = "1")]
+    pub status: ::prost::alloc::string::String,
+    /// 此前已置顶 / Whether it was pinned before
+    #[prost(bool, tag = "2")]
+    pub changed: bool,
+}
+/// 置顶记录 / Pin record
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PinnedMessage {
+    /// 消息ID / Message ID
+    #[prost(string, tag = "1")]
+    pub message_id: ::prost::alloc::string::String,
+    /// 操作者UID / Pinning UID
+    #[prost(string, tag = "2")]
+    pub pinned_by: ::prost::alloc::string::String,
+    /// 置顶时间（毫秒）/ Pin time (milliseconds)
+    #[prost(int64, tag = "3")]
+    pub pinned_at: i64,
+}
+/// 列出置顶请求 / List pins request
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListPinsRequest {
+    /// 房间ID / Room ID
+    #[prost(string, tag = "1")]
+    pub room_id: ::prost::alloc::string::String,
+}
+/// 列出置顶响应 / List pins response
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListPinsResponse {
+    /// 状态 / Status
+    #[prost(string, tag = "1")]
+    pub status: ::prost::alloc::string::String,
+    /// 按置顶时间排序 / Sorted by pin time
+    #[prost(message, repeated, tag = "2")]
+    pub pins: ::prost::alloc::vec::Vec<PinnedMessage>,
+}
+/// 登记群消息应确认成员请求 / Register the members expected to ack a group message
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TrackGroupAckRequest {
+    /// 消息ID / Message ID
+    #[prost(string, tag = "1")]
+    pub message_id: ::prost::alloc::string::String,
+    /// 房间ID / Room ID
+    #[prost(string, tag = "2")]
+    pub room_id: ::prost::alloc::string::String,
+    /// 发送者UID，可为空 / Sender UID, may be empty
+    #[prost(string, tag = "3")]
+    pub sender_uid: ::prost::alloc::string::String,
+    /// 应确认的成员（不含发送者）/ Members expected to ack (sender excluded)
+    #[prost(string, repeated, tag = "4")]
+    pub recipients: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+/// 登记群消息应确认成员响应 / Register group ack members response
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TrackGroupAckResponse {
+    /// 状态 / Status
+    #[prost(string, tag = "1")]
+    pub status: ::prost::alloc::string::String,
+}
+/// 记录成员确认请求 / Record a member ack request
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AddGroupAckRequest {
+    /// 消息ID / Message ID
+    #[prost(string, tag = "1")]
+    pub message_id: ::prost::alloc::string::String,
+    /// 确认者UID / Acking UID
+    #[prost(string, tag = "2")]
+    pub uid: ::prost::alloc::string::String,
+    /// 确认时间（毫秒）/ Ack time (milliseconds)
+    #[prost(int64, tag = "3")]
+    pub acked_at: i64,
+}
+/// 记录成员确认响应 / Record a member ack response
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AddGroupAckResponse {
+    /// 状态 / Status
+    #[prost(string, tag = "1")]
+    pub status: ::prost::alloc::string::String,
+    /// 消息已登记且 uid 为应确认成员 / The message is registered and uid is an expected member
+    #[prost(bool, tag = "2")]
+    pub recorded: bool,
+}
+/// 查询群消息确认进度请求 / Group ack status request
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GroupAckStatusRequest {
+    /// 消息ID / Message ID
+    #[prost(string, tag = "1")]
+    pub message_id: ::prost::alloc::string::String,
+}
+/// 查询群消息确认进度响应 / Group ack status response
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GroupAckStatusResponse {
+    /// 状态 / Status
+    #[prost(string, tag = "1")]
+    pub status: ::prost::alloc::string::String,
+    /// 消息是否已登记 / Whether the message is registered
+    #[prost(bool, tag = "2")]
+    pub found: bool,
+    /// 房间ID / Room ID
+    #[prost(string, tag = "3")]
+    pub room_id: ::prost::alloc::string::String,
+    /// 发送者UID / Sender UID
+    #[prost(string, tag = "4")]
+    pub sender_uid: ::prost::alloc::string::String,
+    /// 已确认成员，按 UID 排序 / Acked members, sorted
+    #[prost(string, repeated, tag = "5")]
+    pub acked: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// 未确认成员，按 UID 排序 / Pending members, sorted
+    #[prost(string, repeated, tag = "6")]
+    pub pending: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
@@ -18,53 +18,93 @@ impl Default for ProtocolFormat {
 pub use super::proto::{
     AckOfflineMessagesRequest,
     AckOfflineMessagesResponse,
+    AddGroupAckRequest,
+    AddGroupAckResponse,
+    AddPinRequest,
+    AddPinResponse,
+    AddReactionRequest,
+    AddReactionResponse,
     AddRoomMemberRequest,
     AddRoomMemberResponse,
+    ArchiveMessagesRequest,
+    ArchiveMessagesResponse,
     BanUserRequest,
     BanUserResponse,
     CountOfflineMessagesRequest,
     CountOfflineMessagesResponse,
+    DeleteOfflineMessagesRequest,
+    DeleteOfflineMessagesResponse,
     EventMessage,
     EventResponse,
 
+    GetMessageRequest,
+    GetMessageResponse,
     GetRoomMembersRequest,
     GetRoomMembersResponse,
+    GroupAckStatusRequest,
+    GroupAckStatusResponse,
 
     // 基础消息 / Basic messages
     HandshakeRequest,
     HandshakeResponse,
     HealthCheckRequest,
     HealthCheckResponse,
+    HistoryMessage,
     // 网关插件消息 / Gateway plugin messages
     HttpRequest,
     HttpResponse,
     KickOutRequest,
     KickOutResponse,
+    ListPinsRequest,
+    ListPinsResponse,
+    ListReactionsRequest,
+    ListReactionsResponse,
+    ListThreadRequest,
+    ListThreadResponse,
     // 认证插件消息 / Authentication plugin messages
     LoginRequest,
     LoginResponse,
     LogoutRequest,
     LogoutResponse,
+    MessageHistoryRequest,
+    MessageHistoryResponse,
     OfflineMessage,
+    PinnedMessage,
     ProxyRequest,
     ProxyResponse,
     PullOfflineMessagesRequest,
     PullOfflineMessagesResponse,
+    PurgeMessagesRequest,
+    PurgeMessagesResponse,
+    ReactionSummary,
     RegisterRouteRequest,
     RegisterRouteResponse,
+    RemovePinRequest,
+    RemovePinResponse,
+    RemoveReactionRequest,
+    RemoveReactionResponse,
     RemoveRoomMemberRequest,
     RemoveRoomMemberResponse,
     RenewTokenRequest,
     RenewTokenResponse,
+    RestoreMessagesRequest,
+    RestoreMessagesResponse,
     // 存储插件消息 / Storage plugin messages
     SaveMessageRequest,
     SaveMessageResponse,
     SaveOfflineMessageRequest,
     SaveOfflineMessageResponse,
+    SearchHit,
+    SearchMessagesRequest,
+    SearchMessagesResponse,
     TokenReplacedRequest,
     TokenReplacedResponse,
+    TrackGroupAckRequest,
+    TrackGroupAckResponse,
     UnregisterRouteRequest,
     UnregisterRouteResponse,
+    UpdateRoomMembersRequest,
+    UpdateRoomMembersResponse,
     ValidateTokenRequest,
     ValidateTokenResponse,
 
@@ -72,6 +112,123 @@ pub use super::proto::{
     WebSocketResponse,
 };
 
+/// 当前线协议版本 / Current wire protocol version
+///
+/// 不兼容的线格式变更（字段语义、事件载荷、帧格式）必须递增此值，
+/// 并同步调整 `MIN_PROTOCOL_VERSION`。见 `proto/README.md`。
+/// Incompatible wire changes (field semantics, event payloads, framing) must bump
+/// this value and revisit `MIN_PROTOCOL_VERSION`. See `proto/README.md`.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// 宿主仍接受的最低协议版本 / Oldest protocol version the host still accepts
+pub const MIN_PROTOCOL_VERSION: u32 = 2;
+
+/// 握手不兼容状态 / Handshake status for incompatible plugins
+pub const HANDSHAKE_INCOMPATIBLE: &str = "incompatible";
+
+/// 握手认证失败状态（TCP 令牌不匹配）/ Handshake status for a rejected TCP auth token
+pub const HANDSHAKE_UNAUTHORIZED: &str = "unauthorized";
+
+/// 宿主推送运行时配置更新的事件类型 / Event type the host uses to push live config updates
+pub const CONFIG_UPDATE_EVENT: &str = "config.update";
+
+/// 要求存储插件立即落盘的存储事件 / Storage event asking the storage plugin to flush to disk now
+pub const STORAGE_FLUSH_EVENT: &str = "storage.flush";
+
+/// 按消息ID查询单条消息的存储事件 / Storage event looking up a single message by ID
+pub const MESSAGE_GET_EVENT: &str = "storage.message.get";
+
+/// 按写入顺序列出回复某条消息的消息的存储事件 / Storage event listing the replies to a message in write order
+pub const MESSAGE_THREAD_EVENT: &str = "storage.message.thread";
+
+/// 添加表情回应的存储事件 / Storage event adding a reaction
+pub const REACTION_ADD_EVENT: &str = "storage.reaction.add";
+
+/// 移除表情回应的存储事件 / Storage event removing a reaction
+pub const REACTION_REMOVE_EVENT: &str = "storage.reaction.remove";
+
+/// 按表情汇总一条消息的回应的存储事件 / Storage event listing a message's reactions aggregated by emoji
+pub const REACTION_LIST_EVENT: &str = "storage.reaction.list";
+
+/// 置顶房间消息的存储事件 / Storage event pinning a room message
+pub const PIN_ADD_EVENT: &str = "storage.pin.add";
+
+/// 取消置顶的存储事件 / Storage event unpinning a room message
+pub const PIN_REMOVE_EVENT: &str = "storage.pin.remove";
+
+/// 列出房间置顶消息的存储事件 / Storage event listing a room's pinned messages
+pub const PIN_LIST_EVENT: &str = "storage.pin.list";
+
+/// 归档消息到对象存储的存储事件 / Storage event archiving messages to object storage
+pub const MESSAGE_ARCHIVE_EVENT: &str = "storage.message.archive";
+
+/// 从对象存储恢复归档消息的存储事件 / Storage event restoring archived messages from object storage
+pub const MESSAGE_RESTORE_EVENT: &str = "storage.message.restore";
+
+/// 按房间删除（可先归档）过期消息的存储事件 / Storage event deleting (optionally archiving first) a room's expired messages
+pub const MESSAGE_PURGE_EVENT: &str = "storage.message.purge";
+
+/// 登记群消息应确认成员的存储事件 / Storage event registering the members expected to ack a group message
+pub const GROUP_ACK_TRACK_EVENT: &str = "storage.group_ack.track";
+
+/// 记录群成员确认的存储事件 / Storage event recording a group member's ack
+pub const GROUP_ACK_ADD_EVENT: &str = "storage.group_ack.add";
+
+/// 查询群消息确认进度的存储事件 / Storage event reading a group message's ack progress
+pub const GROUP_ACK_STATUS_EVENT: &str = "storage.group_ack.status";
+
+/// 批量加入/移除房间成员的存储事件 / Storage event adding and removing room members in bulk
+pub const ROOM_UPDATE_MEMBERS_EVENT: &str = "storage.room.update_members";
+
+/// TCP 传输地址前缀，如 `tcp://127.0.0.1:9700` / TCP transport prefix, e.g. `tcp://127.0.0.1:9700`
+pub const TCP_SCHEME: &str = "tcp://";
+
+/// TCP 认证令牌环境变量（宿主启动插件时注入，sidecar 部署时手动设置）
+/// Env var carrying the TCP auth token (set by the host when spawning, or by the sidecar deployment)
+pub const AUTH_TOKEN_ENV: &str = "V_PLUGIN_AUTH_TOKEN";
+
+/// 插件连接地址为 TCP 时返回 `host:port` / `host:port` when the plugin address is TCP
+pub fn tcp_address(address: &str) -> Option<&str> {
+    address.strip_prefix(TCP_SCHEME)
+}
+
+/// 握手中的协议版本；未填写（旧插件）视为 1
+/// Protocol version from a handshake; unset (old plugins) counts as 1
+pub fn handshake_protocol_version(raw: u32) -> u32 {
+    raw.max(1)
+}
+
+/// 协议版本是否在宿主支持范围内 / Whether a protocol version is within the host's supported range
+pub fn is_supported_protocol_version(version: u32) -> bool {
+    (MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&version)
+}
+
+/// 拦截消息的流控值 / Flow value that blocks a message
+pub const FLOW_STOP: &str = "stop";
+
+impl EventResponse {
+    /// 拦截消息并附带原因，宿主会以 `message_rejected` 转告发送者
+    /// Block a message with a reason; the host relays it to the sender as `message_rejected`
+    ///
+    /// `data` 为 `{"flow": "stop", "code", "reason"}`，`error` 同为 `reason`。
+    /// `data` is `{"flow": "stop", "code", "reason"}` and `error` repeats `reason`.
+    pub fn reject(code: impl Into<String>, reason: impl Into<String>) -> Self {
+        let code = code.into();
+        let reason = reason.into();
+        let data = serde_json::json!({
+            "flow": FLOW_STOP,
+            "code": code,
+            "reason": reason,
+        });
+        Self {
+            status: "ok".to_string(),
+            flow: FLOW_STOP.to_string(),
+            data: serde_json::to_vec(&data).unwrap_or_default(),
+            error: reason,
+        }
+    }
+}
+
 /// 协议协商（仅支持 Protobuf）/ Protocol negotiation (Protobuf only)
 pub fn negotiate_protocol(_client_protocol: &str) -> ProtocolFormat {
     ProtocolFormat::Protobuf
@@ -0,0 +1,256 @@
+//! 批量写入 / Batch writes
+//!
+//! 供基于 SQL 的 [`Repository`](super::Repository) 实现 `create_many` / `update_many`：
+//! 插入按后端的绑定参数上限分块生成多行 `INSERT`，更新逐行按主键执行，二者都在同一事务内完成，
+//! 任一失败整体回滚；返回值为各语句影响行数之和。
+//! Backing for `create_many` / `update_many` in SQL-based [`Repository`](super::Repository)
+//! implementations: inserts are chunked into multi-row `INSERT`s by the backend's bind parameter
+//! limit, updates run one statement per row by primary key, both inside a single transaction that
+//! rolls back as a whole on failure; the result is the summed affected rows.
+
+use crate::db::error::{DbError, Result};
+use crate::db::manager::DbPool;
+use crate::db::model::{ColType, ModelSpec};
+use serde_json::{Map, Value};
+use sqlx::QueryBuilder;
+
+/// PostgreSQL / MySQL 单条语句的绑定参数上限（协议以 u16 计数）
+/// Bind parameter limit per statement for PostgreSQL / MySQL (u16 count on the wire)
+pub const MAX_PARAMS_PG_MYSQL: usize = 65_535;
+/// SQLite 3.32+ 的 `SQLITE_MAX_VARIABLE_NUMBER` 默认值 / SQLite 3.32+ default `SQLITE_MAX_VARIABLE_NUMBER`
+pub const MAX_PARAMS_SQLITE: usize = 32_766;
+
+/// 后端单条语句可绑定的参数数 / Bind parameters a backend accepts per statement
+pub fn max_params(pool: &DbPool) -> usize {
+    match pool {
+        DbPool::Postgres(_) => MAX_PARAMS_PG_MYSQL,
+        #[cfg(feature = "sqlite")]
+        DbPool::Sqlite(_) => MAX_PARAMS_SQLITE,
+        #[cfg(feature = "mysql")]
+        DbPool::MySql(_) => MAX_PARAMS_PG_MYSQL,
+    }
+}
+
+/// 每个 `INSERT` 分块的行数（至少 1）/ Rows per `INSERT` chunk (at least 1)
+pub fn rows_per_chunk(max_params: usize, columns: usize) -> usize {
+    (max_params / columns.max(1)).max(1)
+}
+
+/// 按列类型取出的绑定值 / Bind value extracted per column type
+pub(super) enum BindValue {
+    Text(String),
+    Int64(i64),
+    Int16(i16),
+    Bool(bool),
+    Json(Value),
+    ArrayText(Vec<String>),
+}
+
+impl BindValue {
+    /// 与 `insert_one_spec` 相同的取值与缺省规则 / Same extraction and defaults as `insert_one_spec`
+    pub(super) fn from_field(ty: ColType, value: Option<&Value>) -> Self {
+        match ty {
+            ColType::Text | ColType::Timestamp => {
+                BindValue::Text(value.and_then(|v| v.as_str()).unwrap_or("").to_string())
+            }
+            ColType::Int64 => BindValue::Int64(value.and_then(|v| v.as_i64()).unwrap_or(0)),
+            ColType::Int16 => BindValue::Int16(value.and_then(|v| v.as_i64()).unwrap_or(0) as i16),
+            ColType::Bool => BindValue::Bool(value.and_then(|v| v.as_bool()).unwrap_or(false)),
+            ColType::Json => BindValue::Json(value.cloned().unwrap_or(Value::Null)),
+            ColType::ArrayText => BindValue::ArrayText(
+                value
+                    .and_then(|v| v.as_array())
+                    .map(|a| {
+                        a.iter()
+                            .filter_map(|x| x.as_str().map(|s| s.to_string()))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            ),
+        }
+    }
+}
+
+/// 绑定一个值；`native` 用于 PostgreSQL 的 JSON/数组类型，`text` 在其余后端将二者以 JSON 文本存储
+/// Bind one value; `native` uses PostgreSQL JSON/array types, `text` stores both as JSON text elsewhere
+macro_rules! push_value {
+    ($b:expr, $value:expr, native) => {
+        match $value {
+            BindValue::Text(s) => $b.push_bind(s),
+            BindValue::Int64(n) => $b.push_bind(n),
+            BindValue::Int16(n) => $b.push_bind(n),
+            BindValue::Bool(v) => $b.push_bind(v),
+            BindValue::Json(v) => $b.push_bind(sqlx::types::Json(v)),
+            BindValue::ArrayText(a) => $b.push_bind(a),
+        }
+    };
+    ($b:expr, $value:expr, text) => {
+        match $value {
+            BindValue::Text(s) => $b.push_bind(s),
+            BindValue::Int64(n) => $b.push_bind(n),
+            BindValue::Int16(n) => $b.push_bind(n),
+            BindValue::Bool(v) => $b.push_bind(v),
+            BindValue::Json(v) => $b.push_bind(v.to_string()),
+            BindValue::ArrayText(a) => $b.push_bind(serde_json::Value::from(a).to_string()),
+        }
+    };
+}
+
+/// 在一个事务内按分块执行多行插入 / Run chunked multi-row inserts inside one transaction
+macro_rules! insert_chunks {
+    ($pool:expr, $db:ty, $mode:ident, $prefix:expr, $rows:expr, $per_chunk:expr) => {{
+        let mut tx = $pool.begin().await?;
+        let mut affected = 0u64;
+        let mut rows = $rows.into_iter().peekable();
+        while rows.peek().is_some() {
+            let chunk: Vec<Vec<BindValue>> = rows.by_ref().take($per_chunk).collect();
+            let mut qb = QueryBuilder::<$db>::new($prefix);
+            qb.push_values(chunk, |mut b, row| {
+                for value in row {
+                    push_value!(b, value, $mode);
+                }
+            });
+            affected += qb.build().execute(&mut *tx).await?.rows_affected();
+        }
+        tx.commit().await?;
+        affected
+    }};
+}
+
+/// 在一个事务内逐行按主键更新 / Update row by row by primary key inside one transaction
+macro_rules! update_rows {
+    ($pool:expr, $db:ty, $mode:ident, $prefix:expr, $set:expr, $pk:expr, $rows:expr) => {{
+        let mut tx = $pool.begin().await?;
+        let mut affected = 0u64;
+        for (values, key) in $rows {
+            let mut qb = QueryBuilder::<$db>::new($prefix);
+            for (i, (name, value)) in $set.iter().zip(values).enumerate() {
+                if i > 0 {
+                    qb.push(", ");
+                }
+                qb.push(name).push(" = ");
+                push_value!(qb, value, $mode);
+            }
+            qb.push(" WHERE ").push($pk).push(" = ");
+            push_value!(qb, key, $mode);
+            affected += qb.build().execute(&mut *tx).await?.rows_affected();
+        }
+        tx.commit().await?;
+        affected
+    }};
+}
+
+/// 标识符引用：MySQL 用反引号，其余用双引号 / Identifier quoting: backticks on MySQL, double quotes elsewhere
+pub(super) fn quote(pool: &DbPool, ident: &str) -> String {
+    match pool {
+        #[cfg(feature = "mysql")]
+        DbPool::MySql(_) => format!("`{}`", ident),
+        #[allow(unreachable_patterns)]
+        _ => format!("\"{}\"", ident),
+    }
+}
+
+pub(super) fn object<T: serde::Serialize>(item: &T) -> Result<Map<String, Value>> {
+    match serde_json::to_value(item)? {
+        Value::Object(obj) => Ok(obj),
+        _ => Err(DbError::Config("expected object".to_string())),
+    }
+}
+
+/// 批量插入，返回影响行数之和 / Batch insert and return the summed affected rows
+///
+/// 列来自 [`ModelSpec::columns`]；每块行数 = 后端参数上限 / 列数。
+/// Columns come from [`ModelSpec::columns`]; rows per chunk = backend parameter limit / column count.
+pub async fn insert_many<T: serde::Serialize + ModelSpec>(
+    pool: &DbPool,
+    models: &[T],
+) -> Result<u64> {
+    if models.is_empty() {
+        return Ok(0);
+    }
+    let cols = T::columns();
+    let rows = models
+        .iter()
+        .map(|m| {
+            let obj = object(m)?;
+            Ok(cols
+                .iter()
+                .map(|c| BindValue::from_field(c.ty, obj.get(c.name)))
+                .collect())
+        })
+        .collect::<Result<Vec<Vec<BindValue>>>>()?;
+    let prefix = format!(
+        "INSERT INTO {} ({}) ",
+        quote(pool, T::table_name()),
+        cols.iter()
+            .map(|c| quote(pool, c.name))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let per_chunk = rows_per_chunk(max_params(pool), cols.len());
+    let affected = match pool {
+        DbPool::Postgres(p) => insert_chunks!(p, sqlx::Postgres, native, &prefix, rows, per_chunk),
+        #[cfg(feature = "sqlite")]
+        DbPool::Sqlite(p) => insert_chunks!(p, sqlx::Sqlite, text, &prefix, rows, per_chunk),
+        #[cfg(feature = "mysql")]
+        DbPool::MySql(p) => insert_chunks!(p, sqlx::MySql, text, &prefix, rows, per_chunk),
+    };
+    Ok(affected)
+}
+
+/// 批量按主键更新，返回影响行数之和 / Batch update by primary key and return the summed affected rows
+///
+/// `pk` 必须是 [`ModelSpec::columns`] 中的列；其余列全部写入。
+/// `pk` must be one of [`ModelSpec::columns`]; every other column is written.
+pub async fn update_many<T: serde::Serialize + ModelSpec>(
+    pool: &DbPool,
+    pk: &str,
+    models: &[T],
+) -> Result<u64> {
+    if models.is_empty() {
+        return Ok(0);
+    }
+    let cols = T::columns();
+    let key_col = cols.iter().find(|c| c.name == pk).ok_or_else(|| {
+        DbError::Config(format!(
+            "主键列 {} 不在 ModelSpec 中 / primary key column {} not in ModelSpec",
+            pk, pk
+        ))
+    })?;
+    let set_cols: Vec<_> = cols.iter().filter(|c| c.name != pk).collect();
+    let set: Vec<String> = set_cols.iter().map(|c| quote(pool, c.name)).collect();
+    let rows = models
+        .iter()
+        .map(|m| {
+            let obj = object(m)?;
+            let values: Vec<BindValue> = set_cols
+                .iter()
+                .map(|c| BindValue::from_field(c.ty, obj.get(c.name)))
+                .collect();
+            Ok((values, BindValue::from_field(key_col.ty, obj.get(pk))))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let prefix = format!("UPDATE {} SET ", quote(pool, T::table_name()));
+    let key = quote(pool, pk);
+    let affected = match pool {
+        DbPool::Postgres(p) => update_rows!(p, sqlx::Postgres, native, &prefix, set, &key, rows),
+        #[cfg(feature = "sqlite")]
+        DbPool::Sqlite(p) => update_rows!(p, sqlx::Sqlite, text, &prefix, set, &key, rows),
+        #[cfg(feature = "mysql")]
+        DbPool::MySql(p) => update_rows!(p, sqlx::MySql, text, &prefix, set, &key, rows),
+    };
+    Ok(affected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rows_per_chunk_respects_parameter_limit() {
+        assert_eq!(rows_per_chunk(MAX_PARAMS_SQLITE, 3), 10_922);
+        assert_eq!(rows_per_chunk(MAX_PARAMS_PG_MYSQL, 7), 9_362);
+        assert_eq!(rows_per_chunk(10, 0), 10);
+        assert_eq!(rows_per_chunk(2, 5), 1);
+    }
+}
@@ -0,0 +1,275 @@
+//! 读穿缓存 / Read-through cache
+//!
+//! [`CachingRepository`] 包装任意 [`Repository`]：`read_one` 命中缓存直接返回，未命中时读内层并缓存
+//! （仅缓存存在的记录）；`update`/`update_many`/`delete` 成功与否都会失效对应主键。其余方法直接透传。
+//! [`CachingRepository`] wraps any [`Repository`]: `read_one` answers from the cache on a hit and
+//! reads through (caching only existing rows) on a miss; `update` / `update_many` / `delete`
+//! invalidate the affected keys whether or not they succeed. Everything else passes through.
+//!
+//! 一致性 / Coherence: 缓存是进程内的。集群中其他节点的写入不会失效本节点缓存，
+//! 与本节点写入并发的读也可能在失效后写回旧值，因此读到的数据最多旧一个 TTL；
+//! 仅用于可容忍 TTL 级延迟的热点数据（如系统配置行），需要强一致的读请直接使用内层仓库。
+//! The cache is per process. Writes on other cluster nodes do not invalidate it, and a read racing
+//! a local write may repopulate the old value after invalidation, so reads can be stale for up to
+//! one TTL. Use it for hot rows that tolerate TTL-bounded staleness (such as system config rows);
+//! read from the inner repository when strong consistency is needed.
+
+use super::{Repository, Result};
+use async_trait::async_trait;
+use moka::future::Cache;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+/// 带 `read_one` 缓存的仓库装饰器 / Repository decorator caching `read_one`
+pub struct CachingRepository<R, T, PK> {
+    inner: R,
+    cache: Cache<PK, T>,
+    key_of: fn(&T) -> PK,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<R, T, PK> CachingRepository<R, T, PK>
+where
+    PK: Hash + Eq + Send + Sync + 'static,
+    T: Clone + Send + Sync + 'static,
+{
+    /// 创建装饰器 / Create the decorator
+    ///
+    /// `key_of` 从模型取主键，用于更新时失效；`ttl` 为条目存活时间；`max_capacity` 为最多缓存条数（LRU 淘汰）。
+    /// `key_of` extracts the primary key for invalidation on update; `ttl` is the entry lifetime;
+    /// `max_capacity` bounds the number of cached rows (evicted least recently used first).
+    pub fn new(inner: R, key_of: fn(&T) -> PK, ttl: Duration, max_capacity: u64) -> Self {
+        Self {
+            inner,
+            cache: Cache::builder()
+                .time_to_live(ttl)
+                .max_capacity(max_capacity)
+                .build(),
+            key_of,
+            _marker: PhantomData,
+        }
+    }
+
+    /// 内层仓库 / Inner repository
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    /// 手动失效一个主键（例如收到其他节点的变更通知）/ Invalidate one key manually (e.g. on a change notice from another node)
+    pub async fn invalidate(&self, pk: &PK) {
+        self.cache.invalidate(pk).await;
+    }
+
+    /// 清空缓存 / Drop every cached row
+    pub fn invalidate_all(&self) {
+        self.cache.invalidate_all();
+    }
+}
+
+#[async_trait]
+impl<R, T, PK> Repository<T, PK> for CachingRepository<R, T, PK>
+where
+    R: Repository<T, PK> + Send + Sync,
+    PK: Hash + Eq + Clone + Send + Sync + 'static,
+    T: Clone + Send + Sync + 'static,
+{
+    async fn create(&self, model: &T) -> Result<u64> {
+        self.inner.create(model).await
+    }
+
+    async fn create_many(&self, models: &[T]) -> Result<u64>
+    where
+        T: Sync,
+        Self: Sync,
+    {
+        self.inner.create_many(models).await
+    }
+
+    async fn read_one(&self, pk: PK) -> Result<Option<T>> {
+        if let Some(hit) = self.cache.get(&pk).await {
+            return Ok(Some(hit));
+        }
+        let found = self.inner.read_one(pk.clone()).await?;
+        if let Some(model) = &found {
+            self.cache.insert(pk, model.clone()).await;
+        }
+        Ok(found)
+    }
+
+    async fn read_all(&self) -> Result<Vec<T>> {
+        self.inner.read_all().await
+    }
+
+    async fn find_by(&self, column: &str, value: serde_json::Value) -> Result<Vec<T>>
+    where
+        T: serde::Serialize + crate::db::model::ModelSpec + Send,
+        Self: Sync,
+    {
+        self.inner.find_by(column, value).await
+    }
+
+    async fn find_in(&self, column: &str, values: &[serde_json::Value]) -> Result<Vec<T>>
+    where
+        T: serde::Serialize + crate::db::model::ModelSpec + Send,
+        Self: Sync,
+    {
+        self.inner.find_in(column, values).await
+    }
+
+    async fn update(&self, model: &T) -> Result<u64> {
+        let res = self.inner.update(model).await;
+        self.cache.invalidate(&(self.key_of)(model)).await;
+        res
+    }
+
+    async fn update_many(&self, models: &[T]) -> Result<u64>
+    where
+        T: Sync,
+        Self: Sync,
+    {
+        let res = self.inner.update_many(models).await;
+        for model in models {
+            self.cache.invalidate(&(self.key_of)(model)).await;
+        }
+        res
+    }
+
+    async fn delete(&self, pk: PK) -> Result<u64> {
+        let res = self.inner.delete(pk.clone()).await;
+        self.cache.invalidate(&pk).await;
+        res
+    }
+
+    async fn page(&self, limit: i64, offset: i64) -> Result<Vec<T>> {
+        self.inner.page(limit, offset).await
+    }
+
+    async fn page_after(&self, cursor: Option<PK>, limit: i64) -> Result<(Vec<T>, Option<PK>)> {
+        self.inner.page_after(cursor, limit).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Conf {
+        key: String,
+        value: String,
+    }
+
+    /// 统计 read_one 次数的内存仓库 / In-memory repository counting read_one calls
+    #[derive(Default)]
+    struct CountingRepo {
+        rows: Mutex<HashMap<String, Conf>>,
+        reads: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl Repository<Conf, String> for CountingRepo {
+        async fn create(&self, model: &Conf) -> Result<u64> {
+            self.rows
+                .lock()
+                .unwrap()
+                .insert(model.key.clone(), model.clone());
+            Ok(1)
+        }
+        async fn read_one(&self, pk: String) -> Result<Option<Conf>> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            Ok(self.rows.lock().unwrap().get(&pk).cloned())
+        }
+        async fn read_all(&self) -> Result<Vec<Conf>> {
+            Ok(self.rows.lock().unwrap().values().cloned().collect())
+        }
+        async fn update(&self, model: &Conf) -> Result<u64> {
+            self.create(model).await
+        }
+        async fn delete(&self, pk: String) -> Result<u64> {
+            Ok(self.rows.lock().unwrap().remove(&pk).map_or(0, |_| 1))
+        }
+        async fn page(&self, _limit: i64, _offset: i64) -> Result<Vec<Conf>> {
+            self.read_all().await
+        }
+    }
+
+    fn conf(key: &str, value: &str) -> Conf {
+        Conf {
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+
+    fn repo(ttl: Duration) -> CachingRepository<CountingRepo, Conf, String> {
+        CachingRepository::new(CountingRepo::default(), |c| c.key.clone(), ttl, 100)
+    }
+
+    fn reads(repo: &CachingRepository<CountingRepo, Conf, String>) -> usize {
+        repo.inner().reads.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn test_hit_miss_and_invalidation() {
+        let repo = repo(Duration::from_secs(60));
+        repo.create(&conf("site", "a")).await.unwrap();
+
+        // 首次未命中读内层，之后命中 / First read misses through, then hits
+        assert_eq!(
+            repo.read_one("site".into()).await.unwrap(),
+            Some(conf("site", "a"))
+        );
+        assert_eq!(
+            repo.read_one("site".into()).await.unwrap(),
+            Some(conf("site", "a"))
+        );
+        assert_eq!(reads(&repo), 1);
+
+        // 不存在的记录不缓存 / Missing rows are not cached
+        assert_eq!(repo.read_one("none".into()).await.unwrap(), None);
+        assert_eq!(repo.read_one("none".into()).await.unwrap(), None);
+        assert_eq!(reads(&repo), 3);
+
+        // 更新后失效并读到新值 / Update invalidates and the next read sees the new value
+        repo.update(&conf("site", "b")).await.unwrap();
+        assert_eq!(
+            repo.read_one("site".into()).await.unwrap(),
+            Some(conf("site", "b"))
+        );
+        assert_eq!(reads(&repo), 4);
+
+        repo.update_many(&[conf("site", "c")]).await.unwrap();
+        assert_eq!(
+            repo.read_one("site".into()).await.unwrap(),
+            Some(conf("site", "c"))
+        );
+        assert_eq!(reads(&repo), 5);
+
+        repo.delete("site".into()).await.unwrap();
+        assert_eq!(repo.read_one("site".into()).await.unwrap(), None);
+        assert_eq!(reads(&repo), 6);
+    }
+
+    #[tokio::test]
+    async fn test_entries_expire_after_ttl() {
+        let repo = repo(Duration::from_millis(50));
+        repo.create(&conf("site", "a")).await.unwrap();
+        repo.read_one("site".into()).await.unwrap();
+        // 绕过装饰器直接改内层，模拟其他节点的写入 / Write the inner repo directly, like another node would
+        repo.inner().update(&conf("site", "b")).await.unwrap();
+        assert_eq!(
+            repo.read_one("site".into()).await.unwrap(),
+            Some(conf("site", "a"))
+        );
+
+        tokio::time::sleep(Duration::from_millis(120)).await;
+        assert_eq!(
+            repo.read_one("site".into()).await.unwrap(),
+            Some(conf("site", "b"))
+        );
+        assert_eq!(reads(&repo), 2);
+    }
+}
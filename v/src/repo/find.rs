@@ -0,0 +1,287 @@
+//! 按列查询与键集分页 / Column lookups and keyset pagination
+//!
+//! 供 SQL 实现的 `Repository::find_by` / `find_in`：列名必须出现在 [`ModelSpec::columns`] 中，
+//! 否则返回配置错误（列名会拼入 SQL，白名单校验用于防注入）；值一律绑定，并按列类型转换。
+//! 结果行按 `ColType` 解码为 JSON 后反序列化为模型。
+//! Backing for `Repository::find_by` / `find_in` in SQL implementations: the column must be listed
+//! in [`ModelSpec::columns`] or a config error is returned (the name is spliced into SQL, so the
+//! allow-list guards against injection); values are always bound, converted by column type. Rows
+//! are decoded to JSON by `ColType` and then deserialized into the model.
+//!
+//! [`page_after`] 按主键排序并以 `pk > cursor` 定位，命中主键索引而非像 `OFFSET` 那样扫描并丢弃前面的行。
+//! [`page_after`] orders by primary key and seeks with `pk > cursor`, using the primary key index
+//! instead of scanning and discarding the leading rows as `OFFSET` does.
+
+use super::batch::{max_params, quote, BindValue};
+use crate::db::error::{DbError, Result};
+use crate::db::manager::DbPool;
+use crate::db::model::{ColType, ColumnDef, ModelSpec};
+use serde_json::{Map, Value};
+use sqlx::{QueryBuilder, Row};
+
+/// 绑定 `IN (...)` 列表并取回全部行 / Bind the `IN (...)` list and fetch all rows
+macro_rules! select_in {
+    ($pool:expr, $db:ty, $mode:ident, $prefix:expr, $binds:expr) => {{
+        let mut qb = QueryBuilder::<$db>::new($prefix);
+        let mut list = qb.separated(", ");
+        for value in $binds {
+            push_value!(list, value, $mode);
+        }
+        list.push_unseparated(")");
+        qb.build().fetch_all($pool).await?
+    }};
+}
+
+/// SQLite / MySQL：JSON 与数组列以 JSON 文本存储 / SQLite / MySQL: JSON and array columns are stored as JSON text
+#[cfg(any(feature = "sqlite", feature = "mysql"))]
+macro_rules! text_row {
+    ($row:expr, $cols:expr) => {{
+        let mut map = Map::new();
+        for c in $cols {
+            let value = match c.ty {
+                ColType::Text | ColType::Timestamp => $row
+                    .try_get::<Option<String>, _>(c.name)?
+                    .map(Value::String),
+                ColType::Int64 => $row.try_get::<Option<i64>, _>(c.name)?.map(Value::from),
+                ColType::Int16 => $row.try_get::<Option<i16>, _>(c.name)?.map(Value::from),
+                ColType::Bool => $row.try_get::<Option<bool>, _>(c.name)?.map(Value::from),
+                ColType::Json | ColType::ArrayText => $row
+                    .try_get::<Option<String>, _>(c.name)?
+                    .map(|s| serde_json::from_str::<Value>(&s))
+                    .transpose()?,
+            };
+            map.insert(c.name.to_string(), value.unwrap_or(Value::Null));
+        }
+        Ok::<_, DbError>(Value::Object(map))
+    }};
+}
+
+/// 按主键键集取一页 / Fetch one keyset page ordered by primary key
+macro_rules! select_page {
+    ($pool:expr, $db:ty, $mode:ident, $prefix:expr, $cursor:expr, $order:expr, $limit:expr) => {{
+        let mut qb = QueryBuilder::<$db>::new($prefix);
+        if let Some((cond, value)) = $cursor {
+            qb.push(cond);
+            push_value!(qb, value, $mode);
+        }
+        qb.push($order).push_bind($limit);
+        qb.build().fetch_all($pool).await?
+    }};
+}
+
+/// 校验列名并返回其定义 / Validate a column name and return its definition
+pub fn column<T: ModelSpec>(name: &str) -> Result<&'static ColumnDef> {
+    T::columns().iter().find(|c| c.name == name).ok_or_else(|| {
+        DbError::Config(format!(
+            "未知列 {} / unknown column {} for {}",
+            name,
+            name,
+            T::table_name()
+        ))
+    })
+}
+
+/// 查询 `column = value` 的全部记录 / All rows where `column = value`
+pub async fn find_by<T: serde::de::DeserializeOwned + ModelSpec>(
+    pool: &DbPool,
+    column: &str,
+    value: &Value,
+) -> Result<Vec<T>> {
+    find_in(pool, column, std::slice::from_ref(value)).await
+}
+
+/// 查询 `column IN (values)` 的全部记录，超出参数上限时分批 / All rows where `column IN (values)`, batched past the parameter limit
+pub async fn find_in<T: serde::de::DeserializeOwned + ModelSpec>(
+    pool: &DbPool,
+    column: &str,
+    values: &[Value],
+) -> Result<Vec<T>> {
+    let col = self::column::<T>(column)?;
+    let cols = T::columns();
+    let prefix = format!(
+        "SELECT {} FROM {} WHERE {} IN (",
+        cols.iter()
+            .map(|c| quote(pool, c.name))
+            .collect::<Vec<_>>()
+            .join(", "),
+        quote(pool, T::table_name()),
+        quote(pool, col.name)
+    );
+    let mut out = Vec::new();
+    for chunk in values.chunks(max_params(pool)) {
+        let binds = chunk.iter().map(|v| BindValue::from_field(col.ty, Some(v)));
+        let rows = match pool {
+            DbPool::Postgres(p) => {
+                let rows = select_in!(p, sqlx::Postgres, native, &prefix, binds);
+                rows.iter()
+                    .map(|r| pg_row(r, cols))
+                    .collect::<Result<Vec<_>>>()?
+            }
+            #[cfg(feature = "sqlite")]
+            DbPool::Sqlite(p) => {
+                let rows = select_in!(p, sqlx::Sqlite, text, &prefix, binds);
+                rows.iter()
+                    .map(|r| text_row!(r, cols))
+                    .collect::<Result<Vec<_>>>()?
+            }
+            #[cfg(feature = "mysql")]
+            DbPool::MySql(p) => {
+                let rows = select_in!(p, sqlx::MySql, text, &prefix, binds);
+                rows.iter()
+                    .map(|r| text_row!(r, cols))
+                    .collect::<Result<Vec<_>>>()?
+            }
+        };
+        for row in rows {
+            out.push(serde_json::from_value(row)?);
+        }
+    }
+    Ok(out)
+}
+
+/// 键集分页：返回主键大于 `cursor` 的前 `limit` 条及下一页游标 / Keyset page: the first `limit` rows past `cursor` and the next cursor
+///
+/// `cursor` 为 `None` 时从头开始；返回的游标为本页最后一行的主键，不足一页时为 `None`。
+/// Starts from the beginning when `cursor` is `None`; the returned cursor is the last row's primary
+/// key, or `None` once a page comes back short.
+pub async fn page_after<T, PK>(
+    pool: &DbPool,
+    pk: &str,
+    cursor: Option<&PK>,
+    limit: i64,
+) -> Result<(Vec<T>, Option<PK>)>
+where
+    T: serde::de::DeserializeOwned + ModelSpec,
+    PK: serde::Serialize + serde::de::DeserializeOwned,
+{
+    let pk_col = self::column::<T>(pk)?;
+    if limit <= 0 {
+        return Ok((Vec::new(), None));
+    }
+    let cols = T::columns();
+    let key = quote(pool, pk);
+    let prefix = format!(
+        "SELECT {} FROM {}",
+        cols.iter()
+            .map(|c| quote(pool, c.name))
+            .collect::<Vec<_>>()
+            .join(", "),
+        quote(pool, T::table_name())
+    );
+    let cursor = match cursor {
+        Some(c) => Some((
+            format!(" WHERE {} > ", key),
+            BindValue::from_field(pk_col.ty, Some(&serde_json::to_value(c)?)),
+        )),
+        None => None,
+    };
+    let order = format!(" ORDER BY {} LIMIT ", key);
+    let rows = match pool {
+        DbPool::Postgres(p) => {
+            let rows = select_page!(p, sqlx::Postgres, native, &prefix, cursor, &order, limit);
+            rows.iter()
+                .map(|r| pg_row(r, cols))
+                .collect::<Result<Vec<_>>>()?
+        }
+        #[cfg(feature = "sqlite")]
+        DbPool::Sqlite(p) => {
+            let rows = select_page!(p, sqlx::Sqlite, text, &prefix, cursor, &order, limit);
+            rows.iter()
+                .map(|r| text_row!(r, cols))
+                .collect::<Result<Vec<_>>>()?
+        }
+        #[cfg(feature = "mysql")]
+        DbPool::MySql(p) => {
+            let rows = select_page!(p, sqlx::MySql, text, &prefix, cursor, &order, limit);
+            rows.iter()
+                .map(|r| text_row!(r, cols))
+                .collect::<Result<Vec<_>>>()?
+        }
+    };
+    let next = match rows.last() {
+        Some(last) if rows.len() as i64 == limit => Some(serde_json::from_value(
+            last.get(pk).cloned().unwrap_or(Value::Null),
+        )?),
+        _ => None,
+    };
+    let models = rows
+        .into_iter()
+        .map(serde_json::from_value)
+        .collect::<std::result::Result<Vec<T>, _>>()?;
+    Ok((models, next))
+}
+
+/// PostgreSQL：原生 JSON/数组，时间戳格式与 `QueryPg` 一致 / PostgreSQL: native JSON/arrays, timestamps formatted as in `QueryPg`
+fn pg_row(row: &sqlx::postgres::PgRow, cols: &[ColumnDef]) -> Result<Value> {
+    let mut map = Map::new();
+    for c in cols {
+        let name = c.name;
+        let value = match c.ty {
+            ColType::Text => row.try_get::<Option<String>, _>(name)?.map(Value::String),
+            ColType::Int64 => row
+                .try_get::<Option<i64>, _>(name)
+                .or_else(|_| {
+                    row.try_get::<Option<i32>, _>(name)
+                        .map(|v| v.map(i64::from))
+                })?
+                .map(Value::from),
+            ColType::Int16 => row.try_get::<Option<i16>, _>(name)?.map(Value::from),
+            ColType::Bool => row.try_get::<Option<bool>, _>(name)?.map(Value::from),
+            ColType::Timestamp => row
+                .try_get::<Option<chrono::DateTime<chrono::Utc>>, _>(name)
+                .map(|v| v.map(|dt| dt.naive_utc()))
+                .or_else(|_| row.try_get::<Option<chrono::NaiveDateTime>, _>(name))
+                .map(|v| v.map(|dt| Value::String(dt.format("%Y-%m-%dT%H:%M:%S%.f").to_string())))
+                .or_else(|_| {
+                    row.try_get::<Option<String>, _>(name)
+                        .map(|v| v.map(Value::String))
+                })?,
+            ColType::Json => row
+                .try_get::<Option<sqlx::types::Json<Value>>, _>(name)?
+                .map(|j| j.0),
+            ColType::ArrayText => row
+                .try_get::<Option<Vec<String>>, _>(name)?
+                .map(Value::from),
+        };
+        map.insert(name.to_string(), value.unwrap_or(Value::Null));
+    }
+    Ok(Value::Object(map))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::model::DbModel;
+
+    struct Spec;
+
+    impl DbModel for Spec {
+        fn table_name() -> &'static str {
+            "specs"
+        }
+        fn table_group() -> &'static str {
+            "default"
+        }
+    }
+
+    impl ModelSpec for Spec {
+        fn columns() -> &'static [ColumnDef] {
+            &[ColumnDef {
+                name: "id",
+                ty: ColType::Int64,
+            }]
+        }
+    }
+
+    #[test]
+    fn test_unknown_column_is_rejected() {
+        assert_eq!(column::<Spec>("id").unwrap().name, "id");
+        for bad in ["name", "id; DROP TABLE specs", "\"id\"", ""] {
+            assert!(
+                matches!(column::<Spec>(bad), Err(DbError::Config(_))),
+                "{}",
+                bad
+            );
+        }
+    }
+}
@@ -2,6 +2,13 @@ use crate::db::error::DbError;
 type Result<T> = std::result::Result<T, DbError>;
 use async_trait::async_trait;
 
+#[macro_use]
+pub mod batch;
+#[cfg(feature = "cache")]
+pub mod cache;
+pub mod find;
+pub mod versioned;
+
 /// 通用仓库 Trait，约定标准 CRUD 操作。
 /// 该 Trait 不依赖具体数据库类型，具体实现可使用 MySQL/Postgres/SQLite 的连接池。
 #[async_trait]
@@ -9,18 +16,492 @@ pub trait Repository<T, PK> {
     /// 创建记录，返回影响行数或主键值（实现决定返回语义）。
     async fn create(&self, model: &T) -> Result<u64>;
 
+    /// 批量创建，返回影响行数之和。
+    /// 默认逐条调用 `create`；SQL 实现应改用 [`batch::insert_many`]（事务内分块的多行 INSERT）。
+    async fn create_many(&self, models: &[T]) -> Result<u64>
+    where
+        T: Sync,
+        Self: Sync,
+    {
+        let mut affected = 0;
+        for model in models {
+            affected += self.create(model).await?;
+        }
+        Ok(affected)
+    }
+
     /// 读取一条记录（按主键）。
     async fn read_one(&self, pk: PK) -> Result<Option<T>>;
 
     /// 读取所有记录。
     async fn read_all(&self) -> Result<Vec<T>>;
 
+    /// 按非主键列等值查询。
+    /// 默认读取全部记录后在内存中过滤；SQL 实现应改用 [`find::find_by`]。
+    /// 列名须在 `ModelSpec::columns` 中，未知列返回 `DbError::Config`。
+    async fn find_by(&self, column: &str, value: serde_json::Value) -> Result<Vec<T>>
+    where
+        T: serde::Serialize + crate::db::model::ModelSpec + Send,
+        Self: Sync,
+    {
+        self.find_in(column, &[value]).await
+    }
+
+    /// 按列查询 `column IN (values)`。
+    /// 默认读取全部记录后在内存中过滤；SQL 实现应改用 [`find::find_in`]。
+    async fn find_in(&self, column: &str, values: &[serde_json::Value]) -> Result<Vec<T>>
+    where
+        T: serde::Serialize + crate::db::model::ModelSpec + Send,
+        Self: Sync,
+    {
+        find::column::<T>(column)?;
+        let mut matched = Vec::new();
+        for model in self.read_all().await? {
+            let field = serde_json::to_value(&model)?
+                .get(column)
+                .cloned()
+                .unwrap_or(serde_json::Value::Null);
+            if values.contains(&field) {
+                matched.push(model);
+            }
+        }
+        Ok(matched)
+    }
+
     /// 更新记录（通常按主键）。
+    /// 带 `version` 列的模型可委托给 [`versioned::update_versioned`] 实现乐观锁，
+    /// 版本已变化时返回 [`DbError::Conflict`]。
     async fn update(&self, model: &T) -> Result<u64>;
 
+    /// 批量更新，返回影响行数之和。
+    /// 默认逐条调用 `update`；SQL 实现应改用 [`batch::update_many`]（同一事务内按主键更新）。
+    async fn update_many(&self, models: &[T]) -> Result<u64>
+    where
+        T: Sync,
+        Self: Sync,
+    {
+        let mut affected = 0;
+        for model in models {
+            affected += self.update(model).await?;
+        }
+        Ok(affected)
+    }
+
     /// 删除记录（按主键）。
     async fn delete(&self, pk: PK) -> Result<u64>;
 
     /// 分页读取。
+    /// 基于 OFFSET：数据库需扫描并丢弃前 `offset` 行，深分页在大表上会越来越慢，
+    /// 且翻页期间的插入/删除会导致跳行或重复；大列表请使用 `page_after`。
     async fn page(&self, limit: i64, offset: i64) -> Result<Vec<T>>;
+
+    /// 键集分页：按主键升序返回主键大于 `cursor` 的最多 `limit` 条，以及下一页游标
+    /// （本页最后一行的主键；不足一页时为 `None`）。`cursor` 为 `None` 时从第一页开始。
+    /// SQL 实现可委托给 [`find::page_after`]，每页只需一次主键索引定位。
+    /// 仓库无从得知模型的主键，因此默认实现返回 `DbError::Config`，需要键集分页的实现应覆盖本方法。
+    async fn page_after(&self, _cursor: Option<PK>, _limit: i64) -> Result<(Vec<T>, Option<PK>)>
+    where
+        PK: Send + 'async_trait,
+    {
+        Err(DbError::Config(
+            "该仓库未实现 page_after / page_after is not implemented for this repository"
+                .to_string(),
+        ))
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::db::manager::DbPool;
+    use crate::db::model::{ColType, ColumnDef, DbModel, ModelSpec};
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    struct Item {
+        id: i64,
+        name: String,
+        active: bool,
+    }
+
+    impl DbModel for Item {
+        fn table_name() -> &'static str {
+            "items"
+        }
+        fn table_group() -> &'static str {
+            "default"
+        }
+    }
+
+    impl ModelSpec for Item {
+        fn columns() -> &'static [ColumnDef] {
+            &[
+                ColumnDef {
+                    name: "id",
+                    ty: ColType::Int64,
+                },
+                ColumnDef {
+                    name: "name",
+                    ty: ColType::Text,
+                },
+                ColumnDef {
+                    name: "active",
+                    ty: ColType::Bool,
+                },
+            ]
+        }
+    }
+
+    struct ItemRepo {
+        pool: DbPool,
+    }
+
+    impl ItemRepo {
+        fn sqlite(&self) -> &sqlx::SqlitePool {
+            match &self.pool {
+                DbPool::Sqlite(p) => p,
+                #[allow(unreachable_patterns)]
+                _ => unreachable!(),
+            }
+        }
+
+        fn row((id, name, active): (i64, String, bool)) -> Item {
+            Item { id, name, active }
+        }
+    }
+
+    #[async_trait]
+    impl Repository<Item, i64> for ItemRepo {
+        async fn create(&self, model: &Item) -> Result<u64> {
+            batch::insert_many(&self.pool, std::slice::from_ref(model)).await
+        }
+
+        async fn create_many(&self, models: &[Item]) -> Result<u64> {
+            batch::insert_many(&self.pool, models).await
+        }
+
+        async fn read_one(&self, pk: i64) -> Result<Option<Item>> {
+            let row = sqlx::query_as("SELECT id, name, active FROM items WHERE id = ?")
+                .bind(pk)
+                .fetch_optional(self.sqlite())
+                .await?;
+            Ok(row.map(Self::row))
+        }
+
+        async fn read_all(&self) -> Result<Vec<Item>> {
+            self.page(-1, 0).await
+        }
+
+        async fn find_by(&self, column: &str, value: serde_json::Value) -> Result<Vec<Item>> {
+            find::find_by(&self.pool, column, &value).await
+        }
+
+        async fn find_in(&self, column: &str, values: &[serde_json::Value]) -> Result<Vec<Item>> {
+            find::find_in(&self.pool, column, values).await
+        }
+
+        async fn update(&self, model: &Item) -> Result<u64> {
+            batch::update_many(&self.pool, "id", std::slice::from_ref(model)).await
+        }
+
+        async fn update_many(&self, models: &[Item]) -> Result<u64> {
+            batch::update_many(&self.pool, "id", models).await
+        }
+
+        async fn delete(&self, pk: i64) -> Result<u64> {
+            let res = sqlx::query("DELETE FROM items WHERE id = ?")
+                .bind(pk)
+                .execute(self.sqlite())
+                .await?;
+            Ok(res.rows_affected())
+        }
+
+        async fn page_after(
+            &self,
+            cursor: Option<i64>,
+            limit: i64,
+        ) -> Result<(Vec<Item>, Option<i64>)> {
+            find::page_after(&self.pool, "id", cursor.as_ref(), limit).await
+        }
+
+        async fn page(&self, limit: i64, offset: i64) -> Result<Vec<Item>> {
+            let rows =
+                sqlx::query_as("SELECT id, name, active FROM items ORDER BY id LIMIT ? OFFSET ?")
+                    .bind(limit)
+                    .bind(offset)
+                    .fetch_all(self.sqlite())
+                    .await?;
+            Ok(rows.into_iter().map(Self::row).collect())
+        }
+    }
+
+    async fn repo() -> ItemRepo {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let pool = DbPool::Sqlite(pool);
+        pool.execute("CREATE TABLE items (id INTEGER PRIMARY KEY, name TEXT NOT NULL, active BOOLEAN NOT NULL)")
+            .await
+            .unwrap();
+        ItemRepo { pool }
+    }
+
+    #[tokio::test]
+    async fn test_create_many_and_update_many_in_one_call() {
+        let repo = repo().await;
+        let items: Vec<Item> = (1..=1000)
+            .map(|id| Item {
+                id,
+                name: format!("item-{}", id),
+                active: false,
+            })
+            .collect();
+        assert_eq!(repo.create_many(&items).await.unwrap(), 1000);
+        assert_eq!(repo.read_all().await.unwrap().len(), 1000);
+
+        let renamed: Vec<Item> = items
+            .iter()
+            .filter(|i| i.id % 2 == 0)
+            .map(|i| Item {
+                id: i.id,
+                name: format!("even-{}", i.id),
+                active: true,
+            })
+            .collect();
+        assert_eq!(repo.update_many(&renamed).await.unwrap(), 500);
+        let item = repo.read_one(42).await.unwrap().unwrap();
+        assert_eq!((item.name.as_str(), item.active), ("even-42", true));
+        assert!(!repo.read_one(43).await.unwrap().unwrap().active);
+
+        // 任一行失败时整批回滚 / A failing row rolls the whole batch back
+        let clash = [
+            Item {
+                id: 2001,
+                name: "new".into(),
+                active: false,
+            },
+            Item {
+                id: 1,
+                name: "dup".into(),
+                active: false,
+            },
+        ];
+        assert!(repo.create_many(&clash).await.is_err());
+        assert!(repo.read_one(2001).await.unwrap().is_none());
+        assert_eq!(repo.create_many(&[]).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_find_by_and_find_in() {
+        let repo = repo().await;
+        let items: Vec<Item> = (1..=20)
+            .map(|id| Item {
+                id,
+                name: format!("item-{}", id % 5),
+                active: id % 2 == 0,
+            })
+            .collect();
+        repo.create_many(&items).await.unwrap();
+
+        let mut ids: Vec<i64> = repo
+            .find_by("name", serde_json::json!("item-3"))
+            .await
+            .unwrap()
+            .iter()
+            .map(|i| i.id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec![3, 8, 13, 18]);
+        assert_eq!(
+            repo.find_by("active", serde_json::json!(true))
+                .await
+                .unwrap()
+                .len(),
+            10
+        );
+
+        let found = repo
+            .find_in("id", &[1.into(), 7.into(), 99.into()])
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 2);
+        assert!(repo.find_in("id", &[]).await.unwrap().is_empty());
+
+        // 未知列（含注入尝试）被拒绝 / Unknown columns, injection attempts included, are rejected
+        for bad in ["missing", "id = id OR 1 = 1 --"] {
+            let err = repo.find_by(bad, 1.into()).await.unwrap_err();
+            assert!(matches!(err, DbError::Config(_)));
+        }
+    }
+
+    /// 仅实现基本方法的内存仓库，验证默认的 find_by / find_in
+    /// In-memory repository with only the basic methods, exercising the default find_by / find_in
+    struct MemRepo(Vec<(i64, &'static str)>);
+
+    #[async_trait]
+    impl Repository<Item, i64> for MemRepo {
+        async fn create(&self, _model: &Item) -> Result<u64> {
+            Ok(0)
+        }
+        async fn read_one(&self, _pk: i64) -> Result<Option<Item>> {
+            Ok(None)
+        }
+        async fn read_all(&self) -> Result<Vec<Item>> {
+            Ok(self
+                .0
+                .iter()
+                .map(|(id, name)| Item {
+                    id: *id,
+                    name: name.to_string(),
+                    active: true,
+                })
+                .collect())
+        }
+        async fn update(&self, _model: &Item) -> Result<u64> {
+            Ok(0)
+        }
+        async fn delete(&self, _pk: i64) -> Result<u64> {
+            Ok(0)
+        }
+        async fn page(&self, _limit: i64, _offset: i64) -> Result<Vec<Item>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_default_find_filters_in_memory() {
+        let repo = MemRepo(vec![(1, "a"), (2, "b"), (3, "a")]);
+        assert_eq!(repo.find_by("name", "a".into()).await.unwrap().len(), 2);
+        assert_eq!(
+            repo.find_in("id", &[2.into(), 3.into()])
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(repo.find_by("nope", "a".into()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_default_page_after_reports_unsupported() {
+        let repo = MemRepo(vec![(1, "a")]);
+        let err = repo.page_after(None, 10).await.unwrap_err();
+        assert!(matches!(err, DbError::Config(_)));
+    }
+
+    #[tokio::test]
+    async fn test_page_after_walks_every_row_once() {
+        let repo = repo().await;
+        let items: Vec<Item> = (1..=1000)
+            .map(|id| Item {
+                id: id * 3,
+                name: format!("item-{}", id),
+                active: true,
+            })
+            .collect();
+        repo.create_many(&items).await.unwrap();
+
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let (page, next) = repo.page_after(cursor, 64).await.unwrap();
+            assert!(page.len() <= 64);
+            seen.extend(page.iter().map(|i| i.id));
+            match next {
+                Some(c) => cursor = Some(c),
+                None => break,
+            }
+        }
+        assert_eq!(seen, items.iter().map(|i| i.id).collect::<Vec<_>>());
+
+        let (page, next) = repo.page_after(Some(2997), 10).await.unwrap();
+        assert_eq!((page.len(), next), (1, None));
+        let (page, next) = repo.page_after(Some(3), 2).await.unwrap();
+        assert_eq!(
+            (page.iter().map(|i| i.id).collect::<Vec<_>>(), next),
+            (vec![6, 9], Some(9))
+        );
+    }
+
+    /// 对比深分页的查询计划：OFFSET 全表扫描，键集走主键定位
+    /// Compare deep-page query plans: OFFSET scans the table, keyset seeks the primary key
+    #[tokio::test]
+    async fn test_keyset_seeks_where_offset_scans() {
+        let repo = repo().await;
+        let pool = repo.sqlite();
+        let plan = |sql: &'static str| async move {
+            let rows: Vec<(i64, i64, i64, String)> =
+                sqlx::query_as(&format!("EXPLAIN QUERY PLAN {}", sql))
+                    .fetch_all(pool)
+                    .await
+                    .unwrap();
+            rows.into_iter().map(|r| r.3).collect::<Vec<_>>().join("; ")
+        };
+        let offset =
+            plan("SELECT id, name, active FROM items ORDER BY id LIMIT 50 OFFSET 900000").await;
+        let keyset =
+            plan("SELECT id, name, active FROM items WHERE id > 900000 ORDER BY id LIMIT 50").await;
+        assert!(offset.starts_with("SCAN"), "{}", offset);
+        assert!(keyset.starts_with("SEARCH"), "{}", keyset);
+        assert!(
+            keyset.contains("rowid>?") || keyset.contains("id>?"),
+            "{}",
+            keyset
+        );
+    }
+
+    /// 单线程运行时上并发读仍让出执行器：SQL 在驱动侧执行，读取挂起期间其他任务（计时器）照常推进；
+    /// 若数据库调用阻塞工作线程，计时器在一次读取的开始与结束之间不会前进。
+    /// Concurrent reads on a single-threaded runtime still yield the executor: SQL runs on the
+    /// driver side and other tasks (a ticker) keep progressing while a read is pending; a blocking
+    /// call would leave the ticker where it was between the start and the end of a read.
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_concurrent_reads_do_not_stall_the_executor() {
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let repo = Arc::new(repo().await);
+        let items: Vec<Item> = (1..=5_000)
+            .map(|id| Item {
+                id,
+                name: format!("item-{}", id),
+                active: false,
+            })
+            .collect();
+        repo.create_many(&items).await.unwrap();
+
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+        let ticker = tokio::spawn({
+            let (ticks, stop) = (ticks.clone(), stop.clone());
+            async move {
+                while !stop.load(Ordering::Relaxed) {
+                    tokio::task::yield_now().await;
+                    ticks.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        });
+
+        let mut reads = tokio::task::JoinSet::new();
+        for i in 0..8 {
+            let (repo, ticks) = (repo.clone(), ticks.clone());
+            // name 无索引，每次都全表扫描 / name is unindexed, so every read scans the table
+            reads.spawn(async move {
+                let before = ticks.load(Ordering::Relaxed);
+                let found = repo
+                    .find_by("name", format!("item-{}", 1 + i * 500).into())
+                    .await;
+                (found, ticks.load(Ordering::Relaxed) - before)
+            });
+        }
+        while let Some(read) = reads.join_next().await {
+            let (found, ticked) = read.unwrap();
+            assert_eq!(found.unwrap().len(), 1);
+            assert!(ticked > 0, "the ticker never ran while a read was pending");
+        }
+        stop.store(true, Ordering::Relaxed);
+        ticker.await.unwrap();
+    }
 }
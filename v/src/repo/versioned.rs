@@ -0,0 +1,202 @@
+//! 乐观锁 / Optimistic locking
+//!
+//! 约定：模型包含 `version: i64` 列（[`VERSION_COLUMN`]，`ColType::Int64`），插入时通常为 0。
+//! [`update_versioned`] 生成 `UPDATE ... SET ..., version = version + 1 WHERE pk = ? AND version = ?`，
+//! 其中 `version` 取自传入模型（即读取时的旧版本）；影响 0 行说明记录已被其他写入推进（或已删除），
+//! 返回 [`DbError::Conflict`]，调用方应重新读取后再决定是否重试。
+//! Convention: the model carries a `version: i64` column ([`VERSION_COLUMN`], `ColType::Int64`),
+//! usually 0 on insert. [`update_versioned`] issues
+//! `UPDATE ... SET ..., version = version + 1 WHERE pk = ? AND version = ?` with the version taken
+//! from the given model (the one it was read with); zero affected rows means another write moved
+//! the row on (or deleted it) and yields [`DbError::Conflict`], so the caller should re-read before
+//! deciding whether to retry.
+
+use super::batch::{object, quote, BindValue};
+use crate::db::error::{DbError, Result};
+use crate::db::manager::DbPool;
+use crate::db::model::ModelSpec;
+use sqlx::QueryBuilder;
+
+/// 版本列名 / Version column name
+pub const VERSION_COLUMN: &str = "version";
+
+/// 构建并执行一条带版本条件的更新 / Build and run one version-guarded update
+macro_rules! update_guarded {
+    ($pool:expr, $db:ty, $mode:ident, $prefix:expr, $set:expr, $values:expr, $key:expr, $version:expr) => {{
+        let mut qb = QueryBuilder::<$db>::new($prefix);
+        for (name, value) in $set.iter().zip($values) {
+            qb.push(name).push(" = ");
+            push_value!(qb, value, $mode);
+            qb.push(", ");
+        }
+        qb.push(format!("{0} = {0} + 1 WHERE ", $version.0));
+        qb.push($key.0).push(" = ");
+        push_value!(qb, $key.1, $mode);
+        qb.push(format!(" AND {} = ", $version.0));
+        qb.push_bind($version.1);
+        qb.build().execute($pool).await?.rows_affected()
+    }};
+}
+
+/// 按主键与旧版本更新并递增版本 / Update by primary key and old version, bumping the version
+///
+/// 成功时数据库中的版本为 `model.version + 1`；调用方若继续持有该模型需自行同步。
+/// On success the stored version is `model.version + 1`; callers keeping the model must sync it.
+pub async fn update_versioned<T: serde::Serialize + ModelSpec>(
+    pool: &DbPool,
+    pk: &str,
+    model: &T,
+) -> Result<u64> {
+    let cols = T::columns();
+    let find = |name: &str| {
+        cols.iter().find(|c| c.name == name).ok_or_else(|| {
+            DbError::Config(format!(
+                "列 {} 不在 ModelSpec 中 / column {} not in ModelSpec",
+                name, name
+            ))
+        })
+    };
+    let key_col = find(pk)?;
+    find(VERSION_COLUMN)?;
+    let obj = object(model)?;
+    let version = obj
+        .get(VERSION_COLUMN)
+        .and_then(|v| v.as_i64())
+        .ok_or_else(|| DbError::Config("version 必须为整数 / version must be an integer".into()))?;
+    let set_cols: Vec<_> = cols
+        .iter()
+        .filter(|c| c.name != pk && c.name != VERSION_COLUMN)
+        .collect();
+    let set: Vec<String> = set_cols.iter().map(|c| quote(pool, c.name)).collect();
+    let values = set_cols
+        .iter()
+        .map(|c| BindValue::from_field(c.ty, obj.get(c.name)));
+    let key = (
+        quote(pool, pk),
+        BindValue::from_field(key_col.ty, obj.get(pk)),
+    );
+    let version = (quote(pool, VERSION_COLUMN), version);
+    let prefix = format!("UPDATE {} SET ", quote(pool, T::table_name()));
+    let affected = match pool {
+        DbPool::Postgres(p) => {
+            update_guarded!(
+                p,
+                sqlx::Postgres,
+                native,
+                &prefix,
+                set,
+                values,
+                key,
+                version
+            )
+        }
+        #[cfg(feature = "sqlite")]
+        DbPool::Sqlite(p) => {
+            update_guarded!(p, sqlx::Sqlite, text, &prefix, set, values, key, version)
+        }
+        #[cfg(feature = "mysql")]
+        DbPool::MySql(p) => {
+            update_guarded!(p, sqlx::MySql, text, &prefix, set, values, key, version)
+        }
+    };
+    if affected == 0 {
+        return Err(DbError::Conflict(format!(
+            "{} 的版本已不是 {} / {} no longer at version {}",
+            T::table_name(),
+            version.1,
+            T::table_name(),
+            version.1
+        )));
+    }
+    Ok(affected)
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::db::model::{ColType, ColumnDef, DbModel};
+    use crate::repo::batch;
+    use sqlx::sqlite::SqlitePoolOptions;
+
+    #[derive(serde::Serialize, Clone)]
+    struct Doc {
+        id: i64,
+        body: String,
+        version: i64,
+    }
+
+    impl DbModel for Doc {
+        fn table_name() -> &'static str {
+            "docs"
+        }
+        fn table_group() -> &'static str {
+            "default"
+        }
+    }
+
+    impl ModelSpec for Doc {
+        fn columns() -> &'static [ColumnDef] {
+            &[
+                ColumnDef {
+                    name: "id",
+                    ty: ColType::Int64,
+                },
+                ColumnDef {
+                    name: "body",
+                    ty: ColType::Text,
+                },
+                ColumnDef {
+                    name: "version",
+                    ty: ColType::Int64,
+                },
+            ]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_conflicting_update_is_rejected() {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        let pool = DbPool::Sqlite(pool);
+        pool.execute(
+            "CREATE TABLE docs (id INTEGER PRIMARY KEY, body TEXT NOT NULL, version INTEGER NOT NULL)",
+        )
+        .await
+        .unwrap();
+        let doc = Doc {
+            id: 1,
+            body: "v0".into(),
+            version: 0,
+        };
+        batch::insert_many(&pool, std::slice::from_ref(&doc))
+            .await
+            .unwrap();
+
+        // 两个写者读到同一版本 / Two writers read the same version
+        let mut first = doc.clone();
+        first.body = "first".into();
+        let mut second = doc.clone();
+        second.body = "second".into();
+
+        assert_eq!(update_versioned(&pool, "id", &first).await.unwrap(), 1);
+        let err = update_versioned(&pool, "id", &second).await.unwrap_err();
+        assert!(matches!(err, DbError::Conflict(_)));
+        assert!(!err.is_transient());
+
+        let DbPool::Sqlite(p) = &pool else {
+            unreachable!()
+        };
+        let row: (String, i64) = sqlx::query_as("SELECT body, version FROM docs WHERE id = 1")
+            .fetch_one(p)
+            .await
+            .unwrap();
+        assert_eq!(row, ("first".to_string(), 1));
+
+        // 重新读取后的版本可以继续更新 / Updating from the re-read version succeeds
+        second.version = row.1;
+        assert_eq!(update_versioned(&pool, "id", &second).await.unwrap(), 1);
+    }
+}